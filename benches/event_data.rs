@@ -0,0 +1,158 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use malware_analysis_sandbox::compact_event::{CompactEvent, SysmonEventRef};
+use malware_analysis_sandbox::sysmon_event::SysmonEvent;
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const EVENTS: usize = 10_000;
+
+fn process_create(i: usize) -> String {
+    format!(
+        "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System>\
+         <Provider Name='Microsoft-Windows-Sysmon'/><EventID>1</EventID>\
+         <TimeCreated SystemTime='2023-08-01T12:00:00.{:06}Z'/><EventRecordID>{}</EventRecordID>\
+         <Channel>Microsoft-Windows-Sysmon/Operational</Channel><Computer>DESKTOP-1</Computer>\
+         </System><EventData>\
+         <Data Name='RuleName'>-</Data>\
+         <Data Name='UtcTime'>2023-08-01 12:00:00.000</Data>\
+         <Data Name='ProcessGuid'>{{5770385f-c22a-43e0-bf4c-06f5698ffbd9}}</Data>\
+         <Data Name='ProcessId'>{}</Data>\
+         <Data Name='Image'>C:\\Windows\\System32\\cmd.exe</Data>\
+         <Data Name='CommandLine'>cmd.exe /c echo {} &gt; out.txt</Data>\
+         <Data Name='CurrentDirectory'>C:\\Users\\user\\</Data>\
+         <Data Name='User'>DESKTOP-1\\user</Data>\
+         <Data Name='IntegrityLevel'>Medium</Data>\
+         <Data Name='Hashes'>SHA256=0000000000000000000000000000000000000000000000000000000000000000</Data>\
+         <Data Name='ParentProcessGuid'>{{5770385f-c22a-43e0-bf4c-06f5698ffbd8}}</Data>\
+         <Data Name='ParentProcessId'>4</Data>\
+         <Data Name='ParentImage'>C:\\Windows\\explorer.exe</Data>\
+         <Data Name='ParentCommandLine'>C:\\Windows\\explorer.exe</Data>\
+         </EventData></Event>\n",
+        i % 1_000_000,
+        i,
+        1000 + i,
+        i
+    )
+}
+
+fn log() -> String {
+    (0..EVENTS).map(process_create).collect()
+}
+
+fn retained<T>(build: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = build();
+    let after = ALLOCATED.load(Ordering::Relaxed);
+    (value, after.saturating_sub(before))
+}
+
+fn report_memory(xml: &str) {
+    let (owned, owned_bytes) = retained(|| {
+        SysmonEvent::from_xml_many(xml)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap()
+    });
+    let (compact, compact_bytes) = retained(|| {
+        owned
+            .iter()
+            .map(CompactEvent::from_event)
+            .collect::<Vec<_>>()
+    });
+    let (borrowed, borrowed_bytes) = retained(|| {
+        SysmonEventRef::parse_many(xml)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap()
+    });
+    println!(
+        "retained heap for {} events: HashMap {} KiB, compact {} KiB, borrowed {} KiB",
+        EVENTS,
+        owned_bytes / 1024,
+        compact_bytes / 1024,
+        borrowed_bytes / 1024
+    );
+    drop((owned, compact, borrowed));
+}
+
+fn parse(c: &mut Criterion) {
+    let xml = log();
+    report_memory(&xml);
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(EVENTS as u64));
+    group.sample_size(20);
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            SysmonEvent::from_xml_many(black_box(&xml))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap()
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            SysmonEventRef::parse_many(black_box(&xml))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap()
+        })
+    });
+    group.bench_function("borrowed_to_compact", |b| {
+        b.iter(|| {
+            SysmonEventRef::parse_many(black_box(&xml))
+                .map(|e| e.map(|e| CompactEvent::from_ref(&e)))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap()
+        })
+    });
+    group.finish();
+
+    let owned: Vec<SysmonEvent> = SysmonEvent::from_xml_many(&xml)
+        .collect::<anyhow::Result<_>>()
+        .unwrap();
+    let compact: Vec<CompactEvent> = owned.iter().map(CompactEvent::from_event).collect();
+    let mut group = c.benchmark_group("lookup");
+    group.throughput(Throughput::Elements(EVENTS as u64));
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            owned
+                .iter()
+                .filter(|e| {
+                    e.event_data
+                        .get("Image")
+                        .is_some_and(|i| i.ends_with("cmd.exe"))
+                })
+                .count()
+        })
+    });
+    group.bench_function("compact", |b| {
+        b.iter(|| {
+            compact
+                .iter()
+                .filter(|e| e.get("Image").is_some_and(|i| i.ends_with("cmd.exe")))
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,108 @@
+use std::alloc::{alloc, dealloc, Layout};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const ABI_VERSION: u32 = 1;
+const NAME: &str = "temp-execution";
+const PROCESS_CREATE: u64 = 1;
+const TEMP_DIRS: &[&str] = &[
+    "\\appdata\\local\\temp\\",
+    "\\windows\\temp\\",
+    "/tmp/",
+    "/dev/shm/",
+];
+
+#[derive(Deserialize)]
+struct Context {
+    events: Vec<Value>,
+}
+
+#[derive(Serialize)]
+struct Finding {
+    name: String,
+    level: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+    events: Vec<Value>,
+}
+
+#[derive(Serialize)]
+struct Output {
+    findings: Vec<Finding>,
+}
+
+fn pack(ptr: *const u8, len: usize) -> u64 {
+    ((ptr as u64) << 32) | len as u64
+}
+
+fn layout(len: u32) -> Layout {
+    Layout::from_size_align(len.max(1) as usize, 1).expect("valid layout")
+}
+
+#[no_mangle]
+pub extern "C" fn sandbox_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn sandbox_name() -> u64 {
+    pack(NAME.as_ptr(), NAME.len())
+}
+
+#[no_mangle]
+pub extern "C" fn sandbox_alloc(len: u32) -> *mut u8 {
+    unsafe { alloc(layout(len)) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sandbox_dealloc(ptr: *mut u8, len: u32) {
+    dealloc(ptr, layout(len))
+}
+
+fn analyze(context: Context) -> Output {
+    let events: Vec<Value> = context
+        .events
+        .into_iter()
+        .filter(|e| e["event_id"].as_u64() == Some(PROCESS_CREATE))
+        .filter(|e| {
+            let image = e["event_data"]["Image"]
+                .as_str()
+                .unwrap_or_default()
+                .to_lowercase();
+            TEMP_DIRS.iter().any(|dir| image.contains(dir))
+        })
+        .collect();
+    let mut findings = Vec::new();
+    if !events.is_empty() {
+        findings.push(Finding {
+            name: "Process executed from a temporary directory".to_string(),
+            level: Some("medium".to_string()),
+            description: Some(format!(
+                "{} processes started from temp paths",
+                events.len()
+            )),
+            tags: vec![
+                "attack.execution".to_string(),
+                "attack.t1204.002".to_string(),
+            ],
+            events,
+        });
+    }
+    Output { findings }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sandbox_analyze(ptr: *const u8, len: u32) -> u64 {
+    let input = std::slice::from_raw_parts(ptr, len as usize);
+    let Ok(context) = serde_json::from_slice::<Context>(input) else {
+        return 0;
+    };
+    let Ok(output) = serde_json::to_vec(&analyze(context)) else {
+        return 0;
+    };
+    let len = output.len();
+    let buf = sandbox_alloc(len as u32);
+    std::ptr::copy_nonoverlapping(output.as_ptr(), buf, len);
+    pack(buf, len)
+}
@@ -0,0 +1,1066 @@
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hardening;
+pub mod protocol;
+pub mod time_warp;
+pub mod user_sim;
+
+use std::collections::{HashMap, HashSet};
+use std::future::pending;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use log::{info, warn};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout_at, Duration, Instant};
+
+use crate::analyzer::api_trace::DEFAULT_TRACED_APIS;
+use crate::analyzer::clipboard::ClipboardCapture;
+use crate::artifacts::is_file_event;
+use crate::auditd::AuditdReader;
+use crate::event_reader::SysmonEventReader;
+use crate::hashes::Hashes;
+use crate::netsim::SimulatedRequest;
+use crate::orchestrator::limits::LimitViolation;
+use crate::sync_objects::{parse_handle_output, ObservedMutex};
+use crate::syslog::SyslogReader;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+use crate::tamper::FileTamper;
+use crate::telemetry::{ApiCall, TelemetryEvent, TelemetryReader};
+use crate::vmi::VmiReader;
+use hardening::HardeningProfile;
+use protocol::{
+    read_blob, read_header, write_blob, write_header, DroppedFile, DumpScope, DumpTrigger,
+    ExecutionReport, ExecutionRequest, LogFormat, MemoryDump,
+};
+use time_warp::{real_time, ClockWarp};
+use user_sim::{InputDriver, UserSimScript};
+
+const MAX_PAYLOAD_LEN: u64 = 1024 * 1024 * 1024;
+const DUMP_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const PID_PLACEHOLDER: &str = "{pid}";
+const FACTOR_PLACEHOLDER: &str = "{factor}";
+const URL_PLACEHOLDER: &str = "{url}";
+const PROFILE_PLACEHOLDER: &str = "{profile}";
+const APIS_PLACEHOLDER: &str = "{apis}";
+const BROWSER_PROFILE_DIR: &str = "browser-profile";
+const MAX_SCREENSHOTS: usize = 120;
+const MAX_CLIPBOARD_CAPTURES: usize = 500;
+const MAX_CLIPBOARD_LEN: usize = 4096;
+const MAX_API_CALLS: usize = 100_000;
+const PAUSED_WAKE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct AgentConfig {
+    pub work_dir: PathBuf,
+    pub log_format: LogFormat,
+    pub log_path: Option<PathBuf>,
+    pub log_command: Vec<String>,
+    pub screenshot_command: Vec<String>,
+    pub archive_dir: Option<PathBuf>,
+    pub process_dump_command: Vec<String>,
+    pub full_dump_command: Vec<String>,
+    pub record_command: Vec<String>,
+    pub user_sim: UserSimScript,
+    pub input_driver: InputDriver,
+    pub hardening: Option<HardeningProfile>,
+    pub sleep_hook_command: Vec<String>,
+    pub handle_command: Vec<String>,
+    pub clipboard_command: Vec<String>,
+    pub browser_command: Vec<String>,
+    pub api_trace_command: Vec<String>,
+    pub traced_apis: Vec<String>,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            work_dir: std::env::temp_dir().join("sandbox-agent"),
+            log_format: LogFormat::Syslog,
+            log_path: Some(PathBuf::from("/var/log/syslog")),
+            log_command: Vec::new(),
+            screenshot_command: Vec::new(),
+            archive_dir: None,
+            process_dump_command: Vec::new(),
+            full_dump_command: Vec::new(),
+            record_command: Vec::new(),
+            user_sim: UserSimScript::default(),
+            input_driver: InputDriver::default(),
+            hardening: None,
+            sleep_hook_command: Vec::new(),
+            handle_command: Vec::new(),
+            clipboard_command: Vec::new(),
+            browser_command: [
+                "firefox",
+                "--no-remote",
+                "--profile",
+                PROFILE_PLACEHOLDER,
+                URL_PLACEHOLDER,
+            ]
+            .map(str::to_string)
+            .to_vec(),
+            api_trace_command: Vec::new(),
+            traced_apis: DEFAULT_TRACED_APIS.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+}
+
+pub fn parse_log(log: &[u8], format: LogFormat) -> Vec<SysmonEvent> {
+    let events: Box<dyn Iterator<Item = Result<SysmonEvent>> + '_> = match format {
+        LogFormat::Syslog => Box::new(SyslogReader::new(BufReader::new(log))),
+        LogFormat::Xml => Box::new(SysmonEventReader::new(BufReader::new(log))),
+        LogFormat::Auditd => Box::new(AuditdReader::new(BufReader::new(log))),
+        LogFormat::Vmi => Box::new(VmiReader::new(BufReader::new(log))),
+    };
+    events.filter_map(Result::ok).collect()
+}
+
+async fn run(command: &[String]) -> Result<Vec<u8>> {
+    let (program, args) = command.split_first().context("Empty command")?;
+    let output = Command::new(program).args(args).output().await?;
+    Ok(output.stdout)
+}
+
+fn trigger_process(event: &SysmonEvent) -> Option<(DumpTrigger, u32)> {
+    let (trigger, field) = match event.event_id {
+        SysmonEventId::CREATE_REMOTE_THREAD => (DumpTrigger::CreateRemoteThread, "TargetProcessId"),
+        SysmonEventId::PROCESS_TAMPERING => (DumpTrigger::ProcessTampering, "ProcessId"),
+        _ => return None,
+    };
+    Some((trigger, event.event_data.get(field)?.parse().ok()?))
+}
+
+fn descendants(events: &[SysmonEvent], root: u32) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for event in events {
+        if event.event_id != SysmonEventId::PROCESS_CREATE {
+            continue;
+        }
+        let pid = event
+            .event_data
+            .get("ProcessId")
+            .and_then(|p| p.parse().ok());
+        let parent = event
+            .event_data
+            .get("ParentProcessId")
+            .and_then(|p| p.parse().ok());
+        if let (Some(pid), Some(parent)) = (pid, parent) {
+            children.entry(parent).or_default().push(pid);
+        }
+    }
+    let mut pids = vec![root];
+    let mut i = 0;
+    while i < pids.len() {
+        for child in children.get(&pids[i]).into_iter().flatten() {
+            if !pids.contains(child) {
+                pids.push(*child);
+            }
+        }
+        i += 1;
+    }
+    pids
+}
+
+struct Recording {
+    child: Child,
+    reader: JoinHandle<Vec<u8>>,
+}
+
+impl Recording {
+    async fn stop(mut self) -> Option<Vec<u8>> {
+        if let Err(e) = self.child.kill().await {
+            warn!("Failed to stop desktop recording: {}", e);
+        }
+        match self.reader.await {
+            Ok(video) if !video.is_empty() => Some(video),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Failed to collect desktop recording: {}", e);
+                None
+            }
+        }
+    }
+}
+
+struct ApiTracer {
+    child: Child,
+    reader: JoinHandle<Vec<u8>>,
+}
+
+impl ApiTracer {
+    async fn stop(mut self, traced: &[String]) -> Vec<ApiCall> {
+        if let Err(e) = self.child.kill().await {
+            warn!("Failed to stop API tracer: {}", e);
+        }
+        let output = match self.reader.await {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Failed to collect API trace: {}", e);
+                return Vec::new();
+            }
+        };
+        String::from_utf8_lossy(&output)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str::<ApiCall>(line) {
+                Ok(call) => Some(call),
+                Err(e) => {
+                    warn!("Invalid API trace record: {}", e);
+                    None
+                }
+            })
+            .filter(|call| traced.is_empty() || traced.iter().any(|api| call.is(api)))
+            .take(MAX_API_CALLS)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Pause,
+    Resume,
+    Extend(Duration),
+    Kill,
+}
+
+struct Execution {
+    report: ExecutionReport,
+    log: Vec<u8>,
+    dropped: Vec<Vec<u8>>,
+    screenshots: Vec<(DateTime<Utc>, Vec<u8>)>,
+    dumps: Vec<(MemoryDump, Vec<u8>)>,
+    video: Option<Vec<u8>>,
+    modified: Vec<Vec<u8>>,
+}
+
+async fn next_control(control: &mut Option<mpsc::UnboundedReceiver<Control>>) -> Control {
+    if let Some(receiver) = control {
+        if let Some(action) = receiver.recv().await {
+            return action;
+        }
+        *control = None;
+    }
+    pending().await
+}
+
+async fn signal(pid: u32, name: &str) -> bool {
+    if !cfg!(unix) {
+        warn!("Pausing samples is only supported on unix guests");
+        return false;
+    }
+    let command = vec!["kill".to_string(), format!("-{}", name), pid.to_string()];
+    match run(&command).await {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Failed to send SIG{} to {}: {}", name, pid, e);
+            false
+        }
+    }
+}
+
+struct Runner {
+    config: AgentConfig,
+}
+
+pub struct Agent {
+    listener: TcpListener,
+    runner: Runner,
+}
+
+impl Agent {
+    pub async fn bind<A: ToSocketAddrs>(addr: A, config: AgentConfig) -> Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self {
+            listener,
+            runner: Runner { config },
+        })
+    }
+
+    pub async fn serve(&self) -> Result<()> {
+        loop {
+            let (mut stream, peer) = self.listener.accept().await?;
+            info!("Accepted job from {}", peer);
+            if let Err(e) = self.handle(&mut stream).await {
+                warn!("Job from {} failed: {}", peer, e);
+            }
+        }
+    }
+
+    async fn handle(&self, stream: &mut TcpStream) -> Result<()> {
+        let request: ExecutionRequest = read_header(stream).await?;
+        let sample = read_blob(stream, MAX_PAYLOAD_LEN).await?;
+        let execution = self.runner.execute(&request, &sample, None).await?;
+
+        write_header(stream, &execution.report).await?;
+        write_blob(stream, &execution.log).await?;
+        for content in &execution.dropped {
+            write_blob(stream, content).await?;
+        }
+        for (_, png) in &execution.screenshots {
+            write_blob(stream, png).await?;
+        }
+        for (_, content) in &execution.dumps {
+            write_blob(stream, content).await?;
+        }
+        if let Some(video) = &execution.video {
+            write_blob(stream, video).await?;
+        }
+        for content in &execution.modified {
+            write_blob(stream, content).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Runner {
+    async fn execute(
+        &self,
+        request: &ExecutionRequest,
+        sample: &[u8],
+        mut control: Option<mpsc::UnboundedReceiver<Control>>,
+    ) -> Result<Execution> {
+        tokio::fs::create_dir_all(&self.config.work_dir).await?;
+        let file_name = std::path::Path::new(&request.file_name)
+            .file_name()
+            .context("Invalid sample file name")?;
+        let sample_path = self.config.work_dir.join(file_name);
+        if !request.collect_only && request.url.is_none() {
+            tokio::fs::write(&sample_path, sample).await?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                tokio::fs::set_permissions(&sample_path, std::fs::Permissions::from_mode(0o755))
+                    .await?;
+            }
+        }
+
+        let mut report = ExecutionReport {
+            exit_code: None,
+            timed_out: false,
+            error: None,
+            log_format: self.config.log_format,
+            dropped_files: Vec::new(),
+            screenshots: 0,
+            screenshot_times: Vec::new(),
+            memory_dumps: Vec::new(),
+            video: false,
+            clock_adjustments: Vec::new(),
+            killed: false,
+            mutexes: Vec::new(),
+            clipboard: Vec::new(),
+            api_calls: Vec::new(),
+            modified_files: Vec::new(),
+        };
+
+        let target = match &request.url {
+            Some(url) => self.browser_command(url).await?,
+            None => vec![sample_path.to_string_lossy().into_owned()],
+        };
+        let mut command = match &request.user {
+            Some(user) if cfg!(unix) => {
+                let mut command = Command::new("runuser");
+                command.args(["-u", user, "--"]).args(&target);
+                command
+            }
+            _ => {
+                let mut command = Command::new(&target[0]);
+                command.args(&target[1..]);
+                command
+            }
+        };
+        command
+            .args(&request.arguments)
+            .current_dir(&self.config.work_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+
+        let watch = request
+            .dump_triggers
+            .iter()
+            .any(|t| *t != DumpTrigger::EndOfRun);
+        let observe_handles = !self.config.handle_command.is_empty();
+        let capture_clipboard = !self.config.clipboard_command.is_empty();
+        let screenshot_interval = request
+            .screenshot_interval_secs
+            .filter(|_| request.screenshot)
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+        let mut dumps = Vec::new();
+        let mut dumped = HashSet::new();
+        let mut screenshots = Vec::new();
+        let recording = if request.record_video {
+            self.start_recording()
+        } else {
+            None
+        };
+
+        if let Some(profile) = request
+            .hardening
+            .as_ref()
+            .or(self.config.hardening.as_ref())
+            .filter(|_| !request.collect_only)
+        {
+            profile.apply().await;
+        }
+
+        let script = request
+            .user_sim_script
+            .clone()
+            .unwrap_or_else(|| self.config.user_sim.clone());
+        if request.simulate_user {
+            script.prepare().await;
+        }
+
+        let clock_warp = request.time_warp.as_ref().and_then(ClockWarp::start);
+
+        if request.collect_only {
+            info!(
+                "Collecting for {}s without executing...",
+                request.timeout_secs
+            );
+            sleep(Duration::from_secs(request.timeout_secs)).await;
+            if request.screenshot {
+                self.take_screenshot(&mut screenshots).await;
+            }
+        } else {
+            info!("Executing {}...", target.join(" "));
+            match command.spawn() {
+                Ok(mut child) => {
+                    let root = child.id();
+                    if let (Some(warp), Some(pid)) = (&request.time_warp, root) {
+                        if warp.skip_sleeps {
+                            self.hook_sleeps(pid, warp.factor).await;
+                        }
+                    }
+                    let tracer = root.and_then(|pid| self.start_api_trace(pid));
+                    let driver = self.config.input_driver;
+                    let user_sim = request
+                        .simulate_user
+                        .then(|| tokio::spawn(async move { script.run(driver).await }));
+                    let started = Instant::now();
+                    let mut deadline = started + Duration::from_secs(request.timeout_secs);
+                    if capture_clipboard {
+                        self.capture_clipboard(&mut report.clipboard).await;
+                    }
+                    let mut next_poll = (watch || observe_handles || capture_clipboard)
+                        .then(|| started + DUMP_POLL_INTERVAL);
+                    let mut next_screenshot =
+                        screenshot_interval.map(|interval| started + interval);
+                    let mut status = None;
+                    let mut paused_at: Option<Instant> = None;
+                    while status.is_none() && (paused_at.is_some() || Instant::now() < deadline) {
+                        let limit = match paused_at {
+                            Some(_) => Instant::now() + PAUSED_WAKE_INTERVAL,
+                            None => deadline,
+                        };
+                        let wake = [next_poll, next_screenshot]
+                            .into_iter()
+                            .flatten()
+                            .fold(limit, Instant::min);
+                        let exit = tokio::select! {
+                            exit = timeout_at(wake, child.wait()) => exit,
+                            action = next_control(&mut control) => {
+                                match (action, root) {
+                                    (Control::Pause, Some(pid))
+                                        if paused_at.is_none() && signal(pid, "STOP").await =>
+                                    {
+                                        info!("Paused {}", pid);
+                                        paused_at = Some(Instant::now());
+                                    }
+                                    (Control::Resume, Some(pid))
+                                        if paused_at.is_some() && signal(pid, "CONT").await =>
+                                    {
+                                        info!("Resumed {}", pid);
+                                        if let Some(at) = paused_at.take() {
+                                            deadline += at.elapsed();
+                                        }
+                                    }
+                                    (Control::Extend(extra), _) => {
+                                        info!("Extending the run by {}s", extra.as_secs());
+                                        deadline += extra;
+                                    }
+                                    (Control::Kill, _) => {
+                                        info!("Run killed by the host");
+                                        report.killed = true;
+                                        if let (Some(pid), Some(_)) = (root, paused_at.take()) {
+                                            signal(pid, "CONT").await;
+                                        }
+                                        break;
+                                    }
+                                    _ => (),
+                                }
+                                continue;
+                            }
+                        };
+                        match exit {
+                            Ok(exit) => status = Some(exit?),
+                            Err(_) => {
+                                let now = Instant::now();
+                                if let (Some(next), Some(interval)) =
+                                    (next_screenshot, screenshot_interval)
+                                {
+                                    if now >= next {
+                                        self.take_screenshot(&mut screenshots).await;
+                                        next_screenshot = Some(now + interval);
+                                    }
+                                }
+                                if next_poll.is_some_and(|next| now >= next) {
+                                    if watch {
+                                        self.dump_triggered(request, &mut dumped, &mut dumps).await;
+                                    }
+                                    if let (true, Some(root)) = (observe_handles, root) {
+                                        self.observe_mutexes(root, &mut report.mutexes).await;
+                                    }
+                                    if capture_clipboard {
+                                        self.capture_clipboard(&mut report.clipboard).await;
+                                    }
+                                    next_poll = Some(Instant::now() + DUMP_POLL_INTERVAL);
+                                }
+                            }
+                        }
+                    }
+                    if let Some(user_sim) = user_sim {
+                        user_sim.abort();
+                    }
+                    if let Some(tracer) = tracer {
+                        report.api_calls = tracer.stop(&self.config.traced_apis).await;
+                    }
+                    if let Some(clock_warp) = clock_warp {
+                        report.clock_adjustments = clock_warp.stop();
+                    }
+                    if request.screenshot {
+                        self.take_screenshot(&mut screenshots).await;
+                    }
+                    if let (true, Some(root)) = (observe_handles, root) {
+                        self.observe_mutexes(root, &mut report.mutexes).await;
+                    }
+                    if capture_clipboard {
+                        self.capture_clipboard(&mut report.clipboard).await;
+                    }
+                    if request.dump_triggers.contains(&DumpTrigger::EndOfRun) {
+                        if let Some(root) = root {
+                            self.dump_end_of_run(request, root, &mut dumps).await;
+                        }
+                    }
+                    match status {
+                        Some(status) => report.exit_code = status.code(),
+                        None if report.killed => child.kill().await?,
+                        None => {
+                            warn!(
+                            "Sample was forcefully terminated because it didn't finish within {}s",
+                            request.timeout_secs
+                        );
+                            report.timed_out = true;
+                            child.kill().await?;
+                        }
+                    }
+                }
+                Err(e) => report.error = Some(e.to_string()),
+            }
+        }
+        if watch {
+            self.dump_triggered(request, &mut dumped, &mut dumps).await;
+        }
+        report.memory_dumps = dumps.iter().map(|(dump, _)| dump.clone()).collect();
+        report.screenshots = screenshots.len();
+        report.screenshot_times = screenshots.iter().map(|(time, _)| *time).collect();
+
+        let video = match recording {
+            Some(recording) => recording.stop().await,
+            None => None,
+        };
+        report.video = video.is_some();
+
+        let log = self.read_log().await?;
+
+        let mut dropped = Vec::new();
+        let mut seen = HashSet::new();
+        for event in parse_log(&log, self.config.log_format) {
+            if !is_file_event(&event) {
+                continue;
+            }
+            let Some(path) = event.event_data.get("TargetFilename") else {
+                continue;
+            };
+            if seen.contains(path) {
+                continue;
+            }
+            let content = match event.event_id {
+                SysmonEventId::FILE_DELETE | SysmonEventId::FILE_DELETE_DETECTED => {
+                    self.read_archived(&event).await
+                }
+                _ => tokio::fs::read(path).await.ok(),
+            };
+            match content {
+                Some(content) => {
+                    seen.insert(path.clone());
+                    report.dropped_files.push(DroppedFile {
+                        path: path.clone(),
+                        size: content.len() as u64,
+                    });
+                    dropped.push(content);
+                }
+                None => warn!("Failed to read dropped file '{}'", path),
+            }
+        }
+
+        let mut modified = Vec::new();
+        for golden in &request.golden_files {
+            match tokio::fs::read(&golden.path).await {
+                Ok(content) if !golden.matches(&content) => {
+                    report.modified_files.push(DroppedFile {
+                        path: golden.path.clone(),
+                        size: content.len() as u64,
+                    });
+                    modified.push(content);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to read watched file '{}': {}", golden.path, e),
+            }
+        }
+
+        Ok(Execution {
+            report,
+            log,
+            dropped,
+            screenshots,
+            dumps,
+            video,
+            modified,
+        })
+    }
+
+    async fn hook_sleeps(&self, pid: u32, factor: f64) {
+        if self.config.sleep_hook_command.is_empty() {
+            warn!("Sleep skipping requested but no sleep hook command is configured");
+            return;
+        }
+        let command: Vec<String> = self
+            .config
+            .sleep_hook_command
+            .iter()
+            .map(|arg| {
+                arg.replace(PID_PLACEHOLDER, &pid.to_string())
+                    .replace(FACTOR_PLACEHOLDER, &factor.to_string())
+            })
+            .collect();
+        if let Err(e) = run(&command).await {
+            warn!("Failed to hook sleeps of {}: {}", pid, e);
+        }
+    }
+
+    fn start_api_trace(&self, pid: u32) -> Option<ApiTracer> {
+        if self.config.api_trace_command.is_empty() {
+            return None;
+        }
+        let apis = self.config.traced_apis.join(",");
+        let command: Vec<String> = self
+            .config
+            .api_trace_command
+            .iter()
+            .map(|arg| {
+                arg.replace(PID_PLACEHOLDER, &pid.to_string())
+                    .replace(APIS_PLACEHOLDER, &apis)
+            })
+            .collect();
+        let (program, args) = command.split_first()?;
+        let mut child = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("Failed to start API tracer for {}: {}", pid, e);
+                return None;
+            }
+        };
+        let mut stdout = child.stdout.take()?;
+        let reader = tokio::spawn(async move {
+            let mut output = Vec::new();
+            let _ = stdout.read_to_end(&mut output).await;
+            output
+        });
+        Some(ApiTracer { child, reader })
+    }
+
+    async fn browser_command(&self, url: &str) -> Result<Vec<String>> {
+        if self.config.browser_command.is_empty() {
+            bail!("No browser command configured for URL detonation");
+        }
+        let profile = self.config.work_dir.join(BROWSER_PROFILE_DIR);
+        if profile.exists() {
+            tokio::fs::remove_dir_all(&profile).await?;
+        }
+        tokio::fs::create_dir_all(&profile).await?;
+        let profile = profile.to_string_lossy();
+        Ok(self
+            .config
+            .browser_command
+            .iter()
+            .map(|arg| {
+                arg.replace(PROFILE_PLACEHOLDER, &profile)
+                    .replace(URL_PLACEHOLDER, url)
+            })
+            .collect())
+    }
+
+    async fn take_screenshot(&self, screenshots: &mut Vec<(DateTime<Utc>, Vec<u8>)>) {
+        if self.config.screenshot_command.is_empty() || screenshots.len() >= MAX_SCREENSHOTS {
+            return;
+        }
+        let time = Utc::now();
+        match run(&self.config.screenshot_command).await {
+            Ok(png) if !png.is_empty() => screenshots.push((time, png)),
+            Ok(_) => (),
+            Err(e) => warn!("Failed to take screenshot: {}", e),
+        }
+    }
+
+    fn start_recording(&self) -> Option<Recording> {
+        let (program, args) = self.config.record_command.split_first()?;
+        let mut child = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("Failed to start desktop recording: {}", e);
+                return None;
+            }
+        };
+        let mut stdout = child.stdout.take()?;
+        let reader = tokio::spawn(async move {
+            let mut video = Vec::new();
+            let _ = stdout.read_to_end(&mut video).await;
+            video
+        });
+        Some(Recording { child, reader })
+    }
+
+    async fn read_log(&self) -> Result<Vec<u8>> {
+        match &self.config.log_path {
+            Some(path) => Ok(tokio::fs::read(path).await?),
+            None => run(&self.config.log_command).await,
+        }
+    }
+
+    async fn dump(&self, scope: DumpScope, pid: u32) -> Result<Vec<u8>> {
+        let command: Vec<String> = match scope {
+            DumpScope::Process => self
+                .config
+                .process_dump_command
+                .iter()
+                .map(|arg| arg.replace(PID_PLACEHOLDER, &pid.to_string()))
+                .collect(),
+            DumpScope::Full => self.config.full_dump_command.clone(),
+        };
+        let dump = run(&command).await?;
+        if dump.is_empty() {
+            bail!("Dump command produced no output");
+        }
+        Ok(dump)
+    }
+
+    async fn push_dump(
+        &self,
+        scope: DumpScope,
+        trigger: DumpTrigger,
+        pid: u32,
+        dumps: &mut Vec<(MemoryDump, Vec<u8>)>,
+    ) {
+        info!("Dumping memory of {} ({:?})...", pid, trigger);
+        match self.dump(scope, pid).await {
+            Ok(content) => dumps.push((
+                MemoryDump {
+                    trigger,
+                    process_id: (scope == DumpScope::Process).then_some(pid),
+                    size: content.len() as u64,
+                },
+                content,
+            )),
+            Err(e) => warn!("Failed to dump memory of {}: {}", pid, e),
+        }
+    }
+
+    async fn dump_triggered(
+        &self,
+        request: &ExecutionRequest,
+        dumped: &mut HashSet<(DumpTrigger, u32)>,
+        dumps: &mut Vec<(MemoryDump, Vec<u8>)>,
+    ) {
+        let log = match self.read_log().await {
+            Ok(log) => log,
+            Err(e) => {
+                warn!("Failed to read log for memory dump triggers: {}", e);
+                return;
+            }
+        };
+        for event in parse_log(&log, self.config.log_format) {
+            let Some((trigger, pid)) = trigger_process(&event) else {
+                continue;
+            };
+            if !request.dump_triggers.contains(&trigger) {
+                continue;
+            }
+            let key = match request.dump_scope {
+                DumpScope::Process => (trigger, pid),
+                DumpScope::Full => (trigger, 0),
+            };
+            if dumped.insert(key) {
+                self.push_dump(request.dump_scope, trigger, pid, dumps)
+                    .await;
+            }
+        }
+    }
+
+    async fn dump_end_of_run(
+        &self,
+        request: &ExecutionRequest,
+        pid: u32,
+        dumps: &mut Vec<(MemoryDump, Vec<u8>)>,
+    ) {
+        if request.dump_scope == DumpScope::Full {
+            self.push_dump(DumpScope::Full, DumpTrigger::EndOfRun, pid, dumps)
+                .await;
+            return;
+        }
+        let events = match self.read_log().await {
+            Ok(log) => parse_log(&log, self.config.log_format),
+            Err(_) => Vec::new(),
+        };
+        for pid in descendants(&events, pid) {
+            self.push_dump(DumpScope::Process, DumpTrigger::EndOfRun, pid, dumps)
+                .await;
+        }
+    }
+
+    async fn observe_mutexes(&self, root: u32, mutexes: &mut Vec<ObservedMutex>) {
+        let events = match self.read_log().await {
+            Ok(log) => parse_log(&log, self.config.log_format),
+            Err(_) => Vec::new(),
+        };
+        for pid in descendants(&events, root) {
+            let command: Vec<String> = self
+                .config
+                .handle_command
+                .iter()
+                .map(|arg| arg.replace(PID_PLACEHOLDER, &pid.to_string()))
+                .collect();
+            let output = match run(&command).await {
+                Ok(output) => output,
+                Err(e) => {
+                    warn!("Failed to list handles of {}: {}", pid, e);
+                    continue;
+                }
+            };
+            for name in parse_handle_output(&String::from_utf8_lossy(&output)) {
+                if !mutexes
+                    .iter()
+                    .any(|m| m.process_id == pid && m.name == name)
+                {
+                    mutexes.push(ObservedMutex {
+                        time: Utc::now(),
+                        process_id: pid,
+                        name,
+                    });
+                }
+            }
+        }
+    }
+
+    async fn capture_clipboard(&self, captures: &mut Vec<ClipboardCapture>) {
+        if captures.len() >= MAX_CLIPBOARD_CAPTURES {
+            return;
+        }
+        let time = Utc::now();
+        let output = match run(&self.config.clipboard_command).await {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Failed to read the clipboard: {}", e);
+                return;
+            }
+        };
+        let content: String = String::from_utf8_lossy(&output)
+            .trim_end()
+            .chars()
+            .take(MAX_CLIPBOARD_LEN)
+            .collect();
+        if content.is_empty() || captures.last().is_some_and(|c| c.content == content) {
+            return;
+        }
+        captures.push(ClipboardCapture { time, content });
+    }
+
+    async fn read_archived(&self, event: &SysmonEvent) -> Option<Vec<u8>> {
+        let dir = self.config.archive_dir.as_ref()?;
+        let hashes = Hashes::parse(event.event_data.get("Hashes")?);
+        let values: Vec<String> = hashes.file_hashes().map(|(_, v)| v.to_string()).collect();
+        let mut entries = tokio::fs::read_dir(dir).await.ok()?;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if values.iter().any(|v| name.contains(v.as_str())) {
+                return tokio::fs::read(entry.path()).await.ok();
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct AgentResult {
+    pub report: ExecutionReport,
+    pub sysmon_log: Vec<u8>,
+    pub dropped_files: Vec<(DroppedFile, Vec<u8>)>,
+    pub screenshots: Vec<Vec<u8>>,
+    pub memory_dumps: Vec<(MemoryDump, Vec<u8>)>,
+    pub video: Option<Vec<u8>>,
+    pub pcap: Option<Vec<u8>>,
+    pub netsim: Vec<SimulatedRequest>,
+    pub terminated: Option<LimitViolation>,
+    pub modified_files: Vec<(DroppedFile, Vec<u8>)>,
+    pub tampered: Vec<FileTamper>,
+}
+
+impl AgentResult {
+    pub fn events(&self) -> Vec<SysmonEvent> {
+        let mut events = parse_log(&self.sysmon_log, self.report.log_format);
+        for event in &mut events {
+            event.time_created = self.real_time(event.time_created);
+        }
+        events
+    }
+
+    pub fn telemetry(&self) -> Vec<TelemetryEvent> {
+        let mut telemetry: Vec<TelemetryEvent> = match self.report.log_format {
+            LogFormat::Xml => TelemetryReader::new(BufReader::new(self.sysmon_log.as_slice()))
+                .filter_map(Result::ok)
+                .filter(|e| !matches!(e, TelemetryEvent::Sysmon(_)))
+                .collect(),
+            LogFormat::Syslog | LogFormat::Auditd | LogFormat::Vmi => Vec::new(),
+        };
+        telemetry.extend(
+            self.report
+                .api_calls
+                .iter()
+                .cloned()
+                .map(TelemetryEvent::ApiCall),
+        );
+        for event in &mut telemetry {
+            event.set_time(self.real_time(event.time()));
+        }
+        telemetry
+    }
+
+    pub fn mutexes(&self) -> Vec<ObservedMutex> {
+        self.report
+            .mutexes
+            .iter()
+            .map(|m| ObservedMutex {
+                time: self.real_time(m.time),
+                ..m.clone()
+            })
+            .collect()
+    }
+
+    pub fn clipboard(&self) -> Vec<ClipboardCapture> {
+        self.report
+            .clipboard
+            .iter()
+            .map(|c| ClipboardCapture {
+                time: self.real_time(c.time),
+                ..c.clone()
+            })
+            .collect()
+    }
+
+    pub fn screenshot_times(&self) -> Vec<DateTime<Utc>> {
+        self.report
+            .screenshot_times
+            .iter()
+            .map(|t| self.real_time(*t))
+            .collect()
+    }
+
+    fn real_time<Tz: TimeZone>(&self, time: DateTime<Tz>) -> DateTime<Tz> {
+        real_time(&self.report.clock_adjustments, time)
+    }
+}
+
+pub struct AgentClient {
+    stream: TcpStream,
+}
+
+impl AgentClient {
+    pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        Ok(Self {
+            stream: TcpStream::connect(addr).await?,
+        })
+    }
+
+    pub async fn submit(
+        mut self,
+        request: &ExecutionRequest,
+        sample: &[u8],
+    ) -> Result<AgentResult> {
+        write_header(&mut self.stream, request).await?;
+        write_blob(&mut self.stream, sample).await?;
+
+        let report: ExecutionReport = read_header(&mut self.stream).await?;
+        let sysmon_log = read_blob(&mut self.stream, MAX_PAYLOAD_LEN).await?;
+        let mut dropped_files = Vec::new();
+        for file in &report.dropped_files {
+            let content = read_blob(&mut self.stream, MAX_PAYLOAD_LEN).await?;
+            dropped_files.push((file.clone(), content));
+        }
+        let mut screenshots = Vec::new();
+        for _ in 0..report.screenshots {
+            screenshots.push(read_blob(&mut self.stream, MAX_PAYLOAD_LEN).await?);
+        }
+        let mut memory_dumps = Vec::new();
+        for dump in &report.memory_dumps {
+            let content = read_blob(&mut self.stream, MAX_PAYLOAD_LEN).await?;
+            memory_dumps.push((dump.clone(), content));
+        }
+        let video = if report.video {
+            Some(read_blob(&mut self.stream, MAX_PAYLOAD_LEN).await?)
+        } else {
+            None
+        };
+        let mut modified_files = Vec::new();
+        for file in &report.modified_files {
+            let content = read_blob(&mut self.stream, MAX_PAYLOAD_LEN).await?;
+            modified_files.push((file.clone(), content));
+        }
+
+        Ok(AgentResult {
+            report,
+            sysmon_log,
+            dropped_files,
+            screenshots,
+            memory_dumps,
+            video,
+            pcap: None,
+            netsim: Vec::new(),
+            terminated: None,
+            modified_files,
+            tampered: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, Duration};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{Channel, Endpoint, Server};
+use tonic::{Request, Response, Status, Streaming};
+use uuid::Uuid;
+
+use super::protocol::{ExecutionReport, ExecutionRequest};
+use super::{parse_log, AgentConfig, AgentResult, Control, Execution, Runner, MAX_PAYLOAD_LEN};
+use crate::sysmon_event::SysmonEvent;
+
+pub mod proto {
+    tonic::include_proto!("sandbox.v1");
+}
+
+use proto::agent_client::AgentClient as ProtoClient;
+use proto::agent_message::Payload;
+use proto::agent_server::{Agent as AgentService, AgentServer};
+use proto::submit_chunk::Part;
+use proto::{
+    AgentMessage, ArtifactChunk, ArtifactKind, ControlAction, ControlRequest, ControlResponse,
+    Event, EventsRequest, LogChunk, RunState, SubmitChunk, SubmitResponse,
+};
+
+const CHUNK_LEN: usize = 1024 * 1024;
+const EVENT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_RECONNECTS: usize = 10;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+struct RunLog {
+    messages: Vec<AgentMessage>,
+    state: RunState,
+    events: usize,
+}
+
+struct Run {
+    log: Mutex<RunLog>,
+    notify: watch::Sender<usize>,
+    control: mpsc::UnboundedSender<Control>,
+}
+
+fn finished(state: RunState) -> bool {
+    matches!(state, RunState::Finished | RunState::Failed)
+}
+
+impl Run {
+    fn new(control: mpsc::UnboundedSender<Control>) -> Self {
+        Self {
+            log: Mutex::new(RunLog {
+                messages: Vec::new(),
+                state: RunState::Running,
+                events: 0,
+            }),
+            notify: watch::channel(0).0,
+            control,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RunLog> {
+        self.log.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push_locked(&self, log: &mut RunLog, payload: Payload) {
+        let seq = log.messages.len() as u64;
+        log.messages.push(AgentMessage {
+            seq,
+            payload: Some(payload),
+        });
+        self.notify.send_replace(log.messages.len());
+    }
+
+    fn push(&self, payload: Payload) {
+        let mut log = self.lock();
+        self.push_locked(&mut log, payload);
+    }
+
+    fn push_chunks(&self, data: &[u8], payload: impl Fn(Vec<u8>) -> Payload) {
+        if data.is_empty() {
+            self.push(payload(Vec::new()));
+        }
+        for chunk in data.chunks(CHUNK_LEN) {
+            self.push(payload(chunk.to_vec()));
+        }
+    }
+
+    fn push_events(&self, events: &[SysmonEvent]) {
+        let mut log = self.lock();
+        for event in events.iter().skip(log.events) {
+            match serde_json::to_string(event) {
+                Ok(json) => self.push_locked(&mut log, Payload::Event(Event { json })),
+                Err(e) => warn!("Failed to encode event: {}", e),
+            }
+        }
+        log.events = log.events.max(events.len());
+    }
+
+    fn set_state(&self, state: RunState) {
+        let mut log = self.lock();
+        log.state = state;
+        self.push_locked(&mut log, Payload::State(state as i32));
+    }
+
+    fn state(&self) -> RunState {
+        self.lock().state
+    }
+
+    fn since(&self, seq: usize) -> (Vec<AgentMessage>, bool) {
+        let log = self.lock();
+        let messages = log.messages.get(seq..).unwrap_or_default().to_vec();
+        (messages, finished(log.state))
+    }
+}
+
+async fn poll_events(runner: Arc<Runner>, run: Arc<Run>) {
+    loop {
+        sleep(EVENT_POLL_INTERVAL).await;
+        match runner.read_log().await {
+            Ok(log) => run.push_events(&parse_log(&log, runner.config.log_format)),
+            Err(e) => warn!("Failed to read log for live events: {}", e),
+        }
+    }
+}
+
+fn publish(run: &Run, execution: &Execution) -> Result<()> {
+    run.push_events(&parse_log(&execution.log, execution.report.log_format));
+    run.push_chunks(&execution.log, |data| Payload::Log(LogChunk { data }));
+    let artifacts: [(ArtifactKind, Vec<&Vec<u8>>); 5] = [
+        (
+            ArtifactKind::DroppedFile,
+            execution.dropped.iter().collect(),
+        ),
+        (
+            ArtifactKind::Screenshot,
+            execution.screenshots.iter().map(|(_, png)| png).collect(),
+        ),
+        (
+            ArtifactKind::MemoryDump,
+            execution.dumps.iter().map(|(_, dump)| dump).collect(),
+        ),
+        (ArtifactKind::Video, execution.video.iter().collect()),
+        (
+            ArtifactKind::ModifiedFile,
+            execution.modified.iter().collect(),
+        ),
+    ];
+    for (kind, contents) in artifacts {
+        for (index, content) in contents.into_iter().enumerate() {
+            run.push_chunks(content, |data| {
+                Payload::Artifact(ArtifactChunk {
+                    kind: kind as i32,
+                    index: index as u32,
+                    data,
+                })
+            });
+        }
+    }
+    run.push(Payload::ReportJson(serde_json::to_string(
+        &execution.report,
+    )?));
+    Ok(())
+}
+
+async fn execute(
+    runner: Arc<Runner>,
+    run: Arc<Run>,
+    request: ExecutionRequest,
+    sample: Vec<u8>,
+    control: mpsc::UnboundedReceiver<Control>,
+) {
+    let poller = tokio::spawn(poll_events(runner.clone(), run.clone()));
+    let result = runner.execute(&request, &sample, Some(control)).await;
+    poller.abort();
+    match result.and_then(|execution| publish(&run, &execution)) {
+        Ok(()) => run.set_state(RunState::Finished),
+        Err(e) => {
+            warn!("Run failed: {}", e);
+            run.push(Payload::Error(e.to_string()));
+            run.set_state(RunState::Failed);
+        }
+    }
+}
+
+pub struct GrpcAgent {
+    runner: Arc<Runner>,
+    runs: Mutex<HashMap<String, Arc<Run>>>,
+}
+
+impl GrpcAgent {
+    pub fn new(config: AgentConfig) -> Self {
+        Self {
+            runner: Arc::new(Runner { config }),
+            runs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        Server::builder()
+            .add_service(AgentServer::new(self))
+            .serve(addr)
+            .await?;
+        Ok(())
+    }
+
+    fn runs(&self) -> MutexGuard<'_, HashMap<String, Arc<Run>>> {
+        self.runs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn run(&self, id: &str) -> Result<Arc<Run>, Status> {
+        self.runs()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("Unknown run {}", id)))
+    }
+}
+
+#[tonic::async_trait]
+impl AgentService for GrpcAgent {
+    async fn submit(
+        &self,
+        request: Request<Streaming<SubmitChunk>>,
+    ) -> Result<Response<SubmitResponse>, Status> {
+        let mut stream = request.into_inner();
+        let mut execution_request = None;
+        let mut sample = Vec::new();
+        while let Some(chunk) = stream.message().await? {
+            match chunk.part {
+                Some(Part::RequestJson(json)) => {
+                    execution_request = Some(
+                        serde_json::from_str::<ExecutionRequest>(&json)
+                            .map_err(|e| Status::invalid_argument(e.to_string()))?,
+                    )
+                }
+                Some(Part::Sample(data)) => {
+                    if (sample.len() + data.len()) as u64 > MAX_PAYLOAD_LEN {
+                        return Err(Status::resource_exhausted("Sample is too large"));
+                    }
+                    sample.extend(data);
+                }
+                None => (),
+            }
+        }
+        let execution_request =
+            execution_request.ok_or_else(|| Status::invalid_argument("No execution request"))?;
+
+        let id = Uuid::new_v4().to_string();
+        let (control, receiver) = mpsc::unbounded_channel();
+        let run = Arc::new(Run::new(control));
+        run.set_state(RunState::Running);
+        {
+            let mut runs = self.runs();
+            runs.retain(|_, r| !finished(r.state()));
+            runs.insert(id.clone(), run.clone());
+        }
+        info!("Accepted run {}", id);
+        tokio::spawn(execute(
+            self.runner.clone(),
+            run,
+            execution_request,
+            sample,
+            receiver,
+        ));
+        Ok(Response::new(SubmitResponse { run_id: id }))
+    }
+
+    type EventsStream = ReceiverStream<Result<AgentMessage, Status>>;
+
+    async fn events(
+        &self,
+        request: Request<EventsRequest>,
+    ) -> Result<Response<Self::EventsStream>, Status> {
+        let request = request.into_inner();
+        let run = self.run(&request.run_id)?;
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut changes = run.notify.subscribe();
+            let mut next = request.resume_from as usize;
+            loop {
+                let (messages, finished) = run.since(next);
+                next += messages.len();
+                for message in messages {
+                    if tx.send(Ok(message)).await.is_err() {
+                        return;
+                    }
+                }
+                if finished || changes.changed().await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn control(
+        &self,
+        request: Request<ControlRequest>,
+    ) -> Result<Response<ControlResponse>, Status> {
+        let request = request.into_inner();
+        let run = self.run(&request.run_id)?;
+        let action = match request.action() {
+            ControlAction::Pause => Control::Pause,
+            ControlAction::Resume => Control::Resume,
+            ControlAction::Extend => Control::Extend(Duration::from_secs(request.extend_secs)),
+            ControlAction::Kill => Control::Kill,
+            ControlAction::Unspecified => {
+                return Err(Status::invalid_argument("No control action"))
+            }
+        };
+        if finished(run.state()) || run.control.send(action).is_err() {
+            return Err(Status::failed_precondition("Run has already finished"));
+        }
+        match action {
+            Control::Pause => run.set_state(RunState::Paused),
+            Control::Resume => run.set_state(RunState::Running),
+            Control::Extend(_) | Control::Kill => (),
+        }
+        Ok(Response::new(ControlResponse {
+            state: run.state() as i32,
+        }))
+    }
+}
+
+fn take(artifacts: &mut HashMap<(i32, u32), Vec<u8>>, kind: ArtifactKind, index: usize) -> Vec<u8> {
+    artifacts
+        .remove(&(kind as i32, index as u32))
+        .unwrap_or_default()
+}
+
+fn assemble(
+    report: ExecutionReport,
+    sysmon_log: Vec<u8>,
+    mut artifacts: HashMap<(i32, u32), Vec<u8>>,
+) -> AgentResult {
+    let dropped_files = report
+        .dropped_files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            (
+                file.clone(),
+                take(&mut artifacts, ArtifactKind::DroppedFile, i),
+            )
+        })
+        .collect();
+    let screenshots = (0..report.screenshots)
+        .map(|i| take(&mut artifacts, ArtifactKind::Screenshot, i))
+        .collect();
+    let memory_dumps = report
+        .memory_dumps
+        .iter()
+        .enumerate()
+        .map(|(i, dump)| {
+            (
+                dump.clone(),
+                take(&mut artifacts, ArtifactKind::MemoryDump, i),
+            )
+        })
+        .collect();
+    let video = report
+        .video
+        .then(|| take(&mut artifacts, ArtifactKind::Video, 0));
+    let modified_files = report
+        .modified_files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            (
+                file.clone(),
+                take(&mut artifacts, ArtifactKind::ModifiedFile, i),
+            )
+        })
+        .collect();
+    AgentResult {
+        report,
+        sysmon_log,
+        dropped_files,
+        screenshots,
+        memory_dumps,
+        video,
+        pcap: None,
+        netsim: Vec::new(),
+        terminated: None,
+        modified_files,
+        tampered: Vec::new(),
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcAgentClient {
+    client: ProtoClient<Channel>,
+}
+
+impl GrpcAgentClient {
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let channel = Endpoint::from_shared(format!("http://{}", addr))?
+            .connect()
+            .await?;
+        Ok(Self {
+            client: ProtoClient::new(channel),
+        })
+    }
+
+    pub async fn start(&mut self, request: &ExecutionRequest, sample: &[u8]) -> Result<String> {
+        let mut chunks = vec![SubmitChunk {
+            part: Some(Part::RequestJson(serde_json::to_string(request)?)),
+        }];
+        chunks.extend(sample.chunks(CHUNK_LEN).map(|chunk| SubmitChunk {
+            part: Some(Part::Sample(chunk.to_vec())),
+        }));
+        let response = self.client.submit(tokio_stream::iter(chunks)).await?;
+        Ok(response.into_inner().run_id)
+    }
+
+    pub async fn control(&mut self, run_id: &str, action: Control) -> Result<RunState> {
+        let (action, extend_secs) = match action {
+            Control::Pause => (ControlAction::Pause, 0),
+            Control::Resume => (ControlAction::Resume, 0),
+            Control::Extend(extra) => (ControlAction::Extend, extra.as_secs()),
+            Control::Kill => (ControlAction::Kill, 0),
+        };
+        let response = self
+            .client
+            .control(ControlRequest {
+                run_id: run_id.to_string(),
+                action: action as i32,
+                extend_secs,
+            })
+            .await?;
+        Ok(response.into_inner().state())
+    }
+
+    pub async fn follow<F: FnMut(SysmonEvent)>(
+        &mut self,
+        run_id: &str,
+        mut on_event: F,
+    ) -> Result<AgentResult> {
+        let mut next = 0;
+        let mut reconnects = 0;
+        let mut log = Vec::new();
+        let mut artifacts: HashMap<(i32, u32), Vec<u8>> = HashMap::new();
+        loop {
+            let request = EventsRequest {
+                run_id: run_id.to_string(),
+                resume_from: next,
+            };
+            let error = match self.client.events(request).await {
+                Ok(response) => {
+                    let mut stream = response.into_inner();
+                    loop {
+                        let message = match stream.message().await {
+                            Ok(Some(message)) => message,
+                            Ok(None) => break Status::unavailable("Stream ended early"),
+                            Err(status) => break status,
+                        };
+                        next = message.seq + 1;
+                        reconnects = 0;
+                        match message.payload {
+                            Some(Payload::Event(event)) => {
+                                if let Ok(event) = serde_json::from_str(&event.json) {
+                                    on_event(event);
+                                }
+                            }
+                            Some(Payload::Log(chunk)) => log.extend(chunk.data),
+                            Some(Payload::Artifact(chunk)) => artifacts
+                                .entry((chunk.kind, chunk.index))
+                                .or_default()
+                                .extend(chunk.data),
+                            Some(Payload::ReportJson(json)) => {
+                                return Ok(assemble(serde_json::from_str(&json)?, log, artifacts))
+                            }
+                            Some(Payload::Error(error)) => bail!("Agent run failed: {}", error),
+                            Some(Payload::State(_)) | None => (),
+                        }
+                    }
+                }
+                Err(status) => status,
+            };
+            if error.code() == tonic::Code::NotFound {
+                bail!("Agent lost run {}: {}", run_id, error.message());
+            }
+            reconnects += 1;
+            if reconnects > MAX_RECONNECTS {
+                bail!("Lost connection to the agent: {}", error);
+            }
+            warn!(
+                "Agent stream interrupted ({}), resuming from {}...",
+                error, next
+            );
+            sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    pub async fn submit(
+        mut self,
+        request: &ExecutionRequest,
+        sample: &[u8],
+    ) -> Result<AgentResult> {
+        let run_id = self.start(request, sample).await?;
+        self.follow(&run_id, |_| ()).await
+    }
+}
@@ -0,0 +1,436 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{info, warn};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tokio::time::{sleep, Duration};
+
+pub const BUILTIN_PROFILES: &[&str] = &["minimal", "standard", "dirty"];
+
+const MAX_UPTIME_WAIT: Duration = Duration::from_secs(600);
+const BIOS_KEY: &str = r"HKLM\HARDWARE\DESCRIPTION\System\BIOS";
+const SYSTEM_KEY: &str = r"HKLM\HARDWARE\DESCRIPTION\System";
+const UNINSTALL_KEY: &str = r"HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+const VM_REGISTRY_KEYS: &[&str] = &[
+    r"HKLM\SOFTWARE\Oracle\VirtualBox Guest Additions",
+    r"HKLM\SOFTWARE\VMware, Inc.\VMware Tools",
+    r"HKLM\HARDWARE\ACPI\DSDT\VBOX__",
+    r"HKLM\HARDWARE\ACPI\FADT\VBOX__",
+    r"HKLM\HARDWARE\ACPI\RSDT\VBOX__",
+];
+
+const INSTALLED_SOFTWARE: &[(&str, &str, &str)] = &[
+    ("Google Chrome", "Google LLC", "118.0.5993.89"),
+    ("Mozilla Firefox (x64 en-US)", "Mozilla", "119.0"),
+    (
+        "Adobe Acrobat Reader DC",
+        "Adobe Systems Incorporated",
+        "23.006.20320",
+    ),
+    ("7-Zip 22.01 (x64)", "Igor Pavlov", "22.01"),
+    ("Notepad++ (64-bit x64)", "Notepad++ Team", "8.5.8"),
+    ("VLC media player", "VideoLAN", "3.0.18"),
+    ("Zoom", "Zoom Video Communications, Inc.", "5.16.2"),
+    ("Microsoft Teams", "Microsoft Corporation", "1.6.0.24078"),
+    ("Spotify", "Spotify AB", "1.2.22.982"),
+    ("WinRAR 6.23 (64-bit)", "win.rar GmbH", "6.23.0"),
+];
+
+const DOCUMENT_NAMES: &[&str] = &[
+    "Invoice_2023_04.pdf",
+    "Budget Q3.xlsx",
+    "Meeting notes.docx",
+    "Resume.docx",
+    "Travel itinerary.pdf",
+    "Project plan.docx",
+    "Expenses.xlsx",
+    "Contract draft.docx",
+    "IMG_2041.jpg",
+    "IMG_2057.jpg",
+    "Presentation.pptx",
+    "Tax return 2022.pdf",
+    "Shopping list.txt",
+    "Passwords old.txt",
+    "Family photo.jpg",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistryKind {
+    #[default]
+    Sz,
+    Dword,
+}
+
+impl RegistryKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sz => "REG_SZ",
+            Self::Dword => "REG_DWORD",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegistryValue {
+    pub key: String,
+    pub name: String,
+    #[serde(default)]
+    pub kind: RegistryKind,
+    pub value: String,
+}
+
+impl RegistryValue {
+    fn new(key: &str, name: &str, value: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            name: name.to_string(),
+            kind: RegistryKind::Sz,
+            value: value.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BiosStrings {
+    pub vendor: String,
+    pub version: String,
+    pub manufacturer: String,
+    pub product: String,
+}
+
+fn default_document_dirs() -> Vec<String> {
+    vec![
+        "Desktop".to_string(),
+        "Documents".to_string(),
+        "Downloads".to_string(),
+    ]
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocumentOptions {
+    pub count: usize,
+    pub max_age_days: u64,
+    #[serde(default = "default_document_dirs")]
+    pub dirs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HardeningProfile {
+    #[serde(default)]
+    pub registry: Vec<RegistryValue>,
+    #[serde(default)]
+    pub remove_registry_keys: Vec<String>,
+    #[serde(default)]
+    pub bios: Option<BiosStrings>,
+    #[serde(default)]
+    pub mac_prefix: Option<String>,
+    #[serde(default)]
+    pub documents: Option<DocumentOptions>,
+    #[serde(default)]
+    pub min_uptime_secs: Option<u64>,
+    #[serde(default)]
+    pub clock_skew_secs: Option<(i64, i64)>,
+}
+
+fn software(count: usize) -> Vec<RegistryValue> {
+    INSTALLED_SOFTWARE
+        .iter()
+        .take(count)
+        .flat_map(|(name, publisher, version)| {
+            let key = format!(r"{}\{}", UNINSTALL_KEY, name);
+            [
+                RegistryValue::new(&key, "DisplayName", name),
+                RegistryValue::new(&key, "Publisher", publisher),
+                RegistryValue::new(&key, "DisplayVersion", version),
+            ]
+        })
+        .collect()
+}
+
+impl HardeningProfile {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let bios = BiosStrings {
+            vendor: "Dell Inc.".to_string(),
+            version: "1.14.0".to_string(),
+            manufacturer: "Dell Inc.".to_string(),
+            product: "Latitude 5420".to_string(),
+        };
+        let remove_registry_keys = VM_REGISTRY_KEYS.iter().map(|k| k.to_string()).collect();
+        let profile = match name {
+            "minimal" => Self {
+                remove_registry_keys,
+                bios: Some(bios),
+                ..Self::default()
+            },
+            "standard" => Self {
+                registry: software(5),
+                remove_registry_keys,
+                bios: Some(bios),
+                mac_prefix: Some("F8:BC:12".to_string()),
+                documents: Some(DocumentOptions {
+                    count: 10,
+                    max_age_days: 90,
+                    dirs: default_document_dirs(),
+                }),
+                min_uptime_secs: None,
+                clock_skew_secs: Some((-120, 120)),
+            },
+            "dirty" => Self {
+                registry: software(INSTALLED_SOFTWARE.len()),
+                remove_registry_keys,
+                bios: Some(bios),
+                mac_prefix: Some("F8:BC:12".to_string()),
+                documents: Some(DocumentOptions {
+                    count: DOCUMENT_NAMES.len(),
+                    max_age_days: 400,
+                    dirs: default_document_dirs(),
+                }),
+                min_uptime_secs: Some(20 * 60),
+                clock_skew_secs: Some((-900, 900)),
+            },
+            _ => return None,
+        };
+        Some(profile)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn load(name_or_path: &str) -> Result<Self> {
+        match Self::builtin(name_or_path) {
+            Some(profile) => Ok(profile),
+            None => Self::from_file(name_or_path)
+                .with_context(|| format!("Failed to load hardening profile {}", name_or_path)),
+        }
+    }
+
+    pub async fn apply(&self) {
+        info!("Applying guest hardening...");
+        if let Err(e) = self.apply_registry().await {
+            warn!("Failed to apply registry hardening: {}", e);
+        }
+        if let Some(prefix) = &self.mac_prefix {
+            if let Err(e) = set_mac_prefix(prefix).await {
+                warn!("Failed to change MAC address: {}", e);
+            }
+        }
+        if let Some(documents) = &self.documents {
+            if let Err(e) = create_documents(documents) {
+                warn!("Failed to create recent documents: {}", e);
+            }
+        }
+        if let Some((min, max)) = self.clock_skew_secs {
+            let skew = rand::thread_rng().gen_range(min.min(max)..=max.max(min));
+            info!("Skewing guest clock by {}s", skew);
+            if let Err(e) = skew_clock(skew).await {
+                warn!("Failed to skew clock: {}", e);
+            }
+        }
+        if let Some(min_uptime) = self.min_uptime_secs {
+            wait_for_uptime(Duration::from_secs(min_uptime)).await;
+        }
+    }
+
+    async fn apply_registry(&self) -> Result<()> {
+        if !cfg!(windows) {
+            return Ok(());
+        }
+        for key in &self.remove_registry_keys {
+            let _ = run("reg", &["delete", key, "/f"]).await;
+        }
+        let mut values = self.registry.clone();
+        if let Some(bios) = &self.bios {
+            values.extend([
+                RegistryValue::new(BIOS_KEY, "BIOSVendor", &bios.vendor),
+                RegistryValue::new(BIOS_KEY, "BIOSVersion", &bios.version),
+                RegistryValue::new(BIOS_KEY, "SystemManufacturer", &bios.manufacturer),
+                RegistryValue::new(BIOS_KEY, "SystemProductName", &bios.product),
+                RegistryValue::new(
+                    SYSTEM_KEY,
+                    "SystemBiosVersion",
+                    &format!("{} - {}", bios.vendor, bios.version),
+                ),
+            ]);
+        }
+        for value in &values {
+            run(
+                "reg",
+                &[
+                    "add",
+                    &value.key,
+                    "/v",
+                    &value.name,
+                    "/t",
+                    value.kind.name(),
+                    "/d",
+                    &value.value,
+                    "/f",
+                ],
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program).args(args).output().await?;
+    if !output.status.success() {
+        bail!("{} exited with {}", program, output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn powershell(script: &str) -> Result<String> {
+    run(
+        "powershell",
+        &["-NoProfile", "-NonInteractive", "-Command", script],
+    )
+    .await
+}
+
+fn random_mac(prefix: &str) -> Result<String> {
+    let mut octets: Vec<u8> = prefix
+        .split([':', '-'])
+        .filter(|o| !o.is_empty())
+        .map(|o| u8::from_str_radix(o, 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow!("Invalid MAC prefix '{}'", prefix))?;
+    if octets.len() > 6 {
+        bail!("Invalid MAC prefix '{}'", prefix);
+    }
+    let mut rng = rand::thread_rng();
+    while octets.len() < 6 {
+        octets.push(rng.gen());
+    }
+    Ok(octets
+        .iter()
+        .map(|o| format!("{:02X}", o))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+async fn set_mac_prefix(prefix: &str) -> Result<()> {
+    let mac = random_mac(prefix)?;
+    info!("Changing MAC address to {}", mac);
+    if cfg!(windows) {
+        powershell(&format!(
+            "Get-NetAdapter -Physical | Set-NetAdapter -MacAddress '{}' -Confirm:$false",
+            mac.replace(':', "-")
+        ))
+        .await?;
+        return Ok(());
+    }
+    for entry in std::fs::read_dir("/sys/class/net")? {
+        let interface = entry?.file_name().to_string_lossy().to_string();
+        if interface == "lo" {
+            continue;
+        }
+        run("ip", &["link", "set", "dev", &interface, "down"]).await?;
+        let result = run("ip", &["link", "set", "dev", &interface, "address", &mac]).await;
+        run("ip", &["link", "set", "dev", &interface, "up"]).await?;
+        result?;
+    }
+    Ok(())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("USERPROFILE")
+        .or_else(|| std::env::var_os("HOME"))
+        .map(PathBuf::from)
+}
+
+fn document_content(name: &str, rng: &mut impl Rng) -> Vec<u8> {
+    let magic: &[u8] = match Path::new(name).extension().and_then(|e| e.to_str()) {
+        Some("pdf") => b"%PDF-1.7\n",
+        Some("docx" | "xlsx" | "pptx") => b"PK\x03\x04",
+        Some("jpg") => b"\xFF\xD8\xFF\xE0",
+        _ => b"",
+    };
+    let mut content = magic.to_vec();
+    let len = rng.gen_range(4 * 1024..64 * 1024);
+    content.extend((0..len).map(|_| rng.gen::<u8>()));
+    content
+}
+
+fn create_documents(options: &DocumentOptions) -> Result<()> {
+    let home = home_dir().context("No home directory")?;
+    let mut rng = rand::thread_rng();
+    let mut names = DOCUMENT_NAMES.to_vec();
+    names.shuffle(&mut rng);
+    for name in names.into_iter().take(options.count) {
+        let Some(dir) = options.dirs.choose(&mut rng) else {
+            break;
+        };
+        let dir = home.join(dir);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        if path.exists() {
+            continue;
+        }
+        std::fs::write(&path, document_content(name, &mut rng))?;
+        let age = rng.gen_range(0..options.max_age_days.max(1) * SECONDS_PER_DAY);
+        let modified = SystemTime::now() - std::time::Duration::from_secs(age);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(modified)?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn skew_clock(secs: i64) -> Result<()> {
+    if cfg!(windows) {
+        powershell(&format!(
+            "Set-Date -Adjust (New-TimeSpan -Seconds {})",
+            secs
+        ))
+        .await?;
+    } else {
+        let now = chrono::Utc::now().timestamp() + secs;
+        run("date", &["-s", &format!("@{}", now)]).await?;
+    }
+    Ok(())
+}
+
+async fn uptime() -> Result<Duration> {
+    if cfg!(windows) {
+        let secs: u64 = powershell(
+            "[int64]((Get-Date) - (Get-CimInstance Win32_OperatingSystem).LastBootUpTime).TotalSeconds",
+        )
+        .await?
+        .trim()
+        .parse()?;
+        return Ok(Duration::from_secs(secs));
+    }
+    let uptime = std::fs::read_to_string("/proc/uptime")?;
+    let secs: f64 = uptime
+        .split_whitespace()
+        .next()
+        .context("Empty /proc/uptime")?
+        .parse()?;
+    Ok(Duration::from_secs_f64(secs))
+}
+
+async fn wait_for_uptime(min_uptime: Duration) {
+    match uptime().await {
+        Ok(uptime) if uptime < min_uptime => {
+            let wait = (min_uptime - uptime).min(MAX_UPTIME_WAIT);
+            info!(
+                "Waiting {}s for guest uptime to look realistic...",
+                wait.as_secs()
+            );
+            sleep(wait).await;
+        }
+        Ok(_) => (),
+        Err(e) => warn!("Failed to read guest uptime: {}", e),
+    }
+}
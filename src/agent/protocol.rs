@@ -0,0 +1,213 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::hardening::HardeningProfile;
+use super::user_sim::UserSimScript;
+use crate::analyzer::clipboard::ClipboardCapture;
+use crate::sync_objects::ObservedMutex;
+use crate::tamper::GoldenFile;
+use crate::telemetry::ApiCall;
+
+const MAX_HEADER_LEN: u32 = 16 * 1024 * 1024;
+pub const MAX_TIME_WARP: f64 = 1000.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Syslog,
+    Xml,
+    Auditd,
+    Vmi,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecutionRequest {
+    pub file_name: String,
+    pub arguments: Vec<String>,
+    pub timeout_secs: u64,
+    pub user: Option<String>,
+    pub screenshot: bool,
+    #[serde(default)]
+    pub screenshot_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub record_video: bool,
+    #[serde(default)]
+    pub simulate_user: bool,
+    #[serde(default)]
+    pub user_sim_script: Option<UserSimScript>,
+    #[serde(default)]
+    pub hardening: Option<HardeningProfile>,
+    #[serde(default)]
+    pub time_warp: Option<TimeWarp>,
+    #[serde(default)]
+    pub dump_triggers: Vec<DumpTrigger>,
+    #[serde(default)]
+    pub dump_scope: DumpScope,
+    #[serde(default)]
+    pub reboot: bool,
+    #[serde(default)]
+    pub collect_only: bool,
+    #[serde(default)]
+    pub resubmit_dropped: bool,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub golden_files: Vec<GoldenFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TimeWarp {
+    pub factor: f64,
+    #[serde(default)]
+    pub skip_sleeps: bool,
+}
+
+impl TimeWarp {
+    pub fn new(factor: f64, skip_sleeps: bool) -> Result<Self> {
+        if !factor.is_finite() || !(1.0..=MAX_TIME_WARP).contains(&factor) {
+            bail!(
+                "Time warp factor must be between 1 and {}, got {}",
+                MAX_TIME_WARP,
+                factor
+            );
+        }
+        Ok(Self {
+            factor,
+            skip_sleeps,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockAdjustment {
+    pub real: DateTime<Utc>,
+    pub offset_secs: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DumpTrigger {
+    EndOfRun,
+    CreateRemoteThread,
+    ProcessTampering,
+}
+
+impl FromStr for DumpTrigger {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "end_of_run" | "end" => Ok(Self::EndOfRun),
+            "create_remote_thread" | "remote_thread" => Ok(Self::CreateRemoteThread),
+            "process_tampering" | "tampering" => Ok(Self::ProcessTampering),
+            _ => Err(anyhow!("Unknown dump trigger '{}'", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DumpScope {
+    #[default]
+    Process,
+    Full,
+}
+
+impl FromStr for DumpScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "process" => Ok(Self::Process),
+            "full" => Ok(Self::Full),
+            _ => Err(anyhow!("Unknown dump scope '{}'", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DroppedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryDump {
+    pub trigger: DumpTrigger,
+    pub process_id: Option<u32>,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ExecutionReport {
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub error: Option<String>,
+    pub log_format: LogFormat,
+    pub dropped_files: Vec<DroppedFile>,
+    pub screenshots: usize,
+    #[serde(default)]
+    pub screenshot_times: Vec<DateTime<Utc>>,
+    #[serde(default)]
+    pub memory_dumps: Vec<MemoryDump>,
+    #[serde(default)]
+    pub video: bool,
+    #[serde(default)]
+    pub clock_adjustments: Vec<ClockAdjustment>,
+    #[serde(default)]
+    pub killed: bool,
+    #[serde(default)]
+    pub mutexes: Vec<ObservedMutex>,
+    #[serde(default)]
+    pub clipboard: Vec<ClipboardCapture>,
+    #[serde(default)]
+    pub api_calls: Vec<ApiCall>,
+    #[serde(default)]
+    pub modified_files: Vec<DroppedFile>,
+}
+
+pub async fn write_header<W, T>(writer: &mut W, value: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let header = serde_json::to_vec(value)?;
+    writer.write_u32(header.len() as u32).await?;
+    writer.write_all(&header).await?;
+    Ok(())
+}
+
+pub async fn read_header<R, T>(reader: &mut R) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let len = reader.read_u32().await?;
+    if len > MAX_HEADER_LEN {
+        bail!("Agent header too large: {} bytes", len);
+    }
+    let mut header = vec![0; len as usize];
+    reader.read_exact(&mut header).await?;
+    Ok(serde_json::from_slice(&header)?)
+}
+
+pub async fn write_blob<W: AsyncWrite + Unpin>(writer: &mut W, blob: &[u8]) -> Result<()> {
+    writer.write_u64(blob.len() as u64).await?;
+    writer.write_all(blob).await?;
+    Ok(())
+}
+
+pub async fn read_blob<R: AsyncRead + Unpin>(reader: &mut R, max_len: u64) -> Result<Vec<u8>> {
+    let len = reader.read_u64().await?;
+    if len > max_len {
+        bail!("Agent payload too large: {} bytes", len);
+    }
+    let mut blob = vec![0; len as usize];
+    reader.read_exact(&mut blob).await?;
+    Ok(blob)
+}
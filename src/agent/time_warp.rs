@@ -0,0 +1,84 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use log::{info, warn};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use super::hardening::skew_clock;
+use super::protocol::{ClockAdjustment, TimeWarp, MAX_TIME_WARP};
+
+const WARP_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct ClockWarp {
+    task: JoinHandle<()>,
+    adjustments: Arc<Mutex<Vec<ClockAdjustment>>>,
+}
+
+impl ClockWarp {
+    pub fn start(warp: &TimeWarp) -> Option<Self> {
+        if warp.factor <= 1.0 {
+            return None;
+        }
+        if !warp.factor.is_finite() || warp.factor > MAX_TIME_WARP {
+            warn!("Ignoring time warp factor {}", warp.factor);
+            return None;
+        }
+        info!("Accelerating guest clock {}x", warp.factor);
+        let adjustments = Arc::new(Mutex::new(Vec::new()));
+        let recorded = adjustments.clone();
+        let factor = warp.factor;
+        let task = tokio::spawn(async move {
+            let mut ticks = interval(WARP_INTERVAL);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticks.tick().await;
+            let mut offset = 0;
+            let mut pending = 0.0;
+            loop {
+                ticks.tick().await;
+                pending += WARP_INTERVAL.as_secs_f64() * (factor - 1.0);
+                let step = pending.trunc() as i64;
+                if step == 0 {
+                    continue;
+                }
+                let real = Utc::now() - ChronoDuration::seconds(offset);
+                if let Err(e) = skew_clock(step).await {
+                    warn!("Failed to advance guest clock: {}", e);
+                    continue;
+                }
+                pending -= step as f64;
+                offset += step;
+                if let Ok(mut adjustments) = recorded.lock() {
+                    adjustments.push(ClockAdjustment {
+                        real,
+                        offset_secs: offset,
+                    });
+                }
+            }
+        });
+        Some(Self { task, adjustments })
+    }
+
+    pub fn stop(self) -> Vec<ClockAdjustment> {
+        self.task.abort();
+        self.adjustments
+            .lock()
+            .map(|a| a.clone())
+            .unwrap_or_default()
+    }
+}
+
+pub fn real_time<Tz: TimeZone>(
+    adjustments: &[ClockAdjustment],
+    guest_time: DateTime<Tz>,
+) -> DateTime<Tz> {
+    let guest = guest_time.with_timezone(&Utc);
+    match adjustments
+        .iter()
+        .rev()
+        .find(|a| a.real + ChronoDuration::seconds(a.offset_secs) <= guest)
+    {
+        Some(adjustment) => guest_time - ChronoDuration::seconds(adjustment.offset_secs),
+        None => guest_time,
+    }
+}
@@ -0,0 +1,522 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use log::{debug, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tokio::time::{sleep, Duration};
+
+const TYPE_DELAY_MS: u64 = 80;
+const REPEAT_DELAY: Duration = Duration::from_secs(1);
+const MACRO_OFFICE_VERSIONS: &[&str] = &["14.0", "15.0", "16.0"];
+const MACRO_OFFICE_APPS: &[&str] = &["Word", "Excel", "PowerPoint"];
+
+const DIALOG_BUTTONS: &[&str] = &[
+    "OK",
+    "Yes",
+    "Next",
+    "Next >",
+    "Install",
+    "I Agree",
+    "I accept",
+    "Accept",
+    "Agree",
+    "Run",
+    "Open",
+    "Allow",
+    "Continue",
+    "Enable Content",
+    "Enable Editing",
+    "Enable Macros",
+    "Finish",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Wait {
+        secs: f64,
+    },
+    MoveMouse {
+        x: i32,
+        y: i32,
+    },
+    RandomMouse {
+        moves: u32,
+    },
+    Click {
+        x: Option<i32>,
+        y: Option<i32>,
+        #[serde(default)]
+        button: MouseButton,
+    },
+    Type {
+        text: String,
+    },
+    Key {
+        key: String,
+    },
+    ClickButtons {
+        #[serde(default)]
+        labels: Vec<String>,
+    },
+    EnableMacros,
+}
+
+fn default_screen_width() -> i32 {
+    1024
+}
+
+fn default_screen_height() -> i32 {
+    768
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserSimScript {
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    #[serde(default)]
+    pub repeat: bool,
+    #[serde(default = "default_screen_width")]
+    pub screen_width: i32,
+    #[serde(default = "default_screen_height")]
+    pub screen_height: i32,
+}
+
+impl Default for UserSimScript {
+    fn default() -> Self {
+        Self {
+            actions: vec![
+                Action::EnableMacros,
+                Action::Wait { secs: 3.0 },
+                Action::ClickButtons { labels: Vec::new() },
+                Action::RandomMouse { moves: 5 },
+                Action::Wait { secs: 2.0 },
+            ],
+            repeat: true,
+            screen_width: default_screen_width(),
+            screen_height: default_screen_height(),
+        }
+    }
+}
+
+impl UserSimScript {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub async fn prepare(&self) {
+        if self
+            .actions
+            .iter()
+            .any(|a| matches!(a, Action::EnableMacros))
+        {
+            if let Err(e) = enable_macros().await {
+                warn!("Failed to enable Office macros: {}", e);
+            }
+        }
+    }
+
+    pub async fn run(&self, driver: InputDriver) {
+        if self.actions.is_empty() {
+            return;
+        }
+        loop {
+            for action in &self.actions {
+                if let Err(e) = self.perform(driver, action).await {
+                    debug!("User simulation action {:?} failed: {}", action, e);
+                }
+            }
+            if !self.repeat {
+                return;
+            }
+            sleep(REPEAT_DELAY).await;
+        }
+    }
+
+    async fn perform(&self, driver: InputDriver, action: &Action) -> Result<()> {
+        match action {
+            Action::Wait { secs } => sleep(Duration::from_secs_f64(secs.max(0.0))).await,
+            Action::MoveMouse { x, y } => driver.move_mouse(*x, *y).await?,
+            Action::RandomMouse { moves } => {
+                for _ in 0..*moves {
+                    let (x, y) = {
+                        let mut rng = rand::thread_rng();
+                        (
+                            rng.gen_range(0..self.screen_width.max(1)),
+                            rng.gen_range(0..self.screen_height.max(1)),
+                        )
+                    };
+                    driver.move_mouse(x, y).await?;
+                    sleep(Duration::from_millis(200)).await;
+                }
+            }
+            Action::Click { x, y, button } => {
+                if let (Some(x), Some(y)) = (x, y) {
+                    driver.move_mouse(*x, *y).await?;
+                }
+                driver.click(*button).await?;
+            }
+            Action::Type { text } => driver.type_text(text).await?,
+            Action::Key { key } => driver.key(key).await?,
+            Action::ClickButtons { labels } => {
+                let labels: Vec<&str> = if labels.is_empty() {
+                    DIALOG_BUTTONS.to_vec()
+                } else {
+                    labels.iter().map(String::as_str).collect()
+                };
+                let clicked = driver.click_buttons(&labels).await?;
+                if clicked > 0 {
+                    debug!("Clicked {} dialog buttons", clicked);
+                }
+            }
+            Action::EnableMacros => (),
+        }
+        Ok(())
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program).args(args).output().await?;
+    if !output.status.success() {
+        bail!("{} exited with {}", program, output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn set_dword(key: &str, name: &str, value: u32) -> Result<()> {
+    let value = value.to_string();
+    run(
+        "reg",
+        &[
+            "add",
+            key,
+            "/v",
+            name,
+            "/t",
+            "REG_DWORD",
+            "/d",
+            &value,
+            "/f",
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn enable_macros() -> Result<()> {
+    if !cfg!(windows) {
+        return Ok(());
+    }
+    for version in MACRO_OFFICE_VERSIONS {
+        for app in MACRO_OFFICE_APPS {
+            let key = format!(
+                r"HKCU\Software\Microsoft\Office\{}\{}\Security",
+                version, app
+            );
+            set_dword(&key, "VBAWarnings", 1).await?;
+            set_dword(&key, "AccessVBOM", 1).await?;
+            let protected_view = format!(r"{}\ProtectedView", key);
+            for name in [
+                "DisableInternetFilesInPV",
+                "DisableAttachementsInPV",
+                "DisableUnsafeLocationsInPV",
+            ] {
+                set_dword(&protected_view, name, 1).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputDriver {
+    Xdotool,
+    #[cfg(all(windows, feature = "windows"))]
+    Win32,
+}
+
+impl Default for InputDriver {
+    #[cfg(all(windows, feature = "windows"))]
+    fn default() -> Self {
+        Self::Win32
+    }
+
+    #[cfg(not(all(windows, feature = "windows")))]
+    fn default() -> Self {
+        Self::Xdotool
+    }
+}
+
+impl InputDriver {
+    async fn move_mouse(self, x: i32, y: i32) -> Result<()> {
+        match self {
+            Self::Xdotool => {
+                run("xdotool", &["mousemove", &x.to_string(), &y.to_string()]).await?;
+            }
+            #[cfg(all(windows, feature = "windows"))]
+            Self::Win32 => win32::move_mouse(x, y)?,
+        }
+        Ok(())
+    }
+
+    async fn click(self, button: MouseButton) -> Result<()> {
+        match self {
+            Self::Xdotool => {
+                let button = match button {
+                    MouseButton::Left => "1",
+                    MouseButton::Middle => "2",
+                    MouseButton::Right => "3",
+                };
+                run("xdotool", &["click", button]).await?;
+            }
+            #[cfg(all(windows, feature = "windows"))]
+            Self::Win32 => win32::click(button)?,
+        }
+        Ok(())
+    }
+
+    async fn type_text(self, text: &str) -> Result<()> {
+        match self {
+            Self::Xdotool => {
+                let delay = TYPE_DELAY_MS.to_string();
+                run("xdotool", &["type", "--delay", &delay, "--", text]).await?;
+            }
+            #[cfg(all(windows, feature = "windows"))]
+            Self::Win32 => {
+                for c in text.chars() {
+                    win32::type_char(c)?;
+                    sleep(Duration::from_millis(TYPE_DELAY_MS)).await;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn key(self, key: &str) -> Result<()> {
+        match self {
+            Self::Xdotool => {
+                run("xdotool", &["key", "--", key]).await?;
+            }
+            #[cfg(all(windows, feature = "windows"))]
+            Self::Win32 => win32::key(key)?,
+        }
+        Ok(())
+    }
+
+    async fn click_buttons(self, labels: &[&str]) -> Result<usize> {
+        match self {
+            Self::Xdotool => {
+                let mut clicked = 0;
+                for label in labels {
+                    let Ok(windows) =
+                        run("xdotool", &["search", "--onlyvisible", "--name", label]).await
+                    else {
+                        continue;
+                    };
+                    for window in windows.lines().filter(|w| !w.is_empty()) {
+                        run(
+                            "xdotool",
+                            &["windowactivate", "--sync", window, "key", "Return"],
+                        )
+                        .await?;
+                        clicked += 1;
+                    }
+                }
+                Ok(clicked)
+            }
+            #[cfg(all(windows, feature = "windows"))]
+            Self::Win32 => Ok(win32::click_buttons(labels)),
+        }
+    }
+}
+
+#[cfg(all(windows, feature = "windows"))]
+mod win32 {
+    use std::mem::size_of;
+
+    use anyhow::{bail, Result};
+    use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM};
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        IsWindowEnabled, SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBDINPUT,
+        KEYEVENTF_KEYUP, KEYEVENTF_UNICODE, MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP,
+        MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP,
+        MOUSEINPUT, VK_BACK, VK_CONTROL, VK_DELETE, VK_DOWN, VK_ESCAPE, VK_F1, VK_LEFT, VK_LWIN,
+        VK_MENU, VK_RETURN, VK_RIGHT, VK_SHIFT, VK_SPACE, VK_TAB, VK_UP,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        EnumChildWindows, EnumWindows, GetClassNameW, GetWindowTextW, IsWindowVisible,
+        PostMessageW, SetCursorPos, BM_CLICK,
+    };
+
+    use super::MouseButton;
+
+    fn send(inputs: &[INPUT]) -> Result<()> {
+        let sent = unsafe {
+            SendInput(
+                inputs.len() as u32,
+                inputs.as_ptr(),
+                size_of::<INPUT>() as i32,
+            )
+        };
+        if sent as usize != inputs.len() {
+            bail!("SendInput injected {} of {} events", sent, inputs.len());
+        }
+        Ok(())
+    }
+
+    fn mouse(flags: u32) -> INPUT {
+        INPUT {
+            r#type: INPUT_MOUSE,
+            Anonymous: INPUT_0 {
+                mi: MOUSEINPUT {
+                    dx: 0,
+                    dy: 0,
+                    mouseData: 0,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        }
+    }
+
+    fn keyboard(vk: u16, scan: u16, flags: u32) -> INPUT {
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: vk,
+                    wScan: scan,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        }
+    }
+
+    pub fn move_mouse(x: i32, y: i32) -> Result<()> {
+        if unsafe { SetCursorPos(x, y) } == 0 {
+            bail!("SetCursorPos failed");
+        }
+        Ok(())
+    }
+
+    pub fn click(button: MouseButton) -> Result<()> {
+        let (down, up) = match button {
+            MouseButton::Left => (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP),
+            MouseButton::Right => (MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP),
+            MouseButton::Middle => (MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP),
+        };
+        send(&[mouse(down), mouse(up)])
+    }
+
+    pub fn type_char(c: char) -> Result<()> {
+        let mut units = [0u16; 2];
+        for unit in c.encode_utf16(&mut units) {
+            send(&[
+                keyboard(0, *unit, KEYEVENTF_UNICODE),
+                keyboard(0, *unit, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP),
+            ])?;
+        }
+        Ok(())
+    }
+
+    fn virtual_key(name: &str) -> Option<u16> {
+        let name = name.to_ascii_lowercase();
+        let key = match name.as_str() {
+            "return" | "enter" => VK_RETURN,
+            "tab" => VK_TAB,
+            "escape" | "esc" => VK_ESCAPE,
+            "space" => VK_SPACE,
+            "backspace" => VK_BACK,
+            "delete" => VK_DELETE,
+            "up" => VK_UP,
+            "down" => VK_DOWN,
+            "left" => VK_LEFT,
+            "right" => VK_RIGHT,
+            "alt" => VK_MENU,
+            "ctrl" | "control" => VK_CONTROL,
+            "shift" => VK_SHIFT,
+            "win" | "super" => VK_LWIN,
+            _ => match name.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+                Some(n @ 1..=12) => VK_F1 + n - 1,
+                _ if name.len() == 1 && name.as_bytes()[0].is_ascii_alphanumeric() => {
+                    name.to_ascii_uppercase().as_bytes()[0] as u16
+                }
+                _ => return None,
+            },
+        };
+        Some(key)
+    }
+
+    pub fn key(combination: &str) -> Result<()> {
+        let mut keys = Vec::new();
+        for name in combination.split('+') {
+            match virtual_key(name.trim()) {
+                Some(key) => keys.push(key),
+                None => bail!("Unknown key '{}'", name),
+            }
+        }
+        let mut inputs: Vec<INPUT> = keys.iter().map(|k| keyboard(*k, 0, 0)).collect();
+        inputs.extend(keys.iter().rev().map(|k| keyboard(*k, 0, KEYEVENTF_KEYUP)));
+        send(&inputs)
+    }
+
+    struct Search<'a> {
+        labels: &'a [&'a str],
+        clicked: usize,
+    }
+
+    fn text(hwnd: HWND, get: unsafe extern "system" fn(HWND, *mut u16, i32) -> i32) -> String {
+        let mut buffer = [0u16; 256];
+        let len = unsafe { get(hwnd, buffer.as_mut_ptr(), buffer.len() as i32) };
+        String::from_utf16_lossy(&buffer[..len.max(0) as usize])
+    }
+
+    unsafe extern "system" fn child_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = &mut *(lparam as *mut Search);
+        if IsWindowVisible(hwnd) == 0 || IsWindowEnabled(hwnd) == 0 {
+            return 1;
+        }
+        if !text(hwnd, GetClassNameW).eq_ignore_ascii_case("Button") {
+            return 1;
+        }
+        let label = text(hwnd, GetWindowTextW).replace('&', "");
+        if search
+            .labels
+            .iter()
+            .any(|l| l.eq_ignore_ascii_case(label.trim()))
+        {
+            PostMessageW(hwnd, BM_CLICK, 0, 0);
+            search.clicked += 1;
+        }
+        1
+    }
+
+    unsafe extern "system" fn window_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        if IsWindowVisible(hwnd) != 0 {
+            EnumChildWindows(hwnd, Some(child_callback), lparam);
+        }
+        1
+    }
+
+    pub fn click_buttons(labels: &[&str]) -> usize {
+        let mut search = Search { labels, clicked: 0 };
+        unsafe {
+            EnumWindows(Some(window_callback), &mut search as *mut Search as LPARAM);
+        }
+        search.clicked
+    }
+}
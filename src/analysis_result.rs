@@ -3,19 +3,79 @@ use std::collections::HashMap;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Local};
 use log::info;
-use mongodb::bson::{doc, Document};
+use mongodb::bson::{doc, to_bson, Document};
 use mongodb::options::{ClientOptions, ServerApi, ServerApiVersion};
 use mongodb::{Client, Collection};
 use serde::{Deserialize, Serialize};
 
+use crate::analyzer::clipboard::ClipboardCapture;
+use crate::artifacts::Artifact;
+use crate::lineage::Derivation;
+use crate::memory::MemoryAnalysis;
+use crate::metadata::SampleMetadata;
+use crate::orchestrator::image::ImageMetadata;
+use crate::orchestrator::limits::LimitViolation;
+use crate::quarantine::ParseFailureSummary;
+use crate::storage::retention::DataClass;
+use crate::sync_objects::ObservedMutex;
 use crate::sysmon_event::SysmonEvent;
+use crate::tamper::FileTamper;
+use crate::telemetry::TelemetryEvent;
 
-#[derive(Serialize, Deserialize, Debug)]
+pub fn sample_path(id: &str) -> String {
+    format!("analysis_result/{}/sample/sample", id)
+}
+
+pub fn container_path(id: &str) -> String {
+    format!("analysis_result/{}/sample/container.json", id)
+}
+
+pub fn scripts_dir(id: &str) -> String {
+    format!("analysis_result/{}/sample/scripts", id)
+}
+
+pub fn artifact_dir(id: &str, execution_id: &str) -> String {
+    format!("analysis_result/{}/artifact/{}", id, execution_id)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ExecutionLog {
     pub id: String,
     pub time: DateTime<Local>,
     pub sysmon_events: Vec<SysmonEvent>,
     pub created_files: HashMap<String, String>,
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    #[serde(default)]
+    pub telemetry: Vec<TelemetryEvent>,
+    #[serde(default)]
+    pub memory: Vec<MemoryAnalysis>,
+    #[serde(default)]
+    pub machine: Option<String>,
+    #[serde(default)]
+    pub snapshot: Option<String>,
+    #[serde(default)]
+    pub mutexes: Vec<ObservedMutex>,
+    #[serde(default)]
+    pub clipboard: Vec<ClipboardCapture>,
+    #[serde(default)]
+    pub image: Option<ImageMetadata>,
+    #[serde(default)]
+    pub terminated: Option<LimitViolation>,
+    #[serde(default)]
+    pub purged: Vec<DataClass>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub tampered: Vec<FileTamper>,
+    #[serde(default)]
+    pub parse_failures: ParseFailureSummary,
+    #[serde(default)]
+    pub derivation: Vec<Derivation>,
+    #[serde(default)]
+    pub metadata: SampleMetadata,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -23,6 +83,10 @@ pub struct AnalysisResult {
     pub id: String,
     pub hash: String,
     pub execution_logs: Vec<ExecutionLog>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub metadata: SampleMetadata,
 }
 
 pub struct AnalysisResultManager {
@@ -57,7 +121,15 @@ impl AnalysisResultManager {
         self.find_one(doc! {"hash": hash}).await
     }
 
-    async fn get(&self, id: &str) -> Result<Option<AnalysisResult>> {
+    pub async fn search_hash_for(
+        &self,
+        hash: &str,
+        tenant: Option<&str>,
+    ) -> Result<Option<AnalysisResult>> {
+        self.find_one(doc! {"hash": hash, "tenant": tenant}).await
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<AnalysisResult>> {
         self.find_one(doc! {"id": id}).await
     }
 
@@ -66,6 +138,60 @@ impl AnalysisResultManager {
         Ok(())
     }
 
+    pub async fn derived_from(&self, id: &str) -> Result<Vec<AnalysisResult>> {
+        let mut cursor = self
+            .collection
+            .find(doc! {"execution_logs.derivation.analysis_id": id}, None)
+            .await?;
+        let mut results = Vec::new();
+        while cursor.advance().await? {
+            results.push(cursor.deserialize_current()?);
+        }
+        Ok(results)
+    }
+
+    pub async fn set_metadata(&self, id: &str, metadata: &SampleMetadata) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! {"id": id},
+                doc! {"$set": {"metadata": to_bson(metadata)?}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_run_metadata(
+        &self,
+        id: &str,
+        execution_id: &str,
+        metadata: &SampleMetadata,
+    ) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! {"id": id, "execution_logs.id": execution_id},
+                doc! {"$set": {"execution_logs.$.metadata": to_bson(metadata)?}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn ids(&self) -> Result<Vec<String>> {
+        let ids = self.collection.distinct("id", None, None).await?;
+        Ok(ids
+            .into_iter()
+            .filter_map(|id| id.as_str().map(str::to_string))
+            .collect())
+    }
+
+    pub async fn replace(&self, result: &AnalysisResult) -> Result<()> {
+        self.collection
+            .replace_one(doc! {"id": &result.id}, result, None)
+            .await?;
+        Ok(())
+    }
+
     pub async fn is_exist(&self, id: &str) -> Result<bool> {
         match self.get(id).await? {
             Some(_) => Ok(true),
@@ -74,10 +200,21 @@ impl AnalysisResultManager {
     }
 
     pub async fn make_new_result(&self, id: &str, hash: &str) -> Result<()> {
+        self.make_new_result_for(id, hash, None).await
+    }
+
+    pub async fn make_new_result_for(
+        &self,
+        id: &str,
+        hash: &str,
+        tenant: Option<&str>,
+    ) -> Result<()> {
         let analysis_result = AnalysisResult {
             id: id.to_string(),
             hash: hash.to_string(),
             execution_logs: Vec::new(),
+            tenant: tenant.map(str::to_string),
+            metadata: SampleMetadata::default(),
         };
         self.collection.insert_one(analysis_result, None).await?;
 
@@ -86,15 +223,17 @@ impl AnalysisResultManager {
 
     pub async fn store_execution_log(&self, id: &str, execution_log: ExecutionLog) -> Result<()> {
         let mut prev_analysis_result = self
-            .get(&id)
+            .get(id)
             .await?
             .context("No analysis result for the id")?;
-        self.delete(&id).await?;
+        self.delete(id).await?;
         prev_analysis_result.execution_logs.push(execution_log);
         let new_analysis_result = AnalysisResult {
             id: id.to_string(),
             hash: prev_analysis_result.hash,
             execution_logs: prev_analysis_result.execution_logs,
+            tenant: prev_analysis_result.tenant,
+            metadata: prev_analysis_result.metadata,
         };
 
         self.collection
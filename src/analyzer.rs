@@ -1,2 +1,21 @@
+pub mod api_trace;
 pub mod behavior_detection;
+pub mod capability;
+pub mod clipboard;
+pub mod code_signing;
+pub mod credential_access;
+pub mod dns_anomaly;
+pub mod driver;
+pub mod fingerprint;
+pub mod initial_access;
+pub mod live;
+pub mod masquerading;
+pub mod parentage;
+pub mod persistence;
+pub mod privilege;
+pub mod ransomware;
+pub mod script_block;
+pub mod sigma;
+pub mod signature;
 pub mod surface_detection;
+pub mod yara_scan;
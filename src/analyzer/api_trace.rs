@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::telemetry::ApiCall;
+
+pub const DEFAULT_TRACED_APIS: &[&str] = &[
+    "CreateFile",
+    "RegSetValue",
+    "RegSetValueEx",
+    "VirtualAllocEx",
+    "WriteProcessMemory",
+    "CryptEncrypt",
+    "BCryptEncrypt",
+];
+
+const RUN_KEYS: &[&str] = &[
+    r"\currentversion\run",
+    r"\currentversion\policies\explorer\run",
+    r"\winlogon\userinit",
+    r"\winlogon\shell",
+];
+
+pub struct ApiTraceOptions {
+    pub min_encryptions: usize,
+}
+
+impl Default for ApiTraceOptions {
+    fn default() -> Self {
+        Self {
+            min_encryptions: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiAlert {
+    pub signature: String,
+    pub technique: String,
+    pub level: &'static str,
+    pub process_id: u32,
+    pub calls: Vec<ApiCall>,
+}
+
+fn target_process(call: &ApiCall) -> Option<u32> {
+    call.arg("TargetProcessId")
+        .or_else(|| call.arg("ProcessId"))?
+        .parse()
+        .ok()
+        .filter(|pid| *pid != call.process_id)
+}
+
+fn is_executable(call: &ApiCall) -> bool {
+    call.arg("flProtect")
+        .or_else(|| call.arg("Protect"))
+        .is_some_and(|p| p.to_uppercase().contains("EXECUTE") || is_execute_mask(p))
+}
+
+fn is_execute_mask(protect: &str) -> bool {
+    let value = match protect.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => protect.parse().ok(),
+    };
+    value.is_some_and(|v| v & 0xf0 != 0)
+}
+
+fn detect_injection(calls: &[ApiCall]) -> Vec<ApiAlert> {
+    let mut chains: BTreeMap<(u32, u32), Vec<&ApiCall>> = BTreeMap::new();
+    for call in calls {
+        if !call.is("VirtualAllocEx") && !call.is("WriteProcessMemory") {
+            continue;
+        }
+        if let Some(target) = target_process(call) {
+            chains
+                .entry((call.process_id, target))
+                .or_default()
+                .push(call);
+        }
+    }
+
+    let mut alerts = Vec::new();
+    for ((source, target), chain) in chains {
+        let Some(allocated) = chain.iter().position(|c| c.is("VirtualAllocEx")) else {
+            continue;
+        };
+        if !chain[allocated..]
+            .iter()
+            .any(|c| c.is("WriteProcessMemory"))
+        {
+            continue;
+        }
+        let executable = chain.iter().any(|c| is_executable(c));
+        alerts.push(ApiAlert {
+            signature: format!(
+                "Remote memory allocation and write into process {}{}",
+                target,
+                if executable { " (executable)" } else { "" }
+            ),
+            technique: "T1055".to_string(),
+            level: if executable { "high" } else { "medium" },
+            process_id: source,
+            calls: chain.into_iter().cloned().collect(),
+        });
+    }
+    alerts
+}
+
+fn detect_bulk_encryption(calls: &[ApiCall], options: &ApiTraceOptions) -> Vec<ApiAlert> {
+    let mut encryptions: BTreeMap<u32, Vec<&ApiCall>> = BTreeMap::new();
+    for call in calls {
+        if call.is("CryptEncrypt") || call.is("BCryptEncrypt") {
+            encryptions.entry(call.process_id).or_default().push(call);
+        }
+    }
+    encryptions
+        .into_iter()
+        .filter(|(_, calls)| calls.len() >= options.min_encryptions)
+        .map(|(pid, calls)| ApiAlert {
+            signature: format!("Bulk encryption ({} calls)", calls.len()),
+            technique: "T1486".to_string(),
+            level: "high",
+            process_id: pid,
+            calls: calls.into_iter().take(10).cloned().collect(),
+        })
+        .collect()
+}
+
+fn detect_autostart_writes(calls: &[ApiCall]) -> Vec<ApiAlert> {
+    let mut writes: HashMap<u32, Vec<&ApiCall>> = HashMap::new();
+    for call in calls {
+        if !call.is("RegSetValue") && !call.is("RegSetValueEx") {
+            continue;
+        }
+        let Some(key) = call.arg("KeyName").or_else(|| call.arg("hKey")) else {
+            continue;
+        };
+        let key = key.to_lowercase();
+        if RUN_KEYS.iter().any(|k| key.contains(k)) {
+            writes.entry(call.process_id).or_default().push(call);
+        }
+    }
+    let mut alerts: Vec<ApiAlert> = writes
+        .into_iter()
+        .map(|(pid, calls)| ApiAlert {
+            signature: "Autostart registry value written via API".to_string(),
+            technique: "T1547.001".to_string(),
+            level: "medium",
+            process_id: pid,
+            calls: calls.into_iter().cloned().collect(),
+        })
+        .collect();
+    alerts.sort_by_key(|a| a.process_id);
+    alerts
+}
+
+pub fn detect_api_abuse(calls: &[ApiCall]) -> Vec<ApiAlert> {
+    detect_api_abuse_with(calls, &ApiTraceOptions::default())
+}
+
+pub fn detect_api_abuse_with(calls: &[ApiCall], options: &ApiTraceOptions) -> Vec<ApiAlert> {
+    let mut alerts = detect_injection(calls);
+    alerts.extend(detect_bulk_encryption(calls, options));
+    alerts.extend(detect_autostart_writes(calls));
+    alerts
+}
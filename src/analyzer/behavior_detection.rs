@@ -1,4 +1,4 @@
-mod rule;
+pub mod rule;
 
 use anyhow::Result;
 use itertools::Itertools;
@@ -72,7 +72,7 @@ pub fn detect(events: Vec<SysmonEvent>) -> Result<Vec<SysmonEvent>> {
     struct DeleteExistingFile {}
 
     impl Rule for DeleteExistingFile {
-        fn first_match(&self, events: &Vec<SysmonEvent>) -> Vec<usize> {
+        fn first_match(&self, events: &[SysmonEvent]) -> Vec<usize> {
             let mut result = Vec::new();
             let delete = Filter {
                 event_id: SysmonEventId::FILE_DELETE,
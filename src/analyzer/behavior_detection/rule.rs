@@ -1,5 +1,6 @@
 use crate::sysmon_event::{SysmonEvent, SysmonEventId};
 
+#[derive(Default)]
 pub struct Rules {
     rules: Vec<Box<dyn Rule>>,
 }
@@ -13,7 +14,7 @@ impl Rules {
         self.rules.push(Box::new(rule));
     }
 
-    pub fn match_all(&self, events: &Vec<SysmonEvent>) -> Vec<usize> {
+    pub fn match_all(&self, events: &[SysmonEvent]) -> Vec<usize> {
         let mut result = Vec::new();
         for rule in &self.rules {
             result.extend(rule.first_match(events));
@@ -23,7 +24,7 @@ impl Rules {
 }
 
 pub trait Rule {
-    fn first_match(&self, events: &Vec<SysmonEvent>) -> Vec<usize>;
+    fn first_match(&self, events: &[SysmonEvent]) -> Vec<usize>;
 }
 
 pub struct Filter {
@@ -32,7 +33,7 @@ pub struct Filter {
 }
 
 impl Filter {
-    fn is_match(&self, event: &SysmonEvent) -> bool {
+    pub fn is_match(&self, event: &SysmonEvent) -> bool {
         event.event_id == self.event_id && (self.condition)(event)
     }
 }
@@ -42,10 +43,10 @@ pub struct Single {
 }
 
 impl Rule for Single {
-    fn first_match(&self, events: &Vec<SysmonEvent>) -> Vec<usize> {
+    fn first_match(&self, events: &[SysmonEvent]) -> Vec<usize> {
         let mut result = Vec::new();
         for (i, event) in events.iter().enumerate() {
-            if self.filter.is_match(&event) {
+            if self.filter.is_match(event) {
                 result.push(i);
                 return result;
             }
@@ -59,7 +60,7 @@ pub struct Order {
 }
 
 impl Rule for Order {
-    fn first_match(&self, events: &Vec<SysmonEvent>) -> Vec<usize> {
+    fn first_match(&self, events: &[SysmonEvent]) -> Vec<usize> {
         let mut result = Vec::new();
         let filter_len = self.filters.len();
         if filter_len == 0 {
@@ -68,12 +69,12 @@ impl Rule for Order {
 
         let mut filter_idx = 0;
         for (i, event) in events.iter().enumerate() {
-            if self.filters[filter_idx].is_match(&event) {
+            if self.filters[filter_idx].is_match(event) {
                 result.push(i);
                 filter_idx += 1;
-            } else if filter_idx > 0 && self.filters[filter_idx - 1].is_match(&event) {
+            } else if filter_idx > 0 && self.filters[filter_idx - 1].is_match(event) {
                 result.push(i);
-            } else if self.filters[0].is_match(&event) {
+            } else if self.filters[0].is_match(event) {
                 result.clear();
                 filter_idx = 0;
             }
@@ -92,7 +93,7 @@ pub struct Sequential {
 }
 
 impl Rule for Sequential {
-    fn first_match(&self, events: &Vec<SysmonEvent>) -> Vec<usize> {
+    fn first_match(&self, events: &[SysmonEvent]) -> Vec<usize> {
         let mut result = Vec::new();
         let filter_len = self.filters.len();
         if filter_len == 0 {
@@ -101,10 +102,10 @@ impl Rule for Sequential {
 
         let mut filter_idx = 0;
         for (i, event) in events.iter().enumerate() {
-            if self.filters[filter_idx].is_match(&event) {
+            if self.filters[filter_idx].is_match(event) {
                 result.push(i);
                 filter_idx += 1;
-            } else if self.filters[0].is_match(&event) {
+            } else if self.filters[0].is_match(event) {
                 result.clear();
                 result.push(i);
                 filter_idx = 1;
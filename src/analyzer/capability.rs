@@ -0,0 +1,656 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::attack::Tactic;
+use crate::memory::extract_strings;
+use crate::static_analysis::pe::PeInfo;
+
+const MIN_STRING_LEN: usize = 4;
+
+const BUILTIN_RULES: &[&str] = &[
+    r#"
+rule:
+  meta:
+    name: create reverse shell
+    namespace: communication/c2/shell
+    att&ck:
+      - Execution::Command and Scripting Interpreter::Windows Command Shell [T1059.003]
+  features:
+    - and:
+      - or:
+        - api: ws2_32.WSASocket
+        - api: ws2_32.socket
+      - or:
+        - api: ws2_32.connect
+        - api: ws2_32.WSAConnect
+      - api: CreateProcess
+      - or:
+        - string: /cmd(\.exe)?/i
+        - string: /powershell(\.exe)?/i
+"#,
+    r#"
+rule:
+  meta:
+    name: encrypt data using CryptoAPI
+    namespace: data-manipulation/encryption
+    att&ck:
+      - Impact::Data Encrypted for Impact [T1486]
+  features:
+    - and:
+      - api: advapi32.CryptAcquireContext
+      - or:
+        - api: advapi32.CryptEncrypt
+        - api: advapi32.CryptGenKey
+        - api: advapi32.CryptImportKey
+"#,
+    r#"
+rule:
+  meta:
+    name: encrypt files
+    namespace: impact/encryption
+    att&ck:
+      - Impact::Data Encrypted for Impact [T1486]
+  features:
+    - and:
+      - match: data-manipulation/encryption
+      - or:
+        - api: FindFirstFile
+        - api: FindFirstFileEx
+      - api: FindNextFile
+      - or:
+        - api: WriteFile
+        - api: MoveFile
+        - api: MoveFileEx
+"#,
+    r#"
+rule:
+  meta:
+    name: delete volume shadow copies
+    namespace: impact/inhibit-system-recovery
+    att&ck:
+      - Impact::Inhibit System Recovery [T1490]
+  features:
+    - or:
+      - string: /vssadmin(\.exe)?\s+delete\s+shadows/i
+      - string: /shadowcopy\s+delete/i
+      - string: /bcdedit(\.exe)?.*recoveryenabled\s+no/i
+"#,
+    r#"
+rule:
+  meta:
+    name: inject code into another process
+    namespace: host-interaction/process/inject
+    att&ck:
+      - Defense Evasion::Process Injection [T1055]
+      - Privilege Escalation::Process Injection [T1055]
+  features:
+    - and:
+      - api: OpenProcess
+      - or:
+        - api: VirtualAllocEx
+        - api: NtAllocateVirtualMemory
+      - or:
+        - api: WriteProcessMemory
+        - api: NtWriteVirtualMemory
+      - or:
+        - api: CreateRemoteThread
+        - api: CreateRemoteThreadEx
+        - api: NtCreateThreadEx
+        - api: QueueUserAPC
+"#,
+    r#"
+rule:
+  meta:
+    name: hollow a suspended process
+    namespace: host-interaction/process/inject
+    att&ck:
+      - Defense Evasion::Process Injection::Process Hollowing [T1055.012]
+  features:
+    - and:
+      - api: CreateProcess
+      - or:
+        - api: NtUnmapViewOfSection
+        - api: ZwUnmapViewOfSection
+      - api: WriteProcessMemory
+      - or:
+        - api: SetThreadContext
+        - api: Wow64SetThreadContext
+      - api: ResumeThread
+"#,
+    r#"
+rule:
+  meta:
+    name: capture keystrokes
+    namespace: collection/keylog
+    att&ck:
+      - Collection::Input Capture::Keylogging [T1056.001]
+  features:
+    - or:
+      - and:
+        - api: SetWindowsHookEx
+        - api: CallNextHookEx
+      - and:
+        - 2 or more:
+          - api: GetAsyncKeyState
+          - api: GetKeyState
+          - api: GetKeyboardState
+          - api: MapVirtualKey
+        - api: GetForegroundWindow
+"#,
+    r#"
+rule:
+  meta:
+    name: capture screenshot
+    namespace: collection/screenshot
+    att&ck:
+      - Collection::Screen Capture [T1113]
+  features:
+    - and:
+      - or:
+        - api: GetDC
+        - api: GetWindowDC
+        - api: CreateDC
+      - api: CreateCompatibleBitmap
+      - api: BitBlt
+"#,
+    r#"
+rule:
+  meta:
+    name: persist via Run registry key
+    namespace: persistence/registry/run
+    att&ck:
+      - Persistence::Boot or Logon Autostart Execution::Registry Run Keys / Startup Folder [T1547.001]
+  features:
+    - and:
+      - or:
+        - api: RegSetValue
+        - api: RegSetValueEx
+        - api: RegCreateKey
+        - api: RegCreateKeyEx
+      - substring: CurrentVersion\Run
+"#,
+    r#"
+rule:
+  meta:
+    name: create service
+    namespace: persistence/service
+    att&ck:
+      - Persistence::Create or Modify System Process::Windows Service [T1543.003]
+  features:
+    - and:
+      - api: OpenSCManager
+      - api: CreateService
+"#,
+    r#"
+rule:
+  meta:
+    name: download file from the internet
+    namespace: communication/http/client
+    att&ck:
+      - Command and Control::Ingress Tool Transfer [T1105]
+  features:
+    - or:
+      - api: urlmon.URLDownloadToFile
+      - and:
+        - or:
+          - api: InternetOpenUrl
+          - api: HttpSendRequest
+          - api: WinHttpSendRequest
+        - or:
+          - api: InternetReadFile
+          - api: WinHttpReadData
+        - api: WriteFile
+"#,
+    r#"
+rule:
+  meta:
+    name: check for debugger
+    namespace: anti-analysis/anti-debugging
+    att&ck:
+      - Defense Evasion::Debugger Evasion [T1622]
+  features:
+    - or:
+      - api: IsDebuggerPresent
+      - api: CheckRemoteDebuggerPresent
+      - and:
+        - api: NtQueryInformationProcess
+        - optional:
+          - api: GetCurrentProcess
+"#,
+    r#"
+rule:
+  meta:
+    name: enumerate processes
+    namespace: host-interaction/process/list
+    att&ck:
+      - Discovery::Process Discovery [T1057]
+  features:
+    - or:
+      - and:
+        - api: CreateToolhelp32Snapshot
+        - api: Process32First
+        - api: Process32Next
+      - api: EnumProcesses
+"#,
+    r#"
+rule:
+  meta:
+    name: access LSASS memory
+    namespace: collection/credentials/lsass
+    att&ck:
+      - Credential Access::OS Credential Dumping::LSASS Memory [T1003.001]
+  features:
+    - and:
+      - substring: lsass
+      - or:
+        - api: MiniDumpWriteDump
+        - api: ReadProcessMemory
+"#,
+];
+
+#[derive(Debug, Clone)]
+enum StringPattern {
+    Exact(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+enum Feature {
+    And(Vec<Feature>),
+    Or(Vec<Feature>),
+    Not(Box<Feature>),
+    Optional,
+    AtLeast(usize, Vec<Feature>),
+    Api(String),
+    Import(String),
+    Export(String),
+    Section(String),
+    String(StringPattern),
+    Substring(String),
+    Match(String),
+    Characteristic(String),
+    Unsupported,
+}
+
+#[derive(Debug, Clone)]
+pub struct CapabilityRule {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub attack: Vec<String>,
+    features: Feature,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Capability {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub attack: Vec<String>,
+    pub tags: Vec<String>,
+    pub evidence: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct FileFeatures {
+    apis: HashSet<String>,
+    imports: HashSet<String>,
+    exports: HashSet<String>,
+    sections: HashSet<String>,
+    strings: Vec<String>,
+    embedded_pe: bool,
+}
+
+fn module_name(dll: &str) -> String {
+    let dll = dll.to_lowercase();
+    dll.strip_suffix(".dll").unwrap_or(&dll).to_string()
+}
+
+impl FileFeatures {
+    pub fn extract(pe: &PeInfo, data: &[u8]) -> Self {
+        let mut features = Self::default();
+        for import in &pe.imports {
+            let module = module_name(&import.dll);
+            features.imports.insert(module.clone());
+            for function in &import.functions {
+                let function = function.to_lowercase();
+                features.apis.insert(function.clone());
+                features.apis.insert(format!("{}.{}", module, function));
+            }
+        }
+        features.exports = pe.exports.iter().map(|e| e.to_lowercase()).collect();
+        features.sections = pe.sections.iter().map(|s| s.name.clone()).collect();
+        features.strings = extract_strings(data, MIN_STRING_LEN);
+        features.embedded_pe =
+            data.windows(2).enumerate().skip(1).any(|(offset, w)| {
+                w == b"MZ" && crate::static_analysis::pe::is_pe(&data[offset..])
+            });
+        features
+    }
+
+    fn has_api(&self, api: &str) -> bool {
+        let api = api.to_lowercase();
+        let (module, function) = match api.rsplit_once('.') {
+            Some((module, function)) => (Some(module_name(module)), function.to_string()),
+            None => (None, api),
+        };
+        let qualify = |name: &str| match &module {
+            Some(module) => format!("{}.{}", module, name),
+            None => name.to_string(),
+        };
+        ["", "a", "w"].iter().any(|suffix| {
+            self.apis
+                .contains(&qualify(&format!("{}{}", function, suffix)))
+        })
+    }
+
+    fn has_characteristic(&self, name: &str) -> bool {
+        match name {
+            "embedded pe" => self.embedded_pe,
+            "mixed mode" => self.imports.contains("mscoree"),
+            _ => false,
+        }
+    }
+}
+
+fn parse_string(value: &str) -> Result<StringPattern> {
+    let Some(rest) = value.strip_prefix('/') else {
+        return Ok(StringPattern::Exact(value.to_string()));
+    };
+    let (pattern, flags) = rest.rsplit_once('/').context("Unterminated string regex")?;
+    Ok(StringPattern::Regex(
+        RegexBuilder::new(pattern)
+            .case_insensitive(flags.contains('i'))
+            .build()?,
+    ))
+}
+
+fn scalar(value: &Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => bail!("Expected a scalar feature value"),
+    }
+}
+
+fn children(value: &Value) -> Result<Vec<Feature>> {
+    value
+        .as_sequence()
+        .context("Expected a list of features")?
+        .iter()
+        .map(parse_feature)
+        .collect()
+}
+
+fn parse_feature(value: &Value) -> Result<Feature> {
+    let map = value.as_mapping().context("Expected a feature mapping")?;
+    let (key, value) = map
+        .iter()
+        .find(|(k, _)| k.as_str() != Some("description"))
+        .context("Empty feature")?;
+    let key = key.as_str().context("Feature name is not a string")?;
+    Ok(match key {
+        "and" => Feature::And(children(value)?),
+        "or" => Feature::Or(children(value)?),
+        "not" => {
+            let mut features = children(value)?;
+            if features.len() != 1 {
+                bail!("not expects exactly one feature");
+            }
+            Feature::Not(Box::new(features.remove(0)))
+        }
+        "optional" => Feature::Optional,
+        "api" => Feature::Api(scalar(value)?),
+        "import" => Feature::Import(module_name(&scalar(value)?)),
+        "export" => Feature::Export(scalar(value)?.to_lowercase()),
+        "section" => Feature::Section(scalar(value)?),
+        "string" => Feature::String(parse_string(&scalar(value)?)?),
+        "substring" => Feature::Substring(scalar(value)?.to_lowercase()),
+        "match" => Feature::Match(scalar(value)?),
+        "characteristic" => Feature::Characteristic(scalar(value)?),
+        _ => match key.strip_suffix(" or more").map(str::parse::<usize>) {
+            Some(Ok(count)) => Feature::AtLeast(count, children(value)?),
+            _ => Feature::Unsupported,
+        },
+    })
+}
+
+fn attack_tags(entry: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let tactic = entry.split("::").next().unwrap_or_default().trim();
+    if let Some(tactic) = Tactic::ALL.iter().find(|t| t.name() == tactic) {
+        tags.push(tactic.tag());
+    }
+    if let Some(id) = entry
+        .rsplit_once('[')
+        .and_then(|(_, id)| id.strip_suffix(']'))
+    {
+        tags.push(format!("attack.{}", id.to_lowercase()));
+    }
+    tags
+}
+
+struct Matched<'a> {
+    names: &'a [(String, Option<String>)],
+}
+
+impl Matched<'_> {
+    fn contains(&self, target: &str) -> bool {
+        self.names.iter().any(|(name, namespace)| {
+            name == target
+                || namespace
+                    .as_deref()
+                    .is_some_and(|ns| ns == target || ns.starts_with(&format!("{}/", target)))
+        })
+    }
+}
+
+impl Feature {
+    fn evaluate(
+        &self,
+        features: &FileFeatures,
+        matched: &Matched,
+        evidence: &mut Vec<String>,
+    ) -> bool {
+        let found = |evidence: &mut Vec<String>, hit: bool, description: String| {
+            if hit {
+                evidence.push(description);
+            }
+            hit
+        };
+        match self {
+            Self::And(children) => {
+                let mut local = Vec::new();
+                let hit = children
+                    .iter()
+                    .all(|c| c.evaluate(features, matched, &mut local));
+                if hit {
+                    evidence.extend(local);
+                }
+                hit
+            }
+            Self::Or(children) => children
+                .iter()
+                .any(|c| c.evaluate(features, matched, evidence)),
+            Self::Not(child) => !child.evaluate(features, matched, &mut Vec::new()),
+            Self::Optional => true,
+            Self::AtLeast(count, children) => {
+                let mut local = Vec::new();
+                let hits = children
+                    .iter()
+                    .filter(|c| c.evaluate(features, matched, &mut local))
+                    .count();
+                if hits >= *count {
+                    evidence.extend(local);
+                }
+                hits >= *count
+            }
+            Self::Api(api) => found(evidence, features.has_api(api), format!("api: {}", api)),
+            Self::Import(module) => found(
+                evidence,
+                features.imports.contains(module),
+                format!("import: {}", module),
+            ),
+            Self::Export(name) => found(
+                evidence,
+                features.exports.contains(name),
+                format!("export: {}", name),
+            ),
+            Self::Section(name) => found(
+                evidence,
+                features.sections.contains(name),
+                format!("section: {}", name),
+            ),
+            Self::String(pattern) => {
+                let hit = features.strings.iter().find(|s| match pattern {
+                    StringPattern::Exact(exact) => s == &exact,
+                    StringPattern::Regex(regex) => regex.is_match(s),
+                });
+                match hit {
+                    Some(s) => found(evidence, true, format!("string: {}", s)),
+                    None => false,
+                }
+            }
+            Self::Substring(substring) => {
+                let hit = features
+                    .strings
+                    .iter()
+                    .find(|s| s.to_lowercase().contains(substring));
+                match hit {
+                    Some(s) => found(evidence, true, format!("substring: {}", s)),
+                    None => false,
+                }
+            }
+            Self::Match(target) => found(
+                evidence,
+                matched.contains(target),
+                format!("match: {}", target),
+            ),
+            Self::Characteristic(name) => found(
+                evidence,
+                features.has_characteristic(name),
+                format!("characteristic: {}", name),
+            ),
+            Self::Unsupported => false,
+        }
+    }
+}
+
+impl CapabilityRule {
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let document: Value = serde_yaml::from_str(yaml)?;
+        let rule = &document["rule"];
+        let meta = &rule["meta"];
+        let name = meta["name"]
+            .as_str()
+            .context("Capability rule has no name")?
+            .to_string();
+        let mut features =
+            children(&rule["features"]).with_context(|| format!("Invalid features in {}", name))?;
+        if features.len() != 1 {
+            bail!(
+                "Capability rule {} must have exactly one root feature",
+                name
+            );
+        }
+        Ok(Self {
+            name,
+            namespace: meta["namespace"].as_str().map(str::to_string),
+            attack: meta["att&ck"]
+                .as_sequence()
+                .into_iter()
+                .flatten()
+                .filter_map(|a| a.as_str().map(str::to_string))
+                .collect(),
+            features: features.remove(0),
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_yaml(&fs::read_to_string(path)?)
+            .with_context(|| format!("Failed to load capability rule {}", path.display()))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CapabilityRules {
+    rules: Vec<CapabilityRule>,
+}
+
+impl CapabilityRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut rules = Self::new();
+        for yaml in BUILTIN_RULES {
+            rules.add(CapabilityRule::from_yaml(yaml).expect("valid builtin capability rule"));
+        }
+        rules
+    }
+
+    pub fn add(&mut self, rule: CapabilityRule) {
+        self.rules.push(rule);
+    }
+
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.load_dir(&path)?;
+            } else if path.extension().is_some_and(|e| e == "yml" || e == "yaml") {
+                self.add(CapabilityRule::from_file(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn evaluate(&self, features: &FileFeatures) -> Vec<Capability> {
+        let mut names = Vec::new();
+        let mut capabilities = Vec::new();
+        let mut pending: Vec<&CapabilityRule> = self.rules.iter().collect();
+        loop {
+            let mut progress = false;
+            let mut remaining = Vec::new();
+            for rule in pending {
+                let mut evidence = Vec::new();
+                let matched = Matched { names: &names };
+                if rule.features.evaluate(features, &matched, &mut evidence) {
+                    progress = true;
+                    names.push((rule.name.clone(), rule.namespace.clone()));
+                    evidence.sort();
+                    evidence.dedup();
+                    capabilities.push(Capability {
+                        name: rule.name.clone(),
+                        namespace: rule.namespace.clone(),
+                        tags: rule.attack.iter().flat_map(|a| attack_tags(a)).collect(),
+                        attack: rule.attack.clone(),
+                        evidence,
+                    });
+                } else {
+                    remaining.push(rule);
+                }
+            }
+            pending = remaining;
+            if !progress || pending.is_empty() {
+                break;
+            }
+        }
+        capabilities
+    }
+}
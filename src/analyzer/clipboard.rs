@@ -0,0 +1,288 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub const CLIPBOARD_CONTENT_FIELD: &str = "clipboard_content";
+
+const CAPTURE_TOLERANCE_SECS: i64 = 5;
+
+const BENIGN_CLIPBOARD_IMAGES: &[&str] = &[
+    r"\explorer.exe",
+    r"\rdpclip.exe",
+    r"\ditto.exe",
+    r"\clipx.exe",
+    r"\textinputhost.exe",
+    "/xclip",
+    "/xsel",
+    "/copyq",
+    "/clipit",
+    "/parcellite",
+];
+
+const CRYPTO_ADDRESS_PATTERNS: &[(&str, &str)] = &[
+    (
+        "Bitcoin",
+        r"\b(?:bc1[a-z0-9]{25,62}|[13][a-km-zA-HJ-NP-Z1-9]{25,34})\b",
+    ),
+    ("Ethereum", r"\b0x[a-fA-F0-9]{40}\b"),
+    ("Monero", r"\b[48][0-9AB][1-9A-HJ-NP-Za-km-z]{93}\b"),
+];
+
+fn crypto_patterns() -> &'static [(&'static str, Regex)] {
+    static PATTERNS: OnceLock<Vec<(&str, Regex)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        CRYPTO_ADDRESS_PATTERNS
+            .iter()
+            .map(|(name, p)| (*name, Regex::new(p).unwrap()))
+            .collect()
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardCapture {
+    pub time: DateTime<Utc>,
+    pub content: String,
+}
+
+pub struct ClipboardOptions {
+    pub min_changes: usize,
+    pub window: Duration,
+    pub replace_window: Duration,
+    pub allowlist: Vec<String>,
+}
+
+impl Default for ClipboardOptions {
+    fn default() -> Self {
+        Self {
+            min_changes: 5,
+            window: Duration::seconds(60),
+            replace_window: Duration::seconds(10),
+            allowlist: BENIGN_CLIPBOARD_IMAGES
+                .iter()
+                .map(|i| i.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClipboardAlert {
+    pub process_guid: String,
+    pub image: String,
+    pub change_count: usize,
+    pub max_changes_in_window: usize,
+    pub crypto_addresses: Vec<String>,
+    pub events: Vec<SysmonEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClipperAlert {
+    pub process_guid: Option<String>,
+    pub image: Option<String>,
+    pub currency: String,
+    pub original: String,
+    pub replacement: String,
+    pub delay_ms: i64,
+    pub events: Vec<SysmonEvent>,
+}
+
+struct ClipboardState<'a> {
+    time: DateTime<Utc>,
+    content: &'a str,
+    event: Option<&'a SysmonEvent>,
+}
+
+impl ClipboardState<'_> {
+    fn field(&self, name: &str) -> Option<&String> {
+        self.event.and_then(|e| e.event_data.get(name))
+    }
+}
+
+fn is_allowlisted(image: &str, options: &ClipboardOptions) -> bool {
+    let image = image.to_lowercase();
+    options
+        .allowlist
+        .iter()
+        .any(|a| image.ends_with(&a.to_lowercase()))
+}
+
+fn nearest_capture(
+    captures: &[ClipboardCapture],
+    time: DateTime<Utc>,
+) -> Option<&ClipboardCapture> {
+    let tolerance = Duration::seconds(CAPTURE_TOLERANCE_SECS);
+    captures
+        .iter()
+        .filter(|c| c.time >= time - tolerance && c.time <= time + tolerance)
+        .min_by_key(|c| (c.time - time).num_milliseconds().abs())
+}
+
+pub fn attach_captures(events: &mut [SysmonEvent], captures: &[ClipboardCapture]) {
+    for event in events.iter_mut() {
+        if event.event_id != SysmonEventId::CLIPBOARD_CHANGE
+            || event.enriched(CLIPBOARD_CONTENT_FIELD).is_some()
+        {
+            continue;
+        }
+        if let Some(capture) = nearest_capture(captures, event.time_created.with_timezone(&Utc)) {
+            event.set_enriched(CLIPBOARD_CONTENT_FIELD, capture.content.clone());
+        }
+    }
+}
+
+fn addresses<'a>(re: &Regex, content: &'a str) -> Vec<&'a str> {
+    re.find_iter(content).map(|m| m.as_str()).collect()
+}
+
+pub fn detect_clippers(events: &[SysmonEvent], captures: &[ClipboardCapture]) -> Vec<ClipperAlert> {
+    detect_clippers_with(events, captures, &ClipboardOptions::default())
+}
+
+pub fn detect_clippers_with(
+    events: &[SysmonEvent],
+    captures: &[ClipboardCapture],
+    options: &ClipboardOptions,
+) -> Vec<ClipperAlert> {
+    let changes: Vec<&SysmonEvent> = events
+        .iter()
+        .filter(|e| e.event_id == SysmonEventId::CLIPBOARD_CHANGE)
+        .collect();
+    let mut states: Vec<ClipboardState> = changes
+        .iter()
+        .filter_map(|e| {
+            Some(ClipboardState {
+                time: e.time_created.with_timezone(&Utc),
+                content: e.enriched(CLIPBOARD_CONTENT_FIELD)?,
+                event: Some(*e),
+            })
+        })
+        .collect();
+    let attributed: Vec<&ClipboardCapture> = changes
+        .iter()
+        .filter_map(|e| nearest_capture(captures, e.time_created.with_timezone(&Utc)))
+        .collect();
+    states.extend(
+        captures
+            .iter()
+            .filter(|c| !attributed.contains(c))
+            .map(|c| ClipboardState {
+                time: c.time,
+                content: &c.content,
+                event: None,
+            }),
+    );
+    states.sort_by_key(|s| s.time);
+
+    let mut result: Vec<ClipperAlert> = Vec::new();
+    for (read, write) in states.iter().tuple_windows() {
+        let delay = write.time - read.time;
+        if delay > options.replace_window {
+            continue;
+        }
+        let image = write.field("Image");
+        if image.is_some_and(|i| is_allowlisted(i, options)) {
+            continue;
+        }
+        let process_guid = write.field("ProcessGuid");
+        if process_guid.is_some() && process_guid == read.field("ProcessGuid") {
+            continue;
+        }
+        for (currency, re) in crypto_patterns() {
+            let before = addresses(re, read.content);
+            let after = addresses(re, write.content);
+            let Some(original) = before.iter().find(|a| !after.contains(a)) else {
+                continue;
+            };
+            let Some(replacement) = after.iter().find(|a| !before.contains(a)) else {
+                continue;
+            };
+            let existing = result
+                .iter_mut()
+                .find(|a| a.process_guid.as_ref() == process_guid && a.replacement == *replacement);
+            match existing {
+                Some(alert) => alert.events.extend(write.event.cloned()),
+                None => result.push(ClipperAlert {
+                    process_guid: process_guid.cloned(),
+                    image: image.cloned(),
+                    currency: currency.to_string(),
+                    original: original.to_string(),
+                    replacement: replacement.to_string(),
+                    delay_ms: delay.num_milliseconds(),
+                    events: read.event.into_iter().chain(write.event).cloned().collect(),
+                }),
+            }
+        }
+    }
+    result
+}
+
+fn max_changes_in_window(events: &[&SysmonEvent], window: Duration) -> usize {
+    let mut max = 0;
+    let mut start = 0;
+    for end in 0..events.len() {
+        while events[end].time_created - events[start].time_created > window {
+            start += 1;
+        }
+        max = max.max(end - start + 1);
+    }
+    max
+}
+
+pub fn detect_clipboard_abuse(events: &[SysmonEvent]) -> Vec<ClipboardAlert> {
+    detect_clipboard_abuse_with(events, &ClipboardOptions::default())
+}
+
+pub fn detect_clipboard_abuse_with(
+    events: &[SysmonEvent],
+    options: &ClipboardOptions,
+) -> Vec<ClipboardAlert> {
+    let by_process = events
+        .iter()
+        .filter(|e| e.event_id == SysmonEventId::CLIPBOARD_CHANGE)
+        .into_group_map_by(|e| e.event_data.get("ProcessGuid").cloned().unwrap_or_default());
+
+    let mut result = Vec::new();
+    for (process_guid, mut changes) in by_process {
+        let image = changes[0]
+            .event_data
+            .get("Image")
+            .cloned()
+            .unwrap_or_default();
+        if is_allowlisted(&image, options) {
+            continue;
+        }
+
+        let mut crypto_addresses = Vec::new();
+        for change in &changes {
+            if let Some(content) = change.enriched(CLIPBOARD_CONTENT_FIELD) {
+                for (name, re) in crypto_patterns() {
+                    if re.is_match(content) && !crypto_addresses.contains(&name.to_string()) {
+                        crypto_addresses.push(name.to_string());
+                    }
+                }
+            }
+        }
+
+        changes.sort_by_key(|e| e.time_created);
+        let max_changes = max_changes_in_window(&changes, options.window);
+        if max_changes < options.min_changes && crypto_addresses.is_empty() {
+            continue;
+        }
+
+        result.push(ClipboardAlert {
+            process_guid,
+            image,
+            change_count: changes.len(),
+            max_changes_in_window: max_changes,
+            crypto_addresses,
+            events: changes.into_iter().cloned().collect(),
+        });
+    }
+
+    result
+}
@@ -0,0 +1,310 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use x509_parser::prelude::{CertificateRevocationList, FromDer};
+
+use crate::event_data::{ImageLoadData, TypedEventData};
+use crate::static_analysis::authenticode::CertificateChain;
+use crate::static_analysis::pe::{PeInfo, SignatureStatus};
+use crate::sysmon_event::SysmonEvent;
+
+const DEFAULT_TRUSTED_ROOTS: &[&str] = &[
+    "Microsoft Root Certificate Authority 2010",
+    "Microsoft Root Certificate Authority 2011",
+    "Microsoft Root Authority",
+    "DigiCert Assured ID Root CA",
+    "DigiCert High Assurance EV Root CA",
+    "DigiCert Trusted Root G4",
+    "VeriSign Class 3 Public Primary Certification Authority - G5",
+    "VeriSign Universal Root Certification Authority",
+    "USERTrust RSA Certification Authority",
+    "Sectigo Public Code Signing Root R46",
+    "GlobalSign",
+    "GlobalSign Root CA",
+    "Entrust Root Certification Authority - G2",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignedTarget {
+    Sample,
+    Driver,
+    Image,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationStatus {
+    Valid,
+    Unsigned,
+    Invalid,
+    Untrusted,
+    Revoked,
+    Blocked,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationStatus {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignatureValidation {
+    pub target: SignedTarget,
+    pub path: String,
+    pub signer: Option<String>,
+    pub status: ValidationStatus,
+    pub revocation: RevocationStatus,
+    pub reasons: Vec<String>,
+    pub events: Vec<SysmonEvent>,
+}
+
+#[derive(Debug, Clone)]
+struct RevocationList {
+    issuer: String,
+    serials: Vec<String>,
+}
+
+fn default_trusted_roots() -> Vec<String> {
+    DEFAULT_TRUSTED_ROOTS
+        .iter()
+        .map(|r| r.to_string())
+        .collect()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrustPolicy {
+    #[serde(default = "default_trusted_roots")]
+    pub trusted_roots: Vec<String>,
+    #[serde(default)]
+    pub blocked_signers: Vec<String>,
+    #[serde(default)]
+    pub blocked_thumbprints: Vec<String>,
+    #[serde(default)]
+    pub crl_dir: Option<String>,
+    #[serde(skip)]
+    crls: Vec<RevocationList>,
+}
+
+impl Default for TrustPolicy {
+    fn default() -> Self {
+        Self {
+            trusted_roots: default_trusted_roots(),
+            blocked_signers: Vec::new(),
+            blocked_thumbprints: Vec::new(),
+            crl_dir: None,
+            crls: Vec::new(),
+        }
+    }
+}
+
+fn matches_any(values: &[String], candidate: &str) -> bool {
+    values.iter().any(|v| v.eq_ignore_ascii_case(candidate))
+}
+
+impl TrustPolicy {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut policy: Self = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+        if let Some(dir) = policy.crl_dir.clone() {
+            policy.load_crls(dir)?;
+        }
+        Ok(policy)
+    }
+
+    pub fn load_crls<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let data = std::fs::read(&path)?;
+            let (_, crl) = CertificateRevocationList::from_der(&data)
+                .map_err(|e| anyhow::anyhow!("{}", e))
+                .with_context(|| format!("Invalid CRL {}", path.display()))?;
+            self.crls.push(RevocationList {
+                issuer: crl.issuer().to_string(),
+                serials: crl
+                    .iter_revoked_certificates()
+                    .map(|r| {
+                        r.raw_serial()
+                            .iter()
+                            .map(|b| format!("{:02x}", b))
+                            .collect()
+                    })
+                    .collect(),
+            });
+        }
+        Ok(())
+    }
+
+    fn is_blocked_signer(&self, signer: &str) -> bool {
+        matches_any(&self.blocked_signers, signer)
+    }
+
+    fn revocation(&self, chain: &CertificateChain) -> RevocationStatus {
+        let mut checked = false;
+        for certificate in &chain.certificates {
+            for crl in self.crls.iter().filter(|c| c.issuer == certificate.issuer) {
+                checked = true;
+                if crl.serials.contains(&certificate.serial) {
+                    return RevocationStatus::Revoked;
+                }
+            }
+        }
+        if checked {
+            RevocationStatus::Good
+        } else {
+            RevocationStatus::Unknown
+        }
+    }
+
+    fn is_trusted(&self, chain: &CertificateChain) -> bool {
+        let Some(root) = chain.root() else {
+            return false;
+        };
+        if matches_any(&self.trusted_roots, &root.thumbprint) {
+            return true;
+        }
+        if root.self_signed {
+            return root
+                .common_name
+                .as_deref()
+                .is_some_and(|cn| matches_any(&self.trusted_roots, cn));
+        }
+        self.trusted_roots.iter().any(|r| {
+            root.issuer
+                .to_lowercase()
+                .contains(&format!("cn={}", r.to_lowercase()))
+        })
+    }
+
+    pub fn validate_pe(&self, pe: &PeInfo, now: DateTime<Utc>) -> SignatureValidation {
+        let chain = &pe.certificates;
+        let signer = chain.signer().and_then(|c| c.common_name.clone());
+        let mut reasons = Vec::new();
+        let revocation = self.revocation(chain);
+
+        let status = if pe.signature == SignatureStatus::Unsigned {
+            ValidationStatus::Unsigned
+        } else if chain
+            .certificates
+            .iter()
+            .any(|c| matches_any(&self.blocked_thumbprints, &c.thumbprint))
+            || signer.as_deref().is_some_and(|s| self.is_blocked_signer(s))
+        {
+            reasons.push("certificate is blocked by the trust policy".to_string());
+            ValidationStatus::Blocked
+        } else if revocation == RevocationStatus::Revoked {
+            reasons.push("certificate is listed in a revocation list".to_string());
+            ValidationStatus::Revoked
+        } else if pe.signature == SignatureStatus::DigestMismatch {
+            reasons.push("Authenticode digest does not match the file".to_string());
+            ValidationStatus::Invalid
+        } else if chain.certificates.is_empty() {
+            reasons.push("signature contains no parsable certificate".to_string());
+            ValidationStatus::Invalid
+        } else if !chain.verified {
+            reasons.push("certificate chain signature does not verify".to_string());
+            ValidationStatus::Invalid
+        } else if chain.certificates.len() == 1 && chain.certificates[0].self_signed {
+            reasons.push("signing certificate is self-signed".to_string());
+            ValidationStatus::Untrusted
+        } else if !self.is_trusted(chain) {
+            reasons.push("certificate chain does not end in a trusted root".to_string());
+            ValidationStatus::Untrusted
+        } else {
+            ValidationStatus::Valid
+        };
+        if chain.signer().is_some_and(|c| !c.valid_at(now)) {
+            reasons.push("signing certificate is outside its validity period".to_string());
+        }
+        if chain.signer().is_some_and(|c| !c.code_signing) {
+            reasons.push("signing certificate lacks the code signing usage".to_string());
+        }
+        if status != ValidationStatus::Unsigned && revocation == RevocationStatus::Unknown {
+            let urls: Vec<&str> = chain
+                .certificates
+                .iter()
+                .flat_map(|c| c.crl_urls.iter().map(String::as_str))
+                .collect();
+            if !urls.is_empty() {
+                reasons.push(format!("revocation not checked ({})", urls.join(", ")));
+            }
+        }
+
+        SignatureValidation {
+            target: SignedTarget::Sample,
+            path: "sample".to_string(),
+            signer,
+            status,
+            revocation,
+            reasons,
+            events: Vec::new(),
+        }
+    }
+
+    fn validate_load(&self, target: SignedTarget, data: &ImageLoadData) -> SignatureValidation {
+        let signer = data.signature.clone().filter(|s| !s.is_empty());
+        let status_text = data.signature_status.as_deref().unwrap_or_default();
+        let mut reasons = Vec::new();
+        let (status, revocation) = if signer.as_deref().is_some_and(|s| self.is_blocked_signer(s)) {
+            reasons.push("signer is blocked by the trust policy".to_string());
+            (ValidationStatus::Blocked, RevocationStatus::Unknown)
+        } else if status_text.eq_ignore_ascii_case("revoked") {
+            reasons.push("signature status reported as revoked".to_string());
+            (ValidationStatus::Revoked, RevocationStatus::Revoked)
+        } else if data.signed == Some(false) {
+            (ValidationStatus::Unsigned, RevocationStatus::Unknown)
+        } else if status_text.eq_ignore_ascii_case("valid") {
+            (ValidationStatus::Valid, RevocationStatus::Unknown)
+        } else {
+            reasons.push(format!("signature status reported as {}", status_text));
+            (ValidationStatus::Invalid, RevocationStatus::Unknown)
+        };
+        SignatureValidation {
+            target,
+            path: data.image_loaded.clone(),
+            signer,
+            status,
+            revocation,
+            reasons,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn validate_loads(&self, events: &[SysmonEvent]) -> Vec<SignatureValidation> {
+        let mut validations: Vec<SignatureValidation> = Vec::new();
+        for event in events {
+            let validation = match event.typed_data() {
+                TypedEventData::DriverLoad(data) => self.validate_load(SignedTarget::Driver, &data),
+                TypedEventData::ImageLoad(data) => self.validate_load(SignedTarget::Image, &data),
+                _ => continue,
+            };
+            let flagged = match validation.target {
+                SignedTarget::Driver => validation.status != ValidationStatus::Valid,
+                _ => !matches!(
+                    validation.status,
+                    ValidationStatus::Valid | ValidationStatus::Unsigned
+                ),
+            };
+            if !flagged {
+                continue;
+            }
+            match validations.iter_mut().find(|v| {
+                v.target == validation.target && v.path.eq_ignore_ascii_case(&validation.path)
+            }) {
+                Some(existing) => existing.events.push(event.clone()),
+                None => validations.push(SignatureValidation {
+                    events: vec![event.clone()],
+                    ..validation
+                }),
+            }
+        }
+        validations
+    }
+}
@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cmdline::{normalized_args, program_name};
+use crate::flags::AccessMask;
+use crate::memory::MemoryAnalysis;
+use crate::path::normalize;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const PATH_FIELDS: &[&str] = &["TargetFilename", "CommandLine", "TargetImage"];
+
+const LSASS_READERS: &[&str] = &[
+    r"\windows\system32\wininit.exe",
+    r"\windows\system32\csrss.exe",
+    r"\windows\system32\lsm.exe",
+    r"\windows\system32\wbem\wmiprvse.exe",
+    r"\windows\system32\svchost.exe",
+    r"\windows\system32	askmgr.exe",
+    r"\programdata\microsoft\windows defender\platform",
+    r"\program files\windows defender\msmpeng.exe",
+];
+
+const DUMP_MODULES: &[&str] = &["dbghelp.dll", "dbgcore.dll", "comsvcs.dll"];
+
+const MEMORY_PATTERNS: &[(&str, CredentialTheftKind)] = &[
+    ("sekurlsa::", CredentialTheftKind::LsassMemory),
+    ("lsadump::sam", CredentialTheftKind::SamHive),
+    ("lsadump::secrets", CredentialTheftKind::LsaSecrets),
+    ("lsadump::cache", CredentialTheftKind::CachedCredentials),
+    ("lsadump::dcsync", CredentialTheftKind::DcSync),
+    ("dpapi::", CredentialTheftKind::Dpapi),
+    ("cryptunprotectdata", CredentialTheftKind::Dpapi),
+    (r"\microsoft\protect\s-1-5-", CredentialTheftKind::Dpapi),
+];
+
+#[derive(Debug, Clone)]
+pub struct CredentialStore {
+    pub browser: String,
+    pub fragments: Vec<String>,
+    pub owner_images: Vec<String>,
+}
+
+impl CredentialStore {
+    pub fn new(browser: &str, fragments: &[&str], owner_images: &[&str]) -> Self {
+        Self {
+            browser: browser.to_string(),
+            fragments: fragments.iter().map(|f| normalize(f)).collect(),
+            owner_images: owner_images.iter().map(|i| normalize(i)).collect(),
+        }
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        let chrome = &[r"\chrome.exe", "/chrome", "/google-chrome"];
+        let edge = &[r"\msedge.exe", "/msedge", "/microsoft-edge"];
+        let firefox = &[r"\firefox.exe", "/firefox", "/firefox-bin"];
+
+        let mut stores = Vec::new();
+        for file in ["login data", "cookies", "local state", "web data"] {
+            stores.push(Self::new(
+                "Chrome",
+                &[r"\google\chrome\user data\", &format!(r"\{}", file)],
+                chrome,
+            ));
+            stores.push(Self::new(
+                "Edge",
+                &[r"\microsoft\edge\user data\", &format!(r"\{}", file)],
+                edge,
+            ));
+            stores.push(Self::new(
+                "Chrome",
+                &["/.config/google-chrome/", &format!("/{}", file)],
+                chrome,
+            ));
+            stores.push(Self::new(
+                "Chromium",
+                &["/.config/chromium/", &format!("/{}", file)],
+                &["/chromium", "/chromium-browser"],
+            ));
+            stores.push(Self::new(
+                "Edge",
+                &["/.config/microsoft-edge/", &format!("/{}", file)],
+                edge,
+            ));
+        }
+        for file in ["logins.json", "key4.db", "cookies.sqlite"] {
+            stores.push(Self::new(
+                "Firefox",
+                &[r"\mozilla\firefox\profiles\", &format!(r"\{}", file)],
+                firefox,
+            ));
+            stores.push(Self::new(
+                "Firefox",
+                &["/.mozilla/firefox/", &format!("/{}", file)],
+                firefox,
+            ));
+        }
+
+        stores
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        let mut rest = value;
+        for fragment in &self.fragments {
+            match rest.find(fragment.as_str()) {
+                Some(i) => rest = &rest[i + fragment.len()..],
+                None => return false,
+            }
+        }
+        true
+    }
+
+    fn is_owner(&self, image: &str) -> bool {
+        self.owner_images
+            .iter()
+            .any(|i| image.ends_with(i.as_str()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BrowserCredAccess {
+    pub browser: String,
+    pub process: String,
+    pub path: String,
+    pub event: SysmonEvent,
+}
+
+pub fn detect_browser_cred_access(events: &[SysmonEvent]) -> Vec<BrowserCredAccess> {
+    detect_browser_cred_access_with(events, &CredentialStore::defaults())
+}
+
+pub fn detect_browser_cred_access_with(
+    events: &[SysmonEvent],
+    stores: &[CredentialStore],
+) -> Vec<BrowserCredAccess> {
+    let mut result = Vec::new();
+    for event in events {
+        let checked = event.event_id == SysmonEventId::PROCESS_CREATE
+            || event.event_id == SysmonEventId::PROCESS_ACCESS
+            || event.event_id == SysmonEventId::FILE_CREATE
+            || event.event_id == SysmonEventId::FILE_CREATE_TIME
+            || event.event_id == SysmonEventId::FILE_CREATE_STREAM_HASH
+            || event.event_id == SysmonEventId::FILE_DELETE
+            || event.event_id == SysmonEventId::FILE_DELETE_DETECTED;
+        if !checked {
+            continue;
+        }
+
+        let process = event
+            .event_data
+            .get("Image")
+            .or_else(|| event.event_data.get("SourceImage"))
+            .cloned()
+            .unwrap_or_default();
+        let normalized_process = normalize(&process);
+
+        'fields: for field in PATH_FIELDS {
+            let Some(value) = event.event_data.get(*field) else {
+                continue;
+            };
+            let normalized_value = normalize(value);
+            for store in stores {
+                if store.is_match(&normalized_value) && !store.is_owner(&normalized_process) {
+                    result.push(BrowserCredAccess {
+                        browser: store.browser.clone(),
+                        process: process.clone(),
+                        path: value.clone(),
+                        event: event.clone(),
+                    });
+                    break 'fields;
+                }
+            }
+        }
+    }
+
+    result
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialTheftKind {
+    LsassMemory,
+    SamHive,
+    Ntds,
+    LsaSecrets,
+    CachedCredentials,
+    DcSync,
+    Dpapi,
+}
+
+impl CredentialTheftKind {
+    pub fn technique(&self) -> &'static str {
+        match self {
+            Self::LsassMemory => "T1003.001",
+            Self::SamHive => "T1003.002",
+            Self::Ntds => "T1003.003",
+            Self::LsaSecrets => "T1003.004",
+            Self::CachedCredentials => "T1003.005",
+            Self::DcSync => "T1003.006",
+            Self::Dpapi => "T1003",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CredentialTheft {
+    pub kind: CredentialTheftKind,
+    pub process: String,
+    pub description: String,
+    pub event: Option<SysmonEvent>,
+    pub dump: Option<String>,
+}
+
+fn granted_access(event: &SysmonEvent) -> Option<AccessMask> {
+    AccessMask::parse(event.event_data.get("GrantedAccess")?)
+}
+
+fn lsass_access(event: &SysmonEvent) -> Option<CredentialTheft> {
+    let target = normalize(event.event_data.get("TargetImage")?);
+    if !target.ends_with(r"\lsass.exe") {
+        return None;
+    }
+    let source = event
+        .event_data
+        .get("SourceImage")
+        .cloned()
+        .unwrap_or_default();
+    let normalized_source = normalize(&source);
+    if LSASS_READERS.iter().any(|r| normalized_source.contains(r)) {
+        return None;
+    }
+    let access = granted_access(event)?;
+    if !access.contains(AccessMask::PROCESS_VM_READ) {
+        return None;
+    }
+    let call_trace = event
+        .event_data
+        .get("CallTrace")
+        .map(|t| t.to_lowercase())
+        .unwrap_or_default();
+    let via = DUMP_MODULES
+        .iter()
+        .find(|m| call_trace.contains(*m))
+        .map(|m| format!(" via {}", m))
+        .or_else(|| {
+            call_trace
+                .contains("unknown")
+                .then(|| " from unbacked code".to_string())
+        })
+        .unwrap_or_default();
+    let full = if access.contains(AccessMask::PROCESS_ALL_ACCESS) {
+        " with full access"
+    } else {
+        ""
+    };
+    Some(CredentialTheft {
+        kind: CredentialTheftKind::LsassMemory,
+        process: source,
+        description: format!("LSASS memory read ({}){}{}", access, full, via),
+        event: Some(event.clone()),
+        dump: None,
+    })
+}
+
+fn raw_access(event: &SysmonEvent) -> Option<CredentialTheft> {
+    let image = event.event_data.get("Image").cloned().unwrap_or_default();
+    let normalized = normalize(&image);
+    if normalized.ends_with(r"\system32\svchost.exe") || normalized == "system" {
+        return None;
+    }
+    let device = event.event_data.get("Device").cloned().unwrap_or_default();
+    Some(CredentialTheft {
+        kind: CredentialTheftKind::SamHive,
+        process: image,
+        description: format!(
+            "Raw read of {} bypassing locks on the SAM/SECURITY hives",
+            device
+        ),
+        event: Some(event.clone()),
+        dump: None,
+    })
+}
+
+fn hive_export(event: &SysmonEvent) -> Option<CredentialTheft> {
+    let command_line = event.event_data.get("CommandLine")?;
+    let args = normalized_args(command_line);
+    let program = program_name(args.first()?);
+    let has = |arg: &str| args.iter().any(|a| a == arg);
+    let lower = command_line.to_lowercase();
+    let kind = match program.as_str() {
+        "reg" if has("save") || has("export") => {
+            if lower.contains(r"\sam") {
+                CredentialTheftKind::SamHive
+            } else if lower.contains(r"\security") {
+                CredentialTheftKind::LsaSecrets
+            } else {
+                return None;
+            }
+        }
+        "ntdsutil" if lower.contains("ifm") => CredentialTheftKind::Ntds,
+        "esentutl" | "vssadmin" | "cmd" | "powershell" | "pwsh" if lower.contains("ntds.dit") => {
+            CredentialTheftKind::Ntds
+        }
+        "rundll32" if lower.contains("comsvcs") && lower.contains("minidump") => {
+            CredentialTheftKind::LsassMemory
+        }
+        "procdump" | "procdump64" if lower.contains("lsass") => CredentialTheftKind::LsassMemory,
+        _ => return None,
+    };
+    Some(CredentialTheft {
+        kind,
+        process: event.event_data.get("Image").cloned().unwrap_or_default(),
+        description: format!("Credential store exported: {}", command_line),
+        event: Some(event.clone()),
+        dump: None,
+    })
+}
+
+pub fn detect_credential_theft(
+    events: &[SysmonEvent],
+    memory: &[MemoryAnalysis],
+) -> Vec<CredentialTheft> {
+    let mut thefts = Vec::new();
+    for event in events {
+        let theft = match event.event_id {
+            SysmonEventId::PROCESS_ACCESS => lsass_access(event),
+            SysmonEventId::RAW_ACCESS_READ => raw_access(event),
+            SysmonEventId::PROCESS_CREATE => hive_export(event),
+            _ => None,
+        };
+        thefts.extend(theft);
+    }
+
+    for analysis in memory {
+        let mut seen = Vec::new();
+        for string in &analysis.strings {
+            let lower = string.to_lowercase();
+            for (pattern, kind) in MEMORY_PATTERNS {
+                if !lower.contains(pattern) || seen.contains(kind) {
+                    continue;
+                }
+                seen.push(*kind);
+                thefts.push(CredentialTheft {
+                    kind: *kind,
+                    process: analysis
+                        .process_id
+                        .map(|p| p.to_string())
+                        .unwrap_or_default(),
+                    description: format!("Credential access string in memory: {}", string),
+                    event: None,
+                    dump: Some(analysis.dump.clone()),
+                });
+            }
+        }
+    }
+
+    thefts
+}
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsHeuristic {
+    HighEntropyLabel,
+    LongLabel,
+    HighSubdomainVolume,
+    ManyFailedQueries,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsAnomaly {
+    pub query_name: String,
+    pub heuristic: DnsHeuristic,
+    pub event: SysmonEvent,
+}
+
+pub struct DnsAnomalyOptions {
+    pub min_entropy_label_len: usize,
+    pub max_normalized_entropy: f64,
+    pub max_label_len: usize,
+    pub max_subdomains: usize,
+    pub max_failed_queries: usize,
+}
+
+impl Default for DnsAnomalyOptions {
+    fn default() -> Self {
+        Self {
+            min_entropy_label_len: 12,
+            max_normalized_entropy: 0.9,
+            max_label_len: 40,
+            max_subdomains: 30,
+            max_failed_queries: 10,
+        }
+    }
+}
+
+pub fn shannon_entropy(s: &str) -> f64 {
+    let mut counts = HashMap::new();
+    for c in s.chars() {
+        *counts.entry(c).or_insert(0usize) += 1;
+    }
+    let len = s.chars().count() as f64;
+    counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+pub fn normalized_entropy(s: &str) -> f64 {
+    let len = s.chars().count();
+    if len < 2 {
+        return 0.0;
+    }
+    shannon_entropy(s) / (len as f64).log2()
+}
+
+pub fn parent_domain(name: &str) -> String {
+    let labels: Vec<&str> = name.trim_end_matches('.').split('.').collect();
+    let start = labels.len().saturating_sub(2);
+    labels[start..].join(".").to_lowercase()
+}
+
+pub fn detect_dns_anomalies(events: &[SysmonEvent]) -> Vec<DnsAnomaly> {
+    detect_dns_anomalies_with(events, &DnsAnomalyOptions::default())
+}
+
+pub fn detect_dns_anomalies_with(
+    events: &[SysmonEvent],
+    options: &DnsAnomalyOptions,
+) -> Vec<DnsAnomaly> {
+    let mut result = Vec::new();
+    let mut subdomains: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut failed = Vec::new();
+    let mut reported_parents = HashSet::new();
+
+    for event in events {
+        if event.event_id != SysmonEventId::DNS_QUERY {
+            continue;
+        }
+        let Some(query_name) = event.event_data.get("QueryName") else {
+            continue;
+        };
+        let anomaly = |heuristic| DnsAnomaly {
+            query_name: query_name.clone(),
+            heuristic,
+            event: event.clone(),
+        };
+
+        let labels: Vec<&str> = query_name.trim_end_matches('.').split('.').collect();
+        let subdomain_labels = &labels[..labels.len().saturating_sub(2)];
+        if subdomain_labels
+            .iter()
+            .any(|l| l.len() > options.max_label_len)
+        {
+            result.push(anomaly(DnsHeuristic::LongLabel));
+        } else if subdomain_labels
+            .iter()
+            .chain(labels.iter().rev().nth(1))
+            .any(|l| {
+                l.len() >= options.min_entropy_label_len
+                    && normalized_entropy(l) > options.max_normalized_entropy
+            })
+        {
+            result.push(anomaly(DnsHeuristic::HighEntropyLabel));
+        }
+
+        let parent = parent_domain(query_name);
+        let names = subdomains.entry(parent.clone()).or_default();
+        names.insert(query_name.to_lowercase());
+        if names.len() > options.max_subdomains && reported_parents.insert(parent.clone()) {
+            result.push(anomaly(DnsHeuristic::HighSubdomainVolume));
+        }
+
+        let status = event.event_data.get("QueryStatus").map(String::as_str);
+        if status.is_some_and(|s| s != "0") {
+            failed.push(event);
+        }
+    }
+
+    if failed.len() > options.max_failed_queries {
+        for event in failed {
+            result.push(DnsAnomaly {
+                query_name: event
+                    .event_data
+                    .get("QueryName")
+                    .cloned()
+                    .unwrap_or_default(),
+                heuristic: DnsHeuristic::ManyFailedQueries,
+                event: event.clone(),
+            });
+        }
+    }
+
+    result
+}
@@ -0,0 +1,266 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::analyzer::persistence::detect_service_install;
+use crate::event_data::TypedEventData;
+use crate::hashes::HashAlgorithm;
+use crate::path::normalize;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const VULNERABLE_DRIVERS: &[(&str, &str, Option<&str>, &[&str])] = &[
+    (
+        "rtcore64.sys",
+        "MSI Afterburner RTCore64",
+        Some("CVE-2019-16098"),
+        &["01aa278b07b58dc46c84bd0b1b5c8e9ee4e62ea0bf7a695862444af32e87f1fd"],
+    ),
+    (
+        "gdrv.sys",
+        "Gigabyte gdrv",
+        Some("CVE-2018-19320"),
+        &["31f4cfb4c71da44120752721103a16512444c13c2ac2d857a7e6f13cb679b427"],
+    ),
+    (
+        "dbutil_2_3.sys",
+        "Dell DBUtil",
+        Some("CVE-2021-21551"),
+        &["0296e2ce999e67c76352613a718e11516fe1b0efc3ffdb8918fc999dd76a73a5"],
+    ),
+    (
+        "capcom.sys",
+        "Capcom anti-cheat",
+        None,
+        &["da6ca1fb539f825ca0f012ed6976baf57ef9c70143b7a1e88b4650bf7a925e24"],
+    ),
+    (
+        "iqvw64e.sys",
+        "Intel Network Adapter Diagnostic",
+        Some("CVE-2015-2291"),
+        &[],
+    ),
+    (
+        "mhyprot2.sys",
+        "Genshin Impact anti-cheat",
+        Some("CVE-2020-36603"),
+        &[],
+    ),
+    ("asrdrv106.sys", "ASRock RGB", Some("CVE-2020-15368"), &[]),
+    ("winring0x64.sys", "WinRing0", Some("CVE-2020-14979"), &[]),
+    (
+        "aswarpot.sys",
+        "Avast anti-rootkit",
+        Some("CVE-2022-26522"),
+        &[],
+    ),
+    (
+        "zamguard64.sys",
+        "Zemana AntiMalware",
+        Some("CVE-2021-31728"),
+        &[],
+    ),
+    (
+        "zam64.sys",
+        "Zemana AntiMalware",
+        Some("CVE-2021-31728"),
+        &[],
+    ),
+    ("procexp152.sys", "Process Explorer", None, &[]),
+    ("kprocesshacker.sys", "Process Hacker", None, &[]),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VulnerableDriver {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub cve: Option<String>,
+    #[serde(default)]
+    pub hashes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DriverBlocklist {
+    entries: Vec<VulnerableDriver>,
+}
+
+impl Default for DriverBlocklist {
+    fn default() -> Self {
+        Self::new(
+            VULNERABLE_DRIVERS
+                .iter()
+                .map(|(name, description, cve, hashes)| VulnerableDriver {
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    cve: cve.map(str::to_string),
+                    hashes: hashes.iter().map(|h| h.to_string()).collect(),
+                })
+                .collect(),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedBy {
+    Hash,
+    Name,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VulnerableMatch {
+    pub driver: VulnerableDriver,
+    pub matched_by: MatchedBy,
+}
+
+impl DriverBlocklist {
+    pub fn new(entries: Vec<VulnerableDriver>) -> Self {
+        Self { entries }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let entries: Vec<VulnerableDriver> = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+        let mut blocklist = Self::default();
+        blocklist.entries.extend(entries);
+        Ok(blocklist)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn lookup(&self, path: &str, hashes: &[&str]) -> Option<VulnerableMatch> {
+        let by_hash = self.entries.iter().find(|e| {
+            e.hashes
+                .iter()
+                .any(|h| hashes.iter().any(|other| h.eq_ignore_ascii_case(other)))
+        });
+        if let Some(driver) = by_hash {
+            return Some(VulnerableMatch {
+                driver: driver.clone(),
+                matched_by: MatchedBy::Hash,
+            });
+        }
+        let name = file_name(path);
+        self.entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(&name))
+            .map(|driver| VulnerableMatch {
+                driver: driver.clone(),
+                matched_by: MatchedBy::Name,
+            })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallMethod {
+    Service,
+    Dropped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DriverInstaller {
+    pub method: InstallMethod,
+    pub process_guid: Option<String>,
+    pub image: Option<String>,
+    pub event: SysmonEvent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DriverLoad {
+    pub path: String,
+    pub sha256: Option<String>,
+    pub signer: Option<String>,
+    pub signed: Option<bool>,
+    pub signature_status: Option<String>,
+    pub vulnerable: Option<VulnerableMatch>,
+    pub installer: Option<DriverInstaller>,
+    pub event: SysmonEvent,
+}
+
+impl DriverLoad {
+    pub fn unsigned(&self) -> bool {
+        self.signed == Some(false)
+    }
+
+    pub fn revoked(&self) -> bool {
+        self.signature_status
+            .as_deref()
+            .is_some_and(|s| s.eq_ignore_ascii_case("revoked"))
+    }
+}
+
+fn file_name(path: &str) -> String {
+    let path = normalize(path);
+    path.rsplit('\\').next().unwrap_or(&path).to_string()
+}
+
+fn installer(events: &[SysmonEvent], path: &str) -> Option<DriverInstaller> {
+    let name = file_name(path);
+    let service = detect_service_install(events).into_iter().find_map(|s| {
+        s.binary_path.as_deref().filter(|p| file_name(p) == name)?;
+        s.process_event.or(s.registry_event)
+    });
+    if let Some(event) = service {
+        return Some(DriverInstaller {
+            method: InstallMethod::Service,
+            process_guid: event.event_data.get("ProcessGuid").cloned(),
+            image: event.event_data.get("Image").cloned(),
+            event,
+        });
+    }
+    let target = normalize(path);
+    events
+        .iter()
+        .filter(|e| e.event_id == SysmonEventId::FILE_CREATE)
+        .find(|e| {
+            e.event_data
+                .get("TargetFilename")
+                .is_some_and(|f| normalize(f) == target)
+        })
+        .map(|event| DriverInstaller {
+            method: InstallMethod::Dropped,
+            process_guid: event.event_data.get("ProcessGuid").cloned(),
+            image: event.event_data.get("Image").cloned(),
+            event: event.clone(),
+        })
+}
+
+pub fn analyze_driver_loads(
+    events: &[SysmonEvent],
+    blocklist: &DriverBlocklist,
+) -> Vec<DriverLoad> {
+    let mut loads: Vec<DriverLoad> = Vec::new();
+    for event in events {
+        let TypedEventData::DriverLoad(data) = event.typed_data() else {
+            continue;
+        };
+        if loads
+            .iter()
+            .any(|l| normalize(&l.path) == normalize(&data.image_loaded))
+        {
+            continue;
+        }
+        let hashes: Vec<&str> = data.hashes.file_hashes().map(|(_, h)| h).collect();
+        loads.push(DriverLoad {
+            sha256: data
+                .hashes
+                .get(HashAlgorithm::Sha256)
+                .map(str::to_lowercase),
+            signer: data.signature.clone().filter(|s| !s.is_empty()),
+            signed: data.signed,
+            signature_status: data.signature_status.clone(),
+            vulnerable: blocklist.lookup(&data.image_loaded, &hashes),
+            installer: installer(events, &data.image_loaded),
+            path: data.image_loaded.clone(),
+            event: event.clone(),
+        });
+    }
+    loads
+}
@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::network::NetworkConnect;
+use crate::pcap::{CorrelatedFlow, Flow};
+use crate::sysmon_event::SysmonEvent;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FingerprintKind {
+    Ja3,
+    Ja3s,
+    Hassh,
+    HasshServer,
+}
+
+impl FingerprintKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ja3 => "ja3",
+            Self::Ja3s => "ja3s",
+            Self::Hassh => "hassh",
+            Self::HasshServer => "hassh_server",
+        }
+    }
+
+    fn of<'a>(&self, flow: &'a Flow) -> Option<&'a str> {
+        match self {
+            Self::Ja3 => flow.ja3.as_deref(),
+            Self::Ja3s => flow.ja3s.as_deref(),
+            Self::Hassh => flow.hassh.as_deref(),
+            Self::HasshServer => flow.hassh_server.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockedFingerprint {
+    pub kind: FingerprintKind,
+    pub hash: String,
+    pub name: String,
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintBlocklist {
+    entries: Vec<BlockedFingerprint>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FingerprintMatch<'a> {
+    pub entry: &'a BlockedFingerprint,
+    pub flow: &'a CorrelatedFlow,
+}
+
+impl FingerprintBlocklist {
+    pub fn new(entries: Vec<BlockedFingerprint>) -> Self {
+        Self { entries }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let entries: Vec<BlockedFingerprint> =
+            serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(Self::new(entries))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn evaluate<'a>(&'a self, traffic: &'a [CorrelatedFlow]) -> Vec<FingerprintMatch<'a>> {
+        let mut matches = Vec::new();
+        for flow in traffic {
+            for entry in &self.entries {
+                if entry
+                    .kind
+                    .of(&flow.flow)
+                    .is_some_and(|hash| hash.eq_ignore_ascii_case(&entry.hash))
+                {
+                    matches.push(FingerprintMatch { entry, flow });
+                }
+            }
+        }
+        matches
+    }
+}
+
+pub fn connect_events<'a>(flow: &Flow, events: &'a [SysmonEvent]) -> Vec<&'a SysmonEvent> {
+    events
+        .iter()
+        .filter(|e| {
+            NetworkConnect::from_event(e).is_some_and(|c| {
+                c.source_ip == Some(flow.client.ip())
+                    && c.source_port == Some(flow.client.port())
+                    && c.destination_ip == Some(flow.server.ip())
+                    && c.destination_port == Some(flow.server.port())
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,151 @@
+use crate::cmdline::{program_name, tokenize};
+use crate::path::normalize;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const OFFICE_AND_MAIL: &[&str] = &[
+    "winword",
+    "excel",
+    "powerpnt",
+    "outlook",
+    "msaccess",
+    "mspub",
+    "onenote",
+    "thunderbird",
+    "libreoffice",
+    "soffice",
+    "evolution",
+];
+
+const BROWSERS: &[&str] = &[
+    "chrome",
+    "msedge",
+    "firefox",
+    "iexplore",
+    "opera",
+    "brave",
+    "chromium",
+    "google-chrome",
+    "firefox-bin",
+];
+
+const SCRIPT_HOSTS: &[&str] = &[
+    "cmd",
+    "powershell",
+    "pwsh",
+    "wscript",
+    "cscript",
+    "mshta",
+    "rundll32",
+    "regsvr32",
+    "sh",
+    "bash",
+    "dash",
+    "zsh",
+    "python",
+    "python3",
+    "perl",
+];
+
+const DOWNLOAD_CRADLES: &[&str] = &[
+    "downloadstring",
+    "downloadfile",
+    "invoke-webrequest",
+    "iwr ",
+    "invoke-restmethod",
+    "start-bitstransfer",
+    "-urlcache",
+    "bitsadmin",
+    "wget ",
+    "curl ",
+];
+
+const USER_WRITABLE_DIRS: &[&str] = &[
+    r"\appdata\local\temp\",
+    r"\downloads\",
+    r"\windows\temp\",
+    r"\tmp\",
+    r"\var\tmp\",
+    r"\dev\shm\",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitialAccessKind {
+    PhishingMacro,
+    DriveByDownload,
+    ManualExecution,
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct InitialAccess {
+    pub kind: InitialAccessKind,
+    pub confidence: f32,
+    pub event: SysmonEvent,
+}
+
+fn program(event: &SysmonEvent, field: &str) -> String {
+    event
+        .event_data
+        .get(field)
+        .map(|i| program_name(i))
+        .unwrap_or_default()
+}
+
+fn classify(event: &SysmonEvent) -> Option<(InitialAccessKind, f32)> {
+    let image = program(event, "Image");
+    let parent = program(event, "ParentImage");
+    let command_line = event
+        .event_data
+        .get("CommandLine")
+        .map(|c| c.to_lowercase())
+        .unwrap_or_default();
+
+    if SCRIPT_HOSTS.contains(&image.as_str()) {
+        if OFFICE_AND_MAIL.contains(&parent.as_str()) {
+            return Some((InitialAccessKind::PhishingMacro, 0.8));
+        }
+        if BROWSERS.contains(&parent.as_str()) {
+            return Some((InitialAccessKind::DriveByDownload, 0.7));
+        }
+    }
+
+    let args = tokenize(&command_line).join(" ") + " ";
+    if DOWNLOAD_CRADLES.iter().any(|c| args.contains(c)) {
+        return Some((InitialAccessKind::DriveByDownload, 0.5));
+    }
+
+    let image_path = event
+        .event_data
+        .get("Image")
+        .map(|i| normalize(i))
+        .unwrap_or_default();
+    if USER_WRITABLE_DIRS.iter().any(|d| image_path.contains(d)) {
+        return Some((InitialAccessKind::ManualExecution, 0.4));
+    }
+
+    None
+}
+
+pub fn infer_initial_access(events: &[SysmonEvent]) -> Option<InitialAccess> {
+    let mut processes: Vec<&SysmonEvent> = events
+        .iter()
+        .filter(|e| e.event_id == SysmonEventId::PROCESS_CREATE)
+        .collect();
+    processes.sort_by_key(|e| e.time_created);
+
+    for event in &processes {
+        if let Some((kind, confidence)) = classify(event) {
+            return Some(InitialAccess {
+                kind,
+                confidence,
+                event: (*event).clone(),
+            });
+        }
+    }
+
+    processes.first().map(|e| InitialAccess {
+        kind: InitialAccessKind::Unknown,
+        confidence: 0.1,
+        event: (*e).clone(),
+    })
+}
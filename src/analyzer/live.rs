@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::analyzer::ransomware::{detect_ransomware, RansomwareOptions};
+use crate::analyzer::sigma::SigmaRule;
+use crate::analyzer::signature::SignatureRegistry;
+use crate::report::{ransomware_detection, Detection};
+use crate::sysmon_event::SysmonEvent;
+use crate::verdict::level_rank;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveAction {
+    Extend,
+    Terminate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LivePolicy {
+    #[serde(default = "default_min_level")]
+    pub min_level: String,
+    #[serde(default)]
+    pub action: Option<LiveAction>,
+    #[serde(default = "default_extend_secs")]
+    pub extend_secs: u64,
+    #[serde(default = "default_max_extensions")]
+    pub max_extensions: u32,
+    #[serde(default = "default_evaluation_interval")]
+    pub evaluation_interval: usize,
+}
+
+fn default_min_level() -> String {
+    "critical".to_string()
+}
+
+fn default_extend_secs() -> u64 {
+    120
+}
+
+fn default_max_extensions() -> u32 {
+    1
+}
+
+fn default_evaluation_interval() -> usize {
+    50
+}
+
+impl Default for LivePolicy {
+    fn default() -> Self {
+        Self {
+            min_level: default_min_level(),
+            action: None,
+            extend_secs: default_extend_secs(),
+            max_extensions: default_max_extensions(),
+            evaluation_interval: default_evaluation_interval(),
+        }
+    }
+}
+
+impl LivePolicy {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn triggers(&self, detection: &Detection) -> bool {
+        match (
+            detection.level.as_deref().and_then(level_rank),
+            level_rank(&self.min_level),
+        ) {
+            (Some(level), Some(min)) => level >= min,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LiveFinding {
+    pub time: DateTime<Utc>,
+    pub detection: Detection,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LiveDecision {
+    pub time: DateTime<Utc>,
+    pub action: LiveAction,
+    pub detection: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LiveStatus {
+    pub machine: String,
+    pub started: DateTime<Utc>,
+    pub events: usize,
+    pub findings: Vec<LiveFinding>,
+    pub decisions: Vec<LiveDecision>,
+}
+
+struct LiveSession {
+    status: LiveStatus,
+    events: Vec<SysmonEvent>,
+    pending: usize,
+    extensions: u32,
+    terminated: bool,
+}
+
+impl LiveSession {
+    fn record(&mut self, detection: Detection, merge: bool) -> Option<&Detection> {
+        let existing = self.status.findings.iter_mut().find(|f| {
+            f.detection.source == detection.source
+                && (f.detection.source == "ransomware" || f.detection.name == detection.name)
+        });
+        match existing {
+            Some(finding) if merge => {
+                finding.detection.events.extend(detection.events);
+                None
+            }
+            Some(finding) => {
+                finding.detection = detection;
+                None
+            }
+            None => {
+                self.status.findings.push(LiveFinding {
+                    time: Utc::now(),
+                    detection,
+                });
+                self.status.findings.last().map(|f| &f.detection)
+            }
+        }
+    }
+}
+
+pub struct LiveMonitor {
+    rules: Vec<SigmaRule>,
+    signatures: SignatureRegistry,
+    policy: LivePolicy,
+    sessions: Mutex<HashMap<String, LiveSession>>,
+}
+
+impl fmt::Debug for LiveMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LiveMonitor")
+            .field("rules", &self.rules.len())
+            .field("signatures", &self.signatures.len())
+            .field("policy", &self.policy)
+            .field("sessions", &self.sessions.lock().map_or(0, |s| s.len()))
+            .finish()
+    }
+}
+
+impl LiveMonitor {
+    pub fn new(rules: Vec<SigmaRule>, signatures: SignatureRegistry, policy: LivePolicy) -> Self {
+        Self {
+            rules,
+            signatures,
+            policy,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn policy(&self) -> &LivePolicy {
+        &self.policy
+    }
+
+    pub fn max_extension(&self) -> u64 {
+        match self.policy.action {
+            Some(LiveAction::Extend) => self.policy.extend_secs * self.policy.max_extensions as u64,
+            _ => 0,
+        }
+    }
+
+    pub fn begin(&self, machine: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.insert(
+                machine.to_string(),
+                LiveSession {
+                    status: LiveStatus {
+                        machine: machine.to_string(),
+                        started: Utc::now(),
+                        events: 0,
+                        findings: Vec::new(),
+                        decisions: Vec::new(),
+                    },
+                    events: Vec::new(),
+                    pending: 0,
+                    extensions: 0,
+                    terminated: false,
+                },
+            );
+        }
+    }
+
+    pub fn end(&self, machine: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(machine);
+        }
+    }
+
+    pub fn status(&self, machine: &str) -> Option<LiveStatus> {
+        self.sessions
+            .lock()
+            .ok()?
+            .get(machine)
+            .map(|s| s.status.clone())
+    }
+
+    pub fn observe(&self, machine: &str, event: SysmonEvent) -> Option<LiveAction> {
+        let mut sessions = self.sessions.lock().ok()?;
+        let session = sessions.get_mut(machine)?;
+        session.status.events += 1;
+        session.pending += 1;
+
+        let mut fired = Vec::new();
+        for rule in self.rules.iter().filter(|r| r.is_match(&event)) {
+            let detection = Detection {
+                source: "sigma".to_string(),
+                name: rule.title.clone(),
+                level: rule.level.clone(),
+                tags: rule.tags.clone(),
+                events: vec![event.clone()],
+            };
+            fired.extend(session.record(detection, true).cloned());
+        }
+        session.events.push(event);
+
+        if session.pending >= self.policy.evaluation_interval.max(1) {
+            session.pending = 0;
+            for m in self.signatures.evaluate(&session.events) {
+                let detection = Detection {
+                    source: "signature".to_string(),
+                    name: m.signature,
+                    level: m.level,
+                    tags: m.tags,
+                    events: m.events,
+                };
+                fired.extend(session.record(detection, false).cloned());
+            }
+            if let Some(verdict) =
+                detect_ransomware(&session.events, &[], &RansomwareOptions::default())
+            {
+                fired.extend(
+                    session
+                        .record(ransomware_detection(&verdict), false)
+                        .cloned(),
+                );
+            }
+        }
+
+        let detection = fired.iter().find(|d| self.policy.triggers(d))?;
+        let action = match self.policy.action? {
+            LiveAction::Extend if session.extensions < self.policy.max_extensions => {
+                session.extensions += 1;
+                LiveAction::Extend
+            }
+            LiveAction::Terminate if !session.terminated => {
+                session.terminated = true;
+                LiveAction::Terminate
+            }
+            _ => return None,
+        };
+        session.status.decisions.push(LiveDecision {
+            time: Utc::now(),
+            action,
+            detection: detection.name.clone(),
+        });
+        Some(action)
+    }
+}
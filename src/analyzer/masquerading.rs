@@ -0,0 +1,72 @@
+use crate::encoding::{field_flags, EncodingFlag};
+use crate::sysmon_event::SysmonEvent;
+
+const NAME_FIELDS: &[&str] = &[
+    "Image",
+    "ParentImage",
+    "SourceImage",
+    "TargetImage",
+    "ImageLoaded",
+    "TargetFilename",
+    "OriginalFileName",
+    "CommandLine",
+    "ParentCommandLine",
+];
+
+#[derive(Debug, Clone)]
+pub struct MasqueradeAlert {
+    pub field: String,
+    pub value: String,
+    pub flags: Vec<EncodingFlag>,
+    pub events: Vec<SysmonEvent>,
+}
+
+impl MasqueradeAlert {
+    pub fn technique(&self) -> &'static str {
+        if self.flags.contains(&EncodingFlag::BidiOverride) {
+            "T1036.002"
+        } else {
+            "T1036"
+        }
+    }
+
+    pub fn title(&self) -> String {
+        let trick = match self.flags.first() {
+            Some(EncodingFlag::BidiOverride) => "Right-to-left override",
+            Some(EncodingFlag::MixedScript) => "Homoglyph characters",
+            _ => "Invisible characters",
+        };
+        format!("{} in {}", trick, self.field)
+    }
+}
+
+pub fn detect_masquerading(events: &[SysmonEvent]) -> Vec<MasqueradeAlert> {
+    let mut alerts: Vec<MasqueradeAlert> = Vec::new();
+    for event in events {
+        for field in NAME_FIELDS {
+            let flags: Vec<EncodingFlag> = field_flags(event, field)
+                .into_iter()
+                .filter(EncodingFlag::is_deceptive)
+                .collect();
+            if flags.is_empty() {
+                continue;
+            }
+            let Some(value) = event.event_data.get(*field) else {
+                continue;
+            };
+            match alerts
+                .iter_mut()
+                .find(|a| a.field == *field && a.value == *value)
+            {
+                Some(alert) => alert.events.push(event.clone()),
+                None => alerts.push(MasqueradeAlert {
+                    field: field.to_string(),
+                    value: value.clone(),
+                    flags,
+                    events: vec![event.clone()],
+                }),
+            }
+        }
+    }
+    alerts
+}
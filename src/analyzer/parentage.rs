@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cmdline::program_name;
+use crate::path::normalize;
+use crate::process_tree::{Process, ProcessTree};
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const BUILTIN_RULES: &[&str] = &[
+    r#"
+name = "Office application spawned a command interpreter"
+level = "high"
+tags = ["attack.execution", "attack.t1204.002"]
+
+[[chain]]
+image = ["winword", "excel", "powerpnt", "outlook", "msaccess", "mspub", "onenote", "visio"]
+
+[[chain]]
+image = ["cmd", "powershell", "pwsh", "wscript", "cscript", "mshta", "rundll32", "regsvr32", "certutil", "bitsadmin"]
+"#,
+    r#"
+name = "Office application launched PowerShell through cmd"
+level = "critical"
+tags = ["attack.execution", "attack.t1059.001"]
+
+[[chain]]
+image = ["winword", "excel", "powerpnt", "outlook", "msaccess", "mspub", "onenote", "visio"]
+
+[[chain]]
+image = ["cmd"]
+
+[[chain]]
+image = ["powershell", "pwsh"]
+"#,
+    r#"
+name = "Browser spawned a command interpreter"
+level = "high"
+tags = ["attack.execution", "attack.t1189"]
+
+[[chain]]
+image = ["chrome", "msedge", "firefox", "iexplore", "opera", "brave"]
+
+[[chain]]
+image = ["cmd", "powershell", "pwsh", "wscript", "cscript", "mshta"]
+"#,
+    r#"
+name = "Script host spawned a command interpreter"
+level = "medium"
+tags = ["attack.execution", "attack.t1059"]
+
+[[chain]]
+image = ["wscript", "cscript", "mshta"]
+
+[[chain]]
+image = ["cmd", "powershell", "pwsh", "rundll32", "regsvr32"]
+"#,
+    r#"
+name = "Service started from a user-writable path"
+level = "high"
+tags = ["attack.persistence", "attack.t1543.003"]
+
+[[chain]]
+image = ["services"]
+
+[[chain]]
+path = ["\\appdata\\", "\\temp\\", "\\downloads\\", "\\users\\public\\"]
+"#,
+    r#"
+name = "System process name running from an unexpected location"
+level = "high"
+tags = ["attack.defense_evasion", "attack.t1036.005"]
+
+[[chain]]
+image = ["services", "svchost", "lsass", "csrss", "winlogon", "smss", "wininit", "spoolsv", "taskhostw"]
+not_path = ["c:\\windows\\system32\\", "c:\\windows\\syswow64\\"]
+"#,
+    r#"
+name = "svchost started by an unexpected parent"
+level = "medium"
+tags = ["attack.defense_evasion", "attack.t1036"]
+
+[[chain]]
+not_image = ["services"]
+
+[[chain]]
+image = ["svchost"]
+"#,
+    r#"
+name = "LSASS spawned a child process"
+level = "critical"
+tags = ["attack.credential_access", "attack.t1003.001"]
+
+[[chain]]
+image = ["lsass"]
+
+[[chain]]
+not_image = ["werfault"]
+"#,
+];
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessMatch {
+    #[serde(default)]
+    pub image: Vec<String>,
+    #[serde(default)]
+    pub not_image: Vec<String>,
+    #[serde(default)]
+    pub path: Vec<String>,
+    #[serde(default)]
+    pub not_path: Vec<String>,
+    #[serde(default)]
+    pub command_line: Vec<String>,
+}
+
+impl ProcessMatch {
+    fn matches(&self, process: &Process) -> bool {
+        if process.image.is_empty() {
+            return false;
+        }
+        let name = program_name(&process.image);
+        let path = normalize(&process.image);
+        let command_line = process.command_line.to_lowercase();
+        let any = |patterns: &[String], f: &dyn Fn(&str) -> bool| {
+            patterns.iter().any(|p| f(&p.to_lowercase()))
+        };
+        (self.image.is_empty() || any(&self.image, &|p| name == p))
+            && !any(&self.not_image, &|p| name == p)
+            && (self.path.is_empty() || any(&self.path, &|p| path.contains(p)))
+            && !any(&self.not_path, &|p| path.starts_with(p))
+            && (self.command_line.is_empty()
+                || any(&self.command_line, &|p| command_line.contains(p)))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ParentageRule {
+    pub name: String,
+    pub level: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub chain: Vec<ProcessMatch>,
+}
+
+impl ParentageRule {
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let rule: Self = toml::from_str(toml)?;
+        if rule.chain.is_empty() {
+            bail!("Parentage rule {} has no chain", rule.name);
+        }
+        Ok(rule)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_toml(&fs::read_to_string(path)?)
+            .with_context(|| format!("Failed to load parentage rule {}", path.display()))
+    }
+
+    fn matches<'a>(&self, ancestry: &[&'a Process]) -> Option<Vec<&'a Process>> {
+        let start = ancestry.len().checked_sub(self.chain.len())?;
+        let chain = &ancestry[start..];
+        self.chain
+            .iter()
+            .zip(chain)
+            .all(|(m, p)| m.matches(p))
+            .then(|| chain.to_vec())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChainLink {
+    pub guid: String,
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub command_line: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ParentageMatch {
+    pub rule: String,
+    pub level: Option<String>,
+    pub tags: Vec<String>,
+    pub chain: Vec<ChainLink>,
+    pub events: Vec<SysmonEvent>,
+}
+
+#[derive(Default)]
+pub struct ParentageRules {
+    rules: Vec<ParentageRule>,
+}
+
+impl fmt::Debug for ParentageRules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.rules.iter().map(|r| &r.name))
+            .finish()
+    }
+}
+
+impl ParentageRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut rules = Self::new();
+        for toml in BUILTIN_RULES {
+            rules.add(ParentageRule::from_toml(toml).expect("valid builtin parentage rule"));
+        }
+        rules
+    }
+
+    pub fn add(&mut self, rule: ParentageRule) {
+        self.rules.push(rule);
+    }
+
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.load_dir(&path)?;
+            } else if path.extension().is_some_and(|e| e == "toml") {
+                self.add(ParentageRule::from_file(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn evaluate(&self, events: &[SysmonEvent]) -> Vec<ParentageMatch> {
+        let tree = ProcessTree::from_events(events);
+        let creates: HashMap<&str, &SysmonEvent> = events
+            .iter()
+            .filter(|e| e.event_id == SysmonEventId::PROCESS_CREATE)
+            .filter_map(|e| Some((e.event_data.get("ProcessGuid")?.as_str(), e)))
+            .collect();
+
+        let mut processes: Vec<&Process> = tree.processes().collect();
+        processes.sort_by_key(|p| p.start_time);
+        let mut matches = Vec::new();
+        for process in processes {
+            let ancestry = tree.ancestors(&process.guid);
+            for rule in &self.rules {
+                let Some(chain) = rule.matches(&ancestry) else {
+                    continue;
+                };
+                matches.push(ParentageMatch {
+                    rule: rule.name.clone(),
+                    level: rule.level.clone(),
+                    tags: rule.tags.clone(),
+                    events: chain
+                        .iter()
+                        .filter_map(|p| creates.get(p.guid.as_str()).map(|e| (*e).clone()))
+                        .collect(),
+                    chain: chain
+                        .iter()
+                        .map(|p| ChainLink {
+                            guid: p.guid.clone(),
+                            process_id: p.process_id,
+                            image: p.image.clone(),
+                            command_line: p.command_line.clone(),
+                        })
+                        .collect(),
+                });
+            }
+        }
+        matches
+    }
+}
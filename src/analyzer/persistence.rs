@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::cmdline::{program_name, tokenize};
+use crate::event_data::{TypedEventData, WmiConsumerData, WmiFilterData};
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const SERVICE_IMAGE_PATH_PATTERN: &str =
+    r"(?i)^HKLM\\SYSTEM\\(?:CurrentControlSet|ControlSet\d+)\\Services\\([^\\]+)\\ImagePath$";
+const SERVICE_START_PATTERN: &str =
+    r"(?i)^HKLM\\SYSTEM\\(?:CurrentControlSet|ControlSet\d+)\\Services\\([^\\]+)\\Start$";
+const TASKS_FOLDER_PATTERN: &str = r"(?i)^[a-z]:\\Windows\\System32\\Tasks\\(.+)$";
+const REGISTER_TASK_PATTERN: &str =
+    r#"(?i)Register-ScheduledTask\b.*?-TaskName\s+['"]?([^'"]+?)['"]?(?:\s|$)"#;
+const TASK_ACTION_PATTERN: &str =
+    r#"(?i)-Execute\s+['"]?([^'"\s)]+)['"]?(?:\s+-Argument\s+['"]([^'"]*)['"])?"#;
+const TASK_TRIGGER_PATTERN: &str = r"(?i)New-ScheduledTaskTrigger\s+-(\w+)";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceInstallAlert {
+    pub service_name: String,
+    pub binary_path: Option<String>,
+    pub start_type: Option<String>,
+    pub process_event: Option<SysmonEvent>,
+    pub registry_event: Option<SysmonEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledTaskAlert {
+    pub task_name: String,
+    pub command: Option<String>,
+    pub trigger: Option<String>,
+    pub process_event: Option<SysmonEvent>,
+    pub file_event: Option<SysmonEvent>,
+}
+
+struct ServiceCommand {
+    name: String,
+    binary_path: Option<String>,
+    start_type: Option<String>,
+}
+
+fn strip_prefix_ignore_case<'a>(arg: &'a str, prefix: &str) -> Option<&'a str> {
+    arg.get(..prefix.len())
+        .is_some_and(|p| p.eq_ignore_ascii_case(prefix))
+        .then(|| &arg[prefix.len()..])
+}
+
+fn sc_service(args: &[String]) -> Option<ServiceCommand> {
+    let mut rest = args.iter().skip(1).skip_while(|a| a.starts_with(r"\\"));
+    let command = rest.next()?.to_lowercase();
+    if command != "create" && command != "config" {
+        return None;
+    }
+    let name = rest.next()?.to_string();
+
+    let mut binary_path = None;
+    let mut start_type = None;
+    while let Some(arg) = rest.next() {
+        if arg.eq_ignore_ascii_case("binpath=") {
+            binary_path = rest.next().cloned();
+        } else if let Some(value) = strip_prefix_ignore_case(arg, "binpath=") {
+            binary_path = Some(value.to_string());
+        } else if arg.eq_ignore_ascii_case("start=") {
+            start_type = rest.next().map(|s| s.to_lowercase());
+        } else if let Some(value) = strip_prefix_ignore_case(arg, "start=") {
+            start_type = Some(value.to_lowercase());
+        }
+    }
+
+    Some(ServiceCommand {
+        name,
+        binary_path,
+        start_type,
+    })
+}
+
+fn new_service(args: &[String]) -> Option<ServiceCommand> {
+    let position = args
+        .iter()
+        .position(|a| a.eq_ignore_ascii_case("new-service"))?;
+
+    let mut name = None;
+    let mut binary_path = None;
+    let mut start_type = None;
+    let mut positional = Vec::new();
+    let mut rest = args[position + 1..].iter();
+    while let Some(arg) = rest.next() {
+        let lower = arg.to_lowercase();
+        if lower == "-name" {
+            name = rest.next().cloned();
+        } else if lower.starts_with("-binarypath") {
+            binary_path = rest.next().cloned();
+        } else if lower.starts_with("-startuptype") {
+            start_type = rest.next().map(|s| s.to_lowercase());
+        } else if lower.starts_with('-') {
+            rest.next();
+        } else {
+            positional.push(arg.clone());
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let name = name.or_else(|| positional.next())?;
+    let binary_path = binary_path.or_else(|| positional.next());
+    Some(ServiceCommand {
+        name,
+        binary_path,
+        start_type,
+    })
+}
+
+fn process_service(event: &SysmonEvent) -> Option<ServiceCommand> {
+    let args = tokenize(event.event_data.get("CommandLine")?);
+    match program_name(args.first()?).as_str() {
+        "sc" => sc_service(&args),
+        "powershell" | "pwsh" => new_service(&args),
+        _ => None,
+    }
+}
+
+fn service_start_type(details: &str) -> Option<&'static str> {
+    let value = details.trim().trim_start_matches("DWORD").trim();
+    let value = value.trim_start_matches('(').trim_end_matches(')');
+    let value = match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => value.parse().ok()?,
+    };
+    match value {
+        0 => Some("boot"),
+        1 => Some("system"),
+        2 => Some("auto"),
+        3 => Some("demand"),
+        4 => Some("disabled"),
+        _ => None,
+    }
+}
+
+pub fn detect_service_install(events: &[SysmonEvent]) -> Vec<ServiceInstallAlert> {
+    let re = Regex::new(SERVICE_IMAGE_PATH_PATTERN).unwrap();
+    let start = Regex::new(SERVICE_START_PATTERN).unwrap();
+    let mut alerts: Vec<ServiceInstallAlert> = Vec::new();
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    let mut start_types: HashMap<String, &'static str> = HashMap::new();
+
+    for event in events {
+        if event.event_id == SysmonEventId::PROCESS_CREATE {
+            let Some(command) = process_service(event) else {
+                continue;
+            };
+            match by_name.get(&command.name.to_lowercase()) {
+                Some(&i) if alerts[i].process_event.is_none() => {
+                    alerts[i].process_event = Some(event.clone());
+                    if alerts[i].binary_path.is_none() {
+                        alerts[i].binary_path = command.binary_path;
+                    }
+                    if alerts[i].start_type.is_none() {
+                        alerts[i].start_type = command.start_type;
+                    }
+                }
+                _ => {
+                    by_name.insert(command.name.to_lowercase(), alerts.len());
+                    alerts.push(ServiceInstallAlert {
+                        service_name: command.name,
+                        binary_path: command.binary_path,
+                        start_type: command.start_type,
+                        process_event: Some(event.clone()),
+                        registry_event: None,
+                    });
+                }
+            }
+        } else if event.event_id == SysmonEventId::REGISTRY_EVENT_SET {
+            let Some(target) = event.event_data.get("TargetObject") else {
+                continue;
+            };
+            if let Some(c) = start.captures(target) {
+                let start_type = event
+                    .event_data
+                    .get("Details")
+                    .and_then(|d| service_start_type(d));
+                if let Some(start_type) = start_type {
+                    start_types.insert(c[1].to_lowercase(), start_type);
+                }
+                continue;
+            }
+            let Some(c) = re.captures(target) else {
+                continue;
+            };
+            let name = c[1].to_string();
+            let binary_path = event.event_data.get("Details").cloned();
+            match by_name.get(&name.to_lowercase()) {
+                Some(&i) if alerts[i].registry_event.is_none() => {
+                    alerts[i].registry_event = Some(event.clone());
+                    if binary_path.is_some() {
+                        alerts[i].binary_path = binary_path;
+                    }
+                }
+                _ => {
+                    by_name.insert(name.to_lowercase(), alerts.len());
+                    alerts.push(ServiceInstallAlert {
+                        service_name: name,
+                        binary_path,
+                        start_type: None,
+                        process_event: None,
+                        registry_event: Some(event.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    for alert in &mut alerts {
+        if let Some(start_type) = start_types.get(&alert.service_name.to_lowercase()) {
+            alert.start_type = Some(start_type.to_string());
+        }
+    }
+    alerts
+}
+
+fn task_name(name: &str) -> String {
+    name.trim()
+        .trim_matches('"')
+        .trim_start_matches('\\')
+        .to_string()
+}
+
+fn schtasks_create(args: &[String]) -> Option<ScheduledTaskAlert> {
+    let switch = |arg: &str| arg.trim_start_matches(['/', '-']).to_lowercase();
+    if !args.iter().skip(1).any(|a| switch(a) == "create") {
+        return None;
+    }
+
+    let mut name = None;
+    let mut command = None;
+    let mut schedule = None;
+    let mut modifier = None;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        if !arg.starts_with(['/', '-']) {
+            continue;
+        }
+        match switch(arg).as_str() {
+            "tn" => name = rest.next().map(|n| task_name(n)),
+            "tr" => command = rest.next().cloned(),
+            "sc" => schedule = rest.next().map(|s| s.to_lowercase()),
+            "mo" => modifier = rest.next().cloned(),
+            "xml" => command = rest.next().map(|x| format!("<xml {}>", x)),
+            _ => (),
+        }
+    }
+
+    let trigger = match (schedule, modifier) {
+        (Some(schedule), Some(modifier)) => Some(format!("{} /mo {}", schedule, modifier)),
+        (schedule, _) => schedule,
+    };
+    Some(ScheduledTaskAlert {
+        task_name: name?,
+        command,
+        trigger,
+        process_event: None,
+        file_event: None,
+    })
+}
+
+fn register_scheduled_task(command_line: &str) -> Option<ScheduledTaskAlert> {
+    let register = Regex::new(REGISTER_TASK_PATTERN).unwrap();
+    let name = register.captures(command_line)?;
+    let execute = Regex::new(TASK_ACTION_PATTERN).unwrap();
+    let trigger = Regex::new(TASK_TRIGGER_PATTERN).unwrap();
+    let command = execute.captures(command_line).map(|c| match c.get(2) {
+        Some(argument) => format!("{} {}", &c[1], argument.as_str()),
+        None => c[1].to_string(),
+    });
+    Some(ScheduledTaskAlert {
+        task_name: task_name(&name[1]),
+        command,
+        trigger: trigger.captures(command_line).map(|c| c[1].to_lowercase()),
+        process_event: None,
+        file_event: None,
+    })
+}
+
+fn process_scheduled_task(event: &SysmonEvent) -> Option<ScheduledTaskAlert> {
+    let command_line = event.event_data.get("CommandLine")?;
+    let args = tokenize(command_line);
+    match program_name(args.first()?).as_str() {
+        "schtasks" => schtasks_create(&args),
+        "powershell" | "pwsh" => register_scheduled_task(command_line),
+        _ => None,
+    }
+}
+
+pub fn detect_scheduled_tasks(events: &[SysmonEvent]) -> Vec<ScheduledTaskAlert> {
+    let folder = Regex::new(TASKS_FOLDER_PATTERN).unwrap();
+    let mut alerts: Vec<ScheduledTaskAlert> = Vec::new();
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+
+    for event in events {
+        if event.event_id == SysmonEventId::PROCESS_CREATE {
+            let Some(mut alert) = process_scheduled_task(event) else {
+                continue;
+            };
+            match by_name.get(&alert.task_name.to_lowercase()) {
+                Some(&i) if alerts[i].process_event.is_none() => {
+                    alerts[i].process_event = Some(event.clone());
+                    alerts[i].command = alerts[i].command.take().or(alert.command);
+                    alerts[i].trigger = alerts[i].trigger.take().or(alert.trigger);
+                }
+                _ => {
+                    by_name.insert(alert.task_name.to_lowercase(), alerts.len());
+                    alert.process_event = Some(event.clone());
+                    alerts.push(alert);
+                }
+            }
+        } else if event.event_id == SysmonEventId::FILE_CREATE {
+            let Some(target) = event.event_data.get("TargetFilename") else {
+                continue;
+            };
+            let Some(c) = folder.captures(target) else {
+                continue;
+            };
+            let name = task_name(&c[1]);
+            match by_name.get(&name.to_lowercase()) {
+                Some(&i) if alerts[i].file_event.is_none() => {
+                    alerts[i].file_event = Some(event.clone());
+                }
+                _ => {
+                    by_name.insert(name.to_lowercase(), alerts.len());
+                    alerts.push(ScheduledTaskAlert {
+                        task_name: name,
+                        command: None,
+                        trigger: None,
+                        process_event: None,
+                        file_event: Some(event.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    alerts
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WmiSubscription {
+    pub time: DateTime<FixedOffset>,
+    pub user: Option<String>,
+    pub filter: String,
+    pub consumer: String,
+    pub consumer_class: Option<String>,
+    pub namespace: Option<String>,
+    pub query: Option<String>,
+    pub consumer_type: Option<String>,
+    pub destination: Option<String>,
+    pub events: Vec<SysmonEvent>,
+}
+
+fn is_created(operation: &str) -> bool {
+    operation.eq_ignore_ascii_case("created")
+}
+
+fn wmi_reference(reference: &str) -> (Option<String>, String) {
+    let reference = reference.trim().trim_matches('"');
+    let path = reference.rsplit_once(':').map_or(reference, |(_, p)| p);
+    match path.split_once(".Name=") {
+        Some((class, name)) => (
+            Some(class.to_string()),
+            name.trim_matches(|c| c == '"' || c == '\\').to_string(),
+        ),
+        None => (None, path.to_string()),
+    }
+}
+
+pub fn reconstruct_wmi_subscriptions(events: &[SysmonEvent]) -> Vec<WmiSubscription> {
+    let mut filters: HashMap<String, (WmiFilterData, &SysmonEvent)> = HashMap::new();
+    let mut consumers: HashMap<String, (WmiConsumerData, &SysmonEvent)> = HashMap::new();
+    let mut subscriptions = Vec::new();
+
+    for event in events {
+        match event.typed_data() {
+            TypedEventData::WmiFilter(d) if is_created(&d.operation) => {
+                filters.insert(d.name.to_lowercase(), (d, event));
+            }
+            TypedEventData::WmiConsumer(d) if is_created(&d.operation) => {
+                consumers.insert(d.name.to_lowercase(), (d, event));
+            }
+            TypedEventData::WmiBinding(d) if is_created(&d.operation) => {
+                let (_, filter) = wmi_reference(&d.filter);
+                let (consumer_class, consumer) = wmi_reference(&d.consumer);
+                let filter_data = filters.get(&filter.to_lowercase());
+                let consumer_data = consumers.get(&consumer.to_lowercase());
+                let mut subscription_events: Vec<SysmonEvent> = filter_data
+                    .map(|(_, e)| *e)
+                    .into_iter()
+                    .chain(consumer_data.map(|(_, e)| *e))
+                    .cloned()
+                    .collect();
+                subscription_events.push(event.clone());
+                subscriptions.push(WmiSubscription {
+                    time: event.time_created,
+                    user: d.user,
+                    filter,
+                    consumer,
+                    consumer_class,
+                    namespace: filter_data.map(|(f, _)| f.event_namespace.clone()),
+                    query: filter_data.map(|(f, _)| f.query.clone()),
+                    consumer_type: consumer_data.map(|(c, _)| c.consumer_type.clone()),
+                    destination: consumer_data.map(|(c, _)| c.destination.clone()),
+                    events: subscription_events,
+                });
+            }
+            _ => (),
+        }
+    }
+
+    subscriptions
+}
@@ -0,0 +1,98 @@
+use crate::cmdline::{program_name, tokenize};
+use crate::process_tree::ProcessTree;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+#[derive(Debug, Clone)]
+pub struct PrivilegeSignature {
+    pub name: String,
+    pub programs: Vec<String>,
+    pub args: Vec<String>,
+}
+
+impl PrivilegeSignature {
+    pub fn new(name: &str, programs: &[&str], args: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            programs: programs.iter().map(|p| p.to_lowercase()).collect(),
+            args: args.iter().map(|a| a.to_lowercase()).collect(),
+        }
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("Token privilege enumeration", &["whoami"], &["/priv"]),
+            Self::new("Token privilege enumeration", &["whoami"], &["/all"]),
+            Self::new("Run as another user", &["runas"], &[]),
+            Self::new("PsExec as SYSTEM", &["psexec", "psexec64"], &["-s"]),
+            Self::new("Service security descriptor change", &["sc"], &["sdset"]),
+            Self::new("SeDebugPrivilege request", &[], &["privilege::debug"]),
+            Self::new("Token impersonation", &[], &["token::elevate"]),
+            Self::new("Sudo privilege enumeration", &["sudo"], &["-l"]),
+            Self::new("Setuid bit set", &["chmod"], &["u+s"]),
+            Self::new("Setuid bit set", &["chmod"], &["+s"]),
+            Self::new("Setuid bit set", &["chmod"], &["4755"]),
+            Self::new("File capability set", &["setcap"], &[]),
+        ]
+    }
+
+    fn is_match(&self, args: &[String]) -> bool {
+        let Some(program) = args.first().map(|a| program_name(a)) else {
+            return false;
+        };
+        if !self.programs.is_empty() && !self.programs.contains(&program) {
+            return false;
+        }
+        self.args
+            .iter()
+            .all(|a| args[1..].iter().any(|arg| arg.to_lowercase() == *a))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrivilegeAbuse {
+    pub signature: String,
+    pub chain: Vec<String>,
+    pub event: SysmonEvent,
+}
+
+pub fn detect_privilege_abuse(events: &[SysmonEvent]) -> Vec<PrivilegeAbuse> {
+    detect_privilege_abuse_with(events, &PrivilegeSignature::defaults())
+}
+
+pub fn detect_privilege_abuse_with(
+    events: &[SysmonEvent],
+    signatures: &[PrivilegeSignature],
+) -> Vec<PrivilegeAbuse> {
+    let tree = ProcessTree::from_events(events);
+    let mut result = Vec::new();
+
+    for event in events {
+        if event.event_id != SysmonEventId::PROCESS_CREATE {
+            continue;
+        }
+        let Some(command_line) = event.event_data.get("CommandLine") else {
+            continue;
+        };
+        let args = tokenize(command_line);
+        let Some(signature) = signatures.iter().find(|s| s.is_match(&args)) else {
+            continue;
+        };
+
+        let chain = match event.event_data.get("ProcessGuid") {
+            Some(guid) => tree
+                .ancestors(guid)
+                .iter()
+                .map(|p| p.image.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        result.push(PrivilegeAbuse {
+            signature: signature.name.clone(),
+            chain,
+            event: event.clone(),
+        });
+    }
+
+    result
+}
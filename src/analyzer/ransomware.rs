@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::Duration;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::artifacts::Artifact;
+use crate::cmdline::{normalized_args, program_name};
+use crate::path::normalize;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const RANSOM_NOTE_PATTERN: &str = r"(?i)(?:read[_-]?me|how[_-]?to|decrypt|restore|recover|ransom|!!!|unlock|your[_-]?files|files[_-]?encrypted|instructions?).*\.(?:txt|html?|hta|rtf|url)$";
+
+const PLAINTEXT_EXTENSIONS: &[&str] = &[
+    "txt", "csv", "log", "ini", "xml", "json", "html", "htm", "md", "sql", "bat", "ps1", "vbs",
+    "js", "yml", "yaml", "cfg", "conf", "rtf", "eml", "svg",
+];
+
+pub struct RansomwareOptions {
+    pub min_renamed_files: usize,
+    pub window: Duration,
+    pub min_note_dirs: usize,
+    pub min_entropy: f64,
+    pub min_indicators: usize,
+}
+
+impl Default for RansomwareOptions {
+    fn default() -> Self {
+        Self {
+            min_renamed_files: 20,
+            window: Duration::seconds(60),
+            min_note_dirs: 2,
+            min_entropy: 7.5,
+            min_indicators: 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RansomwareIndicator {
+    MassRename,
+    RecoveryInhibition,
+    RansomNote,
+    HighEntropyOverwrite,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RansomwareEvidence {
+    pub indicator: RansomwareIndicator,
+    pub description: String,
+    pub paths: Vec<String>,
+    pub artifacts: Vec<String>,
+    pub events: Vec<SysmonEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RansomwareVerdict {
+    pub indicators: Vec<RansomwareIndicator>,
+    pub evidence: Vec<RansomwareEvidence>,
+}
+
+pub fn is_recovery_inhibition(event: &SysmonEvent) -> bool {
+    if event.event_id != SysmonEventId::PROCESS_CREATE {
+        return false;
+    }
+    let Some(command_line) = event.event_data.get("CommandLine") else {
+        return false;
+    };
+    let args = normalized_args(command_line);
+    let Some(program) = args.first().map(|a| program_name(a)) else {
+        return false;
+    };
+    let has = |arg: &str| args.iter().any(|a| a == arg);
+    let lower = command_line.to_lowercase();
+    match program.as_str() {
+        "vssadmin" => (has("delete") && has("shadows")) || (has("resize") && has("shadowstorage")),
+        "wmic" => has("shadowcopy") && has("delete"),
+        "wbadmin" => has("delete") && (has("catalog") || has("systemstatebackup")),
+        "bcdedit" => {
+            (lower.contains("recoveryenabled") && has("no"))
+                || (lower.contains("bootstatuspolicy") && lower.contains("ignoreallfailures"))
+        }
+        "powershell" | "pwsh" => lower.contains("win32_shadowcopy") && lower.contains("delete"),
+        _ => false,
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['\\', '/']).next().unwrap_or(path)
+}
+
+fn directory(path: &str) -> &str {
+    path.rsplit_once(['\\', '/']).map_or("", |(dir, _)| dir)
+}
+
+fn appended_extension(path: &str) -> Option<(String, String)> {
+    let (original, extension) = file_name(path).rsplit_once('.')?;
+    let (stem, _) = original.rsplit_once('.')?;
+    if stem.is_empty() || extension.is_empty() || extension.len() > 16 {
+        return None;
+    }
+    let original = format!(
+        "{}{}",
+        &path[..path.len() - file_name(path).len()],
+        original
+    );
+    Some((extension.to_lowercase(), original))
+}
+
+fn extension(path: &str) -> Option<String> {
+    file_name(path)
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+}
+
+fn mass_renames(events: &[SysmonEvent], options: &RansomwareOptions) -> Vec<RansomwareEvidence> {
+    let mut groups: BTreeMap<(String, String), Vec<&SysmonEvent>> = BTreeMap::new();
+    for event in events {
+        if event.event_id != SysmonEventId::FILE_CREATE {
+            continue;
+        }
+        let Some(path) = event.event_data.get("TargetFilename") else {
+            continue;
+        };
+        let Some((extension, _)) = appended_extension(path) else {
+            continue;
+        };
+        let image = event.event_data.get("Image").cloned().unwrap_or_default();
+        groups.entry((image, extension)).or_default().push(event);
+    }
+
+    let mut evidence = Vec::new();
+    for ((image, extension), group) in groups {
+        let burst = group.iter().enumerate().find_map(|(i, first)| {
+            let end = first.time_created + options.window;
+            let burst: Vec<&SysmonEvent> = group[i..]
+                .iter()
+                .take_while(|e| e.time_created <= end)
+                .copied()
+                .collect();
+            (burst.len() >= options.min_renamed_files).then_some(burst)
+        });
+        let Some(burst) = burst else {
+            continue;
+        };
+        let paths: Vec<String> = burst
+            .iter()
+            .filter_map(|e| e.event_data.get("TargetFilename").cloned())
+            .collect();
+        evidence.push(RansomwareEvidence {
+            indicator: RansomwareIndicator::MassRename,
+            description: format!(
+                "{} wrote {} files with the appended extension .{} within {}s",
+                image,
+                paths.len(),
+                extension,
+                options.window.num_seconds()
+            ),
+            paths,
+            artifacts: Vec::new(),
+            events: burst.into_iter().cloned().collect(),
+        });
+    }
+    evidence
+}
+
+fn ransom_notes(events: &[SysmonEvent], options: &RansomwareOptions) -> Vec<RansomwareEvidence> {
+    let pattern = Regex::new(RANSOM_NOTE_PATTERN).unwrap();
+    let mut notes: BTreeMap<String, Vec<&SysmonEvent>> = BTreeMap::new();
+    for event in events {
+        if event.event_id != SysmonEventId::FILE_CREATE {
+            continue;
+        }
+        let Some(path) = event.event_data.get("TargetFilename") else {
+            continue;
+        };
+        let name = file_name(path);
+        if pattern.is_match(name) {
+            notes.entry(name.to_lowercase()).or_default().push(event);
+        }
+    }
+
+    let mut evidence = Vec::new();
+    for (name, drops) in notes {
+        let paths: Vec<String> = drops
+            .iter()
+            .filter_map(|e| e.event_data.get("TargetFilename").cloned())
+            .collect();
+        let dirs: HashSet<String> = paths.iter().map(|p| normalize(directory(p))).collect();
+        if dirs.len() < options.min_note_dirs {
+            continue;
+        }
+        evidence.push(RansomwareEvidence {
+            indicator: RansomwareIndicator::RansomNote,
+            description: format!("Ransom note {} dropped in {} directories", name, dirs.len()),
+            paths,
+            artifacts: Vec::new(),
+            events: drops.into_iter().cloned().collect(),
+        });
+    }
+    evidence
+}
+
+fn high_entropy_overwrites(
+    events: &[SysmonEvent],
+    artifacts: &[Artifact],
+    options: &RansomwareOptions,
+) -> Option<RansomwareEvidence> {
+    let deleted: HashSet<String> = events
+        .iter()
+        .filter(|e| {
+            e.event_id == SysmonEventId::FILE_DELETE
+                || e.event_id == SysmonEventId::FILE_DELETE_DETECTED
+        })
+        .filter_map(|e| e.event_data.get("TargetFilename"))
+        .map(|p| normalize(p))
+        .collect();
+
+    let overwritten: Vec<&Artifact> = artifacts
+        .iter()
+        .filter(|a| a.entropy.is_some_and(|e| e >= options.min_entropy))
+        .filter(|a| {
+            let plaintext =
+                extension(&a.path).is_some_and(|e| PLAINTEXT_EXTENSIONS.contains(&e.as_str()));
+            let replaced = appended_extension(&a.path)
+                .is_some_and(|(_, original)| deleted.contains(&normalize(&original)));
+            plaintext || replaced
+        })
+        .collect();
+    if overwritten.is_empty() {
+        return None;
+    }
+
+    let paths: Vec<String> = overwritten.iter().map(|a| a.path.clone()).collect();
+    let events = events
+        .iter()
+        .filter(|e| e.event_id == SysmonEventId::FILE_CREATE)
+        .filter(|e| {
+            e.event_data
+                .get("TargetFilename")
+                .is_some_and(|p| paths.iter().any(|path| normalize(path) == normalize(p)))
+        })
+        .cloned()
+        .collect();
+    Some(RansomwareEvidence {
+        indicator: RansomwareIndicator::HighEntropyOverwrite,
+        description: format!(
+            "{} collected files have encrypted-looking content (entropy >= {})",
+            overwritten.len(),
+            options.min_entropy
+        ),
+        paths,
+        artifacts: overwritten
+            .iter()
+            .map(|a| a.hashes.sha256.clone())
+            .collect(),
+        events,
+    })
+}
+
+pub fn detect_ransomware(
+    events: &[SysmonEvent],
+    artifacts: &[Artifact],
+    options: &RansomwareOptions,
+) -> Option<RansomwareVerdict> {
+    let mut evidence = mass_renames(events, options);
+
+    let inhibition: Vec<SysmonEvent> = events
+        .iter()
+        .filter(|e| is_recovery_inhibition(e))
+        .cloned()
+        .collect();
+    if !inhibition.is_empty() {
+        evidence.push(RansomwareEvidence {
+            indicator: RansomwareIndicator::RecoveryInhibition,
+            description: format!(
+                "{} commands deleted shadow copies or disabled recovery",
+                inhibition.len()
+            ),
+            paths: Vec::new(),
+            artifacts: Vec::new(),
+            events: inhibition,
+        });
+    }
+
+    evidence.extend(ransom_notes(events, options));
+    evidence.extend(high_entropy_overwrites(events, artifacts, options));
+
+    let mut indicators: Vec<RansomwareIndicator> = Vec::new();
+    for e in &evidence {
+        if !indicators.contains(&e.indicator) {
+            indicators.push(e.indicator);
+        }
+    }
+    let mass_rename = indicators.contains(&RansomwareIndicator::MassRename);
+    if indicators.len() < options.min_indicators && !mass_rename {
+        return None;
+    }
+    Some(RansomwareVerdict {
+        indicators,
+        evidence,
+    })
+}
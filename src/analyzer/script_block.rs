@@ -0,0 +1,102 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::telemetry::ScriptBlock;
+
+#[derive(Debug, Clone)]
+pub struct ScriptBlockSignature {
+    pub name: String,
+    pub technique: String,
+    pattern: Regex,
+}
+
+impl ScriptBlockSignature {
+    pub fn new(name: &str, technique: &str, pattern: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            technique: technique.to_string(),
+            pattern: RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .expect("Invalid script block pattern"),
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.pattern.is_match(text)
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new(
+                "AMSI bypass",
+                "T1562.001",
+                r"amsiInitFailed|AmsiUtils|amsiContext|AmsiScanBuffer",
+            ),
+            Self::new(
+                "Download cradle",
+                "T1105",
+                r"DownloadString|DownloadFile|DownloadData|Invoke-WebRequest|\biwr\b|Start-BitsTransfer",
+            ),
+            Self::new(
+                "Dynamic code execution",
+                "T1059.001",
+                r"Invoke-Expression|\biex\b|\.Invoke\(\)|ScriptBlock\]::Create",
+            ),
+            Self::new(
+                "Base64 decoding",
+                "T1140",
+                r"FromBase64String|-e(nc(odedcommand)?)?\s+[A-Za-z0-9+/=]{20,}",
+            ),
+            Self::new(
+                "Reflective assembly load",
+                "T1620",
+                r"Reflection\.Assembly\]::Load|Assembly\.Load\(",
+            ),
+            Self::new(
+                "Unmanaged API access",
+                "T1106",
+                r"VirtualAlloc|CreateThread|GetProcAddress|Add-Type.*DllImport",
+            ),
+            Self::new(
+                "Credential dumping",
+                "T1003",
+                r"Invoke-Mimikatz|sekurlsa::|lsadump::|MiniDumpWriteDump",
+            ),
+            Self::new(
+                "Defender tampering",
+                "T1562.001",
+                r"Set-MpPreference.*-Disable|Add-MpPreference.*-ExclusionPath",
+            ),
+        ]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptBlockAlert {
+    pub signature: String,
+    pub technique: String,
+    pub script_block: ScriptBlock,
+}
+
+pub fn detect_suspicious_script_blocks(blocks: &[ScriptBlock]) -> Vec<ScriptBlockAlert> {
+    detect_suspicious_script_blocks_with(blocks, &ScriptBlockSignature::defaults())
+}
+
+pub fn detect_suspicious_script_blocks_with(
+    blocks: &[ScriptBlock],
+    signatures: &[ScriptBlockSignature],
+) -> Vec<ScriptBlockAlert> {
+    let mut result = Vec::new();
+    for block in blocks {
+        for signature in signatures {
+            if signature.is_match(&block.text) {
+                result.push(ScriptBlockAlert {
+                    signature: signature.name.clone(),
+                    technique: signature.technique.clone(),
+                    script_block: block.clone(),
+                });
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,790 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+fn category_event_ids(category: &str) -> Option<&'static [SysmonEventId]> {
+    Some(match category {
+        "process_creation" => &[SysmonEventId::PROCESS_CREATE],
+        "file_change" => &[SysmonEventId::FILE_CREATE_TIME],
+        "network_connection" => &[SysmonEventId::NETWORK_CONNECT],
+        "process_termination" => &[SysmonEventId::PROCESS_TERMINATE],
+        "driver_load" => &[SysmonEventId::DRIVER_LOAD],
+        "image_load" => &[SysmonEventId::IMAGE_LOAD],
+        "create_remote_thread" => &[SysmonEventId::CREATE_REMOTE_THREAD],
+        "raw_access_thread" | "raw_access_read" => &[SysmonEventId::RAW_ACCESS_READ],
+        "process_access" => &[SysmonEventId::PROCESS_ACCESS],
+        "file_event" => &[SysmonEventId::FILE_CREATE],
+        "registry_add" | "registry_delete" => &[SysmonEventId::REGISTRY_EVENT_ADD_DELETE],
+        "registry_set" => &[SysmonEventId::REGISTRY_EVENT_SET],
+        "registry_rename" => &[SysmonEventId::REGISTRY_EVENT_RENAME],
+        "registry_event" => &[
+            SysmonEventId::REGISTRY_EVENT_ADD_DELETE,
+            SysmonEventId::REGISTRY_EVENT_SET,
+            SysmonEventId::REGISTRY_EVENT_RENAME,
+        ],
+        "create_stream_hash" => &[SysmonEventId::FILE_CREATE_STREAM_HASH],
+        "pipe_created" => &[
+            SysmonEventId::PIPE_EVENT_CREATE,
+            SysmonEventId::PIPE_EVENT_CONNECT,
+        ],
+        "wmi_event" => &[
+            SysmonEventId::WMI_EVENT_FILTER,
+            SysmonEventId::WMI_EVENT_CONSUMER,
+            SysmonEventId::WMI_EVENT_CONSUMER_FILTER,
+        ],
+        "dns_query" => &[SysmonEventId::DNS_QUERY],
+        "file_delete" => &[
+            SysmonEventId::FILE_DELETE,
+            SysmonEventId::FILE_DELETE_DETECTED,
+        ],
+        "clipboard_capture" => &[SysmonEventId::CLIPBOARD_CHANGE],
+        "process_tampering" => &[SysmonEventId::PROCESS_TAMPERING],
+        "file_block" => &[
+            SysmonEventId::FILE_BLOCK_EXECUTABLE,
+            SysmonEventId::FILE_BLOCK_SHREDDING,
+        ],
+        "file_executable_detected" => &[SysmonEventId::FILE_EXECUTABLE_DETECTED],
+        "sysmon_status" => &[
+            SysmonEventId::SERVICE_STATE_CHANGE,
+            SysmonEventId::CONFIG_STATE_CHANGE,
+        ],
+        "sysmon_error" => &[SysmonEventId::ERROR],
+        _ => return None,
+    })
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct LogSource {
+    category: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawRule {
+    title: String,
+    id: Option<String>,
+    level: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    logsource: LogSource,
+    detection: Mapping,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Pattern(Regex),
+    Null,
+}
+
+impl Matcher {
+    fn is_match(&self, value: Option<&str>) -> bool {
+        match (self, value) {
+            (Matcher::Pattern(re), Some(v)) => re.is_match(v),
+            (Matcher::Null, v) => v.is_none_or(str::is_empty),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FieldCondition {
+    field: String,
+    matchers: Vec<Matcher>,
+    all: bool,
+}
+
+impl FieldCondition {
+    fn is_match(&self, event: &SysmonEvent) -> bool {
+        let event_id;
+        let value = if self.field == "EventID" {
+            event_id = event.event_id.value().to_string();
+            Some(event_id.as_str())
+        } else {
+            event.event_data.get(&self.field).map(String::as_str)
+        };
+
+        if self.all {
+            self.matchers.iter().all(|m| m.is_match(value))
+        } else {
+            self.matchers.iter().any(|m| m.is_match(value))
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Selection {
+    Fields(Vec<Vec<FieldCondition>>),
+    Keywords(Vec<Matcher>),
+}
+
+impl Selection {
+    fn is_match(&self, event: &SysmonEvent) -> bool {
+        match self {
+            Selection::Fields(alternatives) => alternatives
+                .iter()
+                .any(|conditions| conditions.iter().all(|c| c.is_match(event))),
+            Selection::Keywords(keywords) => keywords.iter().any(|k| {
+                event
+                    .event_data
+                    .values()
+                    .any(|v| k.is_match(Some(v.as_str())))
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Condition {
+    Selection(usize),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    OneOf(Vec<usize>),
+    AllOf(Vec<usize>),
+}
+
+impl Condition {
+    fn is_match(&self, selections: &[Selection], event: &SysmonEvent) -> bool {
+        match self {
+            Condition::Selection(i) => selections[*i].is_match(event),
+            Condition::Not(c) => !c.is_match(selections, event),
+            Condition::And(a, b) => a.is_match(selections, event) && b.is_match(selections, event),
+            Condition::Or(a, b) => a.is_match(selections, event) || b.is_match(selections, event),
+            Condition::OneOf(s) => s.iter().any(|i| selections[*i].is_match(event)),
+            Condition::AllOf(s) => s.iter().all(|i| selections[*i].is_match(event)),
+        }
+    }
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn wildcard_to_regex(pattern: &str) -> String {
+    let mut re = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '\\' => match chars.next() {
+                Some(n @ ('*' | '?' | '\\')) => re.push_str(&regex::escape(&n.to_string())),
+                Some(n) => {
+                    re.push_str(&regex::escape("\\"));
+                    re.push_str(&regex::escape(&n.to_string()));
+                }
+                None => re.push_str(&regex::escape("\\")),
+            },
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re
+}
+
+fn compile_matcher(value: &Value, modifiers: &[&str]) -> Result<Matcher> {
+    if value.is_null() {
+        return Ok(Matcher::Null);
+    }
+    let value = value_to_string(value).context("Unsupported Sigma value")?;
+
+    if modifiers.contains(&"re") {
+        let re = RegexBuilder::new(&value)
+            .case_insensitive(modifiers.contains(&"i"))
+            .multi_line(modifiers.contains(&"m"))
+            .dot_matches_new_line(modifiers.contains(&"s"))
+            .build()?;
+        return Ok(Matcher::Pattern(re));
+    }
+
+    let body = wildcard_to_regex(&value);
+    let pattern = if modifiers.contains(&"contains") {
+        format!(".*{}.*", body)
+    } else if modifiers.contains(&"startswith") {
+        format!("{}.*", body)
+    } else if modifiers.contains(&"endswith") {
+        format!(".*{}", body)
+    } else {
+        body
+    };
+    let re = RegexBuilder::new(&format!("^(?s:{})$", pattern))
+        .case_insensitive(true)
+        .build()?;
+    Ok(Matcher::Pattern(re))
+}
+
+fn compile_field_conditions(map: &Mapping) -> Result<Vec<FieldCondition>> {
+    let mut conditions = Vec::new();
+    for (key, value) in map {
+        let key = key.as_str().context("Sigma field name must be a string")?;
+        let mut parts = key.split('|');
+        let field = parts.next().unwrap_or_default().to_string();
+        let modifiers: Vec<&str> = parts.collect();
+        let regex = modifiers.contains(&"re");
+        for modifier in &modifiers {
+            let supported = ["contains", "startswith", "endswith", "re", "all"].contains(modifier)
+                || (regex && ["i", "m", "s"].contains(modifier));
+            if !supported {
+                bail!("Unsupported Sigma modifier: {}", modifier);
+            }
+        }
+
+        let values = match value {
+            Value::Sequence(values) => values.clone(),
+            v => vec![v.clone()],
+        };
+        let matchers = values
+            .iter()
+            .map(|v| compile_matcher(v, &modifiers))
+            .collect::<Result<Vec<_>>>()?;
+        conditions.push(FieldCondition {
+            field,
+            matchers,
+            all: modifiers.contains(&"all"),
+        });
+    }
+    Ok(conditions)
+}
+
+fn compile_selection(value: &Value) -> Result<Selection> {
+    match value {
+        Value::Mapping(map) => Ok(Selection::Fields(vec![compile_field_conditions(map)?])),
+        Value::Sequence(items) if items.iter().all(Value::is_mapping) => {
+            let alternatives = items
+                .iter()
+                .filter_map(Value::as_mapping)
+                .map(compile_field_conditions)
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Selection::Fields(alternatives))
+        }
+        Value::Sequence(items) => {
+            let keywords = items
+                .iter()
+                .map(|v| compile_matcher(v, &["contains"]))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Selection::Keywords(keywords))
+        }
+        v => Ok(Selection::Keywords(vec![compile_matcher(
+            v,
+            &["contains"],
+        )?])),
+    }
+}
+
+struct ConditionParser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+    names: &'a [String],
+}
+
+impl<'a> ConditionParser<'a> {
+    fn new(condition: &'a str, names: &'a [String]) -> Self {
+        let tokens = condition
+            .split_whitespace()
+            .flat_map(|t| {
+                let mut parts = Vec::new();
+                let mut rest = t;
+                while let Some(stripped) = rest.strip_prefix('(') {
+                    parts.push("(");
+                    rest = stripped;
+                }
+                let closing = rest.len() - rest.trim_end_matches(')').len();
+                let word = &rest[..rest.len() - closing];
+                if !word.is_empty() {
+                    parts.push(word);
+                }
+                parts.extend(std::iter::repeat_n(")", closing));
+                parts
+            })
+            .collect();
+        Self {
+            tokens,
+            position: 0,
+            names,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn parse(mut self) -> Result<Condition> {
+        let condition = self.parse_or()?;
+        match self.peek() {
+            None => Ok(condition),
+            Some(t) => Err(anyhow!("Unexpected token in Sigma condition: {}", t)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Condition> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some("or") {
+            self.next();
+            left = Condition::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition> {
+        let mut left = self.parse_not()?;
+        while self.peek() == Some("and") {
+            self.next();
+            left = Condition::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Condition> {
+        if self.peek() == Some("not") {
+            self.next();
+            return Ok(Condition::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn matching(&self, pattern: &str) -> Result<Vec<usize>> {
+        let indices: Vec<usize> = if pattern == "them" {
+            (0..self.names.len()).collect()
+        } else {
+            let re = Regex::new(&format!("^{}$", wildcard_to_regex(pattern)))?;
+            self.names
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| re.is_match(n))
+                .map(|(i, _)| i)
+                .collect()
+        };
+        if indices.is_empty() {
+            bail!("No Sigma selection matches {}", pattern);
+        }
+        Ok(indices)
+    }
+
+    fn parse_atom(&mut self) -> Result<Condition> {
+        match self.next() {
+            Some("(") => {
+                let condition = self.parse_or()?;
+                match self.next() {
+                    Some(")") => Ok(condition),
+                    _ => Err(anyhow!("Unbalanced parenthesis in Sigma condition")),
+                }
+            }
+            Some(quantifier @ ("1" | "all")) if self.peek() == Some("of") => {
+                self.next();
+                let pattern = self.next().context("Missing selection after 'of'")?;
+                let indices = self.matching(pattern)?;
+                Ok(if quantifier == "1" {
+                    Condition::OneOf(indices)
+                } else {
+                    Condition::AllOf(indices)
+                })
+            }
+            Some(name) => {
+                let index = self
+                    .names
+                    .iter()
+                    .position(|n| n == name)
+                    .with_context(|| format!("Unknown Sigma selection: {}", name))?;
+                Ok(Condition::Selection(index))
+            }
+            None => Err(anyhow!("Unexpected end of Sigma condition")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SigmaRule {
+    pub title: String,
+    pub id: Option<String>,
+    pub level: Option<String>,
+    pub tags: Vec<String>,
+    event_ids: Option<&'static [SysmonEventId]>,
+    selections: Vec<Selection>,
+    conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SigmaMatch {
+    pub title: String,
+    pub id: Option<String>,
+    pub level: Option<String>,
+    pub tags: Vec<String>,
+    pub event: SysmonEvent,
+}
+
+impl SigmaRule {
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let raw: RawRule = serde_yaml::from_str(yaml)?;
+
+        let event_ids = match &raw.logsource.category {
+            Some(category) => Some(
+                category_event_ids(category)
+                    .with_context(|| format!("Unsupported Sigma category: {}", category))?,
+            ),
+            None => None,
+        };
+
+        let mut names = Vec::new();
+        let mut selections = Vec::new();
+        let mut condition_values = Vec::new();
+        for (key, value) in &raw.detection {
+            let key = key
+                .as_str()
+                .context("Sigma detection key must be a string")?;
+            match key {
+                "condition" => condition_values.push(value.clone()),
+                "timeframe" => bail!("Sigma timeframe is not supported"),
+                _ => {
+                    names.push(key.to_string());
+                    selections.push(compile_selection(value)?);
+                }
+            }
+        }
+
+        let mut conditions = Vec::new();
+        for value in condition_values {
+            let list = match value {
+                Value::Sequence(list) => list,
+                v => vec![v],
+            };
+            for condition in list {
+                let condition = condition
+                    .as_str()
+                    .context("Sigma condition must be a string")?;
+                if condition.contains('|') {
+                    bail!("Sigma aggregation conditions are not supported");
+                }
+                conditions.push(ConditionParser::new(condition, &names).parse()?);
+            }
+        }
+        if conditions.is_empty() {
+            bail!("Sigma rule {} has no condition", raw.title);
+        }
+
+        Ok(Self {
+            title: raw.title,
+            id: raw.id,
+            level: raw.level,
+            tags: raw.tags,
+            event_ids,
+            selections,
+            conditions,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_yaml(&fs::read_to_string(path)?)
+            .with_context(|| format!("Failed to load Sigma rule {}", path.display()))
+    }
+
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<Self>> {
+        let mut rules = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                rules.extend(Self::load_dir(&path)?);
+            } else if path.extension().is_some_and(|e| e == "yml" || e == "yaml") {
+                rules.push(Self::from_file(&path)?);
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn is_match(&self, event: &SysmonEvent) -> bool {
+        if let Some(event_ids) = self.event_ids {
+            if !event_ids.contains(&event.event_id) {
+                return false;
+            }
+        }
+        self.conditions
+            .iter()
+            .any(|c| c.is_match(&self.selections, event))
+    }
+}
+
+pub fn evaluate(rules: &[SigmaRule], events: &[SysmonEvent]) -> Vec<SigmaMatch> {
+    let mut result = Vec::new();
+    for event in events {
+        for rule in rules {
+            if rule.is_match(event) {
+                result.push(SigmaMatch {
+                    title: rule.title.clone(),
+                    id: rule.id.clone(),
+                    level: rule.level.clone(),
+                    tags: rule.tags.clone(),
+                    event: event.clone(),
+                });
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn event(event_id: SysmonEventId, fields: &[(&str, &str)]) -> SysmonEvent {
+        SysmonEvent {
+            event_id,
+            time_created: DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap(),
+            computer: None,
+            record_id: None,
+            channel: None,
+            event_data: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    fn process(image: &str, command_line: &str) -> SysmonEvent {
+        event(
+            SysmonEventId::PROCESS_CREATE,
+            &[("Image", image), ("CommandLine", command_line)],
+        )
+    }
+
+    fn rule(detection: &str) -> Result<SigmaRule> {
+        SigmaRule::from_yaml(&format!(
+            "title: Test\nlogsource:\n  category: process_creation\ndetection:\n{}",
+            detection
+        ))
+    }
+
+    fn matches(detection: &str, event: &SysmonEvent) -> bool {
+        rule(detection).unwrap().is_match(event)
+    }
+
+    #[test]
+    fn plain_values_are_case_insensitive_wildcards() {
+        let event = process(r"C:\Windows\System32\cmd.exe", "cmd /c whoami");
+        assert!(matches(
+            "  sel:\n    Image: 'c:\\windows\\system32\\CMD.EXE'\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  sel:\n    Image: '*\\cmd.ex?'\n  condition: sel",
+            &event
+        ));
+        assert!(!matches(
+            "  sel:\n    Image: 'cmd.exe'\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  sel:\n    CommandLine|contains: WHOAMI\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  sel:\n    CommandLine|startswith: 'cmd /c'\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  sel:\n    Image|endswith: '\\cmd.exe'\n  condition: sel",
+            &event
+        ));
+        assert!(!matches(
+            "  sel:\n    Image|startswith: '\\cmd.exe'\n  condition: sel",
+            &event
+        ));
+    }
+
+    #[test]
+    fn escaped_wildcards_match_literally() {
+        let star = process("a*b", "");
+        let plain = process("axxb", "");
+        let detection = "  sel:\n    Image: 'a\\*b'\n  condition: sel";
+        assert!(matches(detection, &star));
+        assert!(!matches(detection, &plain));
+    }
+
+    #[test]
+    fn regex_is_case_sensitive_unless_i_is_given() {
+        let event = process("", "powershell -EncodedCommand AAAA");
+        assert!(matches(
+            "  sel:\n    CommandLine|re: '-EncodedCommand\\s'\n  condition: sel",
+            &event
+        ));
+        assert!(!matches(
+            "  sel:\n    CommandLine|re: '-encodedcommand\\s'\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  sel:\n    CommandLine|re|i: '-encodedcommand\\s'\n  condition: sel",
+            &event
+        ));
+    }
+
+    #[test]
+    fn regex_accepts_multiline_and_dotall() {
+        let event = process("", "first\nsecond");
+        assert!(!matches(
+            "  sel:\n    CommandLine|re: '^second$'\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  sel:\n    CommandLine|re|m: '^second$'\n  condition: sel",
+            &event
+        ));
+        assert!(!matches(
+            "  sel:\n    CommandLine|re: 'first.second'\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  sel:\n    CommandLine|re|s: 'first.second'\n  condition: sel",
+            &event
+        ));
+    }
+
+    #[test]
+    fn unsupported_modifiers_are_rejected() {
+        for detection in [
+            "  sel:\n    Image|cased: x\n  condition: sel",
+            "  sel:\n    Image|contains|i: x\n  condition: sel",
+            "  sel:\n    Image|base64: x\n  condition: sel",
+        ] {
+            assert!(rule(detection).is_err(), "{}", detection);
+        }
+    }
+
+    #[test]
+    fn value_lists_are_or_unless_all_is_given() {
+        let event = process("", "net user /add admin");
+        assert!(matches(
+            "  sel:\n    CommandLine|contains:\n      - whoami\n      - '/add'\n  condition: sel",
+            &event
+        ));
+        assert!(!matches(
+            "  sel:\n    CommandLine|contains|all:\n      - whoami\n      - '/add'\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  sel:\n    CommandLine|contains|all:\n      - 'net '\n      - '/add'\n  condition: sel",
+            &event
+        ));
+    }
+
+    #[test]
+    fn null_matches_missing_or_empty_fields() {
+        let detection = "  sel:\n    ParentImage: null\n  condition: sel";
+        assert!(matches(detection, &process("a", "b")));
+        assert!(matches(
+            detection,
+            &event(SysmonEventId::PROCESS_CREATE, &[("ParentImage", "")])
+        ));
+        assert!(!matches(
+            detection,
+            &event(SysmonEventId::PROCESS_CREATE, &[("ParentImage", "x")])
+        ));
+    }
+
+    #[test]
+    fn selections_can_be_alternatives_or_keywords() {
+        let event = process(r"C:\Tools\rundll32.exe", "rundll32 evil.dll,Start");
+        assert!(matches(
+            "  sel:\n    - Image|endswith: '\\cmd.exe'\n    - CommandLine|contains: 'evil.dll'\n  condition: sel",
+            &event
+        ));
+        assert!(matches(
+            "  keywords:\n    - mimikatz\n    - EVIL.DLL\n  condition: keywords",
+            &event
+        ));
+        assert!(!matches(
+            "  keywords:\n    - mimikatz\n  condition: keywords",
+            &event
+        ));
+    }
+
+    #[test]
+    fn conditions_combine_selections() {
+        let event = process(r"C:\Windows\cmd.exe", "cmd /c whoami");
+        let selections = "  sel_image:\n    Image|endswith: '\\cmd.exe'\n  \
+                          sel_whoami:\n    CommandLine|contains: whoami\n  \
+                          filter:\n    CommandLine|contains: '/k'\n";
+        let check =
+            |condition: &str| matches(&format!("{}  condition: {}", selections, condition), &event);
+        assert!(check("sel_image and sel_whoami"));
+        assert!(check("sel_image and not filter"));
+        assert!(!check("sel_image and filter"));
+        assert!(check("filter or sel_whoami"));
+        assert!(check(
+            "(filter or sel_image) and not (filter and sel_whoami)"
+        ));
+        assert!(check("all of sel_*"));
+        assert!(check("1 of sel_* and not filter"));
+        assert!(!check("all of them"));
+        assert!(check("1 of them"));
+    }
+
+    #[test]
+    fn invalid_conditions_are_rejected() {
+        let selection = "  sel:\n    Image: x\n";
+        for condition in [
+            "missing",
+            "(sel",
+            "sel and",
+            "sel sel",
+            "1 of nothing_*",
+            "sel | count() > 5",
+        ] {
+            assert!(
+                rule(&format!("{}  condition: {}", selection, condition)).is_err(),
+                "{}",
+                condition
+            );
+        }
+        assert!(rule(&format!("{}  timeframe: 5m\n  condition: sel", selection)).is_err());
+        assert!(rule(selection).is_err());
+    }
+
+    #[test]
+    fn category_restricts_event_ids() {
+        let detection = "  sel:\n    Image|endswith: '.exe'\n  condition: sel";
+        assert!(matches(detection, &process("a.exe", "")));
+        assert!(!matches(
+            detection,
+            &event(SysmonEventId::IMAGE_LOAD, &[("Image", "a.exe")])
+        ));
+        assert!(SigmaRule::from_yaml(
+            "title: Test\nlogsource:\n  category: unknown\ndetection:\n  sel:\n    Image: x\n  condition: sel"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn evaluate_reports_rule_metadata() {
+        let rule = SigmaRule::from_yaml(
+            "title: Whoami\nid: 1234\nlevel: high\ntags:\n  - attack.t1033\n\
+             logsource:\n  category: process_creation\n\
+             detection:\n  sel:\n    CommandLine|contains: whoami\n  condition: sel",
+        )
+        .unwrap();
+        let events = [process("a", "whoami /all"), process("b", "ipconfig")];
+        let found = evaluate(&[rule], &events);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].title, "Whoami");
+        assert_eq!(found[0].id.as_deref(), Some("1234"));
+        assert_eq!(found[0].level.as_deref(), Some("high"));
+        assert_eq!(found[0].tags, ["attack.t1033"]);
+        assert_eq!(found[0].event.event_data["Image"], "a");
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chrono::Duration;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::path::normalize;
+use crate::sysmon_event::SysmonEvent;
+
+const MAX_PARTIAL_MATCHES: usize = 10_000;
+
+const BUILTIN_SIGNATURES: &[&str] = &[
+    r#"
+name = "Dropped executable registered as scheduled task"
+level = "high"
+tags = ["attack.persistence", "attack.t1053.005"]
+within = 3600
+
+[[steps]]
+event_id = 11
+capture = { dropped = "TargetFilename" }
+match = { TargetFilename = { ends_with = ".exe" } }
+
+[[steps]]
+event_id = 1
+match = { Image = { ends_with = "\\schtasks.exe" }, CommandLine = { contains = "$dropped" } }
+"#,
+    r#"
+name = "Dropped executable registered as autostart"
+level = "high"
+tags = ["attack.persistence", "attack.t1547.001"]
+within = 3600
+
+[[steps]]
+event_id = 11
+capture = { dropped = "TargetFilename" }
+match = { TargetFilename = { ends_with = ".exe" } }
+
+[[steps]]
+event_id = 13
+match = { TargetObject = { contains = "\\CurrentVersion\\Run" }, Details = { contains = "$dropped" } }
+"#,
+    r#"
+name = "Process executed a file it dropped"
+level = "medium"
+tags = ["attack.execution"]
+within = 600
+
+[[steps]]
+event_id = 11
+capture = { dropped = "TargetFilename", writer = "ProcessGuid" }
+
+[[steps]]
+event_id = 1
+match = { Image = { equals = "$dropped" }, ParentProcessGuid = { equals = "$writer" } }
+"#,
+];
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SignatureMatch {
+    pub signature: String,
+    pub level: Option<String>,
+    pub tags: Vec<String>,
+    pub captures: HashMap<String, String>,
+    pub events: Vec<SysmonEvent>,
+}
+
+pub trait Signature: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn evaluate(&self, events: &[SysmonEvent]) -> Vec<SignatureMatch>;
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    pub equals: Option<String>,
+    pub contains: Option<String>,
+    pub starts_with: Option<String>,
+    pub ends_with: Option<String>,
+    pub regex: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub event_id: Option<u8>,
+    #[serde(default, rename = "match")]
+    pub conditions: HashMap<String, Condition>,
+    #[serde(default)]
+    pub capture: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSignature {
+    pub name: String,
+    pub level: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub within: Option<i64>,
+    pub steps: Vec<Step>,
+    #[serde(skip)]
+    regexes: HashMap<String, Regex>,
+}
+
+fn substitute(value: &str, captures: &HashMap<String, String>) -> Option<String> {
+    match value.strip_prefix('$') {
+        Some(name) if !name.starts_with('$') => captures.get(name).cloned(),
+        Some(escaped) => Some(escaped.to_string()),
+        None => Some(value.to_string()),
+    }
+}
+
+impl RuleSignature {
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let mut signature: Self = toml::from_str(toml)?;
+        if signature.steps.is_empty() {
+            bail!("Signature {} has no steps", signature.name);
+        }
+        for step in &signature.steps {
+            for condition in step.conditions.values() {
+                if let Some(pattern) = &condition.regex {
+                    let regex = RegexBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .with_context(|| format!("Invalid regex in {}", signature.name))?;
+                    signature.regexes.insert(pattern.clone(), regex);
+                }
+            }
+        }
+        Ok(signature)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_toml(&fs::read_to_string(path)?)
+            .with_context(|| format!("Failed to load signature {}", path.display()))
+    }
+
+    fn condition_matches(
+        &self,
+        actual: &str,
+        condition: &Condition,
+        captures: &HashMap<String, String>,
+    ) -> bool {
+        let actual = normalize(actual);
+        let check = |expected: &Option<String>, op: fn(&str, &str) -> bool| match expected {
+            Some(expected) => substitute(expected, captures)
+                .is_some_and(|expected| op(&actual, &normalize(&expected))),
+            None => true,
+        };
+        check(&condition.equals, |a, e| a == e)
+            && check(&condition.contains, |a, e| a.contains(e))
+            && check(&condition.starts_with, |a, e| a.starts_with(e))
+            && check(&condition.ends_with, |a, e| a.ends_with(e))
+            && condition.regex.as_ref().is_none_or(|pattern| {
+                self.regexes
+                    .get(pattern)
+                    .is_some_and(|r| r.is_match(&actual))
+            })
+    }
+
+    fn step_matches(
+        &self,
+        step: &Step,
+        event: &SysmonEvent,
+        captures: &HashMap<String, String>,
+    ) -> bool {
+        if step.event_id.is_some_and(|id| id != event.event_id.value()) {
+            return false;
+        }
+        step.conditions.iter().all(|(field, condition)| {
+            event
+                .event_data
+                .get(field)
+                .is_some_and(|actual| self.condition_matches(actual, condition, captures))
+        })
+    }
+}
+
+struct Partial {
+    step: usize,
+    captures: HashMap<String, String>,
+    events: Vec<SysmonEvent>,
+}
+
+impl Partial {
+    fn advance(&self, step: &Step, event: &SysmonEvent) -> Self {
+        let mut captures = self.captures.clone();
+        for (name, field) in &step.capture {
+            if let Some(value) = event.event_data.get(field) {
+                captures.insert(name.clone(), value.clone());
+            }
+        }
+        let mut events = self.events.clone();
+        events.push(event.clone());
+        Self {
+            step: self.step + 1,
+            captures,
+            events,
+        }
+    }
+}
+
+impl Signature for RuleSignature {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&self, events: &[SysmonEvent]) -> Vec<SignatureMatch> {
+        let within = self.within.map(Duration::seconds);
+        let mut partials: Vec<Partial> = Vec::new();
+        let mut matches = Vec::new();
+
+        for event in events {
+            if let Some(within) = within {
+                partials.retain(|p| {
+                    p.events
+                        .first()
+                        .is_none_or(|first| event.time_created - first.time_created <= within)
+                });
+            }
+
+            let mut advanced = Vec::new();
+            for partial in &partials {
+                let step = &self.steps[partial.step];
+                if self.step_matches(step, event, &partial.captures) {
+                    advanced.push(partial.advance(step, event));
+                }
+            }
+            let start = Partial {
+                step: 0,
+                captures: HashMap::new(),
+                events: Vec::new(),
+            };
+            if self.step_matches(&self.steps[0], event, &start.captures) {
+                advanced.push(start.advance(&self.steps[0], event));
+            }
+
+            for partial in advanced {
+                if partial.step == self.steps.len() {
+                    matches.push(SignatureMatch {
+                        signature: self.name.clone(),
+                        level: self.level.clone(),
+                        tags: self.tags.clone(),
+                        captures: partial.captures,
+                        events: partial.events,
+                    });
+                } else if partials.len() < MAX_PARTIAL_MATCHES {
+                    partials.push(partial);
+                }
+            }
+        }
+        matches
+    }
+}
+
+#[derive(Default)]
+pub struct SignatureRegistry {
+    signatures: Vec<Box<dyn Signature>>,
+}
+
+impl fmt::Debug for SignatureRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.signatures.iter().map(|s| s.name()))
+            .finish()
+    }
+}
+
+impl SignatureRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        for toml in BUILTIN_SIGNATURES {
+            registry.register(RuleSignature::from_toml(toml).expect("valid builtin signature"));
+        }
+        registry
+    }
+
+    pub fn register<T: Signature + 'static>(&mut self, signature: T) {
+        self.signatures.push(Box::new(signature));
+    }
+
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.load_dir(&path)?;
+            } else if path.extension().is_some_and(|e| e == "toml") {
+                self.register(RuleSignature::from_file(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    pub fn evaluate(&self, events: &[SysmonEvent]) -> Vec<SignatureMatch> {
+        self.signatures
+            .iter()
+            .flat_map(|s| s.evaluate(events))
+            .collect()
+    }
+}
@@ -11,7 +11,7 @@ pub fn detect<T: AsRef<Path>, U: AsRef<Path>>(
     let compiler = Compiler::new()?.add_rules_file(yara_path)?;
     let rules = compiler.compile_rules()?;
     let results = rules.scan_file(sample_path, timeout)?;
-    if results.len() > 0 {
+    if !results.is_empty() {
         Ok(true)
     } else {
         Ok(false)
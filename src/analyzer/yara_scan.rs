@@ -0,0 +1,235 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use yara::{Compiler, Rule, Rules};
+
+use crate::analysis_result::{artifact_dir, scripts_dir, ExecutionLog};
+use crate::path::normalize;
+use crate::static_analysis::{ExtractedScript, CAPTURED_DIR, DEOBFUSCATED_DIR, SCRIPTS_FILE};
+use crate::sysmon_event::SysmonEvent;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MatchedString {
+    pub identifier: String,
+    pub offset: usize,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct YaraMatch {
+    pub rule: String,
+    pub namespace: String,
+    pub tags: Vec<String>,
+    pub strings: Vec<MatchedString>,
+}
+
+impl From<Rule<'_>> for YaraMatch {
+    fn from(rule: Rule) -> Self {
+        Self {
+            rule: rule.identifier.to_string(),
+            namespace: rule.namespace.to_string(),
+            tags: rule.tags.iter().map(|t| t.to_string()).collect(),
+            strings: rule
+                .strings
+                .into_iter()
+                .flat_map(|s| {
+                    let identifier = s.identifier.to_string();
+                    s.matches.into_iter().map(move |m| MatchedString {
+                        identifier: identifier.clone(),
+                        offset: m.base + m.offset,
+                        data: m.data,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtifactMatch {
+    pub original_path: String,
+    pub artifact_path: PathBuf,
+    pub matches: Vec<YaraMatch>,
+    pub events: Vec<SysmonEvent>,
+}
+
+fn rule_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(rule_files(&path)?);
+        } else if path.extension().is_some_and(|e| e == "yar" || e == "yara") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+pub struct YaraScanner {
+    rules: Rules,
+    timeout: i32,
+}
+
+impl YaraScanner {
+    pub fn new<P: AsRef<Path>>(yara_path: P, timeout: i32) -> Result<Self> {
+        let compiler = Compiler::new()?.add_rules_file(yara_path)?;
+        Ok(Self {
+            rules: compiler.compile_rules()?,
+            timeout,
+        })
+    }
+
+    pub fn from_dir<P: AsRef<Path>>(dir: P, timeout: i32) -> Result<Self> {
+        let mut compiler = Compiler::new()?;
+        for path in rule_files(dir.as_ref())? {
+            compiler = compiler.add_rules_file(&path)?;
+        }
+        Ok(Self {
+            rules: compiler.compile_rules()?,
+            timeout,
+        })
+    }
+
+    pub fn scan_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<YaraMatch>> {
+        let results = self.rules.scan_file(path, self.timeout)?;
+        Ok(results.into_iter().map(YaraMatch::from).collect())
+    }
+
+    pub fn scan_buffer(&self, buffer: &[u8]) -> Result<Vec<YaraMatch>> {
+        let results = self.rules.scan_mem(buffer, self.timeout)?;
+        Ok(results.into_iter().map(YaraMatch::from).collect())
+    }
+
+    pub fn scan_execution_artifacts(
+        &self,
+        id: &str,
+        execution_log: &ExecutionLog,
+    ) -> Vec<ArtifactMatch> {
+        let artifact_dir = artifact_dir(id, &execution_log.id);
+        let mut result = Vec::new();
+        for (original_path, artifact) in &execution_log.created_files {
+            let artifact_path = Path::new(&artifact_dir).join(artifact);
+            if !artifact_path.exists() {
+                continue;
+            }
+            let matches = match self.scan_file(&artifact_path) {
+                Ok(matches) => matches,
+                Err(e) => {
+                    warn!("Failed to scan '{}': {}", original_path, e);
+                    continue;
+                }
+            };
+            if matches.is_empty() {
+                continue;
+            }
+
+            let normalized = normalize(original_path);
+            let events = execution_log
+                .sysmon_events
+                .iter()
+                .filter(|e| {
+                    e.event_data
+                        .get("TargetFilename")
+                        .is_some_and(|f| normalize(f) == normalized)
+                })
+                .cloned()
+                .collect();
+
+            result.push(ArtifactMatch {
+                original_path: original_path.clone(),
+                artifact_path,
+                matches,
+                events,
+            });
+        }
+        result
+    }
+
+    pub fn scan_extracted_scripts(&self, id: &str) -> Result<Vec<ArtifactMatch>> {
+        self.scan_scripts_dir(scripts_dir(id))
+    }
+
+    pub fn scan_deobfuscated_scripts(
+        &self,
+        id: &str,
+        execution_log: &ExecutionLog,
+    ) -> Result<Vec<ArtifactMatch>> {
+        self.scan_scripts_dir(format!(
+            "{}/{}",
+            artifact_dir(id, &execution_log.id),
+            DEOBFUSCATED_DIR
+        ))
+    }
+
+    pub fn scan_captured_scripts(
+        &self,
+        id: &str,
+        execution_log: &ExecutionLog,
+    ) -> Result<Vec<ArtifactMatch>> {
+        self.scan_scripts_dir(format!(
+            "{}/{}",
+            artifact_dir(id, &execution_log.id),
+            CAPTURED_DIR
+        ))
+    }
+
+    pub fn scan_scripts(&self, scripts: &[ExtractedScript]) -> Vec<(String, Vec<YaraMatch>)> {
+        let mut result = Vec::new();
+        for script in scripts {
+            let layers = [&script.source, &script.normalized]
+                .into_iter()
+                .chain(&script.decoded);
+            let mut matches: Vec<YaraMatch> = Vec::new();
+            for layer in layers {
+                match self.scan_buffer(layer.as_bytes()) {
+                    Ok(found) => {
+                        for m in found {
+                            if !matches.iter().any(|e| e.rule == m.rule) {
+                                matches.push(m);
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Failed to scan script '{}': {}", script.name, e),
+                }
+            }
+            if !matches.is_empty() {
+                result.push((script.name.clone(), matches));
+            }
+        }
+        result
+    }
+
+    fn scan_scripts_dir(&self, dir: String) -> Result<Vec<ArtifactMatch>> {
+        if !Path::new(&dir).exists() {
+            return Ok(Vec::new());
+        }
+        let mut result = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let artifact_path = entry?.path();
+            if artifact_path.file_name().is_some_and(|n| n == SCRIPTS_FILE) {
+                continue;
+            }
+            let matches = match self.scan_file(&artifact_path) {
+                Ok(matches) => matches,
+                Err(e) => {
+                    warn!("Failed to scan '{}': {}", artifact_path.display(), e);
+                    continue;
+                }
+            };
+            if matches.is_empty() {
+                continue;
+            }
+            result.push(ArtifactMatch {
+                original_path: artifact_path.display().to_string(),
+                artifact_path,
+                matches,
+                events: Vec::new(),
+            });
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,1725 @@
+pub mod portal;
+#[cfg(feature = "ui")]
+mod ui;
+pub mod users;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use axum::body::{Bytes, StreamBody};
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, patch, post};
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Local, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use sha3::{Digest, Sha3_512};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::agent::hardening::{HardeningProfile, BUILTIN_PROFILES};
+use crate::agent::protocol::{DumpScope, DumpTrigger, ExecutionRequest, TimeWarp};
+use crate::analysis_result::{
+    artifact_dir, container_path, sample_path, scripts_dir, AnalysisResult, AnalysisResultManager,
+    ExecutionLog,
+};
+use crate::analyzer::capability::CapabilityRules;
+use crate::analyzer::code_signing::TrustPolicy;
+use crate::analyzer::driver::DriverBlocklist;
+use crate::analyzer::fingerprint::FingerprintBlocklist;
+use crate::analyzer::live::{LiveMonitor, LiveStatus};
+use crate::analyzer::parentage::ParentageRules;
+use crate::analyzer::sigma::SigmaRule;
+use crate::analyzer::signature::SignatureRegistry;
+use crate::baseline::BaselineStore;
+use crate::browsing::{is_detonatable_url, URL_FILE_NAME};
+use crate::config_extractor::ConfigExtractorRegistry;
+use crate::enrichment::geo::GeoDatabase;
+use crate::event_filter::EventFilter;
+use crate::export::archive::{export_run_to_vec, ArchiveFormat, ArchiveOptions};
+use crate::export::graph::{report_to_cypher, report_to_graphml};
+use crate::export::timesketch::report_to_timesketch;
+use crate::filesystem::FilesystemOptions;
+use crate::lineage::{self, can_derive, Derivation, DerivationKind, Descendant};
+use crate::metadata::{Facet, MetadataUpdate, SampleMetadata};
+use crate::metrics::{self, REPORT_DURATION};
+use crate::orchestrator::limits::ResourceLimits;
+use crate::orchestrator::Hypervisor;
+use crate::pipeline::{Pipeline, PipelineOptions, Progress, ProgressSnapshot, RecordFormat};
+use crate::plugin::AnalyzerRegistry;
+use crate::quarantine::{FileQuarantine, QUARANTINE_FILE};
+use crate::registry::RegistryDiff;
+use crate::report::{ReportDiff, SandboxReport};
+use crate::rule_pack::{RulePackManager, RulePackStatus};
+use crate::scheduler::profile::ProfileRegistry;
+use crate::scheduler::{Job, JobState, JobStore, Scheduler};
+use crate::schema::SchemaKind;
+use crate::scoring::ScoringOptions;
+use crate::similarity::index::{SimilarFile, SimilarityIndex};
+use crate::static_analysis::container::{self, container_kind, write_container};
+use crate::static_analysis::email;
+use crate::static_analysis::pe::is_pe;
+use crate::static_analysis::{extract_scripts, write_scripts};
+use crate::storage::retention::{stored_report_path, DataClass};
+use crate::storage::search::{EventHit, EventQuery, DEFAULT_LIMIT};
+use crate::storage::sqlite::SqliteResultStore;
+use crate::storage::{ResultStore, RunQuery, StoredRun};
+use crate::timeline::{Cursor, EntryKind, TimelineFilter};
+use crate::verdict::VerdictPolicy;
+use portal::Portal;
+use users::{Principal, Role, UserStore};
+
+const API_KEY_HEADER: &str = "x-api-key";
+const MAX_PER_PAGE: usize = 100;
+const DEFAULT_MIN_SIMILARITY: u32 = 50;
+
+#[derive(Debug)]
+pub struct ApiConfig {
+    pub api_keys: Vec<String>,
+    pub rules: Vec<SigmaRule>,
+    pub signatures: SignatureRegistry,
+    pub capabilities: CapabilityRules,
+    pub trust: TrustPolicy,
+    pub parentage: ParentageRules,
+    pub max_upload_size: usize,
+    pub similarity: Option<Arc<Mutex<SimilarityIndex>>>,
+    pub scoring: ScoringOptions,
+    pub verdict: VerdictPolicy,
+    pub filesystem: FilesystemOptions,
+    pub drivers: DriverBlocklist,
+    pub fingerprints: FingerprintBlocklist,
+    pub geo: Option<GeoDatabase>,
+    pub analyzers: AnalyzerRegistry,
+    pub config_extractors: ConfigExtractorRegistry,
+    pub store: Option<Arc<Mutex<SqliteResultStore>>>,
+    pub baselines: Option<BaselineStore>,
+    pub event_filter: Option<EventFilter>,
+    pub pipeline: PipelineOptions,
+    pub users: Option<Arc<Mutex<UserStore>>>,
+    pub live: Option<Arc<LiveMonitor>>,
+    pub profiles: ProfileRegistry,
+    pub portal: Option<Arc<Portal>>,
+    pub rule_packs: Option<Arc<RulePackManager>>,
+}
+
+impl ApiConfig {
+    pub fn report(&self, result: &AnalysisResult) -> anyhow::Result<SandboxReport> {
+        let mut span = metrics::timed("report.build", &REPORT_DURATION, &[]);
+        span.attribute("analysis.id", &result.id);
+        let baseline = match (&self.baselines, result.execution_logs.last()) {
+            (Some(baselines), Some(log)) => baselines.for_log(log)?,
+            _ => None,
+        };
+        let subtracted;
+        let result = match &baseline {
+            Some(baseline) => {
+                subtracted = baseline.subtract_result(result);
+                &subtracted
+            }
+            None => result,
+        };
+        let mut report = SandboxReport::from_analysis_result(result)?;
+        report.add_captured_traffic()?;
+        if let Some(geo) = &self.geo {
+            report.add_geo(geo);
+        }
+        report.add_sigma_detections(&self.rules);
+        report.add_signature_detections(&self.signatures);
+        if let Some(pack) = self.rule_packs.as_ref().and_then(|p| p.current()) {
+            report.add_sigma_detections(&pack.sigma);
+            report.add_signature_detections(&pack.signatures);
+            if let (Some(yara), Some(log)) = (&pack.yara, result.execution_logs.last()) {
+                report.add_yara_detections(&yara.scan_execution_artifacts(&result.id, log));
+            }
+        }
+        report.add_parentage_detections(&self.parentage);
+        report.add_fingerprint_detections(&self.fingerprints);
+        report.add_drivers(&self.drivers);
+        report.add_static_analysis()?;
+        report.add_code_signing(&self.trust);
+        report.add_capabilities(&self.capabilities)?;
+        report.add_configs(&self.config_extractors)?;
+        report.add_analyzers(&self.analyzers, result);
+        report.add_behavior_matrix(result)?;
+        report.add_enrichment()?;
+        report.add_filesystem_summary(&self.filesystem);
+        report.add_score(&self.scoring);
+        report.add_verdict(&self.verdict);
+        Ok(report)
+    }
+
+    pub fn principal(&self, key: &str) -> anyhow::Result<Option<Principal>> {
+        let digest = Sha256::digest(key.as_bytes());
+        if self
+            .api_keys
+            .iter()
+            .any(|k| Sha256::digest(k.as_bytes()) == digest)
+        {
+            return Ok(Some(Principal::root()));
+        }
+        let Some(users) = &self.users else {
+            return Ok(None);
+        };
+        Ok(users
+            .lock()
+            .map_err(|_| anyhow::anyhow!("User store is poisoned"))?
+            .authenticate(key))
+    }
+
+    pub fn persist(&self, report: &SandboxReport) -> anyhow::Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        store
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Result store is poisoned"))?
+            .save_report(report)
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            api_keys: Vec::new(),
+            rules: Vec::new(),
+            signatures: SignatureRegistry::with_defaults(),
+            capabilities: CapabilityRules::with_defaults(),
+            trust: TrustPolicy::default(),
+            parentage: ParentageRules::with_defaults(),
+            max_upload_size: 256 * 1024 * 1024,
+            similarity: None,
+            scoring: ScoringOptions::default(),
+            verdict: VerdictPolicy::default(),
+            filesystem: FilesystemOptions::default(),
+            drivers: DriverBlocklist::default(),
+            fingerprints: FingerprintBlocklist::default(),
+            geo: None,
+            analyzers: AnalyzerRegistry::default(),
+            config_extractors: ConfigExtractorRegistry::with_defaults(),
+            store: None,
+            baselines: None,
+            event_filter: None,
+            pipeline: PipelineOptions::default(),
+            users: None,
+            live: None,
+            profiles: ProfileRegistry::with_defaults(),
+            portal: None,
+            rule_packs: None,
+        }
+    }
+}
+
+pub struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn not_found(what: &str) -> Self {
+        Self(StatusCode::NOT_FOUND, format!("{} not found", what))
+    }
+
+    fn bad_request(message: &str) -> Self {
+        Self(StatusCode::BAD_REQUEST, message.to_string())
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+type Ingests = HashMap<String, (Option<String>, Arc<Progress>)>;
+
+struct ApiState<H, S> {
+    scheduler: Arc<Scheduler<H, S>>,
+    results: Arc<AnalysisResultManager>,
+    config: Arc<ApiConfig>,
+    ingests: Arc<Mutex<Ingests>>,
+    #[cfg(feature = "ui")]
+    sessions: Arc<ui::Sessions>,
+}
+
+impl<H, S> Clone for ApiState<H, S> {
+    fn clone(&self) -> Self {
+        Self {
+            scheduler: self.scheduler.clone(),
+            results: self.results.clone(),
+            config: self.config.clone(),
+            ingests: self.ingests.clone(),
+            #[cfg(feature = "ui")]
+            sessions: self.sessions.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SubmitParams {
+    file_name: Option<String>,
+    timeout: Option<u64>,
+    tags: Option<String>,
+    priority: Option<i32>,
+    screenshot: Option<bool>,
+    screenshot_interval: Option<u64>,
+    record_video: Option<bool>,
+    simulate_user: Option<bool>,
+    hardening: Option<String>,
+    time_warp: Option<f64>,
+    skip_sleeps: Option<bool>,
+    memory_dump: Option<String>,
+    dump_scope: Option<String>,
+    reboot: Option<bool>,
+    resubmit_dropped: Option<bool>,
+    url: Option<String>,
+    detonate_attachments: Option<bool>,
+    unpack: Option<bool>,
+    passwords: Option<String>,
+    cpus: Option<u32>,
+    cpu_cap: Option<u32>,
+    memory_limit: Option<u64>,
+    disk_limit: Option<u64>,
+    wall_clock_limit: Option<u64>,
+    cpu_saturation_limit: Option<u64>,
+    profiles: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Submitted {
+    job_id: String,
+    analysis_id: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct IngestParams {
+    format: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Ingest {
+    ingest_id: String,
+    analysis_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PageParams {
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+struct JobPage {
+    page: usize,
+    per_page: usize,
+    total: usize,
+    jobs: Vec<Job>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ReportParams {
+    format: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ResubmitParams {
+    timeout: Option<u64>,
+    priority: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ArchiveParams {
+    format: Option<String>,
+    password: Option<String>,
+    include_sample: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TimelineParams {
+    process_guid: Option<String>,
+    kind: Option<String>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    after: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RunParams {
+    hash: Option<String>,
+    domain: Option<String>,
+    mutex: Option<String>,
+    tag: Option<String>,
+    campaign: Option<String>,
+    source: Option<String>,
+    tlp: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchParams {
+    q: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SimilarParams {
+    min_score: Option<u32>,
+    limit: Option<usize>,
+}
+
+fn is_safe_component(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('.')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+async fn auth<H, S, B>(
+    State(state): State<ApiState<H, S>>,
+    headers: HeaderMap,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> ApiResult<Response> {
+    let key = headers
+        .get(API_KEY_HEADER)
+        .or_else(|| headers.get(header::AUTHORIZATION))
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.strip_prefix("Bearer ").unwrap_or(v));
+    #[cfg(feature = "ui")]
+    let session = match key {
+        Some(_) => None,
+        None => ui::session_id(&headers)
+            .and_then(|id| state.sessions.key(id, std::time::Instant::now())),
+    };
+    #[cfg(feature = "ui")]
+    let key = key.or(session.as_deref());
+    let principal = match key {
+        Some(key) => state.config.principal(key)?,
+        None => None,
+    };
+    match principal {
+        Some(principal) => {
+            request.extensions_mut().insert(principal);
+            Ok(next.run(request).await)
+        }
+        #[cfg(feature = "ui")]
+        _ if request.uri().path().starts_with("/ui") => {
+            Ok(axum::response::Redirect::to("/ui/login").into_response())
+        }
+        _ => Err(ApiError(
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid API key".to_string(),
+        )),
+    }
+}
+
+async fn visible_result<H, S>(
+    state: &ApiState<H, S>,
+    principal: &Principal,
+    id: &str,
+) -> ApiResult<AnalysisResult> {
+    principal.require(Role::Analyst)?;
+    state
+        .results
+        .get(id)
+        .await?
+        .filter(|r| principal.can_see(r.tenant.as_deref()))
+        .ok_or_else(|| ApiError::not_found("Analysis"))
+}
+
+async fn is_visible<H, S>(
+    state: &ApiState<H, S>,
+    principal: &Principal,
+    id: &str,
+) -> anyhow::Result<bool> {
+    if principal.tenant.is_none() {
+        return Ok(true);
+    }
+    Ok(state
+        .results
+        .get(id)
+        .await?
+        .is_some_and(|r| principal.can_see(r.tenant.as_deref())))
+}
+
+async fn store_sample(
+    results: &AnalysisResultManager,
+    body: &[u8],
+    tenant: Option<&str>,
+) -> anyhow::Result<(String, String)> {
+    let hash = format!("{:x}", Sha3_512::digest(body));
+    match results.search_hash_for(&hash, tenant).await? {
+        Some(r) => write_sample(&r.id, body).await,
+        None => store_new_sample(results, body, tenant).await,
+    }
+}
+
+async fn store_new_sample(
+    results: &AnalysisResultManager,
+    body: &[u8],
+    tenant: Option<&str>,
+) -> anyhow::Result<(String, String)> {
+    let hash = format!("{:x}", Sha3_512::digest(body));
+    let analysis_id = Uuid::new_v4().to_string();
+    results
+        .make_new_result_for(&analysis_id, &hash, tenant)
+        .await?;
+    write_sample(&analysis_id, body).await
+}
+
+async fn write_sample(analysis_id: &str, body: &[u8]) -> anyhow::Result<(String, String)> {
+    let sample_path = sample_path(analysis_id);
+    if let Some(sample_dir) = std::path::Path::new(&sample_path).parent() {
+        tokio::fs::create_dir_all(sample_dir)
+            .await
+            .context("Failed to create sample directory")?;
+    }
+    tokio::fs::write(&sample_path, body)
+        .await
+        .context("Failed to store sample")?;
+    Ok((analysis_id.to_string(), sample_path))
+}
+
+async fn submit_derived<H, S>(
+    scheduler: &Scheduler<H, S>,
+    results: &AnalysisResultManager,
+    template: &Job,
+    link: Derivation,
+    content: &[u8],
+) -> anyhow::Result<Option<String>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let (analysis_id, sample_path) =
+        store_sample(results, content, template.tenant.as_deref()).await?;
+    if analysis_id == template.analysis_id || !can_derive(&template.derivation, &analysis_id) {
+        return Ok(None);
+    }
+    let file_name = link
+        .artifact
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("sample")
+        .to_string();
+    let request = ExecutionRequest {
+        file_name,
+        arguments: Vec::new(),
+        reboot: false,
+        resubmit_dropped: false,
+        url: None,
+        ..template.request.clone()
+    };
+    let mut child = Job::new(&analysis_id, &sample_path, request);
+    child.priority = template.priority;
+    child.tags = template.tags.clone();
+    child.parent = link.job_id.clone();
+    child.tenant = template.tenant.clone();
+    child.limits = template.limits.clone();
+    child.derivation = lineage::extend(&template.derivation, link);
+    Ok(Some(scheduler.submit(child)?))
+}
+
+pub async fn resubmit_dropped<H, S>(
+    scheduler: &Scheduler<H, S>,
+    results: &AnalysisResultManager,
+    job: &Job,
+    log: &ExecutionLog,
+) -> anyhow::Result<Vec<String>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let mut children = Vec::new();
+    if !job.request.resubmit_dropped {
+        return Ok(children);
+    }
+    let dir = artifact_dir(&job.analysis_id, &log.id);
+    for artifact in log.artifacts.iter().filter(|a| !a.dumped()) {
+        let content = match tokio::fs::read(format!("{}/{}", dir, artifact.file_name())).await {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Failed to read dropped file {}: {}", artifact.path, e);
+                continue;
+            }
+        };
+        if !is_pe(&content) && !content.starts_with(b"\x7fELF") {
+            continue;
+        }
+        let link = Derivation {
+            kind: DerivationKind::of_artifact(artifact),
+            analysis_id: job.analysis_id.clone(),
+            job_id: Some(job.id.clone()),
+            execution_id: Some(log.id.clone()),
+            artifact: artifact.path.clone(),
+            sha256: artifact.hashes.sha256.clone(),
+        };
+        children.extend(submit_derived(scheduler, results, job, link, &content).await?);
+    }
+    Ok(children)
+}
+
+async fn submit<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<SubmitParams>,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<Submitted>)>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    principal.require(Role::Submitter)?;
+    let url = match params.url {
+        Some(url) if !is_detonatable_url(&url) => {
+            return Err(ApiError::bad_request(
+                "Only http and https URLs can be detonated",
+            ))
+        }
+        url => url,
+    };
+    let body = match &url {
+        Some(url) => Bytes::from(url.clone()),
+        None if body.is_empty() => return Err(ApiError::bad_request("Empty sample")),
+        None => body,
+    };
+    let mut inner: Vec<(DerivationKind, String, Vec<u8>, Vec<String>)> =
+        match params.detonate_attachments {
+            Some(true) if url.is_none() && email::is_email(&body) => {
+                let data = body.clone();
+                tokio::task::spawn_blocking(move || email::parse(&data))
+                    .await
+                    .map_err(anyhow::Error::from)?
+                    .map_err(|e| ApiError::bad_request(&e.to_string()))?
+                    .attachments
+                    .into_iter()
+                    .map(|a| (DerivationKind::EmailAttachment, a.name, a.data, Vec::new()))
+                    .collect()
+            }
+            Some(true) => return Err(ApiError::bad_request("Sample is not an email")),
+            _ => Vec::new(),
+        };
+    let unpacked = match (params.unpack.unwrap_or(true), container_kind(&body)) {
+        (true, Some(_)) if url.is_none() => {
+            let passwords: Vec<String> = params
+                .passwords
+                .iter()
+                .flat_map(|p| p.split(','))
+                .filter(|p| !p.is_empty())
+                .map(str::to_string)
+                .collect();
+            let data = body.clone();
+            let unpacked =
+                tokio::task::spawn_blocking(move || container::unpack(&data, &passwords))
+                    .await
+                    .map_err(anyhow::Error::from)?;
+            match unpacked {
+                Ok(unpacked) => Some(unpacked),
+                Err(e) => {
+                    warn!("Failed to unpack container: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    let tenant = principal.tenant.as_deref();
+    let (analysis_id, sample_path) = store_sample(&state.results, &body, tenant).await?;
+    if let Some(unpacked) = unpacked {
+        if let Err(e) = write_container(container_path(&analysis_id), &unpacked.container) {
+            warn!("Failed to store container structure: {}", e);
+        }
+        inner.extend(unpacked.files.into_iter().map(|f| {
+            let tags = f.tags();
+            (DerivationKind::ContainerEntry, f.name, f.data, tags)
+        }));
+    }
+
+    let file_name = params.file_name.unwrap_or_else(|| match url {
+        Some(_) => URL_FILE_NAME.to_string(),
+        None => "sample".to_string(),
+    });
+    match extract_scripts(&file_name, &body) {
+        Ok(scripts) if !scripts.is_empty() => {
+            if let Err(e) = write_scripts(scripts_dir(&analysis_id), &scripts) {
+                warn!("Failed to store extracted scripts: {}", e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to extract scripts from {}: {}", file_name, e),
+    }
+
+    let dump_triggers = params
+        .memory_dump
+        .iter()
+        .flat_map(|t| t.split(','))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::parse)
+        .collect::<anyhow::Result<Vec<DumpTrigger>>>()
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+    let dump_scope = match &params.dump_scope {
+        Some(scope) => scope
+            .parse()
+            .map_err(|e: anyhow::Error| ApiError::bad_request(&e.to_string()))?,
+        None => DumpScope::default(),
+    };
+    let hardening = match &params.hardening {
+        Some(name) => Some(HardeningProfile::builtin(name).ok_or_else(|| {
+            ApiError::bad_request(&format!(
+                "Unknown hardening profile '{}', expected one of {}",
+                name,
+                BUILTIN_PROFILES.join(", ")
+            ))
+        })?),
+        None => None,
+    };
+    let time_warp = params
+        .time_warp
+        .map(|factor| TimeWarp::new(factor, params.skip_sleeps.unwrap_or(false)))
+        .transpose()
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+
+    let request = ExecutionRequest {
+        file_name,
+        arguments: Vec::new(),
+        timeout_secs: params.timeout.unwrap_or(60),
+        user: None,
+        screenshot: params.screenshot.unwrap_or(false),
+        screenshot_interval_secs: params.screenshot_interval,
+        record_video: params.record_video.unwrap_or(false),
+        simulate_user: params.simulate_user.unwrap_or(false),
+        user_sim_script: None,
+        hardening,
+        time_warp,
+        dump_triggers,
+        dump_scope,
+        reboot: params.reboot.unwrap_or(false),
+        collect_only: false,
+        resubmit_dropped: params.resubmit_dropped.unwrap_or(url.is_some()),
+        url,
+        golden_files: Vec::new(),
+    };
+    let mut job = Job::new(&analysis_id, &sample_path, request);
+    job.priority = params.priority.unwrap_or(0);
+    job.tenant = principal.tenant.clone();
+    let limits = ResourceLimits {
+        cpus: params.cpus,
+        cpu_cap: params.cpu_cap,
+        memory_mb: params.memory_limit,
+        disk_mb: params.disk_limit,
+        wall_clock_secs: params.wall_clock_limit,
+        cpu_saturation_secs: params.cpu_saturation_limit,
+    };
+    if limits != ResourceLimits::default() {
+        job.limits = Some(limits);
+    }
+    job.tags = params
+        .tags
+        .iter()
+        .flat_map(|t| t.split(','))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let profiles: Vec<&str> = params
+        .profiles
+        .iter()
+        .flat_map(|p| p.split(','))
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    let template = job.clone();
+    let mut children = Vec::new();
+    let job_id = if profiles.is_empty() {
+        state
+            .scheduler
+            .submit(job)
+            .map_err(|e| ApiError::bad_request(&e.to_string()))?
+    } else {
+        let profiles = state
+            .config
+            .profiles
+            .resolve(&profiles)
+            .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+        let mut ids = state
+            .scheduler
+            .submit_profiles(job, &profiles)
+            .map_err(|e| ApiError::bad_request(&e.to_string()))?
+            .into_iter();
+        let job_id = ids.next().unwrap_or_default();
+        children.extend(ids);
+        job_id
+    };
+
+    for (kind, file_name, data, tags) in inner {
+        let (child_id, child_path) = store_sample(&state.results, &data, tenant).await?;
+        if child_id == analysis_id {
+            continue;
+        }
+        let link = Derivation {
+            kind,
+            analysis_id: analysis_id.clone(),
+            job_id: Some(job_id.clone()),
+            execution_id: None,
+            artifact: file_name.clone(),
+            sha256: format!("{:x}", Sha256::digest(&data)),
+        };
+        let request = ExecutionRequest {
+            file_name,
+            resubmit_dropped: false,
+            ..template.request.clone()
+        };
+        let mut child = Job::new(&child_id, &child_path, request);
+        child.priority = template.priority;
+        child.tags = template.tags.iter().cloned().chain(tags).collect();
+        child.parent = Some(job_id.clone());
+        child.tenant = template.tenant.clone();
+        child.limits = template.limits.clone();
+        child.derivation = lineage::extend(&template.derivation, link);
+        children.push(
+            state
+                .scheduler
+                .submit(child)
+                .map_err(|e| ApiError::bad_request(&e.to_string()))?,
+        );
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(Submitted {
+            job_id,
+            analysis_id,
+            children,
+        }),
+    ))
+}
+
+async fn ingest<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    Query(params): Query<IngestParams>,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<Ingest>)> {
+    if body.is_empty() {
+        return Err(ApiError::bad_request("Empty log"));
+    }
+    visible_result(&state, &principal, &id).await?;
+    let format = match params.format.as_deref() {
+        Some(name) => RecordFormat::from_name(name)
+            .ok_or_else(|| ApiError::bad_request("Unsupported log format"))?,
+        None if body.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<') => RecordFormat::Xml,
+        None => RecordFormat::Jsonl,
+    };
+
+    let mut pipeline = Pipeline::new(state.config.pipeline);
+    if let Some(filter) = &state.config.event_filter {
+        pipeline = pipeline.with_filter(filter.clone());
+    }
+    let progress = pipeline.progress();
+    let ingest_id = Uuid::new_v4().to_string();
+    state
+        .ingests
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Ingest registry is poisoned"))?
+        .insert(
+            ingest_id.clone(),
+            (principal.tenant.clone(), progress.clone()),
+        );
+
+    let execution_id = Uuid::new_v4().to_string();
+    let pipeline = pipeline.with_quarantine(FileQuarantine::new(format!(
+        "{}/{}",
+        artifact_dir(&id, &execution_id),
+        QUARANTINE_FILE
+    )));
+    let results = state.results.clone();
+    let analysis_id = id.clone();
+    tokio::spawn(async move {
+        let parsed = tokio::task::spawn_blocking(move || {
+            let mut events = Vec::new();
+            pipeline.run(std::io::Cursor::new(body), format, &mut events)?;
+            anyhow::Ok((events, pipeline.parse_failures()))
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|r| r);
+        let stored = match parsed {
+            Ok((sysmon_events, parse_failures)) => {
+                if !parse_failures.is_empty() {
+                    warn!(
+                        "Quarantined {} unparsable records while ingesting {}",
+                        parse_failures.total, analysis_id
+                    );
+                }
+                let log = ExecutionLog {
+                    id: execution_id,
+                    time: Local::now(),
+                    sysmon_events,
+                    created_files: HashMap::new(),
+                    artifacts: Vec::new(),
+                    telemetry: Vec::new(),
+                    memory: Vec::new(),
+                    machine: None,
+                    snapshot: None,
+                    mutexes: Vec::new(),
+                    clipboard: Vec::new(),
+                    image: None,
+                    terminated: None,
+                    purged: Vec::new(),
+                    url: None,
+                    profile: None,
+                    tampered: Vec::new(),
+                    parse_failures,
+                    derivation: Vec::new(),
+                    metadata: SampleMetadata::default(),
+                };
+                results.store_execution_log(&analysis_id, log).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stored {
+            warn!("Failed to ingest log for {}: {}", analysis_id, e);
+            progress.fail(&e);
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(Ingest {
+            ingest_id,
+            analysis_id: id,
+        }),
+    ))
+}
+
+async fn ingest_status<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<ProgressSnapshot>> {
+    let ingests = state
+        .ingests
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Ingest registry is poisoned"))?;
+    ingests
+        .get(&id)
+        .filter(|(tenant, _)| principal.can_see(tenant.as_deref()))
+        .map(|(_, p)| Json(p.snapshot()))
+        .ok_or_else(|| ApiError::not_found("Ingest"))
+}
+
+async fn scrape<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+) -> ApiResult<Response>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    principal.require(Role::Admin)?;
+    state.scheduler.record_metrics();
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+        .into_response())
+}
+
+async fn rule_packs<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+) -> ApiResult<Json<RulePackStatus>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    principal.require(Role::Admin)?;
+    let manager = state
+        .config
+        .rule_packs
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Rule pack"))?;
+    Ok(Json(manager.status()))
+}
+
+async fn reload_rule_packs<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+) -> ApiResult<Json<serde_json::Value>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    principal.require(Role::Admin)?;
+    let manager = state
+        .config
+        .rule_packs
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Rule pack"))?;
+    let reloaded = manager
+        .reload()
+        .await
+        .map_err(|e| ApiError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    Ok(Json(
+        json!({ "reloaded": reloaded, "status": manager.status() }),
+    ))
+}
+
+async fn schema(Path(kind): Path<String>) -> ApiResult<Json<serde_json::Value>> {
+    let kind = kind.strip_suffix(".json").unwrap_or(&kind);
+    SchemaKind::from_name(kind)
+        .map(|k| Json(k.schema()))
+        .ok_or_else(|| ApiError::not_found("Schema"))
+}
+
+async fn list_jobs<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<PageParams>,
+) -> Json<JobPage>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(20).clamp(1, MAX_PER_PAGE);
+
+    let mut jobs = state.scheduler.jobs();
+    jobs.retain(|j| principal.can_see(j.tenant.as_deref()));
+    jobs.sort_by_key(|j| std::cmp::Reverse(j.submitted));
+    let total = jobs.len();
+    let jobs = jobs
+        .into_iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .collect();
+
+    Json(JobPage {
+        page,
+        per_page,
+        total,
+        jobs,
+    })
+}
+
+async fn job_status<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Job>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    state
+        .scheduler
+        .job(&id)
+        .filter(|j| principal.can_see(j.tenant.as_deref()))
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("Job"))
+}
+
+fn live_status<H, S>(
+    state: &ApiState<H, S>,
+    principal: &Principal,
+    id: &str,
+) -> ApiResult<LiveStatus>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    principal.require(Role::Analyst)?;
+    let job = state
+        .scheduler
+        .job(id)
+        .filter(|j| principal.can_see(j.tenant.as_deref()))
+        .ok_or_else(|| ApiError::not_found("Job"))?;
+    let live = state
+        .config
+        .live
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Live monitor"))?;
+    job.machine
+        .as_deref()
+        .filter(|_| job.state == JobState::Running)
+        .and_then(|machine| live.status(machine))
+        .ok_or_else(|| ApiError::not_found("Running job"))
+}
+
+async fn job_live<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<LiveStatus>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    live_status(&state, &principal, &id).map(Json)
+}
+
+async fn similar<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(sha256): Path<String>,
+    Query(params): Query<SimilarParams>,
+) -> ApiResult<Json<Vec<SimilarFile>>> {
+    principal.require(Role::Analyst)?;
+    let index = state
+        .config
+        .similarity
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Similarity index"))?;
+    let matches = {
+        let index = index
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Similarity index is poisoned"))?;
+        if index.hashes_of(&sha256)?.is_none() {
+            return Err(ApiError::not_found("Hash"));
+        }
+        index.similar_to(
+            &sha256,
+            params.min_score.unwrap_or(DEFAULT_MIN_SIMILARITY),
+            params.limit.unwrap_or(MAX_PER_PAGE).min(MAX_PER_PAGE),
+        )?
+    };
+
+    let mut visible = Vec::with_capacity(matches.len());
+    for m in matches {
+        if is_visible(&state, &principal, &m.file.analysis_id).await? {
+            visible.push(m);
+        }
+    }
+    Ok(Json(visible))
+}
+
+async fn runs<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<RunParams>,
+) -> ApiResult<Json<Vec<StoredRun>>> {
+    principal.require(Role::Analyst)?;
+    let store = state
+        .config
+        .store
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Result store"))?;
+    let mut queries = Vec::new();
+    if let Some(hash) = &params.hash {
+        queries.push(RunQuery::Hash(hash.to_lowercase()));
+    }
+    if let Some(domain) = &params.domain {
+        queries.push(RunQuery::domain(domain));
+    }
+    if let Some(mutex) = &params.mutex {
+        queries.push(RunQuery::mutex(mutex));
+    }
+    for (facet, value) in [
+        (Facet::Tag, &params.tag),
+        (Facet::Campaign, &params.campaign),
+        (Facet::Source, &params.source),
+        (Facet::Tlp, &params.tlp),
+    ] {
+        if let Some(value) = value {
+            queries.push(
+                RunQuery::facet(facet, value).map_err(|e| ApiError::bad_request(&e.to_string()))?,
+            );
+        }
+    }
+    let [query] = <[RunQuery; 1]>::try_from(queries).map_err(|_| {
+        ApiError::bad_request(
+            "Exactly one of hash, domain, mutex, tag, campaign, source or tlp is required",
+        )
+    })?;
+    let found = store
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Result store is poisoned"))?
+        .find_runs(&query)?;
+
+    let mut visible = Vec::with_capacity(found.len());
+    for run in found {
+        if is_visible(&state, &principal, &run.analysis_id).await? {
+            visible.push(run);
+        }
+    }
+    Ok(Json(visible))
+}
+
+async fn search<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<SearchParams>,
+) -> ApiResult<Json<Vec<EventHit>>> {
+    principal.require(Role::Analyst)?;
+    let store = state
+        .config
+        .store
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Result store"))?;
+    let query = EventQuery::parse(&params.q).map_err(|e| ApiError::bad_request(&e.to_string()))?;
+    let hits = store
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Result store is poisoned"))?
+        .search_events(&query, params.limit.unwrap_or(DEFAULT_LIMIT))?;
+
+    let mut visibility = HashMap::new();
+    let mut visible = Vec::with_capacity(hits.len());
+    for hit in hits {
+        let analysis_id = hit.run.analysis_id.clone();
+        let allowed = match visibility.get(&analysis_id) {
+            Some(allowed) => *allowed,
+            None => {
+                let allowed = is_visible(&state, &principal, &analysis_id).await?;
+                visibility.insert(analysis_id, allowed);
+                allowed
+            }
+        };
+        if allowed {
+            visible.push(hit);
+        }
+    }
+    Ok(Json(visible))
+}
+
+async fn report<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    Query(params): Query<ReportParams>,
+) -> ApiResult<Response> {
+    let result = visible_result(&state, &principal, &id).await?;
+    let Some(last) = result.execution_logs.last() else {
+        return Err(ApiError::not_found("Finished execution"));
+    };
+    let format = params.format.as_deref().unwrap_or("json");
+
+    if last.purged.contains(&DataClass::Events) {
+        if format != "json" {
+            return Err(ApiError::bad_request(
+                "Events of this execution have expired, only the JSON report is retained",
+            ));
+        }
+        let stored = tokio::fs::read_to_string(stored_report_path(&id, &last.id))
+            .await
+            .map_err(|_| ApiError::not_found("Retained report"))?;
+        return Ok(([(header::CONTENT_TYPE, "application/json")], stored).into_response());
+    }
+
+    let mut report = state.config.report(&result)?;
+    report.add_descendants(&visible_descendants(&state, &principal, &id).await?);
+
+    match format {
+        "json" => Ok((
+            [(header::CONTENT_TYPE, "application/json")],
+            report.to_json()?,
+        )
+            .into_response()),
+        "html" => Ok(Html(report.to_html()).into_response()),
+        "timesketch" => Ok((
+            [
+                (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.jsonl\"", id),
+                ),
+            ],
+            report_to_timesketch(&report)?.join("\n") + "\n",
+        )
+            .into_response()),
+        "graphml" => Ok((
+            [
+                (header::CONTENT_TYPE, "application/graphml+xml".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.graphml\"", id),
+                ),
+            ],
+            report_to_graphml(&report),
+        )
+            .into_response()),
+        "cypher" => Ok((
+            [
+                (header::CONTENT_TYPE, "text/plain".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.cypher\"", id),
+                ),
+            ],
+            report_to_cypher(&report).join("\n") + "\n",
+        )
+            .into_response()),
+        _ => Err(ApiError::bad_request("Unsupported report format")),
+    }
+}
+
+async fn archive<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    Query(params): Query<ArchiveParams>,
+) -> ApiResult<Response> {
+    let result = visible_result(&state, &principal, &id).await?;
+    if result.execution_logs.is_empty() {
+        return Err(ApiError::not_found("Finished execution"));
+    }
+
+    let mut options = ArchiveOptions::default();
+    if let Some(format) = &params.format {
+        options.format = format
+            .parse::<ArchiveFormat>()
+            .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+    }
+    if let Some(password) = params.password.filter(|p| !p.is_empty()) {
+        options.password = password;
+    }
+    options.include_sample = params.include_sample.unwrap_or(true);
+
+    let report = state.config.report(&result)?;
+    let archive = tokio::task::spawn_blocking(move || export_run_to_vec(&report, &options))
+        .await
+        .map_err(anyhow::Error::from)??;
+    let disposition = format!("attachment; filename=\"{}.zip\"", id);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        archive,
+    )
+        .into_response())
+}
+
+async fn diff<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path((id, other)): Path<(String, String)>,
+) -> ApiResult<Json<ReportDiff>> {
+    let mut reports = Vec::new();
+    for id in [&id, &other] {
+        let result = visible_result(&state, &principal, id).await?;
+        if result.execution_logs.is_empty() {
+            return Err(ApiError::not_found("Finished execution"));
+        }
+        reports.push(state.config.report(&result)?);
+    }
+    Ok(Json(reports[0].diff(&reports[1])))
+}
+
+async fn timeline<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    Query(params): Query<TimelineParams>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let result = visible_result(&state, &principal, &id).await?;
+    if result.execution_logs.is_empty() {
+        return Err(ApiError::not_found("Finished execution"));
+    }
+
+    let kinds = match &params.kind {
+        Some(kind) => kind
+            .split(',')
+            .map(|k| EntryKind::from_name(k).ok_or_else(|| ApiError::bad_request("Invalid kind")))
+            .collect::<ApiResult<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    let after = match &params.after {
+        Some(after) => Some(
+            after
+                .parse::<Cursor>()
+                .map_err(|_| ApiError::bad_request("Invalid cursor"))?,
+        ),
+        None => None,
+    };
+    let filter = TimelineFilter {
+        process_guid: params.process_guid,
+        kinds,
+        start: params.start,
+        end: params.end,
+    };
+
+    let mut report = SandboxReport::from_analysis_result(&result)?;
+    report.add_captured_traffic()?;
+    let timeline = report.timeline()?;
+    let page = timeline.page(
+        &filter,
+        after.as_ref(),
+        params.limit.unwrap_or(MAX_PER_PAGE).clamp(1, MAX_PER_PAGE),
+    );
+
+    Ok(Json(json!({
+        "id": result.id,
+        "execution_id": report.execution_id,
+        "entries": page.entries,
+        "next": page.next,
+    })))
+}
+
+async fn registry_diff<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let result = visible_result(&state, &principal, &id).await?;
+
+    let executions: Vec<_> = result
+        .execution_logs
+        .iter()
+        .map(|log| {
+            json!({
+                "id": log.id,
+                "time": log.time,
+                "registry": RegistryDiff::from_events(&log.sysmon_events),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({ "id": result.id, "executions": executions })))
+}
+
+async fn list_artifacts<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let result = visible_result(&state, &principal, &id).await?;
+
+    let executions: Vec<_> = result
+        .execution_logs
+        .iter()
+        .map(|log| {
+            let files: Vec<_> = log
+                .created_files
+                .iter()
+                .map(|(path, name)| json!({ "path": path, "name": name }))
+                .collect();
+            json!({
+                "id": log.id,
+                "time": log.time,
+                "files": files,
+                "artifacts": log.artifacts,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({ "id": result.id, "executions": executions })))
+}
+
+async fn download_artifact<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path((id, execution_id, name)): Path<(String, String, String)>,
+) -> ApiResult<Response> {
+    if ![&id, &execution_id, &name]
+        .iter()
+        .all(|s| is_safe_component(s))
+    {
+        return Err(ApiError::bad_request("Invalid artifact path"));
+    }
+    visible_result(&state, &principal, &id).await?;
+
+    let path = format!("{}/{}", artifact_dir(&id, &execution_id), name);
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|_| ApiError::not_found("Artifact"))?;
+    let disposition = format!("attachment; filename=\"{}\"", name);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        StreamBody::new(ReaderStream::new(file)),
+    )
+        .into_response())
+}
+
+async fn visible_descendants<H, S>(
+    state: &ApiState<H, S>,
+    principal: &Principal,
+    id: &str,
+) -> anyhow::Result<Vec<AnalysisResult>> {
+    Ok(state
+        .results
+        .derived_from(id)
+        .await?
+        .into_iter()
+        .filter(|r| principal.can_see(r.tenant.as_deref()))
+        .collect())
+}
+
+async fn derivations<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let result = visible_result(&state, &principal, &id).await?;
+    let runs: Vec<_> = result
+        .execution_logs
+        .iter()
+        .map(|log| json!({ "id": log.id, "derivation": log.derivation }))
+        .collect();
+    let descendants: Vec<Descendant> =
+        lineage::descendants(&id, &visible_descendants(&state, &principal, &id).await?);
+    Ok(Json(json!({
+        "id": result.id,
+        "runs": runs,
+        "descendants": descendants,
+    })))
+}
+
+fn store_metadata<H, S>(state: &ApiState<H, S>, result: &AnalysisResult) -> anyhow::Result<()> {
+    let Some(store) = &state.config.store else {
+        return Ok(());
+    };
+    let mut store = store
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Result store is poisoned"))?;
+    for log in &result.execution_logs {
+        store.save_metadata(&log.id, &result.metadata.merged(&log.metadata))?;
+    }
+    Ok(())
+}
+
+async fn sample_metadata<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let result = visible_result(&state, &principal, &id).await?;
+    let runs: Vec<_> = result
+        .execution_logs
+        .iter()
+        .map(|log| json!({ "id": log.id, "metadata": log.metadata }))
+        .collect();
+    Ok(Json(json!({
+        "id": result.id,
+        "metadata": result.metadata,
+        "runs": runs,
+    })))
+}
+
+async fn update_metadata<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    Json(update): Json<MetadataUpdate>,
+) -> ApiResult<Json<SampleMetadata>> {
+    let mut result = visible_result(&state, &principal, &id).await?;
+    update
+        .apply(&mut result.metadata, &principal.name)
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+    state.results.set_metadata(&id, &result.metadata).await?;
+    store_metadata(&state, &result)?;
+    Ok(Json(result.metadata))
+}
+
+async fn update_run_metadata<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path((id, execution_id)): Path<(String, String)>,
+    Json(update): Json<MetadataUpdate>,
+) -> ApiResult<Json<SampleMetadata>> {
+    let mut result = visible_result(&state, &principal, &id).await?;
+    let log = result
+        .execution_logs
+        .iter_mut()
+        .find(|log| log.id == execution_id)
+        .ok_or_else(|| ApiError::not_found("Execution"))?;
+    update
+        .apply(&mut log.metadata, &principal.name)
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+    let metadata = log.metadata.clone();
+    state
+        .results
+        .set_run_metadata(&id, &execution_id, &metadata)
+        .await?;
+    store_metadata(&state, &result)?;
+    Ok(Json(metadata))
+}
+
+async fn resubmit_artifact<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path((id, execution_id, name)): Path<(String, String, String)>,
+    Query(params): Query<ResubmitParams>,
+) -> ApiResult<(StatusCode, Json<Submitted>)>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    principal.require(Role::Submitter)?;
+    if ![&id, &execution_id, &name]
+        .iter()
+        .all(|s| is_safe_component(s))
+    {
+        return Err(ApiError::bad_request("Invalid artifact path"));
+    }
+    let result = visible_result(&state, &principal, &id).await?;
+    let log = result
+        .execution_logs
+        .iter()
+        .find(|l| l.id == execution_id)
+        .ok_or_else(|| ApiError::not_found("Execution"))?;
+    let artifact = log
+        .artifacts
+        .iter()
+        .find(|a| a.file_name() == name)
+        .ok_or_else(|| ApiError::not_found("Artifact"))?;
+    let content = tokio::fs::read(format!("{}/{}", artifact_dir(&id, &execution_id), name))
+        .await
+        .map_err(|_| ApiError::not_found("Artifact"))?;
+
+    let request = ExecutionRequest {
+        file_name: name.clone(),
+        arguments: Vec::new(),
+        timeout_secs: params.timeout.unwrap_or(60),
+        user: None,
+        screenshot: false,
+        screenshot_interval_secs: None,
+        record_video: false,
+        simulate_user: false,
+        user_sim_script: None,
+        hardening: None,
+        time_warp: None,
+        dump_triggers: Vec::new(),
+        dump_scope: DumpScope::default(),
+        reboot: false,
+        collect_only: false,
+        resubmit_dropped: false,
+        url: None,
+        golden_files: Vec::new(),
+    };
+    let mut template = Job::new(&id, &sample_path(&id), request);
+    template.priority = params.priority.unwrap_or(0);
+    template.tenant = result.tenant.clone();
+    template.derivation = log.derivation.clone();
+    let link = Derivation {
+        kind: DerivationKind::of_artifact(artifact),
+        analysis_id: id.clone(),
+        job_id: None,
+        execution_id: Some(execution_id.clone()),
+        artifact: artifact.path.clone(),
+        sha256: artifact.hashes.sha256.clone(),
+    };
+    let job_id = submit_derived(&state.scheduler, &state.results, &template, link, &content)
+        .await
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?
+        .ok_or_else(|| {
+            ApiError(
+                StatusCode::CONFLICT,
+                "Artifact is already part of this derivation chain".to_string(),
+            )
+        })?;
+    let analysis_id = state
+        .scheduler
+        .job(&job_id)
+        .map(|j| j.analysis_id)
+        .unwrap_or_default();
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(Submitted {
+            job_id,
+            analysis_id,
+            children: Vec::new(),
+        }),
+    ))
+}
+
+pub fn router<H, S>(
+    scheduler: Arc<Scheduler<H, S>>,
+    results: Arc<AnalysisResultManager>,
+    config: Arc<ApiConfig>,
+) -> Router
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore + Send + 'static,
+{
+    let max_upload_size = config.max_upload_size;
+    let portal_size = config.portal.as_ref().map(|p| p.config.max_sample_size);
+    let state = ApiState {
+        scheduler,
+        results,
+        config,
+        ingests: Arc::new(Mutex::new(HashMap::new())),
+        #[cfg(feature = "ui")]
+        sessions: Arc::new(ui::Sessions::default()),
+    };
+
+    let router = Router::new()
+        .route(
+            "/samples",
+            post(submit::<H, S>).layer(DefaultBodyLimit::max(max_upload_size)),
+        )
+        .route("/jobs", get(list_jobs::<H, S>))
+        .route("/jobs/:id", get(job_status::<H, S>))
+        .route("/jobs/:id/live", get(job_live::<H, S>))
+        .route(
+            "/analyses/:id/logs",
+            post(ingest::<H, S>).layer(DefaultBodyLimit::max(max_upload_size)),
+        )
+        .route("/analyses/:id/report", get(report::<H, S>))
+        .route("/analyses/:id/archive", get(archive::<H, S>))
+        .route("/analyses/:id/artifacts", get(list_artifacts::<H, S>))
+        .route(
+            "/analyses/:id/artifacts/:execution_id/:name",
+            get(download_artifact::<H, S>),
+        )
+        .route(
+            "/analyses/:id/artifacts/:execution_id/:name/resubmit",
+            post(resubmit_artifact::<H, S>),
+        )
+        .route("/analyses/:id/lineage", get(derivations::<H, S>))
+        .route(
+            "/analyses/:id/metadata",
+            get(sample_metadata::<H, S>).patch(update_metadata::<H, S>),
+        )
+        .route(
+            "/analyses/:id/runs/:execution_id/metadata",
+            patch(update_run_metadata::<H, S>),
+        )
+        .route("/analyses/:id/diff/:other", get(diff::<H, S>))
+        .route("/analyses/:id/registry", get(registry_diff::<H, S>))
+        .route("/analyses/:id/timeline", get(timeline::<H, S>))
+        .route("/similar/:sha256", get(similar::<H, S>))
+        .route("/ingests/:id", get(ingest_status::<H, S>))
+        .route("/runs", get(runs::<H, S>))
+        .route("/search", get(search::<H, S>))
+        .route("/metrics", get(scrape::<H, S>))
+        .route("/rule-packs", get(rule_packs::<H, S>))
+        .route("/rule-packs/reload", post(reload_rule_packs::<H, S>))
+        .route("/schema/:kind", get(schema))
+        .merge(users::routes())
+        .merge(portal::routes());
+    #[cfg(feature = "ui")]
+    let router = router.merge(ui::routes());
+    let router = router.route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        auth::<H, S, _>,
+    ));
+    #[cfg(feature = "ui")]
+    let router = router.merge(ui::public_routes());
+    let router = match portal_size {
+        Some(size) => router.merge(portal::public_routes(size)),
+        None => router,
+    };
+    router.with_state(state)
+}
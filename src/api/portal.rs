@@ -0,0 +1,762 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, DefaultBodyLimit, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::users::{Principal, Role};
+use super::{store_new_sample, ApiError, ApiResult, ApiState, API_KEY_HEADER};
+use crate::agent::protocol::{DumpScope, ExecutionRequest};
+use crate::metadata::SampleMetadata;
+use crate::orchestrator::Hypervisor;
+use crate::report::SandboxReport;
+use crate::scheduler::{Job, JobState, JobStore};
+
+const REDACTED: &str = "[redacted]";
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+const MAX_EXTENSION_LEN: usize = 8;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: usize,
+    pub window_secs: u64,
+}
+
+impl RateLimit {
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_sample_size() -> usize {
+    32 * 1024 * 1024
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+fn default_rate_limit() -> RateLimit {
+    RateLimit {
+        requests: 5,
+        window_secs: 3600,
+    }
+}
+
+fn default_token_rate_limit() -> RateLimit {
+    RateLimit {
+        requests: 100,
+        window_secs: 3600,
+    }
+}
+
+fn default_queue() -> PathBuf {
+    PathBuf::from("portal.json")
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PortalConfig {
+    #[serde(default = "default_true")]
+    pub anonymous: bool,
+    #[serde(default = "default_max_sample_size")]
+    pub max_sample_size: usize,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_rate_limit")]
+    pub rate_limit: RateLimit,
+    #[serde(default = "default_token_rate_limit")]
+    pub token_rate_limit: RateLimit,
+    #[serde(default = "default_true")]
+    pub review: bool,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+    #[serde(default = "default_queue")]
+    pub queue: PathBuf,
+}
+
+impl Default for PortalConfig {
+    fn default() -> Self {
+        Self {
+            anonymous: true,
+            max_sample_size: default_max_sample_size(),
+            timeout_secs: default_timeout(),
+            rate_limit: default_rate_limit(),
+            token_rate_limit: default_token_rate_limit(),
+            review: true,
+            tenant: None,
+            trust_forwarded_for: false,
+            queue: default_queue(),
+        }
+    }
+}
+
+impl PortalConfig {
+    pub fn from_file<P: AsRef<FsPath>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    hits: Mutex<HashMap<String, (Duration, VecDeque<Instant>)>>,
+}
+
+impl RateLimiter {
+    pub fn check(&self, key: &str, limit: &RateLimit, now: Instant) -> Result<Option<Duration>> {
+        let mut hits = self
+            .hits
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Rate limiter is poisoned"))?;
+        hits.retain(|_, (window, times)| {
+            times
+                .back()
+                .is_some_and(|last| now.duration_since(*last) < *window)
+        });
+        let (window, times) = hits
+            .entry(key.to_string())
+            .or_insert_with(|| (limit.window(), VecDeque::new()));
+        *window = limit.window();
+        while times
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= limit.window())
+        {
+            times.pop_front();
+        }
+        if times.len() >= limit.requests {
+            let retry = times
+                .front()
+                .map(|first| limit.window().saturating_sub(now.duration_since(*first)))
+                .unwrap_or_else(|| limit.window());
+            return Ok(Some(retry));
+        }
+        times.push_back(now);
+        Ok(None)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewState {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ReviewState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+impl std::str::FromStr for ReviewState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "pending" => Ok(Self::Pending),
+            "approved" => Ok(Self::Approved),
+            "rejected" => Ok(Self::Rejected),
+            _ => bail!(
+                "Unknown review state '{}', expected pending, approved or rejected",
+                s
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Submission {
+    pub id: String,
+    pub job_id: String,
+    pub analysis_id: String,
+    pub sha256: String,
+    pub file_name: String,
+    pub size: u64,
+    pub source: String,
+    pub submitted: DateTime<Local>,
+    pub state: ReviewState,
+    #[serde(default)]
+    pub reviewer: Option<String>,
+    #[serde(default)]
+    pub reviewed: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct QueueFile {
+    submissions: Vec<Submission>,
+}
+
+#[derive(Debug)]
+pub struct ReviewQueue {
+    path: PathBuf,
+    data: QueueFile,
+}
+
+impl ReviewQueue {
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let data = if path.exists() {
+            serde_json::from_slice(&std::fs::read(&path)?)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            QueueFile::default()
+        };
+        Ok(Self { path, data })
+    }
+
+    fn save(&self) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.data)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, submission: Submission) -> Result<()> {
+        self.data.submissions.push(submission);
+        self.save()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Submission> {
+        self.data.submissions.iter().find(|s| s.id == id)
+    }
+
+    pub fn with_state(&self, state: Option<ReviewState>) -> Vec<Submission> {
+        self.data
+            .submissions
+            .iter()
+            .filter(|s| state.is_none_or(|state| s.state == state))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_published(&self, analysis_id: &str) -> bool {
+        self.data
+            .submissions
+            .iter()
+            .any(|s| s.analysis_id == analysis_id && s.state == ReviewState::Approved)
+    }
+
+    pub fn decide(
+        &mut self,
+        id: &str,
+        state: ReviewState,
+        reviewer: &str,
+        note: Option<String>,
+    ) -> Result<Option<Submission>> {
+        let Some(submission) = self.data.submissions.iter_mut().find(|s| s.id == id) else {
+            return Ok(None);
+        };
+        submission.state = state;
+        submission.reviewer = Some(reviewer.to_string());
+        submission.reviewed = Some(Local::now());
+        submission.note = note;
+        let submission = submission.clone();
+        self.save()?;
+        Ok(Some(submission))
+    }
+}
+
+#[derive(Debug)]
+pub struct Portal {
+    pub config: PortalConfig,
+    pub limiter: RateLimiter,
+    pub queue: Mutex<ReviewQueue>,
+}
+
+impl Portal {
+    pub fn new(config: PortalConfig) -> Result<Self> {
+        let queue = ReviewQueue::open(&config.queue)?;
+        Ok(Self {
+            config,
+            limiter: RateLimiter::default(),
+            queue: Mutex::new(queue),
+        })
+    }
+
+    fn queue(&self) -> ApiResult<std::sync::MutexGuard<'_, ReviewQueue>> {
+        self.queue
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Review queue is poisoned").into())
+    }
+}
+
+pub fn redact(report: &mut SandboxReport) {
+    report.metadata = SampleMetadata::default();
+    if let Some(email) = &mut report.email {
+        for address in email.to.iter_mut().chain(email.cc.iter_mut()) {
+            *address = REDACTED.to_string();
+        }
+        email.received.clear();
+    }
+}
+
+fn neutral_file_name(sha256: &str, file_name: Option<&str>) -> String {
+    let extension = file_name
+        .and_then(|name| FsPath::new(name).extension())
+        .and_then(|e| e.to_str())
+        .filter(|e| e.len() <= MAX_EXTENSION_LEN && e.chars().all(|c| c.is_ascii_alphanumeric()));
+    match extension {
+        Some(extension) => format!("{}.{}", &sha256[..16], extension.to_lowercase()),
+        None => sha256[..16].to_string(),
+    }
+}
+
+fn client_ip(config: &PortalConfig, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+    if !config.trust_forwarded_for {
+        return peer.ip();
+    }
+    headers
+        .get(FORWARDED_FOR_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .and_then(|ip| ip.trim().parse().ok())
+        .unwrap_or_else(|| peer.ip())
+}
+
+fn pseudonym(value: &str) -> String {
+    format!("{:x}", Sha256::digest(value.as_bytes()))
+}
+
+fn portal<H, S>(state: &ApiState<H, S>) -> ApiResult<&Portal> {
+    state
+        .config
+        .portal
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Submission portal"))
+}
+
+fn rate_limited(retry: Duration) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry.as_secs().max(1).to_string())],
+        Json(serde_json::json!({ "error": "Rate limit exceeded" })),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize, Debug)]
+struct PortalSubmitParams {
+    file_name: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct PortalSubmitted {
+    submission_id: String,
+    state: ReviewState,
+}
+
+#[derive(Serialize, Debug)]
+struct SubmissionStatus {
+    submission_id: String,
+    analysis_id: Option<String>,
+    state: ReviewState,
+    job_state: Option<JobState>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ReviewParams {
+    state: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Decision {
+    note: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SharedReportParams {
+    format: Option<String>,
+}
+
+async fn submit<H, S>(
+    State(state): State<ApiState<H, S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(params): Query<PortalSubmitParams>,
+    body: Bytes,
+) -> ApiResult<Response>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let portal = portal(&state)?;
+    let config = &portal.config;
+    let key = headers
+        .get(API_KEY_HEADER)
+        .or_else(|| headers.get(header::AUTHORIZATION))
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.strip_prefix("Bearer ").unwrap_or(v));
+    let principal: Option<Principal> = match key {
+        Some(key) => Some(state.config.principal(key)?.ok_or_else(|| {
+            ApiError(
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid API key".to_string(),
+            )
+        })?),
+        None if config.anonymous => None,
+        None => {
+            return Err(ApiError(
+                StatusCode::UNAUTHORIZED,
+                "Anonymous submissions are disabled".to_string(),
+            ))
+        }
+    };
+    if let Some(principal) = &principal {
+        principal.require(Role::Submitter)?;
+    }
+
+    let (limit_key, limit) = match &principal {
+        Some(principal) => (format!("token:{}", principal.name), config.token_rate_limit),
+        None => (
+            format!("ip:{}", client_ip(config, &headers, peer)),
+            config.rate_limit,
+        ),
+    };
+    if let Some(retry) = portal.limiter.check(&limit_key, &limit, Instant::now())? {
+        return Ok(rate_limited(retry));
+    }
+
+    if body.is_empty() {
+        return Err(ApiError::bad_request("Empty sample"));
+    }
+    if body.len() > config.max_sample_size {
+        return Err(ApiError(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Samples are limited to {} bytes", config.max_sample_size),
+        ));
+    }
+
+    let sha256 = format!("{:x}", Sha256::digest(&body));
+    let file_name = neutral_file_name(&sha256, params.file_name.as_deref());
+    let tenant = match &principal {
+        Some(principal) => principal.tenant.clone().or_else(|| config.tenant.clone()),
+        None => config.tenant.clone(),
+    };
+    let (analysis_id, sample_path) =
+        store_new_sample(&state.results, &body, tenant.as_deref()).await?;
+
+    let request = ExecutionRequest {
+        file_name: file_name.clone(),
+        arguments: Vec::new(),
+        timeout_secs: config.timeout_secs,
+        user: None,
+        screenshot: false,
+        screenshot_interval_secs: None,
+        record_video: false,
+        simulate_user: false,
+        user_sim_script: None,
+        hardening: None,
+        time_warp: None,
+        dump_triggers: Vec::new(),
+        dump_scope: DumpScope::default(),
+        reboot: false,
+        collect_only: false,
+        resubmit_dropped: false,
+        url: None,
+        golden_files: Vec::new(),
+    };
+    let mut job = Job::new(&analysis_id, &sample_path, request);
+    job.tenant = tenant;
+    let job_id = state
+        .scheduler
+        .submit(job)
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+
+    let source = match &principal {
+        Some(principal) => pseudonym(&format!("token:{}", principal.name)),
+        None => pseudonym(&limit_key),
+    };
+    let submission = Submission {
+        id: Uuid::new_v4().to_string(),
+        job_id,
+        analysis_id,
+        sha256,
+        file_name,
+        size: body.len() as u64,
+        source,
+        submitted: Local::now(),
+        state: if config.review {
+            ReviewState::Pending
+        } else {
+            ReviewState::Approved
+        },
+        reviewer: None,
+        reviewed: None,
+        note: None,
+    };
+    let submitted = PortalSubmitted {
+        submission_id: submission.id.clone(),
+        state: submission.state,
+    };
+    portal.queue()?.add(submission)?;
+    Ok((StatusCode::ACCEPTED, Json(submitted)).into_response())
+}
+
+async fn submission_status<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<SubmissionStatus>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let submission = portal(&state)?
+        .queue()?
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| ApiError::not_found("Submission"))?;
+    let published = submission.state == ReviewState::Approved;
+    Ok(Json(SubmissionStatus {
+        submission_id: submission.id,
+        analysis_id: published.then_some(submission.analysis_id),
+        state: submission.state,
+        job_state: state.scheduler.job(&submission.job_id).map(|j| j.state),
+    }))
+}
+
+async fn shared_report<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Path(id): Path<String>,
+    Query(params): Query<SharedReportParams>,
+) -> ApiResult<Response>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    if !portal(&state)?.queue()?.is_published(&id) {
+        return Err(ApiError::not_found("Report"));
+    }
+    let result = state
+        .results
+        .get(&id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Analysis"))?;
+    if result.execution_logs.is_empty() {
+        return Err(ApiError::not_found("Report"));
+    }
+    let mut report = state.config.report(&result)?;
+    redact(&mut report);
+    match params.format.as_deref().unwrap_or("json") {
+        "json" => Ok((
+            [(header::CONTENT_TYPE, "application/json")],
+            report.to_json()?,
+        )
+            .into_response()),
+        "html" => Ok(Html(report.to_html()).into_response()),
+        _ => Err(ApiError::bad_request("Unsupported report format")),
+    }
+}
+
+async fn list_reviews<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<ReviewParams>,
+) -> ApiResult<Json<Vec<Submission>>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let portal = reviewer(&state, &principal)?;
+    let review_state = match params.state.as_deref() {
+        Some("all") => None,
+        Some(s) => Some(
+            s.parse()
+                .map_err(|e: anyhow::Error| ApiError::bad_request(&e.to_string()))?,
+        ),
+        None => Some(ReviewState::Pending),
+    };
+    Ok(Json(portal.queue()?.with_state(review_state)))
+}
+
+fn reviewer<'a, H, S>(state: &'a ApiState<H, S>, principal: &Principal) -> ApiResult<&'a Portal> {
+    principal.require(Role::Analyst)?;
+    let portal = portal(state)?;
+    if !principal.can_see(portal.config.tenant.as_deref()) {
+        return Err(ApiError::not_found("Submission portal"));
+    }
+    Ok(portal)
+}
+
+fn decide<H, S>(
+    state: &ApiState<H, S>,
+    principal: &Principal,
+    id: &str,
+    review_state: ReviewState,
+    decision: Decision,
+) -> ApiResult<Json<Submission>> {
+    reviewer(state, principal)?
+        .queue()?
+        .decide(id, review_state, &principal.name, decision.note)?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("Submission"))
+}
+
+async fn approve<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    decision: Option<Json<Decision>>,
+) -> ApiResult<Json<Submission>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let decision = decision.map(|Json(d)| d).unwrap_or_default();
+    decide(&state, &principal, &id, ReviewState::Approved, decision)
+}
+
+async fn reject<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    decision: Option<Json<Decision>>,
+) -> ApiResult<Json<Submission>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let decision = decision.map(|Json(d)| d).unwrap_or_default();
+    decide(&state, &principal, &id, ReviewState::Rejected, decision)
+}
+
+pub(super) fn public_routes<H, S>(max_sample_size: usize) -> Router<ApiState<H, S>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore + Send + 'static,
+{
+    Router::new()
+        .route(
+            "/portal/samples",
+            post(submit::<H, S>).layer(DefaultBodyLimit::max(max_sample_size)),
+        )
+        .route("/portal/submissions/:id", get(submission_status::<H, S>))
+        .route("/portal/analyses/:id/report", get(shared_report::<H, S>))
+}
+
+pub(super) fn routes<H, S>() -> Router<ApiState<H, S>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore + Send + 'static,
+{
+    Router::new()
+        .route("/portal/reviews", get(list_reviews::<H, S>))
+        .route("/portal/reviews/:id/approve", post(approve::<H, S>))
+        .route("/portal/reviews/:id/reject", post(reject::<H, S>))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(requests: usize, window_secs: u64) -> RateLimit {
+        RateLimit {
+            requests,
+            window_secs,
+        }
+    }
+
+    fn submission(id: &str, analysis_id: &str) -> Submission {
+        Submission {
+            id: id.to_string(),
+            job_id: format!("job-{}", id),
+            analysis_id: analysis_id.to_string(),
+            sha256: "0".repeat(64),
+            file_name: "0000000000000000.exe".to_string(),
+            size: 1,
+            source: "anon".to_string(),
+            submitted: Local::now(),
+            state: ReviewState::Pending,
+            reviewer: None,
+            reviewed: None,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn rate_limiter_rejects_until_the_window_passes() {
+        let limiter = RateLimiter::default();
+        let limit = limit(2, 60);
+        let start = Instant::now();
+        assert_eq!(limiter.check("ip:a", &limit, start).unwrap(), None);
+        assert_eq!(limiter.check("ip:a", &limit, start).unwrap(), None);
+        let later = start + Duration::from_secs(20);
+        assert_eq!(
+            limiter.check("ip:a", &limit, later).unwrap(),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(limiter.check("ip:b", &limit, later).unwrap(), None);
+        let after = start + Duration::from_secs(60);
+        assert_eq!(limiter.check("ip:a", &limit, after).unwrap(), None);
+    }
+
+    #[test]
+    fn rate_limiter_prunes_keys_with_their_own_window() {
+        let limiter = RateLimiter::default();
+        let token = limit(1, 3600);
+        let anonymous = limit(10, 10);
+        let start = Instant::now();
+        assert_eq!(limiter.check("token:a", &token, start).unwrap(), None);
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.check("ip:b", &anonymous, later).unwrap(), None);
+        assert!(limiter.check("token:a", &token, later).unwrap().is_some());
+    }
+
+    #[test]
+    fn review_queue_publishes_only_approved_submissions() {
+        let path = std::env::temp_dir().join(format!("portal-{}.json", Uuid::new_v4()));
+        let mut queue = ReviewQueue::open(&path).unwrap();
+        queue.add(submission("a", "analysis-a")).unwrap();
+        queue.add(submission("b", "analysis-b")).unwrap();
+        assert!(!queue.is_published("analysis-a"));
+        assert_eq!(queue.with_state(Some(ReviewState::Pending)).len(), 2);
+
+        let decided = queue
+            .decide(
+                "a",
+                ReviewState::Approved,
+                "reviewer",
+                Some("ok".to_string()),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(decided.reviewer.as_deref(), Some("reviewer"));
+        queue
+            .decide("b", ReviewState::Rejected, "reviewer", None)
+            .unwrap();
+        assert!(queue
+            .decide("missing", ReviewState::Approved, "reviewer", None)
+            .unwrap()
+            .is_none());
+
+        let reopened = ReviewQueue::open(&path).unwrap();
+        assert!(reopened.is_published("analysis-a"));
+        assert!(!reopened.is_published("analysis-b"));
+        assert_eq!(reopened.with_state(None).len(), 2);
+        assert_eq!(reopened.get("a").unwrap().note.as_deref(), Some("ok"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
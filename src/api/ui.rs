@@ -0,0 +1,607 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Form, Router};
+use rand::Rng;
+use serde::Deserialize;
+
+use super::users::Principal;
+use super::{live_status, visible_result, ApiError, ApiResult, ApiState};
+use crate::analyzer::live::LiveAction;
+use crate::orchestrator::Hypervisor;
+use crate::process_tree::{Process, ProcessTree};
+use crate::report::{escape, SandboxReport};
+use crate::scheduler::{JobState, JobStore};
+use crate::sysmon_event::SysmonEvent;
+
+pub const SESSION_COOKIE: &str = "sandbox_session";
+pub const SESSION_TTL: Duration = Duration::from_secs(8 * 60 * 60);
+
+const HTMX_URL: &str = "https://unpkg.com/htmx.org@1.9.12";
+const EVENTS_PER_PAGE: usize = 100;
+const MAX_RUNS: usize = 200;
+const LIVE_REFRESH: &str = "every 5s";
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 1em 2em; } \
+table { border-collapse: collapse; } td, th { border: 1px solid #999; padding: 2px 6px; text-align: left; vertical-align: top; } \
+nav a { margin-right: 1em; } ul.tree { list-style: none; padding-left: 1.2em; border-left: 1px dotted #999; } \
+.critical, .high { color: #b00; } .medium { color: #b60; } code { white-space: pre-wrap; word-break: break-all; }";
+
+#[derive(Deserialize, Debug)]
+struct Login {
+    api_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventParams {
+    q: Option<String>,
+    event_id: Option<u8>,
+    page: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl Sessions {
+    pub fn create(&self, key: &str, now: Instant) -> anyhow::Result<String> {
+        let mut sessions = self
+            .sessions
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Session store is poisoned"))?;
+        sessions.retain(|_, (_, expires)| *expires > now);
+        let id = rand::thread_rng().gen::<[u8; 32]>().iter().fold(
+            String::with_capacity(64),
+            |mut id, b| {
+                let _ = write!(id, "{:02x}", b);
+                id
+            },
+        );
+        sessions.insert(id.clone(), (key.to_string(), now + SESSION_TTL));
+        Ok(id)
+    }
+
+    pub fn key(&self, id: &str, now: Instant) -> Option<String> {
+        let mut sessions = self.sessions.lock().ok()?;
+        match sessions.get(id) {
+            Some((key, expires)) if *expires > now => Some(key.clone()),
+            Some(_) => {
+                sessions.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn remove(&self, id: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(id);
+        }
+    }
+}
+
+pub(super) fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <script src=\"{}\"></script>\n<style>{}</style>\n</head>\n<body>\n\
+         <nav><a href=\"/ui\">Runs</a>\
+         <form method=\"post\" action=\"/ui/logout\" style=\"display: inline\">\
+         <button type=\"submit\">Sign out</button></form></nav>\n<h1>{}</h1>\n{}</body>\n</html>\n",
+        escape(title),
+        HTMX_URL,
+        STYLE,
+        escape(title),
+        body
+    ))
+}
+
+async fn login_form() -> Html<String> {
+    page(
+        "Sign in",
+        "<form method=\"post\" action=\"/ui/login\">\
+         <label>API key or token <input type=\"password\" name=\"api_key\" autofocus></label> \
+         <button type=\"submit\">Sign in</button></form>\n",
+    )
+}
+
+async fn login<H, S>(State(state): State<ApiState<H, S>>, Form(login): Form<Login>) -> Response {
+    if !matches!(state.config.principal(&login.api_key), Ok(Some(_))) {
+        return (StatusCode::UNAUTHORIZED, login_form().await).into_response();
+    }
+    let id = match state.sessions.create(&login.api_key, Instant::now()) {
+        Ok(id) => id,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; Secure; SameSite=Strict; Max-Age={}",
+        SESSION_COOKIE,
+        id,
+        SESSION_TTL.as_secs()
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/ui")).into_response()
+}
+
+async fn logout<H, S>(State(state): State<ApiState<H, S>>, headers: HeaderMap) -> Response {
+    if let Some(id) = session_id(&headers) {
+        state.sessions.remove(id);
+    }
+    let cookie = format!(
+        "{}=; Path=/; HttpOnly; Secure; SameSite=Strict; Max-Age=0",
+        SESSION_COOKIE
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/ui/login")).into_response()
+}
+
+async fn runs<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+) -> Html<String>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let mut jobs = state.scheduler.jobs();
+    jobs.retain(|j| principal.can_see(j.tenant.as_deref()));
+    jobs.sort_by_key(|j| std::cmp::Reverse(j.submitted));
+
+    let mut body = String::new();
+    let _ = writeln!(
+        body,
+        "<table>\n<tr><th>Submitted</th><th>File</th><th>State</th><th>Machine</th><th>Tags</th><th>Analysis</th></tr>"
+    );
+    for job in jobs.iter().take(MAX_RUNS) {
+        let job_state = match job.state {
+            JobState::Running if state.config.live.is_some() => format!(
+                "<a href=\"/ui/jobs/{}/live\">{}</a>",
+                escape(&job.id),
+                job.state.name()
+            ),
+            _ => job.state.name().to_string(),
+        };
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><a href=\"/ui/analyses/{}\">{}</a></td></tr>",
+            job.submitted.format("%Y-%m-%d %H:%M:%S"),
+            escape(&job.request.file_name),
+            job_state,
+            escape(job.machine.as_deref().unwrap_or("-")),
+            escape(&job.tags.join(", ")),
+            escape(&job.analysis_id),
+            escape(&job.analysis_id)
+        );
+    }
+    let _ = writeln!(body, "</table>");
+    if jobs.len() > MAX_RUNS {
+        let _ = writeln!(
+            body,
+            "<p>Showing the latest {} of {} runs.</p>",
+            MAX_RUNS,
+            jobs.len()
+        );
+    }
+    page("Runs", &body)
+}
+
+async fn load_report<H, S>(
+    state: &ApiState<H, S>,
+    principal: &Principal,
+    id: &str,
+) -> ApiResult<SandboxReport> {
+    let result = visible_result(state, principal, id).await?;
+    if result.execution_logs.is_empty() {
+        return Err(ApiError::not_found("Finished execution"));
+    }
+    Ok(state.config.report(&result)?)
+}
+
+fn write_process(
+    html: &mut String,
+    tree: &ProcessTree,
+    process: &Process,
+    depth: usize,
+) -> std::fmt::Result {
+    write!(
+        html,
+        "<li><b>{}</b> [{}] <code>{}</code>",
+        escape(&process.image),
+        process
+            .process_id
+            .map_or_else(|| "?".to_string(), |p| p.to_string()),
+        escape(&process.command_line)
+    )?;
+    let children = tree.children(&process.guid);
+    if !children.is_empty() && depth < 64 {
+        writeln!(html, "<ul class=\"tree\">")?;
+        for child in children {
+            write_process(html, tree, child, depth + 1)?;
+        }
+        writeln!(html, "</ul>")?;
+    }
+    writeln!(html, "</li>")
+}
+
+fn network_map(report: &SandboxReport) -> String {
+    let mut links: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for connect in &report.network {
+        let endpoint = match (&connect.destination_hostname, connect.destination_ip) {
+            (Some(host), _) if !host.is_empty() => host.clone(),
+            (_, Some(ip)) => ip.to_string(),
+            _ => continue,
+        };
+        let endpoint = match connect.destination_port {
+            Some(port) => format!("{}:{}", endpoint, port),
+            None => endpoint,
+        };
+        let image = connect.image.rsplit('\\').next().unwrap_or(&connect.image);
+        *links.entry((image.to_string(), endpoint)).or_default() += 1;
+    }
+    if links.is_empty() {
+        return "<p>No network connections.</p>\n".to_string();
+    }
+
+    let mut processes: Vec<&str> = links.keys().map(|(p, _)| p.as_str()).collect();
+    processes.dedup();
+    let mut endpoints: Vec<&str> = links.keys().map(|(_, e)| e.as_str()).collect();
+    endpoints.sort();
+    endpoints.dedup();
+    let y = |i: usize| 20 + i * 24;
+    let height = y(processes.len().max(endpoints.len()));
+
+    let mut svg = format!(
+        "<svg width=\"900\" height=\"{}\" font-size=\"12\" xmlns=\"http://www.w3.org/2000/svg\">\n",
+        height
+    );
+    for ((process, endpoint), count) in &links {
+        let from = processes.iter().position(|p| p == process).unwrap_or(0);
+        let to = endpoints.iter().position(|e| e == endpoint).unwrap_or(0);
+        let _ = writeln!(
+            svg,
+            "<line x1=\"260\" y1=\"{}\" x2=\"600\" y2=\"{}\" stroke=\"#888\" stroke-width=\"{}\"><title>{} connections</title></line>",
+            y(from) - 4,
+            y(to) - 4,
+            (*count as f64).log2().max(0.0) + 1.0,
+            count
+        );
+    }
+    for (i, process) in processes.iter().enumerate() {
+        let _ = writeln!(
+            svg,
+            "<text x=\"250\" y=\"{}\" text-anchor=\"end\">{}</text>",
+            y(i),
+            escape(process)
+        );
+    }
+    for (i, endpoint) in endpoints.iter().enumerate() {
+        let _ = writeln!(
+            svg,
+            "<text x=\"610\" y=\"{}\">{}</text>",
+            y(i),
+            escape(endpoint)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+async fn analysis<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Html<String>> {
+    let report = load_report(&state, &principal, &id).await?;
+    let id = escape(&id);
+
+    let mut body = String::new();
+    let _ = writeln!(
+        body,
+        "<p>SHA3-512 <code>{}</code><br>Execution {} at {}<br>Score <b>{:.1}</b><br>Verdict <b>{}</b></p>",
+        escape(&report.hash),
+        escape(&report.execution_id),
+        report.time.format("%Y-%m-%d %H:%M:%S"),
+        report.score.score,
+        report.verdict.verdict
+    );
+    let _ = writeln!(body, "<h2>Verdict rationale</h2>\n<ol>");
+    for step in &report.verdict.rationale {
+        let _ = writeln!(
+            body,
+            "<li>{}: {} &rArr; {}</li>",
+            escape(&step.step),
+            escape(&step.description),
+            step.verdict
+        );
+    }
+    let _ = writeln!(body, "</ol>");
+    let _ = writeln!(
+        body,
+        "<p>Download: <a href=\"/analyses/{id}/report?format=html\">HTML report</a> \
+         <a href=\"/analyses/{id}/report?format=json\">JSON report</a> \
+         <a href=\"/analyses/{id}/report?format=timesketch\">Timesketch timeline</a> \
+         <a href=\"/analyses/{id}/report?format=graphml\">entity graph (GraphML)</a> \
+         <a href=\"/analyses/{id}/report?format=cypher\">entity graph (Cypher)</a> \
+         <a href=\"/analyses/{id}/archive\">artifacts (zip)</a> \
+         <a href=\"/analyses/{id}/archive?format=cart\">artifacts (CaRT)</a></p>"
+    );
+
+    let _ = writeln!(body, "<h2>Detections</h2>\n<table>\n<tr><th>Level</th><th>Source</th><th>Name</th><th>Tags</th><th>Events</th></tr>");
+    for detection in &report.detections {
+        let level = detection.level.as_deref().unwrap_or("-");
+        let _ = writeln!(
+            body,
+            "<tr><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(level),
+            escape(level),
+            escape(&detection.source),
+            escape(&detection.name),
+            escape(&detection.tags.join(", ")),
+            detection.events.len()
+        );
+    }
+    let _ = writeln!(body, "</table>");
+
+    let _ = writeln!(body, "<h2>Process tree</h2>\n<ul class=\"tree\">");
+    for root in report.process_tree.roots() {
+        let _ = write_process(&mut body, &report.process_tree, root, 0);
+    }
+    let _ = writeln!(body, "</ul>");
+
+    let _ = writeln!(body, "<h2>Network</h2>");
+    body.push_str(&network_map(&report));
+
+    let _ = writeln!(
+        body,
+        "<h2>Events</h2>\n<form hx-get=\"/ui/analyses/{id}/events\" hx-target=\"#events\" hx-trigger=\"input changed delay:300ms, submit\">\
+         <input type=\"search\" name=\"q\" placeholder=\"Search event data\"> \
+         <input type=\"number\" name=\"event_id\" min=\"1\" max=\"255\" placeholder=\"Event id\"></form>\n\
+         <table>\n<thead><tr><th>Time</th><th>Event</th><th>Data</th></tr></thead>\n\
+         <tbody id=\"events\" hx-get=\"/ui/analyses/{id}/events\" hx-trigger=\"load\"></tbody>\n</table>"
+    );
+
+    Ok(page(&format!("Analysis {}", report.id), &body))
+}
+
+fn matches(event: &SysmonEvent, params: &EventParams, query: &str) -> bool {
+    if params
+        .event_id
+        .is_some_and(|id| event.event_id.value() != id)
+    {
+        return false;
+    }
+    query.is_empty()
+        || event
+            .event_data
+            .values()
+            .any(|v| v.to_lowercase().contains(query))
+}
+
+async fn events<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    Query(params): Query<EventParams>,
+) -> ApiResult<Html<String>> {
+    let report = load_report(&state, &principal, &id).await?;
+    let query = params
+        .q
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    let page = params.page.unwrap_or(0);
+
+    let mut matching = report
+        .events
+        .iter()
+        .filter(|e| matches(e, &params, &query))
+        .skip(page * EVENTS_PER_PAGE);
+    let mut rows = String::new();
+    for event in matching.by_ref().take(EVENTS_PER_PAGE) {
+        let mut fields: Vec<(&String, &String)> = event.event_data.iter().collect();
+        fields.sort();
+        let data: Vec<String> = fields
+            .iter()
+            .map(|(k, v)| format!("<b>{}</b>: {}", escape(k), escape(v)))
+            .collect();
+        let _ = writeln!(
+            rows,
+            "<tr><td>{}</td><td>{} {}</td><td><code>{}</code></td></tr>",
+            event.time_created.format("%H:%M:%S%.3f"),
+            event.event_id.value(),
+            event.event_id.name(),
+            data.join("<br>")
+        );
+    }
+    if matching.next().is_some() {
+        let mut next = format!("/ui/analyses/{}/events?page={}", escape(&id), page + 1);
+        if !query.is_empty() {
+            let _ = write!(next, "&amp;q={}", escape(&urlencode(&query)));
+        }
+        if let Some(event_id) = params.event_id {
+            let _ = write!(next, "&amp;event_id={}", event_id);
+        }
+        let _ = writeln!(
+            rows,
+            "<tr><td colspan=\"3\"><button hx-get=\"{}\" hx-target=\"closest tr\" hx-swap=\"outerHTML\">Load more</button></td></tr>",
+            next
+        );
+    }
+    if rows.is_empty() {
+        rows.push_str("<tr><td colspan=\"3\">No matching events.</td></tr>\n");
+    }
+    Ok(Html(rows))
+}
+
+async fn live<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<Html<String>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let status = live_status(&state, &principal, &id)?;
+    let id = escape(&id);
+    let body = format!(
+        "<p>Provisional detections on {} while the run is executing. \
+         The final report replaces these once the run completes.</p>\n\
+         <div hx-get=\"/ui/jobs/{id}/live/findings\" hx-trigger=\"load, {}\"></div>\n",
+        escape(&status.machine),
+        LIVE_REFRESH
+    );
+    Ok(page(&format!("Live run {}", id), &body))
+}
+
+async fn live_findings<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> Html<String>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    let Ok(status) = live_status(&state, &principal, &id) else {
+        let analysis = state
+            .scheduler
+            .job(&id)
+            .filter(|j| principal.can_see(j.tenant.as_deref()))
+            .map(|j| j.analysis_id);
+        return Html(match analysis {
+            Some(analysis) => format!(
+                "<p>The run has finished. See <a href=\"/ui/analyses/{}\">the analysis</a>.</p>",
+                escape(&analysis)
+            ),
+            None => "<p>The run has finished.</p>".to_string(),
+        });
+    };
+
+    let mut body = String::new();
+    let _ = writeln!(
+        body,
+        "<p>Started {}, {} events observed.</p>",
+        status.started.format("%Y-%m-%d %H:%M:%S"),
+        status.events
+    );
+    if !status.decisions.is_empty() {
+        let _ = writeln!(body, "<ul>");
+        for decision in &status.decisions {
+            let _ = writeln!(
+                body,
+                "<li>{} {} after {}</li>",
+                decision.time.format("%H:%M:%S"),
+                match decision.action {
+                    LiveAction::Extend => "Extended run",
+                    LiveAction::Terminate => "Terminated run",
+                },
+                escape(&decision.detection)
+            );
+        }
+        let _ = writeln!(body, "</ul>");
+    }
+    let _ = writeln!(body, "<table>\n<tr><th>Time</th><th>Level</th><th>Source</th><th>Name</th><th>Tags</th><th>Events</th></tr>");
+    for finding in &status.findings {
+        let detection = &finding.detection;
+        let level = detection.level.as_deref().unwrap_or("-");
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            finding.time.format("%H:%M:%S"),
+            escape(level),
+            escape(level),
+            escape(&detection.source),
+            escape(&detection.name),
+            escape(&detection.tags.join(", ")),
+            detection.events.len()
+        );
+    }
+    let _ = writeln!(body, "</table>");
+    Html(body)
+}
+
+fn urlencode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub(super) fn public_routes<H, S>() -> Router<ApiState<H, S>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore + Send + 'static,
+{
+    Router::new()
+        .route("/ui/login", get(login_form).post(login::<H, S>))
+        .route("/ui/logout", post(logout::<H, S>))
+}
+
+pub(super) fn routes<H, S>() -> Router<ApiState<H, S>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore + Send + 'static,
+{
+    Router::new()
+        .route("/ui", get(runs::<H, S>))
+        .route("/ui/analyses/:id", get(analysis::<H, S>))
+        .route("/ui/analyses/:id/events", get(events::<H, S>))
+        .route("/ui/jobs/:id/live", get(live::<H, S>))
+        .route("/ui/jobs/:id/live/findings", get(live_findings::<H, S>))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_are_opaque_and_expire() {
+        let sessions = Sessions::default();
+        let now = Instant::now();
+        let id = sessions.create("secret", now).unwrap();
+        assert_eq!(id.len(), 64);
+        assert!(!id.contains("secret"));
+        assert_ne!(id, sessions.create("secret", now).unwrap());
+        assert_eq!(sessions.key(&id, now).as_deref(), Some("secret"));
+        assert_eq!(sessions.key(&id, now + SESSION_TTL), None);
+        assert_eq!(sessions.key(&id, now), None);
+    }
+
+    #[test]
+    fn logout_removes_session() {
+        let sessions = Sessions::default();
+        let now = Instant::now();
+        let id = sessions.create("secret", now).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            format!("theme=dark; {}={}", SESSION_COOKIE, id)
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(session_id(&headers), Some(id.as_str()));
+        sessions.remove(&id);
+        assert_eq!(sessions.key(&id, now), None);
+    }
+}
@@ -0,0 +1,628 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{delete, get, post};
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::{ApiError, ApiResult, ApiState};
+use crate::orchestrator::Hypervisor;
+use crate::scheduler::JobStore;
+
+const TOKEN_PREFIX: &str = "sbx_";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Submitter,
+    Analyst,
+    Admin,
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Submitter => "submitter",
+            Self::Analyst => "analyst",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "submitter" => Ok(Self::Submitter),
+            "analyst" => Ok(Self::Analyst),
+            "admin" => Ok(Self::Admin),
+            _ => bail!("Unknown role '{}', expected submitter, analyst or admin", s),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
+    pub name: String,
+    pub tenant: Option<String>,
+    pub role: Role,
+    pub created: DateTime<Local>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    pub user: String,
+    pub label: Option<String>,
+    pub sha256: String,
+    pub created: DateTime<Local>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub tenant: Option<String>,
+    pub role: Role,
+}
+
+impl Principal {
+    pub fn root() -> Self {
+        Self {
+            name: "api-key".to_string(),
+            tenant: None,
+            role: Role::Admin,
+        }
+    }
+
+    pub fn require(&self, role: Role) -> ApiResult<()> {
+        if self.role >= role {
+            return Ok(());
+        }
+        Err(ApiError(
+            StatusCode::FORBIDDEN,
+            format!("The {} role is required", role.name()),
+        ))
+    }
+
+    pub fn can_see(&self, tenant: Option<&str>) -> bool {
+        match &self.tenant {
+            None => true,
+            Some(own) => tenant == Some(own.as_str()),
+        }
+    }
+
+    fn can_manage(&self, user: &User) -> bool {
+        self.role == Role::Admin && self.can_see(user.tenant.as_deref())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct UserFile {
+    users: Vec<User>,
+    tokens: Vec<ApiToken>,
+}
+
+#[derive(Debug)]
+pub struct UserStore {
+    path: PathBuf,
+    data: UserFile,
+}
+
+fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+impl UserStore {
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let data = if path.exists() {
+            serde_json::from_slice(&std::fs::read(&path)?)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            UserFile::default()
+        };
+        Ok(Self { path, data })
+    }
+
+    fn save(&self) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.data)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn users(&self) -> &[User] {
+        &self.data.users
+    }
+
+    pub fn user(&self, name: &str) -> Option<&User> {
+        self.data.users.iter().find(|u| u.name == name)
+    }
+
+    pub fn add_user(&mut self, name: &str, tenant: Option<&str>, role: Role) -> Result<User> {
+        if name.is_empty() {
+            bail!("User name is empty");
+        }
+        if self.user(name).is_some() {
+            bail!("User {} already exists", name);
+        }
+        let user = User {
+            name: name.to_string(),
+            tenant: tenant.map(str::to_string),
+            role,
+            created: Local::now(),
+        };
+        self.data.users.push(user.clone());
+        self.save()?;
+        Ok(user)
+    }
+
+    pub fn remove_user(&mut self, name: &str) -> Result<bool> {
+        let before = self.data.users.len();
+        self.data.users.retain(|u| u.name != name);
+        self.data.tokens.retain(|t| t.user != name);
+        self.save()?;
+        Ok(self.data.users.len() != before)
+    }
+
+    pub fn issue_token(&mut self, user: &str, label: Option<&str>) -> Result<(ApiToken, String)> {
+        if self.user(user).is_none() {
+            bail!("No user {}", user);
+        }
+        let secret = format!("{}{}", TOKEN_PREFIX, Uuid::new_v4().simple());
+        let token = ApiToken {
+            id: Uuid::new_v4().to_string(),
+            user: user.to_string(),
+            label: label.map(str::to_string),
+            sha256: token_hash(&secret),
+            created: Local::now(),
+        };
+        self.data.tokens.push(token.clone());
+        self.save()?;
+        Ok((token, secret))
+    }
+
+    pub fn token(&self, id: &str) -> Option<&ApiToken> {
+        self.data.tokens.iter().find(|t| t.id == id)
+    }
+
+    pub fn revoke_token(&mut self, id: &str) -> Result<bool> {
+        let before = self.data.tokens.len();
+        self.data.tokens.retain(|t| t.id != id);
+        self.save()?;
+        Ok(self.data.tokens.len() != before)
+    }
+
+    pub fn authenticate(&self, secret: &str) -> Option<Principal> {
+        let hash = token_hash(secret);
+        let token = self.data.tokens.iter().find(|t| t.sha256 == hash)?;
+        let user = self.user(&token.user)?;
+        Some(Principal {
+            name: user.name.clone(),
+            tenant: user.tenant.clone(),
+            role: user.role,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct NewUser {
+    name: String,
+    tenant: Option<String>,
+    role: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct NewToken {
+    label: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct IssuedToken {
+    #[serde(flatten)]
+    token: ApiToken,
+    secret: String,
+}
+
+fn store<H, S>(state: &ApiState<H, S>) -> ApiResult<std::sync::MutexGuard<'_, UserStore>> {
+    state
+        .config
+        .users
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("User store"))?
+        .lock()
+        .map_err(|_| anyhow::anyhow!("User store is poisoned").into())
+}
+
+async fn list_users<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+) -> ApiResult<Json<Vec<User>>> {
+    principal.require(Role::Admin)?;
+    let store = store(&state)?;
+    Ok(Json(
+        store
+            .users()
+            .iter()
+            .filter(|u| principal.can_see(u.tenant.as_deref()))
+            .cloned()
+            .collect(),
+    ))
+}
+
+async fn create_user<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Json(new): Json<NewUser>,
+) -> ApiResult<(StatusCode, Json<User>)> {
+    principal.require(Role::Admin)?;
+    let role: Role = new
+        .role
+        .parse()
+        .map_err(|e: anyhow::Error| ApiError::bad_request(&e.to_string()))?;
+    let tenant = match (&principal.tenant, new.tenant) {
+        (Some(own), Some(tenant)) if *own != tenant => {
+            return Err(ApiError::bad_request(
+                "Users can only be created in your own tenant",
+            ))
+        }
+        (Some(own), _) => Some(own.clone()),
+        (None, tenant) => tenant,
+    };
+    let user = store(&state)?
+        .add_user(&new.name, tenant.as_deref(), role)
+        .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+async fn delete_user<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(name): Path<String>,
+) -> ApiResult<StatusCode> {
+    principal.require(Role::Admin)?;
+    let mut store = store(&state)?;
+    match store.user(&name) {
+        Some(user) if principal.can_manage(user) => {
+            store.remove_user(&name)?;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        _ => Err(ApiError::not_found("User")),
+    }
+}
+
+async fn create_token<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(name): Path<String>,
+    Json(new): Json<NewToken>,
+) -> ApiResult<(StatusCode, Json<IssuedToken>)> {
+    let mut store = store(&state)?;
+    let allowed = match store.user(&name) {
+        Some(user) => user.name == principal.name || principal.can_manage(user),
+        None => false,
+    };
+    if !allowed {
+        return Err(ApiError::not_found("User"));
+    }
+    let (token, secret) = store.issue_token(&name, new.label.as_deref())?;
+    Ok((StatusCode::CREATED, Json(IssuedToken { token, secret })))
+}
+
+async fn revoke_token<H, S>(
+    State(state): State<ApiState<H, S>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let mut store = store(&state)?;
+    let allowed = match store.token(&id).and_then(|t| store.user(&t.user)) {
+        Some(user) => user.name == principal.name || principal.can_manage(user),
+        None => false,
+    };
+    if !allowed {
+        return Err(ApiError::not_found("Token"));
+    }
+    store.revoke_token(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub(super) fn routes<H, S>() -> Router<ApiState<H, S>>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore + Send + 'static,
+{
+    Router::new()
+        .route("/users", get(list_users::<H, S>).post(create_user::<H, S>))
+        .route("/users/:name", delete(delete_user::<H, S>))
+        .route("/users/:name/tokens", post(create_token::<H, S>))
+        .route("/tokens/:id", delete(revoke_token::<H, S>))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use axum::body::Body;
+    use axum::http::{header, Method, Request};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::analysis_result::AnalysisResultManager;
+    use crate::api::{router, ApiConfig, API_KEY_HEADER};
+    use crate::orchestrator::virtualbox::VirtualBox;
+    use crate::orchestrator::Orchestrator;
+    use crate::scheduler::{MemoryStore, Scheduler};
+
+    const ROOT_KEY: &str = "root-key";
+
+    struct Fixture {
+        app: Router,
+        store: Arc<Mutex<UserStore>>,
+        tokens: HashMap<&'static str, String>,
+    }
+
+    async fn fixture() -> Fixture {
+        let path = std::env::temp_dir().join(format!("users-{}.json", Uuid::new_v4()));
+        let mut store = UserStore::open(&path).unwrap();
+        let mut tokens = HashMap::new();
+        for (name, tenant, role) in [
+            ("global", None, Role::Admin),
+            ("admin-a", Some("a"), Role::Admin),
+            ("analyst-a", Some("a"), Role::Analyst),
+            ("submitter-a", Some("a"), Role::Submitter),
+            ("admin-b", Some("b"), Role::Admin),
+            ("analyst-b", Some("b"), Role::Analyst),
+        ] {
+            store.add_user(name, tenant, role).unwrap();
+            tokens.insert(name, store.issue_token(name, None).unwrap().1);
+        }
+        let store = Arc::new(Mutex::new(store));
+        let config = ApiConfig {
+            api_keys: vec![ROOT_KEY.to_string()],
+            users: Some(store.clone()),
+            ..ApiConfig::default()
+        };
+        let scheduler = Scheduler::new(
+            Orchestrator::new(VirtualBox::new()),
+            Vec::new(),
+            MemoryStore::new(),
+        )
+        .unwrap();
+        let results = AnalysisResultManager::init().await.unwrap();
+        Fixture {
+            app: router(Arc::new(scheduler), Arc::new(results), Arc::new(config)),
+            store,
+            tokens,
+        }
+    }
+
+    impl Fixture {
+        async fn call(
+            &self,
+            method: Method,
+            uri: &str,
+            key: &str,
+            body: Option<Value>,
+        ) -> (StatusCode, Value) {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(API_KEY_HEADER, key)
+                .header(header::CONTENT_TYPE, "application/json");
+            let body = body.map_or_else(Body::empty, |b| Body::from(b.to_string()));
+            let response = self
+                .app
+                .clone()
+                .oneshot(request.body(body).unwrap())
+                .await
+                .unwrap();
+            let status = response.status();
+            let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            (
+                status,
+                serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+            )
+        }
+
+        async fn as_user(
+            &self,
+            method: Method,
+            uri: &str,
+            user: &str,
+            body: Option<Value>,
+        ) -> (StatusCode, Value) {
+            self.call(method, uri, &self.tokens[user], body).await
+        }
+
+        fn exists(&self, name: &str) -> bool {
+            self.store.lock().unwrap().user(name).is_some()
+        }
+    }
+
+    fn names(users: &Value) -> Vec<&str> {
+        let mut names: Vec<&str> = users
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|u| u["name"].as_str().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn roles_are_ordered() {
+        assert!(Role::Submitter < Role::Analyst && Role::Analyst < Role::Admin);
+        let analyst = Principal {
+            name: "analyst".to_string(),
+            tenant: None,
+            role: Role::Analyst,
+        };
+        assert!(analyst.require(Role::Submitter).is_ok());
+        assert!(analyst.require(Role::Analyst).is_ok());
+        assert!(analyst.require(Role::Admin).is_err());
+    }
+
+    #[test]
+    fn tenant_principals_only_see_their_tenant() {
+        let principal = Principal {
+            name: "admin-a".to_string(),
+            tenant: Some("a".to_string()),
+            role: Role::Admin,
+        };
+        assert!(principal.can_see(Some("a")));
+        assert!(!principal.can_see(Some("b")));
+        assert!(!principal.can_see(None));
+        assert!(Principal::root().can_see(Some("b")));
+        assert!(Principal::root().can_see(None));
+    }
+
+    #[tokio::test]
+    async fn tokens_are_stored_hashed() {
+        let fixture = fixture().await;
+        let secret = &fixture.tokens["analyst-a"];
+        assert!(secret.starts_with(TOKEN_PREFIX));
+        let store = fixture.store.lock().unwrap();
+        let on_disk = std::fs::read_to_string(&store.path).unwrap();
+        assert!(!on_disk.contains(secret.as_str()));
+        assert!(on_disk.contains(&token_hash(secret)));
+        assert_eq!(
+            store.authenticate(secret).map(|p| p.name),
+            Some("analyst-a".to_string())
+        );
+        assert_eq!(store.authenticate(&token_hash(secret)), None);
+    }
+
+    #[tokio::test]
+    async fn unknown_and_revoked_credentials_are_rejected() {
+        let fixture = fixture().await;
+        let (status, _) = fixture.call(Method::GET, "/users", "wrong", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, users) = fixture.call(Method::GET, "/users", ROOT_KEY, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(names(&users).len(), 6);
+
+        let (status, issued) = fixture
+            .as_user(
+                Method::POST,
+                "/users/admin-b/tokens",
+                "admin-b",
+                Some(json!({"label": "ci"})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let secret = issued["secret"].as_str().unwrap();
+        let id = issued["id"].as_str().unwrap();
+        let (status, _) = fixture.call(Method::GET, "/users", secret, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let uri = format!("/tokens/{}", id);
+        let (status, _) = fixture.as_user(Method::DELETE, &uri, "admin-a", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = fixture.call(Method::DELETE, &uri, secret, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = fixture.call(Method::GET, "/users", secret, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn tenant_admins_are_scoped_to_their_tenant() {
+        let fixture = fixture().await;
+        let (status, users) = fixture
+            .as_user(Method::GET, "/users", "admin-a", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(names(&users), ["admin-a", "analyst-a", "submitter-a"]);
+
+        let (status, _) = fixture
+            .as_user(Method::DELETE, "/users/analyst-b", "admin-a", None)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(fixture.exists("analyst-b"));
+        let (status, _) = fixture
+            .as_user(Method::DELETE, "/users/global", "admin-a", None)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(fixture.exists("global"));
+        let (status, _) = fixture
+            .as_user(
+                Method::POST,
+                "/users/analyst-b/tokens",
+                "admin-a",
+                Some(json!({})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = fixture
+            .as_user(
+                Method::POST,
+                "/users",
+                "admin-a",
+                Some(json!({"name": "intruder", "tenant": "b", "role": "admin"})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, user) = fixture
+            .as_user(
+                Method::POST,
+                "/users",
+                "admin-a",
+                Some(json!({"name": "analyst-a2", "role": "analyst"})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(user["tenant"], "a");
+
+        let (status, _) = fixture
+            .as_user(Method::DELETE, "/users/analyst-a", "admin-a", None)
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(!fixture.exists("analyst-a"));
+        let (status, _) = fixture
+            .as_user(Method::DELETE, "/users/analyst-b", "global", None)
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn user_management_requires_admin() {
+        let fixture = fixture().await;
+        for user in ["analyst-a", "submitter-a"] {
+            let (status, _) = fixture.as_user(Method::GET, "/users", user, None).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            let (status, _) = fixture
+                .as_user(Method::DELETE, "/users/submitter-a", user, None)
+                .await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert!(fixture.exists("submitter-a"));
+        }
+        let (status, _) = fixture
+            .as_user(
+                Method::POST,
+                "/users/analyst-a/tokens",
+                "analyst-a",
+                Some(json!({})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn submitters_cannot_read_results() {
+        let fixture = fixture().await;
+        for uri in ["/analyses/some-id/report", "/analyses/some-id/artifacts"] {
+            let (status, body) = fixture.as_user(Method::GET, uri, "submitter-a", None).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert!(body.to_string().contains("analyst"));
+        }
+    }
+}
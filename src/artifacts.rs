@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset};
+use fuzzyhash::FuzzyHash;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::path::normalize;
+use crate::similarity::tlsh;
+use crate::static_analysis::entropy;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub const ARTIFACTS_FILE: &str = "artifacts.json";
+
+pub const FILE_EVENTS: &[SysmonEventId] = &[
+    SysmonEventId::FILE_CREATE,
+    SysmonEventId::FILE_CREATE_STREAM_HASH,
+    SysmonEventId::FILE_DELETE,
+    SysmonEventId::FILE_DELETE_DETECTED,
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactOperation {
+    Created,
+    StreamCreated,
+    Deleted,
+    Dumped,
+}
+
+impl ArtifactOperation {
+    fn from_event(event: &SysmonEvent) -> Option<Self> {
+        match event.event_id {
+            SysmonEventId::FILE_CREATE => Some(Self::Created),
+            SysmonEventId::FILE_CREATE_STREAM_HASH => Some(Self::StreamCreated),
+            SysmonEventId::FILE_DELETE | SysmonEventId::FILE_DELETE_DETECTED => Some(Self::Deleted),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileHashes {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+    pub ssdeep: String,
+    #[serde(default)]
+    pub tlsh: Option<String>,
+}
+
+impl FileHashes {
+    pub fn compute(data: &[u8]) -> Self {
+        Self {
+            md5: format!("{:x}", Md5::digest(data)),
+            sha1: format!("{:x}", Sha1::digest(data)),
+            sha256: format!("{:x}", Sha256::digest(data)),
+            ssdeep: FuzzyHash::new(data).to_string(),
+            tlsh: tlsh(data),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Provenance {
+    pub operation: ArtifactOperation,
+    pub time: DateTime<FixedOffset>,
+    pub process_guid: Option<String>,
+    pub image: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Artifact {
+    pub path: String,
+    pub size: u64,
+    pub hashes: FileHashes,
+    pub provenance: Vec<Provenance>,
+    #[serde(default)]
+    pub entropy: Option<f64>,
+}
+
+impl Artifact {
+    pub fn file_name(&self) -> &str {
+        &self.hashes.sha256
+    }
+
+    pub fn deleted(&self) -> bool {
+        self.provenance
+            .iter()
+            .any(|p| p.operation == ArtifactOperation::Deleted)
+    }
+
+    pub fn dumped(&self) -> bool {
+        self.provenance
+            .iter()
+            .any(|p| p.operation == ArtifactOperation::Dumped)
+    }
+}
+
+pub fn is_file_event(event: &SysmonEvent) -> bool {
+    FILE_EVENTS.contains(&event.event_id)
+}
+
+pub fn provenance(events: &[SysmonEvent]) -> HashMap<String, Vec<Provenance>> {
+    let mut provenance: HashMap<String, Vec<Provenance>> = HashMap::new();
+    for event in events {
+        let Some(operation) = ArtifactOperation::from_event(event) else {
+            continue;
+        };
+        let Some(path) = event.event_data.get("TargetFilename") else {
+            continue;
+        };
+        provenance
+            .entry(normalize(path))
+            .or_default()
+            .push(Provenance {
+                operation,
+                time: event.time_created,
+                process_guid: event.event_data.get("ProcessGuid").cloned(),
+                image: event.event_data.get("Image").cloned(),
+            });
+    }
+    provenance
+}
+
+pub fn collect<'a, I>(events: &[SysmonEvent], files: I) -> Vec<Artifact>
+where
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+    let mut provenance = provenance(events);
+    files
+        .into_iter()
+        .map(|(path, content)| {
+            let mut history = provenance.remove(&normalize(path)).unwrap_or_default();
+            history.sort_by_key(|p| p.time);
+            Artifact {
+                path: path.to_string(),
+                size: content.len() as u64,
+                hashes: FileHashes::compute(content),
+                provenance: history,
+                entropy: Some(entropy(content)),
+            }
+        })
+        .collect()
+}
+
+pub fn dumped(path: &str, content: &[u8], time: DateTime<FixedOffset>) -> Artifact {
+    Artifact {
+        path: path.to_string(),
+        size: content.len() as u64,
+        hashes: FileHashes::compute(content),
+        provenance: vec![Provenance {
+            operation: ArtifactOperation::Dumped,
+            time,
+            process_guid: None,
+            image: None,
+        }],
+        entropy: Some(entropy(content)),
+    }
+}
+
+pub fn by_sha256<'a>(artifacts: &'a [Artifact], sha256: &str) -> Option<&'a Artifact> {
+    let sha256 = sha256.to_lowercase();
+    artifacts.iter().find(|a| a.hashes.sha256 == sha256)
+}
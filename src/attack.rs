@@ -0,0 +1,427 @@
+mod techniques;
+
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+use crate::analyzer::dns_anomaly::DnsHeuristic;
+use crate::analyzer::initial_access::{infer_initial_access, InitialAccessKind};
+use crate::analyzer::{
+    behavior_detection, clipboard, credential_access, dns_anomaly, persistence, privilege,
+};
+use crate::process_tree::ProcessTree;
+use crate::report::SandboxReport;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+pub use techniques::{find_technique, Technique, TECHNIQUES};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Tactic {
+    InitialAccess,
+    Execution,
+    Persistence,
+    PrivilegeEscalation,
+    DefenseEvasion,
+    CredentialAccess,
+    Discovery,
+    LateralMovement,
+    Collection,
+    CommandAndControl,
+    Exfiltration,
+    Impact,
+}
+
+impl Tactic {
+    pub const ALL: &'static [Self] = &[
+        Self::InitialAccess,
+        Self::Execution,
+        Self::Persistence,
+        Self::PrivilegeEscalation,
+        Self::DefenseEvasion,
+        Self::CredentialAccess,
+        Self::Discovery,
+        Self::LateralMovement,
+        Self::Collection,
+        Self::CommandAndControl,
+        Self::Exfiltration,
+        Self::Impact,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InitialAccess => "Initial Access",
+            Self::Execution => "Execution",
+            Self::Persistence => "Persistence",
+            Self::PrivilegeEscalation => "Privilege Escalation",
+            Self::DefenseEvasion => "Defense Evasion",
+            Self::CredentialAccess => "Credential Access",
+            Self::Discovery => "Discovery",
+            Self::LateralMovement => "Lateral Movement",
+            Self::Collection => "Collection",
+            Self::CommandAndControl => "Command and Control",
+            Self::Exfiltration => "Exfiltration",
+            Self::Impact => "Impact",
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::InitialAccess => "TA0001",
+            Self::Execution => "TA0002",
+            Self::Persistence => "TA0003",
+            Self::PrivilegeEscalation => "TA0004",
+            Self::DefenseEvasion => "TA0005",
+            Self::CredentialAccess => "TA0006",
+            Self::Discovery => "TA0007",
+            Self::LateralMovement => "TA0008",
+            Self::Collection => "TA0009",
+            Self::CommandAndControl => "TA0011",
+            Self::Exfiltration => "TA0010",
+            Self::Impact => "TA0040",
+        }
+    }
+
+    pub fn tag(&self) -> String {
+        format!("attack.{}", self.name().to_lowercase().replace(' ', "_"))
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|t| t.tag().eq_ignore_ascii_case(tag))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Phase {
+    pub tactic: Tactic,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub events: Vec<SysmonEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TechniqueHit {
+    pub tactic: Tactic,
+    pub technique: &'static Technique,
+    pub event: SysmonEvent,
+}
+
+pub fn technique_id_from_tag(tag: &str) -> Option<String> {
+    let id = tag.strip_prefix("attack.")?;
+    let digits = id.strip_prefix('t').or_else(|| id.strip_prefix('T'))?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    Some(format!("T{}", digits))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TechniqueSummary {
+    pub id: String,
+    pub name: Option<String>,
+    pub tactics: Vec<Tactic>,
+    pub sources: Vec<String>,
+    pub events: usize,
+}
+
+fn privilege_technique(signature: &str) -> &'static str {
+    match signature {
+        "Token privilege enumeration" => "T1033",
+        "Run as another user" => "T1134.002",
+        "PsExec as SYSTEM" => "T1569.002",
+        "Service security descriptor change" => "T1543.003",
+        "SeDebugPrivilege request" => "T1134",
+        "Token impersonation" => "T1134.001",
+        "Sudo privilege enumeration" => "T1548.003",
+        "Setuid bit set" => "T1548.001",
+        _ => "T1548",
+    }
+}
+
+fn dns_technique(heuristic: DnsHeuristic) -> &'static str {
+    match heuristic {
+        DnsHeuristic::ManyFailedQueries => "T1568.002",
+        _ => "T1071.004",
+    }
+}
+
+pub fn technique_hits(events: &[SysmonEvent]) -> Result<Vec<TechniqueHit>> {
+    let mut hits = Vec::new();
+    let mut add = |tactic, id: &str, event: SysmonEvent| {
+        if let Some(technique) = find_technique(id) {
+            hits.push(TechniqueHit {
+                tactic,
+                technique,
+                event,
+            });
+        }
+    };
+
+    if let Some(access) = infer_initial_access(events) {
+        let technique = match access.kind {
+            InitialAccessKind::PhishingMacro => Some("T1566.001"),
+            InitialAccessKind::DriveByDownload => Some("T1189"),
+            InitialAccessKind::ManualExecution => Some("T1204.002"),
+            InitialAccessKind::Unknown => None,
+        };
+        if let Some(technique) = technique {
+            add(Tactic::InitialAccess, technique, access.event);
+        }
+    }
+
+    for event in behavior_detection::detect(events.to_vec())? {
+        if event.event_id == SysmonEventId::FILE_DELETE {
+            add(Tactic::DefenseEvasion, "T1070.004", event);
+        } else {
+            add(Tactic::Execution, "T1204.002", event);
+        }
+    }
+
+    for alert in persistence::detect_service_install(events) {
+        for event in alert.process_event.into_iter().chain(alert.registry_event) {
+            add(Tactic::Persistence, "T1543.003", event);
+        }
+    }
+
+    for alert in persistence::detect_scheduled_tasks(events) {
+        for event in alert.process_event.into_iter().chain(alert.file_event) {
+            add(Tactic::Persistence, "T1053.005", event);
+        }
+    }
+
+    for abuse in privilege::detect_privilege_abuse(events) {
+        add(
+            Tactic::PrivilegeEscalation,
+            privilege_technique(&abuse.signature),
+            abuse.event,
+        );
+    }
+
+    let tree = ProcessTree::from_events(events);
+    for warning in tree.parent_spoof_warnings() {
+        let spoofed = events.iter().find(|e| {
+            e.event_id == SysmonEventId::PROCESS_CREATE
+                && e.event_data.get("ProcessGuid") == Some(&warning.child_guid)
+        });
+        if let Some(event) = spoofed {
+            add(Tactic::DefenseEvasion, "T1134.004", event.clone());
+        }
+    }
+
+    for access in credential_access::detect_browser_cred_access(events) {
+        add(Tactic::CredentialAccess, "T1555.003", access.event);
+    }
+
+    for alert in clipboard::detect_clipboard_abuse(events) {
+        for event in alert.events {
+            add(Tactic::Collection, "T1115", event);
+        }
+    }
+
+    for anomaly in dns_anomaly::detect_dns_anomalies(events) {
+        add(
+            Tactic::CommandAndControl,
+            dns_technique(anomaly.heuristic),
+            anomaly.event,
+        );
+    }
+
+    Ok(hits)
+}
+
+pub fn summarize_techniques<'a, I>(tagged: I) -> Vec<TechniqueSummary>
+where
+    I: IntoIterator<Item = (&'a str, &'a [String], usize)>,
+{
+    let mut summaries: BTreeMap<String, TechniqueSummary> = BTreeMap::new();
+    for (source, tags, events) in tagged {
+        let tactics: Vec<Tactic> = tags.iter().filter_map(|t| Tactic::from_tag(t)).collect();
+        for id in tags.iter().filter_map(|t| technique_id_from_tag(t)) {
+            let technique = find_technique(&id);
+            let summary = summaries
+                .entry(id.clone())
+                .or_insert_with(|| TechniqueSummary {
+                    id,
+                    name: technique.map(|t| t.name.to_string()),
+                    tactics: Vec::new(),
+                    sources: Vec::new(),
+                    events: 0,
+                });
+            let known = technique.map(|t| t.tactics).unwrap_or_default();
+            let observed = if tactics.is_empty() { known } else { &tactics };
+            for tactic in observed {
+                if !summary.tactics.contains(tactic) {
+                    summary.tactics.push(*tactic);
+                }
+            }
+            if !summary.sources.iter().any(|s| s == source) {
+                summary.sources.push(source.to_string());
+            }
+            summary.events += events;
+        }
+    }
+
+    let mut result: Vec<TechniqueSummary> = summaries.into_values().collect();
+    for summary in &mut result {
+        summary.tactics.sort();
+    }
+    result
+}
+
+fn phases(tactics: BTreeMap<Tactic, Vec<SysmonEvent>>) -> Vec<Phase> {
+    let mut phases = Vec::new();
+    for (tactic, events) in tactics {
+        let mut keyed: Vec<(String, SysmonEvent)> =
+            events.into_iter().map(|e| (e.dedup_key(), e)).collect();
+        keyed.sort_by(|(a_key, a), (b_key, b)| {
+            (a.time_created, a_key).cmp(&(b.time_created, b_key))
+        });
+        let mut seen = HashSet::new();
+        let events: Vec<SysmonEvent> = keyed
+            .into_iter()
+            .filter(|(key, _)| seen.insert(key.clone()))
+            .map(|(_, e)| e)
+            .collect();
+        let (Some(first), Some(last)) = (events.first(), events.last()) else {
+            continue;
+        };
+        phases.push(Phase {
+            tactic,
+            start: first.time_created,
+            end: last.time_created,
+            events,
+        });
+    }
+    phases
+}
+
+pub fn phases_from_events(events: &[SysmonEvent]) -> Result<Vec<Phase>> {
+    let mut tactics: BTreeMap<Tactic, Vec<SysmonEvent>> = BTreeMap::new();
+    for hit in technique_hits(events)? {
+        tactics.entry(hit.tactic).or_default().push(hit.event);
+    }
+    Ok(phases(tactics))
+}
+
+pub fn attack_phases(report: &SandboxReport) -> Vec<Phase> {
+    let mut tactics: BTreeMap<Tactic, Vec<SysmonEvent>> = BTreeMap::new();
+    for detection in report.detections.iter().filter(|d| !d.events.is_empty()) {
+        let mut detected: Vec<Tactic> = detection
+            .tags
+            .iter()
+            .filter_map(|t| Tactic::from_tag(t))
+            .collect();
+        if detected.is_empty() {
+            detected = detection
+                .tags
+                .iter()
+                .filter_map(|t| find_technique(&technique_id_from_tag(t)?))
+                .flat_map(|t| t.tactics.iter().copied())
+                .collect();
+        }
+        detected.sort();
+        detected.dedup();
+        for tactic in detected {
+            tactics
+                .entry(tactic)
+                .or_default()
+                .extend(detection.events.iter().cloned());
+        }
+    }
+    phases(tactics)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::report::Detection;
+
+    fn event(record_id: u64, time: &str) -> SysmonEvent {
+        SysmonEvent {
+            event_id: SysmonEventId::PROCESS_CREATE,
+            time_created: DateTime::parse_from_rfc3339(time).unwrap(),
+            computer: Some("WIN10".to_string()),
+            record_id: Some(record_id),
+            channel: None,
+            event_data: HashMap::new(),
+        }
+    }
+
+    fn detection(tags: &[&str], events: Vec<SysmonEvent>) -> Detection {
+        Detection {
+            source: "test".to_string(),
+            name: tags.join(","),
+            level: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            events,
+        }
+    }
+
+    fn record_ids(phase: &Phase) -> Vec<u64> {
+        phase.events.iter().filter_map(|e| e.record_id).collect()
+    }
+
+    #[test]
+    fn phases_drop_non_adjacent_duplicates() {
+        let time = "2024-01-01T00:00:00Z";
+        let tactics = BTreeMap::from([(
+            Tactic::Execution,
+            vec![
+                event(2, time),
+                event(1, time),
+                event(2, time),
+                event(1, time),
+            ],
+        )]);
+        let phases = phases(tactics);
+        assert_eq!(phases.len(), 1);
+        assert_eq!(record_ids(&phases[0]), [1, 2]);
+    }
+
+    #[test]
+    fn attack_phases_follow_report_detections() {
+        let mut report = SandboxReport::from_events("hash", Vec::new()).unwrap();
+        report.detections = vec![
+            detection(
+                &["attack.t1547.001"],
+                vec![
+                    event(3, "2024-01-01T00:00:30Z"),
+                    event(4, "2024-01-01T00:01:00Z"),
+                ],
+            ),
+            detection(
+                &["attack.execution", "attack.t1059.001"],
+                vec![event(1, "2024-01-01T00:00:10Z")],
+            ),
+            detection(
+                &["attack.execution"],
+                vec![
+                    event(2, "2024-01-01T00:00:20Z"),
+                    event(1, "2024-01-01T00:00:10Z"),
+                ],
+            ),
+            detection(&["attack.impact"], Vec::new()),
+        ];
+
+        let phases = attack_phases(&report);
+        let tactics: Vec<Tactic> = phases.iter().map(|p| p.tactic).collect();
+        assert_eq!(
+            tactics,
+            [
+                Tactic::Execution,
+                Tactic::Persistence,
+                Tactic::PrivilegeEscalation
+            ]
+        );
+        assert_eq!(record_ids(&phases[0]), [1, 2]);
+        assert_eq!(phases[0].start.to_rfc3339(), "2024-01-01T00:00:10+00:00");
+        assert_eq!(phases[0].end.to_rfc3339(), "2024-01-01T00:00:20+00:00");
+        assert_eq!(record_ids(&phases[1]), [3, 4]);
+        assert_eq!(phases[1].end.to_rfc3339(), "2024-01-01T00:01:00+00:00");
+    }
+}
@@ -0,0 +1,177 @@
+use super::Tactic;
+use super::Tactic::*;
+
+#[derive(Debug)]
+pub struct Technique {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub tactics: &'static [Tactic],
+}
+
+const fn technique(id: &'static str, name: &'static str, tactics: &'static [Tactic]) -> Technique {
+    Technique { id, name, tactics }
+}
+
+pub const TECHNIQUES: &[Technique] = &[
+    technique("T1003", "OS Credential Dumping", &[CredentialAccess]),
+    technique("T1003.001", "LSASS Memory", &[CredentialAccess]),
+    technique("T1003.002", "Security Account Manager", &[CredentialAccess]),
+    technique("T1003.003", "NTDS", &[CredentialAccess]),
+    technique("T1003.004", "LSA Secrets", &[CredentialAccess]),
+    technique(
+        "T1003.005",
+        "Cached Domain Credentials",
+        &[CredentialAccess],
+    ),
+    technique("T1003.006", "DCSync", &[CredentialAccess]),
+    technique("T1014", "Rootkit", &[DefenseEvasion]),
+    technique(
+        "T1016",
+        "System Network Configuration Discovery",
+        &[Discovery],
+    ),
+    technique("T1021.002", "SMB/Windows Admin Shares", &[LateralMovement]),
+    technique(
+        "T1027",
+        "Obfuscated Files or Information",
+        &[DefenseEvasion],
+    ),
+    technique("T1033", "System Owner/User Discovery", &[Discovery]),
+    technique("T1036", "Masquerading", &[DefenseEvasion]),
+    technique("T1041", "Exfiltration Over C2 Channel", &[Exfiltration]),
+    technique("T1047", "Windows Management Instrumentation", &[Execution]),
+    technique(
+        "T1053.003",
+        "Cron",
+        &[Execution, Persistence, PrivilegeEscalation],
+    ),
+    technique(
+        "T1053.005",
+        "Scheduled Task",
+        &[Execution, Persistence, PrivilegeEscalation],
+    ),
+    technique(
+        "T1055",
+        "Process Injection",
+        &[DefenseEvasion, PrivilegeEscalation],
+    ),
+    technique("T1057", "Process Discovery", &[Discovery]),
+    technique("T1059", "Command and Scripting Interpreter", &[Execution]),
+    technique("T1059.001", "PowerShell", &[Execution]),
+    technique("T1059.003", "Windows Command Shell", &[Execution]),
+    technique("T1059.004", "Unix Shell", &[Execution]),
+    technique("T1059.005", "Visual Basic", &[Execution]),
+    technique("T1059.007", "JavaScript", &[Execution]),
+    technique(
+        "T1068",
+        "Exploitation for Privilege Escalation",
+        &[PrivilegeEscalation],
+    ),
+    technique("T1070", "Indicator Removal", &[DefenseEvasion]),
+    technique("T1070.001", "Clear Windows Event Logs", &[DefenseEvasion]),
+    technique(
+        "T1070.002",
+        "Clear Linux or Mac System Logs",
+        &[DefenseEvasion],
+    ),
+    technique("T1070.004", "File Deletion", &[DefenseEvasion]),
+    technique("T1071", "Application Layer Protocol", &[CommandAndControl]),
+    technique("T1071.001", "Web Protocols", &[CommandAndControl]),
+    technique("T1071.004", "DNS", &[CommandAndControl]),
+    technique("T1082", "System Information Discovery", &[Discovery]),
+    technique("T1083", "File and Directory Discovery", &[Discovery]),
+    technique("T1105", "Ingress Tool Transfer", &[CommandAndControl]),
+    technique("T1112", "Modify Registry", &[DefenseEvasion]),
+    technique("T1115", "Clipboard Data", &[Collection]),
+    technique(
+        "T1134",
+        "Access Token Manipulation",
+        &[DefenseEvasion, PrivilegeEscalation],
+    ),
+    technique(
+        "T1134.001",
+        "Token Impersonation/Theft",
+        &[DefenseEvasion, PrivilegeEscalation],
+    ),
+    technique(
+        "T1134.002",
+        "Create Process with Token",
+        &[DefenseEvasion, PrivilegeEscalation],
+    ),
+    technique(
+        "T1134.004",
+        "Parent PID Spoofing",
+        &[DefenseEvasion, PrivilegeEscalation],
+    ),
+    technique("T1189", "Drive-by Compromise", &[InitialAccess]),
+    technique("T1204", "User Execution", &[Execution]),
+    technique("T1204.002", "Malicious File", &[Execution]),
+    technique("T1218", "System Binary Proxy Execution", &[DefenseEvasion]),
+    technique(
+        "T1222",
+        "File and Directory Permissions Modification",
+        &[DefenseEvasion],
+    ),
+    technique("T1486", "Data Encrypted for Impact", &[Impact]),
+    technique("T1490", "Inhibit System Recovery", &[Impact]),
+    technique(
+        "T1543",
+        "Create or Modify System Process",
+        &[Persistence, PrivilegeEscalation],
+    ),
+    technique(
+        "T1543.002",
+        "Systemd Service",
+        &[Persistence, PrivilegeEscalation],
+    ),
+    technique(
+        "T1543.003",
+        "Windows Service",
+        &[Persistence, PrivilegeEscalation],
+    ),
+    technique(
+        "T1546.003",
+        "Windows Management Instrumentation Event Subscription",
+        &[Persistence, PrivilegeEscalation],
+    ),
+    technique(
+        "T1547.001",
+        "Registry Run Keys / Startup Folder",
+        &[Persistence, PrivilegeEscalation],
+    ),
+    technique(
+        "T1548",
+        "Abuse Elevation Control Mechanism",
+        &[DefenseEvasion, PrivilegeEscalation],
+    ),
+    technique(
+        "T1548.001",
+        "Setuid and Setgid",
+        &[DefenseEvasion, PrivilegeEscalation],
+    ),
+    technique(
+        "T1548.003",
+        "Sudo and Sudo Caching",
+        &[DefenseEvasion, PrivilegeEscalation],
+    ),
+    technique(
+        "T1555.003",
+        "Credentials from Web Browsers",
+        &[CredentialAccess],
+    ),
+    technique("T1560", "Archive Collected Data", &[Collection]),
+    technique("T1562.001", "Disable or Modify Tools", &[DefenseEvasion]),
+    technique("T1565.002", "Transmitted Data Manipulation", &[Impact]),
+    technique("T1566.001", "Spearphishing Attachment", &[InitialAccess]),
+    technique(
+        "T1568.002",
+        "Domain Generation Algorithms",
+        &[CommandAndControl],
+    ),
+    technique("T1569.002", "Service Execution", &[Execution]),
+];
+
+pub fn find_technique(id: &str) -> Option<&'static Technique> {
+    let find = |id: &str| TECHNIQUES.iter().find(|t| t.id.eq_ignore_ascii_case(id));
+    find(id).or_else(|| find(id.split('.').next()?))
+}
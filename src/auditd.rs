@@ -0,0 +1,446 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use uuid::Uuid;
+
+use crate::guid::Guid;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId, UTC_TIME_FORMAT};
+
+pub const AUDITD_CHANNEL: &str = "auditd";
+
+const ENRICHMENT_SEPARATOR: char = '\x1d';
+const UNSET_AUID: &str = "4294967295";
+const EINPROGRESS: &str = "-115";
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syscall {
+    Execve,
+    Connect,
+    ExitGroup,
+    Other,
+}
+
+fn syscall(arch: &str, number: &str, name: Option<&str>) -> Syscall {
+    let name = match name {
+        Some(name) => name,
+        None => match (arch, number) {
+            ("c000003e", "59" | "322") | ("c00000b7", "221" | "281") => "execve",
+            ("c000003e", "42") | ("c00000b7", "203") => "connect",
+            ("c000003e", "231" | "60") | ("c00000b7", "94" | "93") => "exit_group",
+            _ => "",
+        },
+    };
+    match name {
+        "execve" | "execveat" => Syscall::Execve,
+        "connect" => Syscall::Connect,
+        "exit_group" | "exit" => Syscall::ExitGroup,
+        _ => Syscall::Other,
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+}
+
+fn decode_value(value: &str) -> String {
+    if let Some(quoted) = unquote(value) {
+        return quoted.to_string();
+    }
+    match decode_hex(value) {
+        Some(bytes) => String::from_utf8_lossy(&bytes).replace('\0', " "),
+        None => value.to_string(),
+    }
+}
+
+fn fields(text: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let Some((key, tail)) = rest.split_once('=') else {
+            break;
+        };
+        let (value, tail) = match tail.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&tail[..end + 2], &quoted[end + 1..]),
+                None => (tail, ""),
+            },
+            None => tail.split_once(' ').unwrap_or((tail, "")),
+        };
+        fields.insert(key.trim().to_string(), value.to_string());
+        rest = tail.trim_start();
+    }
+    fields
+}
+
+fn socket_address(saddr: &str) -> Option<(IpAddr, u16)> {
+    let bytes = decode_hex(saddr)?;
+    let family = u16::from_le_bytes([*bytes.first()?, *bytes.get(1)?]);
+    let port = u16::from_be_bytes([*bytes.get(2)?, *bytes.get(3)?]);
+    let ip = match family {
+        AF_INET => IpAddr::V4(Ipv4Addr::new(
+            *bytes.get(4)?,
+            *bytes.get(5)?,
+            *bytes.get(6)?,
+            *bytes.get(7)?,
+        )),
+        AF_INET6 => {
+            let octets: [u8; 16] = bytes.get(8..24)?.try_into().ok()?;
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some((ip, port))
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    kind: String,
+    fields: HashMap<String, String>,
+    enriched: HashMap<String, String>,
+}
+
+impl Record {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    fn text(&self, key: &str) -> Option<String> {
+        self.get(key)
+            .map(|v| unquote(v).unwrap_or(v).to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    fn string(&self, key: &str) -> Option<String> {
+        self.get(key).filter(|v| *v != "(null)").map(decode_value)
+    }
+}
+
+#[derive(Debug)]
+struct AuditEvent {
+    serial: String,
+    time: DateTime<FixedOffset>,
+    node: Option<String>,
+    records: Vec<Record>,
+}
+
+impl AuditEvent {
+    fn record(&self, kind: &str) -> Option<&Record> {
+        self.records.iter().find(|r| r.kind == kind)
+    }
+
+    fn records<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a Record> + 'a {
+        self.records.iter().filter(move |r| r.kind == kind)
+    }
+}
+
+fn sysmon_event(
+    event_id: SysmonEventId,
+    audit: &AuditEvent,
+    fields: Vec<(&str, Option<String>)>,
+) -> SysmonEvent {
+    let mut event = SysmonEvent {
+        event_id,
+        time_created: audit.time,
+        computer: audit.node.clone(),
+        record_id: audit.serial.parse().ok(),
+        channel: Some(AUDITD_CHANNEL.to_string()),
+        event_data: HashMap::new(),
+    };
+    event.set_field(
+        "UtcTime",
+        audit.time.naive_utc().format(UTC_TIME_FORMAT).to_string(),
+    );
+    for (key, value) in fields {
+        if let Some(value) = value {
+            event.set_field(key, value);
+        }
+    }
+    event
+}
+
+type Line = (String, DateTime<FixedOffset>, Option<String>, Record);
+
+fn parse_line(line: &str) -> Option<Line> {
+    let (raw, enrichment) = line.split_once(ENRICHMENT_SEPARATOR).unwrap_or((line, ""));
+    let (node, raw) = match raw.strip_prefix("node=") {
+        Some(rest) => {
+            let (node, rest) = rest.split_once(' ')?;
+            (Some(node.to_string()), rest)
+        }
+        None => (None, raw),
+    };
+    let (kind, rest) = raw.strip_prefix("type=")?.split_once(' ')?;
+    let rest = rest.strip_prefix("msg=audit(")?;
+    let (stamp, body) = rest.split_once("):")?;
+    let (time, serial) = stamp.split_once(':')?;
+    let (secs, millis) = time.split_once('.').unwrap_or((time, "0"));
+    let time = Utc
+        .timestamp_opt(secs.parse().ok()?, millis.parse::<u32>().ok()? * 1_000_000)
+        .single()?
+        .into();
+
+    let mut body = body.trim();
+    if let Some(inner) = body
+        .strip_prefix("msg='")
+        .and_then(|b| b.strip_suffix('\''))
+    {
+        body = inner;
+    }
+    Some((
+        serial.to_string(),
+        time,
+        node,
+        Record {
+            kind: kind.to_string(),
+            fields: fields(body),
+            enriched: fields(enrichment),
+        },
+    ))
+}
+
+pub struct AuditdReader<R> {
+    reader: R,
+    line: String,
+    current: Option<AuditEvent>,
+    pending: VecDeque<SysmonEvent>,
+    guids: HashMap<String, String>,
+    images: HashMap<String, (String, String)>,
+    eof: bool,
+}
+
+impl<R: BufRead> AuditdReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+            current: None,
+            pending: VecDeque::new(),
+            guids: HashMap::new(),
+            images: HashMap::new(),
+            eof: false,
+        }
+    }
+
+    fn process_guid(&mut self, pid: &str) -> String {
+        self.guids
+            .entry(pid.to_string())
+            .or_insert_with(|| {
+                Guid::from(Uuid::new_v5(&Uuid::NAMESPACE_OID, pid.as_bytes())).to_string()
+            })
+            .clone()
+    }
+
+    fn convert(&mut self, audit: AuditEvent) {
+        let Some(call) = audit.record("SYSCALL") else {
+            return;
+        };
+        let Some(pid) = call.text("pid") else {
+            return;
+        };
+        let success = call.get("success") != Some("no");
+        let syscall = syscall(
+            call.get("arch").unwrap_or_default(),
+            call.get("syscall").unwrap_or_default(),
+            call.enriched.get("SYSCALL").map(String::as_str),
+        );
+        let user = call
+            .enriched
+            .get("UID")
+            .map(String::as_str)
+            .map(decode_value)
+            .or_else(|| call.text("uid"));
+        let logon_id = call.text("auid").filter(|a| a != UNSET_AUID);
+        let cwd = audit.record("CWD").and_then(|r| r.string("cwd"));
+
+        if syscall == Syscall::Execve && success {
+            let command_line = audit.record("EXECVE").map(|r| {
+                let argc: usize = r.get("argc").and_then(|a| a.parse().ok()).unwrap_or(0);
+                (0..argc)
+                    .filter_map(|i| r.string(&format!("a{}", i)))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            });
+            let image = call.string("exe").unwrap_or_default();
+            let ppid = call.text("ppid").unwrap_or_default();
+            let guid = Guid::from(Uuid::new_v5(
+                &Uuid::NAMESPACE_OID,
+                format!("{}:{}", pid, audit.serial).as_bytes(),
+            ))
+            .to_string();
+            self.guids.insert(pid.clone(), guid.clone());
+            let parent_guid = self.process_guid(&ppid);
+            let parent = self.images.get(&ppid).cloned();
+            let command_line = command_line.unwrap_or_else(|| image.clone());
+            self.images
+                .insert(pid.clone(), (image.clone(), command_line.clone()));
+
+            let event = sysmon_event(
+                SysmonEventId::PROCESS_CREATE,
+                &audit,
+                vec![
+                    ("ProcessGuid", Some(guid)),
+                    ("ProcessId", Some(pid.clone())),
+                    ("Image", Some(image)),
+                    ("CommandLine", Some(command_line)),
+                    ("CurrentDirectory", cwd.clone()),
+                    ("User", user.clone()),
+                    ("LogonId", logon_id),
+                    ("ParentProcessGuid", Some(parent_guid)),
+                    ("ParentProcessId", Some(ppid)),
+                    ("ParentImage", parent.as_ref().map(|p| p.0.clone())),
+                    ("ParentCommandLine", parent.map(|p| p.1)),
+                ],
+            );
+            self.pending.push_back(event);
+        }
+
+        let guid = self.process_guid(&pid);
+        let image = self
+            .images
+            .get(&pid)
+            .map(|p| p.0.clone())
+            .or_else(|| call.string("exe"));
+
+        if syscall == Syscall::Connect && (success || call.get("exit") == Some(EINPROGRESS)) {
+            let address = audit
+                .record("SOCKADDR")
+                .and_then(|r| r.get("saddr"))
+                .and_then(socket_address);
+            if let Some((ip, port)) = address {
+                let event = sysmon_event(
+                    SysmonEventId::NETWORK_CONNECT,
+                    &audit,
+                    vec![
+                        ("ProcessGuid", Some(guid.clone())),
+                        ("ProcessId", Some(pid.clone())),
+                        ("Image", image.clone()),
+                        ("User", user.clone()),
+                        ("Initiated", Some("true".to_string())),
+                        ("DestinationIsIpv6", Some(ip.is_ipv6().to_string())),
+                        ("DestinationIp", Some(ip.to_string())),
+                        ("DestinationPort", Some(port.to_string())),
+                    ],
+                );
+                self.pending.push_back(event);
+            }
+        }
+
+        if syscall == Syscall::ExitGroup {
+            let event = sysmon_event(
+                SysmonEventId::PROCESS_TERMINATE,
+                &audit,
+                vec![
+                    ("ProcessGuid", Some(guid.clone())),
+                    ("ProcessId", Some(pid.clone())),
+                    ("Image", image.clone()),
+                    ("User", user.clone()),
+                ],
+            );
+            self.pending.push_back(event);
+        }
+
+        if !success {
+            return;
+        }
+        for path in audit.records("PATH") {
+            let event_id = match path.get("nametype") {
+                Some("CREATE") => SysmonEventId::FILE_CREATE,
+                Some("DELETE") => SysmonEventId::FILE_DELETE_DETECTED,
+                _ => continue,
+            };
+            let Some(name) = path.string("name") else {
+                continue;
+            };
+            let target = match &cwd {
+                Some(cwd) if !name.starts_with('/') => {
+                    format!("{}/{}", cwd.trim_end_matches('/'), name)
+                }
+                _ => name,
+            };
+            let event = sysmon_event(
+                event_id,
+                &audit,
+                vec![
+                    ("ProcessGuid", Some(guid.clone())),
+                    ("ProcessId", Some(pid.clone())),
+                    ("Image", image.clone()),
+                    ("User", user.clone()),
+                    ("TargetFilename", Some(target)),
+                ],
+            );
+            self.pending.push_back(event);
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<Line>> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            if let Some(parsed) = parse_line(self.line.trim_end()) {
+                return Ok(Some(parsed));
+            }
+        }
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        while self.pending.is_empty() && !self.eof {
+            let Some((serial, time, node, record)) = self.read_line()? else {
+                self.eof = true;
+                if let Some(audit) = self.current.take() {
+                    self.convert(audit);
+                }
+                break;
+            };
+            if record.kind == "EOE" {
+                if let Some(audit) = self.current.take() {
+                    self.convert(audit);
+                }
+                continue;
+            }
+            match &mut self.current {
+                Some(audit) if audit.serial == serial => audit.records.push(record),
+                _ => {
+                    let next = AuditEvent {
+                        serial,
+                        time,
+                        node,
+                        records: vec![record],
+                    };
+                    if let Some(audit) = self.current.replace(next) {
+                        self.convert(audit);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for AuditdReader<R> {
+    type Item = Result<SysmonEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.fill().context("Failed to read audit log") {
+            self.eof = true;
+            return Some(Err(e));
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
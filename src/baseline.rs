@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::protocol::{DumpScope, ExecutionRequest};
+use crate::analysis_result::{AnalysisResult, ExecutionLog};
+use crate::path::normalize;
+use crate::sysmon_event::SysmonEvent;
+
+const FINGERPRINT_FIELDS: &[&str] = &[
+    "Image",
+    "ParentImage",
+    "CommandLine",
+    "SourceImage",
+    "TargetImage",
+    "ImageLoaded",
+    "TargetFilename",
+    "TargetObject",
+    "Details",
+    "PipeName",
+    "QueryName",
+    "DestinationHostname",
+    "DestinationPort",
+];
+
+const PATH_FIELDS: &[&str] = &[
+    "Image",
+    "ParentImage",
+    "SourceImage",
+    "TargetImage",
+    "ImageLoaded",
+    "TargetFilename",
+];
+
+fn mask(value: &str) -> String {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN
+        .get_or_init(|| {
+            Regex::new(
+                r"\{?[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\}?|[0-9a-fA-F]{16,}|\d+",
+            )
+            .expect("Invalid baseline mask pattern")
+        })
+        .replace_all(value, "*")
+        .into_owned()
+}
+
+pub fn fingerprint(event: &SysmonEvent) -> String {
+    let mut fingerprint = event.event_id.value().to_string();
+    for field in FINGERPRINT_FIELDS {
+        let Some(value) = event.event_data.get(*field).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let value = match PATH_FIELDS.contains(field) {
+            true => normalize(value),
+            false => value.to_lowercase(),
+        };
+        fingerprint.push('|');
+        fingerprint.push_str(field);
+        fingerprint.push('=');
+        fingerprint.push_str(&mask(&value));
+    }
+    fingerprint
+}
+
+pub fn clean_request(timeout_secs: u64) -> ExecutionRequest {
+    ExecutionRequest {
+        file_name: String::new(),
+        arguments: Vec::new(),
+        timeout_secs,
+        user: None,
+        screenshot: false,
+        screenshot_interval_secs: None,
+        record_video: false,
+        simulate_user: false,
+        user_sim_script: None,
+        hardening: None,
+        time_warp: None,
+        dump_triggers: Vec::new(),
+        dump_scope: DumpScope::default(),
+        reboot: false,
+        collect_only: true,
+        resubmit_dropped: false,
+        url: None,
+        golden_files: Vec::new(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Baseline {
+    pub machine: String,
+    pub snapshot: String,
+    pub version: u32,
+    pub created: DateTime<Utc>,
+    pub runs: usize,
+    pub fingerprints: BTreeMap<String, usize>,
+}
+
+impl Baseline {
+    pub fn new(machine: &str, snapshot: &str) -> Self {
+        Self {
+            machine: machine.to_string(),
+            snapshot: snapshot.to_string(),
+            version: 0,
+            created: Utc::now(),
+            runs: 0,
+            fingerprints: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_run(&mut self, events: &[SysmonEvent]) {
+        let seen: BTreeSet<String> = events.iter().map(fingerprint).collect();
+        for fingerprint in seen {
+            *self.fingerprints.entry(fingerprint).or_default() += 1;
+        }
+        self.runs += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.fingerprints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fingerprints.is_empty()
+    }
+
+    pub fn contains(&self, event: &SysmonEvent) -> bool {
+        self.fingerprints.contains_key(&fingerprint(event))
+    }
+
+    pub fn subtract(&self, events: &[SysmonEvent]) -> Vec<SysmonEvent> {
+        events
+            .iter()
+            .filter(|e| !self.contains(e))
+            .cloned()
+            .collect()
+    }
+
+    pub fn subtract_log(&self, log: &ExecutionLog) -> ExecutionLog {
+        ExecutionLog {
+            sysmon_events: self.subtract(&log.sysmon_events),
+            ..log.clone()
+        }
+    }
+
+    pub fn subtract_result(&self, result: &AnalysisResult) -> AnalysisResult {
+        AnalysisResult {
+            id: result.id.clone(),
+            hash: result.hash.clone(),
+            execution_logs: result
+                .execution_logs
+                .iter()
+                .map(|log| self.subtract_log(log))
+                .collect(),
+            tenant: result.tenant.clone(),
+            metadata: result.metadata.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BaselineStore {
+    dir: PathBuf,
+}
+
+pub(crate) fn component(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+pub(crate) fn versions(dir: &Path) -> Result<Vec<u32>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut versions = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let version = name
+            .to_str()
+            .and_then(|n| n.strip_prefix('v'))
+            .and_then(|n| n.strip_suffix(".json"))
+            .and_then(|n| n.parse::<u32>().ok());
+        versions.extend(version);
+    }
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+impl BaselineStore {
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn snapshot_dir(&self, machine: &str, snapshot: &str) -> PathBuf {
+        self.dir.join(component(machine)).join(component(snapshot))
+    }
+
+    pub fn versions(&self, machine: &str, snapshot: &str) -> Result<Vec<u32>> {
+        versions(&self.snapshot_dir(machine, snapshot))
+    }
+
+    pub fn save(&self, baseline: &mut Baseline) -> Result<u32> {
+        let dir = self.snapshot_dir(&baseline.machine, &baseline.snapshot);
+        fs::create_dir_all(&dir)?;
+        let version = self
+            .versions(&baseline.machine, &baseline.snapshot)?
+            .last()
+            .map_or(1, |v| v + 1);
+        baseline.version = version;
+        fs::write(
+            dir.join(format!("v{}.json", version)),
+            serde_json::to_vec_pretty(baseline)?,
+        )?;
+        Ok(version)
+    }
+
+    pub fn load(&self, machine: &str, snapshot: &str, version: u32) -> Result<Baseline> {
+        let path = self
+            .snapshot_dir(machine, snapshot)
+            .join(format!("v{}.json", version));
+        let data = fs::read(&path)
+            .with_context(|| format!("Failed to read baseline {}", path.display()))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn latest(&self, machine: &str, snapshot: &str) -> Result<Option<Baseline>> {
+        match self.versions(machine, snapshot)?.last() {
+            Some(&version) => Ok(Some(self.load(machine, snapshot, version)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn for_log(&self, log: &ExecutionLog) -> Result<Option<Baseline>> {
+        match (&log.machine, &log.snapshot) {
+            (Some(machine), Some(snapshot)) => self.latest(machine, snapshot),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::ioc::{Confidence, Ioc};
+use crate::netsim::{AttributedRequest, Service};
+use crate::pcap::decode::Transport;
+use crate::pcap::http::HttpExchange;
+use crate::pcap::CorrelatedFlow;
+
+const MIN_CALLBACKS: usize = 4;
+const MIN_INTERVAL_SECS: f64 = 1.0;
+const MAX_JITTER: f64 = 0.25;
+const STRICT_JITTER: f64 = 0.1;
+const MIN_URI_REPETITION: f64 = 0.8;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Callback {
+    pub time: DateTime<Utc>,
+    pub client: SocketAddr,
+    pub destination: String,
+    pub uri: Option<String>,
+    pub image: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Beacon {
+    pub destination: String,
+    pub image: Option<String>,
+    pub callbacks: usize,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub interval_secs: f64,
+    pub jitter: f64,
+    pub uri_pattern: Option<String>,
+    pub uri_repetition: f64,
+    pub confidence: Confidence,
+}
+
+impl Beacon {
+    pub fn ioc(&self) -> Ioc {
+        if let Ok(address) = self.destination.parse::<SocketAddr>() {
+            return Ioc::Ip(address.ip());
+        }
+        if let Ok(ip) = self.destination.parse::<IpAddr>() {
+            return Ioc::Ip(ip);
+        }
+        let host = match self.destination.rsplit_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_ok() => host,
+            _ => &self.destination,
+        };
+        Ioc::Domain(host.to_lowercase())
+    }
+}
+
+fn uri_pattern(uri: &str) -> String {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN
+        .get_or_init(|| {
+            Regex::new(r"[0-9a-fA-F]{8,}|[A-Za-z0-9+_-]{24,}={0,2}|\d+")
+                .expect("Invalid URI pattern")
+        })
+        .replace_all(uri, "*")
+        .into_owned()
+}
+
+pub fn callbacks(
+    traffic: &[CorrelatedFlow],
+    http: &[HttpExchange],
+    requests: &[AttributedRequest],
+) -> Vec<Callback> {
+    let mut callbacks = Vec::new();
+    let mut seen = HashSet::new();
+    let image_of = |client: SocketAddr| {
+        traffic
+            .iter()
+            .find(|t| t.flow.client == client)
+            .and_then(|t| t.image.clone())
+    };
+
+    for r in requests {
+        if !matches!(r.request.service, Service::Http | Service::Https) {
+            continue;
+        }
+        let details = &r.request.details;
+        let Some(destination) = details.get("host").or_else(|| details.get("sni")) else {
+            continue;
+        };
+        let uri = details
+            .contains_key("host")
+            .then(|| r.request.summary.split(' ').nth(1).map(str::to_string))
+            .flatten();
+        seen.insert(r.request.client);
+        callbacks.push(Callback {
+            time: r.request.time,
+            client: r.request.client,
+            destination: destination.to_lowercase(),
+            uri,
+            image: r.image.clone(),
+        });
+    }
+
+    let mut http_clients = HashSet::new();
+    for exchange in http {
+        http_clients.insert(exchange.client);
+        if seen.contains(&exchange.client) {
+            continue;
+        }
+        callbacks.push(Callback {
+            time: exchange.time,
+            client: exchange.client,
+            destination: exchange
+                .host
+                .clone()
+                .unwrap_or_else(|| exchange.server.to_string())
+                .to_lowercase(),
+            uri: Some(exchange.request.uri.clone()),
+            image: image_of(exchange.client),
+        });
+    }
+
+    for t in traffic {
+        let flow = &t.flow;
+        if flow.transport != Transport::Tcp
+            || seen.contains(&flow.client)
+            || http_clients.contains(&flow.client)
+            || flow.bytes_sent == 0
+        {
+            continue;
+        }
+        callbacks.push(Callback {
+            time: flow.first_seen,
+            client: flow.client,
+            destination: flow
+                .sni
+                .clone()
+                .or_else(|| flow.http_host.clone())
+                .unwrap_or_else(|| flow.server.to_string())
+                .to_lowercase(),
+            uri: None,
+            image: t.image.clone(),
+        });
+    }
+
+    callbacks.sort_by_key(|c| c.time);
+    callbacks
+}
+
+fn most_common<'a, I: IntoIterator<Item = &'a str>>(values: I) -> Option<(&'a str, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+}
+
+pub fn detect(callbacks: &[Callback]) -> Vec<Beacon> {
+    let mut by_destination: BTreeMap<&str, Vec<&Callback>> = BTreeMap::new();
+    for callback in callbacks {
+        by_destination
+            .entry(callback.destination.as_str())
+            .or_default()
+            .push(callback);
+    }
+
+    let mut beacons = Vec::new();
+    for (destination, mut group) in by_destination {
+        if group.len() < MIN_CALLBACKS {
+            continue;
+        }
+        group.sort_by_key(|c| c.time);
+        let intervals: Vec<f64> = group
+            .windows(2)
+            .map(|w| (w[1].time - w[0].time).num_milliseconds() as f64 / 1000.0)
+            .collect();
+        let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+        if mean < MIN_INTERVAL_SECS {
+            continue;
+        }
+        let variance =
+            intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / intervals.len() as f64;
+        let jitter = variance.sqrt() / mean;
+        if jitter > MAX_JITTER {
+            continue;
+        }
+
+        let patterns: Vec<String> = group
+            .iter()
+            .filter_map(|c| c.uri.as_deref())
+            .map(uri_pattern)
+            .collect();
+        let (uri_pattern, uri_repetition) = match most_common(patterns.iter().map(String::as_str)) {
+            Some((pattern, count)) => {
+                (Some(pattern.to_string()), count as f64 / group.len() as f64)
+            }
+            None => (None, 1.0),
+        };
+        let confidence = if jitter <= STRICT_JITTER && uri_repetition >= MIN_URI_REPETITION {
+            Confidence::High
+        } else {
+            Confidence::Medium
+        };
+
+        beacons.push(Beacon {
+            destination: destination.to_string(),
+            image: most_common(group.iter().filter_map(|c| c.image.as_deref()))
+                .map(|(image, _)| image.to_string()),
+            callbacks: group.len(),
+            first_seen: group[0].time,
+            last_seen: group[group.len() - 1].time,
+            interval_secs: mean,
+            jitter,
+            uri_pattern,
+            uri_repetition,
+            confidence,
+        });
+    }
+    beacons
+}
@@ -0,0 +1,69 @@
+use clap::{Parser, ValueEnum};
+
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[arg(short, long, default_value = "0.0.0.0:8000")]
+    pub listen: String,
+
+    #[arg(long)]
+    pub work_dir: Option<String>,
+
+    #[arg(long, value_enum, default_value = "syslog")]
+    pub log_format: LogFormat,
+
+    #[arg(long, default_value = "/var/log/syslog")]
+    pub log_path: String,
+
+    #[arg(long, num_args = 1.., conflicts_with = "log_path")]
+    pub log_command: Vec<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub screenshot_command: Vec<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub record_command: Vec<String>,
+
+    #[arg(long)]
+    pub user_sim_script: Option<String>,
+
+    #[arg(long)]
+    pub hardening_profile: Option<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub sleep_hook_command: Vec<String>,
+
+    #[arg(long)]
+    pub archive_dir: Option<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub process_dump_command: Vec<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub full_dump_command: Vec<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub handle_command: Vec<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub clipboard_command: Vec<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub browser_command: Vec<String>,
+
+    #[arg(long, num_args = 1..)]
+    pub api_trace_command: Vec<String>,
+
+    #[arg(long, num_args = 1.., value_delimiter = ',')]
+    pub traced_apis: Vec<String>,
+
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    pub grpc: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum LogFormat {
+    Syslog,
+    Xml,
+    Auditd,
+}
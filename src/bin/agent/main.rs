@@ -0,0 +1,69 @@
+mod args;
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+
+use args::Args;
+#[cfg(feature = "grpc")]
+use malware_analysis_sandbox::agent::grpc::GrpcAgent;
+use malware_analysis_sandbox::agent::hardening::HardeningProfile;
+use malware_analysis_sandbox::agent::protocol::LogFormat;
+use malware_analysis_sandbox::agent::user_sim::UserSimScript;
+use malware_analysis_sandbox::agent::{Agent, AgentConfig};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let args = Args::parse();
+
+    let mut config = AgentConfig {
+        log_format: match args.log_format {
+            args::LogFormat::Syslog => LogFormat::Syslog,
+            args::LogFormat::Xml => LogFormat::Xml,
+            args::LogFormat::Auditd => LogFormat::Auditd,
+        },
+        screenshot_command: args.screenshot_command,
+        record_command: args.record_command,
+        sleep_hook_command: args.sleep_hook_command,
+        archive_dir: args.archive_dir.map(PathBuf::from),
+        process_dump_command: args.process_dump_command,
+        full_dump_command: args.full_dump_command,
+        handle_command: args.handle_command,
+        clipboard_command: args.clipboard_command,
+        api_trace_command: args.api_trace_command,
+        ..AgentConfig::default()
+    };
+    if let Some(script) = &args.user_sim_script {
+        config.user_sim = UserSimScript::from_file(script)?;
+    }
+    if let Some(profile) = &args.hardening_profile {
+        config.hardening = Some(HardeningProfile::load(profile)?);
+    }
+    if !args.browser_command.is_empty() {
+        config.browser_command = args.browser_command;
+    }
+    if !args.traced_apis.is_empty() {
+        config.traced_apis = args.traced_apis;
+    }
+    if let Some(work_dir) = args.work_dir {
+        config.work_dir = PathBuf::from(work_dir);
+    }
+    if args.log_command.is_empty() {
+        config.log_path = Some(PathBuf::from(args.log_path));
+    } else {
+        config.log_path = None;
+        config.log_command = args.log_command;
+    }
+
+    info!("Listening on {}...", args.listen);
+    #[cfg(feature = "grpc")]
+    if args.grpc {
+        return GrpcAgent::new(config).serve(args.listen.parse()?).await;
+    }
+    let agent = Agent::bind(&args.listen, config).await?;
+    agent.serve().await
+}
@@ -7,10 +7,103 @@ pub struct Args {
 
     #[arg(short, long)]
     pub path: String,
+
+    #[arg(long, default_value = "sigma")]
+    pub rules: String,
+
+    #[arg(long)]
+    pub signatures: Option<String>,
+
+    #[arg(long)]
+    pub capability_rules: Option<String>,
+
+    #[arg(long)]
+    pub parentage_rules: Option<String>,
+
+    #[cfg(any(feature = "plugins", feature = "wasm"))]
+    #[arg(long)]
+    pub plugins: Option<String>,
+
+    #[arg(long)]
+    pub weights: Option<String>,
+
+    #[arg(long)]
+    pub verdict_policy: Option<String>,
+
+    #[arg(long)]
+    pub trust_policy: Option<String>,
+
+    #[arg(long)]
+    pub geoip: Option<String>,
+
+    #[arg(long)]
+    pub file_allowlist: Option<String>,
+
+    #[arg(long)]
+    pub fingerprint_blocklist: Option<String>,
+
+    #[arg(long)]
+    pub driver_blocklist: Option<String>,
+
+    #[arg(long)]
+    pub event_filter: Option<String>,
+
+    #[arg(long)]
+    pub archive: Option<String>,
+
+    #[arg(long, default_value = "zip")]
+    pub archive_format: String,
+
+    #[arg(long, default_value = "infected")]
+    pub archive_password: String,
+
+    #[arg(long, value_enum, default_value = "json")]
+    pub format: ReportFormat,
+
+    #[arg(long)]
+    pub expected: Option<String>,
+
+    #[arg(long)]
+    pub update_expected: bool,
+
+    #[arg(long)]
+    pub recommend_options: Option<String>,
+
+    #[arg(long)]
+    pub parquet: Option<String>,
+
+    #[cfg(feature = "arrow")]
+    #[arg(long)]
+    pub arrow: Option<String>,
+
+    #[cfg(feature = "arrow")]
+    #[arg(long, default_value_t = 65536)]
+    pub batch_size: usize,
 }
 
 #[derive(ValueEnum, Clone, Copy, Debug)]
 pub enum Analyzer {
+    Static,
     Surface,
     Behavior,
+    Sigma,
+    Report,
+    Replay,
+    SysmonConfig,
+    Features,
+    #[cfg(feature = "arrow")]
+    Events,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    Json,
+    Html,
+    Stix,
+    Misp,
+    Cef,
+    Leef,
+    Timesketch,
+    Graphml,
+    Cypher,
 }
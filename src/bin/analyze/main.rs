@@ -3,14 +3,223 @@ mod args;
 use std::fs::File;
 use std::io;
 
-use anyhow::Result;
-use args::{Analyzer, Args};
+use anyhow::{bail, Context, Result};
+use args::{Analyzer, Args, ReportFormat};
 use clap::Parser;
-use log::{error, info};
+use log::info;
 use sha3::{Digest, Sha3_512};
 
-use malware_analysis_sandbox::analysis_result::AnalysisResultManager;
+use malware_analysis_sandbox::analysis_result::{AnalysisResult, AnalysisResultManager};
+use malware_analysis_sandbox::analyzer::capability::CapabilityRules;
+use malware_analysis_sandbox::analyzer::code_signing::TrustPolicy;
+use malware_analysis_sandbox::analyzer::driver::DriverBlocklist;
+use malware_analysis_sandbox::analyzer::fingerprint::FingerprintBlocklist;
+use malware_analysis_sandbox::analyzer::parentage::ParentageRules;
+use malware_analysis_sandbox::analyzer::sigma::{self, SigmaRule};
+use malware_analysis_sandbox::analyzer::signature::SignatureRegistry;
 use malware_analysis_sandbox::analyzer::{behavior_detection, surface_detection};
+use malware_analysis_sandbox::config_extractor::ConfigExtractorRegistry;
+use malware_analysis_sandbox::enrichment::geo::GeoDatabase;
+use malware_analysis_sandbox::event_filter::EventFilter;
+use malware_analysis_sandbox::export::archive::{export_run, ArchiveOptions};
+use malware_analysis_sandbox::export::cef::report_to_cef;
+#[cfg(feature = "arrow")]
+use malware_analysis_sandbox::export::columnar;
+use malware_analysis_sandbox::export::graph::{report_to_cypher, report_to_graphml};
+use malware_analysis_sandbox::export::leef::report_to_leef;
+use malware_analysis_sandbox::export::timesketch::report_to_timesketch;
+use malware_analysis_sandbox::features::{to_csv, write_parquet, FeatureVector};
+use malware_analysis_sandbox::filesystem::FilesystemOptions;
+use malware_analysis_sandbox::misp::MispEvent;
+#[cfg(any(feature = "plugins", feature = "wasm"))]
+use malware_analysis_sandbox::plugin::AnalyzerRegistry;
+use malware_analysis_sandbox::replay::{load_corpus, Replayer};
+use malware_analysis_sandbox::report::SandboxReport;
+use malware_analysis_sandbox::scoring::ScoringOptions;
+use malware_analysis_sandbox::static_analysis::{extract_scripts, pe};
+use malware_analysis_sandbox::sysmon_config::{RecommendOptions, SysmonConfig};
+use malware_analysis_sandbox::sysmon_event::SysmonEvent;
+use malware_analysis_sandbox::verdict::VerdictPolicy;
+
+async fn find_result(path: &str, filter: Option<&str>) -> Result<AnalysisResult> {
+    let mut sample = File::open(path)?;
+
+    info!("Calcurating hash of sample...");
+    let mut hasher = Sha3_512::new();
+    io::copy(&mut sample, &mut hasher)?;
+    let hash = format!("{:x}", hasher.finalize());
+
+    let analysis_result_manager = AnalysisResultManager::init().await?;
+
+    info!("Searching result...");
+    let mut result = analysis_result_manager
+        .search_hash(&hash)
+        .await?
+        .context("Analysis result does not exist. Please execute sample and get log first")?;
+
+    if let Some(path) = filter {
+        info!("Filtering events with {}...", path);
+        let filter = EventFilter::from_file(path)?;
+        for log in &mut result.execution_logs {
+            filter.apply_log(log);
+        }
+    }
+    Ok(result)
+}
+
+async fn latest_events(path: &str, filter: Option<&str>) -> Result<Vec<SysmonEvent>> {
+    let analysis_result = find_result(path, filter).await?;
+
+    Ok(analysis_result
+        .execution_logs
+        .last()
+        .context("No execution log for the sample")?
+        .sysmon_events
+        .clone())
+}
+
+struct Pipeline {
+    rules: Vec<SigmaRule>,
+    signatures: SignatureRegistry,
+    capabilities: CapabilityRules,
+    parentage: ParentageRules,
+    geo: Option<GeoDatabase>,
+    fingerprints: Option<FingerprintBlocklist>,
+    drivers: Option<DriverBlocklist>,
+    trust: TrustPolicy,
+    #[cfg(any(feature = "plugins", feature = "wasm"))]
+    analyzers: Option<AnalyzerRegistry>,
+    filesystem: Option<FilesystemOptions>,
+    scoring: Option<ScoringOptions>,
+    verdict: VerdictPolicy,
+}
+
+impl Pipeline {
+    fn load(args: &Args) -> Result<Self> {
+        info!("Loading sigma rules...");
+        let rules = SigmaRule::load_dir(&args.rules)?;
+
+        info!("Loading signatures...");
+        let mut signatures = SignatureRegistry::with_defaults();
+        if let Some(dir) = &args.signatures {
+            signatures.load_dir(dir)?;
+        }
+        let mut capabilities = CapabilityRules::with_defaults();
+        if let Some(dir) = &args.capability_rules {
+            capabilities.load_dir(dir)?;
+        }
+        let mut parentage = ParentageRules::with_defaults();
+        if let Some(dir) = &args.parentage_rules {
+            parentage.load_dir(dir)?;
+        }
+        #[cfg(any(feature = "plugins", feature = "wasm"))]
+        let analyzers = match &args.plugins {
+            Some(dir) => {
+                let mut analyzers = AnalyzerRegistry::new();
+                analyzers.load_dir(dir)?;
+                Some(analyzers)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            rules,
+            signatures,
+            capabilities,
+            parentage,
+            geo: args
+                .geoip
+                .as_ref()
+                .map(GeoDatabase::from_file)
+                .transpose()?,
+            fingerprints: args
+                .fingerprint_blocklist
+                .as_ref()
+                .map(FingerprintBlocklist::from_file)
+                .transpose()?,
+            drivers: args
+                .driver_blocklist
+                .as_ref()
+                .map(DriverBlocklist::from_file)
+                .transpose()?,
+            trust: match &args.trust_policy {
+                Some(path) => TrustPolicy::from_file(path)?,
+                None => TrustPolicy::default(),
+            },
+            #[cfg(any(feature = "plugins", feature = "wasm"))]
+            analyzers,
+            filesystem: args
+                .file_allowlist
+                .as_ref()
+                .map(FilesystemOptions::from_file)
+                .transpose()?,
+            scoring: args
+                .weights
+                .as_ref()
+                .map(ScoringOptions::from_file)
+                .transpose()?,
+            verdict: match &args.verdict_policy {
+                Some(path) => VerdictPolicy::from_file(path)?,
+                None => VerdictPolicy::default(),
+            },
+        })
+    }
+
+    fn report(&self, analysis_result: &AnalysisResult) -> Result<SandboxReport> {
+        let mut report = SandboxReport::from_analysis_result(analysis_result)?;
+        report.add_captured_traffic()?;
+        if let Some(geo) = &self.geo {
+            report.add_geo(geo);
+        }
+        report.add_sigma_detections(&self.rules);
+        report.add_signature_detections(&self.signatures);
+        report.add_parentage_detections(&self.parentage);
+        if let Some(blocklist) = &self.fingerprints {
+            report.add_fingerprint_detections(blocklist);
+        }
+        if let Some(blocklist) = &self.drivers {
+            report.add_drivers(blocklist);
+        }
+        report.add_static_analysis()?;
+        report.add_code_signing(&self.trust);
+        report.add_capabilities(&self.capabilities)?;
+        report.add_configs(&ConfigExtractorRegistry::with_defaults())?;
+        report.add_enrichment()?;
+        #[cfg(any(feature = "plugins", feature = "wasm"))]
+        if let Some(analyzers) = &self.analyzers {
+            report.add_analyzers(analyzers, analysis_result);
+        }
+        if let Some(allowlist) = &self.filesystem {
+            report.add_filesystem_summary(allowlist);
+        }
+        if let Some(weights) = &self.scoring {
+            report.add_score(weights);
+        }
+        report.add_verdict(&self.verdict);
+        Ok(report)
+    }
+}
+
+fn corpus_reports(args: &Args) -> Result<Vec<SandboxReport>> {
+    let results = load_corpus(&args.path)?;
+    let pipeline = Pipeline::load(args)?;
+    let filter = match &args.event_filter {
+        Some(path) => Some(EventFilter::from_file(path)?),
+        None => None,
+    };
+
+    info!("Generating reports for {} analyses...", results.len());
+    let mut reports = Vec::new();
+    for mut result in results {
+        if let Some(filter) = &filter {
+            for log in &mut result.execution_logs {
+                filter.apply_log(log);
+            }
+        }
+        reports.push(pipeline.report(&result)?);
+    }
+    Ok(reports)
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -19,6 +228,17 @@ async fn main() -> Result<()> {
     let args = Args::parse();
 
     match args.analyzer {
+        Analyzer::Static => {
+            info!("Static analyzer is selected");
+            let sample = std::fs::read(&args.path)?;
+            if pe::is_pe(&sample) {
+                let info = pe::analyze(&sample)?;
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                let scripts = extract_scripts(&args.path, &sample)?;
+                println!("{}", serde_json::to_string_pretty(&scripts)?);
+            }
+        }
         Analyzer::Surface => {
             info!("Surface analyzer is selected");
             let result = surface_detection::detect(args.path, "rule.yar", 5)?;
@@ -30,39 +250,160 @@ async fn main() -> Result<()> {
         }
         Analyzer::Behavior => {
             info!("Behavior analyzer is selected");
-            let mut sample = File::open(&args.path)?;
-
-            info!("Calcurating hash of sample...");
-            let mut hasher = Sha3_512::new();
-            io::copy(&mut sample, &mut hasher)?;
-            let hash = format!("{:x}", hasher.finalize());
+            let events = latest_events(&args.path, args.event_filter.as_deref()).await?;
 
-            let analysis_result_manager = AnalysisResultManager::init().await?;
+            info!("Detecting...");
+            let result = behavior_detection::detect(events)?;
 
-            info!("Searching result...");
-            let analysis_result = analysis_result_manager.search_hash(&hash).await?;
-            if let None = analysis_result {
-                error!("Analysis result does not exist");
-                error!("Please execute sample and get log first")
+            if !result.is_empty() {
+                println!("The sample is likely malware");
+                println!("The following behaviors are suspicious");
+                for r in result {
+                    println!("{:#?}", r);
+                }
+            } else {
+                println!("The sample is not malware");
             }
+        }
+        Analyzer::Sigma => {
+            info!("Sigma analyzer is selected");
+            let events = latest_events(&args.path, args.event_filter.as_deref()).await?;
 
-            let ar = analysis_result.unwrap();
+            info!("Loading sigma rules...");
+            let rules = SigmaRule::load_dir(&args.rules)?;
 
             info!("Detecting...");
-            let result = behavior_detection::detect(
-                ar.execution_logs.last().unwrap().sysmon_events.clone(),
-            )?;
+            let result = sigma::evaluate(&rules, &events);
 
-            if result.len() > 0 {
+            if !result.is_empty() {
                 println!("The sample is likely malware");
-                println!("The following behaviors are suspicious");
+                println!("The following rules matched");
                 for r in result {
-                    println!("{:#?}", r);
+                    println!(
+                        "[{}] {} {:?}",
+                        r.level.as_deref().unwrap_or("unknown"),
+                        r.title,
+                        r.tags
+                    );
+                    println!("{:#?}", r.event);
                 }
             } else {
                 println!("The sample is not malware");
             }
         }
+        Analyzer::Report => {
+            info!("Report is selected");
+            let analysis_result = find_result(&args.path, args.event_filter.as_deref()).await?;
+            let pipeline = Pipeline::load(&args)?;
+
+            info!("Generating report...");
+            let report = pipeline.report(&analysis_result)?;
+            if let Some(path) = &args.archive {
+                info!("Writing artifact archive {}...", path);
+                let options = ArchiveOptions {
+                    format: args.archive_format.parse()?,
+                    password: args.archive_password.clone(),
+                    ..ArchiveOptions::default()
+                };
+                let manifest = export_run(&report, &options, File::create(path)?)?;
+                info!("Archived {} files", manifest.entries.len());
+            }
+
+            match args.format {
+                ReportFormat::Json => println!("{}", report.to_json()?),
+                ReportFormat::Html => println!("{}", report.to_html()),
+                ReportFormat::Stix => println!("{}", report.to_stix()?),
+                ReportFormat::Misp => println!("{}", MispEvent::from_report(&report).to_json()?),
+                ReportFormat::Cef => report_to_cef(&report)
+                    .iter()
+                    .for_each(|line| println!("{}", line)),
+                ReportFormat::Leef => report_to_leef(&report)
+                    .iter()
+                    .for_each(|line| println!("{}", line)),
+                ReportFormat::Timesketch => report_to_timesketch(&report)?
+                    .iter()
+                    .for_each(|line| println!("{}", line)),
+                ReportFormat::Graphml => print!("{}", report_to_graphml(&report)),
+                ReportFormat::Cypher => report_to_cypher(&report)
+                    .iter()
+                    .for_each(|line| println!("{}", line)),
+            }
+        }
+        Analyzer::Replay => {
+            info!("Replay is selected");
+            let results = load_corpus(&args.path)?;
+            let pipeline = Pipeline::load(&args)?;
+            let reporter = |result: &AnalysisResult| pipeline.report(result);
+            let filter = match &args.event_filter {
+                Some(path) => Some(EventFilter::from_file(path)?),
+                None => None,
+            };
+
+            let mut replayer = Replayer::new(&reporter).update(args.update_expected);
+            if let Some(dir) = &args.expected {
+                replayer = replayer.with_expected_dir(dir);
+            }
+            if let Some(filter) = &filter {
+                replayer = replayer.with_filter(filter);
+            }
+
+            info!("Replaying {} analyses...", results.len());
+            let outcomes = replayer.replay_corpus(&results);
+            for outcome in &outcomes {
+                println!("{}", serde_json::to_string(outcome)?);
+            }
+            let changed = outcomes.iter().filter(|o| o.changed()).count();
+            if changed > 0 && !args.update_expected {
+                bail!(
+                    "{} of {} replayed analyses changed",
+                    changed,
+                    outcomes.len()
+                );
+            }
+        }
+        Analyzer::SysmonConfig => {
+            info!("Sysmon config recommendation is selected");
+            let options = match &args.recommend_options {
+                Some(path) => RecommendOptions::from_file(path)?,
+                None => RecommendOptions::default(),
+            };
+            let reports = corpus_reports(&args)?;
+            print!(
+                "{}",
+                SysmonConfig::from_reports(&reports, &options).to_xml()
+            );
+        }
+        Analyzer::Features => {
+            info!("Feature export is selected");
+            let vectors: Vec<FeatureVector> = corpus_reports(&args)?
+                .iter()
+                .map(FeatureVector::from_report)
+                .collect();
+            match &args.parquet {
+                Some(path) => write_parquet(&vectors, File::create(path)?)?,
+                None => print!("{}", to_csv(&vectors)),
+            }
+        }
+        #[cfg(feature = "arrow")]
+        Analyzer::Events => {
+            info!("Columnar event export is selected");
+            if args.arrow.is_none() && args.parquet.is_none() {
+                bail!("Event export requires --arrow or --parquet");
+            }
+            let result = find_result(&args.path, args.event_filter.as_deref()).await?;
+            let batches = columnar::record_batches(&result.execution_logs, args.batch_size)?;
+            info!(
+                "Writing {} events in {} batches...",
+                batches.iter().map(|b| b.num_rows()).sum::<usize>(),
+                batches.len()
+            );
+            if let Some(path) = &args.arrow {
+                columnar::write_ipc(&batches, File::create(path)?)?;
+            }
+            if let Some(path) = &args.parquet {
+                columnar::write_parquet(&batches, File::create(path)?)?;
+            }
+        }
     }
 
     Ok(())
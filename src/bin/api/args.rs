@@ -0,0 +1,202 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use clap::{Parser, ValueEnum};
+
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[arg(short, long, default_value = "0.0.0.0:8080")]
+    pub listen: String,
+
+    #[arg(long = "api-key", required = true)]
+    pub api_keys: Vec<String>,
+
+    #[arg(long)]
+    pub users: Option<String>,
+
+    #[arg(long)]
+    pub portal: Option<String>,
+
+    #[arg(long)]
+    pub rule_pack: Option<String>,
+
+    #[arg(long, value_enum, default_value = "libvirt")]
+    pub hypervisor: HypervisorKind,
+
+    #[arg(long, value_enum, default_value = "tcp")]
+    pub agent_transport: AgentTransport,
+
+    #[arg(
+        long = "machine",
+        required = true,
+        value_name = "NAME:SNAPSHOT[:TAG,...]"
+    )]
+    pub machines: Vec<String>,
+
+    #[arg(long, default_value = "queue.db")]
+    pub queue: String,
+
+    #[arg(long, default_value = "similarity.db")]
+    pub similarity: String,
+
+    #[arg(long, default_value = "results.db")]
+    pub results_db: String,
+
+    #[arg(long)]
+    pub retention: Option<String>,
+
+    #[arg(long, default_value_t = 3600, requires = "retention")]
+    pub gc_interval: u64,
+
+    #[arg(long, default_value = "sigma")]
+    pub rules: String,
+
+    #[arg(long)]
+    pub signatures: Option<String>,
+
+    #[arg(long)]
+    pub capability_rules: Option<String>,
+
+    #[arg(long)]
+    pub parentage_rules: Option<String>,
+
+    #[cfg(any(feature = "plugins", feature = "wasm"))]
+    #[arg(long)]
+    pub plugins: Option<String>,
+
+    #[arg(long)]
+    pub weights: Option<String>,
+
+    #[arg(long)]
+    pub verdict_policy: Option<String>,
+
+    #[arg(long)]
+    pub trust_policy: Option<String>,
+
+    #[arg(long)]
+    pub geoip: Option<String>,
+
+    #[arg(long)]
+    pub file_allowlist: Option<String>,
+
+    #[arg(long)]
+    pub fingerprint_blocklist: Option<String>,
+
+    #[arg(long)]
+    pub driver_blocklist: Option<String>,
+
+    #[arg(long)]
+    pub event_filter: Option<String>,
+
+    #[arg(long)]
+    pub baselines: Option<String>,
+
+    #[arg(long, value_name = "RUNS", requires = "baselines")]
+    pub learn_baseline: Option<usize>,
+
+    #[arg(long, default_value_t = 300)]
+    pub baseline_timeout: u64,
+
+    #[arg(long)]
+    pub golden: Option<String>,
+
+    #[arg(long, requires = "golden")]
+    pub capture_golden: bool,
+
+    #[arg(long, value_delimiter = ',')]
+    pub watched_files: Vec<String>,
+
+    #[arg(long)]
+    pub images: Option<String>,
+
+    #[arg(long)]
+    pub limits: Option<String>,
+
+    #[arg(long)]
+    pub live_policy: Option<String>,
+
+    #[arg(long)]
+    pub vmi: Option<String>,
+
+    #[arg(long)]
+    pub ids: Option<String>,
+
+    #[arg(long)]
+    pub profiles: Option<String>,
+
+    #[arg(long, default_value_t = 4)]
+    pub max_concurrency: usize,
+
+    #[arg(long)]
+    pub time_warp: Option<f64>,
+
+    #[arg(long, requires = "time_warp")]
+    pub skip_sleeps: bool,
+
+    #[arg(long, value_name = "BIND_ADDRESS")]
+    pub netsim: Option<IpAddr>,
+
+    #[arg(long, default_value = "10.0.0.1")]
+    pub sinkhole: Ipv4Addr,
+
+    #[arg(long, requires_all = ["netsim", "intercept_key"])]
+    pub intercept_ca: Option<String>,
+
+    #[arg(long, requires = "intercept_ca")]
+    pub intercept_key: Option<String>,
+
+    #[arg(long, requires = "netsim")]
+    pub netsim_upstream: bool,
+
+    #[cfg(feature = "enrichment")]
+    #[arg(long)]
+    pub virustotal_key: Option<String>,
+
+    #[cfg(feature = "enrichment")]
+    #[arg(long)]
+    pub malwarebazaar_key: Option<String>,
+
+    #[cfg(feature = "enrichment")]
+    #[arg(long)]
+    pub urlhaus_key: Option<String>,
+
+    #[cfg(feature = "enrichment")]
+    #[arg(long, default_value = "enrichment_cache")]
+    pub enrichment_cache: String,
+
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    #[arg(long, value_name = "URL")]
+    pub bus: Option<String>,
+
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    #[arg(long, default_value = "sandbox")]
+    pub bus_topic_prefix: String,
+
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    #[arg(long, default_value = "run-id")]
+    pub bus_partitioning: String,
+
+    #[cfg(feature = "notify")]
+    #[arg(long)]
+    pub notify: Option<String>,
+
+    #[cfg(feature = "opencti")]
+    #[arg(long)]
+    pub opencti: Option<String>,
+
+    #[cfg(feature = "otel")]
+    #[arg(long, value_name = "URL")]
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum HypervisorKind {
+    Libvirt,
+    Virtualbox,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum AgentTransport {
+    Tcp,
+    Grpc,
+    Agentless,
+}
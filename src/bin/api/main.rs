@@ -0,0 +1,612 @@
+mod args;
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use clap::Parser;
+use log::{info, warn};
+use tokio::sync::mpsc;
+
+use args::{Args, HypervisorKind};
+use malware_analysis_sandbox::agent::protocol::TimeWarp;
+use malware_analysis_sandbox::analysis_result::{
+    AnalysisResult, AnalysisResultManager, ExecutionLog,
+};
+use malware_analysis_sandbox::analyzer::capability::CapabilityRules;
+use malware_analysis_sandbox::analyzer::code_signing::TrustPolicy;
+use malware_analysis_sandbox::analyzer::driver::DriverBlocklist;
+use malware_analysis_sandbox::analyzer::fingerprint::FingerprintBlocklist;
+use malware_analysis_sandbox::analyzer::live::{LiveMonitor, LivePolicy};
+use malware_analysis_sandbox::analyzer::parentage::ParentageRules;
+use malware_analysis_sandbox::analyzer::sigma::SigmaRule;
+use malware_analysis_sandbox::analyzer::signature::SignatureRegistry;
+use malware_analysis_sandbox::api::portal::{Portal, PortalConfig};
+use malware_analysis_sandbox::api::users::UserStore;
+use malware_analysis_sandbox::api::{resubmit_dropped, router, ApiConfig};
+use malware_analysis_sandbox::baseline::{clean_request, Baseline, BaselineStore};
+#[cfg(any(feature = "kafka", feature = "nats"))]
+use malware_analysis_sandbox::bus::{BusConfig, EventBus};
+use malware_analysis_sandbox::enrichment::geo::GeoDatabase;
+#[cfg(feature = "enrichment")]
+use malware_analysis_sandbox::enrichment::{Enricher, EnrichmentConfig};
+use malware_analysis_sandbox::event_filter::EventFilter;
+use malware_analysis_sandbox::filesystem::FilesystemOptions;
+#[cfg(feature = "otel")]
+use malware_analysis_sandbox::metrics;
+use malware_analysis_sandbox::netsim::{InterceptCa, NetSim, NetSimConfig};
+#[cfg(feature = "notify")]
+use malware_analysis_sandbox::notify::{Notification, Notifier, NotifyConfig};
+#[cfg(feature = "opencti")]
+use malware_analysis_sandbox::opencti::{OpenCtiClient, OpenCtiConfig, OpenCtiPush};
+use malware_analysis_sandbox::orchestrator::image::ImageStore;
+use malware_analysis_sandbox::orchestrator::libvirt::Libvirt;
+use malware_analysis_sandbox::orchestrator::limits::ResourceLimits;
+use malware_analysis_sandbox::orchestrator::virtualbox::VirtualBox;
+use malware_analysis_sandbox::orchestrator::{AgentTransport, Hypervisor, Orchestrator, VmSpec};
+use malware_analysis_sandbox::pcap::ids::IdsConfig;
+use malware_analysis_sandbox::plugin::AnalyzerRegistry;
+use malware_analysis_sandbox::rule_pack::{RulePackConfig, RulePackManager};
+use malware_analysis_sandbox::scheduler::profile::ProfileRegistry;
+use malware_analysis_sandbox::scheduler::sqlite::SqliteStore;
+use malware_analysis_sandbox::scheduler::{Machine, Scheduler, SchedulerOptions};
+use malware_analysis_sandbox::scoring::ScoringOptions;
+use malware_analysis_sandbox::similarity::index::SimilarityIndex;
+use malware_analysis_sandbox::storage::retention::{GarbageCollector, RetentionPolicy};
+use malware_analysis_sandbox::storage::sqlite::SqliteResultStore;
+use malware_analysis_sandbox::tamper::{GoldenStore, DEFAULT_WATCHED_FILES};
+use malware_analysis_sandbox::verdict::VerdictPolicy;
+use malware_analysis_sandbox::vmi::VmiConfig;
+
+fn parse_machine(s: &str, transport: AgentTransport) -> Result<Machine> {
+    let mut parts = s.splitn(3, ':');
+    let name = parts
+        .next()
+        .filter(|n| !n.is_empty())
+        .context("No machine name")?;
+    let snapshot = parts.next().context("No snapshot name")?;
+    let tags: Vec<&str> = parts
+        .next()
+        .map(|t| t.split(',').filter(|t| !t.is_empty()).collect())
+        .unwrap_or_default();
+    let mut spec = VmSpec::new(name, snapshot);
+    spec.transport = transport;
+    Ok(Machine::new(spec, &tags))
+}
+
+fn index_execution(
+    index: &Mutex<SimilarityIndex>,
+    analysis_id: &str,
+    sample_path: &str,
+    log: &ExecutionLog,
+) -> Result<()> {
+    let sample = std::fs::read(sample_path)?;
+    let mut index = index
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Similarity index is poisoned"))?;
+    index.add_sample(analysis_id, &sample)?;
+    index.add_execution(analysis_id, log)
+}
+
+async fn persist_report(
+    results: &AnalysisResultManager,
+    config: &ApiConfig,
+    analysis_id: &str,
+) -> Result<()> {
+    let result = results
+        .get(analysis_id)
+        .await?
+        .context("No analysis result for the id")?;
+    config.persist(&config.report(&result)?)
+}
+
+#[cfg(feature = "enrichment")]
+async fn enrich_report(
+    results: &AnalysisResultManager,
+    config: &ApiConfig,
+    enricher: &Enricher,
+    analysis_id: &str,
+) -> Result<()> {
+    let result = results
+        .get(analysis_id)
+        .await?
+        .context("No analysis result for the id")?;
+    let enrichment = enricher.enrich_report(&config.report(&result)?).await?;
+    info!(
+        "Enriched analysis {} with {} results",
+        analysis_id,
+        enrichment.len()
+    );
+    Ok(())
+}
+
+#[cfg(any(feature = "kafka", feature = "nats"))]
+async fn publish_report(
+    results: &AnalysisResultManager,
+    config: &ApiConfig,
+    bus: &EventBus,
+    analysis_id: &str,
+) -> Result<()> {
+    let result = results
+        .get(analysis_id)
+        .await?
+        .context("No analysis result for the id")?;
+    let published = bus.publish_report(&config.report(&result)?).await?;
+    info!(
+        "Published {} messages for analysis {}",
+        published, analysis_id
+    );
+    Ok(())
+}
+
+#[cfg(feature = "notify")]
+async fn notify_report(
+    results: &AnalysisResultManager,
+    config: &ApiConfig,
+    notifier: &Arc<Notifier>,
+    analysis_id: &str,
+) -> Result<()> {
+    let result = results
+        .get(analysis_id)
+        .await?
+        .context("No analysis result for the id")?;
+    let notification = Notification::from_report(&config.report(&result)?);
+    let notifier = notifier.clone();
+    tokio::spawn(async move {
+        notifier.notify(&notification).await;
+    });
+    Ok(())
+}
+
+#[cfg(feature = "opencti")]
+async fn push_to_opencti(
+    results: &AnalysisResultManager,
+    config: &ApiConfig,
+    opencti: &OpenCtiClient,
+    analysis_id: &str,
+) -> Result<()> {
+    let result = results
+        .get(analysis_id)
+        .await?
+        .context("No analysis result for the id")?;
+    let report = config.report(&result)?;
+    if opencti.config().wants(&report) {
+        opencti
+            .push(&OpenCtiPush::from_report(&report, opencti.config()))
+            .await?;
+    }
+    Ok(())
+}
+
+async fn learn_baselines<H>(
+    orchestrator: &Orchestrator<H>,
+    machines: &[Machine],
+    store: &BaselineStore,
+    runs: usize,
+    timeout_secs: u64,
+) -> Result<()>
+where
+    H: Hypervisor + Send + Sync + 'static,
+{
+    let request = clean_request(timeout_secs);
+    for machine in machines {
+        let vm = &machine.spec;
+        let mut baseline = Baseline::new(&vm.name, &vm.snapshot);
+        for run in 1..=runs {
+            info!(
+                "Recording clean run {}/{} on {}:{}...",
+                run, runs, vm.name, vm.snapshot
+            );
+            let stages = orchestrator
+                .run(vm, &request, &ResourceLimits::default(), &[])
+                .await?;
+            let events: Vec<_> = stages.iter().flat_map(|s| s.events()).collect();
+            baseline.add_run(&events);
+        }
+        let version = store.save(&mut baseline)?;
+        info!(
+            "Saved baseline v{} for {}:{} with {} entries",
+            version,
+            vm.name,
+            vm.snapshot,
+            baseline.len()
+        );
+    }
+    Ok(())
+}
+
+async fn capture_golden<H>(
+    orchestrator: &Orchestrator<H>,
+    machines: &[Machine],
+    paths: &[String],
+) -> Result<()>
+where
+    H: Hypervisor + Send + Sync + 'static,
+{
+    let request = clean_request(0);
+    for machine in machines {
+        let vm = &machine.spec;
+        info!("Capturing golden files of {}:{}...", vm.name, vm.snapshot);
+        let image = orchestrator.capture_golden(vm, &request, paths).await?;
+        info!(
+            "Saved {}/{} golden files for {}:{}",
+            image.files.len(),
+            paths.len(),
+            vm.name,
+            vm.snapshot
+        );
+    }
+    Ok(())
+}
+
+async fn serve<H>(args: Args, hypervisor: H) -> Result<()>
+where
+    H: Hypervisor + Send + Sync + 'static,
+{
+    #[cfg(feature = "otel")]
+    if let Some(endpoint) = &args.otlp_endpoint {
+        info!("Exporting traces to {}...", endpoint);
+        metrics::init_tracing(endpoint)?;
+    }
+
+    let transport = match args.agent_transport {
+        args::AgentTransport::Tcp => AgentTransport::Tcp,
+        args::AgentTransport::Grpc => AgentTransport::Grpc,
+        args::AgentTransport::Agentless => AgentTransport::Agentless,
+    };
+    let mut machines = args
+        .machines
+        .iter()
+        .map(|m| parse_machine(m, transport))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(dir) = &args.images {
+        let images = ImageStore::open(dir)?;
+        for machine in &mut machines {
+            machine.spec.image = images.find(&machine.spec.name, &machine.spec.snapshot)?;
+        }
+    }
+
+    info!("Opening job queue {}...", args.queue);
+    let store = SqliteStore::open(&args.queue)?;
+    let options = SchedulerOptions {
+        max_concurrency: args.max_concurrency,
+        time_warp: args
+            .time_warp
+            .map(|factor| TimeWarp::new(factor, args.skip_sleeps))
+            .transpose()?,
+        limits: match &args.limits {
+            Some(path) => ResourceLimits::from_file(path)?,
+            None => ResourceLimits::default(),
+        },
+        ids: match &args.ids {
+            Some(path) => Some(IdsConfig::from_file(path)?),
+            None => None,
+        },
+        ..SchedulerOptions::default()
+    };
+    let mut orchestrator = Orchestrator::new(hypervisor);
+    if let Some(bind) = args.netsim {
+        let config = NetSimConfig {
+            bind,
+            sinkhole: args.sinkhole,
+            intercept: args
+                .intercept_ca
+                .zip(args.intercept_key)
+                .map(|(cert, key)| InterceptCa {
+                    cert: cert.into(),
+                    key: key.into(),
+                }),
+            upstream: args.netsim_upstream,
+            ..NetSimConfig::default()
+        };
+        orchestrator = orchestrator.with_netsim(Arc::new(NetSim::start(config).await?));
+    }
+    if let Some(path) = &args.vmi {
+        orchestrator = orchestrator.with_vmi(VmiConfig::from_file(path)?);
+    }
+    if let Some(dir) = &args.golden {
+        orchestrator = orchestrator.with_golden(GoldenStore::open(dir)?);
+        if args.capture_golden {
+            let paths = match args.watched_files.is_empty() {
+                true => DEFAULT_WATCHED_FILES
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect(),
+                false => args.watched_files.clone(),
+            };
+            return capture_golden(&orchestrator, &machines, &paths).await;
+        }
+    }
+    let live = match &args.live_policy {
+        Some(path) => {
+            info!("Loading live detection rules...");
+            let mut signatures = SignatureRegistry::with_defaults();
+            if let Some(dir) = &args.signatures {
+                signatures.load_dir(dir)?;
+            }
+            let live = Arc::new(LiveMonitor::new(
+                SigmaRule::load_dir(&args.rules)?,
+                signatures,
+                LivePolicy::from_file(path)?,
+            ));
+            orchestrator = orchestrator.with_live(live.clone());
+            Some(live)
+        }
+        None => None,
+    };
+    let baselines = match &args.baselines {
+        Some(dir) => Some(BaselineStore::open(dir)?),
+        None => None,
+    };
+    if let (Some(store), Some(runs)) = (&baselines, args.learn_baseline) {
+        return learn_baselines(&orchestrator, &machines, store, runs, args.baseline_timeout).await;
+    }
+    let scheduler = Arc::new(Scheduler::with_options(
+        orchestrator,
+        machines,
+        store,
+        options,
+    )?);
+    let results = Arc::new(AnalysisResultManager::init().await?);
+
+    info!("Opening similarity index {}...", args.similarity);
+    let similarity = Arc::new(Mutex::new(SimilarityIndex::open(&args.similarity)?));
+
+    info!("Opening result store {}...", args.results_db);
+    let store = Arc::new(Mutex::new(SqliteResultStore::open(&args.results_db)?));
+
+    info!("Loading signatures...");
+    let mut signatures = SignatureRegistry::with_defaults();
+    if let Some(dir) = &args.signatures {
+        signatures.load_dir(dir)?;
+    }
+    let mut capabilities = CapabilityRules::with_defaults();
+    if let Some(dir) = &args.capability_rules {
+        capabilities.load_dir(dir)?;
+    }
+    let mut parentage = ParentageRules::with_defaults();
+    if let Some(dir) = &args.parentage_rules {
+        parentage.load_dir(dir)?;
+    }
+    #[allow(unused_mut)]
+    let mut analyzers = AnalyzerRegistry::new();
+    #[cfg(any(feature = "plugins", feature = "wasm"))]
+    if let Some(dir) = &args.plugins {
+        info!("Loading analyzer plugins from {}...", dir);
+        analyzers.load_dir(dir)?;
+    }
+
+    let rule_packs = match &args.rule_pack {
+        Some(path) => {
+            let manager = Arc::new(RulePackManager::new(RulePackConfig::from_file(path)?));
+            info!("Loading rule pack...");
+            manager.reload().await?;
+            tokio::spawn(manager.clone().watch());
+            Some(manager)
+        }
+        None => None,
+    };
+
+    info!("Loading sigma rules...");
+    let config = Arc::new(ApiConfig {
+        api_keys: args.api_keys,
+        rules: SigmaRule::load_dir(&args.rules)?,
+        signatures,
+        capabilities,
+        parentage,
+        analyzers,
+        similarity: Some(similarity.clone()),
+        store: Some(store),
+        baselines,
+        event_filter: match &args.event_filter {
+            Some(path) => Some(EventFilter::from_file(path)?),
+            None => None,
+        },
+        scoring: match &args.weights {
+            Some(path) => ScoringOptions::from_file(path)?,
+            None => ScoringOptions::default(),
+        },
+        trust: match &args.trust_policy {
+            Some(path) => TrustPolicy::from_file(path)?,
+            None => TrustPolicy::default(),
+        },
+        verdict: match &args.verdict_policy {
+            Some(path) => VerdictPolicy::from_file(path)?,
+            None => VerdictPolicy::default(),
+        },
+        filesystem: match &args.file_allowlist {
+            Some(path) => FilesystemOptions::from_file(path)?,
+            None => FilesystemOptions::default(),
+        },
+        geo: match &args.geoip {
+            Some(path) => Some(GeoDatabase::from_file(path)?),
+            None => None,
+        },
+        drivers: match &args.driver_blocklist {
+            Some(path) => DriverBlocklist::from_file(path)?,
+            None => DriverBlocklist::default(),
+        },
+        fingerprints: match &args.fingerprint_blocklist {
+            Some(path) => FingerprintBlocklist::from_file(path)?,
+            None => FingerprintBlocklist::default(),
+        },
+        users: match &args.users {
+            Some(path) => Some(Arc::new(Mutex::new(UserStore::open(path)?))),
+            None => None,
+        },
+        live,
+        profiles: match &args.profiles {
+            Some(path) => ProfileRegistry::from_file(path)?,
+            None => ProfileRegistry::with_defaults(),
+        },
+        portal: match &args.portal {
+            Some(path) => Some(Arc::new(Portal::new(PortalConfig::from_file(path)?)?)),
+            None => None,
+        },
+        rule_packs,
+        ..ApiConfig::default()
+    });
+
+    #[cfg(feature = "enrichment")]
+    let enricher = match (
+        &args.virustotal_key,
+        &args.malwarebazaar_key,
+        &args.urlhaus_key,
+    ) {
+        (None, None, None) => None,
+        _ => Some(Enricher::new(EnrichmentConfig {
+            virustotal_key: args.virustotal_key,
+            malwarebazaar_key: args.malwarebazaar_key,
+            urlhaus_key: args.urlhaus_key,
+            cache_dir: args.enrichment_cache.into(),
+            ..EnrichmentConfig::default()
+        })?),
+    };
+
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    let bus = match &args.bus {
+        Some(url) => {
+            info!("Connecting to event bus {}...", url);
+            let config = BusConfig::parse(url)?
+                .with_topic_prefix(&args.bus_topic_prefix)
+                .with_partitioning(args.bus_partitioning.parse()?);
+            Some(EventBus::connect(config).await?)
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "notify")]
+    let notifier = match &args.notify {
+        Some(path) => Some(Arc::new(Notifier::new(NotifyConfig::from_file(path)?)?)),
+        None => None,
+    };
+
+    #[cfg(feature = "opencti")]
+    let opencti = match &args.opencti {
+        Some(path) => Some(OpenCtiClient::new(OpenCtiConfig::from_file(path)?)?),
+        None => None,
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let worker = scheduler.clone();
+    tokio::spawn(async move {
+        if let Err(e) = worker.serve(tx).await {
+            warn!("Scheduler stopped: {}", e);
+        }
+    });
+    let store_results = results.clone();
+    let store_config = config.clone();
+    let children = scheduler.clone();
+    tokio::spawn(async move {
+        while let Some(mut done) = rx.recv().await {
+            if let Some(filter) = &store_config.event_filter {
+                filter.apply_log(&mut done.execution_log);
+            }
+            let job = &done.job;
+            if let Err(e) = index_execution(
+                &similarity,
+                &job.analysis_id,
+                &job.sample_path,
+                &done.execution_log,
+            ) {
+                warn!("Failed to index job {}: {}", job.id, e);
+            }
+            match resubmit_dropped(&children, &store_results, job, &done.execution_log).await {
+                Ok(submitted) if !submitted.is_empty() => {
+                    info!("Job {} spawned {} child jobs", job.id, submitted.len())
+                }
+                Ok(_) => (),
+                Err(e) => warn!("Failed to resubmit files dropped by job {}: {}", job.id, e),
+            }
+            if let Err(e) = store_results
+                .store_execution_log(&done.job.analysis_id, done.execution_log)
+                .await
+            {
+                warn!("Failed to record job {}: {}", done.job.id, e);
+                continue;
+            }
+            #[cfg(feature = "enrichment")]
+            if let Some(enricher) = &enricher {
+                if let Err(e) =
+                    enrich_report(&store_results, &store_config, enricher, &job.analysis_id).await
+                {
+                    warn!("Failed to enrich job {}: {}", job.id, e);
+                }
+            }
+            if let Err(e) = persist_report(&store_results, &store_config, &job.analysis_id).await {
+                warn!("Failed to persist report for job {}: {}", job.id, e);
+            }
+            #[cfg(any(feature = "kafka", feature = "nats"))]
+            if let Some(bus) = &bus {
+                if let Err(e) =
+                    publish_report(&store_results, &store_config, bus, &job.analysis_id).await
+                {
+                    warn!("Failed to publish results of job {}: {}", job.id, e);
+                }
+            }
+            #[cfg(feature = "notify")]
+            if let Some(notifier) = &notifier {
+                if let Err(e) =
+                    notify_report(&store_results, &store_config, notifier, &job.analysis_id).await
+                {
+                    warn!("Failed to notify about job {}: {}", job.id, e);
+                }
+            }
+            #[cfg(feature = "opencti")]
+            if let Some(opencti) = &opencti {
+                if let Err(e) =
+                    push_to_opencti(&store_results, &store_config, opencti, &job.analysis_id).await
+                {
+                    warn!("Failed to push job {} to OpenCTI: {}", job.id, e);
+                }
+            }
+        }
+    });
+
+    if let Some(path) = &args.retention {
+        let policy = RetentionPolicy::from_file(path)?;
+        let gc_results = results.clone();
+        let gc_config = config.clone();
+        let period = Duration::from_secs(args.gc_interval);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let reporter = |result: &AnalysisResult| gc_config.report(result);
+                let mut gc = GarbageCollector::new(&policy, &gc_results).with_reporter(&reporter);
+                if let Some(store) = &gc_config.store {
+                    gc = gc.with_store(&**store);
+                }
+                if let Err(e) = gc.run(Local::now()).await {
+                    warn!("Retention run failed: {}", e);
+                }
+            }
+        });
+    }
+
+    let addr: SocketAddr = args.listen.parse()?;
+    info!("Listening on {}...", addr);
+    let served = axum::Server::bind(&addr)
+        .serve(
+            router(scheduler, results, config).into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await;
+    #[cfg(feature = "otel")]
+    metrics::shutdown_tracing();
+    served?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let args = Args::parse();
+
+    match args.hypervisor {
+        HypervisorKind::Libvirt => serve(args, Libvirt::new()).await,
+        HypervisorKind::Virtualbox => serve(args, VirtualBox::new()).await,
+    }
+}
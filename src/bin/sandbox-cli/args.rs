@@ -0,0 +1,327 @@
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+
+#[derive(Parser, Debug)]
+#[command(name = "sandbox-cli")]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    Parse(ParseArgs),
+    Analyze(AnalyzeArgs),
+    Report(ReportArgs),
+    Submit(SubmitArgs),
+    Tag(TagArgs),
+    Purge(PurgeArgs),
+    Image(ImageArgs),
+    Search(SearchArgs),
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct LogArgs {
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+
+    #[arg(long, value_enum, default_value = "auto")]
+    pub input: LogFormat,
+
+    #[arg(long)]
+    pub workers: Option<usize>,
+
+    #[arg(long)]
+    pub progress: bool,
+
+    #[arg(long)]
+    pub quarantine: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ParseArgs {
+    #[command(flatten)]
+    pub logs: LogArgs,
+
+    #[arg(long)]
+    pub pretty: bool,
+
+    #[arg(long, conflicts_with = "pretty")]
+    pub stream: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct RuleArgs {
+    #[arg(long, default_value = "sigma")]
+    pub rules: String,
+
+    #[arg(long)]
+    pub signatures: Option<String>,
+
+    #[arg(long)]
+    pub weights: Option<String>,
+
+    #[arg(long)]
+    pub verdict_policy: Option<String>,
+
+    #[arg(long)]
+    pub hash: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct AnalyzeArgs {
+    #[command(flatten)]
+    pub logs: LogArgs,
+
+    #[command(flatten)]
+    pub rules: RuleArgs,
+
+    #[arg(long)]
+    pub yara: Option<String>,
+
+    #[arg(long, requires = "yara")]
+    pub sample: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ReportArgs {
+    #[command(flatten)]
+    pub logs: LogArgs,
+
+    #[command(flatten)]
+    pub rules: RuleArgs,
+
+    #[arg(long, value_enum, default_value = "json")]
+    pub format: ReportFormat,
+
+    #[arg(long)]
+    pub checkpoint: Option<String>,
+
+    #[arg(long, default_value_t = 100_000, requires = "checkpoint")]
+    pub checkpoint_every: u64,
+
+    #[arg(long, requires = "checkpoint")]
+    pub preliminary: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct SubmitArgs {
+    pub path: String,
+
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    pub url: String,
+
+    #[arg(long, required = true)]
+    pub api_key: String,
+
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    #[arg(long)]
+    pub tags: Option<String>,
+
+    #[arg(long)]
+    pub priority: Option<i32>,
+
+    #[arg(long)]
+    pub screenshot: bool,
+
+    #[arg(long, requires = "screenshot")]
+    pub screenshot_interval: Option<u64>,
+
+    #[arg(long)]
+    pub record_video: bool,
+
+    #[arg(long)]
+    pub simulate_user: bool,
+
+    #[arg(long)]
+    pub hardening: Option<String>,
+
+    #[arg(long)]
+    pub time_warp: Option<f64>,
+
+    #[arg(long, requires = "time_warp")]
+    pub skip_sleeps: bool,
+
+    #[arg(long, value_delimiter = ',')]
+    pub memory_dump: Vec<String>,
+
+    #[arg(long)]
+    pub dump_scope: Option<String>,
+
+    #[arg(long)]
+    pub reboot: bool,
+
+    #[arg(long)]
+    pub resubmit_dropped: bool,
+
+    #[arg(long)]
+    pub detonate_url: bool,
+
+    #[arg(long)]
+    pub detonate_attachments: bool,
+
+    #[arg(long)]
+    pub no_unpack: bool,
+
+    #[arg(long = "password")]
+    pub passwords: Vec<String>,
+
+    #[arg(long, value_delimiter = ',')]
+    pub profiles: Vec<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct TagArgs {
+    pub analysis_id: String,
+
+    #[arg(long)]
+    pub execution_id: Option<String>,
+
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    pub url: String,
+
+    #[arg(long, required = true)]
+    pub api_key: String,
+
+    #[arg(long = "add", value_delimiter = ',')]
+    pub add_tags: Vec<String>,
+
+    #[arg(long = "remove", value_delimiter = ',')]
+    pub remove_tags: Vec<String>,
+
+    #[arg(long)]
+    pub campaign: Option<String>,
+
+    #[arg(long)]
+    pub source: Option<String>,
+
+    #[arg(long)]
+    pub tlp: Option<String>,
+
+    #[arg(long)]
+    pub note: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct PurgeArgs {
+    pub policy: String,
+
+    #[cfg(feature = "sqlite")]
+    #[arg(long)]
+    pub results_db: Option<String>,
+
+    #[arg(long, default_value = "sigma")]
+    pub rules: String,
+
+    #[arg(long)]
+    pub signatures: Option<String>,
+
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct SearchArgs {
+    pub query: String,
+
+    #[cfg(feature = "sqlite")]
+    #[arg(long)]
+    pub results_db: Option<String>,
+
+    #[cfg(feature = "postgres")]
+    #[arg(long)]
+    pub postgres: Option<String>,
+
+    #[arg(long, default_value_t = 100)]
+    pub limit: usize,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ImageArgs {
+    #[command(subcommand)]
+    pub command: ImageCommand,
+
+    #[arg(long, value_enum, default_value = "libvirt")]
+    pub hypervisor: HypervisorKind,
+
+    #[arg(long, default_value = "images")]
+    pub images: String,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ImageMetadataArgs {
+    #[arg(long)]
+    pub machine: String,
+
+    #[arg(long)]
+    pub os_version: Option<String>,
+
+    #[arg(long = "tool")]
+    pub tooling: Vec<String>,
+
+    #[arg(long)]
+    pub sysmon_config: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImageCommand {
+    Build {
+        name: String,
+
+        #[command(flatten)]
+        metadata: ImageMetadataArgs,
+
+        #[arg(long, num_args = 1.., allow_hyphen_values = true)]
+        provision_command: Vec<String>,
+    },
+    Snapshot {
+        name: String,
+
+        #[command(flatten)]
+        metadata: ImageMetadataArgs,
+    },
+    Verify {
+        name: String,
+
+        #[arg(long)]
+        baselines: Option<String>,
+    },
+    Rollback {
+        name: String,
+
+        #[arg(long)]
+        version: Option<u32>,
+    },
+    List,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum HypervisorKind {
+    Libvirt,
+    Virtualbox,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Auto,
+    Xml,
+    Evtx,
+    Jsonl,
+    Syslog,
+    Auditd,
+    Vmi,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    Json,
+    Html,
+    Stix,
+    Misp,
+    Cef,
+    Leef,
+    Timesketch,
+    Graphml,
+    Cypher,
+}
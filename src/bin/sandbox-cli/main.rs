@@ -0,0 +1,627 @@
+mod args;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "sqlite")]
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use args::{
+    AnalyzeArgs, Args, Command, HypervisorKind, ImageArgs, ImageCommand, ImageMetadataArgs,
+    LogArgs, LogFormat, ParseArgs, PurgeArgs, ReportArgs, ReportFormat, RuleArgs, SearchArgs,
+    SubmitArgs, TagArgs,
+};
+use chrono::Local;
+use clap::Parser;
+use log::{info, warn};
+
+use malware_analysis_sandbox::analysis_result::{AnalysisResult, AnalysisResultManager};
+use malware_analysis_sandbox::analyzer::sigma::SigmaRule;
+use malware_analysis_sandbox::analyzer::signature::SignatureRegistry;
+use malware_analysis_sandbox::analyzer::yara_scan::YaraScanner;
+use malware_analysis_sandbox::auditd::AuditdReader;
+use malware_analysis_sandbox::baseline::BaselineStore;
+use malware_analysis_sandbox::checkpoint::Checkpointer;
+#[cfg(feature = "evtx")]
+use malware_analysis_sandbox::evtx::EvtxReader;
+use malware_analysis_sandbox::export::cef::report_to_cef;
+use malware_analysis_sandbox::export::graph::{report_to_cypher, report_to_graphml};
+use malware_analysis_sandbox::export::leef::report_to_leef;
+use malware_analysis_sandbox::export::timesketch::report_to_timesketch;
+use malware_analysis_sandbox::metadata::MetadataUpdate;
+use malware_analysis_sandbox::misp::MispEvent;
+use malware_analysis_sandbox::orchestrator::image::{ImageManager, ImageOptions, ImageStore};
+use malware_analysis_sandbox::orchestrator::libvirt::Libvirt;
+use malware_analysis_sandbox::orchestrator::virtualbox::VirtualBox;
+use malware_analysis_sandbox::orchestrator::Hypervisor;
+use malware_analysis_sandbox::pipeline::{Pipeline, PipelineOptions, Progress, RecordFormat};
+use malware_analysis_sandbox::quarantine::FileQuarantine;
+use malware_analysis_sandbox::report::SandboxReport;
+use malware_analysis_sandbox::scoring::ScoringOptions;
+use malware_analysis_sandbox::sink::{EventSink, NdjsonSink};
+#[cfg(feature = "postgres")]
+use malware_analysis_sandbox::storage::postgres::PostgresResultStore;
+use malware_analysis_sandbox::storage::retention::{GarbageCollector, RetentionPolicy};
+use malware_analysis_sandbox::storage::search::EventQuery;
+#[cfg(feature = "sqlite")]
+use malware_analysis_sandbox::storage::sqlite::SqliteResultStore;
+use malware_analysis_sandbox::storage::ResultStore;
+use malware_analysis_sandbox::syslog::SyslogReader;
+use malware_analysis_sandbox::sysmon_event::SysmonEvent;
+use malware_analysis_sandbox::verdict::VerdictPolicy;
+use malware_analysis_sandbox::vmi::VmiReader;
+
+fn detect_format(path: &str) -> LogFormat {
+    let file_name = Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    if file_name.starts_with("audit") {
+        return LogFormat::Auditd;
+    }
+    if file_name.starts_with("syslog") {
+        return LogFormat::Syslog;
+    }
+    if file_name.starts_with("vmi") {
+        return LogFormat::Vmi;
+    }
+    match Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("evtx") => LogFormat::Evtx,
+        Some("jsonl" | "ndjson" | "json") => LogFormat::Jsonl,
+        _ => LogFormat::Xml,
+    }
+}
+
+fn show_progress(progress: Arc<Progress>) {
+    thread::spawn(move || {
+        while !progress.is_done() {
+            thread::sleep(Duration::from_secs(1));
+            let snapshot = progress.snapshot();
+            eprintln!(
+                "read {} parsed {} filtered {} delivered {} failed {} in flight {} stalls {}",
+                snapshot.records_read,
+                snapshot.events_parsed,
+                snapshot.events_filtered,
+                snapshot.events_delivered,
+                snapshot.parse_failures,
+                snapshot.batches_in_flight,
+                snapshot.stalls
+            );
+        }
+    });
+}
+
+fn run_pipeline(
+    path: &str,
+    format: RecordFormat,
+    args: &LogArgs,
+    skip: u64,
+    sink: &mut dyn EventSink,
+) -> Result<()> {
+    let mut options = PipelineOptions::default();
+    if let Some(workers) = args.workers {
+        options.workers = workers;
+    }
+    let mut pipeline = Pipeline::new(options).with_skip(skip);
+    if let Some(quarantine) = &args.quarantine {
+        pipeline = pipeline.with_quarantine(FileQuarantine::new(quarantine));
+    }
+    if args.progress {
+        show_progress(pipeline.progress());
+    }
+    pipeline.run(BufReader::new(File::open(path)?), format, sink)?;
+    let failures = pipeline.parse_failures();
+    if !failures.is_empty() {
+        warn!(
+            "Quarantined {} unparsable records from {}",
+            failures.total, path
+        );
+    }
+    Ok(())
+}
+
+fn resolve_format(path: &str, format: LogFormat) -> LogFormat {
+    match format {
+        LogFormat::Auto => detect_format(path),
+        format => format,
+    }
+}
+
+fn read_log(path: &str, args: &LogArgs) -> Result<Vec<SysmonEvent>> {
+    let mut events = Vec::new();
+    match resolve_format(path, args.input) {
+        LogFormat::Xml => run_pipeline(path, RecordFormat::Xml, args, 0, &mut events)?,
+        LogFormat::Jsonl => run_pipeline(path, RecordFormat::Jsonl, args, 0, &mut events)?,
+        LogFormat::Syslog => {
+            events = SyslogReader::new(BufReader::new(File::open(path)?)).collect::<Result<_>>()?
+        }
+        LogFormat::Auditd => {
+            events = AuditdReader::new(BufReader::new(File::open(path)?)).collect::<Result<_>>()?
+        }
+        LogFormat::Vmi => {
+            events = VmiReader::new(BufReader::new(File::open(path)?)).collect::<Result<_>>()?
+        }
+        #[cfg(feature = "evtx")]
+        LogFormat::Evtx => events = EvtxReader::open(path)?.events().collect::<Result<_>>()?,
+        #[cfg(not(feature = "evtx"))]
+        LogFormat::Evtx => anyhow::bail!("EVTX input requires the evtx feature"),
+        LogFormat::Auto => unreachable!(),
+    }
+    Ok(events)
+}
+
+fn read_logs(args: &LogArgs) -> Result<Vec<SysmonEvent>> {
+    let mut events = Vec::new();
+    for path in &args.paths {
+        info!("Reading {}...", path);
+        events.extend(read_log(path, args).with_context(|| format!("Failed to read {}", path))?);
+    }
+    events.sort_by_key(|e| e.time_created);
+    Ok(events)
+}
+
+struct Rules {
+    sigma: Vec<SigmaRule>,
+    signatures: SignatureRegistry,
+    scoring: Option<ScoringOptions>,
+    verdict: VerdictPolicy,
+}
+
+impl Rules {
+    fn load(args: &RuleArgs) -> Result<Self> {
+        info!("Loading sigma rules...");
+        let sigma = SigmaRule::load_dir(&args.rules)?;
+
+        info!("Loading signatures...");
+        let mut signatures = SignatureRegistry::with_defaults();
+        if let Some(dir) = &args.signatures {
+            signatures.load_dir(dir)?;
+        }
+
+        let scoring = match &args.weights {
+            Some(weights) => Some(ScoringOptions::from_file(weights)?),
+            None => None,
+        };
+        let verdict = match &args.verdict_policy {
+            Some(path) => VerdictPolicy::from_file(path)?,
+            None => VerdictPolicy::default(),
+        };
+        Ok(Self {
+            sigma,
+            signatures,
+            scoring,
+            verdict,
+        })
+    }
+
+    fn apply(&self, report: &mut SandboxReport) -> Result<()> {
+        report.add_sigma_detections(&self.sigma);
+        report.add_signature_detections(&self.signatures);
+        if let Some(scoring) = &self.scoring {
+            report.add_score(scoring);
+        }
+        report.add_verdict(&self.verdict);
+        Ok(())
+    }
+}
+
+fn build_report(logs: &LogArgs, rules: &RuleArgs) -> Result<SandboxReport> {
+    let events = read_logs(logs)?;
+    let loaded = Rules::load(rules)?;
+
+    info!("Generating report...");
+    let mut report = SandboxReport::from_events(rules.hash.as_deref().unwrap_or_default(), events)?;
+    loaded.apply(&mut report)?;
+    Ok(report)
+}
+
+fn build_checkpointed_report(args: &ReportArgs, checkpoint: &str) -> Result<SandboxReport> {
+    let rules = Rules::load(&args.rules)?;
+    let analysis = |report: &mut SandboxReport| rules.apply(report);
+    let mut checkpointer =
+        Checkpointer::open(checkpoint, args.rules.hash.as_deref().unwrap_or_default())?
+            .every(args.checkpoint_every)
+            .with_analysis(&analysis);
+    if let Some(preliminary) = &args.preliminary {
+        checkpointer = checkpointer.on_report(move |report| {
+            std::fs::write(preliminary, report.to_json()?)?;
+            Ok(())
+        });
+    }
+
+    for path in &args.logs.paths {
+        if checkpointer.is_complete(path) {
+            info!("Skipping {}, already in the checkpoint", path);
+            continue;
+        }
+        let skip = checkpointer.begin(path);
+        if skip > 0 {
+            info!("Resuming {} after {} records...", path, skip);
+        } else {
+            info!("Reading {}...", path);
+        }
+        match resolve_format(path, args.logs.input) {
+            LogFormat::Xml => {
+                run_pipeline(path, RecordFormat::Xml, &args.logs, skip, &mut checkpointer)
+            }
+            LogFormat::Jsonl => run_pipeline(
+                path,
+                RecordFormat::Jsonl,
+                &args.logs,
+                skip,
+                &mut checkpointer,
+            ),
+            _ => read_log(path, &args.logs).and_then(|events| {
+                events
+                    .iter()
+                    .try_for_each(|event| checkpointer.write(event))
+            }),
+        }
+        .with_context(|| format!("Failed to read {}", path))?;
+        checkpointer.complete(path)?;
+    }
+
+    info!("Generating report...");
+    checkpointer.finish()
+}
+
+fn stream(args: &LogArgs) -> Result<()> {
+    let mut sink = NdjsonSink::new(std::io::stdout().lock());
+    for path in &args.paths {
+        info!("Streaming {}...", path);
+        let format = match resolve_format(path, args.input) {
+            LogFormat::Xml => RecordFormat::Xml,
+            LogFormat::Jsonl => RecordFormat::Jsonl,
+            format => anyhow::bail!("{:?} input cannot be streamed", format),
+        };
+        run_pipeline(path, format, args, 0, &mut sink)
+            .with_context(|| format!("Failed to read {}", path))?;
+    }
+    Ok(())
+}
+
+fn parse(args: &ParseArgs) -> Result<()> {
+    if args.stream {
+        return stream(&args.logs);
+    }
+    let events = read_logs(&args.logs)?;
+    if args.pretty {
+        println!("{}", serde_json::to_string_pretty(&events)?);
+    } else {
+        for event in &events {
+            println!("{}", serde_json::to_string(event)?);
+        }
+    }
+    Ok(())
+}
+
+fn analyze(args: &AnalyzeArgs) -> Result<()> {
+    let report = build_report(&args.logs, &args.rules)?;
+
+    println!("Events: {}", report.events.len());
+    println!("Score: {:.1}", report.score.score);
+    println!("Verdict: {}", report.verdict.verdict);
+    for step in &report.verdict.rationale {
+        println!("  {}: {} => {}", step.step, step.description, step.verdict);
+    }
+    for detection in &report.detections {
+        println!(
+            "[{}] {}: {} ({} events) {:?}",
+            detection.level.as_deref().unwrap_or("unknown"),
+            detection.source,
+            detection.name,
+            detection.events.len(),
+            detection.tags
+        );
+    }
+    if let Some(yara) = &args.yara {
+        let scanner = YaraScanner::new(yara, 5)?;
+        if let Some(sample) = &args.sample {
+            info!("Scanning sample with YARA...");
+            for m in scanner.scan_file(sample)? {
+                println!("[yara] {}:{} {:?}", m.namespace, m.rule, m.tags);
+            }
+        }
+        info!("Scanning captured scripts with YARA...");
+        for (name, matches) in scanner.scan_scripts(&report.captured_scripts) {
+            for m in matches {
+                println!("[yara] {} {}:{} {:?}", name, m.namespace, m.rule, m.tags);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn report(args: &ReportArgs) -> Result<()> {
+    let report = match &args.checkpoint {
+        Some(checkpoint) => build_checkpointed_report(args, checkpoint)?,
+        None => build_report(&args.logs, &args.rules)?,
+    };
+    match args.format {
+        ReportFormat::Json => println!("{}", report.to_json()?),
+        ReportFormat::Html => println!("{}", report.to_html()),
+        ReportFormat::Stix => println!("{}", report.to_stix()?),
+        ReportFormat::Misp => println!("{}", MispEvent::from_report(&report).to_json()?),
+        ReportFormat::Cef => report_to_cef(&report)
+            .iter()
+            .for_each(|line| println!("{}", line)),
+        ReportFormat::Leef => report_to_leef(&report)
+            .iter()
+            .for_each(|line| println!("{}", line)),
+        ReportFormat::Timesketch => report_to_timesketch(&report)?
+            .iter()
+            .for_each(|line| println!("{}", line)),
+        ReportFormat::Graphml => print!("{}", report_to_graphml(&report)),
+        ReportFormat::Cypher => report_to_cypher(&report)
+            .iter()
+            .for_each(|line| println!("{}", line)),
+    }
+    Ok(())
+}
+
+async fn submit(args: &SubmitArgs) -> Result<()> {
+    let (sample, mut query) = if args.detonate_url {
+        (Vec::new(), vec![("url", args.path.clone())])
+    } else {
+        let sample = tokio::fs::read(&args.path)
+            .await
+            .with_context(|| format!("Failed to read {}", args.path))?;
+        let file_name = Path::new(&args.path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("sample");
+        (sample, vec![("file_name", file_name.to_string())])
+    };
+    if let Some(timeout) = args.timeout {
+        query.push(("timeout", timeout.to_string()));
+    }
+    if let Some(tags) = &args.tags {
+        query.push(("tags", tags.clone()));
+    }
+    if let Some(priority) = args.priority {
+        query.push(("priority", priority.to_string()));
+    }
+    if args.screenshot {
+        query.push(("screenshot", "true".to_string()));
+    }
+    if let Some(interval) = args.screenshot_interval {
+        query.push(("screenshot_interval", interval.to_string()));
+    }
+    if args.record_video {
+        query.push(("record_video", "true".to_string()));
+    }
+    if args.simulate_user {
+        query.push(("simulate_user", "true".to_string()));
+    }
+    if let Some(hardening) = &args.hardening {
+        query.push(("hardening", hardening.clone()));
+    }
+    if let Some(factor) = args.time_warp {
+        query.push(("time_warp", factor.to_string()));
+    }
+    if args.skip_sleeps {
+        query.push(("skip_sleeps", "true".to_string()));
+    }
+    if !args.memory_dump.is_empty() {
+        query.push(("memory_dump", args.memory_dump.join(",")));
+    }
+    if let Some(scope) = &args.dump_scope {
+        query.push(("dump_scope", scope.clone()));
+    }
+    if args.reboot {
+        query.push(("reboot", "true".to_string()));
+    }
+    if args.resubmit_dropped {
+        query.push(("resubmit_dropped", "true".to_string()));
+    }
+    if args.detonate_attachments {
+        query.push(("detonate_attachments", "true".to_string()));
+    }
+    if args.no_unpack {
+        query.push(("unpack", "false".to_string()));
+    }
+    if !args.passwords.is_empty() {
+        query.push(("passwords", args.passwords.join(",")));
+    }
+    if !args.profiles.is_empty() {
+        query.push(("profiles", args.profiles.join(",")));
+    }
+
+    info!("Submitting {}...", args.path);
+    let response = reqwest::Client::new()
+        .post(format!("{}/samples", args.url.trim_end_matches('/')))
+        .header("x-api-key", &args.api_key)
+        .query(&query)
+        .body(sample)
+        .send()
+        .await?
+        .error_for_status()?;
+    let submitted: serde_json::Value = response.json().await?;
+    println!("{}", serde_json::to_string_pretty(&submitted)?);
+    Ok(())
+}
+
+async fn tag(args: &TagArgs) -> Result<()> {
+    let update = MetadataUpdate {
+        add_tags: args.add_tags.clone(),
+        remove_tags: args.remove_tags.clone(),
+        campaign: args.campaign.clone(),
+        source: args.source.clone(),
+        tlp: args.tlp.clone(),
+        note: args.note.clone(),
+    };
+    let url = match &args.execution_id {
+        Some(execution_id) => format!(
+            "{}/analyses/{}/runs/{}/metadata",
+            args.url.trim_end_matches('/'),
+            args.analysis_id,
+            execution_id
+        ),
+        None => format!(
+            "{}/analyses/{}/metadata",
+            args.url.trim_end_matches('/'),
+            args.analysis_id
+        ),
+    };
+    let response = reqwest::Client::new()
+        .patch(url)
+        .header("x-api-key", &args.api_key)
+        .json(&update)
+        .send()
+        .await?
+        .error_for_status()?;
+    let metadata: serde_json::Value = response.json().await?;
+    println!("{}", serde_json::to_string_pretty(&metadata)?);
+    Ok(())
+}
+
+async fn purge(args: &PurgeArgs) -> Result<()> {
+    let policy = RetentionPolicy::from_file(&args.policy)
+        .with_context(|| format!("Failed to read {}", args.policy))?;
+    let results = AnalysisResultManager::init().await?;
+
+    info!("Loading sigma rules...");
+    let sigma_rules = SigmaRule::load_dir(&args.rules)?;
+    let mut signatures = SignatureRegistry::with_defaults();
+    if let Some(dir) = &args.signatures {
+        signatures.load_dir(dir)?;
+    }
+    let reporter = |result: &AnalysisResult| -> Result<SandboxReport> {
+        let mut report = SandboxReport::from_analysis_result(result)?;
+        report.add_sigma_detections(&sigma_rules);
+        report.add_signature_detections(&signatures);
+        report.add_score(&ScoringOptions::default());
+        report.add_verdict(&VerdictPolicy::default());
+        Ok(report)
+    };
+
+    let gc = GarbageCollector::new(&policy, &results)
+        .with_reporter(&reporter)
+        .dry_run(args.dry_run);
+    #[cfg(feature = "sqlite")]
+    let store = match &args.results_db {
+        Some(path) => Some(Mutex::new(SqliteResultStore::open(path)?)),
+        None => None,
+    };
+    #[cfg(feature = "sqlite")]
+    let gc = match &store {
+        Some(store) => gc.with_store(store),
+        None => gc,
+    };
+
+    let summary = gc.run(Local::now()).await?;
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
+
+#[cfg_attr(
+    not(any(feature = "sqlite", feature = "postgres")),
+    allow(unused_variables)
+)]
+fn open_store(args: &SearchArgs) -> Result<Box<dyn ResultStore>> {
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = &args.results_db {
+        return Ok(Box::new(SqliteResultStore::open(path)?));
+    }
+    #[cfg(feature = "postgres")]
+    if let Some(params) = &args.postgres {
+        return Ok(Box::new(PostgresResultStore::connect(params)?));
+    }
+    bail!("Searching requires a result store (--results-db or --postgres)")
+}
+
+fn search(args: &SearchArgs) -> Result<()> {
+    let query = EventQuery::parse(&args.query)?;
+    let hits = open_store(args)?.search_events(&query, args.limit)?;
+    println!("{}", serde_json::to_string_pretty(&hits)?);
+    Ok(())
+}
+
+fn image_options(metadata: &ImageMetadataArgs, provision_command: &[String]) -> ImageOptions {
+    ImageOptions {
+        os_version: metadata.os_version.clone(),
+        tooling: metadata.tooling.clone(),
+        sysmon_config: metadata.sysmon_config.as_ref().map(PathBuf::from),
+        provision_command: provision_command.to_vec(),
+    }
+}
+
+async fn run_image<H: Hypervisor>(args: &ImageArgs, hypervisor: H, name: &str) -> Result<()> {
+    let store = ImageStore::open(&args.images)?;
+    let manager = ImageManager::new(&hypervisor, name, &store);
+    let output = match &args.command {
+        ImageCommand::Build {
+            name,
+            metadata,
+            provision_command,
+        } => serde_json::to_value(
+            manager
+                .build(
+                    name,
+                    &metadata.machine,
+                    &image_options(metadata, provision_command),
+                )
+                .await?,
+        )?,
+        ImageCommand::Snapshot { name, metadata } => serde_json::to_value(
+            manager
+                .snapshot(name, &metadata.machine, &image_options(metadata, &[]))
+                .await?,
+        )?,
+        ImageCommand::Verify { name, baselines } => {
+            let baselines = baselines.as_ref().map(BaselineStore::open).transpose()?;
+            let verification = manager.verify(name, baselines.as_ref()).await?;
+            if !verification.passed() {
+                println!("{}", serde_json::to_string_pretty(&verification)?);
+                bail!("Image {} failed verification", name);
+            }
+            serde_json::to_value(verification)?
+        }
+        ImageCommand::Rollback { name, version } => {
+            serde_json::to_value(manager.rollback(name, *version).await?)?
+        }
+        ImageCommand::List => {
+            let mut images = Vec::new();
+            for name in store.names()? {
+                images.extend(store.current(&name)?);
+            }
+            serde_json::to_value(images)?
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+async fn image(args: &ImageArgs) -> Result<()> {
+    match args.hypervisor {
+        HypervisorKind::Libvirt => run_image(args, Libvirt::new(), "libvirt").await,
+        HypervisorKind::Virtualbox => run_image(args, VirtualBox::new(), "virtualbox").await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let args = Args::parse();
+
+    match &args.command {
+        Command::Parse(args) => parse(args),
+        Command::Analyze(args) => analyze(args),
+        Command::Report(args) => report(args),
+        Command::Submit(args) => submit(args).await,
+        Command::Tag(args) => tag(args).await,
+        Command::Purge(args) => purge(args).await,
+        Command::Image(args) => image(args).await,
+        Command::Search(args) => search(args),
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::netsim::{Service, SimulatedRequest};
+use crate::pcap::http::{header, HttpExchange};
+use crate::process_tree::ProcessTree;
+
+pub const URL_FILE_NAME: &str = "url";
+
+const BROWSER_IMAGES: &[&str] = &[
+    "firefox", "chrome", "chromium", "msedge", "iexplore", "opera", "brave", "safari",
+];
+const BROWSER_HELPERS: &[&str] = &[
+    "werfault",
+    "crashpad_handler",
+    "pingsender",
+    "plugin-container",
+    "identity_helper",
+];
+const DOWNLOAD_TYPES: &[&str] = &[
+    "application/octet-stream",
+    "application/x-msdownload",
+    "application/x-dosexec",
+    "application/x-executable",
+    "application/hta",
+    "application/zip",
+    "application/x-rar-compressed",
+    "application/x-7z-compressed",
+    "application/vnd.ms-",
+    "application/vnd.openxmlformats",
+];
+const PLUGIN_TYPES: &[&str] = &[
+    "application/x-shockwave-flash",
+    "application/java-archive",
+    "application/x-java-applet",
+    "application/x-silverlight",
+];
+const MIN_REDIRECT_HOSTS: usize = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadKind {
+    Pe,
+    Elf,
+    Archive,
+    Pdf,
+    Office,
+    Flash,
+}
+
+impl PayloadKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pe => "pe",
+            Self::Elf => "elf",
+            Self::Archive => "archive",
+            Self::Pdf => "pdf",
+            Self::Office => "office",
+            Self::Flash => "flash",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Pe,
+            Self::Elf,
+            Self::Archive,
+            Self::Pdf,
+            Self::Office,
+            Self::Flash,
+        ]
+        .into_iter()
+        .find(|k| k.name() == name)
+    }
+
+    pub fn is_executable(&self) -> bool {
+        matches!(self, Self::Pe | Self::Elf)
+    }
+}
+
+pub fn payload_kind(data: &[u8]) -> Option<PayloadKind> {
+    if data.starts_with(b"MZ") {
+        Some(PayloadKind::Pe)
+    } else if data.starts_with(b"\x7fELF") {
+        Some(PayloadKind::Elf)
+    } else if data.starts_with(b"PK\x03\x04")
+        || data.starts_with(b"Rar!")
+        || data.starts_with(b"7z\xbc\xaf")
+    {
+        Some(PayloadKind::Archive)
+    } else if data.starts_with(b"%PDF") {
+        Some(PayloadKind::Pdf)
+    } else if data.starts_with(b"\xd0\xcf\x11\xe0") {
+        Some(PayloadKind::Office)
+    } else if [b"FWS", b"CWS", b"ZWS"]
+        .iter()
+        .any(|m| data.starts_with(*m))
+    {
+        Some(PayloadKind::Flash)
+    } else {
+        None
+    }
+}
+
+pub fn is_detonatable_url(url: &str) -> bool {
+    let lower = url.to_lowercase();
+    (lower.starts_with("http://") || lower.starts_with("https://"))
+        && host(url).is_some_and(|h| !h.is_empty())
+}
+
+fn host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit('@').next()?;
+    Some(match authority.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => authority,
+    })
+}
+
+fn resolve(base: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let Some((scheme, rest)) = base.split_once("://") else {
+        return location.to_string();
+    };
+    if let Some(location) = location.strip_prefix("//") {
+        return format!("{}://{}", scheme, location);
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if location.starts_with('/') {
+        return format!("{}://{}{}", scheme, authority, location);
+    }
+    let path = rest[authority.len()..]
+        .split(['?', '#'])
+        .next()
+        .unwrap_or("/");
+    let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    format!("{}://{}{}/{}", scheme, authority, dir, location)
+}
+
+fn is_browser(image: &str) -> bool {
+    let name = image
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(image)
+        .to_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    BROWSER_IMAGES.iter().any(|b| name.starts_with(b))
+}
+
+fn is_browser_helper(image: &str) -> bool {
+    let image = image.to_lowercase();
+    is_browser(&image) || BROWSER_HELPERS.iter().any(|h| image.contains(h))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Hop {
+    pub time: DateTime<Utc>,
+    pub method: String,
+    pub url: String,
+    pub status: Option<u16>,
+    pub location: Option<String>,
+    pub content_type: Option<String>,
+    pub content_disposition: Option<String>,
+    pub payload: Option<PayloadKind>,
+    pub sha256: Option<String>,
+}
+
+impl Hop {
+    fn from_simulated(request: &SimulatedRequest) -> Option<Self> {
+        let scheme = match request.service {
+            Service::Http => "http",
+            Service::Https => "https",
+            _ => return None,
+        };
+        let mut parts = request.summary.split_whitespace();
+        let method = parts.next()?.to_string();
+        let target = parts.next()?;
+        let details = &request.details;
+        let url = match (target.contains("://"), details.get("host")) {
+            (true, _) => target.to_string(),
+            (false, Some(host)) => format!("{}://{}{}", scheme, host, target),
+            (false, None) => return None,
+        };
+        Some(Self {
+            time: request.time,
+            method,
+            url,
+            status: details.get("response_status").and_then(|s| s.parse().ok()),
+            location: details.get("location").cloned(),
+            content_type: details.get("response_content_type").cloned(),
+            content_disposition: details.get("content_disposition").cloned(),
+            payload: details
+                .get("payload")
+                .and_then(|p| PayloadKind::from_name(p)),
+            sha256: details.get("payload_sha256").cloned(),
+        })
+    }
+
+    fn from_exchange(exchange: &HttpExchange) -> Self {
+        let response = exchange.response.as_ref();
+        let response_header = |name: &str| {
+            response
+                .and_then(|r| header(&r.headers, name))
+                .map(str::to_string)
+        };
+        Self {
+            time: exchange.time,
+            method: exchange.request.method.clone(),
+            url: exchange.url(),
+            status: response.map(|r| r.status),
+            location: response_header("location"),
+            content_type: response_header("content-type"),
+            content_disposition: response_header("content-disposition"),
+            payload: None,
+            sha256: None,
+        }
+    }
+
+    fn is_redirect(&self) -> bool {
+        self.status.is_some_and(|s| (300..400).contains(&s)) && self.location.is_some()
+    }
+
+    fn is_download(&self) -> bool {
+        let content_type = self.content_type.as_deref().unwrap_or_default();
+        self.payload.is_some()
+            || self
+                .content_disposition
+                .as_deref()
+                .is_some_and(|d| d.to_lowercase().contains("attachment"))
+            || DOWNLOAD_TYPES.iter().any(|t| content_type.starts_with(t))
+    }
+
+    fn file_name(&self) -> Option<String> {
+        let from_disposition = self.content_disposition.as_deref().and_then(|d| {
+            let (_, name) = d.split_once("filename=")?;
+            Some(name.trim_matches(['"', '\'', ';', ' ']).to_string())
+        });
+        from_disposition.or_else(|| {
+            let path = self.url.split(['?', '#']).next()?;
+            let name = path.split_once("://")?.1.rsplit_once('/')?.1;
+            (!name.is_empty()).then(|| name.to_string())
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+    pub status: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Download {
+    pub time: DateTime<Utc>,
+    pub url: String,
+    pub file_name: Option<String>,
+    pub content_type: Option<String>,
+    pub payload: Option<PayloadKind>,
+    pub sha256: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BrowserChild {
+    pub browser: String,
+    pub guid: String,
+    pub image: String,
+    pub command_line: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BrowsingChain {
+    pub url: String,
+    pub hops: Vec<Hop>,
+    pub redirects: Vec<Redirect>,
+    pub downloads: Vec<Download>,
+    pub browser_children: Vec<BrowserChild>,
+    pub indicators: Vec<String>,
+}
+
+impl BrowsingChain {
+    pub fn analyze<'a>(
+        url: &str,
+        requests: impl IntoIterator<Item = &'a SimulatedRequest>,
+        http: &[HttpExchange],
+        tree: &ProcessTree,
+    ) -> Self {
+        let mut hops: Vec<Hop> = requests
+            .into_iter()
+            .filter_map(Hop::from_simulated)
+            .collect();
+        for exchange in http {
+            let hop = Hop::from_exchange(exchange);
+            if hops
+                .iter()
+                .all(|h| h.url != hop.url || h.method != hop.method)
+            {
+                hops.push(hop);
+            }
+        }
+        hops.sort_by_key(|h| h.time);
+
+        let redirects: Vec<Redirect> = hops
+            .iter()
+            .filter(|h| h.is_redirect())
+            .filter_map(|h| {
+                Some(Redirect {
+                    from: h.url.clone(),
+                    to: resolve(&h.url, h.location.as_deref()?),
+                    status: h.status?,
+                })
+            })
+            .collect();
+        let downloads: Vec<Download> = hops
+            .iter()
+            .filter(|h| !h.is_redirect() && h.is_download())
+            .map(|h| Download {
+                time: h.time,
+                url: h.url.clone(),
+                file_name: h.file_name(),
+                content_type: h.content_type.clone(),
+                payload: h.payload,
+                sha256: h.sha256.clone(),
+            })
+            .collect();
+
+        let mut browser_children = Vec::new();
+        for browser in tree.processes().filter(|p| is_browser(&p.image)) {
+            for child in tree.children(&browser.guid) {
+                if !is_browser_helper(&child.image) {
+                    browser_children.push(BrowserChild {
+                        browser: browser.image.clone(),
+                        guid: child.guid.clone(),
+                        image: child.image.clone(),
+                        command_line: child.command_line.clone(),
+                    });
+                }
+            }
+        }
+
+        let mut chain = Self {
+            url: url.to_string(),
+            hops,
+            redirects,
+            downloads,
+            browser_children,
+            indicators: Vec::new(),
+        };
+        chain.indicators = chain.exploit_kit_indicators();
+        chain
+    }
+
+    pub fn redirect_chain(&self) -> Vec<&str> {
+        let mut chain = vec![self.url.as_str()];
+        let mut current = self.url.as_str();
+        while let Some(redirect) = self
+            .redirects
+            .iter()
+            .find(|r| r.from == current && !chain.contains(&r.to.as_str()))
+        {
+            current = redirect.to.as_str();
+            chain.push(current);
+        }
+        chain
+    }
+
+    fn exploit_kit_indicators(&self) -> Vec<String> {
+        let mut indicators = Vec::new();
+        let hosts: HashSet<&str> = self
+            .redirects
+            .iter()
+            .flat_map(|r| [host(&r.from), host(&r.to)])
+            .flatten()
+            .collect();
+        if hosts.len() >= MIN_REDIRECT_HOSTS {
+            indicators.push(format!("Redirect chain across {} hosts", hosts.len()));
+        }
+        for hop in &self.hops {
+            let content_type = hop.content_type.as_deref().unwrap_or_default();
+            if hop.payload == Some(PayloadKind::Flash)
+                || PLUGIN_TYPES.iter().any(|t| content_type.starts_with(t))
+            {
+                indicators.push(format!("Browser plugin content served by {}", hop.url));
+            }
+        }
+        for download in &self.downloads {
+            if download.payload.is_some_and(|p| p.is_executable()) {
+                indicators.push(format!("Executable downloaded from {}", download.url));
+            }
+        }
+        for child in &self.browser_children {
+            indicators.push(format!("Browser spawned {}", child.image));
+        }
+        indicators.dedup();
+        indicators
+    }
+}
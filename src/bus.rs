@@ -0,0 +1,227 @@
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+mod nats;
+
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Error, Result};
+use serde::Serialize;
+
+use crate::report::{Detection, SandboxReport};
+use crate::sysmon_event::SysmonEvent;
+
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaPublisher;
+#[cfg(feature = "nats")]
+pub use nats::NatsPublisher;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusKind {
+    Kafka,
+    Nats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Partitioning {
+    #[default]
+    RunId,
+    AnalysisId,
+    None,
+}
+
+impl FromStr for Partitioning {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "run" | "run-id" => Ok(Self::RunId),
+            "analysis" | "analysis-id" => Ok(Self::AnalysisId),
+            "none" => Ok(Self::None),
+            _ => bail!("Unsupported partitioning: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BusConfig {
+    pub kind: BusKind,
+    pub servers: Vec<String>,
+    pub events_topic: String,
+    pub detections_topic: String,
+    pub partitioning: Partitioning,
+}
+
+impl BusConfig {
+    pub fn new(kind: BusKind, servers: &[&str]) -> Self {
+        Self {
+            kind,
+            servers: servers.iter().map(|s| s.to_string()).collect(),
+            events_topic: "sandbox.events".to_string(),
+            detections_topic: "sandbox.detections".to_string(),
+            partitioning: Partitioning::default(),
+        }
+    }
+
+    pub fn parse(url: &str) -> Result<Self> {
+        let (scheme, servers) = url
+            .split_once("://")
+            .context("Event bus URL has no scheme")?;
+        let kind = match scheme.to_lowercase().as_str() {
+            "kafka" => BusKind::Kafka,
+            "nats" => BusKind::Nats,
+            _ => bail!("Unsupported event bus: {}", scheme),
+        };
+        let servers: Vec<&str> = servers
+            .trim_end_matches('/')
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .collect();
+        if servers.is_empty() {
+            bail!("Event bus URL has no servers");
+        }
+        Ok(Self::new(kind, &servers))
+    }
+
+    pub fn with_topic_prefix(mut self, prefix: &str) -> Self {
+        self.events_topic = format!("{}.events", prefix);
+        self.detections_topic = format!("{}.detections", prefix);
+        self
+    }
+
+    pub fn with_partitioning(mut self, partitioning: Partitioning) -> Self {
+        self.partitioning = partitioning;
+        self
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum BusPayload<'a> {
+    Event(&'a SysmonEvent),
+    Detection(&'a Detection),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BusMessage<'a> {
+    pub analysis_id: &'a str,
+    pub run_id: &'a str,
+    pub sample_hash: &'a str,
+    #[serde(flatten)]
+    pub payload: BusPayload<'a>,
+}
+
+impl<'a> BusMessage<'a> {
+    pub fn key(&self, partitioning: Partitioning) -> Option<&'a str> {
+        match partitioning {
+            Partitioning::RunId => Some(self.run_id),
+            Partitioning::AnalysisId => Some(self.analysis_id),
+            Partitioning::None => None,
+        }
+    }
+}
+
+pub struct Record {
+    pub key: Option<String>,
+    pub value: Vec<u8>,
+}
+
+fn message<'a>(report: &'a SandboxReport, payload: BusPayload<'a>) -> BusMessage<'a> {
+    BusMessage {
+        analysis_id: &report.id,
+        run_id: &report.execution_id,
+        sample_hash: &report.hash,
+        payload,
+    }
+}
+
+pub fn event_messages(report: &SandboxReport) -> Vec<BusMessage<'_>> {
+    report
+        .events
+        .iter()
+        .map(|e| message(report, BusPayload::Event(e)))
+        .collect()
+}
+
+pub fn detection_messages(report: &SandboxReport) -> Vec<BusMessage<'_>> {
+    report
+        .detections
+        .iter()
+        .map(|d| message(report, BusPayload::Detection(d)))
+        .collect()
+}
+
+pub fn records(messages: &[BusMessage], partitioning: Partitioning) -> Result<Vec<Record>> {
+    messages
+        .iter()
+        .map(|m| {
+            Ok(Record {
+                key: m.key(partitioning).map(|k| k.to_string()),
+                value: serde_json::to_vec(m)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(any(feature = "kafka", feature = "nats"))]
+enum Publisher {
+    #[cfg(feature = "kafka")]
+    Kafka(KafkaPublisher),
+    #[cfg(feature = "nats")]
+    Nats(NatsPublisher),
+}
+
+#[cfg(any(feature = "kafka", feature = "nats"))]
+pub struct EventBus {
+    config: BusConfig,
+    publisher: Publisher,
+}
+
+#[cfg(any(feature = "kafka", feature = "nats"))]
+impl EventBus {
+    pub async fn connect(config: BusConfig) -> Result<Self> {
+        let publisher = match config.kind {
+            #[cfg(feature = "kafka")]
+            BusKind::Kafka => Publisher::Kafka(KafkaPublisher::connect(&config.servers).await?),
+            #[cfg(feature = "nats")]
+            BusKind::Nats => Publisher::Nats(NatsPublisher::connect(&config.servers).await?),
+            #[allow(unreachable_patterns)]
+            kind => bail!("{:?} support is not compiled in", kind),
+        };
+        Ok(Self { config, publisher })
+    }
+
+    pub fn config(&self) -> &BusConfig {
+        &self.config
+    }
+
+    async fn publish(&self, topic: &str, records: Vec<Record>) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        match &self.publisher {
+            #[cfg(feature = "kafka")]
+            Publisher::Kafka(publisher) => publisher.publish(topic, records).await,
+            #[cfg(feature = "nats")]
+            Publisher::Nats(publisher) => publisher.publish(topic, records).await,
+        }
+    }
+
+    pub async fn publish_events(&self, report: &SandboxReport) -> Result<usize> {
+        let records = records(&event_messages(report), self.config.partitioning)?;
+        let count = records.len();
+        self.publish(&self.config.events_topic, records).await?;
+        Ok(count)
+    }
+
+    pub async fn publish_detections(&self, report: &SandboxReport) -> Result<usize> {
+        let records = records(&detection_messages(report), self.config.partitioning)?;
+        let count = records.len();
+        self.publish(&self.config.detections_topic, records).await?;
+        Ok(count)
+    }
+
+    pub async fn publish_report(&self, report: &SandboxReport) -> Result<usize> {
+        Ok(self.publish_events(report).await? + self.publish_detections(report).await?)
+    }
+}
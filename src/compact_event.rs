@@ -0,0 +1,497 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, FixedOffset};
+
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const KNOWN_FIELDS: &[&str] = &[
+    "RuleName",
+    "UtcTime",
+    "ProcessGuid",
+    "ProcessId",
+    "Image",
+    "FileVersion",
+    "Description",
+    "Product",
+    "Company",
+    "OriginalFileName",
+    "CommandLine",
+    "CurrentDirectory",
+    "User",
+    "LogonGuid",
+    "LogonId",
+    "TerminalSessionId",
+    "IntegrityLevel",
+    "Hashes",
+    "ParentProcessGuid",
+    "ParentProcessId",
+    "ParentImage",
+    "ParentCommandLine",
+    "ParentUser",
+    "TargetFilename",
+    "CreationUtcTime",
+    "PreviousCreationUtcTime",
+    "Protocol",
+    "Initiated",
+    "SourceIsIpv6",
+    "SourceIp",
+    "SourceHostname",
+    "SourcePort",
+    "SourcePortName",
+    "DestinationIsIpv6",
+    "DestinationIp",
+    "DestinationHostname",
+    "DestinationPort",
+    "DestinationPortName",
+    "ImageLoaded",
+    "Signed",
+    "Signature",
+    "SignatureStatus",
+    "SourceProcessGuid",
+    "SourceProcessId",
+    "SourceImage",
+    "SourceThreadId",
+    "SourceUser",
+    "TargetProcessGuid",
+    "TargetProcessId",
+    "TargetImage",
+    "TargetUser",
+    "NewThreadId",
+    "StartAddress",
+    "StartModule",
+    "StartFunction",
+    "Device",
+    "GrantedAccess",
+    "CallTrace",
+    "EventType",
+    "TargetObject",
+    "Details",
+    "NewName",
+    "Hash",
+    "Contents",
+    "PipeName",
+    "Operation",
+    "EventNamespace",
+    "Name",
+    "Query",
+    "Type",
+    "Destination",
+    "Consumer",
+    "Filter",
+    "QueryName",
+    "QueryStatus",
+    "QueryResults",
+    "IsExecutable",
+    "Archived",
+    "Session",
+    "ClientInfo",
+    "State",
+    "Version",
+    "SchemaVersion",
+];
+
+fn known_fields() -> &'static HashMap<&'static str, u16> {
+    static KNOWN: OnceLock<HashMap<&'static str, u16>> = OnceLock::new();
+    KNOWN.get_or_init(|| {
+        (0..)
+            .zip(KNOWN_FIELDS.iter().copied())
+            .map(|(i, n)| (n, i))
+            .collect()
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FieldName {
+    Known(u16),
+    Other(Box<str>),
+}
+
+impl FieldName {
+    pub fn new(name: &str) -> Self {
+        Self::known(name).unwrap_or_else(|| Self::Other(name.into()))
+    }
+
+    pub fn known(name: &str) -> Option<Self> {
+        known_fields().get(name).copied().map(Self::Known)
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Known(id) => KNOWN_FIELDS.get(*id as usize).copied().unwrap_or_default(),
+            Self::Other(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompactEvent {
+    pub event_id: SysmonEventId,
+    pub time_created: DateTime<FixedOffset>,
+    pub computer: Option<Box<str>>,
+    pub record_id: Option<u64>,
+    pub channel: Option<Box<str>>,
+    fields: Box<[(FieldName, Box<str>)]>,
+}
+
+impl CompactEvent {
+    fn new<'a, I>(
+        event_id: SysmonEventId,
+        time_created: DateTime<FixedOffset>,
+        computer: Option<&str>,
+        record_id: Option<u64>,
+        channel: Option<&str>,
+        fields: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut fields: Vec<(FieldName, Box<str>)> = fields
+            .into_iter()
+            .map(|(name, value)| (FieldName::new(name), value.into()))
+            .collect();
+        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+        fields.dedup_by(|(a, _), (b, _)| a == b);
+        Self {
+            event_id,
+            time_created,
+            computer: computer.map(Into::into),
+            record_id,
+            channel: channel.map(Into::into),
+            fields: fields.into_boxed_slice(),
+        }
+    }
+
+    pub fn from_event(event: &SysmonEvent) -> Self {
+        Self::new(
+            event.event_id,
+            event.time_created,
+            event.computer.as_deref(),
+            event.record_id,
+            event.channel.as_deref(),
+            event
+                .event_data
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str())),
+        )
+    }
+
+    pub fn from_ref(event: &SysmonEventRef) -> Self {
+        Self::new(
+            event.event_id,
+            event.time_created,
+            event.computer,
+            event.record_id,
+            event.channel,
+            event.event_data.iter().map(|(k, v)| (*k, v.as_ref())),
+        )
+    }
+
+    pub fn get_field(&self, field: &FieldName) -> Option<&str> {
+        self.fields
+            .binary_search_by(|(name, _)| name.cmp(field))
+            .ok()
+            .map(|i| self.fields[i].1.as_ref())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        if let Some(field) = FieldName::known(name) {
+            return self.get_field(&field);
+        }
+        self.fields
+            .binary_search_by(|(field, _)| match field {
+                FieldName::Known(_) => Ordering::Less,
+                FieldName::Other(other) => other.as_ref().cmp(name),
+            })
+            .ok()
+            .map(|i| self.fields[i].1.as_ref())
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_ref()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn to_event(&self) -> SysmonEvent {
+        SysmonEvent {
+            event_id: self.event_id,
+            time_created: self.time_created,
+            computer: self.computer.as_deref().map(String::from),
+            record_id: self.record_id,
+            channel: self.channel.as_deref().map(String::from),
+            event_data: self
+                .fields()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SysmonEventRef<'a> {
+    pub event_id: SysmonEventId,
+    pub time_created: DateTime<FixedOffset>,
+    pub computer: Option<&'a str>,
+    pub record_id: Option<u64>,
+    pub channel: Option<&'a str>,
+    pub event_data: Vec<(&'a str, Cow<'a, str>)>,
+}
+
+fn element_text<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}", name);
+    let start = xml.find(&open)?;
+    let rest = &xml[start + open.len()..];
+    let body_start = rest.find('>')?;
+    if rest[..body_start].ends_with('/') {
+        return Some("");
+    }
+    let body = &rest[body_start + 1..];
+    let end = body.find('<')?;
+    Some(&body[..end])
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    loop {
+        let i = rest.find(name)?;
+        let before = rest[..i].chars().last();
+        let after = rest[i + name.len()..].trim_start();
+        rest = &rest[i + name.len()..];
+        if !matches!(before, Some(c) if c.is_whitespace()) {
+            continue;
+        }
+        let Some(after) = after.strip_prefix('=') else {
+            continue;
+        };
+        let after = after.trim_start();
+        let quote = after.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let value = &after[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+}
+
+fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+impl<'a> SysmonEventRef<'a> {
+    pub fn parse(xml: &'a str) -> Result<Self> {
+        let system = match (xml.find("<System>"), xml.find("</System>")) {
+            (Some(start), Some(end)) if start < end => &xml[start..end],
+            _ => return Err(anyhow!("No System node")),
+        };
+        let event_id = element_text(system, "EventID")
+            .context("No EventID")?
+            .trim()
+            .parse::<SysmonEventId>()
+            .map_err(|_| anyhow!("Invalid EventID"))?;
+        let time_tag = system
+            .find("<TimeCreated")
+            .map(|i| &system[i..])
+            .and_then(|t| t.find('>').map(|end| &t[..end]))
+            .context("No TimeCreated")?;
+        let time_created = DateTime::parse_from_rfc3339(
+            attribute(time_tag, "SystemTime").context("TimeCreated has no SystemTime attribute")?,
+        )?;
+
+        let mut event_data = Vec::new();
+        if let Some(start) = xml.find("<EventData") {
+            let mut rest = &xml[start..];
+            while let Some(i) = rest.find("<Data") {
+                rest = &rest[i + "<Data".len()..];
+                let tag_end = rest.find('>').context("Unterminated Data element")?;
+                let tag = &rest[..tag_end];
+                let Some(name) = attribute(tag, "Name") else {
+                    continue;
+                };
+                rest = &rest[tag_end + 1..];
+                if tag.ends_with('/') {
+                    event_data.push((name, Cow::Borrowed("")));
+                    continue;
+                }
+                let end = rest.find("</Data>").context("Unterminated Data element")?;
+                event_data.push((name, unescape(&rest[..end])));
+                rest = &rest[end + "</Data>".len()..];
+            }
+        }
+
+        Ok(Self {
+            event_id,
+            time_created,
+            computer: element_text(system, "Computer"),
+            record_id: element_text(system, "EventRecordID").and_then(|t| t.trim().parse().ok()),
+            channel: element_text(system, "Channel"),
+            event_data,
+        })
+    }
+
+    pub fn parse_many(xml: &'a str) -> impl Iterator<Item = Result<SysmonEventRef<'a>>> + 'a {
+        let mut rest = xml;
+        std::iter::from_fn(move || loop {
+            let start = rest.find("<Event")?;
+            let after = rest[start + "<Event".len()..].chars().next()?;
+            if !matches!(after, ' ' | '>' | '\t' | '\r' | '\n') {
+                rest = &rest[start + 1..];
+                continue;
+            }
+            let Some(end) = rest[start..].find("</Event>") else {
+                rest = "";
+                return Some(Err(anyhow!("Unterminated Event element")));
+            };
+            let end = start + end + "</Event>".len();
+            let element = &rest[start..end];
+            rest = &rest[end..];
+            return Some(SysmonEventRef::parse(element));
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.event_data
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.as_ref())
+    }
+
+    pub fn to_event(&self) -> SysmonEvent {
+        SysmonEvent {
+            event_id: self.event_id,
+            time_created: self.time_created,
+            computer: self.computer.map(String::from),
+            record_id: self.record_id,
+            channel: self.channel.map(String::from),
+            event_data: self
+                .event_data
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl From<&SysmonEvent> for CompactEvent {
+    fn from(event: &SysmonEvent) -> Self {
+        Self::from_event(event)
+    }
+}
+
+impl From<&CompactEvent> for SysmonEvent {
+    fn from(event: &CompactEvent) -> Self {
+        event.to_event()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(fields: &[(&str, &str)]) -> SysmonEvent {
+        SysmonEvent {
+            event_id: SysmonEventId::PROCESS_CREATE,
+            time_created: DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap(),
+            computer: Some("WIN10".to_string()),
+            record_id: Some(7),
+            channel: None,
+            event_data: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn known_and_unknown_fields_round_trip() {
+        let original = event(&[
+            ("Image", "cmd.exe"),
+            ("CustomField", "a"),
+            ("AnotherField", "b"),
+            ("ProcessId", "42"),
+        ]);
+        let compact = CompactEvent::from_event(&original);
+        assert_eq!(compact.len(), 4);
+        assert_eq!(compact.get("Image"), Some("cmd.exe"));
+        assert_eq!(compact.get("CustomField"), Some("a"));
+        assert_eq!(compact.get("AnotherField"), Some("b"));
+        assert_eq!(compact.get("Missing"), None);
+        assert_eq!(compact.get("CommandLine"), None);
+        assert_eq!(compact.get_field(&FieldName::new("ProcessId")), Some("42"));
+        assert_eq!(compact.to_event().event_data, original.event_data);
+    }
+
+    #[test]
+    fn unknown_fields_are_not_interned_globally() {
+        assert!(matches!(FieldName::new("Image"), FieldName::Known(_)));
+        assert_eq!(FieldName::new("Image").as_str(), "Image");
+        for i in 0..=u16::MAX as usize + 1 {
+            let name = format!("Field{}", i);
+            let field = FieldName::new(&name);
+            assert_eq!(field, FieldName::Other(name.as_str().into()));
+            assert_eq!(field.as_str(), name);
+        }
+        let compact = CompactEvent::from_event(&event(&[("Field70000", "x")]));
+        assert_eq!(compact.get("Field70000"), Some("x"));
+    }
+
+    #[test]
+    fn borrowed_events_convert_to_compact() {
+        let xml = "<Event><System><EventID>1</EventID>\
+                   <TimeCreated SystemTime='2024-01-01T00:00:00Z'/>\
+                   <EventRecordID>3</EventRecordID><Computer>WIN10</Computer></System>\
+                   <EventData><Data Name='Image'>a &amp; b</Data><Data Name='Extra'/></EventData>\
+                   </Event>";
+        let parsed = SysmonEventRef::parse(xml).unwrap();
+        assert_eq!(parsed.get("Image"), Some("a & b"));
+        assert_eq!(parsed.record_id, Some(3));
+        let compact = CompactEvent::from_ref(&parsed);
+        assert_eq!(compact.get("Extra"), Some(""));
+        assert_eq!(compact.computer.as_deref(), Some("WIN10"));
+    }
+}
@@ -0,0 +1,164 @@
+mod cobalt_strike;
+mod generic;
+
+pub use cobalt_strike::CobaltStrikeExtractor;
+pub use generic::GenericCarver;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::ioc::Ioc;
+use crate::metrics::{self, ANALYZER_DURATION};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputKind {
+    Sample,
+    MemoryDump,
+    Payload,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractionInput<'a> {
+    pub name: &'a str,
+    pub kind: InputKind,
+    pub data: &'a [u8],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MalwareConfig {
+    pub family: String,
+    pub source: String,
+    pub kind: InputKind,
+    pub c2: Vec<String>,
+    pub urls: Vec<String>,
+    pub values: BTreeMap<String, String>,
+}
+
+impl MalwareConfig {
+    pub fn new(family: &str, input: &ExtractionInput) -> Self {
+        Self {
+            family: family.to_string(),
+            source: input.name.to_string(),
+            kind: input.kind,
+            c2: Vec::new(),
+            urls: Vec::new(),
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn iocs(&self) -> Vec<Ioc> {
+        let mut iocs: Vec<Ioc> = self.c2.iter().map(|c2| host_ioc(c2)).collect();
+        for url in &self.urls {
+            iocs.push(Ioc::Url(url.clone()));
+            iocs.extend(url_host(url).map(host_ioc));
+        }
+        iocs.dedup();
+        iocs
+    }
+
+    fn same_as(&self, other: &Self) -> bool {
+        self.family == other.family && self.c2 == other.c2 && self.urls == other.urls
+    }
+}
+
+pub fn url_host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+fn host_ioc(destination: &str) -> Ioc {
+    let host = match destination.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => host,
+        _ => destination,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => Ioc::Ip(ip),
+        Err(_) => Ioc::Domain(host.to_lowercase()),
+    }
+}
+
+pub trait ConfigExtractor: Send + Sync {
+    fn family(&self) -> &str;
+
+    fn extract(&self, input: &ExtractionInput) -> Result<Vec<MalwareConfig>>;
+}
+
+#[derive(Default)]
+pub struct ConfigExtractorRegistry {
+    extractors: Vec<Box<dyn ConfigExtractor>>,
+}
+
+impl fmt::Debug for ConfigExtractorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.extractors.iter().map(|e| e.family()))
+            .finish()
+    }
+}
+
+impl ConfigExtractorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(CobaltStrikeExtractor);
+        registry.register(GenericCarver);
+        registry
+    }
+
+    pub fn register<T: ConfigExtractor + 'static>(&mut self, extractor: T) {
+        self.extractors.push(Box::new(extractor));
+    }
+
+    pub fn len(&self) -> usize {
+        self.extractors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.extractors.is_empty()
+    }
+
+    pub fn run(&self, inputs: &[ExtractionInput]) -> Vec<MalwareConfig> {
+        let mut configs: Vec<MalwareConfig> = Vec::new();
+        for extractor in &self.extractors {
+            let name = format!("config:{}", extractor.family());
+            let mut span = metrics::timed(
+                "config_extractor.run",
+                &ANALYZER_DURATION,
+                &[("analyzer", name.as_str())],
+            );
+            for input in inputs {
+                match extractor.extract(input) {
+                    Ok(found) => {
+                        for config in found {
+                            if configs.iter().all(|c| !c.same_as(&config)) {
+                                configs.push(config);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        span.fail(&e);
+                        warn!(
+                            "Config extractor {} failed on {}: {}",
+                            extractor.family(),
+                            input.name,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+        configs
+    }
+}
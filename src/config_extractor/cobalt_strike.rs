@@ -0,0 +1,177 @@
+use anyhow::Result;
+
+use super::{ConfigExtractor, ExtractionInput, MalwareConfig};
+
+const FAMILY: &str = "cobalt_strike";
+const XOR_KEYS: &[u8] = &[0x69, 0x2e, 0x00];
+const CONFIG_HEADER: &[u8] = &[0x00, 0x01, 0x00, 0x01, 0x00, 0x02];
+const CONFIG_SIZE: usize = 4096;
+const MAX_CONFIGS: usize = 8;
+
+const TYPE_SHORT: u16 = 1;
+const TYPE_INT: u16 = 2;
+const TYPE_DATA: u16 = 3;
+
+const BEACON_TYPE: u16 = 1;
+const PORT: u16 = 2;
+const C2_SERVER: u16 = 8;
+const SETTINGS: &[(u16, &str)] = &[
+    (BEACON_TYPE, "beacon_type"),
+    (PORT, "port"),
+    (3, "sleep_time"),
+    (4, "max_get_size"),
+    (5, "jitter"),
+    (C2_SERVER, "c2_server"),
+    (9, "user_agent"),
+    (10, "post_uri"),
+    (15, "pipe_name"),
+    (26, "http_get_verb"),
+    (27, "http_post_verb"),
+    (29, "spawnto_x86"),
+    (30, "spawnto_x64"),
+    (37, "watermark"),
+];
+
+pub struct CobaltStrikeExtractor;
+
+enum Value {
+    Number(u32),
+    Data(String),
+}
+
+fn u16_be(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn find_all(data: &[u8], needle: &[u8]) -> Vec<usize> {
+    data.windows(needle.len())
+        .enumerate()
+        .filter(|(_, w)| *w == needle)
+        .map(|(i, _)| i)
+        .take(MAX_CONFIGS)
+        .collect()
+}
+
+fn parse_settings(blob: &[u8]) -> Vec<(u16, Value)> {
+    let mut settings = Vec::new();
+    let mut offset = 0;
+    while let (Some(id), Some(kind), Some(len)) = (
+        u16_be(blob, offset),
+        u16_be(blob, offset + 2),
+        u16_be(blob, offset + 4),
+    ) {
+        if id == 0 {
+            break;
+        }
+        let start = offset + 6;
+        let Some(value) = blob.get(start..start + len as usize) else {
+            break;
+        };
+        let value = match kind {
+            TYPE_SHORT => Value::Number(u16_be(value, 0).unwrap_or_default() as u32),
+            TYPE_INT => Value::Number(u32_be(value, 0).unwrap_or_default()),
+            TYPE_DATA => {
+                let end = value.iter().position(|b| *b == 0).unwrap_or(value.len());
+                Value::Data(String::from_utf8_lossy(&value[..end]).into_owned())
+            }
+            _ => break,
+        };
+        settings.push((id, value));
+        offset = start + len as usize;
+    }
+    settings
+}
+
+fn beacon_type(value: u32) -> &'static str {
+    match value {
+        0 => "http",
+        1 => "hybrid_http_dns",
+        2 => "smb",
+        4 => "tcp",
+        8 => "https",
+        16 => "bind_tcp",
+        _ => "unknown",
+    }
+}
+
+fn config(input: &ExtractionInput, settings: Vec<(u16, Value)>) -> Option<MalwareConfig> {
+    let mut config = MalwareConfig::new(FAMILY, input);
+    let (mut servers, mut port, mut https) = (None, None, false);
+    for (id, value) in settings {
+        let Some((_, name)) = SETTINGS.iter().find(|(setting, _)| *setting == id) else {
+            continue;
+        };
+        let value = match (id, value) {
+            (BEACON_TYPE, Value::Number(n)) => {
+                https = n == 8;
+                beacon_type(n).to_string()
+            }
+            (PORT, Value::Number(n)) => {
+                port = Some(n);
+                n.to_string()
+            }
+            (C2_SERVER, Value::Data(s)) => {
+                servers = Some(s.clone());
+                s
+            }
+            (_, Value::Number(n)) => n.to_string(),
+            (_, Value::Data(s)) => s,
+        };
+        config.values.insert(name.to_string(), value);
+    }
+
+    let servers = servers.filter(|s| !s.is_empty())?;
+    let scheme = if https { "https" } else { "http" };
+    let parts: Vec<&str> = servers.split(',').collect();
+    for pair in parts.chunks(2) {
+        let host = pair[0].trim();
+        if host.is_empty() {
+            continue;
+        }
+        let c2 = match port {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+        if let Some(uri) = pair.get(1) {
+            config
+                .urls
+                .push(format!("{}://{}{}", scheme, c2, uri.trim()));
+        }
+        if !config.c2.contains(&c2) {
+            config.c2.push(c2);
+        }
+    }
+    Some(config)
+}
+
+impl ConfigExtractor for CobaltStrikeExtractor {
+    fn family(&self) -> &str {
+        FAMILY
+    }
+
+    fn extract(&self, input: &ExtractionInput) -> Result<Vec<MalwareConfig>> {
+        let mut configs = Vec::new();
+        for &key in XOR_KEYS {
+            let header: Vec<u8> = CONFIG_HEADER.iter().map(|b| b ^ key).collect();
+            for offset in find_all(input.data, &header) {
+                let end = (offset + CONFIG_SIZE).min(input.data.len());
+                let blob: Vec<u8> = input.data[offset..end].iter().map(|b| b ^ key).collect();
+                if let Some(mut config) = config(input, parse_settings(&blob)) {
+                    config
+                        .values
+                        .insert("xor_key".to_string(), format!("{:#04x}", key));
+                    configs.push(config);
+                }
+            }
+        }
+        Ok(configs)
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use regex::Regex;
+
+use super::{url_host, ConfigExtractor, ExtractionInput, MalwareConfig};
+use crate::memory::extract_strings;
+
+const FAMILY: &str = "generic";
+const URL_PREFIXES: &[&[u8]] = &[b"https://", b"http://"];
+const MIN_URL_LEN: usize = 12;
+const MAX_URL_LEN: usize = 512;
+const MIN_BASE64_LEN: usize = 24;
+const MAX_URLS: usize = 64;
+
+pub struct GenericCarver;
+
+fn is_url_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~:/?#[]@!$&'()*+,;=%".contains(&b)
+}
+
+fn url_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"https?://[A-Za-z0-9.-]+(?::\d+)?(?:/[^\s\x22'<>]*)?")
+            .expect("Invalid URL pattern")
+    })
+}
+
+fn is_plausible(url: &str) -> bool {
+    url.len() >= MIN_URL_LEN
+        && url_host(url).is_some_and(|h| {
+            let host = h.split(':').next().unwrap_or(h);
+            host.contains('.') && !host.starts_with('.') && !host.ends_with('.')
+        })
+}
+
+fn carve_xor(data: &[u8], urls: &mut BTreeSet<String>, keys: &mut BTreeSet<u8>) {
+    let mut offset = 0;
+    while offset < data.len() && urls.len() < MAX_URLS {
+        let key = data[offset] ^ b'h';
+        let prefix = URL_PREFIXES.iter().find(|prefix| {
+            key != 0
+                && data
+                    .get(offset..offset + prefix.len())
+                    .is_some_and(|w| w.iter().zip(prefix.iter()).all(|(a, b)| a ^ key == *b))
+        });
+        let Some(prefix) = prefix else {
+            offset += 1;
+            continue;
+        };
+        let decoded: Vec<u8> = data[offset..]
+            .iter()
+            .take(MAX_URL_LEN)
+            .map(|b| b ^ key)
+            .take_while(|b| is_url_byte(*b))
+            .collect();
+        let url = String::from_utf8_lossy(&decoded).into_owned();
+        if is_plausible(&url) {
+            urls.insert(url);
+            keys.insert(key);
+        }
+        offset += prefix.len().max(decoded.len());
+    }
+}
+
+fn carve_base64(data: &[u8], urls: &mut BTreeSet<String>, encoded: &mut usize) {
+    for candidate in extract_strings(data, MIN_BASE64_LEN) {
+        if urls.len() >= MAX_URLS {
+            return;
+        }
+        let candidate = candidate.trim();
+        if !candidate
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='))
+        {
+            continue;
+        }
+        let Ok(decoded) = STANDARD.decode(candidate) else {
+            continue;
+        };
+        let text = String::from_utf8_lossy(&decoded);
+        for url in url_pattern().find_iter(&text).map(|m| m.as_str()) {
+            if is_plausible(url) && urls.insert(url.to_string()) {
+                *encoded += 1;
+            }
+        }
+    }
+}
+
+impl ConfigExtractor for GenericCarver {
+    fn family(&self) -> &str {
+        FAMILY
+    }
+
+    fn extract(&self, input: &ExtractionInput) -> Result<Vec<MalwareConfig>> {
+        let mut urls = BTreeSet::new();
+        let mut keys = BTreeSet::new();
+        let mut encoded = 0;
+        carve_xor(input.data, &mut urls, &mut keys);
+        carve_base64(input.data, &mut urls, &mut encoded);
+        if urls.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut config = MalwareConfig::new(FAMILY, input);
+        for url in &urls {
+            if let Some(host) = url_host(url) {
+                if !config.c2.iter().any(|c| c == host) {
+                    config.c2.push(host.to_string());
+                }
+            }
+        }
+        config.urls = urls.into_iter().collect();
+        if !keys.is_empty() {
+            let keys: Vec<String> = keys.iter().map(|k| format!("{:#04x}", k)).collect();
+            config.values.insert("xor_keys".to_string(), keys.join(","));
+        }
+        if encoded > 0 {
+            config
+                .values
+                .insert("base64_urls".to_string(), encoded.to_string());
+        }
+        Ok(vec![config])
+    }
+}
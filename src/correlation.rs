@@ -0,0 +1,147 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::guid::Guid;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const ACTOR_GUID_FIELDS: &[&str] = &["ProcessGuid", "SourceProcessGuid", "SourceProcessGUID"];
+const TARGET_GUID_FIELDS: &[&str] = &["TargetProcessGuid", "TargetProcessGUID"];
+
+fn first_guid(event: &SysmonEvent, fields: &[&str]) -> Option<Guid> {
+    fields.iter().find_map(|f| event.guid(f))
+}
+
+pub fn actor_guid(event: &SysmonEvent) -> Option<Guid> {
+    first_guid(event, ACTOR_GUID_FIELDS)
+}
+
+pub fn target_guid(event: &SysmonEvent) -> Option<Guid> {
+    first_guid(event, TARGET_GUID_FIELDS)
+}
+
+#[derive(Debug, Default)]
+pub struct EventIndex<'a> {
+    events: &'a [SysmonEvent],
+    by_process: HashMap<Guid, Vec<usize>>,
+    by_target: HashMap<Guid, Vec<usize>>,
+    by_parent: HashMap<Guid, Vec<usize>>,
+    by_logon: HashMap<Guid, Vec<usize>>,
+    creates: HashMap<Guid, usize>,
+}
+
+impl<'a> EventIndex<'a> {
+    pub fn new(events: &'a [SysmonEvent]) -> Self {
+        let mut index = Self {
+            events,
+            ..Default::default()
+        };
+        for (i, event) in events.iter().enumerate() {
+            if let Some(guid) = actor_guid(event) {
+                index.by_process.entry(guid).or_default().push(i);
+            }
+            if let Some(guid) = target_guid(event) {
+                index.by_target.entry(guid).or_default().push(i);
+            }
+            if event.event_id != SysmonEventId::PROCESS_CREATE {
+                continue;
+            }
+            if let Some(guid) = event.process_guid() {
+                index.creates.entry(guid).or_insert(i);
+            }
+            if let Some(parent) = event.parent_process_guid() {
+                index.by_parent.entry(parent).or_default().push(i);
+            }
+            if let Some(logon) = event.logon_guid() {
+                index.by_logon.entry(logon).or_default().push(i);
+            }
+        }
+        index
+    }
+
+    fn resolve(&self, indices: Option<&Vec<usize>>) -> Vec<&'a SysmonEvent> {
+        let events = self.events;
+        indices
+            .map(|i| i.iter().map(|&i| &events[i]).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn events(&self) -> &'a [SysmonEvent] {
+        self.events
+    }
+
+    pub fn process_events(&self, guid: &Guid) -> Vec<&'a SysmonEvent> {
+        self.resolve(self.by_process.get(guid))
+    }
+
+    pub fn targeting(&self, guid: &Guid) -> Vec<&'a SysmonEvent> {
+        self.resolve(self.by_target.get(guid))
+    }
+
+    pub fn process_create(&self, guid: &Guid) -> Option<&'a SysmonEvent> {
+        self.creates.get(guid).map(|&i| &self.events[i])
+    }
+
+    pub fn parent(&self, guid: &Guid) -> Option<&'a SysmonEvent> {
+        self.process_create(&self.process_create(guid)?.parent_process_guid()?)
+    }
+
+    pub fn children(&self, guid: &Guid) -> Vec<&'a SysmonEvent> {
+        self.resolve(self.by_parent.get(guid))
+    }
+
+    pub fn ancestors(&self, guid: &Guid) -> Vec<&'a SysmonEvent> {
+        let mut ancestors = Vec::new();
+        let mut seen = HashSet::from([*guid]);
+        let mut current = *guid;
+        while let Some(parent) = self.parent(&current) {
+            match parent.process_guid() {
+                Some(parent_guid) if seen.insert(parent_guid) => current = parent_guid,
+                _ => break,
+            }
+            ancestors.push(parent);
+        }
+        ancestors
+    }
+
+    pub fn logon_processes(&self, logon: &Guid) -> Vec<&'a SysmonEvent> {
+        self.resolve(self.by_logon.get(logon))
+    }
+
+    pub fn logon_events(&self, logon: &Guid) -> Vec<&'a SysmonEvent> {
+        let mut indices: Vec<usize> = self
+            .by_logon
+            .get(logon)
+            .into_iter()
+            .flatten()
+            .filter_map(|&i| self.events[i].process_guid())
+            .filter_map(|guid| self.by_process.get(&guid))
+            .flatten()
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(|i| &self.events[i]).collect()
+    }
+
+    pub fn logon_of(&self, guid: &Guid) -> Option<Guid> {
+        self.process_create(guid)?.logon_guid()
+    }
+
+    pub fn processes(&self) -> impl Iterator<Item = &Guid> {
+        self.by_process.keys()
+    }
+
+    pub fn logons(&self) -> impl Iterator<Item = &Guid> {
+        self.by_logon.keys()
+    }
+}
+
+pub fn join_by_process<'a, 'b>(
+    left: &'a [SysmonEvent],
+    right: &'b [SysmonEvent],
+) -> Vec<(&'a SysmonEvent, &'b SysmonEvent)> {
+    let index = EventIndex::new(right);
+    left.iter()
+        .filter_map(|l| Some((l, actor_guid(l)?)))
+        .flat_map(|(l, guid)| index.process_events(&guid).into_iter().map(move |r| (l, r)))
+        .collect()
+}
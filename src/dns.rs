@@ -0,0 +1,246 @@
+pub mod encrypted;
+#[cfg(feature = "sqlite")]
+pub mod passive;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::pcap::dns::record_type_name;
+use crate::pcap::{distance, dns_responses, Packet};
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub fn parse_query_results(results: &str) -> Vec<IpAddr> {
+    results
+        .split(';')
+        .filter_map(|r| {
+            let r = r.trim();
+            let r = r.strip_prefix("::ffff:").unwrap_or(r);
+            r.parse().ok()
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    pub domain: String,
+    pub time: DateTime<FixedOffset>,
+    pub process_guid: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ResolutionMap {
+    resolutions: HashMap<IpAddr, Resolution>,
+}
+
+impl ResolutionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, event: &SysmonEvent) {
+        if event.event_id != SysmonEventId::DNS_QUERY
+            || event
+                .event_data
+                .get("QueryStatus")
+                .is_some_and(|s| s != "0")
+        {
+            return;
+        }
+        let (Some(domain), Some(results)) = (
+            event.event_data.get("QueryName"),
+            event.event_data.get("QueryResults"),
+        ) else {
+            return;
+        };
+
+        for ip in parse_query_results(results) {
+            self.resolutions.insert(
+                ip,
+                Resolution {
+                    domain: domain.clone(),
+                    time: event.time_created,
+                    process_guid: event.event_data.get("ProcessGuid").cloned(),
+                },
+            );
+        }
+    }
+
+    pub fn lookup(&self, ip: &IpAddr) -> Option<&Resolution> {
+        self.resolutions.get(ip)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolveConnect {
+    pub domain: String,
+    pub ip: IpAddr,
+    pub resolved_at: DateTime<FixedOffset>,
+    pub connected_at: DateTime<FixedOffset>,
+    pub delta: Duration,
+    pub process_guid: Option<String>,
+}
+
+fn sorted_by_time(events: &[SysmonEvent]) -> Vec<&SysmonEvent> {
+    let mut sorted: Vec<&SysmonEvent> = events.iter().collect();
+    sorted.sort_by_key(|e| e.time_created);
+    sorted
+}
+
+pub fn dns_to_connect_latency(events: &[SysmonEvent]) -> Vec<ResolveConnect> {
+    let mut map = ResolutionMap::new();
+    let mut result = Vec::new();
+
+    for event in sorted_by_time(events) {
+        if event.event_id == SysmonEventId::DNS_QUERY {
+            map.add(event);
+        } else if event.event_id == SysmonEventId::NETWORK_CONNECT {
+            let Some(ip) = event
+                .event_data
+                .get("DestinationIp")
+                .and_then(|ip| ip.parse::<IpAddr>().ok())
+            else {
+                continue;
+            };
+            if let Some(resolution) = map.lookup(&ip) {
+                result.push(ResolveConnect {
+                    domain: resolution.domain.clone(),
+                    ip,
+                    resolved_at: resolution.time,
+                    connected_at: event.time_created,
+                    delta: event.time_created - resolution.time,
+                    process_guid: event.event_data.get("ProcessGuid").cloned(),
+                });
+            }
+        }
+    }
+
+    result
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionSource {
+    Sysmon,
+    Pcap,
+    EncryptedDns,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DnsAnswer {
+    pub record_type: String,
+    pub data: String,
+    pub ttl: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnsResolution {
+    pub time: DateTime<FixedOffset>,
+    pub query: String,
+    pub status: Option<String>,
+    pub rcode: Option<u8>,
+    pub answers: Vec<DnsAnswer>,
+    pub process_guid: Option<String>,
+    pub image: Option<String>,
+    pub sources: Vec<ResolutionSource>,
+}
+
+pub fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_end_matches('.').to_lowercase()
+}
+
+pub fn parse_query_answers(results: &str) -> Vec<DnsAnswer> {
+    results
+        .split(';')
+        .filter_map(|r| {
+            let r = r.trim();
+            if let Some(rest) = r.strip_prefix("type:") {
+                let mut parts = rest.split_whitespace();
+                let record_type = parts.next()?.parse().ok()?;
+                return Some(DnsAnswer {
+                    record_type: record_type_name(record_type),
+                    data: normalize_domain(parts.next()?),
+                    ttl: None,
+                });
+            }
+            let ip: IpAddr = r.strip_prefix("::ffff:").unwrap_or(r).parse().ok()?;
+            Some(DnsAnswer {
+                record_type: if ip.is_ipv4() { "A" } else { "AAAA" }.to_string(),
+                data: ip.to_string(),
+                ttl: None,
+            })
+        })
+        .collect()
+}
+
+pub fn resolution_timeline(events: &[SysmonEvent]) -> Vec<DnsResolution> {
+    sorted_by_time(events)
+        .into_iter()
+        .filter(|e| e.event_id == SysmonEventId::DNS_QUERY)
+        .filter_map(|event| {
+            let data = &event.event_data;
+            Some(DnsResolution {
+                time: event.time_created,
+                query: normalize_domain(data.get("QueryName")?),
+                status: data.get("QueryStatus").cloned(),
+                rcode: None,
+                answers: data
+                    .get("QueryResults")
+                    .map(|r| parse_query_answers(r))
+                    .unwrap_or_default(),
+                process_guid: data.get("ProcessGuid").cloned(),
+                image: data.get("Image").cloned(),
+                sources: vec![ResolutionSource::Sysmon],
+            })
+        })
+        .collect()
+}
+
+pub fn merge_captured_dns(
+    timeline: &mut Vec<DnsResolution>,
+    packets: &[Packet],
+    tolerance: Duration,
+) {
+    for (time, _, message) in dns_responses(packets) {
+        let Some((query, _)) = message.questions.first() else {
+            continue;
+        };
+        let query = normalize_domain(query);
+        let answers: Vec<DnsAnswer> = message
+            .answers
+            .iter()
+            .map(|a| DnsAnswer {
+                record_type: record_type_name(a.record_type),
+                data: normalize_domain(&a.data),
+                ttl: Some(a.ttl),
+            })
+            .collect();
+
+        let existing = timeline.iter_mut().find(|r| {
+            r.query == query
+                && !r.sources.contains(&ResolutionSource::Pcap)
+                && distance(r.time.with_timezone(&Utc), time) <= tolerance
+        });
+        match existing {
+            Some(resolution) => {
+                resolution.sources.push(ResolutionSource::Pcap);
+                resolution.rcode = Some(message.rcode);
+                if !answers.is_empty() {
+                    resolution.answers = answers;
+                }
+            }
+            None => timeline.push(DnsResolution {
+                time: time.fixed_offset(),
+                query,
+                status: None,
+                rcode: Some(message.rcode),
+                answers,
+                process_guid: None,
+                image: None,
+                sources: vec![ResolutionSource::Pcap],
+            }),
+        }
+    }
+    timeline.sort_by_key(|r| r.time);
+}
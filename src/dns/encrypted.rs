@@ -0,0 +1,262 @@
+use std::net::{IpAddr, SocketAddr};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{normalize_domain, DnsResolution, ResolutionSource};
+use crate::netsim::{AttributedRequest, Service};
+use crate::pcap::decode::Transport;
+use crate::pcap::dns::{parse_message, record_type_name};
+use crate::pcap::http::{header, HttpExchange};
+use crate::pcap::CorrelatedFlow;
+
+pub const DOT_PORT: u16 = 853;
+pub const DOH_CONTENT_TYPE: &str = "application/dns-message";
+pub const DOH_QUERY_DETAIL: &str = "doh_query";
+pub const DOH_TYPE_DETAIL: &str = "doh_type";
+
+const HTTPS_PORT: u16 = 443;
+
+const DOH_PROVIDERS: &[&str] = &[
+    "dns.google",
+    "dns.google.com",
+    "cloudflare-dns.com",
+    "mozilla.cloudflare-dns.com",
+    "chrome.cloudflare-dns.com",
+    "one.one.one.one",
+    "dns.quad9.net",
+    "dns9.quad9.net",
+    "doh.opendns.com",
+    "dns.adguard.com",
+    "dns.adguard-dns.com",
+    "doh.cleanbrowsing.org",
+    "dns.nextdns.io",
+    "doh.dns.sb",
+    "dns.alidns.com",
+    "doh.pub",
+];
+
+const DOH_RESOLVERS: &[&str] = &[
+    "1.1.1.1",
+    "1.0.0.1",
+    "8.8.8.8",
+    "8.8.4.4",
+    "9.9.9.9",
+    "149.112.112.112",
+    "208.67.222.222",
+    "208.67.220.220",
+    "94.140.14.14",
+    "94.140.15.15",
+    "2606:4700:4700::1111",
+    "2001:4860:4860::8888",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptedDnsProtocol {
+    DoH,
+    DoT,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptedDnsQuery {
+    pub time: DateTime<Utc>,
+    pub query: String,
+    pub record_type: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptedDnsSession {
+    pub protocol: EncryptedDnsProtocol,
+    pub client: SocketAddr,
+    pub server: Option<SocketAddr>,
+    pub resolver: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub process_guid: Option<String>,
+    pub image: Option<String>,
+    pub intercepted: bool,
+    pub queries: Vec<EncryptedDnsQuery>,
+}
+
+fn is_doh_provider(host: &str) -> bool {
+    let host = normalize_domain(host);
+    DOH_PROVIDERS
+        .iter()
+        .any(|p| host == *p || host.ends_with(&format!(".{}", p)))
+}
+
+fn is_doh_resolver(ip: IpAddr) -> bool {
+    DOH_RESOLVERS
+        .iter()
+        .any(|r| r.parse::<IpAddr>().is_ok_and(|r| r == ip))
+}
+
+pub fn doh_query(uri: &str, content_type: Option<&str>, body: &[u8]) -> Option<(String, String)> {
+    let message =
+        if content_type.is_some_and(|t| t.starts_with(DOH_CONTENT_TYPE)) && !body.is_empty() {
+            parse_message(body)?
+        } else {
+            let (_, query) = uri.split_once('?')?;
+            let encoded = query
+                .split('&')
+                .find_map(|p| p.strip_prefix("dns="))?
+                .trim_end_matches('=');
+            parse_message(&URL_SAFE_NO_PAD.decode(encoded).ok()?)?
+        };
+    if message.response {
+        return None;
+    }
+    let (name, record_type) = message.questions.into_iter().next()?;
+    Some((normalize_domain(&name), record_type_name(record_type)))
+}
+
+fn session(
+    sessions: &mut Vec<EncryptedDnsSession>,
+    protocol: EncryptedDnsProtocol,
+    client: SocketAddr,
+    server: Option<SocketAddr>,
+    time: DateTime<Utc>,
+) -> &mut EncryptedDnsSession {
+    let index = match sessions
+        .iter()
+        .position(|s| s.client == client && (server.is_none() || s.server == server))
+    {
+        Some(index) => index,
+        None => {
+            sessions.push(EncryptedDnsSession {
+                protocol,
+                client,
+                server,
+                resolver: None,
+                first_seen: time,
+                process_guid: None,
+                image: None,
+                intercepted: false,
+                queries: Vec::new(),
+            });
+            sessions.len() - 1
+        }
+    };
+    &mut sessions[index]
+}
+
+pub fn detect(
+    traffic: &[CorrelatedFlow],
+    http: &[HttpExchange],
+    requests: &[AttributedRequest],
+) -> Vec<EncryptedDnsSession> {
+    let mut sessions = Vec::new();
+
+    for t in traffic {
+        let flow = &t.flow;
+        if flow.transport != Transport::Tcp {
+            continue;
+        }
+        let protocol = if flow.server.port() == DOT_PORT {
+            EncryptedDnsProtocol::DoT
+        } else if flow.server.port() == HTTPS_PORT
+            && (flow.sni.as_deref().is_some_and(is_doh_provider)
+                || (flow.sni.is_none() && is_doh_resolver(flow.server.ip())))
+        {
+            EncryptedDnsProtocol::DoH
+        } else {
+            continue;
+        };
+        let session = session(
+            &mut sessions,
+            protocol,
+            flow.client,
+            Some(flow.server),
+            flow.first_seen,
+        );
+        session.resolver = flow.sni.clone();
+        session.process_guid = t.process_guid.clone();
+        session.image = t.image.clone();
+    }
+
+    for exchange in http {
+        let content_type = header(&exchange.request.headers, "content-type");
+        let Some((query, record_type)) = doh_query(&exchange.request.uri, content_type, &[]) else {
+            continue;
+        };
+        let image = traffic.iter().find(|t| t.flow.client == exchange.client);
+        let session = session(
+            &mut sessions,
+            EncryptedDnsProtocol::DoH,
+            exchange.client,
+            Some(exchange.server),
+            exchange.time,
+        );
+        session.resolver = session.resolver.take().or(exchange.host.clone());
+        session.intercepted = true;
+        if let Some(t) = image {
+            session.process_guid = t.process_guid.clone();
+            session.image = t.image.clone();
+        }
+        session.queries.push(EncryptedDnsQuery {
+            time: exchange.time,
+            query,
+            record_type,
+        });
+    }
+
+    for r in requests {
+        if !matches!(r.request.service, Service::Http | Service::Https) {
+            continue;
+        }
+        let details = &r.request.details;
+        let Some(query) = details.get(DOH_QUERY_DETAIL) else {
+            continue;
+        };
+        let session = session(
+            &mut sessions,
+            EncryptedDnsProtocol::DoH,
+            r.request.client,
+            None,
+            r.request.time,
+        );
+        session.resolver = session
+            .resolver
+            .take()
+            .or_else(|| details.get("host").or_else(|| details.get("sni")).cloned());
+        session.intercepted = true;
+        session.process_guid = session.process_guid.take().or(r.process_guid.clone());
+        session.image = session.image.take().or(r.image.clone());
+        session.queries.push(EncryptedDnsQuery {
+            time: r.request.time,
+            query: query.clone(),
+            record_type: details.get(DOH_TYPE_DETAIL).cloned().unwrap_or_default(),
+        });
+    }
+
+    sessions.sort_by_key(|s| s.first_seen);
+    sessions
+}
+
+pub fn merge_encrypted_dns(timeline: &mut Vec<DnsResolution>, sessions: &[EncryptedDnsSession]) {
+    for session in sessions {
+        for query in &session.queries {
+            let time: DateTime<FixedOffset> = query.time.fixed_offset();
+            let known = timeline.iter().any(|r| {
+                r.query == query.query
+                    && r.time == time
+                    && r.sources.contains(&ResolutionSource::EncryptedDns)
+            });
+            if known {
+                continue;
+            }
+            timeline.push(DnsResolution {
+                time,
+                query: query.query.clone(),
+                status: None,
+                rcode: None,
+                answers: Vec::new(),
+                process_guid: session.process_guid.clone(),
+                image: session.image.clone(),
+                sources: vec![ResolutionSource::EncryptedDns],
+            });
+        }
+    }
+    timeline.sort_by_key(|r| r.time);
+}
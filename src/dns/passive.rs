@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+
+use super::{normalize_domain, DnsResolution};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS records (
+    query TEXT NOT NULL,
+    record_type TEXT NOT NULL,
+    data TEXT NOT NULL,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (query, record_type, data)
+);
+CREATE TABLE IF NOT EXISTS sightings (
+    query TEXT NOT NULL,
+    analysis_id TEXT NOT NULL,
+    execution_id TEXT NOT NULL,
+    time TEXT NOT NULL,
+    image TEXT
+);
+CREATE INDEX IF NOT EXISTS records_data ON records(data);
+CREATE INDEX IF NOT EXISTS sightings_query ON sightings(query);
+";
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PassiveDnsRecord {
+    pub query: String,
+    pub record_type: String,
+    pub data: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Sighting {
+    pub query: String,
+    pub analysis_id: String,
+    pub execution_id: String,
+    pub time: String,
+    pub image: Option<String>,
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn record(row: &Row) -> rusqlite::Result<PassiveDnsRecord> {
+    Ok(PassiveDnsRecord {
+        query: row.get(0)?,
+        record_type: row.get(1)?,
+        data: row.get(2)?,
+        first_seen: row.get(3)?,
+        last_seen: row.get(4)?,
+        count: row.get(5)?,
+    })
+}
+
+pub struct PassiveDns {
+    conn: Connection,
+}
+
+impl PassiveDns {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    pub fn record(
+        &mut self,
+        analysis_id: &str,
+        execution_id: &str,
+        timeline: &[DnsResolution],
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO records (query, record_type, data, first_seen, last_seen, count)
+                 VALUES (?1, ?2, ?3, ?4, ?4, 1)
+                 ON CONFLICT (query, record_type, data) DO UPDATE SET
+                     first_seen = min(first_seen, excluded.first_seen),
+                     last_seen = max(last_seen, excluded.last_seen),
+                     count = count + 1",
+            )?;
+            let mut sighting = tx.prepare(
+                "INSERT INTO sightings (query, analysis_id, execution_id, time, image)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+
+            for resolution in timeline {
+                let time = timestamp(resolution.time.with_timezone(&Utc));
+                sighting.execute(params![
+                    resolution.query,
+                    analysis_id,
+                    execution_id,
+                    time,
+                    resolution.image,
+                ])?;
+                for answer in &resolution.answers {
+                    upsert.execute(params![
+                        resolution.query,
+                        answer.record_type,
+                        answer.data,
+                        time
+                    ])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn by_query(&self, domain: &str) -> Result<Vec<PassiveDnsRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT query, record_type, data, first_seen, last_seen, count FROM records
+             WHERE query = ?1 OR query LIKE ?2 ORDER BY last_seen DESC",
+        )?;
+        let domain = normalize_domain(domain);
+        let subdomains = format!("%.{}", domain);
+        let rows = stmt.query_map(params![domain, subdomains], record)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn by_data(&self, data: &str) -> Result<Vec<PassiveDnsRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT query, record_type, data, first_seen, last_seen, count FROM records
+             WHERE data = ?1 ORDER BY last_seen DESC",
+        )?;
+        let rows = stmt.query_map(params![normalize_domain(data)], record)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn sightings(&self, domain: &str) -> Result<Vec<Sighting>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT query, analysis_id, execution_id, time, image FROM sightings
+             WHERE query = ?1 ORDER BY time DESC",
+        )?;
+        let rows = stmt.query_map(params![normalize_domain(domain)], |row| {
+            Ok(Sighting {
+                query: row.get(0)?,
+                analysis_id: row.get(1)?,
+                execution_id: row.get(2)?,
+                time: row.get(3)?,
+                image: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
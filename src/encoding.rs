@@ -0,0 +1,243 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sysmon_event::{SysmonEvent, ENRICHED_PREFIX};
+
+pub const FLAGS_KEY: &str = "encoding";
+pub const LONG_FIELD_CHARS: usize = 8191;
+pub const MAX_FIELD_CHARS: usize = 1 << 20;
+
+const UTF16_LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16_BE_BOM: &[u8] = &[0xfe, 0xff];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingFlag {
+    InvalidUtf8,
+    Utf16,
+    EmbeddedNul,
+    ControlCharacters,
+    BidiOverride,
+    InvisibleCharacters,
+    MixedScript,
+    Long,
+    Truncated,
+}
+
+impl EncodingFlag {
+    pub const ALL: &'static [Self] = &[
+        Self::InvalidUtf8,
+        Self::Utf16,
+        Self::EmbeddedNul,
+        Self::ControlCharacters,
+        Self::BidiOverride,
+        Self::InvisibleCharacters,
+        Self::MixedScript,
+        Self::Long,
+        Self::Truncated,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InvalidUtf8 => "invalid_utf8",
+            Self::Utf16 => "utf16",
+            Self::EmbeddedNul => "embedded_nul",
+            Self::ControlCharacters => "control_characters",
+            Self::BidiOverride => "bidi_override",
+            Self::InvisibleCharacters => "invisible_characters",
+            Self::MixedScript => "mixed_script",
+            Self::Long => "long",
+            Self::Truncated => "truncated",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().find(|f| f.name() == name.trim()).copied()
+    }
+
+    pub fn is_deceptive(&self) -> bool {
+        matches!(
+            self,
+            Self::BidiOverride | Self::InvisibleCharacters | Self::MixedScript
+        )
+    }
+}
+
+pub fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+pub fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00ad}' | '\u{034f}' | '\u{115f}' | '\u{1160}' | '\u{180e}' | '\u{200b}'..='\u{200d}'
+            | '\u{2060}'..='\u{2064}' | '\u{3164}' | '\u{feff}' | '\u{ffa0}'
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c {
+        'a'..='z' | 'A'..='Z' | '\u{00c0}'..='\u{024f}' | '\u{1e00}'..='\u{1eff}' => {
+            Some(Script::Latin)
+        }
+        '\u{0370}'..='\u{03ff}' | '\u{1f00}'..='\u{1fff}' => Some(Script::Greek),
+        '\u{0400}'..='\u{052f}' => Some(Script::Cyrillic),
+        '\u{0530}'..='\u{058f}' => Some(Script::Armenian),
+        _ => None,
+    }
+}
+
+fn has_mixed_script(value: &str) -> bool {
+    value.split(|c: char| !c.is_alphanumeric()).any(|token| {
+        let mut scripts = token.chars().filter_map(script);
+        let Some(first) = scripts.next() else {
+            return false;
+        };
+        scripts.any(|s| s != first)
+    })
+}
+
+pub fn decode(bytes: &[u8]) -> (String, bool) {
+    if let Some(units) = bytes.strip_prefix(UTF16_LE_BOM) {
+        return decode_utf16(units, u16::from_le_bytes);
+    }
+    if let Some(units) = bytes.strip_prefix(UTF16_BE_BOM) {
+        return decode_utf16(units, u16::from_be_bytes);
+    }
+    let mut text = String::with_capacity(bytes.len());
+    let mut lossy = false;
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            let _ = write!(text, "\\x{:02x}", byte);
+            lossy = true;
+        }
+    }
+    (text, lossy)
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> (String, bool) {
+    let units = bytes.chunks_exact(2).map(|b| unit([b[0], b[1]]));
+    let mut text = String::with_capacity(bytes.len() / 2);
+    let mut lossy = !bytes.len().is_multiple_of(2);
+    for c in char::decode_utf16(units) {
+        match c {
+            Ok(c) => text.push(c),
+            Err(e) => {
+                let _ = write!(text, "\\u{{{:04x}}}", e.unpaired_surrogate());
+                lossy = true;
+            }
+        }
+    }
+    (text, lossy)
+}
+
+fn is_utf16_artifact(value: &str) -> bool {
+    let nuls = value.chars().filter(|c| *c == '\0').count();
+    nuls > 0 && nuls * 2 >= value.chars().count() - 1
+}
+
+pub fn inspect(value: &str) -> Vec<EncodingFlag> {
+    let mut flags = Vec::new();
+    if value.contains('\0') {
+        flags.push(EncodingFlag::EmbeddedNul);
+    }
+    if value
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\0' | '\t' | '\r' | '\n'))
+    {
+        flags.push(EncodingFlag::ControlCharacters);
+    }
+    if value.chars().any(is_bidi_control) {
+        flags.push(EncodingFlag::BidiOverride);
+    }
+    if value.chars().any(is_invisible) {
+        flags.push(EncodingFlag::InvisibleCharacters);
+    }
+    if has_mixed_script(value) {
+        flags.push(EncodingFlag::MixedScript);
+    }
+    if value.chars().count() > LONG_FIELD_CHARS {
+        flags.push(EncodingFlag::Long);
+    }
+    flags
+}
+
+pub fn clean(value: &str) -> (String, Vec<EncodingFlag>) {
+    let mut flags = Vec::new();
+    let mut value = if is_utf16_artifact(value) {
+        flags.push(EncodingFlag::Utf16);
+        value.replace('\0', "")
+    } else {
+        value.trim_end_matches('\0').to_string()
+    };
+    if let Some((index, _)) = value.char_indices().nth(MAX_FIELD_CHARS) {
+        value.truncate(index);
+        flags.push(EncodingFlag::Truncated);
+    }
+    flags.extend(inspect(&value));
+    (value, flags)
+}
+
+pub fn flags_field(field: &str) -> String {
+    format!("{}.{}", FLAGS_KEY, field)
+}
+
+pub fn harden_event(event: &mut SysmonEvent) {
+    let fields: Vec<String> = event
+        .event_data
+        .keys()
+        .filter(|k| !k.starts_with(ENRICHED_PREFIX))
+        .cloned()
+        .collect();
+    for field in fields {
+        if event.enriched(&flags_field(&field)).is_some() {
+            continue;
+        }
+        let (value, flags) = clean(&event.event_data[&field]);
+        if flags.is_empty() {
+            continue;
+        }
+        let names: Vec<&str> = flags.iter().map(EncodingFlag::name).collect();
+        event.set_enriched(&flags_field(&field), names.join(","));
+        event.set_field(field, value);
+    }
+}
+
+pub fn field_flags(event: &SysmonEvent, field: &str) -> Vec<EncodingFlag> {
+    match event.enriched(&flags_field(field)) {
+        Some(flags) => flags
+            .split(',')
+            .filter_map(EncodingFlag::from_name)
+            .collect(),
+        None => event
+            .event_data
+            .get(field)
+            .map(|v| inspect(v))
+            .unwrap_or_default(),
+    }
+}
+
+pub fn safe_display(value: &str) -> String {
+    let mut safe = String::with_capacity(value.len());
+    for c in value.chars() {
+        if is_bidi_control(c) || is_invisible(c) || (c.is_control() && !matches!(c, '\t' | '\n')) {
+            let _ = write!(safe, "[U+{:04X}]", c as u32);
+        } else {
+            safe.push(c);
+        }
+    }
+    safe
+}
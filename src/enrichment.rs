@@ -0,0 +1,59 @@
+#[cfg(feature = "enrichment")]
+mod client;
+pub mod geo;
+#[cfg(feature = "enrichment")]
+mod malwarebazaar;
+#[cfg(feature = "enrichment")]
+mod urlhaus;
+#[cfg(feature = "enrichment")]
+mod virustotal;
+
+#[cfg(feature = "enrichment")]
+pub use client::{Enricher, EnrichmentConfig};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ioc::Ioc;
+
+pub const ENRICHMENT_FILE: &str = "enrichment.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+    VirusTotal,
+    MalwareBazaar,
+    Urlhaus,
+}
+
+impl Provider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::VirusTotal => "virustotal",
+            Self::MalwareBazaar => "malwarebazaar",
+            Self::Urlhaus => "urlhaus",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Unknown,
+    Clean,
+    Suspicious,
+    Malicious,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Enrichment {
+    pub provider: Provider,
+    pub subject: Ioc,
+    pub verdict: Verdict,
+    pub detections: Option<u32>,
+    pub engines: Option<u32>,
+    pub family: Option<String>,
+    pub tags: Vec<String>,
+    pub link: Option<String>,
+    pub queried: DateTime<Utc>,
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+
+use super::{malwarebazaar, urlhaus, virustotal, Enrichment, Provider, ENRICHMENT_FILE};
+use crate::analysis_result::{artifact_dir, sample_path};
+use crate::event_data::TypedEventData;
+use crate::ioc::Ioc;
+use crate::report::SandboxReport;
+
+const MAX_SUBJECTS: usize = 50;
+
+#[derive(Debug, Clone)]
+pub struct EnrichmentConfig {
+    pub virustotal_key: Option<String>,
+    pub malwarebazaar_key: Option<String>,
+    pub urlhaus_key: Option<String>,
+    pub cache_dir: PathBuf,
+    pub cache_ttl: ChronoDuration,
+    pub intervals: HashMap<Provider, Duration>,
+}
+
+impl Default for EnrichmentConfig {
+    fn default() -> Self {
+        Self {
+            virustotal_key: None,
+            malwarebazaar_key: None,
+            urlhaus_key: None,
+            cache_dir: PathBuf::from("enrichment_cache"),
+            cache_ttl: ChronoDuration::days(7),
+            intervals: HashMap::from([
+                (Provider::VirusTotal, Duration::from_secs(15)),
+                (Provider::MalwareBazaar, Duration::from_secs(1)),
+                (Provider::Urlhaus, Duration::from_secs(1)),
+            ]),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    time: chrono::DateTime<Utc>,
+    result: Option<Enrichment>,
+}
+
+#[derive(Debug)]
+pub struct Enricher {
+    config: EnrichmentConfig,
+    client: reqwest::Client,
+    next_request: HashMap<Provider, Mutex<Instant>>,
+}
+
+impl Enricher {
+    pub fn new(config: EnrichmentConfig) -> Result<Self> {
+        let next_request = [
+            Provider::VirusTotal,
+            Provider::MalwareBazaar,
+            Provider::Urlhaus,
+        ]
+        .into_iter()
+        .map(|p| (p, Mutex::new(Instant::now())))
+        .collect();
+        Ok(Self {
+            config,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            next_request,
+        })
+    }
+
+    fn key(&self, provider: Provider) -> Option<&str> {
+        match provider {
+            Provider::VirusTotal => self.config.virustotal_key.as_deref(),
+            Provider::MalwareBazaar => self.config.malwarebazaar_key.as_deref(),
+            Provider::Urlhaus => self.config.urlhaus_key.as_deref(),
+        }
+    }
+
+    fn cache_path(&self, provider: Provider, subject: &Ioc) -> PathBuf {
+        let name = format!(
+            "{:x}",
+            Sha256::digest(format!("{}:{}", subject.kind(), subject.value()))
+        );
+        self.config
+            .cache_dir
+            .join(provider.name())
+            .join(format!("{}.json", name))
+    }
+
+    async fn cached(&self, provider: Provider, subject: &Ioc) -> Option<Option<Enrichment>> {
+        let data = tokio::fs::read(self.cache_path(provider, subject))
+            .await
+            .ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&data).ok()?;
+        (Utc::now() - entry.time < self.config.cache_ttl).then_some(entry.result)
+    }
+
+    async fn store(&self, provider: Provider, subject: &Ioc, result: &Option<Enrichment>) {
+        let path = self.cache_path(provider, subject);
+        let entry = CacheEntry {
+            time: Utc::now(),
+            result: result.clone(),
+        };
+        let write = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&path, serde_json::to_vec(&entry)?).await?;
+            anyhow::Ok(())
+        };
+        if let Err(e) = write.await {
+            warn!("Failed to cache {} lookup: {}", provider.name(), e);
+        }
+    }
+
+    async fn throttle(&self, provider: Provider) {
+        let Some(next) = self.next_request.get(&provider) else {
+            return;
+        };
+        let mut next = next.lock().await;
+        sleep_until(*next).await;
+        let interval = self
+            .config
+            .intervals
+            .get(&provider)
+            .copied()
+            .unwrap_or_default();
+        *next = Instant::now() + interval;
+    }
+
+    pub async fn lookup(&self, provider: Provider, subject: &Ioc) -> Result<Option<Enrichment>> {
+        let Some(key) = self.key(provider) else {
+            return Ok(None);
+        };
+        if let Some(result) = self.cached(provider, subject).await {
+            return Ok(result);
+        }
+        self.throttle(provider).await;
+        let result = match provider {
+            Provider::VirusTotal => virustotal::lookup(&self.client, key, subject).await?,
+            Provider::MalwareBazaar => malwarebazaar::lookup(&self.client, key, subject).await?,
+            Provider::Urlhaus => urlhaus::lookup(&self.client, key, subject).await?,
+        };
+        self.store(provider, subject, &result).await;
+        Ok(result)
+    }
+
+    fn subjects(report: &SandboxReport) -> Vec<Ioc> {
+        let mut subjects = Vec::new();
+        if let Ok(sample) = std::fs::read(sample_path(&report.id)) {
+            subjects.push(Ioc::Sha256(format!("{:x}", Sha256::digest(sample))));
+        }
+        subjects.extend(
+            report
+                .artifacts
+                .iter()
+                .map(|a| Ioc::Sha256(a.hashes.sha256.clone())),
+        );
+        subjects.extend(report.events.iter().filter_map(|e| {
+            let hashes = match e.typed_data() {
+                TypedEventData::FileCreate(d) | TypedEventData::FileCreateStreamHash(d) => d.hashes,
+                TypedEventData::FileExecutableDetected(d) | TypedEventData::FileDelete(d) => {
+                    d.hashes
+                }
+                _ => return None,
+            };
+            let (algorithm, value) = hashes.strongest()?;
+            Some(Ioc::from_hash(algorithm, value))
+        }));
+        subjects.extend(
+            report
+                .iocs
+                .iter()
+                .filter(|i| matches!(i.ioc, Ioc::Domain(_) | Ioc::Url(_)))
+                .map(|i| i.ioc.clone()),
+        );
+        let mut seen = std::collections::HashSet::new();
+        subjects.retain(|s| seen.insert(s.clone()));
+        subjects.truncate(MAX_SUBJECTS);
+        subjects
+    }
+
+    pub async fn enrich(&self, report: &SandboxReport) -> Vec<Enrichment> {
+        let mut results = Vec::new();
+        for subject in Self::subjects(report) {
+            let providers: &[Provider] = match subject {
+                Ioc::Md5(_) | Ioc::Sha1(_) | Ioc::Sha256(_) => &[
+                    Provider::VirusTotal,
+                    Provider::MalwareBazaar,
+                    Provider::Urlhaus,
+                ],
+                _ => &[Provider::VirusTotal, Provider::Urlhaus],
+            };
+            for &provider in providers {
+                match self.lookup(provider, &subject).await {
+                    Ok(Some(result)) => results.push(result),
+                    Ok(None) => (),
+                    Err(e) => warn!(
+                        "{} lookup of {} failed: {}",
+                        provider.name(),
+                        subject.value(),
+                        e
+                    ),
+                }
+            }
+        }
+        results
+    }
+
+    pub async fn enrich_report(&self, report: &SandboxReport) -> Result<Vec<Enrichment>> {
+        let results = self.enrich(report).await;
+        let path = format!(
+            "{}/{}",
+            artifact_dir(&report.id, &report.execution_id),
+            ENRICHMENT_FILE
+        );
+        tokio::fs::write(path, serde_json::to_vec_pretty(&results)?).await?;
+        Ok(results)
+    }
+}
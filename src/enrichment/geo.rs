@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+pub const BULLETPROOF_TAG: &str = "bulletproof";
+
+const CLOUD_ASNS: &[(u32, &str)] = &[
+    (16509, "aws"),
+    (14618, "aws"),
+    (8075, "azure"),
+    (8068, "azure"),
+    (15169, "gcp"),
+    (396982, "gcp"),
+    (13335, "cloudflare"),
+    (14061, "digitalocean"),
+    (16276, "ovh"),
+    (24940, "hetzner"),
+    (63949, "linode"),
+    (20473, "vultr"),
+    (45102, "alibaba"),
+    (31898, "oracle"),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GeoInfo {
+    pub ip: IpAddr,
+    pub asn: Option<u32>,
+    pub as_org: Option<String>,
+    pub country: Option<String>,
+    pub cloud: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct GeoOptions {
+    #[serde(default)]
+    pub asn_database: Option<String>,
+    #[serde(default)]
+    pub country_database: Option<String>,
+    #[serde(default)]
+    pub proxies: Vec<IpAddr>,
+    #[serde(default)]
+    pub asn_tags: HashMap<String, Vec<u32>>,
+}
+
+impl GeoOptions {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+#[cfg(feature = "geoip")]
+type Reader = maxminddb::Reader<Vec<u8>>;
+
+#[cfg(feature = "geoip")]
+fn open(path: &Option<String>) -> Result<Option<Reader>> {
+    match path {
+        Some(path) => Ok(Some(maxminddb::Reader::open_readfile(path)?)),
+        None => Ok(None),
+    }
+}
+
+pub struct GeoDatabase {
+    #[cfg(feature = "geoip")]
+    asn: Option<Reader>,
+    #[cfg(feature = "geoip")]
+    country: Option<Reader>,
+    proxies: Vec<IpAddr>,
+    asn_tags: HashMap<u32, Vec<String>>,
+}
+
+impl fmt::Debug for GeoDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GeoDatabase")
+            .field("proxies", &self.proxies)
+            .field("tagged_asns", &self.asn_tags.len())
+            .finish()
+    }
+}
+
+impl GeoDatabase {
+    pub fn load(options: &GeoOptions) -> Result<Self> {
+        #[cfg(not(feature = "geoip"))]
+        if options.asn_database.is_some() || options.country_database.is_some() {
+            anyhow::bail!("GeoIP databases require the geoip feature");
+        }
+        let mut asn_tags: HashMap<u32, Vec<String>> = HashMap::new();
+        for (tag, asns) in &options.asn_tags {
+            for asn in asns {
+                asn_tags.entry(*asn).or_default().push(tag.to_lowercase());
+            }
+        }
+        Ok(Self {
+            #[cfg(feature = "geoip")]
+            asn: open(&options.asn_database)?,
+            #[cfg(feature = "geoip")]
+            country: open(&options.country_database)?,
+            proxies: options.proxies.clone(),
+            asn_tags,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::load(&GeoOptions::from_file(path)?)
+    }
+
+    pub fn is_proxy(&self, ip: &IpAddr) -> bool {
+        self.proxies.contains(ip)
+    }
+
+    #[cfg(feature = "geoip")]
+    fn asn(&self, ip: IpAddr) -> (Option<u32>, Option<String>) {
+        use maxminddb::geoip2;
+
+        let Some(reader) = &self.asn else {
+            return (None, None);
+        };
+        match reader.lookup::<geoip2::Asn>(ip) {
+            Ok(asn) => (
+                asn.autonomous_system_number,
+                asn.autonomous_system_organization.map(str::to_string),
+            ),
+            Err(_) => (None, None),
+        }
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    fn asn(&self, _ip: IpAddr) -> (Option<u32>, Option<String>) {
+        (None, None)
+    }
+
+    #[cfg(feature = "geoip")]
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        use maxminddb::geoip2;
+
+        let reader = self.country.as_ref()?;
+        let country = reader.lookup::<geoip2::Country>(ip).ok()?;
+        country.country.and_then(|c| c.iso_code).map(str::to_string)
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    fn country(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let (asn, as_org) = self.asn(ip);
+        let country = self.country(ip);
+        if asn.is_none() && country.is_none() {
+            return None;
+        }
+        let cloud = asn.and_then(|asn| {
+            CLOUD_ASNS
+                .iter()
+                .find(|(a, _)| *a == asn)
+                .map(|(_, provider)| provider.to_string())
+        });
+        let mut tags: Vec<String> = asn
+            .and_then(|asn| self.asn_tags.get(&asn).cloned())
+            .unwrap_or_default();
+        if let Some(cloud) = &cloud {
+            tags.push(format!("cloud:{}", cloud));
+        }
+        Some(GeoInfo {
+            ip,
+            asn,
+            as_org,
+            country,
+            cloud,
+            tags,
+        })
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde_json::Value;
+
+use super::{Enrichment, Provider, Verdict};
+use crate::ioc::Ioc;
+
+const API_URL: &str = "https://mb-api.abuse.ch/api/v1/";
+
+pub(super) async fn lookup(
+    client: &reqwest::Client,
+    key: &str,
+    subject: &Ioc,
+) -> Result<Option<Enrichment>> {
+    let (Ioc::Md5(hash) | Ioc::Sha1(hash) | Ioc::Sha256(hash)) = subject else {
+        return Ok(None);
+    };
+
+    let body: Value = client
+        .post(API_URL)
+        .header("Auth-Key", key)
+        .form(&[("query", "get_info"), ("hash", hash.as_str())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    match body["query_status"].as_str() {
+        Some("ok") => (),
+        Some("hash_not_found") | Some("no_results") => return Ok(None),
+        status => bail!(
+            "MalwareBazaar query failed: {}",
+            status.unwrap_or("unknown")
+        ),
+    }
+    let sample = &body["data"][0];
+
+    Ok(Some(Enrichment {
+        provider: Provider::MalwareBazaar,
+        subject: subject.clone(),
+        verdict: Verdict::Malicious,
+        detections: None,
+        engines: None,
+        family: sample["signature"].as_str().map(str::to_string),
+        tags: sample["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|t| t.as_str().map(str::to_string))
+            .collect(),
+        link: sample["sha256_hash"]
+            .as_str()
+            .map(|sha256| format!("https://bazaar.abuse.ch/sample/{}/", sha256)),
+        queried: Utc::now(),
+    }))
+}
@@ -0,0 +1,70 @@
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde_json::Value;
+
+use super::{Enrichment, Provider, Verdict};
+use crate::ioc::Ioc;
+
+const API_URL: &str = "https://urlhaus-api.abuse.ch/v1";
+
+pub(super) async fn lookup(
+    client: &reqwest::Client,
+    key: &str,
+    subject: &Ioc,
+) -> Result<Option<Enrichment>> {
+    let (endpoint, field, value) = match subject {
+        Ioc::Md5(hash) => ("payload", "md5_hash", hash.clone()),
+        Ioc::Sha256(hash) => ("payload", "sha256_hash", hash.clone()),
+        Ioc::Domain(domain) => ("host", "host", domain.clone()),
+        Ioc::Ip(ip) => ("host", "host", ip.to_string()),
+        Ioc::Url(url) => ("url", "url", url.clone()),
+        _ => return Ok(None),
+    };
+
+    let body: Value = client
+        .post(format!("{}/{}/", API_URL, endpoint))
+        .header("Auth-Key", key)
+        .form(&[(field, value.as_str())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    match body["query_status"].as_str() {
+        Some("ok") => (),
+        Some("no_results") => return Ok(None),
+        status => bail!("URLhaus query failed: {}", status.unwrap_or("unknown")),
+    }
+
+    let urls = body["url_count"]
+        .as_str()
+        .and_then(|c| c.parse().ok())
+        .or_else(|| body["url_count"].as_u64().map(|c| c as u32));
+    let family = body["signature"]
+        .as_str()
+        .or_else(|| body["threat"].as_str())
+        .map(str::to_string);
+    let verdict = match body["url_status"].as_str() {
+        Some("online") => Verdict::Malicious,
+        Some(_) => Verdict::Suspicious,
+        None if urls.unwrap_or_default() > 0 => Verdict::Malicious,
+        None => Verdict::Suspicious,
+    };
+
+    Ok(Some(Enrichment {
+        provider: Provider::Urlhaus,
+        subject: subject.clone(),
+        verdict,
+        detections: urls,
+        engines: None,
+        family,
+        tags: body["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|t| t.as_str().map(str::to_string))
+            .collect(),
+        link: body["urlhaus_reference"].as_str().map(str::to_string),
+        queried: Utc::now(),
+    }))
+}
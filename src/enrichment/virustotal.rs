@@ -0,0 +1,77 @@
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use super::{Enrichment, Provider, Verdict};
+use crate::ioc::Ioc;
+
+const API_URL: &str = "https://www.virustotal.com/api/v3";
+const GUI_URL: &str = "https://www.virustotal.com/gui";
+
+pub(super) async fn lookup(
+    client: &reqwest::Client,
+    key: &str,
+    subject: &Ioc,
+) -> Result<Option<Enrichment>> {
+    let (path, gui) = match subject {
+        Ioc::Md5(hash) | Ioc::Sha1(hash) | Ioc::Sha256(hash) => {
+            (format!("files/{}", hash), format!("file/{}", hash))
+        }
+        Ioc::Domain(domain) => (format!("domains/{}", domain), format!("domain/{}", domain)),
+        Ioc::Ip(ip) => (format!("ip_addresses/{}", ip), format!("ip-address/{}", ip)),
+        Ioc::Url(url) => {
+            let id = URL_SAFE_NO_PAD.encode(url);
+            (format!("urls/{}", id), format!("url/{}", id))
+        }
+        _ => return Ok(None),
+    };
+
+    let response = client
+        .get(format!("{}/{}", API_URL, path))
+        .header("x-apikey", key)
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let body: Value = response.error_for_status()?.json().await?;
+    let attributes = &body["data"]["attributes"];
+    let stats = &attributes["last_analysis_stats"];
+    let count = |name: &str| stats[name].as_u64().unwrap_or_default() as u32;
+    let detections = count("malicious");
+    let engines = ["malicious", "suspicious", "undetected", "harmless"]
+        .into_iter()
+        .map(count)
+        .sum();
+    let verdict = if detections >= 3 {
+        Verdict::Malicious
+    } else if detections > 0 || count("suspicious") > 0 {
+        Verdict::Suspicious
+    } else if engines > 0 {
+        Verdict::Clean
+    } else {
+        Verdict::Unknown
+    };
+
+    Ok(Some(Enrichment {
+        provider: Provider::VirusTotal,
+        subject: subject.clone(),
+        verdict,
+        detections: Some(detections),
+        engines: Some(engines),
+        family: attributes["popular_threat_classification"]["suggested_threat_label"]
+            .as_str()
+            .map(str::to_string),
+        tags: attributes["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|t| t.as_str().map(str::to_string))
+            .collect(),
+        link: Some(format!("{}/{}", GUI_URL, gui)),
+        queried: Utc::now(),
+    }))
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::iter::once;
+use std::mem::size_of;
+use std::ptr::null;
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use log::warn;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use windows_sys::core::GUID;
+use windows_sys::Win32::Foundation::{ERROR_ALREADY_EXISTS, ERROR_SUCCESS};
+use windows_sys::Win32::System::Diagnostics::Etw::{
+    CloseTrace, ControlTraceW, EnableTraceEx2, OpenTraceW, ProcessTrace, StartTraceW,
+    TdhGetProperty, TdhGetPropertySize, EVENT_CONTROL_CODE_ENABLE_PROVIDER, EVENT_RECORD,
+    EVENT_TRACE_CONTROL_STOP, EVENT_TRACE_LOGFILEW, EVENT_TRACE_PROPERTIES,
+    EVENT_TRACE_REAL_TIME_MODE, PROCESS_TRACE_MODE_EVENT_RECORD, PROCESS_TRACE_MODE_REAL_TIME,
+    PROPERTY_DATA_DESCRIPTOR, WNODE_FLAG_TRACED_GUID,
+};
+
+use crate::telemetry::{Provider, TelemetryEvent, TelemetryRecord};
+
+pub const DEFAULT_SESSION_NAME: &str = "malware-analysis-sandbox";
+
+const TRACE_LEVEL_VERBOSE: u8 = 5;
+const KERNEL_PROCESS_KEYWORD: u64 = 0x10;
+const FILETIME_UNIX_EPOCH: i64 = 116_444_736_000_000_000;
+const INVALID_PROCESSTRACE_HANDLE: u64 = u64::MAX;
+
+#[derive(Clone, Copy)]
+enum PropertyType {
+    Unicode,
+    Ansi,
+    U32,
+    U64,
+    Binary,
+}
+
+fn provider_guid(provider: Provider) -> Option<GUID> {
+    match provider {
+        Provider::Sysmon | Provider::Security | Provider::ApiTrace => None,
+        Provider::PowerShell => Some(GUID::from_u128(0xa0c1853b_5c40_4b15_8766_3cf1c58f985a)),
+        Provider::DnsClient => Some(GUID::from_u128(0x1c95126e_7eea_49a9_a3fe_a378b03ddb4d)),
+        Provider::KernelProcess => Some(GUID::from_u128(0x22fb2cd6_0e7b_422b_a0c7_2fad1fd0e716)),
+        Provider::Amsi => Some(GUID::from_u128(0x2a576b87_09a7_520e_c21a_4942f0271d67)),
+    }
+}
+
+fn provider_keyword(provider: Provider) -> u64 {
+    match provider {
+        Provider::KernelProcess => KERNEL_PROCESS_KEYWORD,
+        _ => 0,
+    }
+}
+
+fn properties(provider: Provider, event_id: u16) -> &'static [(&'static str, PropertyType)] {
+    match (provider, event_id) {
+        (Provider::PowerShell, 4104) => &[
+            ("MessageNumber", PropertyType::U32),
+            ("MessageTotal", PropertyType::U32),
+            ("ScriptBlockText", PropertyType::Unicode),
+            ("ScriptBlockId", PropertyType::Unicode),
+            ("Path", PropertyType::Unicode),
+        ],
+        (Provider::PowerShell, 4103) => &[
+            ("ContextInfo", PropertyType::Unicode),
+            ("Payload", PropertyType::Unicode),
+        ],
+        (Provider::Amsi, 1101) => &[
+            ("scanResult", PropertyType::U32),
+            ("appname", PropertyType::Unicode),
+            ("contentname", PropertyType::Unicode),
+            ("content", PropertyType::Binary),
+        ],
+        (Provider::DnsClient, 3008) => &[
+            ("QueryName", PropertyType::Unicode),
+            ("QueryType", PropertyType::U32),
+            ("QueryOptions", PropertyType::U64),
+            ("QueryStatus", PropertyType::U32),
+            ("QueryResults", PropertyType::Unicode),
+        ],
+        (Provider::KernelProcess, 1) => &[
+            ("ProcessID", PropertyType::U32),
+            ("ParentProcessID", PropertyType::U32),
+            ("SessionID", PropertyType::U32),
+            ("ImageName", PropertyType::Unicode),
+        ],
+        (Provider::KernelProcess, 2) => &[
+            ("ProcessID", PropertyType::U32),
+            ("ExitCode", PropertyType::U32),
+            ("ImageName", PropertyType::Ansi),
+        ],
+        _ => &[],
+    }
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(once(0)).collect()
+}
+
+fn filetime(timestamp: i64) -> DateTime<FixedOffset> {
+    let nanos = (timestamp - FILETIME_UNIX_EPOCH) * 100;
+    Utc.timestamp_nanos(nanos).into()
+}
+
+unsafe fn property(record: *const EVENT_RECORD, name: &str, kind: PropertyType) -> Option<String> {
+    let name = wide(name);
+    let descriptor = PROPERTY_DATA_DESCRIPTOR {
+        PropertyName: name.as_ptr() as u64,
+        ArrayIndex: u32::MAX,
+        Reserved: 0,
+    };
+    let mut size = 0;
+    if TdhGetPropertySize(record, 0, null(), 1, &descriptor, &mut size) != ERROR_SUCCESS {
+        return None;
+    }
+    let mut buffer = vec![0u8; size as usize];
+    if TdhGetProperty(record, 0, null(), 1, &descriptor, size, buffer.as_mut_ptr()) != ERROR_SUCCESS
+    {
+        return None;
+    }
+
+    let number = |len: usize| {
+        let mut bytes = [0u8; 8];
+        bytes[..len].copy_from_slice(buffer.get(..len)?);
+        Some(u64::from_le_bytes(bytes).to_string())
+    };
+    match kind {
+        PropertyType::Unicode => {
+            let units: Vec<u16> = buffer
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0)
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        PropertyType::Ansi => {
+            let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+            Some(String::from_utf8_lossy(&buffer[..len]).into_owned())
+        }
+        PropertyType::U32 => number(4),
+        PropertyType::U64 => number(8),
+        PropertyType::Binary => Some(buffer.iter().map(|b| format!("{:02x}", b)).collect()),
+    }
+}
+
+unsafe fn telemetry_event(record: *const EVENT_RECORD) -> Option<TelemetryEvent> {
+    let header = &(*record).EventHeader;
+    let provider = [
+        Provider::PowerShell,
+        Provider::DnsClient,
+        Provider::KernelProcess,
+        Provider::Amsi,
+    ]
+    .into_iter()
+    .find(|p| {
+        provider_guid(*p).is_some_and(|g| {
+            let id = &header.ProviderId;
+            (g.data1, g.data2, g.data3, g.data4) == (id.data1, id.data2, id.data3, id.data4)
+        })
+    })?;
+    let event_id = header.EventDescriptor.Id;
+
+    let mut fields = HashMap::new();
+    for (name, kind) in properties(provider, event_id) {
+        if let Some(value) = property(record, name, *kind) {
+            fields.insert(name.to_string(), value);
+        }
+    }
+    TelemetryEvent::from_record(TelemetryRecord {
+        provider,
+        event_id,
+        time: filetime(header.TimeStamp),
+        process_id: Some(header.ProcessId),
+        fields,
+    })
+}
+
+unsafe extern "system" fn callback(record: *mut EVENT_RECORD) {
+    let sender = &*((*record).UserContext as *const UnboundedSender<TelemetryEvent>);
+    if let Some(event) = telemetry_event(record) {
+        let _ = sender.send(event);
+    }
+}
+
+struct SessionProperties {
+    buffer: Vec<u64>,
+}
+
+impl SessionProperties {
+    fn new(name: &str) -> Self {
+        let name = wide(name);
+        let size = size_of::<EVENT_TRACE_PROPERTIES>() + name.len() * 2;
+        let mut buffer = vec![0u64; size.div_ceil(8)];
+        let properties = buffer.as_mut_ptr() as *mut EVENT_TRACE_PROPERTIES;
+        unsafe {
+            (*properties).Wnode.BufferSize = size as u32;
+            (*properties).Wnode.Flags = WNODE_FLAG_TRACED_GUID;
+            (*properties).Wnode.ClientContext = 1;
+            (*properties).LogFileMode = EVENT_TRACE_REAL_TIME_MODE;
+            (*properties).LoggerNameOffset = size_of::<EVENT_TRACE_PROPERTIES>() as u32;
+        }
+        Self { buffer }
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut EVENT_TRACE_PROPERTIES {
+        self.buffer.as_mut_ptr() as *mut EVENT_TRACE_PROPERTIES
+    }
+}
+
+fn stop_session(name: &str) -> u32 {
+    let wide_name = wide(name);
+    let mut properties = SessionProperties::new(name);
+    unsafe {
+        ControlTraceW(
+            0,
+            wide_name.as_ptr(),
+            properties.as_mut_ptr(),
+            EVENT_TRACE_CONTROL_STOP,
+        )
+    }
+}
+
+pub struct EtwSession {
+    name: String,
+    consumer: Option<JoinHandle<()>>,
+    _sender: Box<UnboundedSender<TelemetryEvent>>,
+}
+
+impl EtwSession {
+    pub fn start(providers: &[Provider]) -> Result<(Self, UnboundedReceiver<TelemetryEvent>)> {
+        Self::start_named(DEFAULT_SESSION_NAME, providers)
+    }
+
+    pub fn start_named(
+        name: &str,
+        providers: &[Provider],
+    ) -> Result<(Self, UnboundedReceiver<TelemetryEvent>)> {
+        let wide_name = wide(name);
+        let mut handle = 0;
+        let mut properties = SessionProperties::new(name);
+        let mut status =
+            unsafe { StartTraceW(&mut handle, wide_name.as_ptr(), properties.as_mut_ptr()) };
+        if status == ERROR_ALREADY_EXISTS {
+            warn!("Stopping stale ETW session {}", name);
+            stop_session(name);
+            properties = SessionProperties::new(name);
+            status =
+                unsafe { StartTraceW(&mut handle, wide_name.as_ptr(), properties.as_mut_ptr()) };
+        }
+        if status != ERROR_SUCCESS {
+            return Err(anyhow!("StartTrace failed with {}", status));
+        }
+
+        for provider in providers {
+            let Some(guid) = provider_guid(*provider) else {
+                warn!("{} is not collected through ETW", provider.name());
+                continue;
+            };
+            let status = unsafe {
+                EnableTraceEx2(
+                    handle,
+                    &guid,
+                    EVENT_CONTROL_CODE_ENABLE_PROVIDER,
+                    TRACE_LEVEL_VERBOSE,
+                    provider_keyword(*provider),
+                    0,
+                    0,
+                    null(),
+                )
+            };
+            if status != ERROR_SUCCESS {
+                stop_session(name);
+                return Err(anyhow!(
+                    "EnableTraceEx2 failed for {} with {}",
+                    provider.name(),
+                    status
+                ));
+            }
+        }
+
+        let (sender, receiver) = unbounded_channel();
+        let sender = Box::new(sender);
+        let mut logger_name = wide_name.clone();
+        let mut logfile: EVENT_TRACE_LOGFILEW = unsafe { std::mem::zeroed() };
+        logfile.LoggerName = logger_name.as_mut_ptr();
+        logfile.Anonymous1.ProcessTraceMode =
+            PROCESS_TRACE_MODE_REAL_TIME | PROCESS_TRACE_MODE_EVENT_RECORD;
+        logfile.Anonymous2.EventRecordCallback = Some(callback);
+        logfile.Context = &*sender as *const UnboundedSender<TelemetryEvent> as *mut c_void;
+
+        let trace = unsafe { OpenTraceW(&mut logfile) };
+        if trace == INVALID_PROCESSTRACE_HANDLE {
+            stop_session(name);
+            return Err(anyhow!("OpenTrace failed"));
+        }
+        let consumer = thread::spawn(move || {
+            let status = unsafe { ProcessTrace(&trace, 1, null(), null()) };
+            if status != ERROR_SUCCESS {
+                warn!("ProcessTrace exited with {}", status);
+            }
+            unsafe {
+                CloseTrace(trace);
+            }
+        });
+
+        Ok((
+            Self {
+                name: name.to_string(),
+                consumer: Some(consumer),
+                _sender: sender,
+            },
+            receiver,
+        ))
+    }
+}
+
+impl Drop for EtwSession {
+    fn drop(&mut self) {
+        stop_session(&self.name);
+        if let Some(consumer) = self.consumer.take() {
+            let _ = consumer.join();
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::auditd::AUDITD_CHANNEL;
+use crate::dns::parse_query_results;
+use crate::flags::{AccessMask, RegistryValueType};
+use crate::hashes::Hashes;
+use crate::network::NetworkConnect;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+use crate::telemetry::LINUX_SYSMON_PROVIDER;
+
+const NO_INTEGRITY_LEVEL: &str = "no level";
+const PATH_FIELDS: &[&str] = &["Image", "SourceImage", "TargetFilename", "ImageLoaded"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Windows,
+    Linux,
+}
+
+fn text(data: &HashMap<String, String>, field: &str) -> String {
+    data.get(field).cloned().unwrap_or_default()
+}
+
+fn opt_text(data: &HashMap<String, String>, field: &str) -> Option<String> {
+    data.get(field)
+        .filter(|v| !v.is_empty() && *v != "-")
+        .cloned()
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
+
+fn parse<T: FromStr>(data: &HashMap<String, String>, field: &str) -> Option<T> {
+    data.get(field).and_then(|v| v.trim().parse().ok())
+}
+
+fn parse_hex(data: &HashMap<String, String>, field: &str) -> Option<u64> {
+    let value = data.get(field)?.trim();
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))?;
+    u64::from_str_radix(digits, 16).ok()
+}
+
+fn parse_bool(data: &HashMap<String, String>, field: &str) -> Option<bool> {
+    data.get(field).map(|v| v.eq_ignore_ascii_case("true"))
+}
+
+fn hashes(data: &HashMap<String, String>, field: &str) -> Hashes {
+    data.get(field)
+        .map(|h| Hashes::parse(h))
+        .unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessCreateData {
+    pub process_guid: String,
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub command_line: String,
+    pub current_directory: String,
+    pub user: Option<String>,
+    pub logon_guid: Option<String>,
+    pub logon_id: Option<u64>,
+    pub integrity_level: Option<String>,
+    pub hashes: Hashes,
+    pub parent_process_guid: Option<String>,
+    pub parent_process_id: Option<u32>,
+    pub parent_image: Option<String>,
+    pub parent_command_line: Option<String>,
+    pub original_file_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessTerminateData {
+    pub process_guid: String,
+    pub process_id: Option<u32>,
+    pub image: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileCreateData {
+    pub process_guid: String,
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub target_filename: String,
+    pub creation_utc_time: Option<String>,
+    pub previous_creation_utc_time: Option<String>,
+    pub hashes: Hashes,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileDeleteData {
+    pub process_guid: String,
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub target_filename: String,
+    pub hashes: Hashes,
+    pub is_executable: Option<bool>,
+    pub archived: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageLoadData {
+    pub process_guid: Option<String>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub image_loaded: String,
+    pub hashes: Hashes,
+    pub signed: Option<bool>,
+    pub signature: Option<String>,
+    pub signature_status: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateRemoteThreadData {
+    pub source_process_guid: String,
+    pub source_process_id: Option<u32>,
+    pub source_image: String,
+    pub target_process_guid: String,
+    pub target_process_id: Option<u32>,
+    pub target_image: String,
+    pub new_thread_id: Option<u32>,
+    pub start_address: Option<u64>,
+    pub start_module: Option<String>,
+    pub start_function: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessAccessData {
+    pub source_process_guid: String,
+    pub source_process_id: Option<u32>,
+    pub source_image: String,
+    pub target_process_guid: String,
+    pub target_process_id: Option<u32>,
+    pub target_image: String,
+    pub granted_access: Option<AccessMask>,
+    pub call_trace: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegistryEventData {
+    pub event_type: String,
+    pub process_guid: String,
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub target_object: String,
+    pub details: Option<String>,
+    pub value_type: Option<RegistryValueType>,
+    pub new_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PipeEventData {
+    pub event_type: String,
+    pub process_guid: String,
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub pipe_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WmiFilterData {
+    pub operation: String,
+    pub user: Option<String>,
+    pub event_namespace: String,
+    pub name: String,
+    pub query: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WmiConsumerData {
+    pub operation: String,
+    pub user: Option<String>,
+    pub name: String,
+    pub consumer_type: String,
+    pub destination: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WmiBindingData {
+    pub operation: String,
+    pub user: Option<String>,
+    pub consumer: String,
+    pub filter: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnsQueryData {
+    pub process_guid: String,
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub query_name: String,
+    pub query_status: Option<u32>,
+    pub query_results: Vec<IpAddr>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceStateChangeData {
+    pub state: String,
+    pub version: Option<String>,
+    pub schema_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfigStateChangeData {
+    pub configuration: Option<String>,
+    pub configuration_file_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErrorReportData {
+    pub id: String,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum TypedEventData {
+    ProcessCreate(ProcessCreateData),
+    FileCreateTime(FileCreateData),
+    NetworkConnect(NetworkConnect),
+    ServiceStateChange(ServiceStateChangeData),
+    ProcessTerminate(ProcessTerminateData),
+    DriverLoad(ImageLoadData),
+    ImageLoad(ImageLoadData),
+    CreateRemoteThread(CreateRemoteThreadData),
+    ProcessAccess(ProcessAccessData),
+    FileCreate(FileCreateData),
+    RegistryEvent(RegistryEventData),
+    FileCreateStreamHash(FileCreateData),
+    ConfigStateChange(ConfigStateChangeData),
+    PipeEvent(PipeEventData),
+    WmiFilter(WmiFilterData),
+    WmiConsumer(WmiConsumerData),
+    WmiBinding(WmiBindingData),
+    DnsQuery(DnsQueryData),
+    FileDelete(FileDeleteData),
+    FileBlock(FileDeleteData),
+    FileExecutableDetected(FileDeleteData),
+    Error(ErrorReportData),
+    Other,
+}
+
+fn file_create(data: &HashMap<String, String>, hash_field: &str) -> FileCreateData {
+    FileCreateData {
+        process_guid: text(data, "ProcessGuid"),
+        process_id: parse(data, "ProcessId"),
+        image: text(data, "Image"),
+        target_filename: text(data, "TargetFilename"),
+        creation_utc_time: opt_text(data, "CreationUtcTime"),
+        previous_creation_utc_time: opt_text(data, "PreviousCreationUtcTime"),
+        hashes: hashes(data, hash_field),
+    }
+}
+
+fn file_delete(data: &HashMap<String, String>) -> FileDeleteData {
+    FileDeleteData {
+        process_guid: text(data, "ProcessGuid"),
+        process_id: parse(data, "ProcessId"),
+        image: text(data, "Image"),
+        target_filename: text(data, "TargetFilename"),
+        hashes: hashes(data, "Hashes"),
+        is_executable: parse_bool(data, "IsExecutable"),
+        archived: parse_bool(data, "Archived"),
+    }
+}
+
+fn image_load(data: &HashMap<String, String>) -> ImageLoadData {
+    ImageLoadData {
+        process_guid: opt_text(data, "ProcessGuid"),
+        process_id: parse(data, "ProcessId"),
+        image: opt_text(data, "Image"),
+        image_loaded: text(data, "ImageLoaded"),
+        hashes: hashes(data, "Hashes"),
+        signed: parse_bool(data, "Signed"),
+        signature: opt_text(data, "Signature"),
+        signature_status: opt_text(data, "SignatureStatus"),
+    }
+}
+
+impl SysmonEvent {
+    pub fn platform(&self) -> Platform {
+        let linux_channel = self
+            .channel
+            .as_deref()
+            .is_some_and(|c| c.starts_with(LINUX_SYSMON_PROVIDER) || c == AUDITD_CHANNEL);
+        let linux_path = PATH_FIELDS
+            .iter()
+            .find_map(|f| self.event_data.get(*f))
+            .is_some_and(|p| p.starts_with('/'));
+        if linux_channel || linux_path {
+            Platform::Linux
+        } else {
+            Platform::Windows
+        }
+    }
+
+    pub fn typed_data(&self) -> TypedEventData {
+        let data = &self.event_data;
+        match self.event_id {
+            SysmonEventId::PROCESS_CREATE => TypedEventData::ProcessCreate(ProcessCreateData {
+                process_guid: text(data, "ProcessGuid"),
+                process_id: parse(data, "ProcessId"),
+                image: text(data, "Image"),
+                command_line: text(data, "CommandLine"),
+                current_directory: text(data, "CurrentDirectory"),
+                user: opt_text(data, "User"),
+                logon_guid: opt_text(data, "LogonGuid"),
+                logon_id: parse_hex(data, "LogonId").or_else(|| parse(data, "LogonId")),
+                integrity_level: opt_text(data, "IntegrityLevel")
+                    .filter(|l| !l.eq_ignore_ascii_case(NO_INTEGRITY_LEVEL)),
+                hashes: hashes(data, "Hashes"),
+                parent_process_guid: opt_text(data, "ParentProcessGuid"),
+                parent_process_id: parse(data, "ParentProcessId"),
+                parent_image: opt_text(data, "ParentImage"),
+                parent_command_line: opt_text(data, "ParentCommandLine"),
+                original_file_name: opt_text(data, "OriginalFileName"),
+            }),
+            SysmonEventId::FILE_CREATE_TIME => {
+                TypedEventData::FileCreateTime(file_create(data, "Hashes"))
+            }
+            SysmonEventId::NETWORK_CONNECT => match NetworkConnect::from_event(self) {
+                Some(connect) => TypedEventData::NetworkConnect(connect),
+                None => TypedEventData::Other,
+            },
+            SysmonEventId::SERVICE_STATE_CHANGE => {
+                TypedEventData::ServiceStateChange(ServiceStateChangeData {
+                    state: text(data, "State"),
+                    version: opt_text(data, "Version"),
+                    schema_version: opt_text(data, "SchemaVersion"),
+                })
+            }
+            SysmonEventId::PROCESS_TERMINATE => {
+                TypedEventData::ProcessTerminate(ProcessTerminateData {
+                    process_guid: text(data, "ProcessGuid"),
+                    process_id: parse(data, "ProcessId"),
+                    image: text(data, "Image"),
+                })
+            }
+            SysmonEventId::DRIVER_LOAD => TypedEventData::DriverLoad(image_load(data)),
+            SysmonEventId::IMAGE_LOAD => TypedEventData::ImageLoad(image_load(data)),
+            SysmonEventId::CREATE_REMOTE_THREAD => {
+                TypedEventData::CreateRemoteThread(CreateRemoteThreadData {
+                    source_process_guid: text(data, "SourceProcessGuid"),
+                    source_process_id: parse(data, "SourceProcessId"),
+                    source_image: text(data, "SourceImage"),
+                    target_process_guid: text(data, "TargetProcessGuid"),
+                    target_process_id: parse(data, "TargetProcessId"),
+                    target_image: text(data, "TargetImage"),
+                    new_thread_id: parse(data, "NewThreadId"),
+                    start_address: parse_hex(data, "StartAddress"),
+                    start_module: opt_text(data, "StartModule"),
+                    start_function: opt_text(data, "StartFunction"),
+                })
+            }
+            SysmonEventId::PROCESS_ACCESS => TypedEventData::ProcessAccess(ProcessAccessData {
+                source_process_guid: text(data, "SourceProcessGUID"),
+                source_process_id: parse(data, "SourceProcessId"),
+                source_image: text(data, "SourceImage"),
+                target_process_guid: text(data, "TargetProcessGUID"),
+                target_process_id: parse(data, "TargetProcessId"),
+                target_image: text(data, "TargetImage"),
+                granted_access: data.get("GrantedAccess").and_then(|a| AccessMask::parse(a)),
+                call_trace: opt_text(data, "CallTrace"),
+            }),
+            SysmonEventId::FILE_CREATE => TypedEventData::FileCreate(file_create(data, "Hashes")),
+            SysmonEventId::REGISTRY_EVENT_ADD_DELETE
+            | SysmonEventId::REGISTRY_EVENT_SET
+            | SysmonEventId::REGISTRY_EVENT_RENAME => {
+                TypedEventData::RegistryEvent(RegistryEventData {
+                    event_type: text(data, "EventType"),
+                    process_guid: text(data, "ProcessGuid"),
+                    process_id: parse(data, "ProcessId"),
+                    image: text(data, "Image"),
+                    target_object: text(data, "TargetObject"),
+                    details: opt_text(data, "Details"),
+                    value_type: opt_text(data, "Details")
+                        .map(|d| RegistryValueType::from_details(&d)),
+                    new_name: opt_text(data, "NewName"),
+                })
+            }
+            SysmonEventId::FILE_CREATE_STREAM_HASH => {
+                TypedEventData::FileCreateStreamHash(file_create(data, "Hash"))
+            }
+            SysmonEventId::CONFIG_STATE_CHANGE => {
+                TypedEventData::ConfigStateChange(ConfigStateChangeData {
+                    configuration: opt_text(data, "Configuration"),
+                    configuration_file_hash: opt_text(data, "ConfigurationFileHash"),
+                })
+            }
+            SysmonEventId::PIPE_EVENT_CREATE | SysmonEventId::PIPE_EVENT_CONNECT => {
+                TypedEventData::PipeEvent(PipeEventData {
+                    event_type: text(data, "EventType"),
+                    process_guid: text(data, "ProcessGuid"),
+                    process_id: parse(data, "ProcessId"),
+                    image: text(data, "Image"),
+                    pipe_name: text(data, "PipeName"),
+                })
+            }
+            SysmonEventId::WMI_EVENT_FILTER => TypedEventData::WmiFilter(WmiFilterData {
+                operation: text(data, "Operation"),
+                user: opt_text(data, "User"),
+                event_namespace: unquote(&text(data, "EventNamespace")),
+                name: unquote(&text(data, "Name")),
+                query: unquote(&text(data, "Query")),
+            }),
+            SysmonEventId::WMI_EVENT_CONSUMER => TypedEventData::WmiConsumer(WmiConsumerData {
+                operation: text(data, "Operation"),
+                user: opt_text(data, "User"),
+                name: unquote(&text(data, "Name")),
+                consumer_type: text(data, "Type"),
+                destination: unquote(&text(data, "Destination")),
+            }),
+            SysmonEventId::WMI_EVENT_CONSUMER_FILTER => {
+                TypedEventData::WmiBinding(WmiBindingData {
+                    operation: text(data, "Operation"),
+                    user: opt_text(data, "User"),
+                    consumer: text(data, "Consumer"),
+                    filter: text(data, "Filter"),
+                })
+            }
+            SysmonEventId::DNS_QUERY => TypedEventData::DnsQuery(DnsQueryData {
+                process_guid: text(data, "ProcessGuid"),
+                process_id: parse(data, "ProcessId"),
+                image: text(data, "Image"),
+                query_name: text(data, "QueryName"),
+                query_status: parse(data, "QueryStatus"),
+                query_results: data
+                    .get("QueryResults")
+                    .map(|r| parse_query_results(r))
+                    .unwrap_or_default(),
+            }),
+            SysmonEventId::FILE_DELETE | SysmonEventId::FILE_DELETE_DETECTED => {
+                TypedEventData::FileDelete(file_delete(data))
+            }
+            SysmonEventId::FILE_BLOCK_EXECUTABLE | SysmonEventId::FILE_BLOCK_SHREDDING => {
+                TypedEventData::FileBlock(file_delete(data))
+            }
+            SysmonEventId::FILE_EXECUTABLE_DETECTED => {
+                TypedEventData::FileExecutableDetected(file_delete(data))
+            }
+            SysmonEventId::ERROR => TypedEventData::Error(ErrorReportData {
+                id: text(data, "ID"),
+                description: text(data, "Description"),
+            }),
+            _ => TypedEventData::Other,
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use roxmltree::{Document, Node};
+
+use crate::analysis_result::ExecutionLog;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const RULE_ELEMENT: &str = "Rule";
+const RULE_GROUP_ELEMENT: &str = "RuleGroup";
+const EVENT_FILTERING_ELEMENT: &str = "EventFiltering";
+
+fn event_ids(element: &str) -> Option<&'static [SysmonEventId]> {
+    let ids: &'static [SysmonEventId] = match element {
+        "ProcessCreate" => &[SysmonEventId::PROCESS_CREATE],
+        "FileCreateTime" => &[SysmonEventId::FILE_CREATE_TIME],
+        "NetworkConnect" => &[SysmonEventId::NETWORK_CONNECT],
+        "ProcessTerminate" => &[SysmonEventId::PROCESS_TERMINATE],
+        "DriverLoad" => &[SysmonEventId::DRIVER_LOAD],
+        "ImageLoad" => &[SysmonEventId::IMAGE_LOAD],
+        "CreateRemoteThread" => &[SysmonEventId::CREATE_REMOTE_THREAD],
+        "RawAccessRead" => &[SysmonEventId::RAW_ACCESS_READ],
+        "ProcessAccess" => &[SysmonEventId::PROCESS_ACCESS],
+        "FileCreate" => &[SysmonEventId::FILE_CREATE],
+        "RegistryEvent" => &[
+            SysmonEventId::REGISTRY_EVENT_ADD_DELETE,
+            SysmonEventId::REGISTRY_EVENT_SET,
+            SysmonEventId::REGISTRY_EVENT_RENAME,
+        ],
+        "FileCreateStreamHash" => &[SysmonEventId::FILE_CREATE_STREAM_HASH],
+        "PipeEvent" => &[
+            SysmonEventId::PIPE_EVENT_CREATE,
+            SysmonEventId::PIPE_EVENT_CONNECT,
+        ],
+        "WmiEvent" => &[
+            SysmonEventId::WMI_EVENT_FILTER,
+            SysmonEventId::WMI_EVENT_CONSUMER,
+            SysmonEventId::WMI_EVENT_CONSUMER_FILTER,
+        ],
+        "DnsQuery" => &[SysmonEventId::DNS_QUERY],
+        "FileDelete" => &[SysmonEventId::FILE_DELETE],
+        "ClipboardChange" => &[SysmonEventId::CLIPBOARD_CHANGE],
+        "ProcessTampering" => &[SysmonEventId::PROCESS_TAMPERING],
+        "FileDeleteDetected" => &[SysmonEventId::FILE_DELETE_DETECTED],
+        "FileBlockExecutable" => &[SysmonEventId::FILE_BLOCK_EXECUTABLE],
+        "FileBlockShredding" => &[SysmonEventId::FILE_BLOCK_SHREDDING],
+        "FileExecutableDetected" => &[SysmonEventId::FILE_EXECUTABLE_DETECTED],
+        _ => return None,
+    };
+    Some(ids)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Is,
+    IsNot,
+    IsAny,
+    Contains,
+    ContainsAny,
+    ContainsAll,
+    Excludes,
+    ExcludesAny,
+    ExcludesAll,
+    BeginWith,
+    NotBeginWith,
+    EndWith,
+    NotEndWith,
+    LessThan,
+    MoreThan,
+    Image,
+}
+
+impl Condition {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "is" => Ok(Self::Is),
+            "is not" => Ok(Self::IsNot),
+            "is any" => Ok(Self::IsAny),
+            "contains" => Ok(Self::Contains),
+            "contains any" => Ok(Self::ContainsAny),
+            "contains all" => Ok(Self::ContainsAll),
+            "excludes" => Ok(Self::Excludes),
+            "excludes any" => Ok(Self::ExcludesAny),
+            "excludes all" => Ok(Self::ExcludesAll),
+            "begin with" => Ok(Self::BeginWith),
+            "not begin with" => Ok(Self::NotBeginWith),
+            "end with" => Ok(Self::EndWith),
+            "not end with" => Ok(Self::NotEndWith),
+            "less than" => Ok(Self::LessThan),
+            "more than" => Ok(Self::MoreThan),
+            "image" => Ok(Self::Image),
+            _ => Err(anyhow!("Unknown condition '{}'", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldCondition {
+    pub field: String,
+    pub condition: Condition,
+    pub value: String,
+}
+
+impl FieldCondition {
+    fn values(&self) -> impl Iterator<Item = String> + '_ {
+        self.value
+            .split(';')
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty())
+    }
+
+    pub fn matches(&self, event: &SysmonEvent) -> bool {
+        let Some(actual) = event.event_data.get(&self.field) else {
+            return false;
+        };
+        let actual = actual.to_lowercase();
+        let value = self.value.trim().to_lowercase();
+        match self.condition {
+            Condition::Is => actual == value,
+            Condition::IsNot => actual != value,
+            Condition::IsAny => self.values().any(|v| actual == v),
+            Condition::Contains => actual.contains(&value),
+            Condition::ContainsAny => self.values().any(|v| actual.contains(&v)),
+            Condition::ContainsAll => self.values().all(|v| actual.contains(&v)),
+            Condition::Excludes => !actual.contains(&value),
+            Condition::ExcludesAny => self.values().any(|v| !actual.contains(&v)),
+            Condition::ExcludesAll => self.values().all(|v| !actual.contains(&v)),
+            Condition::BeginWith => actual.starts_with(&value),
+            Condition::NotBeginWith => !actual.starts_with(&value),
+            Condition::EndWith => actual.ends_with(&value),
+            Condition::NotEndWith => !actual.ends_with(&value),
+            Condition::LessThan => matches!(
+                (actual.parse::<i64>(), value.parse::<i64>()),
+                (Ok(a), Ok(v)) if a < v
+            ),
+            Condition::MoreThan => matches!(
+                (actual.parse::<i64>(), value.parse::<i64>()),
+                (Ok(a), Ok(v)) if a > v
+            ),
+            Condition::Image => {
+                actual == value || actual.rsplit(['\\', '/']).next() == Some(value.as_str())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    And,
+    Or,
+}
+
+impl Relation {
+    fn from_attribute(node: Node, default: Self) -> Self {
+        match node.attribute("groupRelation") {
+            Some(r) if r.eq_ignore_ascii_case("and") => Self::And,
+            Some(r) if r.eq_ignore_ascii_case("or") => Self::Or,
+            _ => default,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: Option<String>,
+    pub relation: Relation,
+    pub conditions: Vec<FieldCondition>,
+}
+
+impl Rule {
+    pub fn matches(&self, event: &SysmonEvent) -> bool {
+        match self.relation {
+            Relation::And => self.conditions.iter().all(|c| c.matches(event)),
+            Relation::Or => self.conditions.iter().any(|c| c.matches(event)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnMatch {
+    Include,
+    Exclude,
+}
+
+#[derive(Debug, Clone)]
+struct EventRules {
+    event_ids: &'static [SysmonEventId],
+    on_match: OnMatch,
+    rules: Vec<Rule>,
+}
+
+#[derive(Default, Clone)]
+pub struct EventFilter {
+    rules: Vec<EventRules>,
+}
+
+impl fmt::Debug for EventFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.rules
+                    .iter()
+                    .map(|r| (r.event_ids[0].name(), r.on_match, r.rules.len())),
+            )
+            .finish()
+    }
+}
+
+fn field_condition(node: Node) -> Result<FieldCondition> {
+    Ok(FieldCondition {
+        field: node.tag_name().name().to_string(),
+        condition: Condition::from_name(node.attribute("condition").unwrap_or("is"))?,
+        value: node.text().unwrap_or_default().to_string(),
+    })
+}
+
+impl EventFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let document = Document::parse(xml)?;
+        let filtering = document
+            .descendants()
+            .find(|n| n.has_tag_name(EVENT_FILTERING_ELEMENT))
+            .context("No EventFiltering section in the configuration")?;
+
+        let mut filter = Self::new();
+        for child in filtering.children().filter(Node::is_element) {
+            if child.has_tag_name(RULE_GROUP_ELEMENT) {
+                let relation = Relation::from_attribute(child, Relation::Or);
+                for event in child.children().filter(Node::is_element) {
+                    filter.add_event(event, relation)?;
+                }
+            } else {
+                filter.add_event(child, Relation::Or)?;
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let xml = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_xml(&xml).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    fn add_event(&mut self, node: Node, relation: Relation) -> Result<()> {
+        let element = node.tag_name().name();
+        let Some(event_ids) = event_ids(element) else {
+            bail!("Unknown event type '{}'", element);
+        };
+        let on_match = match node.attribute("onmatch") {
+            Some(m) if m.eq_ignore_ascii_case("include") => OnMatch::Include,
+            Some(m) if m.eq_ignore_ascii_case("exclude") => OnMatch::Exclude,
+            _ => bail!("{} has no valid onmatch attribute", element),
+        };
+
+        let mut rules = Vec::new();
+        let mut fields = Vec::new();
+        for child in node.children().filter(Node::is_element) {
+            if child.has_tag_name(RULE_ELEMENT) {
+                rules.push(Rule {
+                    name: child.attribute("name").map(str::to_string),
+                    relation: Relation::from_attribute(child, Relation::Or),
+                    conditions: child
+                        .children()
+                        .filter(Node::is_element)
+                        .map(field_condition)
+                        .collect::<Result<_>>()?,
+                });
+            } else {
+                fields.push(field_condition(child)?);
+            }
+        }
+        match relation {
+            Relation::And if !fields.is_empty() => rules.push(Rule {
+                name: None,
+                relation,
+                conditions: fields,
+            }),
+            _ => rules.extend(fields.into_iter().map(|field| Rule {
+                name: None,
+                relation: Relation::Or,
+                conditions: vec![field],
+            })),
+        }
+
+        self.rules.push(EventRules {
+            event_ids,
+            on_match,
+            rules,
+        });
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.iter().map(|r| r.rules.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn keep(&self, event: &SysmonEvent) -> bool {
+        let mut configured = false;
+        let mut included = false;
+        for rules in self
+            .rules
+            .iter()
+            .filter(|r| r.event_ids.contains(&event.event_id))
+        {
+            let matched = rules.rules.iter().any(|r| r.matches(event));
+            match rules.on_match {
+                OnMatch::Exclude if matched => return false,
+                OnMatch::Exclude => (),
+                OnMatch::Include => {
+                    configured = true;
+                    included |= matched;
+                }
+            }
+        }
+        !configured || included
+    }
+
+    pub fn apply(&self, events: Vec<SysmonEvent>) -> Vec<SysmonEvent> {
+        events.into_iter().filter(|e| self.keep(e)).collect()
+    }
+
+    pub fn apply_log(&self, log: &mut ExecutionLog) {
+        let events = std::mem::take(&mut log.sysmon_events);
+        log.sysmon_events = self.apply(events);
+    }
+}
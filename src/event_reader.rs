@@ -0,0 +1,114 @@
+use std::io::BufRead;
+
+use anyhow::Result;
+
+use crate::sysmon_event::{ParseOptions, SysmonEvent};
+
+const START_TAG: &[u8] = b"<Event";
+const END_TAG: &[u8] = b"</Event>";
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn find_start(buffer: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(i) = find(&buffer[offset..], START_TAG) {
+        let start = offset + i;
+        match buffer.get(start + START_TAG.len()) {
+            Some(b' ' | b'>' | b'\t' | b'\r' | b'\n') => return Some(start),
+            Some(_) => offset = start + 1,
+            None => return None,
+        }
+    }
+    None
+}
+
+pub struct SysmonEventReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    options: ParseOptions,
+    eof: bool,
+    keep_truncated: bool,
+}
+
+impl<R: BufRead> SysmonEventReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            options: ParseOptions::default(),
+            eof: false,
+            keep_truncated: false,
+        }
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub(crate) fn keep_truncated(mut self) -> Self {
+        self.keep_truncated = true;
+        self
+    }
+
+    pub(crate) fn next_element(&mut self) -> Result<Option<String>> {
+        match self.next_raw()? {
+            Some(element) => Ok(Some(String::from_utf8(element)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn next_raw(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            match find_start(&self.buffer) {
+                Some(start) => {
+                    if let Some(i) = find(&self.buffer[start..], END_TAG) {
+                        let end = start + i + END_TAG.len();
+                        return Ok(Some(self.buffer.drain(..end).skip(start).collect()));
+                    }
+                    if self.eof && self.keep_truncated {
+                        return Ok(Some(self.buffer.drain(..).skip(start).collect()));
+                    }
+                }
+                None => {
+                    let keep = START_TAG.len().min(self.buffer.len());
+                    self.buffer.drain(..self.buffer.len() - keep);
+                }
+            }
+
+            if self.eof {
+                return Ok(None);
+            }
+            let chunk = self.reader.fill_buf()?;
+            if chunk.is_empty() {
+                self.eof = true;
+                continue;
+            }
+            self.buffer.extend_from_slice(chunk);
+            let n = chunk.len();
+            self.reader.consume(n);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for SysmonEventReader<R> {
+    type Item = Result<SysmonEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_element() {
+            Ok(Some(xml)) => {
+                Some(SysmonEvent::from_xml_with(&xml, &self.options).map(|parsed| parsed.event))
+            }
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl SysmonEvent {
+    pub fn from_xml_many(xml: &str) -> SysmonEventReader<&[u8]> {
+        SysmonEventReader::new(xml.as_bytes())
+    }
+}
@@ -0,0 +1,27 @@
+use std::path::Path;
+
+use anyhow::Result;
+use evtx::EvtxParser;
+
+use crate::sysmon_event::SysmonEvent;
+
+pub struct EvtxReader {
+    parser: EvtxParser<std::fs::File>,
+}
+
+impl EvtxReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            parser: EvtxParser::from_path(path)?,
+        })
+    }
+
+    pub fn events(&mut self) -> impl Iterator<Item = Result<SysmonEvent>> + '_ {
+        self.parser.records().map(|record| {
+            let record = record?;
+            let mut event = SysmonEvent::from_xml(&record.data)?;
+            event.record_id.get_or_insert(record.event_record_id);
+            Ok(event)
+        })
+    }
+}
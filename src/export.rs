@@ -0,0 +1,12 @@
+pub mod archive;
+pub mod cef;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+pub mod elastic;
+pub mod forward;
+pub mod graph;
+pub mod leef;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stix;
+pub mod timesketch;
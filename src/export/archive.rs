@@ -0,0 +1,289 @@
+use std::io::{Cursor, Seek, Write};
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use md5::Md5;
+use serde::Serialize;
+use serde_json::json;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use zip::unstable::write::FileOptionsExt;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::analysis_result::{artifact_dir, sample_path};
+use crate::pcap::PCAP_FILE_NAME;
+use crate::report::SandboxReport;
+
+pub const DEFAULT_PASSWORD: &str = "infected";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const REPORT_FILE: &str = "report.json";
+
+const CART_MAGIC: &[u8; 4] = b"CART";
+const CART_FOOTER_MAGIC: &[u8; 4] = b"TRAC";
+const CART_VERSION: u16 = 1;
+const CART_DEFAULT_KEY: [u8; 16] = [3, 1, 4, 1, 5, 9, 2, 6, 3, 1, 4, 1, 5, 9, 2, 6];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    Zip,
+    Cart,
+}
+
+impl std::str::FromStr for ArchiveFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "zip" => Ok(Self::Zip),
+            "cart" => Ok(Self::Cart),
+            _ => bail!("Unknown archive format '{}', expected zip or cart", s),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Sample,
+    DroppedFile,
+    MemoryDump,
+    Pcap,
+    Report,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub original_path: Option<String>,
+    pub size: u64,
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Manifest {
+    pub analysis_id: String,
+    pub sample_hash: String,
+    pub execution_id: String,
+    pub created: DateTime<Utc>,
+    pub format: ArchiveFormat,
+    pub password: Option<String>,
+    pub entries: Vec<ManifestEntry>,
+}
+
+pub struct ArchiveOptions {
+    pub format: ArchiveFormat,
+    pub password: String,
+    pub include_sample: bool,
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> Self {
+        Self {
+            format: ArchiveFormat::Zip,
+            password: DEFAULT_PASSWORD.to_string(),
+            include_sample: true,
+        }
+    }
+}
+
+struct Rc4 {
+    state: [u8; 256],
+    i: u8,
+    j: u8,
+}
+
+impl Rc4 {
+    fn new(key: &[u8]) -> Self {
+        let mut state = [0u8; 256];
+        for (i, s) in state.iter_mut().enumerate() {
+            *s = i as u8;
+        }
+        let mut j: u8 = 0;
+        for i in 0..256 {
+            j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+            state.swap(i, j as usize);
+        }
+        Self { state, i: 0, j: 0 }
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        for byte in data {
+            self.i = self.i.wrapping_add(1);
+            self.j = self.j.wrapping_add(self.state[self.i as usize]);
+            self.state.swap(self.i as usize, self.j as usize);
+            let k = self.state
+                [self.state[self.i as usize].wrapping_add(self.state[self.j as usize]) as usize];
+            *byte ^= k;
+        }
+    }
+}
+
+pub fn to_cart(name: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let mut header = serde_json::to_vec(&json!({ "name": name }))?;
+    Rc4::new(&CART_DEFAULT_KEY).apply(&mut header);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    let mut body = encoder.finish()?;
+    Rc4::new(&CART_DEFAULT_KEY).apply(&mut body);
+
+    let mut footer = serde_json::to_vec(&json!({
+        "length": data.len().to_string(),
+        "md5": format!("{:x}", Md5::digest(data)),
+        "sha1": format!("{:x}", Sha1::digest(data)),
+        "sha256": format!("{:x}", Sha256::digest(data)),
+    }))?;
+    Rc4::new(&CART_DEFAULT_KEY).apply(&mut footer);
+
+    let mut cart = Vec::with_capacity(38 + header.len() + body.len() + footer.len() + 28);
+    cart.extend_from_slice(CART_MAGIC);
+    cart.extend_from_slice(&CART_VERSION.to_le_bytes());
+    cart.extend_from_slice(&0u64.to_le_bytes());
+    cart.extend_from_slice(&CART_DEFAULT_KEY);
+    cart.extend_from_slice(&(header.len() as u64).to_le_bytes());
+    cart.extend_from_slice(&header);
+    cart.extend_from_slice(&body);
+    let footer_position = cart.len() as u64;
+    cart.extend_from_slice(&footer);
+    cart.extend_from_slice(CART_FOOTER_MAGIC);
+    cart.extend_from_slice(&0u64.to_le_bytes());
+    cart.extend_from_slice(&footer_position.to_le_bytes());
+    cart.extend_from_slice(&(footer.len() as u64).to_le_bytes());
+    Ok(cart)
+}
+
+fn entry(
+    name: String,
+    kind: EntryKind,
+    original_path: Option<String>,
+    data: &[u8],
+) -> ManifestEntry {
+    ManifestEntry {
+        name,
+        kind,
+        original_path,
+        size: data.len() as u64,
+        md5: format!("{:x}", Md5::digest(data)),
+        sha1: format!("{:x}", Sha1::digest(data)),
+        sha256: format!("{:x}", Sha256::digest(data)),
+    }
+}
+
+fn read_if_exists(path: &str) -> Result<Option<Vec<u8>>> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+    Ok(Some(std::fs::read(path)?))
+}
+
+fn collect(
+    report: &SandboxReport,
+    options: &ArchiveOptions,
+) -> Result<Vec<(ManifestEntry, Vec<u8>)>> {
+    let dir = artifact_dir(&report.id, &report.execution_id);
+    let mut files = Vec::new();
+
+    if options.include_sample {
+        if let Some(data) = read_if_exists(&sample_path(&report.id))? {
+            files.push((
+                entry("sample/sample".to_string(), EntryKind::Sample, None, &data),
+                data,
+            ));
+        }
+    }
+    for artifact in &report.artifacts {
+        let path = format!("{}/{}", dir, artifact.file_name());
+        if let Some(data) = read_if_exists(&path)? {
+            let name = format!("dropped/{}", artifact.file_name());
+            files.push((
+                entry(
+                    name,
+                    EntryKind::DroppedFile,
+                    Some(artifact.path.clone()),
+                    &data,
+                ),
+                data,
+            ));
+        }
+    }
+    for memory in &report.memory {
+        let path = format!("{}/{}", dir, memory.dump);
+        if let Some(data) = read_if_exists(&path)? {
+            let name = format!("memory/{}", memory.dump);
+            files.push((entry(name, EntryKind::MemoryDump, None, &data), data));
+        }
+    }
+    if let Some(data) = read_if_exists(&format!("{}/{}", dir, PCAP_FILE_NAME))? {
+        let name = format!("network/{}", PCAP_FILE_NAME);
+        files.push((entry(name, EntryKind::Pcap, None, &data), data));
+    }
+    let data = report.to_json()?.into_bytes();
+    files.push((
+        entry(REPORT_FILE.to_string(), EntryKind::Report, None, &data),
+        data,
+    ));
+    Ok(files)
+}
+
+pub fn export_run<W: Write + Seek>(
+    report: &SandboxReport,
+    options: &ArchiveOptions,
+    writer: W,
+) -> Result<Manifest> {
+    let files = collect(report, options)?;
+    let mut manifest = Manifest {
+        analysis_id: report.id.clone(),
+        sample_hash: report.hash.clone(),
+        execution_id: report.execution_id.clone(),
+        created: Utc::now(),
+        format: options.format,
+        password: None,
+        entries: Vec::new(),
+    };
+
+    let mut zip = ZipWriter::new(writer);
+    let plain = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (mut entry, data) in files {
+        match options.format {
+            ArchiveFormat::Zip => {
+                let encrypted = plain.with_deprecated_encryption(options.password.as_bytes());
+                zip.start_file(entry.name.as_str(), encrypted)?;
+                zip.write_all(&data)?;
+            }
+            ArchiveFormat::Cart if entry.kind == EntryKind::Report => {
+                zip.start_file(entry.name.as_str(), plain)?;
+                zip.write_all(&data)?;
+            }
+            ArchiveFormat::Cart => {
+                let original = entry.name.rsplit('/').next().unwrap_or_default();
+                let cart = to_cart(original, &data)?;
+                entry.name = format!("{}.cart", entry.name);
+                zip.start_file(entry.name.as_str(), plain)?;
+                zip.write_all(&cart)?;
+            }
+        }
+        manifest.entries.push(entry);
+    }
+    if options.format == ArchiveFormat::Zip {
+        manifest.password = Some(options.password.clone());
+    }
+
+    zip.start_file(MANIFEST_FILE, plain)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    zip.finish()?;
+    Ok(manifest)
+}
+
+pub fn export_run_to_vec(report: &SandboxReport, options: &ArchiveOptions) -> Result<Vec<u8>> {
+    let mut buffer = Cursor::new(Vec::new());
+    export_run(report, options, &mut buffer)?;
+    Ok(buffer.into_inner())
+}
@@ -0,0 +1,160 @@
+use crate::report::{Detection, SandboxReport};
+use crate::sysmon_event::SysmonEvent;
+
+pub const VENDOR: &str = "and-es";
+pub const PRODUCT: &str = "malware-analysis-sandbox";
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const EVENT_FIELDS: &[(&str, &str)] = &[
+    ("User", "suser"),
+    ("Image", "sproc"),
+    ("ProcessId", "spid"),
+    ("SourceImage", "sproc"),
+    ("SourceProcessId", "spid"),
+    ("TargetImage", "dproc"),
+    ("TargetProcessId", "dpid"),
+    ("ParentImage", "deviceProcessName"),
+    ("TargetFilename", "filePath"),
+    ("ImageLoaded", "filePath"),
+    ("Hashes", "fileHash"),
+    ("Protocol", "proto"),
+    ("SourceIp", "src"),
+    ("SourcePort", "spt"),
+    ("SourceHostname", "shost"),
+    ("DestinationIp", "dst"),
+    ("DestinationPort", "dpt"),
+    ("DestinationHostname", "dhost"),
+    ("QueryName", "request"),
+    ("TargetObject", "request"),
+    ("RuleName", "cat"),
+];
+
+const CUSTOM_FIELDS: &[&str] = &[
+    "CommandLine",
+    "ProcessGuid",
+    "ParentCommandLine",
+    "ParentProcessGuid",
+    "Details",
+    "QueryResults",
+];
+
+pub fn escape_header(value: &str) -> String {
+    value.replace('\\', r"\\").replace('|', r"\|")
+}
+
+pub fn escape_extension(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '=' => escaped.push_str(r"\="),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn severity(level: Option<&str>) -> u8 {
+    match level.map(str::to_lowercase).as_deref() {
+        Some("informational") => 1,
+        Some("low") => 3,
+        Some("medium") => 5,
+        Some("high") => 8,
+        Some("critical") => 10,
+        _ => 5,
+    }
+}
+
+fn line(signature_id: &str, name: &str, severity: u8, extension: &[(String, String)]) -> String {
+    let extension: Vec<String> = extension
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, escape_extension(v)))
+        .collect();
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|{}|{}",
+        escape_header(VENDOR),
+        escape_header(PRODUCT),
+        escape_header(VERSION),
+        escape_header(signature_id),
+        escape_header(name),
+        severity,
+        extension.join(" ")
+    )
+}
+
+fn event_extension(event: &SysmonEvent) -> Vec<(String, String)> {
+    let mut extension = vec![(
+        "rt".to_string(),
+        event.time_created.timestamp_millis().to_string(),
+    )];
+    if let Some(computer) = &event.computer {
+        extension.push(("dvchost".to_string(), computer.clone()));
+    }
+    if let Some(record_id) = event.record_id {
+        extension.push(("externalId".to_string(), record_id.to_string()));
+    }
+    for (field, key) in EVENT_FIELDS {
+        let Some(value) = event.event_data.get(*field).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        if extension.iter().any(|(k, _)| k == key) {
+            continue;
+        }
+        extension.push((key.to_string(), value.clone()));
+    }
+    let custom = CUSTOM_FIELDS
+        .iter()
+        .filter_map(|f| Some((*f, event.event_data.get(*f).filter(|v| !v.is_empty())?)));
+    for (i, (label, value)) in custom.take(6).enumerate() {
+        extension.push((format!("cs{}", i + 1), value.clone()));
+        extension.push((format!("cs{}Label", i + 1), label.to_string()));
+    }
+    extension
+}
+
+pub fn event_to_cef(event: &SysmonEvent) -> String {
+    line(
+        &event.event_id.value().to_string(),
+        event.event_id.name(),
+        1,
+        &event_extension(event),
+    )
+}
+
+pub fn detection_to_cef(report: &SandboxReport, detection: &Detection) -> String {
+    let mut extension = match detection.events.first() {
+        Some(event) => event_extension(event),
+        None => vec![("rt".to_string(), report.time.timestamp_millis().to_string())],
+    };
+    extension.retain(|(k, _)| !k.starts_with("cs") && k != "cat");
+    extension.push(("cat".to_string(), detection.source.clone()));
+    extension.push(("cs1".to_string(), report.id.clone()));
+    extension.push(("cs1Label".to_string(), "AnalysisId".to_string()));
+    extension.push(("cs2".to_string(), report.hash.clone()));
+    extension.push(("cs2Label".to_string(), "SampleHash".to_string()));
+    extension.push(("cs3".to_string(), detection.tags.join(",")));
+    extension.push(("cs3Label".to_string(), "Tags".to_string()));
+    extension.push(("cnt".to_string(), detection.events.len().to_string()));
+    line(
+        &format!("{}:{}", detection.source, detection.name),
+        &detection.name,
+        severity(detection.level.as_deref()),
+        &extension,
+    )
+}
+
+pub fn report_to_cef(report: &SandboxReport) -> Vec<String> {
+    report
+        .events
+        .iter()
+        .map(event_to_cef)
+        .chain(
+            report
+                .detections
+                .iter()
+                .map(|d| detection_to_cef(report, d)),
+        )
+        .collect()
+}
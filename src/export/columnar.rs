@@ -0,0 +1,151 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{
+    Array, ArrayRef, MapBuilder, StringArray, StringBuilder, TimestampMicrosecondArray,
+    UInt64Array, UInt8Array,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::analysis_result::ExecutionLog;
+use crate::sysmon_event::SysmonEvent;
+
+pub const DEFAULT_BATCH_SIZE: usize = 65536;
+
+const PROMOTED_FIELDS: &[(&str, &str)] = &[
+    ("process_guid", "ProcessGuid"),
+    ("image", "Image"),
+    ("command_line", "CommandLine"),
+    ("parent_image", "ParentImage"),
+    ("target_filename", "TargetFilename"),
+    ("target_object", "TargetObject"),
+    ("destination_ip", "DestinationIp"),
+    ("destination_port", "DestinationPort"),
+    ("query_name", "QueryName"),
+    ("hashes", "Hashes"),
+];
+
+fn event_data_builder() -> MapBuilder<StringBuilder, StringBuilder> {
+    MapBuilder::new(None, StringBuilder::new(), StringBuilder::new())
+}
+
+pub fn event_schema() -> SchemaRef {
+    let mut fields = vec![
+        Field::new("execution_id", DataType::Utf8, false),
+        Field::new(
+            "time",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("event_id", DataType::UInt8, false),
+        Field::new("record_id", DataType::UInt64, true),
+        Field::new("computer", DataType::Utf8, true),
+        Field::new("channel", DataType::Utf8, true),
+    ];
+    fields.extend(
+        PROMOTED_FIELDS
+            .iter()
+            .map(|(name, _)| Field::new(*name, DataType::Utf8, true)),
+    );
+    fields.push(Field::new(
+        "event_data",
+        event_data_builder().finish().data_type().clone(),
+        false,
+    ));
+    Arc::new(Schema::new(fields))
+}
+
+pub fn to_record_batch(execution_id: &str, events: &[SysmonEvent]) -> Result<RecordBatch> {
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(vec![execution_id; events.len()])),
+        Arc::new(
+            TimestampMicrosecondArray::from(
+                events
+                    .iter()
+                    .map(|e| e.time_created.timestamp_micros())
+                    .collect::<Vec<_>>(),
+            )
+            .with_timezone("UTC"),
+        ),
+        Arc::new(UInt8Array::from(
+            events
+                .iter()
+                .map(|e| e.event_id.value())
+                .collect::<Vec<_>>(),
+        )),
+        Arc::new(UInt64Array::from(
+            events.iter().map(|e| e.record_id).collect::<Vec<_>>(),
+        )),
+        Arc::new(StringArray::from(
+            events
+                .iter()
+                .map(|e| e.computer.as_deref())
+                .collect::<Vec<_>>(),
+        )),
+        Arc::new(StringArray::from(
+            events
+                .iter()
+                .map(|e| e.channel.as_deref())
+                .collect::<Vec<_>>(),
+        )),
+    ];
+    for (_, key) in PROMOTED_FIELDS {
+        columns.push(Arc::new(StringArray::from(
+            events
+                .iter()
+                .map(|e| e.event_data.get(*key).map(String::as_str))
+                .collect::<Vec<_>>(),
+        )));
+    }
+
+    let mut event_data = event_data_builder();
+    for event in events {
+        let mut entries: Vec<_> = event.event_data.iter().collect();
+        entries.sort();
+        for (key, value) in entries {
+            event_data.keys().append_value(key);
+            event_data.values().append_value(value);
+        }
+        event_data.append(true)?;
+    }
+    columns.push(Arc::new(event_data.finish()));
+
+    Ok(RecordBatch::try_new(event_schema(), columns)?)
+}
+
+pub fn record_batches(logs: &[ExecutionLog], batch_size: usize) -> Result<Vec<RecordBatch>> {
+    let mut batches = Vec::new();
+    for log in logs {
+        for chunk in log.sysmon_events.chunks(batch_size.max(1)) {
+            batches.push(to_record_batch(&log.id, chunk)?);
+        }
+    }
+    Ok(batches)
+}
+
+pub fn write_ipc<W: Write>(batches: &[RecordBatch], writer: W) -> Result<()> {
+    let mut writer = FileWriter::try_new(writer, &event_schema())?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+pub fn write_parquet<W: Write + Send>(batches: &[RecordBatch], writer: W) -> Result<()> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, event_schema(), Some(properties))?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.close()?;
+    Ok(())
+}
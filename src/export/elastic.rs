@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+use crate::dns::parse_query_results;
+use crate::hashes::Hashes;
+use crate::network::NetworkConnect;
+use crate::report::{Detection, SandboxReport};
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub const DEFAULT_INDEX: &str = "sandbox-sysmon";
+#[cfg(feature = "elastic")]
+const DEFAULT_BATCH_SIZE: usize = 500;
+const ECS_VERSION: &str = "8.11.0";
+
+fn timestamp<Tz: chrono::TimeZone>(time: &DateTime<Tz>) -> String {
+    time.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn field<'a>(data: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    data.get(name)
+        .map(|v| v.as_str())
+        .filter(|v| !v.is_empty() && *v != "-")
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['\\', '/']).next().unwrap_or(path)
+}
+
+fn directory(path: &str) -> Option<&str> {
+    path.rfind(['\\', '/']).map(|i| &path[..i])
+}
+
+fn hashes(data: &HashMap<String, String>, name: &str) -> Value {
+    let hashes = field(data, name).map(Hashes::parse).unwrap_or_default();
+    json!({
+        "md5": hashes.md5,
+        "sha1": hashes.sha1,
+        "sha256": hashes.sha256,
+    })
+}
+
+fn prune(value: Value) -> Option<Value> {
+    match value {
+        Value::Null => None,
+        Value::Object(map) => {
+            let map: Map<String, Value> = map
+                .into_iter()
+                .filter_map(|(k, v)| Some((k, prune(v)?)))
+                .collect();
+            (!map.is_empty()).then_some(Value::Object(map))
+        }
+        Value::Array(values) => {
+            let values: Vec<Value> = values.into_iter().filter_map(prune).collect();
+            (!values.is_empty()).then_some(Value::Array(values))
+        }
+        Value::String(s) if s.is_empty() => None,
+        value => Some(value),
+    }
+}
+
+fn categorize(id: &SysmonEventId) -> (&'static [&'static str], &'static [&'static str]) {
+    match *id {
+        SysmonEventId::PROCESS_CREATE => (&["process"], &["start"]),
+        SysmonEventId::PROCESS_TERMINATE => (&["process"], &["end"]),
+        SysmonEventId::PROCESS_ACCESS
+        | SysmonEventId::CREATE_REMOTE_THREAD
+        | SysmonEventId::PROCESS_TAMPERING => (&["process"], &["access"]),
+        SysmonEventId::NETWORK_CONNECT => (&["network"], &["connection", "start"]),
+        SysmonEventId::DNS_QUERY => (&["network"], &["protocol", "info"]),
+        SysmonEventId::FILE_CREATE | SysmonEventId::FILE_CREATE_STREAM_HASH => {
+            (&["file"], &["creation"])
+        }
+        SysmonEventId::FILE_CREATE_TIME => (&["file"], &["change"]),
+        SysmonEventId::FILE_DELETE | SysmonEventId::FILE_DELETE_DETECTED => {
+            (&["file"], &["deletion"])
+        }
+        SysmonEventId::IMAGE_LOAD | SysmonEventId::DRIVER_LOAD => (&["library"], &["start"]),
+        SysmonEventId::REGISTRY_EVENT_ADD_DELETE
+        | SysmonEventId::REGISTRY_EVENT_SET
+        | SysmonEventId::REGISTRY_EVENT_RENAME => (&["registry"], &["change"]),
+        _ => (&[], &["info"]),
+    }
+}
+
+fn process(data: &HashMap<String, String>) -> Value {
+    let guid = field(data, "ProcessGuid").or_else(|| field(data, "SourceProcessGuid"));
+    let image = field(data, "Image").or_else(|| field(data, "SourceImage"));
+    json!({
+        "entity_id": guid,
+        "pid": field(data, "ProcessId")
+            .or_else(|| field(data, "SourceProcessId"))
+            .and_then(|p| p.parse::<u32>().ok()),
+        "executable": image,
+        "name": image.map(file_name),
+        "command_line": field(data, "CommandLine"),
+        "working_directory": field(data, "CurrentDirectory"),
+        "hash": hashes(data, "Hashes"),
+        "pe": {
+            "original_file_name": field(data, "OriginalFileName"),
+            "company": field(data, "Company"),
+            "product": field(data, "Product"),
+            "description": field(data, "Description"),
+        },
+        "parent": {
+            "entity_id": field(data, "ParentProcessGuid"),
+            "pid": field(data, "ParentProcessId").and_then(|p| p.parse::<u32>().ok()),
+            "executable": field(data, "ParentImage"),
+            "name": field(data, "ParentImage").map(file_name),
+            "command_line": field(data, "ParentCommandLine"),
+        },
+    })
+}
+
+fn file(event: &SysmonEvent) -> Value {
+    let data = &event.event_data;
+    let path = field(data, "TargetFilename").or_else(|| field(data, "ImageLoaded"));
+    let hash = match event.event_id {
+        SysmonEventId::FILE_CREATE_STREAM_HASH => hashes(data, "Hash"),
+        SysmonEventId::IMAGE_LOAD
+        | SysmonEventId::DRIVER_LOAD
+        | SysmonEventId::FILE_DELETE
+        | SysmonEventId::FILE_DELETE_DETECTED => hashes(data, "Hashes"),
+        _ => Value::Null,
+    };
+    json!({
+        "path": path,
+        "name": path.map(file_name),
+        "directory": path.and_then(directory),
+        "hash": hash,
+        "code_signature": {
+            "signed": field(data, "Signed").map(|s| s.eq_ignore_ascii_case("true")),
+            "subject_name": field(data, "Signature"),
+            "status": field(data, "SignatureStatus"),
+        },
+    })
+}
+
+fn network(event: &SysmonEvent) -> (Value, Value, Value) {
+    let Some(connect) = NetworkConnect::from_event(event) else {
+        return (Value::Null, Value::Null, Value::Null);
+    };
+    let network = json!({
+        "transport": connect.protocol.to_lowercase(),
+        "direction": if connect.initiated { "egress" } else { "ingress" },
+    });
+    let source = json!({
+        "ip": connect.source_ip,
+        "port": connect.source_port,
+        "domain": field(&event.event_data, "SourceHostname"),
+    });
+    let destination = json!({
+        "ip": connect.destination_ip,
+        "port": connect.destination_port,
+        "domain": connect.destination_hostname,
+    });
+    (network, source, destination)
+}
+
+fn dns(event: &SysmonEvent) -> Value {
+    if event.event_id != SysmonEventId::DNS_QUERY {
+        return Value::Null;
+    }
+    let data = &event.event_data;
+    json!({
+        "question": { "name": field(data, "QueryName") },
+        "response_code": field(data, "QueryStatus"),
+        "resolved_ip": field(data, "QueryResults").map(parse_query_results),
+    })
+}
+
+fn registry(event: &SysmonEvent) -> Value {
+    let data = &event.event_data;
+    let Some(path) = field(data, "TargetObject") else {
+        return Value::Null;
+    };
+    json!({
+        "path": path,
+        "value": file_name(path),
+        "data": { "strings": field(data, "Details").map(|d| vec![d]) },
+    })
+}
+
+pub fn ecs_event(event: &SysmonEvent) -> Value {
+    let data = &event.event_data;
+    let (category, kind) = categorize(&event.event_id);
+    let (network, source, destination) = network(event);
+    let document = json!({
+        "@timestamp": timestamp(&event.time_created),
+        "ecs": { "version": ECS_VERSION },
+        "event": {
+            "kind": "event",
+            "code": event.event_id.value().to_string(),
+            "action": event.event_id.name(),
+            "category": category,
+            "type": kind,
+            "provider": "Microsoft-Windows-Sysmon",
+            "module": "sysmon",
+            "dataset": "sysmon.operational",
+        },
+        "host": { "name": event.computer },
+        "user": { "name": field(data, "User") },
+        "rule": { "name": field(data, "RuleName") },
+        "process": process(data),
+        "file": file(event),
+        "dns": dns(event),
+        "network": network,
+        "source": source,
+        "destination": destination,
+        "registry": registry(event),
+        "winlog": {
+            "channel": event.channel,
+            "record_id": event.record_id,
+            "computer_name": event.computer,
+            "event_id": event.event_id.value(),
+            "event_data": data,
+        },
+    });
+    prune(document).unwrap_or_default()
+}
+
+pub fn ecs_detection(report: &SandboxReport, detection: &Detection) -> Value {
+    let first = detection.events.first();
+    let techniques: Vec<String> = detection
+        .tags
+        .iter()
+        .filter_map(|t| t.strip_prefix("attack.t"))
+        .map(|t| format!("T{}", t.to_uppercase()))
+        .collect();
+    let document = json!({
+        "@timestamp": timestamp(&first.map(|e| e.time_created).unwrap_or(report.time.into())),
+        "ecs": { "version": ECS_VERSION },
+        "event": {
+            "kind": "alert",
+            "category": ["malware"],
+            "type": ["indicator"],
+            "module": "sandbox",
+            "dataset": "sandbox.detection",
+            "severity": detection.level.as_deref().map(severity),
+        },
+        "rule": {
+            "name": detection.name,
+            "ruleset": detection.source,
+        },
+        "tags": detection.tags,
+        "threat": {
+            "framework": (!techniques.is_empty()).then_some("MITRE ATT&CK"),
+            "technique": { "id": techniques },
+        },
+        "host": { "name": first.and_then(|e| e.computer.clone()) },
+        "process": first.map(|e| process(&e.event_data)),
+        "labels": {
+            "analysis_id": report.id,
+            "execution_id": report.execution_id,
+            "sample_hash": report.hash,
+            "matched_events": detection.events.len(),
+        },
+    });
+    prune(document).unwrap_or_default()
+}
+
+fn severity(level: &str) -> u8 {
+    match level.to_lowercase().as_str() {
+        "informational" => 21,
+        "low" => 47,
+        "medium" => 73,
+        "high" => 99,
+        "critical" => 100,
+        _ => 0,
+    }
+}
+
+pub fn bulk_body<'a, I>(index: &str, documents: I) -> Result<String>
+where
+    I: IntoIterator<Item = &'a Value>,
+{
+    let mut body = String::new();
+    for document in documents {
+        body.push_str(&serde_json::to_string(
+            &json!({ "create": { "_index": index } }),
+        )?);
+        body.push('\n');
+        body.push_str(&serde_json::to_string(document)?);
+        body.push('\n');
+    }
+    Ok(body)
+}
+
+#[cfg(feature = "elastic")]
+#[derive(Debug, Clone)]
+pub enum ElasticAuth {
+    None,
+    Basic { user: String, password: String },
+    ApiKey(String),
+}
+
+#[cfg(feature = "elastic")]
+#[derive(Debug, Clone, Default)]
+pub struct BulkSummary {
+    pub indexed: usize,
+    pub failed: usize,
+    pub errors: Vec<String>,
+}
+
+#[cfg(feature = "elastic")]
+pub struct ElasticClient {
+    url: String,
+    auth: ElasticAuth,
+    events_index: String,
+    detections_index: String,
+    batch_size: usize,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "elastic")]
+impl ElasticClient {
+    pub fn new(url: &str, auth: ElasticAuth, verify_tls: bool) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(!verify_tls)
+            .build()?;
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            auth,
+            events_index: DEFAULT_INDEX.to_string(),
+            detections_index: format!("{}-detections", DEFAULT_INDEX),
+            batch_size: DEFAULT_BATCH_SIZE,
+            client,
+        })
+    }
+
+    pub fn with_index(mut self, index: &str) -> Self {
+        self.events_index = index.to_string();
+        self.detections_index = format!("{}-detections", index);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub async fn bulk(&self, index: &str, documents: &[Value]) -> Result<BulkSummary> {
+        #[derive(serde::Deserialize)]
+        struct BulkResponse {
+            items: Vec<HashMap<String, Value>>,
+        }
+
+        let mut summary = BulkSummary::default();
+        for chunk in documents.chunks(self.batch_size) {
+            let request = self
+                .client
+                .post(format!("{}/_bulk", self.url))
+                .header("Content-Type", "application/x-ndjson")
+                .body(bulk_body(index, chunk)?);
+            let request = match &self.auth {
+                ElasticAuth::None => request,
+                ElasticAuth::Basic { user, password } => request.basic_auth(user, Some(password)),
+                ElasticAuth::ApiKey(key) => {
+                    request.header("Authorization", format!("ApiKey {}", key))
+                }
+            };
+            let response: BulkResponse = request.send().await?.error_for_status()?.json().await?;
+            for item in response.items.iter().flat_map(|i| i.values()) {
+                match item.get("error") {
+                    Some(error) => {
+                        summary.failed += 1;
+                        summary.errors.push(error.to_string());
+                    }
+                    None => summary.indexed += 1,
+                }
+            }
+        }
+        Ok(summary)
+    }
+
+    pub async fn ship_events(&self, events: &[SysmonEvent]) -> Result<BulkSummary> {
+        let documents: Vec<Value> = events.iter().map(ecs_event).collect();
+        self.bulk(&self.events_index, &documents).await
+    }
+
+    pub async fn ship_report(&self, report: &SandboxReport) -> Result<BulkSummary> {
+        let mut summary = self.ship_events(&report.events).await?;
+        let detections: Vec<Value> = report
+            .detections
+            .iter()
+            .map(|d| ecs_detection(report, d))
+            .collect();
+        let shipped = self.bulk(&self.detections_index, &detections).await?;
+        summary.indexed += shipped.indexed;
+        summary.failed += shipped.failed;
+        summary.errors.extend(shipped.errors);
+        Ok(summary)
+    }
+}
@@ -0,0 +1,193 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+use chrono::{SecondsFormat, Utc};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+
+use super::cef::{detection_to_cef, event_to_cef};
+use super::leef::{detection_to_leef, event_to_leef};
+use crate::report::SandboxReport;
+
+pub const SEVERITY_WARNING: u8 = 4;
+pub const SEVERITY_INFORMATIONAL: u8 = 6;
+const FACILITY_LOCAL0: u8 = 16;
+const MAX_UDP_MESSAGE_LEN: usize = 65_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Udp,
+    Tcp,
+    #[cfg(feature = "tls")]
+    Tls,
+}
+
+impl FromStr for Protocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "udp" => Ok(Self::Udp),
+            "tcp" => Ok(Self::Tcp),
+            #[cfg(feature = "tls")]
+            "tls" => Ok(Self::Tls),
+            _ => bail!("Unsupported syslog protocol: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiemFormat {
+    Cef,
+    Leef,
+}
+
+impl FromStr for SiemFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cef" => Ok(Self::Cef),
+            "leef" => Ok(Self::Leef),
+            _ => bail!("Unsupported SIEM format: {}", s),
+        }
+    }
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+}
+
+#[cfg(feature = "tls")]
+async fn connect_tls(host: &str, stream: TcpStream) -> Result<Connection> {
+    use std::sync::Arc;
+    use tokio_rustls::rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+    use tokio_rustls::TlsConnector;
+
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from(host)?, stream)
+        .await?;
+    Ok(Connection::Tls(Box::new(stream)))
+}
+
+pub struct SyslogForwarder {
+    connection: Connection,
+    hostname: String,
+    app_name: String,
+    facility: u8,
+}
+
+impl SyslogForwarder {
+    pub async fn connect(protocol: Protocol, host: &str, port: u16) -> Result<Self> {
+        let connection = match protocol {
+            Protocol::Udp => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect((host, port)).await?;
+                Connection::Udp(socket)
+            }
+            Protocol::Tcp => Connection::Tcp(TcpStream::connect((host, port)).await?),
+            #[cfg(feature = "tls")]
+            Protocol::Tls => connect_tls(host, TcpStream::connect((host, port)).await?).await?,
+        };
+        Ok(Self {
+            connection,
+            hostname: std::env::var("HOSTNAME").unwrap_or_else(|_| "-".to_string()),
+            app_name: "sandbox".to_string(),
+            facility: FACILITY_LOCAL0,
+        })
+    }
+
+    pub fn with_app_name(mut self, app_name: &str) -> Self {
+        self.app_name = app_name.to_string();
+        self
+    }
+
+    pub fn with_facility(mut self, facility: u8) -> Self {
+        self.facility = facility.min(23);
+        self
+    }
+
+    pub fn frame(&self, severity: u8, message: &str) -> String {
+        format!(
+            "<{}>1 {} {} {} - - - {}",
+            u16::from(self.facility) * 8 + u16::from(severity.min(7)),
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.hostname,
+            self.app_name,
+            message.replace(['\r', '\n'], " ")
+        )
+    }
+
+    pub async fn send(&mut self, severity: u8, message: &str) -> Result<()> {
+        let mut frame = self.frame(severity, message);
+        match &mut self.connection {
+            Connection::Udp(socket) => {
+                if frame.len() > MAX_UDP_MESSAGE_LEN {
+                    let mut end = MAX_UDP_MESSAGE_LEN;
+                    while !frame.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    frame.truncate(end);
+                }
+                socket.send(frame.as_bytes()).await?;
+            }
+            Connection::Tcp(stream) => {
+                frame.push('\n');
+                stream.write_all(frame.as_bytes()).await?;
+            }
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => {
+                frame.push('\n');
+                stream.write_all(frame.as_bytes()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn forward_report(
+        &mut self,
+        report: &SandboxReport,
+        format: SiemFormat,
+    ) -> Result<()> {
+        for event in &report.events {
+            let line = match format {
+                SiemFormat::Cef => event_to_cef(event),
+                SiemFormat::Leef => event_to_leef(event),
+            };
+            self.send(SEVERITY_INFORMATIONAL, &line).await?;
+        }
+        for detection in &report.detections {
+            let line = match format {
+                SiemFormat::Cef => detection_to_cef(report, detection),
+                SiemFormat::Leef => detection_to_leef(report, detection),
+            };
+            self.send(SEVERITY_WARNING, &line).await?;
+        }
+        self.flush().await
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        match &mut self.connection {
+            Connection::Udp(_) => (),
+            Connection::Tcp(stream) => stream.flush().await?,
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => stream.flush().await?,
+        }
+        Ok(())
+    }
+}
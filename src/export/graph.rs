@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::dns::parse_query_results;
+use crate::network::NetworkConnect;
+use crate::path::normalize;
+use crate::report::SandboxReport;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Sample,
+    Process,
+    File,
+    RegistryKey,
+    Domain,
+    Ip,
+}
+
+impl NodeKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Sample => "Sample",
+            Self::Process => "Process",
+            Self::File => "File",
+            Self::RegistryKey => "RegistryKey",
+            Self::Domain => "Domain",
+            Self::Ip => "IP",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    Executed,
+    Created,
+    Wrote,
+    Deleted,
+    Connected,
+    Resolved,
+}
+
+impl EdgeKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Executed => "EXECUTED",
+            Self::Created => "CREATED",
+            Self::Wrote => "WROTE",
+            Self::Deleted => "DELETED",
+            Self::Connected => "CONNECTED",
+            Self::Resolved => "RESOLVED",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Node {
+    pub id: String,
+    pub kind: NodeKind,
+    pub name: String,
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    pub kind: EdgeKind,
+    pub time: Option<String>,
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    #[serde(skip)]
+    index: BTreeMap<String, usize>,
+}
+
+fn node_id(kind: NodeKind, key: &str) -> String {
+    format!("{}:{}", kind.label().to_lowercase(), key)
+}
+
+fn process_id(guid: &str) -> String {
+    node_id(NodeKind::Process, &guid.to_lowercase())
+}
+
+impl Graph {
+    pub fn from_report(report: &SandboxReport) -> Self {
+        let mut graph = Self::default();
+        let sample = graph.node(NodeKind::Sample, &report.hash, &report.hash);
+        graph.set(&sample, "analysis_id", &report.id);
+
+        for process in report.process_tree.processes() {
+            let id = graph.node(
+                NodeKind::Process,
+                &process.guid.to_lowercase(),
+                &process.image,
+            );
+            graph.set(&id, "guid", &process.guid);
+            graph.set(&id, "command_line", &process.command_line);
+            if let Some(pid) = process.process_id {
+                graph.set(&id, "process_id", &pid.to_string());
+            }
+            let time = process.start_time.map(|t| t.to_rfc3339());
+            match process
+                .parent_guid
+                .as_deref()
+                .filter(|p| report.process_tree.get(p).is_some())
+            {
+                Some(parent) => graph.edge(process_id(parent), id, EdgeKind::Created, time),
+                None => graph.edge(sample.clone(), id, EdgeKind::Executed, time),
+            }
+        }
+
+        for event in &report.events {
+            graph.add_event(event);
+        }
+        graph
+    }
+
+    fn node(&mut self, kind: NodeKind, key: &str, name: &str) -> String {
+        let id = node_id(kind, key);
+        if !self.index.contains_key(&id) {
+            self.index.insert(id.clone(), self.nodes.len());
+            self.nodes.push(Node {
+                id: id.clone(),
+                kind,
+                name: name.to_string(),
+                properties: BTreeMap::new(),
+            });
+        }
+        id
+    }
+
+    fn set(&mut self, id: &str, key: &str, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        if let Some(&i) = self.index.get(id) {
+            self.nodes[i]
+                .properties
+                .insert(key.to_string(), value.to_string());
+        }
+    }
+
+    fn edge(&mut self, source: String, target: String, kind: EdgeKind, time: Option<String>) {
+        self.edges.push(Edge {
+            source,
+            target,
+            kind,
+            time,
+            properties: BTreeMap::new(),
+        });
+    }
+
+    fn actor(&mut self, event: &SysmonEvent) -> Option<String> {
+        let guid = event.event_data.get("ProcessGuid")?;
+        let image = event
+            .event_data
+            .get("Image")
+            .map(String::as_str)
+            .unwrap_or_default();
+        Some(self.node(NodeKind::Process, &guid.to_lowercase(), image))
+    }
+
+    fn add_event(&mut self, event: &SysmonEvent) {
+        let time = Some(event.time_created.to_rfc3339());
+        let data = &event.event_data;
+        match event.event_id {
+            SysmonEventId::FILE_CREATE
+            | SysmonEventId::FILE_CREATE_STREAM_HASH
+            | SysmonEventId::FILE_DELETE
+            | SysmonEventId::FILE_DELETE_DETECTED => {
+                let (Some(actor), Some(path)) = (self.actor(event), data.get("TargetFilename"))
+                else {
+                    return;
+                };
+                let file = self.node(NodeKind::File, &normalize(path), path);
+                if let Some(hashes) = data.get("Hashes") {
+                    self.set(&file, "hashes", hashes);
+                }
+                let kind = match event.event_id {
+                    SysmonEventId::FILE_CREATE => EdgeKind::Created,
+                    SysmonEventId::FILE_CREATE_STREAM_HASH => EdgeKind::Wrote,
+                    _ => EdgeKind::Deleted,
+                };
+                self.edge(actor, file, kind, time);
+            }
+            SysmonEventId::REGISTRY_EVENT_ADD_DELETE | SysmonEventId::REGISTRY_EVENT_SET => {
+                let (Some(actor), Some(key)) = (self.actor(event), data.get("TargetObject")) else {
+                    return;
+                };
+                let node = self.node(NodeKind::RegistryKey, &key.to_lowercase(), key);
+                let kind = match data.get("EventType").map(String::as_str) {
+                    Some("SetValue") => EdgeKind::Wrote,
+                    Some("DeleteKey") | Some("DeleteValue") => EdgeKind::Deleted,
+                    _ => EdgeKind::Created,
+                };
+                self.edge(actor, node, kind, time);
+                if let Some(details) = data.get("Details").filter(|_| kind == EdgeKind::Wrote) {
+                    if let Some(edge) = self.edges.last_mut() {
+                        edge.properties
+                            .insert("details".to_string(), details.clone());
+                    }
+                }
+            }
+            SysmonEventId::NETWORK_CONNECT => {
+                let Some(connect) = NetworkConnect::from_event(event) else {
+                    return;
+                };
+                let (Some(actor), Some(ip)) = (self.actor(event), connect.destination_ip) else {
+                    return;
+                };
+                let node = self.node(NodeKind::Ip, &ip.to_string(), &ip.to_string());
+                self.edge(actor, node, EdgeKind::Connected, time);
+                if let Some(edge) = self.edges.last_mut() {
+                    edge.properties
+                        .insert("protocol".to_string(), connect.protocol.to_lowercase());
+                    if let Some(port) = connect.destination_port {
+                        edge.properties.insert("port".to_string(), port.to_string());
+                    }
+                }
+                if let Some(hostname) = connect.destination_hostname.filter(|h| !h.is_empty()) {
+                    let domain = self.node(NodeKind::Domain, &hostname.to_lowercase(), &hostname);
+                    self.link(domain, ip.to_string(), time_of(event));
+                }
+            }
+            SysmonEventId::DNS_QUERY => {
+                let (Some(actor), Some(query)) = (self.actor(event), data.get("QueryName")) else {
+                    return;
+                };
+                let domain = self.node(NodeKind::Domain, &query.to_lowercase(), query);
+                self.edge(actor, domain.clone(), EdgeKind::Resolved, time);
+                let results = data
+                    .get("QueryResults")
+                    .map(|r| parse_query_results(r))
+                    .unwrap_or_default();
+                for ip in results {
+                    self.link(domain.clone(), ip.to_string(), time_of(event));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn link(&mut self, domain: String, ip: String, time: Option<String>) {
+        let ip = self.node(NodeKind::Ip, &ip, &ip);
+        let exists = self
+            .edges
+            .iter()
+            .any(|e| e.kind == EdgeKind::Resolved && e.source == domain && e.target == ip);
+        if !exists {
+            self.edge(domain, ip, EdgeKind::Resolved, time);
+        }
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::new();
+        let _ = self.write_graphml(&mut xml);
+        xml
+    }
+
+    fn write_graphml(&self, xml: &mut String) -> std::fmt::Result {
+        let node_keys: Vec<&String> = self
+            .nodes
+            .iter()
+            .flat_map(|n| n.properties.keys())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let edge_keys: Vec<&String> = self
+            .edges
+            .iter()
+            .flat_map(|e| e.properties.keys())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            xml,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            xml,
+            r#"  <key id="kind" for="all" attr.name="kind" attr.type="string"/>"#
+        )?;
+        writeln!(
+            xml,
+            r#"  <key id="name" for="node" attr.name="name" attr.type="string"/>"#
+        )?;
+        writeln!(
+            xml,
+            r#"  <key id="time" for="edge" attr.name="time" attr.type="string"/>"#
+        )?;
+        for key in &node_keys {
+            writeln!(
+                xml,
+                r#"  <key id="node_{0}" for="node" attr.name="{0}" attr.type="string"/>"#,
+                escape(key)
+            )?;
+        }
+        for key in &edge_keys {
+            writeln!(
+                xml,
+                r#"  <key id="edge_{0}" for="edge" attr.name="{0}" attr.type="string"/>"#,
+                escape(key)
+            )?;
+        }
+        writeln!(xml, r#"  <graph id="run" edgedefault="directed">"#)?;
+        for node in &self.nodes {
+            writeln!(xml, r#"    <node id="{}">"#, escape(&node.id))?;
+            data(xml, "kind", node.kind.label())?;
+            data(xml, "name", &node.name)?;
+            for (key, value) in &node.properties {
+                data(xml, &format!("node_{}", key), value)?;
+            }
+            writeln!(xml, "    </node>")?;
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            writeln!(
+                xml,
+                r#"    <edge id="e{}" source="{}" target="{}">"#,
+                i,
+                escape(&edge.source),
+                escape(&edge.target)
+            )?;
+            data(xml, "kind", edge.kind.label())?;
+            if let Some(time) = &edge.time {
+                data(xml, "time", time)?;
+            }
+            for (key, value) in &edge.properties {
+                data(xml, &format!("edge_{}", key), value)?;
+            }
+            writeln!(xml, "    </edge>")?;
+        }
+        writeln!(xml, "  </graph>")?;
+        writeln!(xml, "</graphml>")
+    }
+
+    pub fn to_cypher(&self) -> Vec<String> {
+        let mut statements = Vec::new();
+        for node in &self.nodes {
+            let mut properties = vec![format!("n.name = {}", quote(&node.name))];
+            properties.extend(
+                node.properties
+                    .iter()
+                    .map(|(k, v)| format!("n.{} = {}", k, quote(v))),
+            );
+            statements.push(format!(
+                "MERGE (n:{} {{id: {}}}) SET {};",
+                node.kind.label(),
+                quote(&node.id),
+                properties.join(", ")
+            ));
+        }
+        for edge in &self.edges {
+            let mut properties: Vec<String> = edge
+                .time
+                .iter()
+                .map(|t| format!("time: {}", quote(t)))
+                .collect();
+            properties.extend(
+                edge.properties
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, quote(v))),
+            );
+            statements.push(format!(
+                "MATCH (a {{id: {}}}), (b {{id: {}}}) MERGE (a)-[:{} {{{}}}]->(b);",
+                quote(&edge.source),
+                quote(&edge.target),
+                edge.kind.label(),
+                properties.join(", ")
+            ));
+        }
+        statements
+    }
+}
+
+fn time_of(event: &SysmonEvent) -> Option<String> {
+    Some(event.time_created.to_rfc3339())
+}
+
+fn data(xml: &mut String, key: &str, value: &str) -> std::fmt::Result {
+    writeln!(
+        xml,
+        r#"      <data key="{}">{}</data>"#,
+        escape(key),
+        escape(value)
+    )
+}
+
+fn escape(value: &str) -> String {
+    crate::report::escape(value)
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+pub fn report_to_graphml(report: &SandboxReport) -> String {
+    Graph::from_report(report).to_graphml()
+}
+
+pub fn report_to_cypher(report: &SandboxReport) -> Vec<String> {
+    Graph::from_report(report).to_cypher()
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+use chrono::{DateTime, SecondsFormat, TimeZone};
+
+use super::cef::{severity, PRODUCT, VENDOR, VERSION};
+use crate::report::{Detection, SandboxReport};
+use crate::sysmon_event::SysmonEvent;
+
+const DELIMITER: char = '\t';
+const DEV_TIME_FORMAT: &str = "yyyy-MM-dd'T'HH:mm:ss.SSSXXX";
+
+const EVENT_FIELDS: &[(&str, &str)] = &[
+    ("User", "usrName"),
+    ("Image", "Image"),
+    ("ProcessId", "ProcessId"),
+    ("CommandLine", "CommandLine"),
+    ("ProcessGuid", "ProcessGuid"),
+    ("ParentImage", "ParentImage"),
+    ("ParentCommandLine", "ParentCommandLine"),
+    ("ParentProcessGuid", "ParentProcessGuid"),
+    ("SourceImage", "SourceImage"),
+    ("TargetImage", "TargetImage"),
+    ("TargetFilename", "TargetFilename"),
+    ("ImageLoaded", "ImageLoaded"),
+    ("TargetObject", "TargetObject"),
+    ("Details", "Details"),
+    ("Hashes", "Hashes"),
+    ("Protocol", "proto"),
+    ("SourceIp", "src"),
+    ("SourcePort", "srcPort"),
+    ("DestinationIp", "dst"),
+    ("DestinationPort", "dstPort"),
+    ("DestinationHostname", "dstHostName"),
+    ("QueryName", "QueryName"),
+    ("QueryResults", "QueryResults"),
+    ("RuleName", "RuleName"),
+];
+
+pub fn escape_header(value: &str) -> String {
+    value.replace('\\', r"\\").replace('|', r"\|")
+}
+
+pub fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            DELIMITER => escaped.push_str(r"\t"),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn line(event_id: &str, attributes: &[(String, String)]) -> String {
+    let attributes: Vec<String> = attributes
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, escape_value(v)))
+        .collect();
+    format!(
+        "LEEF:2.0|{}|{}|{}|{}|x{:02x}|{}",
+        escape_header(VENDOR),
+        escape_header(PRODUCT),
+        escape_header(VERSION),
+        escape_header(event_id),
+        DELIMITER as u8,
+        attributes.join(&DELIMITER.to_string())
+    )
+}
+
+fn dev_time<Tz>(time: &DateTime<Tz>) -> Vec<(String, String)>
+where
+    Tz: TimeZone,
+    Tz::Offset: fmt::Display,
+{
+    vec![
+        (
+            "devTime".to_string(),
+            time.to_rfc3339_opts(SecondsFormat::Millis, false),
+        ),
+        ("devTimeFormat".to_string(), DEV_TIME_FORMAT.to_string()),
+    ]
+}
+
+fn event_attributes(event: &SysmonEvent) -> Vec<(String, String)> {
+    let mut attributes = dev_time(&event.time_created);
+    attributes.push(("cat".to_string(), event.event_id.name().to_string()));
+    attributes.push(("sev".to_string(), "1".to_string()));
+    if let Some(computer) = &event.computer {
+        attributes.push(("identHostName".to_string(), computer.clone()));
+    }
+    for (field, key) in EVENT_FIELDS {
+        if let Some(value) = event.event_data.get(*field).filter(|v| !v.is_empty()) {
+            attributes.push((key.to_string(), value.clone()));
+        }
+    }
+    attributes
+}
+
+pub fn event_to_leef(event: &SysmonEvent) -> String {
+    line(
+        &event.event_id.value().to_string(),
+        &event_attributes(event),
+    )
+}
+
+pub fn detection_to_leef(report: &SandboxReport, detection: &Detection) -> String {
+    let mut attributes = match detection.events.first() {
+        Some(event) => event_attributes(event),
+        None => dev_time(&report.time),
+    };
+    attributes.retain(|(k, _)| !["cat", "sev", "RuleName"].contains(&k.as_str()));
+    attributes.extend([
+        ("cat".to_string(), detection.source.clone()),
+        (
+            "sev".to_string(),
+            severity(detection.level.as_deref()).to_string(),
+        ),
+        ("RuleName".to_string(), detection.name.clone()),
+        ("Tags".to_string(), detection.tags.join(",")),
+        ("AnalysisId".to_string(), report.id.clone()),
+        ("SampleHash".to_string(), report.hash.clone()),
+        (
+            "MatchedEvents".to_string(),
+            detection.events.len().to_string(),
+        ),
+    ]);
+    line(
+        &format!("{}:{}", detection.source, detection.name),
+        &attributes,
+    )
+}
+
+pub fn report_to_leef(report: &SandboxReport) -> Vec<String> {
+    report
+        .events
+        .iter()
+        .map(event_to_leef)
+        .chain(
+            report
+                .detections
+                .iter()
+                .map(|d| detection_to_leef(report, d)),
+        )
+        .collect()
+}
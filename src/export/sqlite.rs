@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+
+use crate::sysmon_event::SysmonEvent;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    event_id INTEGER NOT NULL,
+    computer TEXT,
+    record_id INTEGER
+);
+CREATE TABLE IF NOT EXISTS event_data (
+    event INTEGER NOT NULL REFERENCES events(id),
+    key TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_event_id ON events(event_id);
+CREATE INDEX IF NOT EXISTS events_time ON events(time);
+CREATE INDEX IF NOT EXISTS event_data_event ON event_data(event);
+CREATE INDEX IF NOT EXISTS event_data_key_value ON event_data(key, value);
+";
+
+pub fn to_sqlite<P: AsRef<Path>>(events: &[SysmonEvent], path: P) -> Result<()> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut insert_event = tx.prepare(
+            "INSERT INTO events (time, event_id, computer, record_id) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        let mut insert_data =
+            tx.prepare("INSERT INTO event_data (event, key, value) VALUES (?1, ?2, ?3)")?;
+
+        for event in events {
+            insert_event.execute(params![
+                event.time_created.to_rfc3339(),
+                event.event_id.value(),
+                event.computer,
+                event.record_id,
+            ])?;
+            let id = tx.last_insert_rowid();
+            for (key, value) in &event.event_data {
+                insert_data.execute(params![id, key, value])?;
+            }
+        }
+    }
+    tx.commit()?;
+
+    Ok(())
+}
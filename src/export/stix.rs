@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::ioc::{Confidence, Indicator, Ioc};
+use crate::report::SandboxReport;
+
+const SCO_NAMESPACE: Uuid = Uuid::from_u128(0x00abedb4_aa42_466c_9c01_fed23315a9b7);
+
+fn timestamp<Tz: chrono::TimeZone>(time: &DateTime<Tz>) -> String {
+    time.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn object_id(kind: &str, key: &str) -> String {
+    format!("{}--{}", kind, Uuid::new_v5(&SCO_NAMESPACE, key.as_bytes()))
+}
+
+fn escape_pattern(value: &str) -> String {
+    value.replace('\\', r"\\").replace('\'', r"\'")
+}
+
+pub(crate) fn confidence(confidence: Confidence) -> u8 {
+    match confidence {
+        Confidence::Low => 15,
+        Confidence::Medium => 50,
+        Confidence::High => 85,
+    }
+}
+
+pub(crate) fn observable(ioc: &Ioc) -> Option<(Value, String)> {
+    let value = ioc.value();
+    let (kind, property, body) = match ioc {
+        Ioc::Ip(ip) if ip.is_ipv4() => ("ipv4-addr", "value", json!({ "value": value })),
+        Ioc::Ip(_) => ("ipv6-addr", "value", json!({ "value": value })),
+        Ioc::Domain(_) => ("domain-name", "value", json!({ "value": value })),
+        Ioc::Url(_) => ("url", "value", json!({ "value": value })),
+        Ioc::Email(_) => ("email-addr", "value", json!({ "value": value })),
+        Ioc::Md5(_) => ("file", "hashes.MD5", json!({ "hashes": { "MD5": value } })),
+        Ioc::Sha1(_) => (
+            "file",
+            "hashes.'SHA-1'",
+            json!({ "hashes": { "SHA-1": value } }),
+        ),
+        Ioc::Sha256(_) => (
+            "file",
+            "hashes.'SHA-256'",
+            json!({ "hashes": { "SHA-256": value } }),
+        ),
+        Ioc::FilePath(_) => {
+            let (parent, name) = value.rsplit_once(['\\', '/']).unwrap_or(("", &value));
+            let mut body = json!({ "name": name });
+            if !parent.is_empty() {
+                body["x_path"] = json!(parent);
+            }
+            ("file", "name", body)
+        }
+        Ioc::RegistryKey(_) => ("windows-registry-key", "key", json!({ "key": value })),
+        Ioc::Mutex(_) => ("mutex", "name", json!({ "name": value })),
+        Ioc::Imphash(_) | Ioc::Pipe(_) => return None,
+    };
+
+    let mut object = body;
+    object["type"] = json!(kind);
+    object["spec_version"] = json!("2.1");
+    object["id"] = json!(object_id(kind, &format!("{}:{}", kind, value)));
+
+    let value = match ioc {
+        Ioc::FilePath(_) => object["name"].as_str().unwrap_or_default().to_string(),
+        _ => value,
+    };
+    let pattern = format!("[{}:{} = '{}']", kind, property, escape_pattern(&value));
+    Some((object, pattern))
+}
+
+fn relationship(report: &SandboxReport, kind: &str, source: &str, target: &str) -> Value {
+    let created = timestamp(&report.time);
+    json!({
+        "type": "relationship",
+        "spec_version": "2.1",
+        "id": object_id("relationship", &format!("{}:{}:{}:{}", report.id, kind, source, target)),
+        "created": created,
+        "modified": created,
+        "relationship_type": kind,
+        "source_ref": source,
+        "target_ref": target,
+    })
+}
+
+fn indicator(report: &SandboxReport, indicator: &Indicator, pattern: &str) -> Value {
+    let created = timestamp(&report.time);
+    json!({
+        "type": "indicator",
+        "spec_version": "2.1",
+        "id": object_id("indicator", &format!("{}:{}", report.id, pattern)),
+        "created": created,
+        "modified": created,
+        "name": format!("{} {}", indicator.ioc.kind(), indicator.ioc.value()),
+        "indicator_types": ["malicious-activity"],
+        "pattern": pattern,
+        "pattern_type": "stix",
+        "valid_from": timestamp(&indicator.first_seen),
+        "confidence": confidence(indicator.confidence),
+    })
+}
+
+pub fn to_stix_bundle(report: &SandboxReport) -> Value {
+    let created = timestamp(&report.time);
+    let mut objects = Vec::new();
+
+    let sample_id = object_id("file", &format!("file:{}", report.hash));
+    objects.push(json!({
+        "type": "file",
+        "spec_version": "2.1",
+        "id": sample_id,
+        "hashes": { "SHA3-512": report.hash },
+    }));
+
+    let malware_id = object_id("malware", &report.id);
+    objects.push(json!({
+        "type": "malware",
+        "spec_version": "2.1",
+        "id": malware_id,
+        "created": created,
+        "modified": created,
+        "name": report.hash,
+        "is_family": false,
+        "sample_refs": [sample_id],
+    }));
+
+    let mut observables: BTreeMap<String, Value> = BTreeMap::new();
+    for i in report.iocs.iter() {
+        let Some((object, pattern)) = observable(&i.ioc) else {
+            continue;
+        };
+        let indicator = indicator(report, i, &pattern);
+        objects.push(relationship(
+            report,
+            "indicates",
+            indicator["id"].as_str().unwrap_or_default(),
+            &malware_id,
+        ));
+        objects.push(indicator);
+        let id = object["id"].as_str().unwrap_or_default().to_string();
+        observables.insert(id, object);
+    }
+
+    if !observables.is_empty() {
+        let first = report.events.iter().map(|e| e.time_created).min();
+        let last = report.events.iter().map(|e| e.time_created).max();
+        objects.push(json!({
+            "type": "observed-data",
+            "spec_version": "2.1",
+            "id": object_id("observed-data", &report.id),
+            "created": created,
+            "modified": created,
+            "first_observed": first.map(|t| timestamp(&t)).unwrap_or(created.clone()),
+            "last_observed": last.map(|t| timestamp(&t)).unwrap_or(created.clone()),
+            "number_observed": 1,
+            "object_refs": observables.keys().collect::<Vec<_>>(),
+        }));
+        objects.extend(observables.into_values());
+    }
+
+    for technique in &report.techniques {
+        let attack_pattern_id = object_id("attack-pattern", &technique.id);
+        let name = technique.name.clone().unwrap_or(technique.id.clone());
+        let url = format!(
+            "https://attack.mitre.org/techniques/{}/",
+            technique.id.replace('.', "/")
+        );
+        objects.push(json!({
+            "type": "attack-pattern",
+            "spec_version": "2.1",
+            "id": attack_pattern_id,
+            "created": created,
+            "modified": created,
+            "name": name,
+            "external_references": [{
+                "source_name": "mitre-attack",
+                "external_id": technique.id,
+                "url": url,
+            }],
+        }));
+        objects.push(relationship(
+            report,
+            "uses",
+            &malware_id,
+            &attack_pattern_id,
+        ));
+    }
+
+    json!({
+        "type": "bundle",
+        "id": object_id("bundle", &report.id),
+        "objects": objects,
+    })
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value};
+
+use crate::report::SandboxReport;
+use crate::sysmon_event::SysmonEvent;
+use crate::timeline::{EntryData, EntryKind, TimelineEntry};
+
+const PARSER: &str = "malware_analysis_sandbox";
+
+type EventKey = (u8, DateTime<Utc>, Option<u64>);
+
+fn event_key(event: &SysmonEvent) -> EventKey {
+    (
+        event.event_id.value(),
+        event.time_created.with_timezone(&Utc),
+        event.record_id,
+    )
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit()) {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            snake.push(c);
+        } else if !snake.ends_with('_') {
+            snake.push('_');
+        }
+        previous = Some(c);
+    }
+    snake
+}
+
+fn data_type(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Sysmon => "sandbox:sysmon:event",
+        EntryKind::Flow => "sandbox:network:flow",
+        EntryKind::Observation => "sandbox:netsim:request",
+        EntryKind::Screenshot => "sandbox:screenshot",
+        EntryKind::ScriptBlock => "sandbox:powershell:script_block",
+    }
+}
+
+fn timestamp_desc(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Sysmon | EntryKind::ScriptBlock => "Event Time",
+        EntryKind::Flow => "First Seen",
+        EntryKind::Observation => "Request Time",
+        EntryKind::Screenshot => "Capture Time",
+    }
+}
+
+fn source_short(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Sysmon | EntryKind::ScriptBlock => "EVT",
+        EntryKind::Flow | EntryKind::Observation => "NET",
+        EntryKind::Screenshot => "FILE",
+    }
+}
+
+fn insert_flattened(line: &mut Map<String, Value>, value: Value) {
+    let Value::Object(fields) = value else {
+        return;
+    };
+    for (name, value) in fields {
+        let name = snake_case(&name);
+        if line.contains_key(&name) {
+            continue;
+        }
+        let value = match value {
+            Value::Null => continue,
+            Value::Object(_) | Value::Array(_) => Value::String(value.to_string()),
+            value => value,
+        };
+        line.insert(name, value);
+    }
+}
+
+pub fn entry_to_timesketch(
+    report: &SandboxReport,
+    entry: &TimelineEntry,
+    tags: &[String],
+) -> Result<Value> {
+    let mut line = Map::new();
+    line.insert("message".to_string(), entry.summary.clone().into());
+    line.insert(
+        "datetime".to_string(),
+        entry
+            .time
+            .to_rfc3339_opts(SecondsFormat::Micros, true)
+            .into(),
+    );
+    line.insert(
+        "timestamp".to_string(),
+        entry.time.timestamp_micros().into(),
+    );
+    line.insert(
+        "original_datetime".to_string(),
+        entry
+            .original_time
+            .to_rfc3339_opts(SecondsFormat::Micros, false)
+            .into(),
+    );
+    line.insert("clock_domain".to_string(), entry.clock.name().into());
+    line.insert(
+        "timestamp_desc".to_string(),
+        timestamp_desc(entry.kind).into(),
+    );
+    line.insert("data_type".to_string(), data_type(entry.kind).into());
+    line.insert("parser".to_string(), PARSER.into());
+    line.insert("source_short".to_string(), source_short(entry.kind).into());
+    line.insert("source_long".to_string(), entry.kind.name().into());
+    line.insert("analysis_id".to_string(), report.id.clone().into());
+    line.insert(
+        "execution_id".to_string(),
+        report.execution_id.clone().into(),
+    );
+    line.insert("sample_hash".to_string(), report.hash.clone().into());
+    if let Some(guid) = &entry.process_guid {
+        line.insert("process_guid".to_string(), guid.clone().into());
+    }
+    if !tags.is_empty() {
+        line.insert("tag".to_string(), tags.to_vec().into());
+    }
+
+    match &entry.data {
+        EntryData::Sysmon(event) => {
+            line.insert(
+                "event_identifier".to_string(),
+                event.event_id.value().into(),
+            );
+            line.insert("event_name".to_string(), event.event_id.name().into());
+            if let Some(computer) = &event.computer {
+                line.insert("hostname".to_string(), computer.clone().into());
+            }
+            if let Some(record_id) = event.record_id {
+                line.insert("record_number".to_string(), record_id.into());
+            }
+            let mut fields: Vec<(&String, &String)> = event.event_data.iter().collect();
+            fields.sort();
+            for (name, value) in fields {
+                line.entry(snake_case(name))
+                    .or_insert_with(|| value.clone().into());
+            }
+        }
+        data => insert_flattened(&mut line, serde_json::to_value(data)?),
+    }
+    Ok(Value::Object(line))
+}
+
+pub fn report_to_timesketch(report: &SandboxReport) -> Result<Vec<String>> {
+    let mut tags: HashMap<EventKey, Vec<String>> = HashMap::new();
+    for detection in &report.detections {
+        for event in &detection.events {
+            let tags = tags.entry(event_key(event)).or_default();
+            if !tags.contains(&detection.name) {
+                tags.push(detection.name.clone());
+            }
+        }
+    }
+
+    let timeline = report.timeline()?;
+    let mut lines = Vec::with_capacity(timeline.len());
+    for entry in timeline.entries() {
+        let tags = match &entry.data {
+            EntryData::Sysmon(event) => tags.get(&event_key(event)).map(Vec::as_slice),
+            _ => None,
+        };
+        let line = entry_to_timesketch(report, entry, tags.unwrap_or_default())?;
+        lines.push(serde_json::to_string(&line)?);
+    }
+    Ok(lines)
+}
@@ -0,0 +1,394 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::analyzer::api_trace::DEFAULT_TRACED_APIS;
+use crate::event_data::Platform;
+use crate::report::SandboxReport;
+use crate::sysmon_event::SysmonEventId;
+
+pub const ID_COLUMNS: &[&str] = &["id", "execution_id", "hash"];
+pub const CATEGORICAL_FEATURES: &[&str] = &["platform", "verdict", "file_kind", "pe_machine"];
+
+const HIGH_ENTROPY: f64 = 7.0;
+
+const SCALAR_FEATURES: [&str; 42] = [
+    "score",
+    "signatures",
+    "processes",
+    "max_process_depth",
+    "file_changes",
+    "artifacts",
+    "registry_changes",
+    "tampered_files",
+    "detections",
+    "detections_high",
+    "detections_medium",
+    "detections_low",
+    "techniques",
+    "iocs",
+    "section_count",
+    "section_entropy_min",
+    "section_entropy_mean",
+    "section_entropy_max",
+    "high_entropy_sections",
+    "writable_executable_sections",
+    "import_dlls",
+    "import_functions",
+    "exports",
+    "resources",
+    "connections",
+    "distinct_destination_ips",
+    "distinct_destination_ports",
+    "dns_queries",
+    "distinct_domains",
+    "http_requests",
+    "beacons",
+    "ids_alerts",
+    "flows",
+    "bytes_sent",
+    "bytes_received",
+    "command_line_length_max",
+    "command_line_length_mean",
+    "strings",
+    "base64_strings",
+    "url_strings",
+    "scripts",
+    "memory_payloads",
+];
+
+pub fn numeric_feature_names() -> Vec<String> {
+    let mut names: Vec<String> = SysmonEventId::ALL
+        .iter()
+        .map(|id| format!("event_{}", id.value()))
+        .collect();
+    names.extend(
+        DEFAULT_TRACED_APIS
+            .iter()
+            .map(|api| format!("api_{}", api.to_lowercase())),
+    );
+    names.push("api_other".to_string());
+    names.extend(SCALAR_FEATURES.iter().map(|name| name.to_string()));
+    names
+}
+
+pub fn column_names() -> Vec<String> {
+    ID_COLUMNS
+        .iter()
+        .chain(CATEGORICAL_FEATURES)
+        .map(|name| name.to_string())
+        .chain(numeric_feature_names())
+        .collect()
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FeatureVector {
+    pub id: String,
+    pub execution_id: String,
+    pub hash: String,
+    pub categorical: Vec<String>,
+    pub numeric: Vec<f64>,
+}
+
+fn platform_name(platform: Option<Platform>) -> &'static str {
+    match platform {
+        Some(Platform::Windows) => "windows",
+        Some(Platform::Linux) => "linux",
+        None => "unknown",
+    }
+}
+
+fn base64_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"[A-Za-z0-9+/]{40,}={0,2}").expect("Invalid base64 pattern"))
+}
+
+fn url_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)\b(?:https?|ftp)://").expect("Invalid URL pattern"))
+}
+
+fn event_histogram(report: &SandboxReport) -> Vec<f64> {
+    SysmonEventId::ALL
+        .iter()
+        .map(|id| report.events.iter().filter(|e| e.event_id == *id).count() as f64)
+        .collect()
+}
+
+fn api_histogram(report: &SandboxReport) -> Vec<f64> {
+    let mut counts = vec![0.0; DEFAULT_TRACED_APIS.len() + 1];
+    for call in &report.api_calls {
+        let index = DEFAULT_TRACED_APIS
+            .iter()
+            .position(|api| api.eq_ignore_ascii_case(&call.api))
+            .unwrap_or(DEFAULT_TRACED_APIS.len());
+        counts[index] += 1.0;
+    }
+    counts
+}
+
+fn process_depth(report: &SandboxReport) -> usize {
+    report
+        .process_tree
+        .processes()
+        .map(|p| report.process_tree.ancestors(&p.guid).len())
+        .max()
+        .unwrap_or(0)
+}
+
+fn detections_at(report: &SandboxReport, levels: &[&str]) -> usize {
+    report
+        .detections
+        .iter()
+        .filter(|d| {
+            d.level
+                .as_deref()
+                .is_some_and(|l| levels.contains(&l.to_lowercase().as_str()))
+        })
+        .count()
+}
+
+fn strings(report: &SandboxReport) -> Vec<&str> {
+    report
+        .process_tree
+        .processes()
+        .map(|p| p.command_line.as_str())
+        .chain(
+            report
+                .memory
+                .iter()
+                .flat_map(|m| m.strings.iter().map(String::as_str)),
+        )
+        .chain(
+            report
+                .scripts
+                .iter()
+                .chain(&report.captured_scripts)
+                .flat_map(|s| s.decoded.iter().map(String::as_str)),
+        )
+        .collect()
+}
+
+fn scalars(report: &SandboxReport) -> [f64; SCALAR_FEATURES.len()] {
+    let pe = report.static_analysis.as_ref();
+    let sections = pe.map(|pe| pe.sections.as_slice()).unwrap_or_default();
+    let entropies: Vec<f64> = sections.iter().map(|s| s.entropy).collect();
+    let entropy_min = entropies.iter().copied().reduce(f64::min).unwrap_or(0.0);
+    let entropy_max = entropies.iter().copied().reduce(f64::max).unwrap_or(0.0);
+    let entropy_mean = match entropies.len() {
+        0 => 0.0,
+        n => entropies.iter().sum::<f64>() / n as f64,
+    };
+
+    let ips: HashSet<_> = report
+        .network
+        .iter()
+        .filter_map(|c| c.destination_ip)
+        .collect();
+    let ports: HashSet<_> = report
+        .network
+        .iter()
+        .filter_map(|c| c.destination_port)
+        .collect();
+    let domains: HashSet<String> = report.dns.iter().map(|d| d.query.to_lowercase()).collect();
+
+    let command_lines: Vec<usize> = report
+        .process_tree
+        .processes()
+        .map(|p| p.command_line.chars().count())
+        .collect();
+    let command_line_mean = match command_lines.len() {
+        0 => 0.0,
+        n => command_lines.iter().sum::<usize>() as f64 / n as f64,
+    };
+    let strings = strings(report);
+
+    [
+        report.score.score,
+        report.score.signatures.len() as f64,
+        report.process_tree.processes().count() as f64,
+        process_depth(report) as f64,
+        report.file_changes.len() as f64,
+        report.artifacts.len() as f64,
+        report.registry_changes.len() as f64,
+        report.tampered.len() as f64,
+        report.detections.len() as f64,
+        detections_at(report, &["high", "critical"]) as f64,
+        detections_at(report, &["medium"]) as f64,
+        detections_at(report, &["low", "informational"]) as f64,
+        report.techniques.len() as f64,
+        report.iocs.len() as f64,
+        sections.len() as f64,
+        entropy_min,
+        entropy_mean,
+        entropy_max,
+        sections
+            .iter()
+            .filter(|s| s.entropy >= HIGH_ENTROPY)
+            .count() as f64,
+        sections
+            .iter()
+            .filter(|s| s.executable && s.writable)
+            .count() as f64,
+        pe.map_or(0, |pe| pe.imports.len()) as f64,
+        pe.map_or(0, |pe| pe.imports.iter().map(|i| i.functions.len()).sum()) as f64,
+        pe.map_or(0, |pe| pe.exports.len()) as f64,
+        pe.map_or(0, |pe| pe.resources.len()) as f64,
+        report.network.len() as f64,
+        ips.len() as f64,
+        ports.len() as f64,
+        report.dns.len() as f64,
+        domains.len() as f64,
+        report.http.len() as f64,
+        report.beacons.len() as f64,
+        report.ids_alerts.len() as f64,
+        report.traffic.len() as f64,
+        report
+            .traffic
+            .iter()
+            .map(|f| f.flow.bytes_sent)
+            .sum::<u64>() as f64,
+        report
+            .traffic
+            .iter()
+            .map(|f| f.flow.bytes_received)
+            .sum::<u64>() as f64,
+        command_lines.iter().copied().max().unwrap_or(0) as f64,
+        command_line_mean,
+        strings.len() as f64,
+        strings
+            .iter()
+            .map(|s| base64_pattern().find_iter(s).count())
+            .sum::<usize>() as f64,
+        strings
+            .iter()
+            .map(|s| url_pattern().find_iter(s).count())
+            .sum::<usize>() as f64,
+        (report.scripts.len() + report.captured_scripts.len()) as f64,
+        report
+            .memory
+            .iter()
+            .map(|m| m.payloads.len())
+            .sum::<usize>() as f64,
+    ]
+}
+
+impl FeatureVector {
+    pub fn from_report(report: &SandboxReport) -> Self {
+        let pe = report.static_analysis.as_ref();
+        let file_kind = match pe {
+            Some(pe) if pe.is_dll => "dll",
+            Some(_) => "exe",
+            None if report.email.is_some() => "email",
+            None => "other",
+        };
+        let categorical = vec![
+            platform_name(report.platform).to_string(),
+            report.verdict.verdict.to_string(),
+            file_kind.to_string(),
+            pe.map(|pe| pe.machine.clone()).unwrap_or_default(),
+        ];
+
+        let mut numeric = event_histogram(report);
+        numeric.extend(api_histogram(report));
+        numeric.extend(scalars(report));
+
+        Self {
+            id: report.id.clone(),
+            execution_id: report.execution_id.clone(),
+            hash: report.hash.clone(),
+            categorical,
+            numeric,
+        }
+    }
+
+    fn fields(&self) -> Vec<String> {
+        [&self.id, &self.execution_id, &self.hash]
+            .into_iter()
+            .chain(&self.categorical)
+            .cloned()
+            .chain(self.numeric.iter().map(|v| v.to_string()))
+            .collect()
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn to_csv(vectors: &[FeatureVector]) -> String {
+    let mut csv = String::new();
+    let _ = writeln!(csv, "{}", column_names().join(","));
+    for vector in vectors {
+        let fields: Vec<String> = vector.fields().iter().map(|f| csv_field(f)).collect();
+        let _ = writeln!(csv, "{}", fields.join(","));
+    }
+    csv
+}
+
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W: std::io::Write + Send>(vectors: &[FeatureVector], writer: W) -> Result<()> {
+    use std::sync::Arc;
+
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    let text_columns = ID_COLUMNS.len() + CATEGORICAL_FEATURES.len();
+    let mut message = String::from("message features {\n");
+    for (index, name) in column_names().iter().enumerate() {
+        if index < text_columns {
+            let _ = writeln!(message, "  required binary {} (UTF8);", name);
+        } else {
+            let _ = writeln!(message, "  required double {};", name);
+        }
+    }
+    message.push('}');
+
+    let schema = Arc::new(parse_message_type(&message)?);
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut file = SerializedFileWriter::new(writer, schema, properties)?;
+    let mut row_group = file.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        if index < text_columns {
+            let values: Vec<ByteArray> = vectors
+                .iter()
+                .map(|v| ByteArray::from(v.fields()[index].as_str()))
+                .collect();
+            column
+                .typed::<ByteArrayType>()
+                .write_batch(&values, None, None)?;
+        } else {
+            let values: Vec<f64> = vectors
+                .iter()
+                .map(|v| v.numeric[index - text_columns])
+                .collect();
+            column
+                .typed::<DoubleType>()
+                .write_batch(&values, None, None)?;
+        }
+        column.close()?;
+        index += 1;
+    }
+    row_group.close()?;
+    file.close()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn write_parquet<W: std::io::Write + Send>(
+    _vectors: &[FeatureVector],
+    _writer: W,
+) -> Result<()> {
+    anyhow::bail!("Parquet export requires the parquet feature")
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+use crate::path::normalize;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const PATH_FIELDS: &[&str] = &["TargetFilename", "Image", "ImageLoaded", "TargetObject"];
+
+pub fn file_touch_history<'a>(events: &'a [SysmonEvent], path: &str) -> Vec<&'a SysmonEvent> {
+    let path = normalize(path);
+    events
+        .iter()
+        .filter(|e| {
+            PATH_FIELDS
+                .iter()
+                .any(|f| e.event_data.get(*f).is_some_and(|v| normalize(v) == path))
+        })
+        .collect()
+}
+
+const DEFAULT_ALLOWLIST: &[&str] = &[
+    r"c:\windows\prefetch\",
+    r"\appdata\local\microsoft\windows\inetcache\",
+    r"\appdata\local\microsoft\windows\webcache\",
+    r"\appdata\local\microsoft\windows\explorer\thumbcache_",
+    r"\appdata\locallow\microsoft\cryptneturlcache\",
+    r"\appdata\local\temp\castore",
+    r"\windows\serviceprofiles\localservice\appdata\local\temp\castore",
+    r"c:\programdata\microsoft\windows\wer\",
+    r"\appdata\local\microsoft\windows\wer\",
+    r"\appdata\local\crashdumps\",
+    r"c:\windows\system32\catroot2\",
+    r"c:\windows\softwaredistribution\",
+    r"c:\programdata\microsoft\windows defender\",
+    r"c:\windows\system32\winevt\logs\",
+    r"c:\windows\system32\sru\",
+];
+
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "dll", "sys", "scr", "com", "pif", "cpl", "ocx", "ps1", "vbs", "vbe", "js", "jse",
+    "wsf", "hta", "bat", "cmd", "lnk", "msi",
+];
+
+const STARTUP_FOLDERS: &[&str] = &[
+    r"\microsoft\windows\start menu\programs\startup\",
+    r"c:\windows\system32\tasks\",
+    r"c:\windows\tasks\",
+];
+
+const USER_WRITABLE: &[&str] = &[
+    r"\appdata\",
+    r"c:\programdata\",
+    r"c:\users\public\",
+    r"\temp\",
+];
+
+#[derive(Debug, Clone)]
+pub struct FilesystemOptions {
+    pub allowlist: Vec<String>,
+}
+
+impl Default for FilesystemOptions {
+    fn default() -> Self {
+        Self {
+            allowlist: DEFAULT_ALLOWLIST.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl FilesystemOptions {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let extra: Vec<String> = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+        let mut options = Self::default();
+        options.allowlist.extend(extra.iter().map(|p| normalize(p)));
+        Ok(options)
+    }
+
+    pub fn is_allowlisted(&self, path: &str) -> bool {
+        let path = normalize(path);
+        self.allowlist.iter().any(|p| path.contains(p.as_str()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOperation {
+    Created,
+    Deleted,
+    Timestomped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileInterest {
+    Executable,
+    Startup,
+    UserWritable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileOperationRecord {
+    pub operation: FileOperation,
+    pub time: DateTime<FixedOffset>,
+    pub path: String,
+    pub previous_creation_time: Option<String>,
+    pub interest: Vec<FileInterest>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessFileActivity {
+    pub process_guid: String,
+    pub image: String,
+    pub created: usize,
+    pub deleted: usize,
+    pub timestomped: usize,
+    pub suppressed: usize,
+    pub operations: Vec<FileOperationRecord>,
+}
+
+impl ProcessFileActivity {
+    pub fn interesting(&self) -> impl Iterator<Item = &FileOperationRecord> {
+        self.operations.iter().filter(|o| !o.interest.is_empty())
+    }
+}
+
+pub fn interest(path: &str) -> Vec<FileInterest> {
+    let path = normalize(path);
+    let mut interest = Vec::new();
+    let executable = path
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| EXECUTABLE_EXTENSIONS.contains(&ext));
+    if executable {
+        interest.push(FileInterest::Executable);
+    }
+    if STARTUP_FOLDERS.iter().any(|f| path.contains(f)) {
+        interest.push(FileInterest::Startup);
+    }
+    if executable && USER_WRITABLE.iter().any(|f| path.contains(f)) {
+        interest.push(FileInterest::UserWritable);
+    }
+    interest
+}
+
+fn operation(event: &SysmonEvent) -> Option<FileOperation> {
+    match event.event_id {
+        SysmonEventId::FILE_CREATE => Some(FileOperation::Created),
+        SysmonEventId::FILE_DELETE | SysmonEventId::FILE_DELETE_DETECTED => {
+            Some(FileOperation::Deleted)
+        }
+        SysmonEventId::FILE_CREATE_TIME => Some(FileOperation::Timestomped),
+        _ => None,
+    }
+}
+
+pub fn summarize(events: &[SysmonEvent]) -> Vec<ProcessFileActivity> {
+    summarize_with(events, &FilesystemOptions::default())
+}
+
+pub fn summarize_with(
+    events: &[SysmonEvent],
+    options: &FilesystemOptions,
+) -> Vec<ProcessFileActivity> {
+    let mut activity: Vec<ProcessFileActivity> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for event in events {
+        let Some(operation) = operation(event) else {
+            continue;
+        };
+        let Some(path) = event.event_data.get("TargetFilename") else {
+            continue;
+        };
+        let process_guid = event
+            .event_data
+            .get("ProcessGuid")
+            .cloned()
+            .unwrap_or_default();
+        let i = *index.entry(process_guid.clone()).or_insert_with(|| {
+            activity.push(ProcessFileActivity {
+                process_guid,
+                image: event.event_data.get("Image").cloned().unwrap_or_default(),
+                created: 0,
+                deleted: 0,
+                timestomped: 0,
+                suppressed: 0,
+                operations: Vec::new(),
+            });
+            activity.len() - 1
+        });
+        let process = &mut activity[i];
+        if options.is_allowlisted(path) {
+            process.suppressed += 1;
+            continue;
+        }
+        match operation {
+            FileOperation::Created => process.created += 1,
+            FileOperation::Deleted => process.deleted += 1,
+            FileOperation::Timestomped => process.timestomped += 1,
+        }
+        process.operations.push(FileOperationRecord {
+            operation,
+            time: event.time_created,
+            path: path.clone(),
+            previous_creation_time: event.event_data.get("PreviousCreationUtcTime").cloned(),
+            interest: interest(path),
+        });
+    }
+    activity.retain(|a| !a.operations.is_empty());
+    activity
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+
+use serde::de::{self, Deserializer};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+const ACCESS_RIGHTS: &[(u32, &str)] = &[
+    (0x0000_0001, "PROCESS_TERMINATE"),
+    (0x0000_0002, "PROCESS_CREATE_THREAD"),
+    (0x0000_0004, "PROCESS_SET_SESSIONID"),
+    (0x0000_0008, "PROCESS_VM_OPERATION"),
+    (0x0000_0010, "PROCESS_VM_READ"),
+    (0x0000_0020, "PROCESS_VM_WRITE"),
+    (0x0000_0040, "PROCESS_DUP_HANDLE"),
+    (0x0000_0080, "PROCESS_CREATE_PROCESS"),
+    (0x0000_0100, "PROCESS_SET_QUOTA"),
+    (0x0000_0200, "PROCESS_SET_INFORMATION"),
+    (0x0000_0400, "PROCESS_QUERY_INFORMATION"),
+    (0x0000_0800, "PROCESS_SUSPEND_RESUME"),
+    (0x0000_1000, "PROCESS_QUERY_LIMITED_INFORMATION"),
+    (0x0000_2000, "PROCESS_SET_LIMITED_INFORMATION"),
+    (0x0001_0000, "DELETE"),
+    (0x0002_0000, "READ_CONTROL"),
+    (0x0004_0000, "WRITE_DAC"),
+    (0x0008_0000, "WRITE_OWNER"),
+    (0x0010_0000, "SYNCHRONIZE"),
+    (0x0100_0000, "ACCESS_SYSTEM_SECURITY"),
+    (0x0200_0000, "MAXIMUM_ALLOWED"),
+    (0x1000_0000, "GENERIC_ALL"),
+    (0x2000_0000, "GENERIC_EXECUTE"),
+    (0x4000_0000, "GENERIC_WRITE"),
+    (0x8000_0000, "GENERIC_READ"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AccessMask(pub u32);
+
+impl AccessMask {
+    pub const PROCESS_VM_READ: Self = Self(0x0010);
+    pub const PROCESS_ALL_ACCESS: Self = Self(0x001f_ffff);
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let parsed = match value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        {
+            Some(digits) => u32::from_str_radix(digits, 16),
+            None => value.parse(),
+        };
+        parsed.ok().map(Self)
+    }
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut remaining = self.0;
+        if self.contains(Self::PROCESS_ALL_ACCESS) {
+            names.push("PROCESS_ALL_ACCESS".to_string());
+            remaining &= !Self::PROCESS_ALL_ACCESS.0;
+        }
+        for (bit, name) in ACCESS_RIGHTS {
+            if remaining & bit != 0 {
+                names.push(name.to_string());
+                remaining &= !bit;
+            }
+        }
+        if remaining != 0 {
+            names.push(format!("0x{:x}", remaining));
+        }
+        names
+    }
+}
+
+impl fmt::Display for AccessMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:x}", self.0)?;
+        let names = self.names();
+        if !names.is_empty() {
+            write!(f, " ({})", names.join(" | "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for AccessMask {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AccessMask", 2)?;
+        state.serialize_field("mask", &self.0)?;
+        state.serialize_field("flags", &self.names())?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for AccessMask {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(u32),
+            Text(String),
+            Decoded { mask: u32 },
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(mask) | Repr::Decoded { mask } => Ok(Self(mask)),
+            Repr::Text(s) => Self::parse(&s)
+                .ok_or_else(|| de::Error::custom(format!("Invalid access mask: {}", s))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegistryValueType {
+    #[serde(rename = "REG_SZ")]
+    String,
+    #[serde(rename = "REG_EXPAND_SZ")]
+    ExpandString,
+    #[serde(rename = "REG_MULTI_SZ")]
+    MultiString,
+    #[serde(rename = "REG_DWORD")]
+    Dword,
+    #[serde(rename = "REG_QWORD")]
+    Qword,
+    #[serde(rename = "REG_BINARY")]
+    Binary,
+}
+
+impl RegistryValueType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::String => "REG_SZ",
+            Self::ExpandString => "REG_EXPAND_SZ",
+            Self::MultiString => "REG_MULTI_SZ",
+            Self::Dword => "REG_DWORD",
+            Self::Qword => "REG_QWORD",
+            Self::Binary => "REG_BINARY",
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(Self::String),
+            2 => Some(Self::ExpandString),
+            3 => Some(Self::Binary),
+            4 => Some(Self::Dword),
+            7 => Some(Self::MultiString),
+            11 => Some(Self::Qword),
+            _ => None,
+        }
+    }
+
+    pub fn from_details(details: &str) -> Self {
+        let lower = details.trim().to_lowercase();
+        if lower.starts_with("dword (") {
+            Self::Dword
+        } else if lower.starts_with("qword (") || lower.starts_with("hex(b):") {
+            Self::Qword
+        } else if lower.starts_with("hex(2):") {
+            Self::ExpandString
+        } else if lower.starts_with("hex(7):") {
+            Self::MultiString
+        } else if lower == "binary data" || lower.starts_with("hex:") {
+            Self::Binary
+        } else {
+            Self::String
+        }
+    }
+}
+
+impl fmt::Display for RegistryValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LogonType(pub u32);
+
+impl LogonType {
+    pub fn name(&self) -> Option<&'static str> {
+        match self.0 {
+            0 => Some("System"),
+            2 => Some("Interactive"),
+            3 => Some("Network"),
+            4 => Some("Batch"),
+            5 => Some("Service"),
+            7 => Some("Unlock"),
+            8 => Some("NetworkCleartext"),
+            9 => Some("NewCredentials"),
+            10 => Some("RemoteInteractive"),
+            11 => Some("CachedInteractive"),
+            12 => Some("CachedRemoteInteractive"),
+            13 => Some("CachedUnlock"),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..=13).map(Self).find(|t| {
+            t.name()
+                .is_some_and(|n| n.eq_ignore_ascii_case(name.trim()))
+        })
+    }
+}
+
+impl fmt::Display for LogonType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} ({})", name, self.0),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl Serialize for LogonType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("LogonType", 2)?;
+        state.serialize_field("code", &self.0)?;
+        state.serialize_field("name", &self.name())?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for LogonType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(u32),
+            Text(String),
+            Decoded { code: u32 },
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(code) | Repr::Decoded { code } => Ok(Self(code)),
+            Repr::Text(s) => s
+                .trim()
+                .parse()
+                .ok()
+                .map(Self)
+                .or_else(|| Self::from_name(&s))
+                .ok_or_else(|| de::Error::custom(format!("Invalid logon type: {}", s))),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::sysmon_event::SysmonEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Guid(Uuid);
+
+impl Guid {
+    pub fn nil() -> Self {
+        Self(Uuid::nil())
+    }
+
+    pub fn is_nil(&self) -> bool {
+        self.0.is_nil()
+    }
+
+    pub fn as_uuid(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl From<Uuid> for Guid {
+    fn from(uuid: Uuid) -> Self {
+        Self(uuid)
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:X}", self.0.braced())
+    }
+}
+
+impl FromStr for Guid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Uuid::parse_str(s.trim())?))
+    }
+}
+
+impl Serialize for Guid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Guid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl SysmonEvent {
+    pub fn guid(&self, field: &str) -> Option<Guid> {
+        self.event_data
+            .get(field)?
+            .parse()
+            .ok()
+            .filter(|g: &Guid| !g.is_nil())
+    }
+
+    pub fn process_guid(&self) -> Option<Guid> {
+        self.guid("ProcessGuid")
+    }
+
+    pub fn parent_process_guid(&self) -> Option<Guid> {
+        self.guid("ParentProcessGuid")
+    }
+
+    pub fn logon_guid(&self) -> Option<Guid> {
+        self.guid("LogonGuid")
+    }
+}
@@ -0,0 +1,162 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Imphash,
+}
+
+impl HashAlgorithm {
+    pub const ALL: &'static [Self] = &[Self::Sha1, Self::Md5, Self::Sha256, Self::Imphash];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha1 => "SHA1",
+            Self::Sha256 => "SHA256",
+            Self::Imphash => "IMPHASH",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn hex_len(&self) -> usize {
+        match self {
+            Self::Md5 | Self::Imphash => 32,
+            Self::Sha1 => 40,
+            Self::Sha256 => 64,
+        }
+    }
+
+    pub fn is_file_hash(&self) -> bool {
+        *self != Self::Imphash
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hashes {
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+    pub imphash: Option<String>,
+}
+
+fn valid(algorithm: HashAlgorithm, value: &str) -> bool {
+    value.len() == algorithm.hex_len() && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl Hashes {
+    pub fn parse(s: &str) -> Self {
+        let mut hashes = Self::default();
+        for pair in s.split(',') {
+            let Some((algorithm, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let Some(algorithm) = HashAlgorithm::from_name(algorithm) else {
+                continue;
+            };
+            let value = value.trim().to_lowercase();
+            if valid(algorithm, &value) {
+                hashes.set(algorithm, value);
+            }
+        }
+        hashes
+    }
+
+    pub fn get(&self, algorithm: HashAlgorithm) -> Option<&str> {
+        match algorithm {
+            HashAlgorithm::Md5 => self.md5.as_deref(),
+            HashAlgorithm::Sha1 => self.sha1.as_deref(),
+            HashAlgorithm::Sha256 => self.sha256.as_deref(),
+            HashAlgorithm::Imphash => self.imphash.as_deref(),
+        }
+    }
+
+    pub fn set(&mut self, algorithm: HashAlgorithm, value: String) {
+        let field = match algorithm {
+            HashAlgorithm::Md5 => &mut self.md5,
+            HashAlgorithm::Sha1 => &mut self.sha1,
+            HashAlgorithm::Sha256 => &mut self.sha256,
+            HashAlgorithm::Imphash => &mut self.imphash,
+        };
+        *field = Some(value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (HashAlgorithm, &str)> {
+        HashAlgorithm::ALL
+            .iter()
+            .filter_map(|&a| self.get(a).map(|v| (a, v)))
+    }
+
+    pub fn file_hashes(&self) -> impl Iterator<Item = (HashAlgorithm, &str)> {
+        self.iter().filter(|(a, _)| a.is_file_hash())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    pub fn strongest(&self) -> Option<(HashAlgorithm, &str)> {
+        [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Md5,
+        ]
+        .into_iter()
+        .find_map(|a| self.get(a).map(|v| (a, v)))
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        let hash = hash.trim();
+        self.file_hashes()
+            .any(|(_, v)| v.eq_ignore_ascii_case(hash))
+    }
+
+    pub fn matches(&self, other: &Hashes) -> bool {
+        let mut shared = self
+            .file_hashes()
+            .filter_map(|(a, v)| other.get(a).map(|o| v == o))
+            .peekable();
+        shared.peek().is_some() && shared.all(|equal| equal)
+    }
+
+    pub fn merge(&mut self, other: &Hashes) {
+        for (algorithm, value) in other.iter() {
+            if self.get(algorithm).is_none() {
+                self.set(algorithm, value.to_string());
+            }
+        }
+    }
+}
+
+impl FromStr for Hashes {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse(s))
+    }
+}
+
+impl fmt::Display for Hashes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (algorithm, value)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}={}", algorithm.name(), value.to_uppercase())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::event_data::TypedEventData;
+use crate::hashes::{HashAlgorithm, Hashes};
+use crate::network::NetworkConnect;
+use crate::sysmon_event::SysmonEvent;
+
+const URL_PATTERN: &str = r#"(?i)\b(?:https?|ftp)://[^\s"'<>`|]+"#;
+
+const PERSISTENCE_KEYS: &[&str] = &[
+    r"\software\microsoft\windows\currentversion\run",
+    r"\software\wow6432node\microsoft\windows\currentversion\run",
+    r"\software\microsoft\windows\currentversion\policies\explorer\run",
+    r"\software\microsoft\windows nt\currentversion\winlogon\",
+    r"\software\microsoft\windows nt\currentversion\image file execution options\",
+    r"\software\microsoft\windows nt\currentversion\windows\appinit_dlls",
+    r"\software\microsoft\windows\currentversion\explorer\shell folders\startup",
+    r"\software\microsoft\windows\currentversion\explorer\user shell folders\startup",
+    r"\system\currentcontrolset\services\",
+    r"\system\currentcontrolset\control\session manager\bootexecute",
+    r"\environment\userinitmprlogonscript",
+];
+
+const IGNORED_DOMAIN_SUFFIXES: &[&str] = &[".arpa", ".local", "localhost"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum Ioc {
+    Ip(IpAddr),
+    Domain(String),
+    Url(String),
+    Email(String),
+    Md5(String),
+    Sha1(String),
+    Sha256(String),
+    Imphash(String),
+    Mutex(String),
+    Pipe(String),
+    FilePath(String),
+    RegistryKey(String),
+}
+
+impl Ioc {
+    pub fn from_hash(algorithm: HashAlgorithm, value: &str) -> Self {
+        let value = value.to_lowercase();
+        match algorithm {
+            HashAlgorithm::Md5 => Self::Md5(value),
+            HashAlgorithm::Sha1 => Self::Sha1(value),
+            HashAlgorithm::Sha256 => Self::Sha256(value),
+            HashAlgorithm::Imphash => Self::Imphash(value),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Ip(_) => "ip",
+            Self::Domain(_) => "domain",
+            Self::Url(_) => "url",
+            Self::Email(_) => "email",
+            Self::Md5(_) => "md5",
+            Self::Sha1(_) => "sha1",
+            Self::Sha256(_) => "sha256",
+            Self::Imphash(_) => "imphash",
+            Self::Mutex(_) => "mutex",
+            Self::Pipe(_) => "pipe",
+            Self::FilePath(_) => "file_path",
+            Self::RegistryKey(_) => "registry_key",
+        }
+    }
+
+    pub fn value(&self) -> String {
+        match self {
+            Self::Ip(ip) => ip.to_string(),
+            Self::Domain(v)
+            | Self::Url(v)
+            | Self::Email(v)
+            | Self::Md5(v)
+            | Self::Sha1(v)
+            | Self::Sha256(v)
+            | Self::Imphash(v)
+            | Self::Mutex(v)
+            | Self::Pipe(v)
+            | Self::FilePath(v)
+            | Self::RegistryKey(v) => v.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Indicator {
+    pub ioc: Ioc,
+    pub confidence: Confidence,
+    pub first_seen: DateTime<FixedOffset>,
+    pub occurrences: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct IocSet {
+    #[serde(
+        serialize_with = "serialize_indicators",
+        deserialize_with = "deserialize_indicators"
+    )]
+    indicators: BTreeMap<Ioc, Indicator>,
+}
+
+fn serialize_indicators<S: Serializer>(
+    indicators: &BTreeMap<Ioc, Indicator>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(indicators.values())
+}
+
+fn deserialize_indicators<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Ioc, Indicator>, D::Error> {
+    let indicators = Vec::<Indicator>::deserialize(deserializer)?;
+    Ok(indicators
+        .into_iter()
+        .map(|indicator| (indicator.ioc.clone(), indicator))
+        .collect())
+}
+
+pub(crate) fn is_routable(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast())
+        }
+        IpAddr::V6(ip) => !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast()),
+    }
+}
+
+pub(crate) fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private(),
+        IpAddr::V6(ip) => (ip.segments()[0] & 0xfe00) == 0xfc00,
+    }
+}
+
+fn is_ignored_domain(domain: &str) -> bool {
+    let domain = domain.to_lowercase();
+    !domain.contains('.') || IGNORED_DOMAIN_SUFFIXES.iter().any(|s| domain.ends_with(s))
+}
+
+fn is_persistence_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    PERSISTENCE_KEYS.iter().any(|k| key.contains(k))
+}
+
+impl IocSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, ioc: Ioc, confidence: Confidence, time: DateTime<FixedOffset>) {
+        let indicator = self.indicators.entry(ioc.clone()).or_insert(Indicator {
+            ioc,
+            confidence,
+            first_seen: time,
+            occurrences: 0,
+        });
+        indicator.confidence = indicator.confidence.max(confidence);
+        indicator.first_seen = indicator.first_seen.min(time);
+        indicator.occurrences += 1;
+    }
+
+    pub fn get(&self, ioc: &Ioc) -> Option<&Indicator> {
+        self.indicators.get(ioc)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Indicator> {
+        self.indicators.values()
+    }
+
+    pub fn with_min_confidence(&self, confidence: Confidence) -> impl Iterator<Item = &Indicator> {
+        self.iter().filter(move |i| i.confidence >= confidence)
+    }
+
+    pub fn len(&self) -> usize {
+        self.indicators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indicators.is_empty()
+    }
+
+    fn insert_hashes(
+        &mut self,
+        hashes: &Hashes,
+        confidence: Confidence,
+        time: DateTime<FixedOffset>,
+    ) {
+        for (algorithm, value) in hashes.iter() {
+            self.insert(Ioc::from_hash(algorithm, value), confidence, time);
+        }
+    }
+
+    pub fn from_events(events: &[SysmonEvent]) -> Self {
+        let url = Regex::new(URL_PATTERN).unwrap();
+        let mut set = Self::new();
+
+        for event in events {
+            let time = event.time_created;
+            match event.typed_data() {
+                TypedEventData::ProcessCreate(d) => {
+                    for m in url.find_iter(&d.command_line) {
+                        let value = m.as_str().trim_end_matches([',', ';', ')', ']']);
+                        set.insert(Ioc::Url(value.to_string()), Confidence::High, time);
+                    }
+                    set.insert_hashes(&d.hashes, Confidence::Low, time);
+                }
+                TypedEventData::NetworkConnect(NetworkConnect {
+                    destination_ip,
+                    destination_hostname,
+                    initiated,
+                    ..
+                }) => {
+                    if let Some(ip) = destination_ip.filter(is_routable) {
+                        let confidence = if is_private(&ip) {
+                            Confidence::Low
+                        } else if initiated {
+                            Confidence::High
+                        } else {
+                            Confidence::Medium
+                        };
+                        set.insert(Ioc::Ip(ip), confidence, time);
+                    }
+                    if let Some(hostname) = destination_hostname.filter(|h| !is_ignored_domain(h)) {
+                        set.insert(
+                            Ioc::Domain(hostname.to_lowercase()),
+                            Confidence::Medium,
+                            time,
+                        );
+                    }
+                }
+                TypedEventData::DnsQuery(d) => {
+                    if is_ignored_domain(&d.query_name) {
+                        continue;
+                    }
+                    set.insert(
+                        Ioc::Domain(d.query_name.to_lowercase()),
+                        Confidence::Medium,
+                        time,
+                    );
+                    for ip in d.query_results.iter().filter(|ip| is_routable(ip)) {
+                        set.insert(Ioc::Ip(*ip), Confidence::Low, time);
+                    }
+                }
+                TypedEventData::FileCreate(d) | TypedEventData::FileCreateStreamHash(d) => {
+                    set.insert(Ioc::FilePath(d.target_filename), Confidence::Medium, time);
+                    set.insert_hashes(&d.hashes, Confidence::High, time);
+                }
+                TypedEventData::FileExecutableDetected(d) => {
+                    set.insert(Ioc::FilePath(d.target_filename), Confidence::Medium, time);
+                    set.insert_hashes(&d.hashes, Confidence::High, time);
+                }
+                TypedEventData::FileDelete(d) | TypedEventData::FileBlock(d) => {
+                    set.insert_hashes(&d.hashes, Confidence::High, time);
+                }
+                TypedEventData::PipeEvent(d) => {
+                    set.insert(Ioc::Pipe(d.pipe_name), Confidence::Medium, time);
+                }
+                TypedEventData::RegistryEvent(d) if is_persistence_key(&d.target_object) => {
+                    set.insert(Ioc::RegistryKey(d.target_object), Confidence::High, time);
+                }
+                _ => (),
+            }
+        }
+
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ioc_set_round_trips_through_json() {
+        let time = DateTime::parse_from_rfc3339("2024-01-01T00:00:00+00:00").unwrap();
+        let mut set = IocSet::new();
+        set.insert(
+            Ioc::Ip("203.0.113.7".parse().unwrap()),
+            Confidence::Low,
+            time,
+        );
+        set.insert(
+            Ioc::Domain("evil.example".to_string()),
+            Confidence::Medium,
+            time,
+        );
+        set.insert(
+            Ioc::Domain("evil.example".to_string()),
+            Confidence::High,
+            time,
+        );
+
+        let json = serde_json::to_string(&set).unwrap();
+        let parsed: IocSet = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        let domain = parsed
+            .get(&Ioc::Domain("evil.example".to_string()))
+            .unwrap();
+        assert_eq!(domain.confidence, Confidence::High);
+        assert_eq!(domain.occurrences, 2);
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+    }
+}
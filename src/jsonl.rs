@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use serde_json::{Map, Value};
+
+use crate::schema::event_from_value;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const NXLOG_HEADER_FIELDS: &[&str] = &[
+    "EventTime",
+    "EventReceivedTime",
+    "Hostname",
+    "EventID",
+    "RecordNumber",
+    "Channel",
+    "SourceModuleName",
+    "SourceModuleType",
+    "SourceName",
+    "ProviderGuid",
+    "Keywords",
+    "EventType",
+    "SeverityValue",
+    "Severity",
+    "Version",
+    "Task",
+    "OpcodeValue",
+    "ExecutionProcessID",
+    "ThreadID",
+    "Domain",
+    "AccountName",
+    "UserID",
+    "AccountType",
+    "Category",
+    "Opcode",
+    "Message",
+];
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Object(o) => o.get("#text").and_then(text),
+        _ => None,
+    }
+}
+
+fn get<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |v, key| v.get(key))
+}
+
+fn parse_time(s: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(s).ok().or_else(|| {
+        ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+            .iter()
+            .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+            .map(|t| Utc.from_utc_datetime(&t).fixed_offset())
+    })
+}
+
+fn event_id(value: &Value) -> Result<SysmonEventId> {
+    text(value).context("EventID is empty")?.trim().parse()
+}
+
+fn event_data_map(map: &Map<String, Value>) -> HashMap<String, String> {
+    map.iter()
+        .filter_map(|(k, v)| Some((k.clone(), text(v)?)))
+        .collect()
+}
+
+fn winlogbeat(winlog: &Value, root: &Value) -> Result<SysmonEvent> {
+    let time = root
+        .get("@timestamp")
+        .or_else(|| get(root, &["event", "created"]))
+        .and_then(Value::as_str)
+        .and_then(parse_time)
+        .context("No @timestamp")?;
+
+    Ok(SysmonEvent {
+        event_id: event_id(winlog.get("event_id").context("No winlog.event_id")?)?,
+        time_created: time,
+        computer: winlog.get("computer_name").and_then(text),
+        record_id: winlog
+            .get("record_id")
+            .and_then(text)
+            .and_then(|r| r.parse().ok()),
+        channel: winlog.get("channel").and_then(text),
+        event_data: winlog
+            .get("event_data")
+            .and_then(Value::as_object)
+            .map(event_data_map)
+            .unwrap_or_default(),
+    })
+}
+
+fn xml_shaped(event: &Value) -> Result<SysmonEvent> {
+    let system = event.get("System").context("No Event.System")?;
+    let time = get(system, &["TimeCreated", "@SystemTime"])
+        .or_else(|| get(system, &["TimeCreated", "SystemTime"]))
+        .and_then(Value::as_str)
+        .and_then(parse_time)
+        .context("No TimeCreated")?;
+
+    let mut event_data = HashMap::new();
+    match get(event, &["EventData", "Data"]) {
+        Some(Value::Array(items)) => {
+            for item in items {
+                let name = item.get("@Name").or_else(|| item.get("Name"));
+                if let Some(name) = name.and_then(Value::as_str) {
+                    let value = item.get("#text").and_then(text).unwrap_or_default();
+                    event_data.insert(name.to_string(), value);
+                }
+            }
+        }
+        Some(Value::Object(map)) => event_data = event_data_map(map),
+        _ => {
+            if let Some(map) = event.get("EventData").and_then(Value::as_object) {
+                event_data = event_data_map(map);
+            }
+        }
+    }
+
+    Ok(SysmonEvent {
+        event_id: event_id(system.get("EventID").context("No EventID")?)?,
+        time_created: time,
+        computer: system.get("Computer").and_then(text),
+        record_id: system
+            .get("EventRecordID")
+            .and_then(text)
+            .and_then(|r| r.parse().ok()),
+        channel: system.get("Channel").and_then(text),
+        event_data,
+    })
+}
+
+fn nxlog(map: &Map<String, Value>) -> Result<SysmonEvent> {
+    let time = map
+        .get("EventTime")
+        .and_then(Value::as_str)
+        .and_then(parse_time)
+        .context("No EventTime")?;
+    let event_data = map
+        .iter()
+        .filter(|(k, _)| !NXLOG_HEADER_FIELDS.contains(&k.as_str()))
+        .filter_map(|(k, v)| Some((k.clone(), text(v)?)))
+        .collect();
+
+    Ok(SysmonEvent {
+        event_id: event_id(map.get("EventID").context("No EventID")?)?,
+        time_created: time,
+        computer: map.get("Hostname").and_then(text),
+        record_id: map
+            .get("RecordNumber")
+            .and_then(text)
+            .and_then(|r| r.parse().ok()),
+        channel: map.get("Channel").and_then(text),
+        event_data,
+    })
+}
+
+pub fn from_json_value(value: &Value) -> Result<SysmonEvent> {
+    let value = value.get("result").unwrap_or(value);
+
+    if let Some(raw) = value.get("_raw").and_then(Value::as_str) {
+        if raw.trim_start().starts_with('<') {
+            return SysmonEvent::from_xml(raw);
+        }
+        return from_json_value(&serde_json::from_str(raw)?);
+    }
+    if let Some(winlog) = value.get("winlog") {
+        return winlogbeat(winlog, value);
+    }
+    if let Some(event) = value.get("Event") {
+        return xml_shaped(event);
+    }
+    if value.get("event_id").is_some() && value.get("event_data").is_some() {
+        return event_from_value(value.clone());
+    }
+    match value {
+        Value::Object(map) if map.contains_key("EventID") => nxlog(map),
+        _ => bail!("Unrecognized JSON event shape"),
+    }
+}
+
+pub struct JsonlReader<R> {
+    reader: R,
+    line: String,
+}
+
+impl<R: BufRead> JsonlReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonlReader<R> {
+    type Item = Result<SysmonEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(e.into())),
+            }
+            let line = self.line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            return Some(
+                serde_json::from_str::<Value>(line)
+                    .map_err(Into::into)
+                    .and_then(|v| from_json_value(&v)),
+            );
+        }
+    }
+}
@@ -1,5 +1,76 @@
+pub mod agent;
 pub mod analysis_result;
 pub mod analyzer;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod artifacts;
+pub mod attack;
+pub mod auditd;
+pub mod baseline;
+pub mod beacon;
+pub mod browsing;
+pub mod bus;
+pub mod checkpoint;
+pub mod clock;
+pub mod cmdline;
+#[cfg(all(windows, feature = "windows"))]
+pub mod collector;
+pub mod compact_event;
+pub mod config_extractor;
+pub mod correlation;
+pub mod dns;
+pub mod encoding;
+pub mod enrichment;
+#[cfg(all(windows, feature = "windows"))]
+pub mod etw;
+pub mod event_data;
+pub mod event_filter;
+pub mod event_reader;
+#[cfg(feature = "evtx")]
+pub mod evtx;
+pub mod export;
+pub mod features;
+pub mod filesystem;
+pub mod flags;
+pub mod guid;
+pub mod hashes;
+pub mod ioc;
+pub mod jsonl;
+pub mod lineage;
+pub mod memory;
+pub mod metadata;
+pub mod metrics;
+pub mod misp;
+pub mod netsim;
+pub mod network;
+pub mod notify;
+pub mod opencti;
+pub mod orchestrator;
+pub mod path;
+pub mod pcap;
+pub mod pipeline;
+pub mod plugin;
+pub mod process_tree;
+pub mod quarantine;
+pub mod registry;
+pub mod replay;
+pub mod report;
+pub mod rule_pack;
 pub mod sandbox;
+pub mod scheduler;
+pub mod schema;
+pub mod scoring;
+pub mod similarity;
+pub mod sink;
+pub mod static_analysis;
+pub mod storage;
+pub mod sync_objects;
+pub mod syslog;
+pub mod sysmon_config;
 pub mod sysmon_event;
+pub mod tamper;
+pub mod telemetry;
+pub mod timeline;
+pub mod verdict;
 pub mod vm;
+pub mod vmi;
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis_result::AnalysisResult;
+use crate::artifacts::Artifact;
+
+pub const MAX_GENERATIONS: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DerivationKind {
+    DroppedFile,
+    UnpackedPayload,
+    ContainerEntry,
+    EmailAttachment,
+}
+
+impl DerivationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DroppedFile => "dropped_file",
+            Self::UnpackedPayload => "unpacked_payload",
+            Self::ContainerEntry => "container_entry",
+            Self::EmailAttachment => "email_attachment",
+        }
+    }
+
+    pub fn of_artifact(artifact: &Artifact) -> Self {
+        if artifact.dumped() {
+            Self::UnpackedPayload
+        } else {
+            Self::DroppedFile
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Derivation {
+    pub kind: DerivationKind,
+    pub analysis_id: String,
+    #[serde(default)]
+    pub job_id: Option<String>,
+    #[serde(default)]
+    pub execution_id: Option<String>,
+    pub artifact: String,
+    pub sha256: String,
+}
+
+pub fn extend(chain: &[Derivation], link: Derivation) -> Vec<Derivation> {
+    let mut chain = chain.to_vec();
+    chain.push(link);
+    chain
+}
+
+pub fn can_derive(chain: &[Derivation], analysis_id: &str) -> bool {
+    chain.len() < MAX_GENERATIONS && !chain.iter().any(|d| d.analysis_id == analysis_id)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Descendant {
+    pub analysis_id: String,
+    pub execution_id: String,
+    pub generation: usize,
+    pub derivation: Derivation,
+}
+
+pub fn descendants(analysis_id: &str, results: &[AnalysisResult]) -> Vec<Descendant> {
+    let mut descendants: Vec<Descendant> = results
+        .iter()
+        .flat_map(|result| {
+            result.execution_logs.iter().filter_map(|log| {
+                let index = log
+                    .derivation
+                    .iter()
+                    .position(|d| d.analysis_id == analysis_id)?;
+                Some(Descendant {
+                    analysis_id: result.id.clone(),
+                    execution_id: log.id.clone(),
+                    generation: log.derivation.len() - index,
+                    derivation: log.derivation.last()?.clone(),
+                })
+            })
+        })
+        .collect();
+    descendants.sort_by(|a, b| {
+        (a.generation, &a.derivation.analysis_id, &a.analysis_id).cmp(&(
+            b.generation,
+            &b.derivation.analysis_id,
+            &b.analysis_id,
+        ))
+    });
+    descendants
+}
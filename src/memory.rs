@@ -0,0 +1,368 @@
+pub mod payload;
+
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use goblin::elf::program_header::{PF_W, PF_X, PT_LOAD};
+use goblin::elf::Elf;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::protocol::{DumpTrigger, MemoryDump};
+use payload::Payload;
+
+pub const MEMORY_FILE: &str = "memory.json";
+pub const DUMP_PREFIX: &str = "memory-";
+
+const MIN_STRING_LEN: usize = 6;
+const MAX_STRINGS: usize = 1000;
+const PAGE_SIZE: usize = 0x1000;
+
+const MINIDUMP_SIGNATURE: &[u8] = b"MDMP";
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const MEMORY64_LIST_STREAM: u32 = 9;
+const MEMORY_INFO_LIST_STREAM: u32 = 16;
+const MINIDUMP_MODULE_SIZE: usize = 108;
+
+const MEM_COMMIT: u32 = 0x1000;
+const MEM_IMAGE: u32 = 0x100_0000;
+const PAGE_READWRITE: u32 = 0x04;
+const PAGE_WRITECOPY: u32 = 0x08;
+const PAGE_EXECUTE_READWRITE: u32 = 0x40;
+const NT_FILE: u32 = 0x4649_4c45;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    RwxRegion,
+    UnbackedPe,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryFinding {
+    pub kind: FindingKind,
+    pub address: u64,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryAnalysis {
+    pub dump: String,
+    pub trigger: DumpTrigger,
+    pub process_id: Option<u32>,
+    pub strings: Vec<String>,
+    pub findings: Vec<MemoryFinding>,
+    #[serde(default)]
+    pub payloads: Vec<Payload>,
+}
+
+impl MemoryAnalysis {
+    pub fn rwx_regions(&self) -> impl Iterator<Item = &MemoryFinding> {
+        self.findings
+            .iter()
+            .filter(|f| f.kind == FindingKind::RwxRegion)
+    }
+
+    pub fn unbacked_pes(&self) -> impl Iterator<Item = &MemoryFinding> {
+        self.findings
+            .iter()
+            .filter(|f| f.kind == FindingKind::UnbackedPe)
+    }
+}
+
+struct Region {
+    address: u64,
+    size: u64,
+    executable: bool,
+    writable: bool,
+    backed: bool,
+    data: Range<usize>,
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+pub fn is_minidump(data: &[u8]) -> bool {
+    data.starts_with(MINIDUMP_SIGNATURE)
+}
+
+fn streams(data: &[u8]) -> Vec<(u32, Range<usize>)> {
+    let count = u32_at(data, 8).unwrap_or_default() as usize;
+    let directory = u32_at(data, 12).unwrap_or_default() as usize;
+    (0..count)
+        .filter_map(|i| {
+            let entry = directory + i * 12;
+            let kind = u32_at(data, entry)?;
+            let size = u32_at(data, entry + 4)? as usize;
+            let rva = u32_at(data, entry + 8)? as usize;
+            (rva + size <= data.len()).then_some((kind, rva..rva + size))
+        })
+        .collect()
+}
+
+fn minidump_regions(data: &[u8]) -> Result<Vec<Region>> {
+    let streams = streams(data);
+    let stream = |kind: u32| {
+        streams
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, range)| range.start)
+    };
+
+    let mut modules = Vec::new();
+    if let Some(start) = stream(MODULE_LIST_STREAM) {
+        let count = u32_at(data, start).unwrap_or_default() as usize;
+        for i in 0..count {
+            let entry = start + 4 + i * MINIDUMP_MODULE_SIZE;
+            let (Some(base), Some(size)) = (u64_at(data, entry), u32_at(data, entry + 8)) else {
+                break;
+            };
+            modules.push(base..base + size as u64);
+        }
+    }
+
+    let mut infos = Vec::new();
+    if let Some(start) = stream(MEMORY_INFO_LIST_STREAM) {
+        let header = u32_at(data, start).unwrap_or_default() as usize;
+        let entry_size = u32_at(data, start + 4).unwrap_or_default() as usize;
+        let count = u64_at(data, start + 8).unwrap_or_default() as usize;
+        for i in (0..count).take_while(|_| entry_size >= 44) {
+            let entry = start + header + i * entry_size;
+            let (Some(base), Some(size), Some(state), Some(protect), Some(kind)) = (
+                u64_at(data, entry),
+                u64_at(data, entry + 24),
+                u32_at(data, entry + 32),
+                u32_at(data, entry + 36),
+                u32_at(data, entry + 40),
+            ) else {
+                break;
+            };
+            if state == MEM_COMMIT {
+                infos.push((base..base + size, protect, kind));
+            }
+        }
+    }
+
+    let mut ranges = Vec::new();
+    if let Some(start) = stream(MEMORY64_LIST_STREAM) {
+        let count = u64_at(data, start).unwrap_or_default() as usize;
+        let mut rva = u64_at(data, start + 8).unwrap_or_default() as usize;
+        for i in 0..count {
+            let entry = start + 16 + i * 16;
+            let (Some(address), Some(size)) = (u64_at(data, entry), u64_at(data, entry + 8)) else {
+                break;
+            };
+            let end = rva.saturating_add(size as usize);
+            ranges.push((address, size, rva..end));
+            rva = end;
+        }
+    } else if let Some(start) = stream(MEMORY_LIST_STREAM) {
+        let count = u32_at(data, start).unwrap_or_default() as usize;
+        for i in 0..count {
+            let entry = start + 4 + i * 16;
+            let (Some(address), Some(size), Some(rva)) = (
+                u64_at(data, entry),
+                u32_at(data, entry + 8),
+                u32_at(data, entry + 12),
+            ) else {
+                break;
+            };
+            let rva = rva as usize;
+            ranges.push((address, size as u64, rva..rva + size as usize));
+        }
+    }
+    if ranges.is_empty() {
+        bail!("Minidump contains no memory");
+    }
+
+    Ok(ranges
+        .into_iter()
+        .filter(|(_, _, data_range)| data_range.end <= data.len())
+        .map(|(address, size, data)| {
+            let info = infos.iter().find(|(range, _, _)| range.contains(&address));
+            let protect = info
+                .map(|(_, protect, _)| protect & 0xff)
+                .unwrap_or_default();
+            let image = match info {
+                Some((_, _, kind)) => *kind == MEM_IMAGE,
+                None => modules.iter().any(|m| m.contains(&address)),
+            };
+            Region {
+                address,
+                size,
+                executable: protect & 0xf0 != 0,
+                writable: matches!(
+                    protect,
+                    PAGE_READWRITE | PAGE_WRITECOPY | PAGE_EXECUTE_READWRITE
+                ),
+                backed: image,
+                data,
+            }
+        })
+        .collect())
+}
+
+fn elf_core_regions(data: &[u8]) -> Result<Vec<Region>> {
+    let elf = Elf::parse(data)?;
+    let mut files = Vec::new();
+    if let Some(notes) = elf.iter_note_headers(data) {
+        for note in notes.flatten() {
+            if note.n_type != NT_FILE {
+                continue;
+            }
+            let count = u64_at(note.desc, 0).unwrap_or_default() as usize;
+            for i in 0..count {
+                let entry = 16 + i * 24;
+                let (Some(start), Some(end)) =
+                    (u64_at(note.desc, entry), u64_at(note.desc, entry + 8))
+                else {
+                    break;
+                };
+                files.push(start..end);
+            }
+        }
+    }
+
+    Ok(elf
+        .program_headers
+        .iter()
+        .filter(|h| h.p_type == PT_LOAD && h.p_filesz > 0)
+        .filter(|h| h.p_offset.saturating_add(h.p_filesz) <= data.len() as u64)
+        .map(|h| Region {
+            address: h.p_vaddr,
+            size: h.p_memsz,
+            executable: h.p_flags & PF_X != 0,
+            writable: h.p_flags & PF_W != 0,
+            backed: files.iter().any(|f| f.contains(&h.p_vaddr)),
+            data: h.p_offset as usize..(h.p_offset + h.p_filesz) as usize,
+        })
+        .collect())
+}
+
+fn regions(data: &[u8]) -> Result<Vec<Region>> {
+    if is_minidump(data) {
+        minidump_regions(data)
+    } else if data.starts_with(b"\x7fELF") {
+        elf_core_regions(data)
+    } else {
+        bail!("Unsupported memory dump format");
+    }
+}
+
+fn is_pe_header(data: &[u8]) -> bool {
+    if !data.starts_with(b"MZ") {
+        return false;
+    }
+    let Some(offset) = u32_at(data, 0x3c) else {
+        return false;
+    };
+    data.get(offset as usize..offset as usize + 4) == Some(b"PE\0\0".as_slice())
+}
+
+fn find_findings(data: &[u8], regions: &[Region]) -> Vec<MemoryFinding> {
+    let mut findings = Vec::new();
+    for region in regions {
+        if region.executable && region.writable {
+            findings.push(MemoryFinding {
+                kind: FindingKind::RwxRegion,
+                address: region.address,
+                size: region.size,
+            });
+        }
+        if region.backed {
+            continue;
+        }
+        let bytes = &data[region.data.clone()];
+        for offset in (0..bytes.len()).step_by(PAGE_SIZE) {
+            if is_pe_header(&bytes[offset..]) {
+                findings.push(MemoryFinding {
+                    kind: FindingKind::UnbackedPe,
+                    address: region.address + offset as u64,
+                    size: region.size - offset as u64,
+                });
+            }
+        }
+    }
+    findings
+}
+
+fn ascii_strings(data: &[u8], min_len: usize) -> Vec<String> {
+    data.split(|b| !(b.is_ascii_graphic() || *b == b' '))
+        .filter(|s| s.len() >= min_len)
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
+
+fn utf16_strings(data: &[u8], min_len: usize) -> Vec<String> {
+    let mut strings = Vec::new();
+    for start in 0..2 {
+        let mut current = String::new();
+        for chunk in data[start.min(data.len())..].chunks_exact(2) {
+            match (chunk[0], chunk[1]) {
+                (c, 0) if c.is_ascii_graphic() || c == b' ' => current.push(c as char),
+                _ if current.len() >= min_len => strings.push(std::mem::take(&mut current)),
+                _ => current.clear(),
+            }
+        }
+        if current.len() >= min_len {
+            strings.push(current);
+        }
+    }
+    strings
+}
+
+pub fn extract_strings(data: &[u8], min_len: usize) -> Vec<String> {
+    let mut strings = ascii_strings(data, min_len);
+    strings.extend(utf16_strings(data, min_len));
+    strings
+}
+
+fn is_interesting(s: &str) -> bool {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN
+        .get_or_init(|| {
+            Regex::new(
+                r"(?i)https?://|\b(?:\d{1,3}\.){3}\d{1,3}\b|\\software\\|hkey_|[a-z]:\\|\.(?:exe|dll|ps1|bat|vbs)\b|powershell|cmd\.exe|/bin/|/tmp/|cryptunprotect|sekurlsa::|lsadump::|dpapi::",
+            )
+            .expect("Invalid memory string pattern")
+        })
+        .is_match(s)
+}
+
+pub fn analyze(name: &str, dump: &MemoryDump, data: &[u8]) -> MemoryAnalysis {
+    let findings = match regions(data) {
+        Ok(regions) => find_findings(data, &regions),
+        Err(_) => Vec::new(),
+    };
+    let mut seen = HashSet::new();
+    let strings = extract_strings(data, MIN_STRING_LEN)
+        .into_iter()
+        .filter(|s| is_interesting(s))
+        .filter(|s| seen.insert(s.clone()))
+        .take(MAX_STRINGS)
+        .collect();
+    MemoryAnalysis {
+        dump: name.to_string(),
+        trigger: dump.trigger,
+        process_id: dump.process_id,
+        strings,
+        findings,
+        payloads: Vec::new(),
+    }
+}
+
+pub fn dump_file_name(index: usize) -> String {
+    format!("{}{}.dmp", DUMP_PREFIX, index)
+}
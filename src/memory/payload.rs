@@ -0,0 +1,592 @@
+use std::collections::{HashMap, HashSet};
+
+use goblin::pe::options::ParseOptions;
+use goblin::pe::PE;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    is_minidump, is_pe_header, streams, u32_at, u64_at, Region, MINIDUMP_MODULE_SIZE,
+    MODULE_LIST_STREAM, PAGE_SIZE,
+};
+use crate::static_analysis::pe;
+
+pub const PAYLOAD_SCHEME: &str = "memory://";
+
+const MAX_PAYLOAD_SIZE: usize = 64 * 1024 * 1024;
+const MAX_PAYLOADS: usize = 32;
+const MAX_DESCRIPTORS: usize = 512;
+const MAX_THUNKS: usize = 4096;
+const MAX_NAME_LEN: usize = 256;
+const MIN_MODIFIED_RATIO: f64 = 0.2;
+const MIN_IAT_RUN: usize = 2;
+
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const SECTION_HEADER_SIZE: usize = 40;
+const IMPORT_DESCRIPTOR_SIZE: usize = 20;
+const IMPORT_DIRECTORY: usize = 1;
+const IMAGE_SCN_CNT_CODE: u32 = 0x20;
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const REBUILT_SECTION_NAME: &[u8] = b".rimp\0\0\0";
+const REBUILT_SECTION_FLAGS: u32 = 0xc000_0040;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadOrigin {
+    Unbacked,
+    ModifiedImage,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportRecovery {
+    Restored,
+    Rebuilt,
+    Unresolved,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecoveredImport {
+    pub dll: String,
+    pub functions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Payload {
+    pub path: String,
+    pub address: u64,
+    pub size: u64,
+    pub origin: PayloadOrigin,
+    pub sha256: String,
+    pub imphash: Option<String>,
+    pub import_recovery: ImportRecovery,
+    pub imports: Vec<RecoveredImport>,
+}
+
+struct Headers {
+    pe: usize,
+    optional: usize,
+    sections: usize,
+    section_count: usize,
+    plus: bool,
+}
+
+impl Headers {
+    fn parse(image: &[u8]) -> Option<Self> {
+        let pe = u32_at(image, 0x3c)? as usize;
+        if image.get(pe..pe + 4)? != b"PE\0\0" {
+            return None;
+        }
+        let optional = pe + 24;
+        let plus = match u16_at(image, optional)? {
+            PE32_MAGIC => false,
+            PE32_PLUS_MAGIC => true,
+            _ => return None,
+        };
+        Some(Self {
+            pe,
+            optional,
+            sections: optional + u16_at(image, pe + 20)? as usize,
+            section_count: u16_at(image, pe + 6)? as usize,
+            plus,
+        })
+    }
+
+    fn pointer_size(&self) -> usize {
+        if self.plus {
+            8
+        } else {
+            4
+        }
+    }
+
+    fn time_date_stamp(&self, image: &[u8]) -> Option<u32> {
+        u32_at(image, self.pe + 8)
+    }
+
+    fn entry_point(&self, image: &[u8]) -> Option<u32> {
+        u32_at(image, self.optional + 16)
+    }
+
+    fn section_alignment(&self, image: &[u8]) -> usize {
+        u32_at(image, self.optional + 32)
+            .filter(|a| *a > 0)
+            .unwrap_or(PAGE_SIZE as u32) as usize
+    }
+
+    fn size_of_image(&self, image: &[u8]) -> Option<usize> {
+        u32_at(image, self.optional + 56).map(|s| s as usize)
+    }
+
+    fn size_of_headers(&self, image: &[u8]) -> usize {
+        u32_at(image, self.optional + 60).unwrap_or(PAGE_SIZE as u32) as usize
+    }
+
+    fn data_directory(&self, index: usize) -> usize {
+        self.optional + if self.plus { 112 } else { 96 } + index * 8
+    }
+
+    fn section(&self, index: usize) -> usize {
+        self.sections + index * SECTION_HEADER_SIZE
+    }
+}
+
+struct Module {
+    name: String,
+    base: u64,
+    size: u64,
+}
+
+struct IatRun {
+    offset: usize,
+    dll: String,
+    functions: Vec<String>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+    if let Some(slot) = data.get_mut(offset..offset + bytes.len()) {
+        slot.copy_from_slice(bytes);
+    }
+}
+
+fn pointer_at(data: &[u8], offset: usize, size: usize) -> Option<u64> {
+    match size {
+        8 => u64_at(data, offset),
+        _ => u32_at(data, offset).map(u64::from),
+    }
+}
+
+fn put_pointer(data: &mut [u8], offset: usize, value: u64, size: usize) {
+    match size {
+        8 => put(data, offset, &value.to_le_bytes()),
+        _ => put(data, offset, &(value as u32).to_le_bytes()),
+    }
+}
+
+fn align_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+fn c_string(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let end = bytes.iter().take(MAX_NAME_LEN).position(|b| *b == 0)?;
+    let name = &bytes[..end];
+    (!name.is_empty() && name.iter().all(u8::is_ascii_graphic))
+        .then(|| String::from_utf8_lossy(name).into_owned())
+}
+
+fn read_image(data: &[u8], regions: &[Region], base: u64, size: usize) -> Vec<u8> {
+    let mut image = vec![0; size];
+    let end = base.saturating_add(size as u64);
+    for region in regions {
+        let region_end = region
+            .address
+            .saturating_add(region.data.len() as u64)
+            .min(end);
+        let start = region.address.max(base);
+        if start >= region_end {
+            continue;
+        }
+        let source = region.data.start + (start - region.address) as usize;
+        let target = (start - base) as usize;
+        let len = (region_end - start) as usize;
+        image[target..target + len].copy_from_slice(&data[source..source + len]);
+    }
+    image
+}
+
+fn minidump_string(data: &[u8], offset: usize) -> Option<String> {
+    let len = u32_at(data, offset)? as usize;
+    let units: Vec<u16> = data
+        .get(offset + 4..offset + 4 + len)?
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+fn modules(data: &[u8]) -> Vec<Module> {
+    if !is_minidump(data) {
+        return Vec::new();
+    }
+    let Some((_, stream)) = streams(data)
+        .into_iter()
+        .find(|(kind, _)| *kind == MODULE_LIST_STREAM)
+    else {
+        return Vec::new();
+    };
+    let count = u32_at(data, stream.start).unwrap_or_default() as usize;
+    (0..count)
+        .map_while(|i| {
+            let entry = stream.start + 4 + i * MINIDUMP_MODULE_SIZE;
+            let path = minidump_string(data, u32_at(data, entry + 20)? as usize)?;
+            Some(Module {
+                name: path
+                    .rsplit(['\\', '/'])
+                    .next()
+                    .unwrap_or(&path)
+                    .to_lowercase(),
+                base: u64_at(data, entry)?,
+                size: u32_at(data, entry + 8)? as u64,
+            })
+        })
+        .collect()
+}
+
+fn exports(data: &[u8], regions: &[Region]) -> HashMap<u64, (String, String)> {
+    let options = ParseOptions { resolve_rva: false };
+    let mut exports = HashMap::new();
+    for module in modules(data) {
+        let size = (module.size as usize).min(MAX_PAYLOAD_SIZE);
+        let image = read_image(data, regions, module.base, size);
+        let Ok(pe) = PE::parse_with_opts(&image, &options) else {
+            continue;
+        };
+        for export in pe.exports.iter().filter(|e| e.reexport.is_none()) {
+            if let Some(name) = export.name {
+                exports
+                    .entry(module.base + export.rva as u64)
+                    .or_insert_with(|| (module.name.clone(), name.to_string()));
+            }
+        }
+    }
+    exports
+}
+
+fn is_modified(image: &[u8], headers: &Headers, sample: &[u8], sample_headers: &Headers) -> bool {
+    if headers.entry_point(image) != sample_headers.entry_point(sample) {
+        return true;
+    }
+    let (mut differing, mut total) = (0usize, 0usize);
+    for i in 0..sample_headers.section_count {
+        let header = sample_headers.section(i);
+        let (Some(virtual_size), Some(address), Some(raw_size), Some(raw), Some(flags)) = (
+            u32_at(sample, header + 8),
+            u32_at(sample, header + 12),
+            u32_at(sample, header + 16),
+            u32_at(sample, header + 20),
+            u32_at(sample, header + 36),
+        ) else {
+            break;
+        };
+        if flags & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) == 0 {
+            continue;
+        }
+        let len = virtual_size.min(raw_size) as usize;
+        let (Some(on_disk), Some(in_memory)) = (
+            sample.get(raw as usize..raw as usize + len),
+            image.get(address as usize..address as usize + len),
+        ) else {
+            continue;
+        };
+        differing += on_disk
+            .iter()
+            .zip(in_memory)
+            .filter(|(a, b)| a != b)
+            .count();
+        total += len;
+    }
+    total > 0 && differing as f64 / total as f64 >= MIN_MODIFIED_RATIO
+}
+
+fn realign(image: &mut [u8], headers: &Headers, base: u64) {
+    let alignment = headers.section_alignment(image);
+    put(
+        image,
+        headers.optional + 36,
+        &(alignment as u32).to_le_bytes(),
+    );
+    if headers.plus {
+        put(image, headers.optional + 24, &base.to_le_bytes());
+    } else {
+        put(image, headers.optional + 28, &(base as u32).to_le_bytes());
+    }
+    let sections: Vec<(usize, usize)> = (0..headers.section_count)
+        .map(|i| headers.section(i))
+        .map_while(|header| Some((header, u32_at(image, header + 12)? as usize)))
+        .collect();
+    for (i, (header, address)) in sections.iter().enumerate() {
+        let end = sections
+            .get(i + 1)
+            .map(|(_, next)| *next)
+            .unwrap_or(image.len())
+            .min(image.len());
+        let size = end.saturating_sub(*address) as u32;
+        put(image, header + 16, &size.to_le_bytes());
+        put(image, header + 20, &(*address as u32).to_le_bytes());
+    }
+}
+
+fn restore_imports(image: &mut [u8], headers: &Headers) -> Option<Vec<RecoveredImport>> {
+    let directory = u32_at(image, headers.data_directory(IMPORT_DIRECTORY))? as usize;
+    if directory == 0 {
+        return None;
+    }
+    let size = headers.pointer_size();
+    let ordinal = 1u64 << (size * 8 - 1);
+    let mut imports = Vec::new();
+    let mut patches = Vec::new();
+    for i in 0..MAX_DESCRIPTORS {
+        let descriptor = directory + i * IMPORT_DESCRIPTOR_SIZE;
+        let original = u32_at(image, descriptor)? as usize;
+        let name = u32_at(image, descriptor + 12)? as usize;
+        let first = u32_at(image, descriptor + 16)? as usize;
+        if name == 0 && first == 0 {
+            break;
+        }
+        if original == 0 {
+            return None;
+        }
+        let mut functions = Vec::new();
+        for j in 0..MAX_THUNKS {
+            let thunk = pointer_at(image, original + j * size, size)?;
+            if thunk == 0 {
+                break;
+            }
+            functions.push(match thunk & ordinal {
+                0 => c_string(image, thunk as usize + 2)?,
+                _ => format!("#{}", thunk & 0xffff),
+            });
+            patches.push((first + j * size, thunk));
+        }
+        imports.push(RecoveredImport {
+            dll: c_string(image, name)?,
+            functions,
+        });
+    }
+    if imports.is_empty() {
+        return None;
+    }
+    for (offset, thunk) in patches {
+        put_pointer(image, offset, thunk, size);
+    }
+    Some(imports)
+}
+
+fn iat_runs(
+    image: &[u8],
+    headers: &Headers,
+    exports: &HashMap<u64, (String, String)>,
+) -> Vec<IatRun> {
+    let size = headers.pointer_size();
+    let mut blocks: Vec<Vec<(usize, &String, &String)>> = Vec::new();
+    let mut block = Vec::new();
+    let mut offset = align_up(headers.size_of_headers(image), size);
+    while offset + size <= image.len() {
+        let value = pointer_at(image, offset, size).unwrap_or_default();
+        match exports.get(&value) {
+            Some((dll, name)) => block.push((offset, dll, name)),
+            None if block.len() >= MIN_IAT_RUN => blocks.push(std::mem::take(&mut block)),
+            None => block.clear(),
+        }
+        offset += size;
+    }
+    if block.len() >= MIN_IAT_RUN {
+        blocks.push(block);
+    }
+
+    let mut runs: Vec<IatRun> = Vec::new();
+    for block in blocks {
+        let mut previous = None;
+        for (offset, dll, name) in block {
+            match runs.last_mut() {
+                Some(run) if previous.is_some_and(|p| p + size == offset) && run.dll == *dll => {
+                    run.functions.push(name.clone())
+                }
+                _ => runs.push(IatRun {
+                    offset,
+                    dll: dll.clone(),
+                    functions: vec![name.clone()],
+                }),
+            }
+            previous = Some(offset);
+        }
+    }
+    runs
+}
+
+fn rebuild_imports(image: &mut Vec<u8>, headers: &Headers, runs: &[IatRun]) -> bool {
+    let section_header = headers.section(headers.section_count);
+    let first_section = u32_at(image, headers.section(0) + 12).unwrap_or_default() as usize;
+    let header_space = headers.size_of_headers(image).min(first_section);
+    if runs.is_empty() || section_header + SECTION_HEADER_SIZE > header_space {
+        return false;
+    }
+    let size = headers.pointer_size();
+    let alignment = headers.section_alignment(image);
+    let address = align_up(image.len(), alignment);
+
+    let descriptors = (runs.len() + 1) * IMPORT_DESCRIPTOR_SIZE;
+    let mut section = vec![0; descriptors];
+    let mut thunks = Vec::new();
+    for run in runs {
+        thunks.push(section.len());
+        section.resize(section.len() + (run.functions.len() + 1) * size, 0);
+    }
+    for (i, run) in runs.iter().enumerate() {
+        let name = address + section.len();
+        section.extend(run.dll.as_bytes());
+        section.push(0);
+        section.resize(align_up(section.len(), 2), 0);
+
+        let descriptor = i * IMPORT_DESCRIPTOR_SIZE;
+        put(
+            &mut section,
+            descriptor,
+            &((address + thunks[i]) as u32).to_le_bytes(),
+        );
+        put(&mut section, descriptor + 12, &(name as u32).to_le_bytes());
+        put(
+            &mut section,
+            descriptor + 16,
+            &(run.offset as u32).to_le_bytes(),
+        );
+        for (j, function) in run.functions.iter().enumerate() {
+            let hint = (address + section.len()) as u64;
+            section.extend([0, 0]);
+            section.extend(function.as_bytes());
+            section.push(0);
+            section.resize(align_up(section.len(), 2), 0);
+            put_pointer(&mut section, thunks[i] + j * size, hint, size);
+            put_pointer(image, run.offset + j * size, hint, size);
+        }
+    }
+
+    let virtual_size = section.len();
+    let raw_size = align_up(virtual_size, alignment);
+    image.resize(address, 0);
+    image.extend(section);
+    image.resize(address + raw_size, 0);
+
+    put(image, section_header, REBUILT_SECTION_NAME);
+    put(
+        image,
+        section_header + 8,
+        &(virtual_size as u32).to_le_bytes(),
+    );
+    put(image, section_header + 12, &(address as u32).to_le_bytes());
+    put(image, section_header + 16, &(raw_size as u32).to_le_bytes());
+    put(image, section_header + 20, &(address as u32).to_le_bytes());
+    put(
+        image,
+        section_header + 36,
+        &REBUILT_SECTION_FLAGS.to_le_bytes(),
+    );
+    put(
+        image,
+        headers.pe + 6,
+        &(headers.section_count as u16 + 1).to_le_bytes(),
+    );
+    put(
+        image,
+        headers.optional + 56,
+        &((address + raw_size) as u32).to_le_bytes(),
+    );
+    let directory = headers.data_directory(IMPORT_DIRECTORY);
+    put(image, directory, &(address as u32).to_le_bytes());
+    put(image, directory + 4, &(descriptors as u32).to_le_bytes());
+    true
+}
+
+pub fn payload_path(process_id: Option<u32>, address: u64) -> String {
+    match process_id {
+        Some(pid) => format!("{}{}/{:#x}", PAYLOAD_SCHEME, pid, address),
+        None => format!("{}{:#x}", PAYLOAD_SCHEME, address),
+    }
+}
+
+pub fn extract(
+    data: &[u8],
+    process_id: Option<u32>,
+    sample: Option<&[u8]>,
+) -> Vec<(Payload, Vec<u8>)> {
+    let Ok(regions) = super::regions(data) else {
+        return Vec::new();
+    };
+    let sample = sample.and_then(|s| Some((s, Headers::parse(s)?)));
+
+    let mut candidates = Vec::new();
+    let mut seen = HashSet::new();
+    for region in &regions {
+        let bytes = &data[region.data.clone()];
+        for offset in (0..bytes.len()).step_by(PAGE_SIZE) {
+            let header = &bytes[offset..];
+            if !is_pe_header(header) {
+                continue;
+            }
+            let Some(headers) = Headers::parse(header) else {
+                continue;
+            };
+            let address = region.address + offset as u64;
+            let size = headers
+                .size_of_image(header)
+                .unwrap_or_default()
+                .min(MAX_PAYLOAD_SIZE);
+            if size == 0 || !seen.insert(address) {
+                continue;
+            }
+            let image = read_image(data, &regions, address, size);
+            let origin = match &sample {
+                _ if !region.backed => PayloadOrigin::Unbacked,
+                Some((sample, sample_headers))
+                    if headers.time_date_stamp(&image)
+                        == sample_headers.time_date_stamp(sample)
+                        && is_modified(&image, &headers, sample, sample_headers) =>
+                {
+                    PayloadOrigin::ModifiedImage
+                }
+                _ => continue,
+            };
+            candidates.push((address, origin, headers, image));
+        }
+    }
+    candidates.truncate(MAX_PAYLOADS);
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+
+    let exports = exports(data, &regions);
+    candidates
+        .into_iter()
+        .map(|(address, origin, headers, mut image)| {
+            let size = image.len() as u64;
+            realign(&mut image, &headers, address);
+            let (import_recovery, imports) = match restore_imports(&mut image, &headers) {
+                Some(imports) => (ImportRecovery::Restored, imports),
+                None => {
+                    let runs = iat_runs(&image, &headers, &exports);
+                    let recovery = match rebuild_imports(&mut image, &headers, &runs) {
+                        true => ImportRecovery::Rebuilt,
+                        false => ImportRecovery::Unresolved,
+                    };
+                    let imports = runs
+                        .into_iter()
+                        .map(|r| RecoveredImport {
+                            dll: r.dll,
+                            functions: r.functions,
+                        })
+                        .collect();
+                    (recovery, imports)
+                }
+            };
+            let payload = Payload {
+                path: payload_path(process_id, address),
+                address,
+                size,
+                origin,
+                sha256: format!("{:x}", Sha256::digest(&image)),
+                imphash: pe::analyze(&image).ok().and_then(|info| info.imphash),
+                import_recovery,
+                imports,
+            };
+            (payload, image)
+        })
+        .collect()
+}
@@ -0,0 +1,231 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_TAG_CHARS: usize = 64;
+pub const MAX_FIELD_CHARS: usize = 256;
+pub const MAX_NOTE_CHARS: usize = 4096;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Tlp {
+    Clear,
+    Green,
+    Amber,
+    AmberStrict,
+    Red,
+}
+
+impl Tlp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Clear => "clear",
+            Self::Green => "green",
+            Self::Amber => "amber",
+            Self::AmberStrict => "amber_strict",
+            Self::Red => "red",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Clear => "TLP:CLEAR",
+            Self::Green => "TLP:GREEN",
+            Self::Amber => "TLP:AMBER",
+            Self::AmberStrict => "TLP:AMBER+STRICT",
+            Self::Red => "TLP:RED",
+        }
+    }
+}
+
+impl FromStr for Tlp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.trim().to_lowercase();
+        let level = lower.strip_prefix("tlp:").unwrap_or(&lower);
+        match level {
+            "clear" | "white" => Ok(Self::Clear),
+            "green" => Ok(Self::Green),
+            "amber" => Ok(Self::Amber),
+            "amber+strict" | "amber_strict" => Ok(Self::AmberStrict),
+            "red" => Ok(Self::Red),
+            _ => bail!(
+                "Unknown TLP level '{}', expected clear, green, amber, amber+strict or red",
+                s
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facet {
+    Tag,
+    Campaign,
+    Source,
+    Tlp,
+}
+
+impl Facet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Tag => "tag",
+            Self::Campaign => "campaign",
+            Self::Source => "source",
+            Self::Tlp => "tlp",
+        }
+    }
+
+    pub fn normalize(&self, value: &str) -> Result<String> {
+        match self {
+            Self::Tag => normalize_tag(value),
+            Self::Tlp => Ok(value.parse::<Tlp>()?.name().to_string()),
+            Self::Campaign | Self::Source => Ok(value.trim().to_lowercase()),
+        }
+    }
+}
+
+pub fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() {
+        bail!("Tags must not be empty");
+    }
+    if tag.chars().count() > MAX_TAG_CHARS {
+        bail!("Tag '{}' is longer than {} characters", tag, MAX_TAG_CHARS);
+    }
+    if !tag
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/'))
+    {
+        bail!(
+            "Tag '{}' contains characters other than letters, digits and -_.:/",
+            tag
+        );
+    }
+    Ok(tag)
+}
+
+fn field(name: &str, value: &str) -> Result<Option<String>> {
+    let value = value.trim();
+    if value.chars().count() > MAX_FIELD_CHARS {
+        bail!("The {} is longer than {} characters", name, MAX_FIELD_CHARS);
+    }
+    Ok((!value.is_empty()).then(|| value.to_string()))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AnalystNote {
+    pub author: String,
+    pub time: DateTime<Local>,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SampleMetadata {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub campaign: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub tlp: Option<Tlp>,
+    #[serde(default)]
+    pub notes: Vec<AnalystNote>,
+}
+
+impl SampleMetadata {
+    pub fn merged(&self, run: &Self) -> Self {
+        let mut tags = self.tags.clone();
+        for tag in &run.tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        let mut notes: Vec<AnalystNote> = self.notes.iter().chain(&run.notes).cloned().collect();
+        notes.sort_by_key(|n| n.time);
+        Self {
+            tags,
+            campaign: run.campaign.clone().or_else(|| self.campaign.clone()),
+            source: run.source.clone().or_else(|| self.source.clone()),
+            tlp: run.tlp.or(self.tlp),
+            notes,
+        }
+    }
+
+    pub fn facets(&self) -> Vec<(Facet, String)> {
+        let mut facets: Vec<(Facet, String)> =
+            self.tags.iter().map(|t| (Facet::Tag, t.clone())).collect();
+        if let Some(campaign) = &self.campaign {
+            facets.push((Facet::Campaign, campaign.to_lowercase()));
+        }
+        if let Some(source) = &self.source {
+            facets.push((Facet::Source, source.to_lowercase()));
+        }
+        if let Some(tlp) = self.tlp {
+            facets.push((Facet::Tlp, tlp.name().to_string()));
+        }
+        facets
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MetadataUpdate {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add_tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tlp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl MetadataUpdate {
+    pub fn apply(&self, metadata: &mut SampleMetadata, author: &str) -> Result<()> {
+        let mut updated = metadata.clone();
+        for tag in &self.remove_tags {
+            let tag = normalize_tag(tag)?;
+            updated.tags.retain(|t| *t != tag);
+        }
+        for tag in &self.add_tags {
+            let tag = normalize_tag(tag)?;
+            if !updated.tags.contains(&tag) {
+                updated.tags.push(tag);
+            }
+        }
+        if let Some(campaign) = &self.campaign {
+            updated.campaign = field("campaign", campaign)?;
+        }
+        if let Some(source) = &self.source {
+            updated.source = field("source", source)?;
+        }
+        if let Some(tlp) = &self.tlp {
+            updated.tlp = match tlp.trim() {
+                "" => None,
+                tlp => Some(tlp.parse()?),
+            };
+        }
+        if let Some(note) = &self.note {
+            let text = note.trim();
+            if text.chars().count() > MAX_NOTE_CHARS {
+                bail!("Notes are limited to {} characters", MAX_NOTE_CHARS);
+            }
+            if !text.is_empty() {
+                updated.notes.push(AnalystNote {
+                    author: author.to_string(),
+                    time: Local::now(),
+                    text: text.to_string(),
+                });
+            }
+        }
+        *metadata = updated;
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+#[cfg(feature = "otel")]
+use anyhow::Result;
+#[cfg(feature = "otel")]
+use opentelemetry::trace::{Span as _, Status, Tracer};
+#[cfg(feature = "otel")]
+use opentelemetry::{global, KeyValue};
+
+#[cfg(feature = "otel")]
+const TRACER_NAME: &str = "malware-analysis-sandbox";
+
+pub const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+            Self::Histogram => "histogram",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Metric {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: MetricKind,
+}
+
+pub static JOBS_SUBMITTED: Metric = Metric::counter(
+    "sandbox_jobs_submitted_total",
+    "Jobs accepted by the scheduler.",
+);
+pub static RUNS: Metric =
+    Metric::counter("sandbox_runs_total", "Finished job attempts by outcome.");
+pub static JOBS: Metric = Metric::gauge("sandbox_jobs", "Jobs known to the scheduler by state.");
+pub static QUEUE_DEPTH: Metric = Metric::gauge(
+    "sandbox_queue_depth",
+    "Jobs waiting for a machine, including jobs waiting to be retried.",
+);
+pub static MACHINES: Metric = Metric::gauge("sandbox_machines", "Analysis machines by status.");
+pub static QUEUE_WAIT: Metric = Metric::histogram(
+    "sandbox_queue_wait_seconds",
+    "Time from submission to the first machine assignment.",
+);
+pub static RUN_DURATION: Metric = Metric::histogram(
+    "sandbox_run_duration_seconds",
+    "Wall time of a detonation including capture and snapshot revert.",
+);
+pub static VM_OPERATION: Metric = Metric::histogram(
+    "sandbox_vm_operation_seconds",
+    "Latency of hypervisor operations.",
+);
+pub static AGENT_REQUEST: Metric = Metric::histogram(
+    "sandbox_agent_request_seconds",
+    "Time to reach the guest agent and receive its results.",
+);
+pub static ANALYZER_DURATION: Metric = Metric::histogram(
+    "sandbox_analyzer_duration_seconds",
+    "Latency of analyzer plugins.",
+);
+pub static REPORT_DURATION: Metric = Metric::histogram(
+    "sandbox_report_duration_seconds",
+    "Time to build a report from stored results.",
+);
+pub static PIPELINE_EVENTS: Metric = Metric::counter(
+    "sandbox_pipeline_events_total",
+    "Records handled by the parsing pipeline by result.",
+);
+pub static PIPELINE_DURATION: Metric = Metric::histogram(
+    "sandbox_pipeline_duration_seconds",
+    "Wall time of a parsing pipeline run.",
+);
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Debug)]
+enum Series {
+    Value(f64),
+    Histogram {
+        buckets: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+type Family = (&'static Metric, BTreeMap<Labels, Series>);
+
+fn registry() -> &'static Mutex<BTreeMap<&'static str, Family>> {
+    static REGISTRY: OnceLock<Mutex<BTreeMap<&'static str, Family>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+fn labels(labels: &[(&str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+impl Metric {
+    pub const fn counter(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: MetricKind::Counter,
+        }
+    }
+
+    pub const fn gauge(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: MetricKind::Gauge,
+        }
+    }
+
+    pub const fn histogram(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: MetricKind::Histogram,
+        }
+    }
+
+    fn update(&'static self, labels: Labels, update: impl FnOnce(&mut Series)) {
+        let Ok(mut registry) = registry().lock() else {
+            return;
+        };
+        let (_, series) = registry
+            .entry(self.name)
+            .or_insert_with(|| (self, BTreeMap::new()));
+        let series = series.entry(labels).or_insert_with(|| match self.kind {
+            MetricKind::Histogram => Series::Histogram {
+                buckets: vec![0; DURATION_BUCKETS.len()],
+                sum: 0.0,
+                count: 0,
+            },
+            _ => Series::Value(0.0),
+        });
+        update(series);
+    }
+
+    pub fn inc(&'static self, labels: &[(&str, &str)]) {
+        self.add(labels, 1.0);
+    }
+
+    pub fn add(&'static self, labels: &[(&str, &str)], value: f64) {
+        self.update(self::labels(labels), |series| {
+            if let Series::Value(v) = series {
+                *v += value;
+            }
+        });
+    }
+
+    pub fn set(&'static self, labels: &[(&str, &str)], value: f64) {
+        self.update(self::labels(labels), |series| {
+            if let Series::Value(v) = series {
+                *v = value;
+            }
+        });
+    }
+
+    pub fn observe(&'static self, labels: &[(&str, &str)], value: f64) {
+        self.observe_owned(self::labels(labels), value);
+    }
+
+    fn observe_owned(&'static self, labels: Labels, value: f64) {
+        self.update(labels, |series| {
+            if let Series::Histogram {
+                buckets,
+                sum,
+                count,
+            } = series
+            {
+                for (bucket, le) in buckets.iter_mut().zip(DURATION_BUCKETS) {
+                    if value <= *le {
+                        *bucket += 1;
+                    }
+                }
+                *sum += value;
+                *count += 1;
+            }
+        });
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_labels(labels: &Labels, extra: Option<(&str, &str)>) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .chain(extra)
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+        .collect();
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+pub fn render() -> String {
+    let mut text = String::new();
+    let Ok(registry) = registry().lock() else {
+        return text;
+    };
+    for (name, (metric, series)) in registry.iter() {
+        let _ = writeln!(text, "# HELP {} {}", name, metric.help);
+        let _ = writeln!(text, "# TYPE {} {}", name, metric.kind.name());
+        for (labels, series) in series {
+            match series {
+                Series::Value(value) => {
+                    let _ = writeln!(text, "{}{} {}", name, format_labels(labels, None), value);
+                }
+                Series::Histogram {
+                    buckets,
+                    sum,
+                    count,
+                } => {
+                    for (bucket, le) in buckets.iter().zip(DURATION_BUCKETS) {
+                        let le = le.to_string();
+                        let _ = writeln!(
+                            text,
+                            "{}_bucket{} {}",
+                            name,
+                            format_labels(labels, Some(("le", &le))),
+                            bucket
+                        );
+                    }
+                    let _ = writeln!(
+                        text,
+                        "{}_bucket{} {}",
+                        name,
+                        format_labels(labels, Some(("le", "+Inf"))),
+                        count
+                    );
+                    let _ = writeln!(text, "{}_sum{} {}", name, format_labels(labels, None), sum);
+                    let _ = writeln!(
+                        text,
+                        "{}_count{} {}",
+                        name,
+                        format_labels(labels, None),
+                        count
+                    );
+                }
+            }
+        }
+    }
+    text
+}
+
+pub struct Span {
+    started: Instant,
+    timer: Option<(&'static Metric, Labels)>,
+    #[cfg(feature = "otel")]
+    span: global::BoxedSpan,
+}
+
+pub fn span(name: &'static str) -> Span {
+    #[cfg(not(feature = "otel"))]
+    let _ = name;
+    Span {
+        started: Instant::now(),
+        timer: None,
+        #[cfg(feature = "otel")]
+        span: global::tracer(TRACER_NAME).start(name),
+    }
+}
+
+pub fn timed(name: &'static str, metric: &'static Metric, labels: &[(&str, &str)]) -> Span {
+    let mut span = span(name);
+    for (key, value) in labels {
+        span.attribute(key, value);
+    }
+    span.timer = Some((metric, self::labels(labels)));
+    span
+}
+
+impl Span {
+    #[allow(unused_variables)]
+    pub fn attribute(&mut self, key: &str, value: &str) {
+        #[cfg(feature = "otel")]
+        self.span
+            .set_attribute(KeyValue::new(key.to_string(), value.to_string()));
+    }
+
+    #[allow(unused_variables)]
+    pub fn fail(&mut self, error: &anyhow::Error) {
+        #[cfg(feature = "otel")]
+        self.span.set_status(Status::error(error.to_string()));
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some((metric, labels)) = self.timer.take() {
+            metric.observe_owned(labels, self.started.elapsed().as_secs_f64());
+        }
+        #[cfg(feature = "otel")]
+        self.span.end();
+    }
+}
+
+#[cfg(feature = "otel")]
+pub fn init_tracing(endpoint: &str) -> Result<()> {
+    use opentelemetry::sdk::{trace, Resource};
+    use opentelemetry_otlp::WithExportConfig;
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                TRACER_NAME,
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)?;
+    Ok(())
+}
+
+#[cfg(feature = "otel")]
+pub fn shutdown_tracing() {
+    global::shutdown_tracer_provider();
+}
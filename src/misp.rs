@@ -0,0 +1,282 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::ioc::{Confidence, Ioc};
+use crate::report::SandboxReport;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MispAttribute {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub category: String,
+    pub value: String,
+    pub to_ids: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_relation: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub comment: String,
+}
+
+impl MispAttribute {
+    pub fn new(kind: &str, category: &str, value: &str, to_ids: bool) -> Self {
+        Self {
+            kind: kind.to_string(),
+            category: category.to_string(),
+            value: value.to_string(),
+            to_ids,
+            object_relation: None,
+            comment: String::new(),
+        }
+    }
+
+    fn relation(relation: &str, kind: &str, value: &str) -> Self {
+        Self {
+            object_relation: Some(relation.to_string()),
+            ..Self::new(kind, "Other", value, false)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MispObject {
+    pub name: String,
+    #[serde(rename = "meta-category")]
+    pub meta_category: String,
+    #[serde(rename = "Attribute")]
+    pub attributes: Vec<MispAttribute>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MispTag {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MispEvent {
+    pub info: String,
+    pub date: String,
+    pub threat_level_id: String,
+    pub analysis: String,
+    pub distribution: String,
+    #[serde(rename = "Attribute")]
+    pub attributes: Vec<MispAttribute>,
+    #[serde(rename = "Object")]
+    pub objects: Vec<MispObject>,
+    #[serde(rename = "Tag")]
+    pub tags: Vec<MispTag>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MispEventWrapper<T> {
+    #[serde(rename = "Event")]
+    event: T,
+}
+
+fn ioc_attribute(ioc: &Ioc) -> (&'static str, &'static str) {
+    match ioc {
+        Ioc::Ip(_) => ("ip-dst", "Network activity"),
+        Ioc::Domain(_) => ("domain", "Network activity"),
+        Ioc::Url(_) => ("url", "Network activity"),
+        Ioc::Email(_) => ("email-src", "Payload delivery"),
+        Ioc::Md5(_) => ("md5", "Payload delivery"),
+        Ioc::Sha1(_) => ("sha1", "Payload delivery"),
+        Ioc::Sha256(_) => ("sha256", "Payload delivery"),
+        Ioc::Imphash(_) => ("imphash", "Payload delivery"),
+        Ioc::Mutex(_) => ("mutex", "Artifacts dropped"),
+        Ioc::Pipe(_) => ("named pipe", "Artifacts dropped"),
+        Ioc::FilePath(_) => ("filename", "Artifacts dropped"),
+        Ioc::RegistryKey(_) => ("regkey", "Persistence mechanism"),
+    }
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit(['\\', '/']).next().unwrap_or(path)
+}
+
+impl MispEvent {
+    pub fn from_report(report: &SandboxReport) -> Self {
+        let attributes = report
+            .iocs
+            .iter()
+            .map(|i| {
+                let (kind, category) = ioc_attribute(&i.ioc);
+                MispAttribute::new(
+                    kind,
+                    category,
+                    &i.ioc.value(),
+                    i.confidence == Confidence::High,
+                )
+            })
+            .collect();
+
+        let mut objects = vec![MispObject {
+            name: "file".to_string(),
+            meta_category: "file".to_string(),
+            attributes: vec![MispAttribute::relation(
+                "sha3-512",
+                "sha3-512",
+                &report.hash,
+            )],
+        }];
+
+        let mut connections = HashSet::new();
+        for connect in &report.network {
+            let Some(destination) = connect.destination_ip else {
+                continue;
+            };
+            let key = (
+                destination,
+                connect.destination_port,
+                connect.protocol.clone(),
+            );
+            if !connections.insert(key) {
+                continue;
+            }
+            let mut attributes = vec![MispAttribute::relation(
+                "ip-dst",
+                "ip-dst",
+                &destination.to_string(),
+            )];
+            if let Some(port) = connect.destination_port {
+                attributes.push(MispAttribute::relation(
+                    "dst-port",
+                    "port",
+                    &port.to_string(),
+                ));
+            }
+            if let Some(ip) = connect.source_ip {
+                attributes.push(MispAttribute::relation("ip-src", "ip-src", &ip.to_string()));
+            }
+            if let Some(hostname) = &connect.destination_hostname {
+                attributes.push(MispAttribute::relation(
+                    "hostname-dst",
+                    "hostname",
+                    hostname,
+                ));
+            }
+            if !connect.protocol.is_empty() {
+                attributes.push(MispAttribute::relation(
+                    "layer4-protocol",
+                    "text",
+                    &connect.protocol.to_uppercase(),
+                ));
+            }
+            objects.push(MispObject {
+                name: "network-connection".to_string(),
+                meta_category: "network".to_string(),
+                attributes,
+            });
+        }
+
+        for process in report.process_tree.processes() {
+            let mut attributes = vec![
+                MispAttribute::relation("name", "text", basename(&process.image)),
+                MispAttribute::relation("image", "filename", &process.image),
+            ];
+            if !process.command_line.is_empty() {
+                attributes.push(MispAttribute::relation(
+                    "command-line",
+                    "text",
+                    &process.command_line,
+                ));
+            }
+            if let Some(pid) = process.process_id {
+                attributes.push(MispAttribute::relation("pid", "text", &pid.to_string()));
+            }
+            if let Some(pid) = process.parent_process_id {
+                attributes.push(MispAttribute::relation(
+                    "parent-pid",
+                    "text",
+                    &pid.to_string(),
+                ));
+            }
+            if let Some(start) = process.start_time {
+                attributes.push(MispAttribute::relation(
+                    "start-time",
+                    "datetime",
+                    &start.to_rfc3339(),
+                ));
+            }
+            objects.push(MispObject {
+                name: "process".to_string(),
+                meta_category: "misc".to_string(),
+                attributes,
+            });
+        }
+
+        let tags = report
+            .techniques
+            .iter()
+            .map(|t| MispTag {
+                name: format!(
+                    "misp-galaxy:mitre-attack-pattern=\"{} - {}\"",
+                    t.name.as_deref().unwrap_or(&t.id),
+                    t.id
+                ),
+            })
+            .collect();
+
+        let threat_level = if report.detections.is_empty() { 4 } else { 2 };
+
+        Self {
+            info: format!("Sandbox analysis of {}", report.hash),
+            date: report.time.format("%Y-%m-%d").to_string(),
+            threat_level_id: threat_level.to_string(),
+            analysis: "2".to_string(),
+            distribution: "0".to_string(),
+            attributes,
+            objects,
+            tags,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&MispEventWrapper {
+            event: self,
+        })?)
+    }
+}
+
+#[cfg(feature = "misp")]
+pub struct MispClient {
+    url: String,
+    key: String,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "misp")]
+impl MispClient {
+    pub fn new(url: &str, key: &str, verify_tls: bool) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(!verify_tls)
+            .build()?;
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            key: key.to_string(),
+            client,
+        })
+    }
+
+    pub async fn add_event(&self, event: &MispEvent) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Created {
+            id: String,
+        }
+
+        let response: MispEventWrapper<Created> = self
+            .client
+            .post(format!("{}/events/add", self.url))
+            .header("Authorization", &self.key)
+            .header("Accept", "application/json")
+            .json(&MispEventWrapper { event })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response.event.id)
+    }
+}
@@ -0,0 +1,266 @@
+mod dns;
+mod http;
+#[cfg(feature = "mitm")]
+mod mitm;
+mod smtp;
+mod tls;
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::task::JoinHandle;
+
+use crate::dns::normalize_domain;
+use crate::network::NetworkConnect;
+use crate::pcap::distance;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub const NETSIM_LOG_FILE: &str = "netsim.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Service {
+    Dns,
+    Http,
+    Https,
+    Smtp,
+}
+
+impl Service {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Dns => "dns",
+            Self::Http => "http",
+            Self::Https => "https",
+            Self::Smtp => "smtp",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimulatedRequest {
+    pub time: DateTime<Utc>,
+    pub service: Service,
+    pub client: SocketAddr,
+    pub summary: String,
+    pub details: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InterceptCa {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct NetSimConfig {
+    pub bind: IpAddr,
+    pub sinkhole: Ipv4Addr,
+    pub dns_ttl: u32,
+    pub ports: HashMap<Service, u16>,
+    pub intercept: Option<InterceptCa>,
+    pub upstream: bool,
+}
+
+impl Default for NetSimConfig {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            sinkhole: Ipv4Addr::new(10, 0, 0, 1),
+            dns_ttl: 300,
+            ports: HashMap::from([
+                (Service::Dns, 53),
+                (Service::Http, 80),
+                (Service::Https, 443),
+                (Service::Smtp, 25),
+            ]),
+            intercept: None,
+            upstream: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct RequestLog {
+    requests: Arc<Mutex<Vec<SimulatedRequest>>>,
+}
+
+impl RequestLog {
+    fn push(
+        &self,
+        service: Service,
+        client: SocketAddr,
+        summary: String,
+        details: HashMap<String, String>,
+    ) {
+        info!("[{}] {} {}", service.name(), client, summary);
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(SimulatedRequest {
+                time: Utc::now(),
+                service,
+                client,
+                summary,
+                details,
+            });
+        }
+    }
+}
+
+pub struct NetSim {
+    log: RequestLog,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl NetSim {
+    pub async fn start(config: NetSimConfig) -> Result<Self> {
+        let log = RequestLog::default();
+        let mut tasks = Vec::new();
+
+        #[cfg(feature = "mitm")]
+        let interceptor = match &config.intercept {
+            Some(ca) => Some(Arc::new(mitm::Interceptor::load_or_create(
+                &ca.cert, &ca.key,
+            )?)),
+            None => None,
+        };
+        #[cfg(not(feature = "mitm"))]
+        if config.intercept.is_some() {
+            anyhow::bail!("TLS interception requires the mitm feature");
+        }
+
+        let upstream = config.upstream;
+        for (&service, &port) in &config.ports {
+            let addr = SocketAddr::new(config.bind, port);
+            let log = log.clone();
+            let task = match service {
+                Service::Dns => {
+                    let socket = UdpSocket::bind(addr).await?;
+                    let (sinkhole, ttl) = (config.sinkhole, config.dns_ttl);
+                    tokio::spawn(async move { dns::serve(socket, sinkhole, ttl, log).await })
+                }
+                Service::Http | Service::Https | Service::Smtp => {
+                    let listener = TcpListener::bind(addr).await?;
+                    #[cfg(feature = "mitm")]
+                    let interceptor = interceptor.clone();
+                    tokio::spawn(async move {
+                        loop {
+                            let (stream, client) = match listener.accept().await {
+                                Ok(accepted) => accepted,
+                                Err(e) => {
+                                    warn!("[{}] accept failed: {}", service.name(), e);
+                                    continue;
+                                }
+                            };
+                            let log = log.clone();
+                            #[cfg(feature = "mitm")]
+                            let interceptor = interceptor.clone();
+                            tokio::spawn(async move {
+                                let result = match service {
+                                    Service::Http => {
+                                        http::handle(stream, client, &log, upstream).await
+                                    }
+                                    #[cfg(feature = "mitm")]
+                                    Service::Https => match &interceptor {
+                                        Some(interceptor) => {
+                                            mitm::handle(
+                                                stream,
+                                                client,
+                                                &log,
+                                                interceptor,
+                                                upstream,
+                                            )
+                                            .await
+                                        }
+                                        None => tls::handle(stream, client, &log).await,
+                                    },
+                                    #[cfg(not(feature = "mitm"))]
+                                    Service::Https => tls::handle(stream, client, &log).await,
+                                    _ => smtp::handle(stream, client, &log).await,
+                                };
+                                if let Err(e) = result {
+                                    warn!("[{}] {}: {}", service.name(), client, e);
+                                }
+                            });
+                        }
+                    })
+                }
+            };
+            info!("Simulating {} on {}", service.name(), addr);
+            tasks.push(task);
+        }
+
+        Ok(Self { log, tasks })
+    }
+
+    pub fn requests(&self) -> Vec<SimulatedRequest> {
+        self.log
+            .requests
+            .lock()
+            .map(|r| r.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn requests_from(&self, client: IpAddr, since: DateTime<Utc>) -> Vec<SimulatedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.client.ip() == client && r.time >= since)
+            .collect()
+    }
+
+    pub fn stop(self) {
+        for task in self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AttributedRequest {
+    pub process_guid: Option<String>,
+    pub image: Option<String>,
+    pub request: SimulatedRequest,
+}
+
+pub fn attribute(
+    requests: &[SimulatedRequest],
+    events: &[SysmonEvent],
+    tolerance: Duration,
+    offset: Duration,
+) -> Vec<AttributedRequest> {
+    requests
+        .iter()
+        .map(|request| {
+            let nearest = events
+                .iter()
+                .filter(|e| {
+                    let time = e.time_created.with_timezone(&Utc) + offset;
+                    distance(time, request.time) <= tolerance
+                        && match request.service {
+                            Service::Dns => {
+                                e.event_id == SysmonEventId::DNS_QUERY
+                                    && e.event_data.get("QueryName").map(|q| normalize_domain(q))
+                                        == request.details.get("query").cloned()
+                            }
+                            _ => NetworkConnect::from_event(e).is_some_and(|c| {
+                                c.source_ip == Some(request.client.ip())
+                                    && c.source_port == Some(request.client.port())
+                            }),
+                        }
+                })
+                .min_by_key(|e| {
+                    distance(e.time_created.with_timezone(&Utc) + offset, request.time)
+                });
+            AttributedRequest {
+                process_guid: nearest.and_then(|e| e.event_data.get("ProcessGuid").cloned()),
+                image: nearest.and_then(|e| e.event_data.get("Image").cloned()),
+                request: request.clone(),
+            }
+        })
+        .collect()
+}
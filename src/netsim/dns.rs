@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+use log::warn;
+use tokio::net::UdpSocket;
+
+use super::{RequestLog, Service};
+use crate::dns::normalize_domain;
+use crate::pcap::dns::{parse_message, record_type_name, spoofed_response};
+
+const MAX_DATAGRAM_LEN: usize = 4096;
+
+pub(super) async fn serve(socket: UdpSocket, sinkhole: Ipv4Addr, ttl: u32, log: RequestLog) {
+    let mut buffer = vec![0; MAX_DATAGRAM_LEN];
+    loop {
+        let (len, client) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                warn!("[dns] receive failed: {}", e);
+                continue;
+            }
+        };
+        let query = &buffer[..len];
+        let Some(message) = parse_message(query).filter(|m| !m.response) else {
+            continue;
+        };
+        let Some((name, query_type)) = message.questions.first() else {
+            continue;
+        };
+
+        let name = normalize_domain(name);
+        let query_type = record_type_name(*query_type);
+        log.push(
+            Service::Dns,
+            client,
+            format!("{} {}", query_type, name),
+            HashMap::from([
+                ("query".to_string(), name),
+                ("type".to_string(), query_type),
+                ("answer".to_string(), sinkhole.to_string()),
+            ]),
+        );
+
+        if let Some(response) = spoofed_response(query, Some(sinkhole), ttl) {
+            if let Err(e) = socket.send_to(&response, client).await {
+                warn!("[dns] send to {} failed: {}", client, e);
+            }
+        }
+    }
+}
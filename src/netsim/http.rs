@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+
+use super::{RequestLog, Service};
+use crate::browsing::payload_kind;
+use crate::dns::encrypted::{doh_query, DOH_QUERY_DETAIL, DOH_TYPE_DETAIL};
+use crate::pcap::http::{header, parse_head, read_body};
+
+const MAX_HEADER_LEN: usize = 64 * 1024;
+const MAX_BODY_LEN: usize = 1024 * 1024;
+const MAX_UPSTREAM_RESPONSE_LEN: u64 = 64 * 1024 * 1024;
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(30);
+const HOP_BY_HOP_HEADERS: &[&str] = &["connection", "proxy-connection", "keep-alive"];
+const RESPONSE_BODY: &str = "<html><head><title>OK</title></head><body>OK</body></html>";
+
+pub(super) async fn read_head<S>(stream: &mut S) -> Result<(Vec<u8>, Vec<u8>)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buffer.split_off(end + 4);
+            return Ok((buffer, rest));
+        }
+        if buffer.len() > MAX_HEADER_LEN {
+            bail!("Request header too long");
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("Connection closed before request was complete");
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+}
+
+pub(super) async fn handle(
+    stream: TcpStream,
+    client: SocketAddr,
+    log: &RequestLog,
+    upstream: bool,
+) -> Result<()> {
+    serve(stream, client, log, Service::Http, HashMap::new(), upstream).await
+}
+
+pub(super) async fn serve<S>(
+    mut stream: S,
+    client: SocketAddr,
+    log: &RequestLog,
+    service: Service,
+    mut details: HashMap<String, String>,
+    upstream: bool,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (head, mut body) = read_head(&mut stream).await?;
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or_default().to_string();
+
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.trim().to_lowercase();
+        if matches!(
+            name.as_str(),
+            "host" | "user-agent" | "content-type" | "cookie"
+        ) {
+            details.insert(name, value.trim().to_string());
+        }
+    }
+
+    let content_length: usize = head
+        .lines()
+        .find_map(|l| {
+            let (name, value) = l.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse().ok())?
+        })
+        .unwrap_or(0);
+    let wanted = content_length.min(MAX_BODY_LEN);
+    let mut chunk = [0; 4096];
+    while body.len() < wanted {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    let uri = request_line.split(' ').nth(1).unwrap_or_default();
+    if let Some((query, record_type)) =
+        doh_query(uri, details.get("content-type").map(String::as_str), &body)
+    {
+        details.insert(DOH_QUERY_DETAIL.to_string(), query);
+        details.insert(DOH_TYPE_DETAIL.to_string(), record_type);
+    }
+    if !body.is_empty() {
+        details.insert("body_len".to_string(), body.len().to_string());
+        details.insert(
+            "body_preview".to_string(),
+            crate::pcap::decode::preview(&body, 256),
+        );
+    }
+
+    let response = match upstream {
+        true => match forward(service, &head, &body).await {
+            Ok(response) => {
+                record_response(&response, &mut details);
+                response
+            }
+            Err(e) => {
+                details.insert("upstream_error".to_string(), e.to_string());
+                simulated_response(&mut details)
+            }
+        },
+        false => simulated_response(&mut details),
+    };
+    log.push(service, client, request_line, details);
+
+    stream.write_all(&response).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn simulated_response(details: &mut HashMap<String, String>) -> Vec<u8> {
+    details.insert("response_status".to_string(), "200".to_string());
+    details.insert("response_len".to_string(), RESPONSE_BODY.len().to_string());
+    format!(
+        "HTTP/1.1 200 OK\r\nServer: Apache\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        RESPONSE_BODY.len(),
+        RESPONSE_BODY
+    )
+    .into_bytes()
+}
+
+fn record_response(response: &[u8], details: &mut HashMap<String, String>) {
+    let Some((status_line, headers, offset)) = parse_head(response) else {
+        return;
+    };
+    if let Some(status) = status_line.split_whitespace().nth(1) {
+        details.insert("response_status".to_string(), status.to_string());
+    }
+    for (name, key) in [
+        ("location", "location"),
+        ("content-type", "response_content_type"),
+        ("content-disposition", "content_disposition"),
+    ] {
+        if let Some(value) = header(&headers, name) {
+            details.insert(key.to_string(), value.to_string());
+        }
+    }
+    let (body, _) = read_body(&response[offset..], &headers, true);
+    details.insert("response_len".to_string(), body.len().to_string());
+    if let Some(kind) = payload_kind(&body) {
+        details.insert("payload".to_string(), kind.name().to_string());
+        details.insert(
+            "payload_sha256".to_string(),
+            format!("{:x}", Sha256::digest(&body)),
+        );
+    }
+}
+
+async fn forward(service: Service, head: &str, body: &[u8]) -> Result<Vec<u8>> {
+    let mut lines = head.lines();
+    let mut request = format!("{}\r\n", lines.next().unwrap_or_default());
+    let mut host = None;
+    for line in lines.filter(|l| !l.is_empty()) {
+        let name = line.split(':').next().unwrap_or_default().trim();
+        if name.eq_ignore_ascii_case("host") {
+            host = line.split_once(':').map(|(_, v)| v.trim().to_string());
+        }
+        if !HOP_BY_HOP_HEADERS.contains(&name.to_lowercase().as_str()) {
+            request.push_str(line);
+            request.push_str("\r\n");
+        }
+    }
+    request.push_str("Connection: close\r\n\r\n");
+    let mut request = request.into_bytes();
+    request.extend_from_slice(body);
+
+    let host = host.context("Request has no Host header")?;
+    let default_port = match service {
+        Service::Https => 443,
+        _ => 80,
+    };
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if port.parse::<u16>().is_ok() => (name.to_string(), port.parse()?),
+        _ => (host, default_port),
+    };
+
+    timeout(UPSTREAM_TIMEOUT, async {
+        let stream = TcpStream::connect((name.as_str(), port)).await?;
+        match service {
+            #[cfg(feature = "mitm")]
+            Service::Https => exchange(super::mitm::connect(&name, stream).await?, &request).await,
+            _ => exchange(stream, &request).await,
+        }
+    })
+    .await
+    .context("Upstream did not answer in time")?
+}
+
+async fn exchange<S>(mut stream: S, request: &[u8]) -> Result<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(request).await?;
+    let mut response = Vec::new();
+    (&mut stream)
+        .take(MAX_UPSTREAM_RESPONSE_LEN)
+        .read_to_end(&mut response)
+        .await?;
+    Ok(response)
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair, KeyUsagePurpose,
+};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::server::Acceptor;
+use tokio_rustls::rustls::{
+    self, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerConfig, ServerName,
+};
+use tokio_rustls::{LazyConfigAcceptor, TlsConnector};
+
+use super::{http, RequestLog, Service};
+
+const CA_NAME: &str = "Sandbox Interception CA";
+const FALLBACK_NAME: &str = "localhost";
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Interceptor {
+    ca: Certificate,
+    configs: Mutex<HashMap<String, Arc<ServerConfig>>>,
+}
+
+impl Interceptor {
+    pub fn load_or_create(cert_path: &Path, key_path: &Path) -> Result<Self> {
+        let ca = if cert_path.exists() {
+            let cert = std::fs::read_to_string(cert_path)
+                .with_context(|| format!("Failed to read {}", cert_path.display()))?;
+            let key = std::fs::read_to_string(key_path)
+                .with_context(|| format!("Failed to read {}", key_path.display()))?;
+            let params = CertificateParams::from_ca_cert_pem(&cert, KeyPair::from_pem(&key)?)?;
+            Certificate::from_params(params)?
+        } else {
+            let mut params = CertificateParams::default();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params.key_usages = vec![
+                KeyUsagePurpose::KeyCertSign,
+                KeyUsagePurpose::CrlSign,
+                KeyUsagePurpose::DigitalSignature,
+            ];
+            params.distinguished_name.push(DnType::CommonName, CA_NAME);
+            let ca = Certificate::from_params(params)?;
+            std::fs::write(cert_path, ca.serialize_pem()?)?;
+            std::fs::write(key_path, ca.serialize_private_key_pem())?;
+            info!(
+                "Generated interception CA {}, install it in the guest trust store",
+                cert_path.display()
+            );
+            ca
+        };
+        Ok(Self {
+            ca,
+            configs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn config(&self, server_name: &str) -> Result<Arc<ServerConfig>> {
+        let mut configs = self
+            .configs
+            .lock()
+            .map_err(|_| anyhow!("Certificate cache is poisoned"))?;
+        if let Some(config) = configs.get(server_name) {
+            return Ok(config.clone());
+        }
+        let mut params = CertificateParams::new(vec![server_name.to_string()]);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, server_name);
+        let cert = Certificate::from_params(params)?;
+        let chain = vec![rustls::Certificate(
+            cert.serialize_der_with_signer(&self.ca)?,
+        )];
+        let key = rustls::PrivateKey(cert.serialize_private_key_der());
+        let config = Arc::new(
+            ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(chain, key)?,
+        );
+        configs.insert(server_name.to_string(), config.clone());
+        Ok(config)
+    }
+}
+
+pub(super) async fn handle(
+    stream: TcpStream,
+    client: SocketAddr,
+    log: &RequestLog,
+    interceptor: &Interceptor,
+    upstream: bool,
+) -> Result<()> {
+    let start = timeout(
+        HANDSHAKE_TIMEOUT,
+        LazyConfigAcceptor::new(Acceptor::default(), stream),
+    )
+    .await??;
+    let sni = start.client_hello().server_name().map(str::to_string);
+    let mut details = HashMap::new();
+    if let Some(sni) = &sni {
+        details.insert("sni".to_string(), sni.clone());
+    }
+    let config = interceptor.config(sni.as_deref().unwrap_or(FALLBACK_NAME))?;
+
+    let error = match timeout(HANDSHAKE_TIMEOUT, start.into_stream(config)).await {
+        Ok(Ok(stream)) => {
+            details.insert("tls".to_string(), "intercepted".to_string());
+            return http::serve(stream, client, log, Service::Https, details, upstream).await;
+        }
+        Ok(Err(e)) => anyhow::Error::from(e),
+        Err(e) => e.into(),
+    };
+
+    details.insert("tls".to_string(), "handshake_failed".to_string());
+    log.push(
+        Service::Https,
+        client,
+        format!("ClientHello {}", sni.as_deref().unwrap_or("(no SNI)")),
+        details,
+    );
+    Err(error)
+}
+
+pub(super) async fn connect(host: &str, stream: TcpStream) -> Result<TlsStream<TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from(host)?, stream)
+        .await?)
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use super::{RequestLog, Service};
+
+const MAX_MESSAGE_LEN: usize = 10 * 1024 * 1024;
+
+pub(super) async fn handle(stream: TcpStream, client: SocketAddr, log: &RequestLog) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer
+        .write_all(b"220 mail.example.com ESMTP ready\r\n")
+        .await?;
+
+    let mut from = String::new();
+    let mut recipients = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let command = line.trim_end();
+        let verb = command
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_uppercase();
+        let argument = command.get(verb.len()..).unwrap_or_default().trim();
+
+        let reply: &[u8] = match verb.as_str() {
+            "HELO" | "EHLO" => b"250 mail.example.com\r\n",
+            "MAIL" => {
+                from = argument.trim_start_matches("FROM:").trim().to_string();
+                b"250 OK\r\n"
+            }
+            "RCPT" => {
+                recipients.push(argument.trim_start_matches("TO:").trim().to_string());
+                b"250 OK\r\n"
+            }
+            "DATA" => {
+                writer
+                    .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                    .await?;
+                let mut subject = String::new();
+                let mut size = 0;
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line).await? == 0 {
+                        return Ok(());
+                    }
+                    if line.trim_end() == "." {
+                        break;
+                    }
+                    size += line.len();
+                    if subject.is_empty() {
+                        if let Some(s) = line.strip_prefix("Subject:") {
+                            subject = s.trim().to_string();
+                        }
+                    }
+                    if size > MAX_MESSAGE_LEN {
+                        break;
+                    }
+                }
+                log.push(
+                    Service::Smtp,
+                    client,
+                    format!("MAIL FROM {} TO {}", from, recipients.join(", ")),
+                    HashMap::from([
+                        ("from".to_string(), from.clone()),
+                        ("to".to_string(), recipients.join(", ")),
+                        ("subject".to_string(), subject),
+                        ("size".to_string(), size.to_string()),
+                    ]),
+                );
+                recipients.clear();
+                b"250 OK: queued\r\n"
+            }
+            "RSET" => {
+                recipients.clear();
+                b"250 OK\r\n"
+            }
+            "NOOP" => b"250 OK\r\n",
+            "QUIT" => {
+                writer.write_all(b"221 Bye\r\n").await?;
+                return Ok(());
+            }
+            _ => b"502 Command not implemented\r\n",
+        };
+        writer.write_all(reply).await?;
+    }
+}
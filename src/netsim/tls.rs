@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+
+use super::{RequestLog, Service};
+use crate::pcap::decode::tls_sni;
+
+const MAX_CLIENT_HELLO_LEN: usize = 16 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(super) async fn handle(
+    mut stream: TcpStream,
+    client: SocketAddr,
+    log: &RequestLog,
+) -> Result<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    while buffer.len() < MAX_CLIENT_HELLO_LEN {
+        let n = timeout(READ_TIMEOUT, stream.read(&mut chunk)).await??;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        let record_len = buffer
+            .get(3..5)
+            .map(|l| usize::from(u16::from_be_bytes([l[0], l[1]])));
+        if record_len.is_some_and(|l| buffer.len() >= l + 5) {
+            break;
+        }
+    }
+
+    let sni = tls_sni(&buffer);
+    let mut details = HashMap::new();
+    if let Some(sni) = &sni {
+        details.insert("sni".to_string(), sni.clone());
+    }
+    log.push(
+        Service::Https,
+        client,
+        format!("ClientHello {}", sni.as_deref().unwrap_or("(no SNI)")),
+        details,
+    );
+    Ok(())
+}
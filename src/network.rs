@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ByteCounts {
+    pub sent: u64,
+    pub received: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NetworkConnect {
+    pub process_guid: Option<String>,
+    pub image: String,
+    pub protocol: String,
+    pub initiated: bool,
+    pub source_ip: Option<IpAddr>,
+    pub source_port: Option<u16>,
+    pub destination_ip: Option<IpAddr>,
+    pub destination_port: Option<u16>,
+    pub destination_hostname: Option<String>,
+    bytes_sent: Option<u64>,
+    bytes_received: Option<u64>,
+}
+
+fn parse<T: FromStr>(data: &HashMap<String, String>, field: &str) -> Option<T> {
+    data.get(field).and_then(|v| v.trim().parse().ok())
+}
+
+impl NetworkConnect {
+    pub fn from_event(event: &SysmonEvent) -> Option<Self> {
+        if event.event_id != SysmonEventId::NETWORK_CONNECT {
+            return None;
+        }
+        let data = &event.event_data;
+
+        Some(Self {
+            process_guid: data.get("ProcessGuid").cloned(),
+            image: data.get("Image").cloned().unwrap_or_default(),
+            protocol: data.get("Protocol").cloned().unwrap_or_default(),
+            initiated: data.get("Initiated").is_some_and(|i| i == "true"),
+            source_ip: parse(data, "SourceIp"),
+            source_port: parse(data, "SourcePort"),
+            destination_ip: parse(data, "DestinationIp"),
+            destination_port: parse(data, "DestinationPort"),
+            destination_hostname: data
+                .get("DestinationHostname")
+                .filter(|h| !h.is_empty() && *h != "-")
+                .cloned(),
+            bytes_sent: parse(data, "BytesSent"),
+            bytes_received: parse(data, "BytesReceived"),
+        })
+    }
+
+    pub fn bytes(&self) -> Option<ByteCounts> {
+        if self.bytes_sent.is_none() && self.bytes_received.is_none() {
+            return None;
+        }
+        Some(ByteCounts {
+            sent: self.bytes_sent.unwrap_or_default(),
+            received: self.bytes_received.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DestinationVolume {
+    pub destination: IpAddr,
+    pub connections: usize,
+    pub bytes: ByteCounts,
+}
+
+pub fn volume_by_destination(events: &[SysmonEvent]) -> Vec<DestinationVolume> {
+    let mut volumes: HashMap<IpAddr, DestinationVolume> = HashMap::new();
+    for connect in events.iter().filter_map(NetworkConnect::from_event) {
+        let (Some(destination), Some(bytes)) = (connect.destination_ip, connect.bytes()) else {
+            continue;
+        };
+        let volume = volumes.entry(destination).or_insert(DestinationVolume {
+            destination,
+            connections: 0,
+            bytes: ByteCounts::default(),
+        });
+        volume.connections += 1;
+        volume.bytes.sent += bytes.sent;
+        volume.bytes.received += bytes.received;
+    }
+
+    let mut result: Vec<DestinationVolume> = volumes.into_values().collect();
+    result.sort_by_key(|v| std::cmp::Reverse(v.bytes.sent));
+    result
+}
@@ -0,0 +1,160 @@
+#[cfg(feature = "notify")]
+mod client;
+
+#[cfg(feature = "notify")]
+pub use client::{sign, Notifier};
+
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::report::SandboxReport;
+use crate::verdict::Verdict;
+
+pub const SIGNATURE_HEADER: &str = "x-sandbox-signature";
+
+const MAX_LISTED_DETECTIONS: usize = 10;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EmailConfig {
+    pub server: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub starttls: bool,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Channel {
+    Webhook { url: String, secret: Option<String> },
+    Slack { url: String },
+    Email(EmailConfig),
+}
+
+impl Channel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Webhook { .. } => "webhook",
+            Self::Slack { .. } => "slack",
+            Self::Email(_) => "email",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Target {
+    pub min_score: Option<f64>,
+    #[serde(flatten)]
+    pub channel: Channel,
+}
+
+impl Target {
+    pub fn wants(&self, notification: &Notification) -> bool {
+        self.min_score.is_none_or(|min| notification.score >= min)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NotifyConfig {
+    pub targets: Vec<Target>,
+    #[serde(default = "default_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_backoff")]
+    pub backoff_secs: u64,
+}
+
+fn default_retries() -> u32 {
+    5
+}
+
+fn default_backoff() -> u64 {
+    2
+}
+
+impl NotifyConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct NotifiedDetection {
+    pub source: String,
+    pub name: String,
+    pub level: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Notification {
+    pub analysis_id: String,
+    pub execution_id: String,
+    pub hash: String,
+    pub time: DateTime<Local>,
+    pub score: f64,
+    pub verdict: Verdict,
+    pub detections: Vec<NotifiedDetection>,
+}
+
+impl Notification {
+    pub fn from_report(report: &SandboxReport) -> Self {
+        Self {
+            analysis_id: report.id.clone(),
+            execution_id: report.execution_id.clone(),
+            hash: report.hash.clone(),
+            time: report.time,
+            score: report.score.score,
+            verdict: report.verdict.verdict,
+            detections: report
+                .detections
+                .iter()
+                .map(|d| NotifiedDetection {
+                    source: d.source.clone(),
+                    name: d.name.clone(),
+                    level: d.level.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        format!(
+            "Analysis {} finished with score {:.1} ({})",
+            self.analysis_id, self.score, self.verdict
+        )
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\nSHA3-512: {}\nExecution: {} at {}\nDetections: {}\n",
+            self.subject(),
+            self.hash,
+            self.execution_id,
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            self.detections.len()
+        );
+        for detection in self.detections.iter().take(MAX_LISTED_DETECTIONS) {
+            let _ = writeln!(
+                text,
+                "- [{}] {}: {}",
+                detection.level.as_deref().unwrap_or("-"),
+                detection.source,
+                detection.name
+            );
+        }
+        if self.detections.len() > MAX_LISTED_DETECTIONS {
+            let _ = writeln!(
+                text,
+                "... and {} more",
+                self.detections.len() - MAX_LISTED_DETECTIONS
+            );
+        }
+        text
+    }
+}
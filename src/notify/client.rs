@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+use hmac::{Hmac, Mac};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{info, warn};
+use serde_json::json;
+use sha2::Sha256;
+use tokio::time::{sleep, Duration};
+
+use super::{Channel, EmailConfig, Notification, NotifyConfig, Target, SIGNATURE_HEADER};
+
+pub fn sign(secret: &str, body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(body);
+    Ok(format!("sha256={:x}", mac.finalize().into_bytes()))
+}
+
+#[derive(Debug)]
+pub struct Notifier {
+    config: NotifyConfig,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(config: NotifyConfig) -> Result<Self> {
+        Ok(Self {
+            config,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+        })
+    }
+
+    pub async fn notify(&self, notification: &Notification) -> usize {
+        let mut sent = 0;
+        for target in self.config.targets.iter().filter(|t| t.wants(notification)) {
+            match self.deliver_with_retry(target, notification).await {
+                Ok(()) => sent += 1,
+                Err(e) => warn!(
+                    "Failed to send {} notification for {}: {}",
+                    target.channel.name(),
+                    notification.analysis_id,
+                    e
+                ),
+            }
+        }
+        if sent > 0 {
+            info!(
+                "Sent {} notifications for analysis {}",
+                sent, notification.analysis_id
+            );
+        }
+        sent
+    }
+
+    async fn deliver_with_retry(&self, target: &Target, notification: &Notification) -> Result<()> {
+        let mut delay = Duration::from_secs(self.config.backoff_secs);
+        let mut attempt = 0;
+        loop {
+            match self.deliver(&target.channel, notification).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < self.config.max_retries => {
+                    attempt += 1;
+                    warn!(
+                        "{} notification attempt {}/{} failed, retrying in {:?}: {}",
+                        target.channel.name(),
+                        attempt,
+                        self.config.max_retries,
+                        delay,
+                        e
+                    );
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn deliver(&self, channel: &Channel, notification: &Notification) -> Result<()> {
+        match channel {
+            Channel::Webhook { url, secret } => {
+                let body = serde_json::to_vec(notification)?;
+                let mut request = self
+                    .client
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json");
+                if let Some(secret) = secret {
+                    request = request.header(SIGNATURE_HEADER, sign(secret, &body)?);
+                }
+                request.body(body).send().await?.error_for_status()?;
+            }
+            Channel::Slack { url } => {
+                self.client
+                    .post(url)
+                    .json(&json!({ "text": notification.to_text() }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            Channel::Email(email) => send_email(email, notification).await?,
+        }
+        Ok(())
+    }
+}
+
+async fn send_email(email: &EmailConfig, notification: &Notification) -> Result<()> {
+    if email.to.is_empty() {
+        bail!("Email notification has no recipients");
+    }
+    let mut builder = Message::builder()
+        .from(email.from.parse::<Mailbox>()?)
+        .subject(notification.subject());
+    for to in &email.to {
+        builder = builder.to(to.parse::<Mailbox>()?);
+    }
+    let message = builder
+        .header(ContentType::TEXT_PLAIN)
+        .body(notification.to_text())?;
+
+    let mut transport = if email.starttls {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&email.server)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&email.server)?
+    };
+    if let Some(port) = email.port {
+        transport = transport.port(port);
+    }
+    if let (Some(username), Some(password)) = (&email.username, &email.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    transport.build().send(message).await?;
+    Ok(())
+}
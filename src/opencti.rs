@@ -0,0 +1,168 @@
+#[cfg(feature = "opencti")]
+mod client;
+
+#[cfg(feature = "opencti")]
+pub use client::{OpenCtiClient, PushSummary};
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::export::stix::{confidence, observable};
+use crate::ioc::Confidence;
+use crate::report::SandboxReport;
+use crate::verdict::Verdict;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OpenCtiConfig {
+    pub url: String,
+    pub token: String,
+    #[serde(default = "default_marking")]
+    pub marking: String,
+    #[serde(default = "default_min_verdict")]
+    pub min_verdict: Verdict,
+    #[serde(default = "default_min_confidence")]
+    pub min_confidence: Confidence,
+    #[serde(default = "default_verify_tls")]
+    pub verify_tls: bool,
+}
+
+fn default_marking() -> String {
+    "TLP:AMBER".to_string()
+}
+
+fn default_min_verdict() -> Verdict {
+    Verdict::Suspicious
+}
+
+fn default_min_confidence() -> Confidence {
+    Confidence::Medium
+}
+
+fn default_verify_tls() -> bool {
+    true
+}
+
+impl OpenCtiConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn wants(&self, report: &SandboxReport) -> bool {
+        report.verdict.verdict >= self.min_verdict
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MalwareEntity {
+    pub name: String,
+    pub description: String,
+    pub confidence: u8,
+    pub labels: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IndicatorEntity {
+    pub name: String,
+    pub pattern: String,
+    pub observable_type: String,
+    pub valid_from: String,
+    pub confidence: u8,
+    pub score: u8,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AttackPatternEntity {
+    pub mitre_id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct OpenCtiPush {
+    pub malware: MalwareEntity,
+    pub indicators: Vec<IndicatorEntity>,
+    pub attack_patterns: Vec<AttackPatternEntity>,
+}
+
+fn verdict_confidence(verdict: Verdict) -> u8 {
+    match verdict {
+        Verdict::Malicious => 85,
+        Verdict::Suspicious => 50,
+        Verdict::Clean | Verdict::Unknown => 15,
+    }
+}
+
+fn observable_type(kind: &str) -> &str {
+    match kind {
+        "ipv4-addr" => "IPv4-Addr",
+        "ipv6-addr" => "IPv6-Addr",
+        "domain-name" => "Domain-Name",
+        "url" => "Url",
+        "email-addr" => "Email-Addr",
+        "file" => "StixFile",
+        "windows-registry-key" => "Windows-Registry-Key",
+        "mutex" => "Mutex",
+        other => other,
+    }
+}
+
+impl OpenCtiPush {
+    pub fn from_report(report: &SandboxReport, config: &OpenCtiConfig) -> Self {
+        let score = (report.score.score * 10.0).clamp(0.0, 100.0) as u8;
+        let indicators = report
+            .iocs
+            .with_min_confidence(config.min_confidence)
+            .filter_map(|i| {
+                let (object, pattern) = observable(&i.ioc)?;
+                let kind = object["type"].as_str().unwrap_or_default();
+                Some(IndicatorEntity {
+                    name: format!("{} {}", i.ioc.kind(), i.ioc.value()),
+                    pattern,
+                    observable_type: observable_type(kind).to_string(),
+                    valid_from: i
+                        .first_seen
+                        .with_timezone(&Utc)
+                        .to_rfc3339_opts(SecondsFormat::Millis, true),
+                    confidence: confidence(i.confidence),
+                    score,
+                })
+            })
+            .collect();
+
+        let attack_patterns = report
+            .techniques
+            .iter()
+            .map(|t| AttackPatternEntity {
+                mitre_id: t.id.clone(),
+                name: t.name.clone().unwrap_or(t.id.clone()),
+            })
+            .collect();
+
+        let detections: Vec<&str> = report
+            .detections
+            .iter()
+            .map(|d| d.name.as_str())
+            .take(10)
+            .collect();
+        let mut description = format!(
+            "Sandbox analysis {} scored {:.1} ({})",
+            report.id, report.score.score, report.verdict.verdict
+        );
+        if !detections.is_empty() {
+            description.push_str(&format!(". Detections: {}", detections.join("; ")));
+        }
+
+        Self {
+            malware: MalwareEntity {
+                name: report.hash.clone(),
+                description,
+                confidence: verdict_confidence(report.verdict.verdict),
+                labels: vec!["sandbox".to_string(), report.verdict.verdict.to_string()],
+            },
+            indicators,
+            attack_patterns,
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::time::Duration;
+
+use super::{OpenCtiConfig, OpenCtiPush};
+
+const MARKINGS_QUERY: &str = "query Markings($search: String) { \
+    markingDefinitions(search: $search, first: 50) { edges { node { id definition } } } }";
+const MALWARE_MUTATION: &str =
+    "mutation MalwareAdd($input: MalwareAddInput!) { malwareAdd(input: $input) { id } }";
+const INDICATOR_MUTATION: &str =
+    "mutation IndicatorAdd($input: IndicatorAddInput!) { indicatorAdd(input: $input) { id } }";
+const ATTACK_PATTERN_MUTATION: &str = "mutation AttackPatternAdd($input: AttackPatternAddInput!) \
+    { attackPatternAdd(input: $input) { id } }";
+const RELATIONSHIP_MUTATION: &str =
+    "mutation RelationshipAdd($input: StixCoreRelationshipAddInput!) \
+    { stixCoreRelationshipAdd(input: $input) { id } }";
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct Created {
+    id: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PushSummary {
+    pub malware: String,
+    pub indicators: usize,
+    pub attack_patterns: usize,
+    pub relationships: usize,
+}
+
+#[derive(Debug)]
+pub struct OpenCtiClient {
+    config: OpenCtiConfig,
+    client: reqwest::Client,
+}
+
+impl OpenCtiClient {
+    pub fn new(config: OpenCtiConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(!config.verify_tls)
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(Self { config, client })
+    }
+
+    pub fn config(&self) -> &OpenCtiConfig {
+        &self.config
+    }
+
+    async fn query<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T> {
+        let response: GraphQlResponse<T> = self
+            .client
+            .post(format!("{}/graphql", self.config.url.trim_end_matches('/')))
+            .bearer_auth(&self.config.token)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if !response.errors.is_empty() {
+            let messages: Vec<String> = response.errors.into_iter().map(|e| e.message).collect();
+            bail!("OpenCTI request failed: {}", messages.join("; "));
+        }
+        response.data.context("OpenCTI returned no data")
+    }
+
+    async fn create(&self, mutation: &str, field: &str, input: Value) -> Result<String> {
+        let data: Value = self.query(mutation, json!({ "input": input })).await?;
+        let created: Created = serde_json::from_value(data[field].clone())
+            .with_context(|| format!("OpenCTI did not return {}", field))?;
+        Ok(created.id)
+    }
+
+    pub async fn marking_id(&self, definition: &str) -> Result<Option<String>> {
+        let data: Value = self
+            .query(MARKINGS_QUERY, json!({ "search": definition }))
+            .await?;
+        let id = data["markingDefinitions"]["edges"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|edge| &edge["node"])
+            .find(|node| {
+                node["definition"]
+                    .as_str()
+                    .is_some_and(|d| d.eq_ignore_ascii_case(definition))
+            })
+            .and_then(|node| node["id"].as_str())
+            .map(str::to_string);
+        Ok(id)
+    }
+
+    async fn relate(
+        &self,
+        kind: &str,
+        from: &str,
+        to: &str,
+        confidence: u8,
+        markings: &[String],
+    ) -> Result<String> {
+        self.create(
+            RELATIONSHIP_MUTATION,
+            "stixCoreRelationshipAdd",
+            json!({
+                "relationship_type": kind,
+                "fromId": from,
+                "toId": to,
+                "confidence": confidence,
+                "objectMarking": markings,
+            }),
+        )
+        .await
+    }
+
+    pub async fn push(&self, push: &OpenCtiPush) -> Result<PushSummary> {
+        let markings: Vec<String> = match self.marking_id(&self.config.marking).await? {
+            Some(id) => vec![id],
+            None => {
+                warn!("OpenCTI has no marking definition {}", self.config.marking);
+                Vec::new()
+            }
+        };
+
+        let malware = &push.malware;
+        let malware_id = self
+            .create(
+                MALWARE_MUTATION,
+                "malwareAdd",
+                json!({
+                    "name": malware.name,
+                    "description": malware.description,
+                    "is_family": false,
+                    "confidence": malware.confidence,
+                    "objectLabel": malware.labels,
+                    "objectMarking": markings,
+                }),
+            )
+            .await?;
+        let mut summary = PushSummary {
+            malware: malware_id.clone(),
+            ..PushSummary::default()
+        };
+
+        for indicator in &push.indicators {
+            let result = self
+                .create(
+                    INDICATOR_MUTATION,
+                    "indicatorAdd",
+                    json!({
+                        "name": indicator.name,
+                        "pattern": indicator.pattern,
+                        "pattern_type": "stix",
+                        "x_opencti_main_observable_type": indicator.observable_type,
+                        "valid_from": indicator.valid_from,
+                        "confidence": indicator.confidence,
+                        "x_opencti_score": indicator.score,
+                        "objectMarking": markings,
+                    }),
+                )
+                .await;
+            let id = match result {
+                Ok(id) => id,
+                Err(e) => {
+                    warn!("Failed to create indicator {}: {}", indicator.name, e);
+                    continue;
+                }
+            };
+            summary.indicators += 1;
+            self.relate(
+                "indicates",
+                &id,
+                &malware_id,
+                indicator.confidence,
+                &markings,
+            )
+            .await?;
+            summary.relationships += 1;
+        }
+
+        for pattern in &push.attack_patterns {
+            let id = self
+                .create(
+                    ATTACK_PATTERN_MUTATION,
+                    "attackPatternAdd",
+                    json!({
+                        "name": pattern.name,
+                        "x_mitre_id": pattern.mitre_id,
+                    }),
+                )
+                .await?;
+            summary.attack_patterns += 1;
+            self.relate("uses", &malware_id, &id, malware.confidence, &markings)
+                .await?;
+            summary.relationships += 1;
+        }
+
+        info!(
+            "Pushed {} indicators and {} attack patterns to OpenCTI malware {}",
+            summary.indicators, summary.attack_patterns, summary.malware
+        );
+        Ok(summary)
+    }
+}
@@ -0,0 +1,734 @@
+pub mod image;
+pub mod libvirt;
+pub mod limits;
+pub mod virtualbox;
+pub mod vmi;
+
+use std::collections::HashMap;
+use std::fs::{create_dir_all, write};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{Local, Utc};
+use log::{info, warn};
+use tokio::time::{sleep, timeout, Duration, Instant};
+use uuid::Uuid;
+
+#[cfg(feature = "grpc")]
+use crate::agent::grpc::GrpcAgentClient;
+use crate::agent::protocol::{ExecutionReport, ExecutionRequest, LogFormat};
+#[cfg(feature = "grpc")]
+use crate::agent::Control;
+use crate::agent::{AgentClient, AgentResult};
+use crate::analysis_result::{artifact_dir, sample_path, ExecutionLog};
+#[cfg(feature = "grpc")]
+use crate::analyzer::live::LiveAction;
+use crate::analyzer::live::LiveMonitor;
+use crate::artifacts::{collect, dumped, ARTIFACTS_FILE};
+use crate::memory::payload::{self, Payload};
+use crate::memory::{self, dump_file_name, MEMORY_FILE};
+use crate::metadata::SampleMetadata;
+use crate::metrics::{self, AGENT_REQUEST, VM_OPERATION};
+use crate::netsim::{NetSim, NETSIM_LOG_FILE};
+use crate::pcap::{Capture, PCAP_FILE_NAME};
+use crate::quarantine::ParseFailureSummary;
+use crate::static_analysis::{
+    captured_scripts, deobfuscate_powershell, write_scripts, CAPTURED_DIR, DEOBFUSCATED_DIR,
+};
+use crate::sysmon_event::SysmonEvent;
+use crate::tamper::{GoldenFile, GoldenImage, GoldenStore};
+use crate::telemetry::TelemetryEvent;
+use crate::timeline::{screenshot_path, RECORDING_FILE, SCREENSHOT_TIMES_FILE};
+use crate::vmi::VmiConfig;
+use image::ImageMetadata;
+use limits::{ResourceLimits, ResourceUsage, Watchdog};
+use vmi::VmiSession;
+
+pub trait Hypervisor {
+    fn restore_snapshot(&self, vm: &str, snapshot: &str)
+        -> impl Future<Output = Result<()>> + Send;
+
+    fn take_snapshot(&self, vm: &str, snapshot: &str) -> impl Future<Output = Result<()>> + Send;
+
+    fn disks(&self, vm: &str) -> impl Future<Output = Result<Vec<PathBuf>>> + Send;
+
+    fn start(&self, vm: &str) -> impl Future<Output = Result<()>> + Send;
+
+    fn stop(&self, vm: &str) -> impl Future<Output = Result<()>> + Send;
+
+    fn guest_address(&self, vm: &str) -> impl Future<Output = Result<IpAddr>> + Send;
+
+    fn apply_limits(
+        &self,
+        vm: &str,
+        limits: &ResourceLimits,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn usage(&self, vm: &str) -> impl Future<Output = Result<ResourceUsage>> + Send;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AgentTransport {
+    #[default]
+    Tcp,
+    Grpc,
+    Agentless,
+}
+
+#[derive(Debug, Clone)]
+pub struct VmSpec {
+    pub name: String,
+    pub snapshot: String,
+    pub agent_port: u16,
+    pub transport: AgentTransport,
+    pub address: Option<IpAddr>,
+    pub capture_interface: Option<String>,
+    pub image: Option<ImageMetadata>,
+}
+
+impl VmSpec {
+    pub fn new(name: &str, snapshot: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            snapshot: snapshot.to_string(),
+            agent_port: 8000,
+            transport: AgentTransport::default(),
+            address: None,
+            capture_interface: None,
+            image: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrchestratorOptions {
+    pub boot_timeout: Duration,
+    pub poll_interval: Duration,
+    pub result_margin: Duration,
+    pub watchdog_interval: Duration,
+}
+
+impl Default for OrchestratorOptions {
+    fn default() -> Self {
+        Self {
+            boot_timeout: Duration::from_secs(300),
+            poll_interval: Duration::from_secs(2),
+            result_margin: Duration::from_secs(120),
+            watchdog_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+pub struct Orchestrator<H> {
+    hypervisor: H,
+    options: OrchestratorOptions,
+    netsim: Option<Arc<NetSim>>,
+    live: Option<Arc<LiveMonitor>>,
+    vmi: Option<VmiConfig>,
+    golden: Option<GoldenStore>,
+}
+
+impl<H: Hypervisor> Orchestrator<H> {
+    pub fn new(hypervisor: H) -> Self {
+        Self::with_options(hypervisor, OrchestratorOptions::default())
+    }
+
+    pub fn with_options(hypervisor: H, options: OrchestratorOptions) -> Self {
+        Self {
+            hypervisor,
+            options,
+            netsim: None,
+            live: None,
+            vmi: None,
+            golden: None,
+        }
+    }
+
+    pub fn with_netsim(mut self, netsim: Arc<NetSim>) -> Self {
+        self.netsim = Some(netsim);
+        self
+    }
+
+    pub fn with_live(mut self, live: Arc<LiveMonitor>) -> Self {
+        self.live = Some(live);
+        self
+    }
+
+    pub fn with_vmi(mut self, vmi: VmiConfig) -> Self {
+        self.vmi = Some(vmi);
+        self
+    }
+
+    pub fn with_golden(mut self, golden: GoldenStore) -> Self {
+        self.golden = Some(golden);
+        self
+    }
+
+    pub fn hypervisor(&self) -> &H {
+        &self.hypervisor
+    }
+
+    async fn connect_agent<C, F, Fut>(&self, vm: &VmSpec, connect: F) -> Result<C>
+    where
+        F: Fn(SocketAddr) -> Fut,
+        Fut: Future<Output = Result<C>>,
+    {
+        let deadline = Instant::now() + self.options.boot_timeout;
+        loop {
+            let address = match vm.address {
+                Some(address) => Ok(address),
+                None => self.hypervisor.guest_address(&vm.name).await,
+            };
+            let result = match address {
+                Ok(address) => connect(SocketAddr::new(address, vm.agent_port)).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(client) => return Ok(client),
+                Err(e) if Instant::now() >= deadline => {
+                    return Err(e).context(format!("Agent on {} did not come up", vm.name))
+                }
+                Err(_) => sleep(self.options.poll_interval).await,
+            }
+        }
+    }
+
+    async fn detonate(
+        &self,
+        vm: &VmSpec,
+        request: &ExecutionRequest,
+        limits: &ResourceLimits,
+        sample: &[u8],
+    ) -> Result<AgentResult> {
+        info!("Restoring {} to snapshot {}...", vm.name, vm.snapshot);
+        let span = vm_span("vm.restore", "restore", vm);
+        self.hypervisor
+            .restore_snapshot(&vm.name, &vm.snapshot)
+            .await?;
+        drop(span);
+
+        if *limits != ResourceLimits::default() {
+            if let Err(e) = self.hypervisor.apply_limits(&vm.name, limits).await {
+                warn!("Failed to apply resource limits to {}: {}", vm.name, e);
+            }
+        }
+
+        info!("Starting {}...", vm.name);
+        let span = vm_span("vm.start", "start", vm);
+        self.hypervisor.start(&vm.name).await?;
+        drop(span);
+
+        info!("Submitting sample...");
+        self.submit(vm, request, sample).await
+    }
+
+    async fn submit(
+        &self,
+        vm: &VmSpec,
+        request: &ExecutionRequest,
+        sample: &[u8],
+    ) -> Result<AgentResult> {
+        let transport = match vm.transport {
+            AgentTransport::Tcp => "tcp",
+            AgentTransport::Grpc => "grpc",
+            AgentTransport::Agentless => "agentless",
+        };
+        let mut span = metrics::timed(
+            "agent.submit",
+            &AGENT_REQUEST,
+            &[("machine", vm.name.as_str()), ("transport", transport)],
+        );
+        let limit = Duration::from_secs(request.timeout_secs) + self.options.result_margin;
+        let result = match vm.transport {
+            AgentTransport::Tcp => {
+                info!("Waiting for agent...");
+                let client = self.connect_agent(vm, AgentClient::connect).await?;
+                timeout(limit, client.submit(request, sample)).await
+            }
+            #[cfg(feature = "grpc")]
+            AgentTransport::Grpc => {
+                info!("Waiting for agent...");
+                let client = self.connect_agent(vm, GrpcAgentClient::connect).await?;
+                match &self.live {
+                    Some(live) => {
+                        let limit = limit + Duration::from_secs(live.max_extension());
+                        timeout(limit, self.follow_live(live, vm, client, request, sample)).await
+                    }
+                    None => timeout(limit, client.submit(request, sample)).await,
+                }
+            }
+            #[cfg(not(feature = "grpc"))]
+            AgentTransport::Grpc => anyhow::bail!("gRPC agent transport is not compiled in"),
+            AgentTransport::Agentless => {
+                let delay = self.vmi.as_ref().map_or(0, |c| c.boot_delay_secs);
+                let limit = limit + Duration::from_secs(delay);
+                timeout(limit, self.introspect(vm, request, sample)).await
+            }
+        };
+        let result = result.context("Agent did not return results in time")?;
+        if let Err(e) = &result {
+            span.fail(e);
+        }
+        result
+    }
+
+    #[cfg(feature = "grpc")]
+    async fn follow_live(
+        &self,
+        live: &LiveMonitor,
+        vm: &VmSpec,
+        mut client: GrpcAgentClient,
+        request: &ExecutionRequest,
+        sample: &[u8],
+    ) -> Result<AgentResult> {
+        let run_id = client.start(request, sample).await?;
+        let mut control = client.clone();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let follow = client.follow(&run_id, move |event| {
+            let _ = sender.send(event);
+        });
+        let watch = async {
+            while let Some(event) = receiver.recv().await {
+                let action = match live.observe(&vm.name, event) {
+                    Some(LiveAction::Extend) => {
+                        info!("Critical detection on {}, extending run", vm.name);
+                        Control::Extend(Duration::from_secs(live.policy().extend_secs))
+                    }
+                    Some(LiveAction::Terminate) => {
+                        warn!("Critical detection on {}, terminating run", vm.name);
+                        Control::Kill
+                    }
+                    None => continue,
+                };
+                if let Err(e) = control.control(&run_id, action).await {
+                    warn!("Failed to control run on {}: {}", vm.name, e);
+                }
+            }
+        };
+        let (result, ()) = tokio::join!(follow, watch);
+        result
+    }
+
+    async fn introspect(
+        &self,
+        vm: &VmSpec,
+        request: &ExecutionRequest,
+        sample: &[u8],
+    ) -> Result<AgentResult> {
+        let config = self
+            .vmi
+            .as_ref()
+            .context("Agentless transport requires an introspection backend")?;
+        sleep(Duration::from_secs(config.boot_delay_secs)).await;
+
+        info!("Introspecting {}...", vm.name);
+        let session = VmiSession::start(config, &vm.name)?;
+        if !sample.is_empty() && !request.collect_only && !config.inject_command.is_empty() {
+            let sample_file =
+                std::env::temp_dir().join(format!("{}-{}", Uuid::new_v4(), request.file_name));
+            write(&sample_file, sample)?;
+            let injected = VmiSession::inject(config, &vm.name, &sample_file).await;
+            let _ = std::fs::remove_file(&sample_file);
+            if let Err(e) = injected {
+                let _ = session.stop().await;
+                return Err(e);
+            }
+        }
+        sleep(Duration::from_secs(request.timeout_secs)).await;
+        let sysmon_log = session.stop().await?;
+
+        Ok(AgentResult {
+            report: ExecutionReport {
+                timed_out: true,
+                log_format: LogFormat::Vmi,
+                ..ExecutionReport::default()
+            },
+            sysmon_log,
+            dropped_files: Vec::new(),
+            screenshots: Vec::new(),
+            memory_dumps: Vec::new(),
+            video: None,
+            pcap: None,
+            netsim: Vec::new(),
+            terminated: None,
+            modified_files: Vec::new(),
+            tampered: Vec::new(),
+        })
+    }
+
+    async fn reboot(&self, vm: &VmSpec, request: &ExecutionRequest) -> Result<AgentResult> {
+        let golden = match &self.golden {
+            Some(store) => store.load(&vm.name, &vm.snapshot).unwrap_or_else(|e| {
+                warn!("Failed to load golden image of {}: {}", vm.name, e);
+                None
+            }),
+            None => None,
+        };
+        info!("Rebooting {}...", vm.name);
+        self.hypervisor.stop(&vm.name).await?;
+        self.hypervisor.start(&vm.name).await?;
+
+        info!("Collecting after reboot...");
+        let request = ExecutionRequest {
+            screenshot_interval_secs: None,
+            record_video: false,
+            simulate_user: false,
+            user_sim_script: None,
+            hardening: None,
+            time_warp: None,
+            dump_triggers: Vec::new(),
+            reboot: false,
+            collect_only: true,
+            resubmit_dropped: false,
+            url: None,
+            golden_files: golden.as_ref().map(|g| g.files.clone()).unwrap_or_default(),
+            ..request.clone()
+        };
+        let mut stage = self.submit(vm, &request, &[]).await?;
+        if let (Some(store), Some(golden)) = (&self.golden, &golden) {
+            for (file, content) in &stage.modified_files {
+                match store.diff(golden, &file.path, content) {
+                    Ok(Some(tamper)) => {
+                        warn!(
+                            "System file {} was modified: {}",
+                            file.path,
+                            tamper.summary()
+                        );
+                        stage.tampered.push(tamper);
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        "Failed to diff {} against the golden image: {}",
+                        file.path, e
+                    ),
+                }
+            }
+        }
+        Ok(stage)
+    }
+
+    pub async fn capture_golden(
+        &self,
+        vm: &VmSpec,
+        request: &ExecutionRequest,
+        paths: &[String],
+    ) -> Result<GoldenImage> {
+        let store = self
+            .golden
+            .as_ref()
+            .context("Capturing a golden image requires a golden store")?;
+        let request = ExecutionRequest {
+            golden_files: paths.iter().map(|p| GoldenFile::unknown(p)).collect(),
+            ..request.clone()
+        };
+        let stages = self
+            .run(vm, &request, &ResourceLimits::default(), &[])
+            .await?;
+        let files: Vec<(String, Vec<u8>)> = stages
+            .into_iter()
+            .flat_map(|s| s.modified_files)
+            .map(|(file, content)| (file.path, content))
+            .collect();
+        store.save(&vm.name, &vm.snapshot, &files)
+    }
+
+    async fn stages(
+        &self,
+        vm: &VmSpec,
+        request: &ExecutionRequest,
+        limits: &ResourceLimits,
+        sample: &[u8],
+        stages: &mut Vec<AgentResult>,
+    ) -> Result<()> {
+        stages.push(self.detonate(vm, request, limits, sample).await?);
+        if request.reboot {
+            match self.reboot(vm, request).await {
+                Ok(stage) => stages.push(stage),
+                Err(e) => warn!("Reboot stage on {} failed: {}", vm.name, e),
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn run(
+        &self,
+        vm: &VmSpec,
+        request: &ExecutionRequest,
+        limits: &ResourceLimits,
+        sample: &[u8],
+    ) -> Result<Vec<AgentResult>> {
+        let capture = match &vm.capture_interface {
+            Some(interface) => {
+                let path =
+                    std::env::temp_dir().join(format!("{}-{}.pcap", vm.name, Uuid::new_v4()));
+                match Capture::start(interface, &path, None) {
+                    Ok(capture) => Some((capture, path)),
+                    Err(e) => {
+                        warn!("Failed to capture traffic of {}: {}", vm.name, e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let started = Utc::now();
+        let mut stages = Vec::new();
+        if let Some(live) = &self.live {
+            live.begin(&vm.name);
+        }
+        let mut watchdog = Watchdog::new(
+            &self.hypervisor,
+            &vm.name,
+            limits,
+            self.options.watchdog_interval,
+        );
+        let outcome = tokio::select! {
+            result = self.stages(vm, request, limits, sample, &mut stages) => Ok(result),
+            violation = watchdog.watch() => Err(violation),
+        };
+        let mut result = match outcome {
+            Ok(result) => result.map(|()| stages),
+            Err(violation) => {
+                warn!("Terminating run on {}: {}", vm.name, violation);
+                stages.push(AgentResult {
+                    report: ExecutionReport {
+                        killed: true,
+                        error: Some(violation.to_string()),
+                        ..ExecutionReport::default()
+                    },
+                    sysmon_log: Vec::new(),
+                    dropped_files: Vec::new(),
+                    screenshots: Vec::new(),
+                    memory_dumps: Vec::new(),
+                    video: None,
+                    pcap: None,
+                    netsim: Vec::new(),
+                    terminated: Some(violation),
+                    modified_files: Vec::new(),
+                    tampered: Vec::new(),
+                });
+                Ok(stages)
+            }
+        };
+
+        if let (Some(netsim), Some(result)) = (
+            &self.netsim,
+            result.as_mut().ok().and_then(|r| r.first_mut()),
+        ) {
+            let address = match vm.address {
+                Some(address) => Ok(address),
+                None => self.hypervisor.guest_address(&vm.name).await,
+            };
+            match address {
+                Ok(address) => result.netsim = netsim.requests_from(address, started),
+                Err(e) => warn!("Failed to collect simulated traffic of {}: {}", vm.name, e),
+            }
+        }
+
+        if let Some((capture, path)) = capture {
+            if let Err(e) = capture.stop().await {
+                warn!("Failed to stop capture of {}: {}", vm.name, e);
+            }
+            match tokio::fs::read(&path).await {
+                Ok(pcap) => {
+                    if let Some(result) = result.as_mut().ok().and_then(|r| r.first_mut()) {
+                        result.pcap = Some(pcap);
+                    }
+                }
+                Err(e) => warn!("Failed to read capture of {}: {}", vm.name, e),
+            }
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+
+        if let Some(live) = &self.live {
+            live.end(&vm.name);
+        }
+
+        info!("Reverting {}...", vm.name);
+        let _span = vm_span("vm.revert", "revert", vm);
+        if let Err(e) = self.hypervisor.stop(&vm.name).await {
+            warn!("Failed to stop {}: {}", vm.name, e);
+        }
+        if let Err(e) = self
+            .hypervisor
+            .restore_snapshot(&vm.name, &vm.snapshot)
+            .await
+        {
+            warn!("Failed to revert {}: {}", vm.name, e);
+        }
+
+        result
+    }
+}
+
+fn vm_span(name: &'static str, operation: &str, vm: &VmSpec) -> metrics::Span {
+    metrics::timed(
+        name,
+        &VM_OPERATION,
+        &[("operation", operation), ("machine", vm.name.as_str())],
+    )
+}
+
+pub fn save_artifacts(id: &str, stages: &[AgentResult]) -> Result<ExecutionLog> {
+    let execution_id = Uuid::new_v4().to_string();
+    let artifact_dir = artifact_dir(id, &execution_id);
+    create_dir_all(&artifact_dir)?;
+
+    let mut events = Vec::new();
+    let mut telemetry = Vec::new();
+    let mut dropped = Vec::new();
+    let mut screenshot_times = Vec::new();
+    let mut memory = Vec::new();
+    let mut payloads: Vec<(Payload, Vec<u8>)> = Vec::new();
+    let sample = std::fs::read(sample_path(id)).ok();
+    let mut mutexes = Vec::new();
+    let mut clipboard = Vec::new();
+    let mut terminated = None;
+    let mut tampered = Vec::new();
+    let mut screenshots = 0;
+    let mut video = None;
+    for (stage, result) in stages.iter().enumerate() {
+        let log_name = match stage {
+            0 => "sysmon.log".to_string(),
+            _ => format!("sysmon-reboot-{}.log", stage),
+        };
+        write(format!("{}/{}", artifact_dir, log_name), &result.sysmon_log)?;
+
+        let last = events.iter().map(|e: &SysmonEvent| e.time_created).max();
+        events.extend(
+            result
+                .events()
+                .into_iter()
+                .filter(|e| last.is_none_or(|last| e.time_created > last)),
+        );
+        let last = telemetry.iter().map(TelemetryEvent::time).max();
+        telemetry.extend(
+            result
+                .telemetry()
+                .into_iter()
+                .filter(|e| last.is_none_or(|last| e.time() > last)),
+        );
+        for (file, content) in &result.dropped_files {
+            dropped.retain(|(path, _): &(&str, &[u8])| *path != file.path);
+            dropped.push((file.path.as_str(), content.as_slice()));
+        }
+
+        for png in &result.screenshots {
+            write(screenshot_path(&artifact_dir, screenshots), png)?;
+            screenshots += 1;
+        }
+        screenshot_times.extend(result.screenshot_times());
+        mutexes.extend(result.mutexes());
+        clipboard.extend(result.clipboard());
+        if terminated.is_none() {
+            terminated = result.terminated.clone();
+        }
+        tampered.extend(result.tampered.iter().cloned());
+        if video.is_none() {
+            video = result.video.as_ref();
+        }
+
+        for (dump, content) in &result.memory_dumps {
+            let name = dump_file_name(memory.len());
+            write(format!("{}/{}", artifact_dir, name), content)?;
+            let mut analysis = memory::analyze(&name, dump, content);
+            for (payload, image) in payload::extract(content, dump.process_id, sample.as_deref()) {
+                analysis.payloads.push(payload.clone());
+                payloads.push((payload, image));
+            }
+            memory.push(analysis);
+        }
+
+        if !result.netsim.is_empty() {
+            write(
+                format!("{}/{}", artifact_dir, NETSIM_LOG_FILE),
+                serde_json::to_vec_pretty(&result.netsim)?,
+            )?;
+        }
+        if let Some(pcap) = &result.pcap {
+            write(format!("{}/{}", artifact_dir, PCAP_FILE_NAME), pcap)?;
+        }
+    }
+
+    let mut artifacts = collect(&events, dropped.iter().copied());
+    let now = Local::now().into();
+    artifacts.extend(
+        payloads
+            .iter()
+            .map(|(payload, image)| dumped(&payload.path, image, now)),
+    );
+    let contents = dropped
+        .iter()
+        .map(|(_, content)| *content)
+        .chain(payloads.iter().map(|(_, image)| image.as_slice()));
+    let mut created_files = HashMap::new();
+    for (artifact, content) in artifacts.iter().zip(contents) {
+        write(
+            format!("{}/{}", artifact_dir, artifact.file_name()),
+            content,
+        )?;
+        created_files.insert(artifact.path.clone(), artifact.file_name().to_string());
+    }
+    if !artifacts.is_empty() {
+        write(
+            format!("{}/{}", artifact_dir, ARTIFACTS_FILE),
+            serde_json::to_vec_pretty(&artifacts)?,
+        )?;
+    }
+
+    if !screenshot_times.is_empty() {
+        write(
+            format!("{}/{}", artifact_dir, SCREENSHOT_TIMES_FILE),
+            serde_json::to_vec_pretty(&screenshot_times)?,
+        )?;
+    }
+    if let Some(video) = video {
+        write(format!("{}/{}", artifact_dir, RECORDING_FILE), video)?;
+    }
+
+    let captured = captured_scripts(&telemetry);
+    if !captured.is_empty() {
+        write_scripts(format!("{}/{}", artifact_dir, CAPTURED_DIR), &captured)?;
+    }
+    let deobfuscated = deobfuscate_powershell(&events, &captured);
+    if !deobfuscated.is_empty() {
+        write_scripts(
+            format!("{}/{}", artifact_dir, DEOBFUSCATED_DIR),
+            &deobfuscated,
+        )?;
+    }
+
+    if !memory.is_empty() {
+        write(
+            format!("{}/{}", artifact_dir, MEMORY_FILE),
+            serde_json::to_vec_pretty(&memory)?,
+        )?;
+    }
+
+    Ok(ExecutionLog {
+        id: execution_id,
+        time: Local::now(),
+        sysmon_events: events,
+        created_files,
+        artifacts,
+        telemetry,
+        memory,
+        machine: None,
+        snapshot: None,
+        mutexes,
+        clipboard,
+        image: None,
+        terminated,
+        purged: Vec::new(),
+        url: None,
+        profile: None,
+        tampered,
+        parse_failures: ParseFailureSummary::default(),
+        derivation: Vec::new(),
+        metadata: SampleMetadata::default(),
+    })
+}
@@ -0,0 +1,357 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::Hypervisor;
+use crate::baseline::{component, versions, Baseline, BaselineStore};
+
+const CURRENT_FILE: &str = "current";
+const VM_PLACEHOLDER: &str = "{vm}";
+const SNAPSHOT_PLACEHOLDER: &str = "{snapshot}";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageMetadata {
+    pub name: String,
+    pub machine: String,
+    pub snapshot: String,
+    pub version: u32,
+    pub hypervisor: String,
+    pub created: DateTime<Utc>,
+    #[serde(default)]
+    pub os_version: Option<String>,
+    #[serde(default)]
+    pub tooling: Vec<String>,
+    #[serde(default)]
+    pub sysmon_config_hash: Option<String>,
+    #[serde(default)]
+    pub disk_checksum: Option<String>,
+    #[serde(default)]
+    pub baseline_checksum: Option<String>,
+    #[serde(default)]
+    pub verified: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImageOptions {
+    pub os_version: Option<String>,
+    pub tooling: Vec<String>,
+    pub sysmon_config: Option<PathBuf>,
+    pub provision_command: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Match,
+    Mismatch,
+    Recorded,
+    Unavailable,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImageVerification {
+    pub image: ImageMetadata,
+    pub disk: CheckStatus,
+    pub baseline: CheckStatus,
+}
+
+impl ImageVerification {
+    pub fn passed(&self) -> bool {
+        self.disk != CheckStatus::Mismatch && self.baseline != CheckStatus::Mismatch
+    }
+}
+
+pub fn file_checksum<P: AsRef<Path>>(path: P) -> Result<String> {
+    Ok(format!("{:x}", Sha256::digest(fs::read(path)?)))
+}
+
+pub fn disk_checksum(disks: &[PathBuf]) -> Result<String> {
+    let mut disks = disks.to_vec();
+    disks.sort();
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    for disk in &disks {
+        let mut file = fs::File::open(disk)
+            .with_context(|| format!("Failed to open disk {}", disk.display()))?;
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn baseline_checksum(baseline: &Baseline) -> String {
+    let mut hasher = Sha256::new();
+    for fingerprint in baseline.fingerprints.keys() {
+        hasher.update(fingerprint.as_bytes());
+        hasher.update(b"\n");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageStore {
+    dir: PathBuf,
+}
+
+impl ImageStore {
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn image_dir(&self, name: &str) -> PathBuf {
+        self.dir.join(component(name))
+    }
+
+    pub fn names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                names.extend(entry.file_name().to_str().map(str::to_string));
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn versions(&self, name: &str) -> Result<Vec<u32>> {
+        versions(&self.image_dir(name))
+    }
+
+    pub fn next_version(&self, name: &str) -> Result<u32> {
+        Ok(self.versions(name)?.last().map_or(1, |v| v + 1))
+    }
+
+    pub fn save(&self, image: &ImageMetadata) -> Result<()> {
+        let dir = self.image_dir(&image.name);
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(format!("v{}.json", image.version)),
+            serde_json::to_vec_pretty(image)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn load(&self, name: &str, version: u32) -> Result<ImageMetadata> {
+        let path = self.image_dir(name).join(format!("v{}.json", version));
+        let data =
+            fs::read(&path).with_context(|| format!("Failed to read image {}", path.display()))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn set_current(&self, name: &str, version: u32) -> Result<()> {
+        fs::write(self.image_dir(name).join(CURRENT_FILE), version.to_string())?;
+        Ok(())
+    }
+
+    pub fn current(&self, name: &str) -> Result<Option<ImageMetadata>> {
+        let path = self.image_dir(name).join(CURRENT_FILE);
+        let version = match fs::read_to_string(&path) {
+            Ok(version) => version.trim().parse().ok(),
+            Err(_) => self.versions(name)?.last().copied(),
+        };
+        match version {
+            Some(version) => Ok(Some(self.load(name, version)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn find(&self, machine: &str, snapshot: &str) -> Result<Option<ImageMetadata>> {
+        for name in self.names()? {
+            for version in self.versions(&name)? {
+                let image = self.load(&name, version)?;
+                if image.machine == machine && image.snapshot == snapshot {
+                    return Ok(Some(image));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+pub struct ImageManager<'a, H> {
+    hypervisor: &'a H,
+    hypervisor_name: String,
+    store: &'a ImageStore,
+}
+
+impl<'a, H: Hypervisor> ImageManager<'a, H> {
+    pub fn new(hypervisor: &'a H, hypervisor_name: &str, store: &'a ImageStore) -> Self {
+        Self {
+            hypervisor,
+            hypervisor_name: hypervisor_name.to_string(),
+            store,
+        }
+    }
+
+    async fn provision(&self, machine: &str, snapshot: &str, command: &[String]) -> Result<()> {
+        let command: Vec<String> = command
+            .iter()
+            .map(|arg| {
+                arg.replace(VM_PLACEHOLDER, machine)
+                    .replace(SNAPSHOT_PLACEHOLDER, snapshot)
+            })
+            .collect();
+        let (program, args) = command.split_first().context("Empty provision command")?;
+        let status = tokio::process::Command::new(program)
+            .args(args)
+            .status()
+            .await?;
+        if !status.success() {
+            bail!("Provisioning {} failed with {}", machine, status);
+        }
+        Ok(())
+    }
+
+    pub async fn build(
+        &self,
+        name: &str,
+        machine: &str,
+        options: &ImageOptions,
+    ) -> Result<ImageMetadata> {
+        if !options.provision_command.is_empty() {
+            let version = self.store.next_version(name)?;
+            info!("Provisioning {}...", machine);
+            self.hypervisor.start(machine).await?;
+            self.provision(
+                machine,
+                &format!("{}-v{}", name, version),
+                &options.provision_command,
+            )
+            .await?;
+        }
+        self.snapshot(name, machine, options).await
+    }
+
+    pub async fn snapshot(
+        &self,
+        name: &str,
+        machine: &str,
+        options: &ImageOptions,
+    ) -> Result<ImageMetadata> {
+        let version = self.store.next_version(name)?;
+        let snapshot = format!("{}-v{}", name, version);
+        info!("Stopping {}...", machine);
+        self.hypervisor.stop(machine).await?;
+        info!("Taking snapshot {} of {}...", snapshot, machine);
+        self.hypervisor.take_snapshot(machine, &snapshot).await?;
+
+        let disks = self.hypervisor.disks(machine).await?;
+        let image = ImageMetadata {
+            name: name.to_string(),
+            machine: machine.to_string(),
+            snapshot,
+            version,
+            hypervisor: self.hypervisor_name.clone(),
+            created: Utc::now(),
+            os_version: options.os_version.clone(),
+            tooling: options.tooling.clone(),
+            sysmon_config_hash: match &options.sysmon_config {
+                Some(path) => Some(file_checksum(path)?),
+                None => None,
+            },
+            disk_checksum: if disks.is_empty() {
+                None
+            } else {
+                Some(disk_checksum(&disks)?)
+            },
+            baseline_checksum: None,
+            verified: None,
+        };
+        self.store.save(&image)?;
+        self.store.set_current(name, version)?;
+        Ok(image)
+    }
+
+    pub async fn verify(
+        &self,
+        name: &str,
+        baselines: Option<&BaselineStore>,
+    ) -> Result<ImageVerification> {
+        let mut image = self
+            .store
+            .current(name)?
+            .with_context(|| format!("No image named {}", name))?;
+        info!("Restoring {} to {}...", image.machine, image.snapshot);
+        self.hypervisor.stop(&image.machine).await?;
+        self.hypervisor
+            .restore_snapshot(&image.machine, &image.snapshot)
+            .await?;
+
+        let disks = self.hypervisor.disks(&image.machine).await?;
+        let disk = if disks.is_empty() {
+            None
+        } else {
+            Some(disk_checksum(&disks)?)
+        };
+        let disk = check(&mut image.disk_checksum, disk);
+
+        let baseline = match baselines {
+            Some(store) => store
+                .latest(&image.machine, &image.snapshot)?
+                .map(|b| baseline_checksum(&b)),
+            None => None,
+        };
+        let baseline = check(&mut image.baseline_checksum, baseline);
+
+        if disk != CheckStatus::Mismatch && baseline != CheckStatus::Mismatch {
+            image.verified = Some(Utc::now());
+        }
+        self.store.save(&image)?;
+        Ok(ImageVerification {
+            image,
+            disk,
+            baseline,
+        })
+    }
+
+    pub async fn rollback(&self, name: &str, version: Option<u32>) -> Result<ImageMetadata> {
+        let current = self
+            .store
+            .current(name)?
+            .with_context(|| format!("No image named {}", name))?;
+        let version = match version {
+            Some(version) => version,
+            None => self
+                .store
+                .versions(name)?
+                .into_iter()
+                .rev()
+                .find(|v| *v < current.version)
+                .with_context(|| format!("No version of {} before v{}", name, current.version))?,
+        };
+        let image = self.store.load(name, version)?;
+        info!("Rolling {} back to {}...", image.machine, image.snapshot);
+        self.hypervisor.stop(&image.machine).await?;
+        self.hypervisor
+            .restore_snapshot(&image.machine, &image.snapshot)
+            .await?;
+        self.store.set_current(name, version)?;
+        Ok(image)
+    }
+}
+
+fn check(recorded: &mut Option<String>, actual: Option<String>) -> CheckStatus {
+    match (recorded.as_ref(), actual) {
+        (_, None) => CheckStatus::Unavailable,
+        (Some(recorded), Some(actual)) if *recorded == actual => CheckStatus::Match,
+        (Some(_), Some(_)) => CheckStatus::Mismatch,
+        (None, Some(actual)) => {
+            *recorded = Some(actual);
+            CheckStatus::Recorded
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use tokio::time::{sleep, Duration, Instant};
+
+use super::limits::{ResourceLimits, ResourceUsage};
+use super::Hypervisor;
+
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const CPU_QUOTA_PERIOD: u64 = 100_000;
+
+#[derive(Debug, Clone, Default)]
+pub struct Libvirt {
+    uri: Option<String>,
+}
+
+impl Libvirt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_uri(uri: &str) -> Self {
+        Self {
+            uri: Some(uri.to_string()),
+        }
+    }
+
+    async fn virsh(&self, args: &[&str]) -> Result<String> {
+        let mut cmd = tokio::process::Command::new("virsh");
+        if let Some(uri) = &self.uri {
+            cmd.args(["-c", uri]);
+        }
+        cmd.args(args);
+
+        let output = cmd.output().await?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(anyhow!(
+                "virsh {:?} failed with {}: {:?}",
+                args,
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        }
+    }
+
+    async fn is_running(&self, vm: &str) -> Result<bool> {
+        Ok(self.virsh(&["domstate", vm]).await?.trim() == "running")
+    }
+
+    async fn domstats(&self, vm: &str) -> Result<HashMap<String, u64>> {
+        let output = self
+            .virsh(&["domstats", vm, "--cpu-total", "--balloon", "--vcpu"])
+            .await?;
+        Ok(output
+            .lines()
+            .filter_map(|line| line.trim().split_once('='))
+            .filter_map(|(key, value)| Some((key.to_string(), value.parse().ok()?)))
+            .collect())
+    }
+}
+
+impl Hypervisor for Libvirt {
+    async fn restore_snapshot(&self, vm: &str, snapshot: &str) -> Result<()> {
+        self.virsh(&["snapshot-revert", vm, snapshot, "--force"])
+            .await?;
+        Ok(())
+    }
+
+    async fn take_snapshot(&self, vm: &str, snapshot: &str) -> Result<()> {
+        self.virsh(&["snapshot-create-as", vm, snapshot, "--atomic"])
+            .await?;
+        Ok(())
+    }
+
+    async fn disks(&self, vm: &str) -> Result<Vec<PathBuf>> {
+        let output = self.virsh(&["domblklist", vm, "--details"]).await?;
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                match columns.as_slice() {
+                    ["file", "disk", _, source] => Some(PathBuf::from(source)),
+                    _ => None,
+                }
+            })
+            .collect())
+    }
+
+    async fn start(&self, vm: &str) -> Result<()> {
+        if !self.is_running(vm).await? {
+            self.virsh(&["start", vm]).await?;
+        }
+        Ok(())
+    }
+
+    async fn stop(&self, vm: &str) -> Result<()> {
+        if self.is_running(vm).await? {
+            self.virsh(&["destroy", vm]).await?;
+        }
+        Ok(())
+    }
+
+    async fn apply_limits(&self, vm: &str, limits: &ResourceLimits) -> Result<()> {
+        if let Some(cpus) = limits.cpus {
+            self.virsh(&["setvcpus", vm, &cpus.to_string(), "--config"])
+                .await?;
+        }
+        if let Some(memory) = limits.memory_mb {
+            self.virsh(&["setmem", vm, &(memory * 1024).to_string(), "--config"])
+                .await?;
+        }
+        if let Some(cap) = limits.cpu_cap {
+            let quota = format!("vcpu_quota={}", CPU_QUOTA_PERIOD * cap as u64 / 100);
+            let period = format!("vcpu_period={}", CPU_QUOTA_PERIOD);
+            self.virsh(&[
+                "schedinfo",
+                vm,
+                "--config",
+                "--set",
+                &period,
+                "--set",
+                &quota,
+            ])
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn usage(&self, vm: &str) -> Result<ResourceUsage> {
+        let before = self.domstats(vm).await?;
+        let started = Instant::now();
+        sleep(CPU_SAMPLE_INTERVAL).await;
+        let after = self.domstats(vm).await?;
+
+        let vcpus = after.get("vcpu.current").copied().unwrap_or(1).max(1);
+        let cpu_percent = match (before.get("cpu.time"), after.get("cpu.time")) {
+            (Some(before), Some(after)) => {
+                let busy = after.saturating_sub(*before) as f64;
+                let elapsed = started.elapsed().as_nanos() as f64 * vcpus as f64;
+                Some(busy / elapsed * 100.0)
+            }
+            _ => None,
+        };
+        let memory_kib = match (after.get("balloon.available"), after.get("balloon.unused")) {
+            (Some(available), Some(unused)) => Some(available.saturating_sub(*unused)),
+            _ => after.get("balloon.rss").copied(),
+        };
+        Ok(ResourceUsage {
+            cpu_percent,
+            memory_mb: memory_kib.map(|kib| kib / 1024),
+        })
+    }
+
+    async fn guest_address(&self, vm: &str) -> Result<IpAddr> {
+        let output = self.virsh(&["domifaddr", vm, "--source", "lease"]).await?;
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut columns = line.split_whitespace();
+                let protocol = columns.nth(2)?;
+                let address = columns.next()?.split('/').next()?;
+                (protocol == "ipv4").then(|| address.parse().ok()).flatten()
+            })
+            .next()
+            .context("Guest has no IPv4 address yet")
+    }
+}
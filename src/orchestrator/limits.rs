@@ -0,0 +1,233 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, Duration, Instant};
+
+use super::Hypervisor;
+
+const MEMORY_TRIP_RATIO: f64 = 0.95;
+const CPU_SATURATION_PERCENT: f64 = 95.0;
+const MB: u64 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    #[serde(default)]
+    pub cpus: Option<u32>,
+    #[serde(default)]
+    pub cpu_cap: Option<u32>,
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    #[serde(default)]
+    pub disk_mb: Option<u64>,
+    #[serde(default)]
+    pub wall_clock_secs: Option<u64>,
+    #[serde(default)]
+    pub cpu_saturation_secs: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn merge(&self, overrides: &ResourceLimits) -> Self {
+        Self {
+            cpus: overrides.cpus.or(self.cpus),
+            cpu_cap: overrides.cpu_cap.or(self.cpu_cap),
+            memory_mb: overrides.memory_mb.or(self.memory_mb),
+            disk_mb: overrides.disk_mb.or(self.disk_mb),
+            wall_clock_secs: overrides.wall_clock_secs.or(self.wall_clock_secs),
+            cpu_saturation_secs: overrides.cpu_saturation_secs.or(self.cpu_saturation_secs),
+        }
+    }
+
+    pub fn is_watched(&self) -> bool {
+        self.memory_mb.is_some()
+            || self.disk_mb.is_some()
+            || self.wall_clock_secs.is_some()
+            || self.cpu_saturation_secs.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceUsage {
+    pub cpu_percent: Option<f64>,
+    pub memory_mb: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+    Cpu,
+    Memory,
+    Disk,
+    WallClock,
+}
+
+impl LimitKind {
+    fn unit(&self) -> &'static str {
+        match self {
+            Self::Cpu | Self::WallClock => "s",
+            Self::Memory | Self::Disk => "MB",
+        }
+    }
+}
+
+impl fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Cpu => "CPU",
+            Self::Memory => "memory",
+            Self::Disk => "disk",
+            Self::WallClock => "wall-clock",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LimitViolation {
+    pub kind: LimitKind,
+    pub limit: u64,
+    pub observed: u64,
+    pub time: DateTime<Utc>,
+}
+
+impl fmt::Display for LimitViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} limit exceeded ({}{unit} observed, limit {}{unit})",
+            self.kind,
+            self.observed,
+            self.limit,
+            unit = self.kind.unit()
+        )
+    }
+}
+
+pub fn disk_usage(disks: &[PathBuf]) -> u64 {
+    disks
+        .iter()
+        .filter_map(|disk| std::fs::metadata(disk).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+pub struct Watchdog<'a, H> {
+    hypervisor: &'a H,
+    vm: &'a str,
+    limits: &'a ResourceLimits,
+    interval: Duration,
+    started: Instant,
+    disk_baseline: Option<u64>,
+    saturated_since: Option<Instant>,
+}
+
+impl<'a, H: Hypervisor> Watchdog<'a, H> {
+    pub fn new(
+        hypervisor: &'a H,
+        vm: &'a str,
+        limits: &'a ResourceLimits,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            hypervisor,
+            vm,
+            limits,
+            interval,
+            started: Instant::now(),
+            disk_baseline: None,
+            saturated_since: None,
+        }
+    }
+
+    fn violation(kind: LimitKind, limit: u64, observed: u64) -> LimitViolation {
+        LimitViolation {
+            kind,
+            limit,
+            observed,
+            time: Utc::now(),
+        }
+    }
+
+    async fn disk_usage(&self) -> Option<u64> {
+        match self.hypervisor.disks(self.vm).await {
+            Ok(disks) => Some(disk_usage(&disks)),
+            Err(e) => {
+                warn!("Failed to list disks of {}: {}", self.vm, e);
+                None
+            }
+        }
+    }
+
+    async fn check(&mut self) -> Option<LimitViolation> {
+        let elapsed = self.started.elapsed();
+        if let Some(limit) = self.limits.wall_clock_secs {
+            if elapsed.as_secs() >= limit {
+                return Some(Self::violation(
+                    LimitKind::WallClock,
+                    limit,
+                    elapsed.as_secs(),
+                ));
+            }
+        }
+
+        if let (Some(limit), Some(baseline)) = (self.limits.disk_mb, self.disk_baseline) {
+            let grown = self
+                .disk_usage()
+                .await
+                .unwrap_or(baseline)
+                .saturating_sub(baseline)
+                / MB;
+            if grown >= limit {
+                return Some(Self::violation(LimitKind::Disk, limit, grown));
+            }
+        }
+
+        if self.limits.memory_mb.is_none() && self.limits.cpu_saturation_secs.is_none() {
+            return None;
+        }
+        let usage = self.hypervisor.usage(self.vm).await.unwrap_or_default();
+        if let (Some(limit), Some(used)) = (self.limits.memory_mb, usage.memory_mb) {
+            if used as f64 >= limit as f64 * MEMORY_TRIP_RATIO {
+                return Some(Self::violation(LimitKind::Memory, limit, used));
+            }
+        }
+        if let Some(limit) = self.limits.cpu_saturation_secs {
+            if usage
+                .cpu_percent
+                .is_some_and(|p| p >= CPU_SATURATION_PERCENT)
+            {
+                let since = *self.saturated_since.get_or_insert_with(Instant::now);
+                let saturated = since.elapsed().as_secs();
+                if saturated >= limit {
+                    return Some(Self::violation(LimitKind::Cpu, limit, saturated));
+                }
+            } else {
+                self.saturated_since = None;
+            }
+        }
+        None
+    }
+
+    pub async fn watch(&mut self) -> LimitViolation {
+        if !self.limits.is_watched() {
+            return std::future::pending().await;
+        }
+        self.started = Instant::now();
+        if self.limits.disk_mb.is_some() {
+            self.disk_baseline = self.disk_usage().await;
+        }
+        loop {
+            sleep(self.interval).await;
+            if let Some(violation) = self.check().await {
+                return violation;
+            }
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use tokio::time::{sleep, Duration};
+
+use super::limits::{ResourceLimits, ResourceUsage};
+use super::Hypervisor;
+
+const GUEST_IP_PROPERTY: &str = "/VirtualBox/GuestInfo/Net/0/V4/IP";
+const USAGE_METRICS: &str =
+    "Guest/CPU/Load/User,Guest/CPU/Load/Kernel,Guest/RAM/Usage/Total,Guest/RAM/Usage/Free";
+const POWEROFF_POLLS: usize = 30;
+const DISK_EXTENSIONS: &[&str] = &[".vdi", ".vmdk", ".vhd", ".vhdx"];
+
+#[derive(Debug, Clone)]
+pub struct GuestCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VirtualBox {
+    credentials: Option<GuestCredentials>,
+}
+
+impl VirtualBox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_credentials(username: &str, password: &str) -> Self {
+        Self {
+            credentials: Some(GuestCredentials {
+                username: username.to_string(),
+                password: password.to_string(),
+            }),
+        }
+    }
+
+    async fn vboxmanage(&self, args: &[&str]) -> Result<String> {
+        let output = tokio::process::Command::new("VBoxManage")
+            .args(args)
+            .output()
+            .await?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(anyhow!(
+                "VBoxManage {:?} failed with {}: {:?}",
+                args.first(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        }
+    }
+
+    async fn state(&self, vm: &str) -> Result<String> {
+        let info = self
+            .vboxmanage(&["showvminfo", vm, "--machinereadable"])
+            .await?;
+        info.lines()
+            .find_map(|l| l.strip_prefix("VMState="))
+            .map(|s| s.trim_matches('"').to_string())
+            .context("No VMState in showvminfo output")
+    }
+
+    fn guestcontrol_args<'a>(&'a self, vm: &'a str, command: &'a str) -> Result<Vec<&'a str>> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .context("Guest control requires guest credentials")?;
+        Ok(vec![
+            "guestcontrol",
+            vm,
+            command,
+            "--username",
+            &credentials.username,
+            "--password",
+            &credentials.password,
+        ])
+    }
+
+    pub async fn copy_to_guest<P: AsRef<Path>>(
+        &self,
+        vm: &str,
+        source: P,
+        target_dir: &str,
+    ) -> Result<()> {
+        let source = source
+            .as_ref()
+            .to_str()
+            .context("source must be valid unicode")?;
+        let mut args = self.guestcontrol_args(vm, "copyto")?;
+        args.extend(["--target-directory", target_dir, source]);
+        self.vboxmanage(&args).await?;
+        Ok(())
+    }
+
+    pub async fn copy_from_guest<P: AsRef<Path>>(
+        &self,
+        vm: &str,
+        source: &str,
+        target_dir: P,
+    ) -> Result<()> {
+        let target_dir = target_dir
+            .as_ref()
+            .to_str()
+            .context("target_dir must be valid unicode")?;
+        let mut args = self.guestcontrol_args(vm, "copyfrom")?;
+        args.extend(["--target-directory", target_dir, source]);
+        self.vboxmanage(&args).await?;
+        Ok(())
+    }
+
+    pub async fn exec_in_guest(&self, vm: &str, exe: &str, arguments: &[&str]) -> Result<String> {
+        let mut args = self.guestcontrol_args(vm, "run")?;
+        args.extend(["--exe", exe, "--wait-stdout", "--"]);
+        args.push(exe);
+        args.extend(arguments);
+        self.vboxmanage(&args).await
+    }
+}
+
+impl Hypervisor for VirtualBox {
+    async fn restore_snapshot(&self, vm: &str, snapshot: &str) -> Result<()> {
+        self.stop(vm).await?;
+        self.vboxmanage(&["snapshot", vm, "restore", snapshot])
+            .await?;
+        Ok(())
+    }
+
+    async fn take_snapshot(&self, vm: &str, snapshot: &str) -> Result<()> {
+        self.vboxmanage(&["snapshot", vm, "take", snapshot]).await?;
+        Ok(())
+    }
+
+    async fn disks(&self, vm: &str) -> Result<Vec<PathBuf>> {
+        let info = self
+            .vboxmanage(&["showvminfo", vm, "--machinereadable"])
+            .await?;
+        Ok(info
+            .lines()
+            .filter_map(|l| l.split_once('='))
+            .map(|(_, value)| value.trim_matches('"'))
+            .filter(|value| {
+                let value = value.to_lowercase();
+                DISK_EXTENSIONS.iter().any(|e| value.ends_with(e))
+            })
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    async fn start(&self, vm: &str) -> Result<()> {
+        if self.state(vm).await? != "running" {
+            self.vboxmanage(&["startvm", vm, "--type", "headless"])
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn stop(&self, vm: &str) -> Result<()> {
+        match self.state(vm).await?.as_str() {
+            "running" | "paused" | "stuck" => {
+                self.vboxmanage(&["controlvm", vm, "poweroff"]).await?;
+            }
+            _ => return Ok(()),
+        }
+
+        for _ in 0..POWEROFF_POLLS {
+            if self.state(vm).await? == "poweroff" {
+                return Ok(());
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+        bail!("{} did not power off", vm)
+    }
+
+    async fn apply_limits(&self, vm: &str, limits: &ResourceLimits) -> Result<()> {
+        let cpus = limits.cpus.map(|c| c.to_string());
+        let memory = limits.memory_mb.map(|m| m.to_string());
+        let cap = limits.cpu_cap.map(|c| c.to_string());
+        let mut args = vec!["modifyvm", vm];
+        for (option, value) in [
+            ("--cpus", &cpus),
+            ("--memory", &memory),
+            ("--cpuexecutioncap", &cap),
+        ] {
+            if let Some(value) = value {
+                args.extend([option, value.as_str()]);
+            }
+        }
+        if args.len() > 2 {
+            self.vboxmanage(&args).await?;
+        }
+        if limits.memory_mb.is_some() || limits.cpu_saturation_secs.is_some() {
+            self.vboxmanage(&["metrics", "setup", "--period", "1", "--samples", "1", vm])
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn usage(&self, vm: &str) -> Result<ResourceUsage> {
+        let output = self
+            .vboxmanage(&["metrics", "query", vm, USAGE_METRICS])
+            .await?;
+        let mut values = HashMap::new();
+        for line in output.lines() {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let Some(index) = columns.iter().position(|c| c.starts_with("Guest/")) else {
+                continue;
+            };
+            let value = columns
+                .get(index + 1)
+                .map(|v| v.trim_end_matches('%'))
+                .and_then(|v| v.parse::<f64>().ok());
+            if let Some(value) = value {
+                values.insert(columns[index], value);
+            }
+        }
+        let cpu_percent = match (
+            values.get("Guest/CPU/Load/User"),
+            values.get("Guest/CPU/Load/Kernel"),
+        ) {
+            (Some(user), Some(kernel)) => Some(user + kernel),
+            (user, kernel) => user.or(kernel).copied(),
+        };
+        let memory_mb = match (
+            values.get("Guest/RAM/Usage/Total"),
+            values.get("Guest/RAM/Usage/Free"),
+        ) {
+            (Some(total), Some(free)) => Some(((total - free).max(0.0) / 1024.0) as u64),
+            _ => None,
+        };
+        Ok(ResourceUsage {
+            cpu_percent,
+            memory_mb,
+        })
+    }
+
+    async fn guest_address(&self, vm: &str) -> Result<IpAddr> {
+        let output = self
+            .vboxmanage(&["guestproperty", "get", vm, GUEST_IP_PROPERTY])
+            .await?;
+        output
+            .trim()
+            .strip_prefix("Value:")
+            .context("Guest has no IPv4 address yet")?
+            .trim()
+            .parse()
+            .context("Invalid guest IPv4 address")
+    }
+}
@@ -0,0 +1,74 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+
+use crate::vmi::VmiConfig;
+
+const VM_PLACEHOLDER: &str = "{vm}";
+const SAMPLE_PLACEHOLDER: &str = "{sample}";
+
+fn command(template: &[String], vm: &str, sample: Option<&Path>) -> Result<Command> {
+    let sample = sample.map(|p| p.to_string_lossy().to_string());
+    let args: Vec<String> = template
+        .iter()
+        .map(|arg| {
+            let arg = arg.replace(VM_PLACEHOLDER, vm);
+            match &sample {
+                Some(sample) => arg.replace(SAMPLE_PLACEHOLDER, sample),
+                None => arg,
+            }
+        })
+        .collect();
+    let (program, args) = args.split_first().context("Empty introspection command")?;
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok(command)
+}
+
+pub struct VmiSession {
+    child: Child,
+    output: JoinHandle<std::io::Result<Vec<u8>>>,
+}
+
+impl VmiSession {
+    pub fn start(config: &VmiConfig, vm: &str) -> Result<Self> {
+        let mut child = command(&config.trace_command, vm, None)?
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start introspection of {}", vm))?;
+        let mut stdout = child
+            .stdout
+            .take()
+            .context("Introspection backend has no output")?;
+        let output = tokio::spawn(async move {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).await?;
+            Ok(output)
+        });
+        Ok(Self { child, output })
+    }
+
+    pub async fn inject(config: &VmiConfig, vm: &str, sample: &Path) -> Result<()> {
+        let status = command(&config.inject_command, vm, Some(sample))?
+            .status()
+            .await?;
+        if !status.success() {
+            bail!("Injecting sample into {} failed with {}", vm, status);
+        }
+        Ok(())
+    }
+
+    pub async fn stop(mut self) -> Result<Vec<u8>> {
+        if let Err(e) = self.child.start_kill() {
+            warn!("Failed to stop introspection backend: {}", e);
+        }
+        self.child.wait().await?;
+        Ok(self.output.await??)
+    }
+}
@@ -0,0 +1,46 @@
+const ENV_VARS: &[(&str, &str)] = &[
+    ("%systemroot%", r"c:\windows"),
+    ("%windir%", r"c:\windows"),
+    ("%systemdrive%", "c:"),
+    ("%programdata%", r"c:\programdata"),
+    ("%programfiles%", r"c:\program files"),
+    ("%programfiles(x86)%", r"c:\program files (x86)"),
+    ("%public%", r"c:\users\public"),
+];
+
+const DEVICE_PREFIXES: &[&str] = &[r"\\?\unc\", r"\\?\", r"\??\", r"\\.\"];
+
+pub fn normalize(path: &str) -> String {
+    let mut normalized = path
+        .trim()
+        .trim_matches('"')
+        .to_lowercase()
+        .replace('/', r"\");
+
+    for prefix in DEVICE_PREFIXES {
+        if let Some(stripped) = normalized.strip_prefix(prefix) {
+            normalized = if *prefix == r"\\?\unc\" {
+                format!(r"\\{}", stripped)
+            } else {
+                stripped.to_string()
+            };
+            break;
+        }
+    }
+
+    for (var, value) in ENV_VARS {
+        if normalized.starts_with(var) {
+            normalized = format!("{}{}", value, &normalized[var.len()..]);
+            break;
+        }
+    }
+
+    let name_start = normalized.rfind('\\').map_or(0, |i| i + 1);
+    if let Some(i) = normalized[name_start..].find(':') {
+        if name_start > 0 || i > 1 {
+            normalized.truncate(name_start + i);
+        }
+    }
+
+    normalized
+}
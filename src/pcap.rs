@@ -0,0 +1,412 @@
+pub mod decode;
+pub mod dns;
+pub mod fingerprint;
+pub mod http;
+pub mod ids;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::process::{Child, Command};
+
+use crate::network::NetworkConnect;
+use crate::sysmon_event::SysmonEvent;
+use decode::{decode, http_host, http_request_line, preview, tls_sni, Transport, TCP_ACK, TCP_SYN};
+use dns::{parse_message, DnsMessage, DNS_PORT};
+
+pub const PCAP_FILE_NAME: &str = "traffic.pcap";
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_OPTION_TSRESOL: u16 = 9;
+const PREVIEW_LEN: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub time: DateTime<Utc>,
+    pub link_type: u32,
+    pub data: Vec<u8>,
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Cursor<'a> {
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes: [u8; 2] = self
+            .data
+            .get(offset..offset + 2)
+            .context("Truncated capture")?
+            .try_into()?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes: [u8; 4] = self
+            .data
+            .get(offset..offset + 4)
+            .context("Truncated capture")?
+            .try_into()?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        self.data
+            .get(offset..offset + len)
+            .context("Truncated capture")
+    }
+}
+
+fn timestamp(ticks: u64, ticks_per_sec: u64) -> DateTime<Utc> {
+    let secs = (ticks / ticks_per_sec) as i64;
+    let nanos = ((ticks % ticks_per_sec) * 1_000_000_000 / ticks_per_sec) as u32;
+    Utc.timestamp_opt(secs, nanos).single().unwrap_or_default()
+}
+
+fn parse_classic(data: &[u8], big_endian: bool, ticks_per_sec: u64) -> Result<Vec<Packet>> {
+    let cursor = Cursor { data, big_endian };
+    let link_type = cursor.u32(20)? & 0x0fffffff;
+
+    let mut packets = Vec::new();
+    let mut offset = 24;
+    while offset + 16 <= data.len() {
+        let secs = u64::from(cursor.u32(offset)?);
+        let frac = u64::from(cursor.u32(offset + 4)?);
+        let captured_len = cursor.u32(offset + 8)? as usize;
+        packets.push(Packet {
+            time: timestamp(secs * ticks_per_sec + frac, ticks_per_sec),
+            link_type,
+            data: cursor.bytes(offset + 16, captured_len)?.to_vec(),
+        });
+        offset += 16 + captured_len;
+    }
+    Ok(packets)
+}
+
+fn tsresol(cursor: &Cursor, mut offset: usize, end: usize) -> Result<u64> {
+    while offset + 4 <= end {
+        let code = cursor.u16(offset)?;
+        let len = usize::from(cursor.u16(offset + 2)?);
+        if code == 0 {
+            break;
+        }
+        if code == PCAPNG_OPTION_TSRESOL && len == 1 {
+            let resol = cursor.bytes(offset + 4, 1)?[0];
+            let exponent = u32::from(resol & 0x7f);
+            return Ok(if resol & 0x80 == 0 {
+                10u64.checked_pow(exponent).context("Invalid if_tsresol")?
+            } else {
+                1u64.checked_shl(exponent).context("Invalid if_tsresol")?
+            });
+        }
+        offset += 4 + len.div_ceil(4) * 4;
+    }
+    Ok(1_000_000)
+}
+
+fn parse_pcapng(data: &[u8]) -> Result<Vec<Packet>> {
+    let mut cursor = Cursor {
+        data,
+        big_endian: false,
+    };
+    let mut interfaces: Vec<(u32, u64)> = Vec::new();
+    let mut packets = Vec::new();
+    let mut offset = 0;
+
+    while offset + 12 <= data.len() {
+        let block_type = cursor.u32(offset)?;
+        if block_type == PCAPNG_SECTION_HEADER {
+            let magic = u32::from_le_bytes(cursor.bytes(offset + 8, 4)?.try_into()?);
+            cursor.big_endian = magic != PCAPNG_BYTE_ORDER_MAGIC;
+            interfaces.clear();
+        }
+        let block_len = cursor.u32(offset + 4)? as usize;
+        if block_len < 12 || offset + block_len > data.len() {
+            bail!("Invalid pcapng block length {}", block_len);
+        }
+
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => {
+                let link_type = u32::from(cursor.u16(offset + 8)?);
+                let resolution = tsresol(&cursor, offset + 16, offset + block_len - 4)?;
+                interfaces.push((link_type, resolution));
+            }
+            PCAPNG_ENHANCED_PACKET => {
+                let interface = cursor.u32(offset + 8)? as usize;
+                let &(link_type, resolution) = interfaces
+                    .get(interface)
+                    .context("Packet references unknown interface")?;
+                let ticks = (u64::from(cursor.u32(offset + 12)?) << 32)
+                    | u64::from(cursor.u32(offset + 16)?);
+                let captured_len = cursor.u32(offset + 20)? as usize;
+                packets.push(Packet {
+                    time: timestamp(ticks, resolution),
+                    link_type,
+                    data: cursor.bytes(offset + 28, captured_len)?.to_vec(),
+                });
+            }
+            _ => (),
+        }
+        offset += block_len;
+    }
+    Ok(packets)
+}
+
+pub fn parse(data: &[u8]) -> Result<Vec<Packet>> {
+    let magic: [u8; 4] = data.get(..4).context("Not a capture file")?.try_into()?;
+    match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+        (PCAP_MAGIC_MICROS, _) => parse_classic(data, false, 1_000_000),
+        (_, PCAP_MAGIC_MICROS) => parse_classic(data, true, 1_000_000),
+        (PCAP_MAGIC_NANOS, _) => parse_classic(data, false, 1_000_000_000),
+        (_, PCAP_MAGIC_NANOS) => parse_classic(data, true, 1_000_000_000),
+        (PCAPNG_SECTION_HEADER, _) => parse_pcapng(data),
+        _ => bail!("Unknown capture file format"),
+    }
+}
+
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<Packet>> {
+    parse(&std::fs::read(path)?)
+}
+
+pub fn dns_responses(packets: &[Packet]) -> Vec<(DateTime<Utc>, SocketAddr, DnsMessage)> {
+    packets
+        .iter()
+        .filter_map(|packet| {
+            let segment = decode(packet.link_type, &packet.data)?;
+            if segment.transport != Transport::Udp || segment.source.port() != DNS_PORT {
+                return None;
+            }
+            let message = parse_message(segment.payload).filter(|m| m.response)?;
+            Some((packet.time, segment.destination, message))
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Flow {
+    pub transport: Transport,
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub packets: usize,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub sni: Option<String>,
+    pub http_host: Option<String>,
+    pub http_requests: Vec<String>,
+    pub payload_preview: Option<String>,
+    pub ja3: Option<String>,
+    pub ja3s: Option<String>,
+    pub hassh: Option<String>,
+    pub hassh_server: Option<String>,
+}
+
+impl Flow {
+    fn matches(&self, transport: Transport, a: SocketAddr, b: SocketAddr) -> bool {
+        self.transport == transport
+            && ((self.client == a && self.server == b) || (self.client == b && self.server == a))
+    }
+}
+
+pub fn flows(packets: &[Packet]) -> Vec<Flow> {
+    let mut flows: Vec<Flow> = Vec::new();
+    let mut index: HashMap<(Transport, SocketAddr, SocketAddr), usize> = HashMap::new();
+
+    for packet in packets {
+        let Some(segment) = decode(packet.link_type, &packet.data) else {
+            continue;
+        };
+        let key = if segment.source <= segment.destination {
+            (segment.transport, segment.source, segment.destination)
+        } else {
+            (segment.transport, segment.destination, segment.source)
+        };
+        let i = *index.entry(key).or_insert_with(|| {
+            let syn_ack = segment.tcp_flags & (TCP_SYN | TCP_ACK) == TCP_SYN | TCP_ACK;
+            let (client, server) = if syn_ack {
+                (segment.destination, segment.source)
+            } else {
+                (segment.source, segment.destination)
+            };
+            flows.push(Flow {
+                transport: segment.transport,
+                client,
+                server,
+                first_seen: packet.time,
+                last_seen: packet.time,
+                packets: 0,
+                bytes_sent: 0,
+                bytes_received: 0,
+                sni: None,
+                http_host: None,
+                http_requests: Vec::new(),
+                payload_preview: None,
+                ja3: None,
+                ja3s: None,
+                hassh: None,
+                hassh_server: None,
+            });
+            flows.len() - 1
+        });
+
+        let flow = &mut flows[i];
+        flow.packets += 1;
+        flow.first_seen = flow.first_seen.min(packet.time);
+        flow.last_seen = flow.last_seen.max(packet.time);
+        let payload = segment.payload;
+        if segment.source != flow.client {
+            flow.bytes_received += payload.len() as u64;
+            if flow.ja3s.is_none() {
+                flow.ja3s = fingerprint::ja3s(payload);
+            }
+            if flow.hassh_server.is_none() {
+                flow.hassh_server = fingerprint::hassh_server(payload);
+            }
+            continue;
+        }
+        flow.bytes_sent += payload.len() as u64;
+        if payload.is_empty() {
+            continue;
+        }
+        if flow.payload_preview.is_none() {
+            flow.payload_preview = Some(preview(payload, PREVIEW_LEN));
+        }
+        if flow.sni.is_none() {
+            flow.sni = tls_sni(payload);
+        }
+        if flow.ja3.is_none() {
+            flow.ja3 = fingerprint::ja3(payload);
+        }
+        if flow.hassh.is_none() {
+            flow.hassh = fingerprint::hassh(payload);
+        }
+        if let Some(line) = http_request_line(payload) {
+            flow.http_requests.push(line);
+            if flow.http_host.is_none() {
+                flow.http_host = http_host(payload);
+            }
+        }
+    }
+    flows
+}
+
+pub(crate) fn distance(a: DateTime<Utc>, b: DateTime<Utc>) -> Duration {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CorrelatedFlow {
+    pub process_guid: Option<String>,
+    pub image: Option<String>,
+    pub flow: Flow,
+}
+
+pub(crate) fn connect_matches(flow: &Flow, connect: &NetworkConnect) -> bool {
+    let transport = match connect.protocol.to_lowercase().as_str() {
+        "tcp" => Transport::Tcp,
+        "udp" => Transport::Udp,
+        _ => return false,
+    };
+    let endpoints = (
+        connect.source_ip,
+        connect.source_port,
+        connect.destination_ip,
+        connect.destination_port,
+    );
+    let (Some(sip), Some(sport), Some(dip), Some(dport)) = endpoints else {
+        return false;
+    };
+    flow.matches(
+        transport,
+        SocketAddr::new(sip, sport),
+        SocketAddr::new(dip, dport),
+    )
+}
+
+pub fn correlate(
+    events: &[SysmonEvent],
+    flows: &[Flow],
+    tolerance: Duration,
+    offset: Duration,
+) -> Vec<CorrelatedFlow> {
+    let connects: Vec<(DateTime<Utc>, NetworkConnect)> = events
+        .iter()
+        .filter_map(|e| {
+            Some((
+                e.time_created.with_timezone(&Utc) + offset,
+                NetworkConnect::from_event(e)?,
+            ))
+        })
+        .collect();
+
+    flows
+        .iter()
+        .map(|flow| {
+            let connect = connects
+                .iter()
+                .filter(|(time, c)| {
+                    connect_matches(flow, c) && distance(*time, flow.first_seen) <= tolerance
+                })
+                .min_by_key(|(time, _)| distance(*time, flow.first_seen))
+                .map(|(_, c)| c);
+            CorrelatedFlow {
+                process_guid: connect.and_then(|c| c.process_guid.clone()),
+                image: connect.map(|c| c.image.clone()),
+                flow: flow.clone(),
+            }
+        })
+        .collect()
+}
+
+pub struct Capture {
+    child: Child,
+}
+
+impl Capture {
+    pub fn start<P: AsRef<Path>>(interface: &str, path: P, filter: Option<&str>) -> Result<Self> {
+        let mut command = Command::new("tcpdump");
+        command
+            .args(["-i", interface, "-U", "-n", "-w"])
+            .arg(path.as_ref())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(filter) = filter {
+            command.arg(filter);
+        }
+        let child = command
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start tcpdump on {}: {}", interface, e))?;
+        Ok(Self { child })
+    }
+
+    pub async fn stop(mut self) -> Result<()> {
+        self.child.kill().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use serde::{Deserialize, Serialize};
+
+pub const LINKTYPE_NULL: u32 = 0;
+pub const LINKTYPE_ETHERNET: u32 = 1;
+pub const LINKTYPE_RAW: u32 = 101;
+pub const LINKTYPE_LINUX_SLL: u32 = 113;
+pub const LINKTYPE_IPV4: u32 = 228;
+pub const LINKTYPE_IPV6: u32 = 229;
+pub const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+pub const TCP_FIN: u8 = 0x01;
+pub const TCP_SYN: u8 = 0x02;
+pub const TCP_RST: u8 = 0x04;
+pub const TCP_ACK: u8 = 0x10;
+
+const HTTP_METHODS: &[&str] = &[
+    "GET", "POST", "PUT", "HEAD", "DELETE", "OPTIONS", "PATCH", "CONNECT", "TRACE",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl Transport {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Tcp => "tcp",
+            Self::Udp => "udp",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Segment<'a> {
+    pub transport: Transport,
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub tcp_flags: u8,
+    pub seq: u32,
+    pub payload: &'a [u8],
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn ip_payload(link_type: u32, data: &[u8]) -> Option<&[u8]> {
+    let (ethertype, offset) = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = u16_at(data, 12)?;
+            let mut offset = 14;
+            while ethertype == ETHERTYPE_VLAN {
+                ethertype = u16_at(data, offset + 2)?;
+                offset += 4;
+            }
+            (ethertype, offset)
+        }
+        LINKTYPE_LINUX_SLL => (u16_at(data, 14)?, 16),
+        LINKTYPE_LINUX_SLL2 => (u16_at(data, 0)?, 20),
+        LINKTYPE_NULL => return data.get(4..),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => return Some(data),
+        _ => return None,
+    };
+    match ethertype {
+        ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => data.get(offset..),
+        _ => None,
+    }
+}
+
+pub fn decode(link_type: u32, data: &[u8]) -> Option<Segment<'_>> {
+    let ip = ip_payload(link_type, data)?;
+    let (protocol, source, destination, transport) = match ip.first()? >> 4 {
+        4 => {
+            let header_len = usize::from(ip[0] & 0x0f) * 4;
+            let total_len = usize::from(u16_at(ip, 2)?);
+            let fragment_offset = u16_at(ip, 6)? & 0x1fff;
+            if header_len < 20 || fragment_offset != 0 {
+                return None;
+            }
+            let source: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let destination: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            let end = total_len.clamp(header_len, ip.len());
+            (
+                *ip.get(9)?,
+                IpAddr::V4(Ipv4Addr::from(source)),
+                IpAddr::V4(Ipv4Addr::from(destination)),
+                ip.get(header_len..end)?,
+            )
+        }
+        6 => {
+            let payload_len = usize::from(u16_at(ip, 4)?);
+            let source: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let destination: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            let end = (40 + payload_len).min(ip.len());
+            (
+                *ip.get(6)?,
+                IpAddr::V6(Ipv6Addr::from(source)),
+                IpAddr::V6(Ipv6Addr::from(destination)),
+                ip.get(40..end)?,
+            )
+        }
+        _ => return None,
+    };
+
+    match protocol {
+        6 => {
+            let data_offset = usize::from(transport.get(12)? >> 4) * 4;
+            Some(Segment {
+                transport: Transport::Tcp,
+                source: SocketAddr::new(source, u16_at(transport, 0)?),
+                destination: SocketAddr::new(destination, u16_at(transport, 2)?),
+                tcp_flags: *transport.get(13)?,
+                seq: u32_at(transport, 4)?,
+                payload: transport.get(data_offset.max(20)..)?,
+            })
+        }
+        17 => Some(Segment {
+            transport: Transport::Udp,
+            source: SocketAddr::new(source, u16_at(transport, 0)?),
+            destination: SocketAddr::new(destination, u16_at(transport, 2)?),
+            tcp_flags: 0,
+            seq: 0,
+            payload: transport.get(8..)?,
+        }),
+        _ => None,
+    }
+}
+
+pub fn tls_sni(payload: &[u8]) -> Option<String> {
+    if *payload.first()? != 0x16 || *payload.get(5)? != 0x01 {
+        return None;
+    }
+    let mut offset = 5 + 4 + 2 + 32;
+    offset += 1 + usize::from(*payload.get(offset)?);
+    offset += 2 + usize::from(u16_at(payload, offset)?);
+    offset += 1 + usize::from(*payload.get(offset)?);
+    let extensions_end = offset + 2 + usize::from(u16_at(payload, offset)?);
+    offset += 2;
+
+    while offset + 4 <= extensions_end {
+        let extension_type = u16_at(payload, offset)?;
+        let extension_len = usize::from(u16_at(payload, offset + 2)?);
+        offset += 4;
+        if extension_type == 0 {
+            let name_type = *payload.get(offset + 2)?;
+            let name_len = usize::from(u16_at(payload, offset + 3)?);
+            if name_type != 0 {
+                return None;
+            }
+            let name = payload.get(offset + 5..offset + 5 + name_len)?;
+            return String::from_utf8(name.to_vec()).ok();
+        }
+        offset += extension_len;
+    }
+    None
+}
+
+pub fn http_request_line(payload: &[u8]) -> Option<String> {
+    let end = payload.windows(2).position(|w| w == b"\r\n")?;
+    let line = std::str::from_utf8(&payload[..end]).ok()?;
+    let (method, rest) = line.split_once(' ')?;
+    if !HTTP_METHODS.contains(&method) || !rest.contains("HTTP/") {
+        return None;
+    }
+    Some(line.to_string())
+}
+
+pub fn http_host(payload: &[u8]) -> Option<String> {
+    let header_end = payload
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap_or(payload.len());
+    let headers = String::from_utf8_lossy(&payload[..header_end]);
+    headers.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("host")
+            .then(|| value.trim().to_string())
+    })
+}
+
+pub fn preview(payload: &[u8], len: usize) -> String {
+    payload
+        .iter()
+        .take(len)
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,156 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+pub const DNS_PORT: u16 = 53;
+
+const MAX_POINTERS: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub record_type: u16,
+    pub ttl: u32,
+    pub data: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub response: bool,
+    pub rcode: u8,
+    pub questions: Vec<(String, u16)>,
+    pub answers: Vec<DnsRecord>,
+}
+
+pub fn record_type_name(record_type: u16) -> String {
+    match record_type {
+        1 => "A".to_string(),
+        2 => "NS".to_string(),
+        5 => "CNAME".to_string(),
+        6 => "SOA".to_string(),
+        12 => "PTR".to_string(),
+        15 => "MX".to_string(),
+        16 => "TXT".to_string(),
+        28 => "AAAA".to_string(),
+        33 => "SRV".to_string(),
+        65 => "HTTPS".to_string(),
+        t => format!("TYPE{}", t),
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn name_at(message: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..MAX_POINTERS {
+        loop {
+            let len = *message.get(offset)?;
+            if len & 0xc0 == 0xc0 {
+                let pointer = usize::from(u16_at(message, offset)? & 0x3fff);
+                end.get_or_insert(offset + 2);
+                offset = pointer;
+                break;
+            }
+            if len == 0 {
+                let end = end.unwrap_or(offset + 1);
+                return Some((labels.join("."), end));
+            }
+            let label = message.get(offset + 1..offset + 1 + usize::from(len))?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            offset += 1 + usize::from(len);
+        }
+    }
+    None
+}
+
+fn record_data(message: &[u8], record_type: u16, offset: usize, len: usize) -> Option<String> {
+    let data = message.get(offset..offset + len)?;
+    match record_type {
+        1 => {
+            let octets: [u8; 4] = data.try_into().ok()?;
+            Some(Ipv4Addr::from(octets).to_string())
+        }
+        28 => {
+            let octets: [u8; 16] = data.try_into().ok()?;
+            Some(Ipv6Addr::from(octets).to_string())
+        }
+        2 | 5 | 12 => name_at(message, offset).map(|(name, _)| name),
+        15 => name_at(message, offset + 2).map(|(name, _)| name),
+        16 => Some(String::from_utf8_lossy(data.get(1..)?).into_owned()),
+        _ => Some(data.iter().map(|b| format!("{:02x}", b)).collect()),
+    }
+}
+
+pub fn parse_message(message: &[u8]) -> Option<DnsMessage> {
+    let flags = u16_at(message, 2)?;
+    let question_count = u16_at(message, 4)?;
+    let answer_count = u16_at(message, 6)?;
+
+    let mut offset = 12;
+    let mut questions = Vec::new();
+    for _ in 0..question_count {
+        let (name, end) = name_at(message, offset)?;
+        questions.push((name, u16_at(message, end)?));
+        offset = end + 4;
+    }
+
+    let mut answers = Vec::new();
+    for _ in 0..answer_count {
+        let (name, end) = name_at(message, offset)?;
+        let record_type = u16_at(message, end)?;
+        let ttl = u32_at(message, end + 4)?;
+        let len = usize::from(u16_at(message, end + 8)?);
+        let data_offset = end + 10;
+        if let Some(data) = record_data(message, record_type, data_offset, len) {
+            answers.push(DnsRecord {
+                name,
+                record_type,
+                ttl,
+                data,
+            });
+        }
+        offset = data_offset + len;
+    }
+
+    Some(DnsMessage {
+        id: u16_at(message, 0)?,
+        response: flags & 0x8000 != 0,
+        rcode: (flags & 0x000f) as u8,
+        questions,
+        answers,
+    })
+}
+
+pub fn spoofed_response(query: &[u8], address: Option<Ipv4Addr>, ttl: u32) -> Option<Vec<u8>> {
+    let message = parse_message(query)?;
+    let (_, query_type) = message.questions.first()?;
+    let (_, name_end) = name_at(query, 12)?;
+    let question_end = name_end + 4;
+
+    let answer = address.filter(|_| *query_type == 1 || *query_type == 255);
+    let flags: u16 = 0x8080 | (u16_at(query, 2)? & 0x0100);
+    let mut response = Vec::with_capacity(question_end + 16);
+    response.extend_from_slice(&message.id.to_be_bytes());
+    response.extend_from_slice(&flags.to_be_bytes());
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&u16::from(answer.is_some()).to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0]);
+    response.extend_from_slice(query.get(12..question_end)?);
+    if let Some(address) = answer {
+        response.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1]);
+        response.extend_from_slice(&ttl.to_be_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&address.octets());
+    }
+    Some(response)
+}
@@ -0,0 +1,179 @@
+use itertools::Itertools;
+use md5::{Digest, Md5};
+
+const TLS_HANDSHAKE: u8 = 0x16;
+const CLIENT_HELLO: u8 = 0x01;
+const SERVER_HELLO: u8 = 0x02;
+const EXTENSION_SUPPORTED_GROUPS: u16 = 0x000a;
+const EXTENSION_EC_POINT_FORMATS: u16 = 0x000b;
+const SSH_MSG_KEXINIT: u8 = 20;
+const SSH_COOKIE_LEN: usize = 16;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn md5_hex(s: &str) -> String {
+    format!("{:x}", Md5::digest(s))
+}
+
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn join(values: &[u16]) -> String {
+    values
+        .iter()
+        .filter(|v| !is_grease(**v))
+        .map(u16::to_string)
+        .join("-")
+}
+
+fn u16_list(data: &[u8], offset: usize, len: usize) -> Option<Vec<u16>> {
+    let list = data.get(offset..offset + len)?;
+    Some(
+        list.chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect(),
+    )
+}
+
+fn handshake(payload: &[u8], kind: u8) -> Option<&[u8]> {
+    if *payload.first()? != TLS_HANDSHAKE || *payload.get(5)? != kind {
+        return None;
+    }
+    payload.get(9..)
+}
+
+fn extensions(body: &[u8], mut offset: usize) -> Vec<(u16, &[u8])> {
+    let mut extensions = Vec::new();
+    let Some(len) = u16_at(body, offset) else {
+        return extensions;
+    };
+    let end = (offset + 2 + usize::from(len)).min(body.len());
+    offset += 2;
+    while offset + 4 <= end {
+        let (Some(kind), Some(len)) = (u16_at(body, offset), u16_at(body, offset + 2)) else {
+            break;
+        };
+        let Some(data) = body.get(offset + 4..offset + 4 + usize::from(len)) else {
+            break;
+        };
+        extensions.push((kind, data));
+        offset += 4 + usize::from(len);
+    }
+    extensions
+}
+
+pub fn ja3_string(payload: &[u8]) -> Option<String> {
+    let body = handshake(payload, CLIENT_HELLO)?;
+    let version = u16_at(body, 0)?;
+    let mut offset = 2 + 32;
+    offset += 1 + usize::from(*body.get(offset)?);
+    let cipher_len = usize::from(u16_at(body, offset)?);
+    let ciphers = u16_list(body, offset + 2, cipher_len)?;
+    offset += 2 + cipher_len;
+    offset += 1 + usize::from(*body.get(offset)?);
+
+    let extensions = extensions(body, offset);
+    let mut groups = Vec::new();
+    let mut point_formats = Vec::new();
+    for (kind, data) in &extensions {
+        match *kind {
+            EXTENSION_SUPPORTED_GROUPS => {
+                let len = usize::from(u16_at(data, 0).unwrap_or_default());
+                groups = u16_list(data, 2, len).unwrap_or_default();
+            }
+            EXTENSION_EC_POINT_FORMATS => {
+                let len = usize::from(data.first().copied().unwrap_or_default());
+                point_formats = data.get(1..1 + len).unwrap_or_default().to_vec();
+            }
+            _ => (),
+        }
+    }
+    let kinds: Vec<u16> = extensions.iter().map(|(kind, _)| *kind).collect();
+    Some(format!(
+        "{},{},{},{},{}",
+        version,
+        join(&ciphers),
+        join(&kinds),
+        join(&groups),
+        point_formats.iter().join("-")
+    ))
+}
+
+pub fn ja3(payload: &[u8]) -> Option<String> {
+    ja3_string(payload).map(|s| md5_hex(&s))
+}
+
+pub fn ja3s_string(payload: &[u8]) -> Option<String> {
+    let body = handshake(payload, SERVER_HELLO)?;
+    let version = u16_at(body, 0)?;
+    let mut offset = 2 + 32;
+    offset += 1 + usize::from(*body.get(offset)?);
+    let cipher = u16_at(body, offset)?;
+    offset += 2 + 1;
+    let kinds: Vec<u16> = extensions(body, offset)
+        .iter()
+        .map(|(kind, _)| *kind)
+        .collect();
+    Some(format!("{},{},{}", version, cipher, join(&kinds)))
+}
+
+pub fn ja3s(payload: &[u8]) -> Option<String> {
+    ja3s_string(payload).map(|s| md5_hex(&s))
+}
+
+fn name_lists(payload: &[u8]) -> Option<Vec<String>> {
+    let mut packet = payload;
+    if packet.starts_with(b"SSH-") {
+        let end = packet.iter().position(|&b| b == b'\n')?;
+        packet = &packet[end + 1..];
+    }
+    let padding = usize::from(*packet.get(4)?);
+    let packet_len = u32_at(packet, 0)? as usize;
+    if *packet.get(5)? != SSH_MSG_KEXINIT || padding >= packet_len {
+        return None;
+    }
+    let mut offset = 6 + SSH_COOKIE_LEN;
+    let mut lists = Vec::new();
+    for _ in 0..8 {
+        let len = u32_at(packet, offset)? as usize;
+        let list = packet.get(offset + 4..offset + 4 + len)?;
+        lists.push(String::from_utf8_lossy(list).into_owned());
+        offset += 4 + len;
+    }
+    Some(lists)
+}
+
+pub fn hassh_string(payload: &[u8]) -> Option<String> {
+    let lists = name_lists(payload)?;
+    Some(format!(
+        "{};{};{};{}",
+        lists[0], lists[2], lists[4], lists[6]
+    ))
+}
+
+pub fn hassh(payload: &[u8]) -> Option<String> {
+    hassh_string(payload).map(|s| md5_hex(&s))
+}
+
+pub fn hassh_server_string(payload: &[u8]) -> Option<String> {
+    let lists = name_lists(payload)?;
+    Some(format!(
+        "{};{};{};{}",
+        lists[0], lists[3], lists[5], lists[7]
+    ))
+}
+
+pub fn hassh_server(payload: &[u8]) -> Option<String> {
+    hassh_server_string(payload).map(|s| md5_hex(&s))
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::decode::{decode, http_request_line, preview, Transport};
+use super::Packet;
+
+const MAX_STREAM_LEN: usize = 4 * 1024 * 1024;
+const BODY_PREVIEW_LEN: usize = 256;
+
+type Head = (String, Vec<(String, String)>, usize);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub uri: String,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    pub body_len: usize,
+    pub body_preview: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body_len: usize,
+    pub body_preview: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpExchange {
+    pub time: DateTime<Utc>,
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+    pub host: Option<String>,
+    pub request: HttpRequest,
+    pub response: Option<HttpResponse>,
+}
+
+impl HttpExchange {
+    pub fn url(&self) -> String {
+        if self.request.uri.contains("://") {
+            return self.request.uri.clone();
+        }
+        let host = self.host.clone().unwrap_or_else(|| self.server.to_string());
+        format!("http://{}{}", host, self.request.uri)
+    }
+}
+
+pub(crate) fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+#[derive(Default)]
+struct Stream<'a> {
+    segments: Vec<(u32, DateTime<Utc>, &'a [u8])>,
+}
+
+impl Stream<'_> {
+    fn reassemble(&self) -> (Vec<u8>, Vec<(usize, DateTime<Utc>)>) {
+        let mut data = Vec::new();
+        let mut marks = Vec::new();
+        let Some(&(first, _, _)) = self.segments.first() else {
+            return (data, marks);
+        };
+        let mut ordered: Vec<(i64, DateTime<Utc>, &[u8])> = self
+            .segments
+            .iter()
+            .map(|&(seq, time, payload)| (i64::from(seq.wrapping_sub(first) as i32), time, payload))
+            .collect();
+        ordered.sort_by_key(|(offset, time, _)| (*offset, *time));
+        let start = ordered[0].0;
+        for (offset, time, payload) in ordered {
+            let offset = (offset - start) as usize;
+            if offset > data.len() || data.len() >= MAX_STREAM_LEN {
+                break;
+            }
+            let overlap = data.len() - offset;
+            if overlap < payload.len() {
+                marks.push((data.len(), time));
+                data.extend_from_slice(&payload[overlap..]);
+            }
+        }
+        (data, marks)
+    }
+}
+
+fn time_at(marks: &[(usize, DateTime<Utc>)], offset: usize) -> Option<DateTime<Utc>> {
+    marks
+        .iter()
+        .take_while(|(start, _)| *start <= offset)
+        .last()
+        .map(|(_, time)| *time)
+}
+
+pub(crate) fn parse_head(data: &[u8]) -> Option<Head> {
+    let end = data.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&data[..end]);
+    let mut lines = head.lines();
+    let first = lines.next()?.to_string();
+    let headers = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    Some((first, headers, end + 4))
+}
+
+fn chunked_body(data: &[u8]) -> (Vec<u8>, usize) {
+    let mut body = Vec::new();
+    let mut offset = 0;
+    while let Some(end) = data[offset..].windows(2).position(|w| w == b"\r\n") {
+        let line = String::from_utf8_lossy(&data[offset..offset + end]);
+        let Ok(size) = usize::from_str_radix(line.split(';').next().unwrap_or("").trim(), 16)
+        else {
+            break;
+        };
+        offset += end + 2;
+        if size == 0 {
+            if data[offset..].starts_with(b"\r\n") {
+                offset += 2;
+            }
+            break;
+        }
+        let chunk_end = offset.saturating_add(size).min(data.len());
+        body.extend_from_slice(&data[offset..chunk_end]);
+        offset = (chunk_end + 2).min(data.len());
+    }
+    (body, offset)
+}
+
+pub(crate) fn read_body(
+    data: &[u8],
+    headers: &[(String, String)],
+    until_close: bool,
+) -> (Vec<u8>, usize) {
+    if header(headers, "transfer-encoding").is_some_and(|v| v.to_lowercase().contains("chunked")) {
+        return chunked_body(data);
+    }
+    let len = match header(headers, "content-length").and_then(|v| v.parse::<usize>().ok()) {
+        Some(len) => len.min(data.len()),
+        None if until_close => data.len(),
+        None => 0,
+    };
+    (data[..len].to_vec(), len)
+}
+
+fn body_preview(body: &[u8]) -> Option<String> {
+    (!body.is_empty()).then(|| preview(body, BODY_PREVIEW_LEN))
+}
+
+fn parse_requests(
+    data: &[u8],
+    marks: &[(usize, DateTime<Utc>)],
+) -> Vec<(DateTime<Utc>, HttpRequest)> {
+    let mut requests = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() && http_request_line(&data[offset..]).is_some() {
+        let Some((line, headers, head_len)) = parse_head(&data[offset..]) else {
+            break;
+        };
+        let mut parts = line.splitn(3, ' ');
+        let method = parts.next().unwrap_or_default().to_string();
+        let uri = parts.next().unwrap_or_default().to_string();
+        let version = parts.next().unwrap_or_default().to_string();
+        let (body, body_len) = read_body(&data[offset + head_len..], &headers, false);
+        let Some(time) = time_at(marks, offset) else {
+            break;
+        };
+        requests.push((
+            time,
+            HttpRequest {
+                method,
+                uri,
+                version,
+                headers,
+                body_len: body.len(),
+                body_preview: body_preview(&body),
+            },
+        ));
+        offset += head_len + body_len;
+    }
+    requests
+}
+
+fn parse_responses(data: &[u8], methods: &[&str]) -> Vec<HttpResponse> {
+    let mut responses = Vec::new();
+    let mut offset = 0;
+    for method in methods {
+        if !data[offset..].starts_with(b"HTTP/") {
+            break;
+        }
+        let Some((line, headers, head_len)) = parse_head(&data[offset..]) else {
+            break;
+        };
+        let mut parts = line.splitn(3, ' ');
+        parts.next();
+        let status = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+        let reason = parts.next().unwrap_or_default().to_string();
+        let bodyless = *method == "HEAD" || matches!(status, 100..=199 | 204 | 304);
+        let (body, body_len) = if bodyless {
+            (Vec::new(), 0)
+        } else {
+            read_body(&data[offset + head_len..], &headers, true)
+        };
+        responses.push(HttpResponse {
+            status,
+            reason,
+            headers,
+            body_len: body.len(),
+            body_preview: body_preview(&body),
+        });
+        offset += head_len + body_len;
+    }
+    responses
+}
+
+pub fn exchanges(packets: &[Packet]) -> Vec<HttpExchange> {
+    let mut streams: HashMap<(SocketAddr, SocketAddr), Stream> = HashMap::new();
+    for packet in packets {
+        let Some(segment) = decode(packet.link_type, &packet.data) else {
+            continue;
+        };
+        if segment.transport != Transport::Tcp || segment.payload.is_empty() {
+            continue;
+        }
+        streams
+            .entry((segment.source, segment.destination))
+            .or_default()
+            .segments
+            .push((segment.seq, packet.time, segment.payload));
+    }
+
+    let mut exchanges = Vec::new();
+    for (&(client, server), stream) in &streams {
+        let (data, marks) = stream.reassemble();
+        if http_request_line(&data).is_none() {
+            continue;
+        }
+        let requests = parse_requests(&data, &marks);
+        let responses = match streams.get(&(server, client)) {
+            Some(reverse) => {
+                let methods: Vec<&str> = requests.iter().map(|(_, r)| r.method.as_str()).collect();
+                parse_responses(&reverse.reassemble().0, &methods)
+            }
+            None => Vec::new(),
+        };
+        let mut responses = responses.into_iter();
+        for (time, request) in requests {
+            exchanges.push(HttpExchange {
+                time,
+                client,
+                server,
+                host: header(&request.headers, "host").map(str::to_string),
+                response: responses.next(),
+                request,
+            });
+        }
+    }
+    exchanges.sort_by_key(|e| e.time);
+    exchanges
+}
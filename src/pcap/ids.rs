@@ -0,0 +1,319 @@
+use std::io::{BufRead, BufReader};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use super::{CorrelatedFlow, PCAP_FILE_NAME};
+
+pub const IDS_DIR: &str = "ids";
+pub const EVE_FILE: &str = "eve.json";
+pub const NOTICE_FILE: &str = "notice.log";
+
+const PCAP_PLACEHOLDER: &str = "{pcap}";
+const OUTPUT_PLACEHOLDER: &str = "{output}";
+const SURICATA_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f%z";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct IdsConfig {
+    #[serde(default = "default_suricata_command")]
+    pub suricata_command: Vec<String>,
+    #[serde(default = "default_zeek_command")]
+    pub zeek_command: Vec<String>,
+}
+
+fn default_suricata_command() -> Vec<String> {
+    [
+        "suricata",
+        "-k",
+        "none",
+        "-r",
+        PCAP_PLACEHOLDER,
+        "-l",
+        OUTPUT_PLACEHOLDER,
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+fn default_zeek_command() -> Vec<String> {
+    ["zeek", "-C", "-r", PCAP_PLACEHOLDER, "LogAscii::use_json=T"]
+        .map(str::to_string)
+        .to_vec()
+}
+
+impl Default for IdsConfig {
+    fn default() -> Self {
+        Self {
+            suricata_command: default_suricata_command(),
+            zeek_command: default_zeek_command(),
+        }
+    }
+}
+
+impl IdsConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub async fn inspect(&self, artifact_dir: &str) -> Result<()> {
+        let pcap = Path::new(artifact_dir).join(PCAP_FILE_NAME);
+        if !pcap.exists() {
+            return Ok(());
+        }
+        let output = Path::new(artifact_dir).join(IDS_DIR);
+        tokio::fs::create_dir_all(&output).await?;
+        for (engine, template) in [
+            (IdsEngine::Suricata, &self.suricata_command),
+            (IdsEngine::Zeek, &self.zeek_command),
+        ] {
+            if template.is_empty() {
+                continue;
+            }
+            if let Err(e) = run(template, &pcap, &output).await {
+                warn!(
+                    "Failed to run {} on {}: {}",
+                    engine.name(),
+                    pcap.display(),
+                    e
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn run(template: &[String], pcap: &Path, output: &Path) -> Result<()> {
+    let pcap = pcap.to_string_lossy();
+    let output_dir = output.to_string_lossy();
+    let args: Vec<String> = template
+        .iter()
+        .map(|arg| {
+            arg.replace(PCAP_PLACEHOLDER, &pcap)
+                .replace(OUTPUT_PLACEHOLDER, &output_dir)
+        })
+        .collect();
+    let (program, args) = args.split_first().context("Empty IDS command")?;
+    let status = Command::new(program)
+        .args(args)
+        .current_dir(output)
+        .kill_on_drop(true)
+        .status()
+        .await?;
+    if !status.success() {
+        bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdsEngine {
+    Suricata,
+    Zeek,
+}
+
+impl IdsEngine {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Suricata => "suricata",
+            Self::Zeek => "zeek",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdsAlert {
+    pub engine: IdsEngine,
+    pub time: DateTime<Utc>,
+    pub signature_id: Option<u64>,
+    pub signature: String,
+    pub category: Option<String>,
+    pub severity: Option<u8>,
+    pub protocol: Option<String>,
+    pub source: Option<SocketAddr>,
+    pub destination: Option<SocketAddr>,
+    pub techniques: Vec<String>,
+    pub process_guid: Option<String>,
+    pub image: Option<String>,
+}
+
+impl IdsAlert {
+    pub fn level(&self) -> &'static str {
+        match (self.engine, self.severity) {
+            (IdsEngine::Suricata, Some(1)) => "high",
+            (IdsEngine::Suricata, Some(3..)) => "low",
+            _ => "medium",
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self.signature_id {
+            Some(sid) => format!("[{}] {}", sid, self.signature),
+            None => self.signature.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct EveRecord {
+    timestamp: String,
+    event_type: String,
+    #[serde(default)]
+    src_ip: Option<IpAddr>,
+    #[serde(default)]
+    src_port: Option<u16>,
+    #[serde(default)]
+    dest_ip: Option<IpAddr>,
+    #[serde(default)]
+    dest_port: Option<u16>,
+    #[serde(default)]
+    proto: Option<String>,
+    #[serde(default)]
+    alert: Option<EveAlert>,
+}
+
+#[derive(Deserialize)]
+struct EveAlert {
+    #[serde(default)]
+    signature_id: Option<u64>,
+    signature: String,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    severity: Option<u8>,
+    #[serde(default)]
+    metadata: Option<EveMetadata>,
+}
+
+#[derive(Deserialize, Default)]
+struct EveMetadata {
+    #[serde(default)]
+    mitre_technique_id: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ZeekNotice {
+    ts: f64,
+    note: String,
+    #[serde(default)]
+    msg: Option<String>,
+    #[serde(rename = "id.orig_h", default)]
+    orig_h: Option<IpAddr>,
+    #[serde(rename = "id.orig_p", default)]
+    orig_p: Option<u16>,
+    #[serde(rename = "id.resp_h", default)]
+    resp_h: Option<IpAddr>,
+    #[serde(rename = "id.resp_p", default)]
+    resp_p: Option<u16>,
+    #[serde(default)]
+    proto: Option<String>,
+}
+
+fn endpoint(ip: Option<IpAddr>, port: Option<u16>) -> Option<SocketAddr> {
+    Some(SocketAddr::new(ip?, port.unwrap_or(0)))
+}
+
+fn read_lines<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Vec<T>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping invalid record in {}: {}", path.display(), e),
+        }
+    }
+    Ok(records)
+}
+
+pub fn read_alerts<P: AsRef<Path>>(dir: P) -> Result<Vec<IdsAlert>> {
+    let dir = dir.as_ref();
+    let mut alerts = Vec::new();
+    for record in read_lines::<EveRecord>(&dir.join(EVE_FILE))? {
+        let Some(alert) = record.alert.filter(|_| record.event_type == "alert") else {
+            continue;
+        };
+        let time = DateTime::parse_from_str(&record.timestamp, SURICATA_TIME_FORMAT)
+            .with_context(|| format!("Invalid Suricata timestamp '{}'", record.timestamp))?;
+        alerts.push(IdsAlert {
+            engine: IdsEngine::Suricata,
+            time: time.with_timezone(&Utc),
+            signature_id: alert.signature_id,
+            signature: alert.signature,
+            category: alert.category,
+            severity: alert.severity,
+            protocol: record.proto.map(|p| p.to_lowercase()),
+            source: endpoint(record.src_ip, record.src_port),
+            destination: endpoint(record.dest_ip, record.dest_port),
+            techniques: alert.metadata.unwrap_or_default().mitre_technique_id,
+            process_guid: None,
+            image: None,
+        });
+    }
+    for notice in read_lines::<ZeekNotice>(&dir.join(NOTICE_FILE))? {
+        let Some(time) = Utc
+            .timestamp_micros((notice.ts * 1_000_000.0) as i64)
+            .single()
+        else {
+            continue;
+        };
+        let signature = match notice.msg {
+            Some(msg) => format!("{}: {}", notice.note, msg),
+            None => notice.note,
+        };
+        alerts.push(IdsAlert {
+            engine: IdsEngine::Zeek,
+            time,
+            signature_id: None,
+            signature,
+            category: None,
+            severity: None,
+            protocol: notice.proto.map(|p| p.to_lowercase()),
+            source: endpoint(notice.orig_h, notice.orig_p),
+            destination: endpoint(notice.resp_h, notice.resp_p),
+            techniques: Vec::new(),
+            process_guid: None,
+            image: None,
+        });
+    }
+    alerts.sort_by_key(|a| a.time);
+    Ok(alerts)
+}
+
+fn same_endpoint(a: SocketAddr, b: SocketAddr) -> bool {
+    a.ip() == b.ip() && (a.port() == 0 || b.port() == 0 || a.port() == b.port())
+}
+
+pub fn attribute(alerts: &mut [IdsAlert], flows: &[CorrelatedFlow]) {
+    for alert in alerts {
+        let (Some(source), Some(destination)) = (alert.source, alert.destination) else {
+            continue;
+        };
+        let flow = flows.iter().find(|f| {
+            let transport = alert
+                .protocol
+                .as_deref()
+                .is_none_or(|p| p == f.flow.transport.name());
+            let forward =
+                same_endpoint(source, f.flow.client) && same_endpoint(destination, f.flow.server);
+            let reverse =
+                same_endpoint(source, f.flow.server) && same_endpoint(destination, f.flow.client);
+            transport && (forward || reverse) && f.process_guid.is_some()
+        });
+        if let Some(flow) = flow {
+            alert.process_guid = flow.process_guid.clone();
+            alert.image = flow.image.clone();
+        }
+    }
+}
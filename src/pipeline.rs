@@ -0,0 +1,442 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::encoding::{decode, harden_event, EncodingFlag, FLAGS_KEY};
+use crate::event_filter::EventFilter;
+use crate::event_reader::SysmonEventReader;
+use crate::jsonl::from_json_value;
+use crate::metrics::{self, PIPELINE_DURATION, PIPELINE_EVENTS};
+use crate::quarantine::{ParseFailureSummary, QuarantineSink, QuarantinedRecord};
+use crate::sink::EventSink;
+use crate::sysmon_event::{ParseOptions, SysmonEvent};
+
+type RawBatch = (u64, Vec<Vec<u8>>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    Xml,
+    Jsonl,
+}
+
+impl RecordFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Xml => "xml",
+            Self::Jsonl => "jsonl",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "xml" => Some(Self::Xml),
+            "jsonl" | "ndjson" | "json" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineOptions {
+    pub workers: usize,
+    pub batch_size: usize,
+    pub max_batches_in_flight: usize,
+    pub parse: ParseOptions,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            workers: thread::available_parallelism().map_or(4, |n| n.get()),
+            batch_size: 512,
+            max_batches_in_flight: 16,
+            parse: ParseOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Progress {
+    records_skipped: AtomicU64,
+    records_read: AtomicU64,
+    events_parsed: AtomicU64,
+    parse_failures: AtomicU64,
+    events_filtered: AtomicU64,
+    events_delivered: AtomicU64,
+    batches_in_flight: AtomicU64,
+    stalls: AtomicU64,
+    done: AtomicBool,
+    error: Mutex<Option<String>>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ProgressSnapshot {
+    pub records_skipped: u64,
+    pub records_read: u64,
+    pub events_parsed: u64,
+    pub parse_failures: u64,
+    pub events_filtered: u64,
+    pub events_delivered: u64,
+    pub batches_in_flight: u64,
+    pub stalls: u64,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+impl Progress {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            records_skipped: self.records_skipped.load(Ordering::Relaxed),
+            records_read: self.records_read.load(Ordering::Relaxed),
+            events_parsed: self.events_parsed.load(Ordering::Relaxed),
+            parse_failures: self.parse_failures.load(Ordering::Relaxed),
+            events_filtered: self.events_filtered.load(Ordering::Relaxed),
+            events_delivered: self.events_delivered.load(Ordering::Relaxed),
+            batches_in_flight: self.batches_in_flight.load(Ordering::Relaxed),
+            stalls: self.stalls.load(Ordering::Relaxed),
+            done: self.done.load(Ordering::Relaxed),
+            error: self.error.lock().ok().and_then(|e| e.clone()),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn fail(&self, error: &anyhow::Error) {
+        if let Ok(mut slot) = self.error.lock() {
+            *slot = Some(format!("{:#}", error));
+        }
+        self.done.store(true, Ordering::Relaxed);
+    }
+
+    fn add(counter: &AtomicU64, n: usize) {
+        counter.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+struct Batch {
+    sequence: u64,
+    records: usize,
+    events: Vec<SysmonEvent>,
+    failures: Vec<QuarantinedRecord>,
+}
+
+pub struct Pipeline {
+    options: PipelineOptions,
+    filter: Option<EventFilter>,
+    progress: Arc<Progress>,
+    skip: u64,
+    quarantine: Option<Mutex<Box<dyn QuarantineSink>>>,
+    failures: Mutex<ParseFailureSummary>,
+}
+
+impl Pipeline {
+    pub fn new(options: PipelineOptions) -> Self {
+        Self {
+            options,
+            filter: None,
+            progress: Progress::new(),
+            skip: 0,
+            quarantine: None,
+            failures: Mutex::new(ParseFailureSummary::default()),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn with_progress(mut self, progress: Arc<Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn with_skip(mut self, records: u64) -> Self {
+        self.skip = records;
+        self
+    }
+
+    pub fn with_quarantine<Q: QuarantineSink + 'static>(mut self, quarantine: Q) -> Self {
+        self.quarantine = Some(Mutex::new(Box::new(quarantine)));
+        self
+    }
+
+    pub fn progress(&self) -> Arc<Progress> {
+        self.progress.clone()
+    }
+
+    pub fn parse_failures(&self) -> ParseFailureSummary {
+        self.failures.lock().map(|f| f.clone()).unwrap_or_default()
+    }
+
+    pub fn run<R: BufRead + Send>(
+        &self,
+        reader: R,
+        format: RecordFormat,
+        sink: &mut dyn EventSink,
+    ) -> Result<ProgressSnapshot> {
+        let started = Instant::now();
+        let mut span = metrics::timed(
+            "pipeline.run",
+            &PIPELINE_DURATION,
+            &[("format", format.name())],
+        );
+        let result = thread::scope(|scope| {
+            let in_flight = self.options.max_batches_in_flight.max(1);
+            let (permit_tx, permit_rx) = sync_channel::<()>(in_flight);
+            for _ in 0..in_flight {
+                permit_tx.send(())?;
+            }
+            let (raw_tx, raw_rx) = sync_channel::<RawBatch>(in_flight);
+            let (parsed_tx, parsed_rx) = sync_channel::<Batch>(in_flight);
+
+            let reader = scope.spawn(move || self.read(reader, format, permit_rx, raw_tx));
+
+            let raw_rx = Arc::new(Mutex::new(raw_rx));
+            for _ in 0..self.options.workers.max(1) {
+                let raw_rx = raw_rx.clone();
+                let parsed_tx = parsed_tx.clone();
+                scope.spawn(move || self.parse(format, raw_rx, parsed_tx));
+            }
+            drop(raw_rx);
+            drop(parsed_tx);
+
+            let mut pending: BTreeMap<u64, Batch> = BTreeMap::new();
+            let mut next = 0;
+            let mut consumed = self.skip;
+            for batch in parsed_rx {
+                pending.insert(batch.sequence, batch);
+                while let Some(batch) = pending.remove(&next) {
+                    next += 1;
+                    consumed += batch.records as u64;
+                    self.deliver(batch, sink)?;
+                    sink.checkpoint(consumed)?;
+                    self.progress
+                        .batches_in_flight
+                        .fetch_sub(1, Ordering::Relaxed);
+                    let _ = permit_tx.send(());
+                }
+            }
+            sink.flush()?;
+            if let Some(quarantine) = &self.quarantine {
+                quarantine
+                    .lock()
+                    .map_err(|_| anyhow!("Quarantine sink is poisoned"))?
+                    .flush()?;
+            }
+
+            reader
+                .join()
+                .map_err(|_| anyhow!("Pipeline reader panicked"))?
+        });
+        if let Err(e) = &result {
+            self.progress.fail(e);
+            span.fail(e);
+        }
+        result?;
+        self.progress.done.store(true, Ordering::Relaxed);
+
+        let snapshot = self.progress.snapshot();
+        for (outcome, count) in [
+            ("parsed", snapshot.events_parsed),
+            ("failed", snapshot.parse_failures),
+            ("filtered", snapshot.events_filtered),
+            ("delivered", snapshot.events_delivered),
+        ] {
+            PIPELINE_EVENTS.add(&[("result", outcome)], count as f64);
+        }
+        info!(
+            "Pipeline delivered {} of {} records in {:.1}s ({} stalls)",
+            snapshot.events_delivered,
+            snapshot.records_read,
+            started.elapsed().as_secs_f64(),
+            snapshot.stalls
+        );
+        Ok(snapshot)
+    }
+
+    fn read<R: BufRead>(
+        &self,
+        reader: R,
+        format: RecordFormat,
+        permits: Receiver<()>,
+        raw_tx: SyncSender<(u64, Vec<Vec<u8>>)>,
+    ) -> Result<()> {
+        let mut records = Records::new(reader, format);
+        for _ in 0..self.skip {
+            if records.next_record()?.is_none() {
+                break;
+            }
+            self.progress
+                .records_skipped
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        let batch_size = self.options.batch_size.max(1);
+        let mut sequence = 0;
+        loop {
+            let mut batch = Vec::with_capacity(batch_size);
+            while batch.len() < batch_size {
+                match records.next_record()? {
+                    Some(record) => batch.push(record),
+                    None => break,
+                }
+            }
+            if batch.is_empty() {
+                return Ok(());
+            }
+            Progress::add(&self.progress.records_read, batch.len());
+
+            match permits.try_recv() {
+                Ok(()) => (),
+                Err(TryRecvError::Empty) => {
+                    self.progress.stalls.fetch_add(1, Ordering::Relaxed);
+                    if permits.recv().is_err() {
+                        return Ok(());
+                    }
+                }
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+            self.progress
+                .batches_in_flight
+                .fetch_add(1, Ordering::Relaxed);
+            if raw_tx.send((sequence, batch)).is_err() {
+                return Ok(());
+            }
+            sequence += 1;
+        }
+    }
+
+    fn parse(
+        &self,
+        format: RecordFormat,
+        raw_rx: Arc<Mutex<Receiver<RawBatch>>>,
+        parsed_tx: SyncSender<Batch>,
+    ) {
+        loop {
+            let received = match raw_rx.lock() {
+                Ok(rx) => rx.recv(),
+                Err(_) => return,
+            };
+            let Ok((sequence, records)) = received else {
+                return;
+            };
+            let mut batch = Batch {
+                sequence,
+                records: records.len(),
+                events: Vec::with_capacity(records.len()),
+                failures: Vec::new(),
+            };
+            let first = self.skip + sequence * self.options.batch_size.max(1) as u64;
+            for (i, record) in records.iter().enumerate() {
+                match parse_record(record, format, &self.options.parse) {
+                    Ok(event) => batch.events.push(event),
+                    Err(e) => batch.failures.push(QuarantinedRecord::new(
+                        format.name(),
+                        Some(first + i as u64),
+                        record,
+                        &e,
+                    )),
+                }
+            }
+            Progress::add(&self.progress.events_parsed, batch.events.len());
+            Progress::add(&self.progress.parse_failures, batch.failures.len());
+            if let Some(filter) = &self.filter {
+                let before = batch.events.len();
+                batch.events.retain(|e| filter.keep(e));
+                Progress::add(&self.progress.events_filtered, before - batch.events.len());
+            }
+            if parsed_tx.send(batch).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn deliver(&self, batch: Batch, sink: &mut dyn EventSink) -> Result<()> {
+        for record in batch.failures {
+            self.quarantine(record)?;
+        }
+        for event in &batch.events {
+            sink.write(event)?;
+        }
+        Progress::add(&self.progress.events_delivered, batch.events.len());
+        Ok(())
+    }
+
+    fn quarantine(&self, record: QuarantinedRecord) -> Result<()> {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.add(&record);
+        }
+        match &self.quarantine {
+            Some(quarantine) => quarantine
+                .lock()
+                .map_err(|_| anyhow!("Quarantine sink is poisoned"))?
+                .quarantine(record),
+            None if self.options.parse.lenient => {
+                warn!("Skipping unparsable record: {}", record.error);
+                Ok(())
+            }
+            None => Err(anyhow!("{}", record.error)),
+        }
+    }
+}
+
+enum Records<R> {
+    Xml(SysmonEventReader<R>),
+    Jsonl(R, Vec<u8>),
+}
+
+impl<R: BufRead> Records<R> {
+    fn new(reader: R, format: RecordFormat) -> Self {
+        match format {
+            RecordFormat::Xml => Self::Xml(SysmonEventReader::new(reader).keep_truncated()),
+            RecordFormat::Jsonl => Self::Jsonl(reader, Vec::new()),
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<Vec<u8>>> {
+        match self {
+            Self::Xml(reader) => reader.next_raw(),
+            Self::Jsonl(reader, line) => loop {
+                line.clear();
+                if reader.read_until(b'\n', line)? == 0 {
+                    return Ok(None);
+                }
+                if !line.iter().all(u8::is_ascii_whitespace) {
+                    return Ok(Some(line.clone()));
+                }
+            },
+        }
+    }
+}
+
+fn parse_record(
+    record: &[u8],
+    format: RecordFormat,
+    options: &ParseOptions,
+) -> Result<SysmonEvent> {
+    let (record, lossy) = decode(record);
+    let mut event = match format {
+        RecordFormat::Xml => SysmonEvent::from_xml_with(record.trim(), options)?.event,
+        RecordFormat::Jsonl => from_json_value(&serde_json::from_str::<Value>(record.trim())?)?,
+    };
+    harden_event(&mut event);
+    if lossy {
+        event.set_enriched(FLAGS_KEY, EncodingFlag::InvalidUtf8.name());
+    }
+    Ok(event)
+}
@@ -0,0 +1,162 @@
+#[cfg(feature = "plugins")]
+mod dynamic;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use std::fmt;
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::analysis_result::{artifact_dir, sample_path, AnalysisResult, ExecutionLog};
+use crate::artifacts::Artifact;
+use crate::metrics::{self, ANALYZER_DURATION};
+use crate::sysmon_event::SysmonEvent;
+use crate::telemetry::TelemetryEvent;
+
+#[cfg(feature = "plugins")]
+pub use dynamic::{DynamicAnalyzer, ABI_VERSION};
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmAnalyzer, WASM_ABI_VERSION};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AnalysisContext<'a> {
+    pub analysis_id: &'a str,
+    pub execution_id: &'a str,
+    pub sample_hash: &'a str,
+    pub sample_path: String,
+    pub artifact_dir: String,
+    pub events: &'a [SysmonEvent],
+    pub telemetry: &'a [TelemetryEvent],
+    pub artifacts: &'a [Artifact],
+}
+
+impl<'a> AnalysisContext<'a> {
+    pub fn new(result: &'a AnalysisResult, log: &'a ExecutionLog) -> Self {
+        Self {
+            analysis_id: &result.id,
+            execution_id: &log.id,
+            sample_hash: &result.hash,
+            sample_path: sample_path(&result.id),
+            artifact_dir: artifact_dir(&result.id, &log.id),
+            events: &log.sysmon_events,
+            telemetry: &log.telemetry,
+            artifacts: &log.artifacts,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Finding {
+    pub name: String,
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub events: Vec<SysmonEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnalyzerArtifact {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AnalyzerOutput {
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+    #[serde(default)]
+    pub artifacts: Vec<AnalyzerArtifact>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnalyzerResult {
+    pub analyzer: String,
+    pub output: AnalyzerOutput,
+}
+
+pub trait Analyzer: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn analyze(&self, context: &AnalysisContext) -> Result<AnalyzerOutput>;
+}
+
+#[derive(Default)]
+pub struct AnalyzerRegistry {
+    analyzers: Vec<Box<dyn Analyzer>>,
+}
+
+impl fmt::Debug for AnalyzerRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.analyzers.iter().map(|a| a.name()))
+            .finish()
+    }
+}
+
+impl AnalyzerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Analyzer + 'static>(&mut self, analyzer: T) {
+        self.analyzers.push(Box::new(analyzer));
+    }
+
+    #[cfg(any(feature = "plugins", feature = "wasm"))]
+    pub fn load_dir<P: AsRef<std::path::Path>>(&mut self, dir: P) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+                continue;
+            };
+            match extension {
+                #[cfg(feature = "wasm")]
+                "wasm" => self.register(WasmAnalyzer::load(&path)?),
+                #[cfg(feature = "plugins")]
+                e if e == std::env::consts::DLL_EXTENSION => {
+                    self.register(DynamicAnalyzer::load(&path)?)
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.analyzers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.analyzers.is_empty()
+    }
+
+    pub fn run(&self, context: &AnalysisContext) -> Vec<AnalyzerResult> {
+        let mut results = Vec::new();
+        for analyzer in &self.analyzers {
+            let mut span = metrics::timed(
+                "analyzer.run",
+                &ANALYZER_DURATION,
+                &[("analyzer", analyzer.name())],
+            );
+            match analyzer.analyze(context) {
+                Ok(output) => results.push(AnalyzerResult {
+                    analyzer: analyzer.name().to_string(),
+                    output,
+                }),
+                Err(e) => {
+                    span.fail(&e);
+                    warn!("Analyzer {} failed: {}", analyzer.name(), e)
+                }
+            }
+        }
+        results
+    }
+}
@@ -0,0 +1,75 @@
+use std::ffi::{c_char, CStr, CString};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use libloading::Library;
+
+use super::{AnalysisContext, Analyzer, AnalyzerOutput};
+
+pub const ABI_VERSION: u32 = 1;
+
+const ABI_VERSION_SYMBOL: &[u8] = b"sandbox_plugin_abi_version\0";
+const NAME_SYMBOL: &[u8] = b"sandbox_plugin_name\0";
+const ANALYZE_SYMBOL: &[u8] = b"sandbox_plugin_analyze\0";
+const FREE_SYMBOL: &[u8] = b"sandbox_plugin_free\0";
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type NameFn = unsafe extern "C" fn() -> *const c_char;
+type AnalyzeFn = unsafe extern "C" fn(context: *const c_char) -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(output: *mut c_char);
+
+pub struct DynamicAnalyzer {
+    name: String,
+    analyze: AnalyzeFn,
+    free: FreeFn,
+    _library: Library,
+}
+
+impl DynamicAnalyzer {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let library = unsafe { Library::new(path) }
+            .with_context(|| format!("Failed to load plugin {}", path.display()))?;
+        unsafe {
+            let version = library.get::<AbiVersionFn>(ABI_VERSION_SYMBOL)?();
+            if version != ABI_VERSION {
+                bail!(
+                    "Plugin {} uses ABI version {}, expected {}",
+                    path.display(),
+                    version,
+                    ABI_VERSION
+                );
+            }
+            let name = library.get::<NameFn>(NAME_SYMBOL)?();
+            if name.is_null() {
+                bail!("Plugin {} has no name", path.display());
+            }
+            let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+            let analyze = *library.get::<AnalyzeFn>(ANALYZE_SYMBOL)?;
+            let free = *library.get::<FreeFn>(FREE_SYMBOL)?;
+            Ok(Self {
+                name,
+                analyze,
+                free,
+                _library: library,
+            })
+        }
+    }
+}
+
+impl Analyzer for DynamicAnalyzer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn analyze(&self, context: &AnalysisContext) -> Result<AnalyzerOutput> {
+        let input = CString::new(serde_json::to_vec(context)?)?;
+        let output = unsafe { (self.analyze)(input.as_ptr()) };
+        if output.is_null() {
+            bail!("Plugin {} returned no output", self.name);
+        }
+        let json = unsafe { CStr::from_ptr(output) }.to_bytes().to_vec();
+        unsafe { (self.free)(output) };
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use wasmtime::{
+    Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use super::{AnalysisContext, Analyzer, AnalyzerOutput};
+
+pub const WASM_ABI_VERSION: u32 = 1;
+
+const MAX_MEMORY: usize = 256 * 1024 * 1024;
+const MAX_OUTPUT_LEN: usize = 16 * 1024 * 1024;
+const FUEL: u64 = 10_000_000_000;
+
+struct State {
+    limits: StoreLimits,
+}
+
+pub struct WasmAnalyzer {
+    name: String,
+    engine: Engine,
+    module: Module,
+}
+
+fn unpack(packed: u64) -> (u32, u32) {
+    ((packed >> 32) as u32, packed as u32)
+}
+
+fn read(store: &Store<State>, memory: &Memory, ptr: u32, len: u32) -> Result<Vec<u8>> {
+    if len as usize > MAX_OUTPUT_LEN {
+        bail!("Plugin output of {} bytes is too large", len);
+    }
+    let mut buf = vec![0; len as usize];
+    memory.read(store, ptr as usize, &mut buf)?;
+    Ok(buf)
+}
+
+impl WasmAnalyzer {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, path)
+            .with_context(|| format!("Failed to load plugin {}", path.display()))?;
+        let mut analyzer = Self {
+            name: String::new(),
+            engine,
+            module,
+        };
+
+        let (mut store, instance) = analyzer.instantiate()?;
+        let version = instance
+            .get_typed_func::<(), u32>(&mut store, "sandbox_abi_version")?
+            .call(&mut store, ())?;
+        if version != WASM_ABI_VERSION {
+            bail!(
+                "Plugin {} uses ABI version {}, expected {}",
+                path.display(),
+                version,
+                WASM_ABI_VERSION
+            );
+        }
+        let memory = analyzer.memory(&mut store, &instance)?;
+        let (ptr, len) = unpack(
+            instance
+                .get_typed_func::<(), u64>(&mut store, "sandbox_name")?
+                .call(&mut store, ())?,
+        );
+        analyzer.name = String::from_utf8(read(&store, &memory, ptr, len)?)?;
+        Ok(analyzer)
+    }
+
+    fn instantiate(&self) -> Result<(Store<State>, Instance)> {
+        let mut store = Store::new(
+            &self.engine,
+            State {
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY)
+                    .instances(1)
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.add_fuel(FUEL)?;
+        let instance = Linker::new(&self.engine).instantiate(&mut store, &self.module)?;
+        Ok((store, instance))
+    }
+
+    fn memory(&self, store: &mut Store<State>, instance: &Instance) -> Result<Memory> {
+        instance
+            .get_memory(store, "memory")
+            .context("Plugin does not export its memory")
+    }
+}
+
+impl Analyzer for WasmAnalyzer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn analyze(&self, context: &AnalysisContext) -> Result<AnalyzerOutput> {
+        let input = serde_json::to_vec(context)?;
+        let (mut store, instance) = self.instantiate()?;
+        let memory = self.memory(&mut store, &instance)?;
+        let alloc = instance.get_typed_func::<u32, u32>(&mut store, "sandbox_alloc")?;
+        let dealloc = instance.get_typed_func::<(u32, u32), ()>(&mut store, "sandbox_dealloc")?;
+        let analyze = instance.get_typed_func::<(u32, u32), u64>(&mut store, "sandbox_analyze")?;
+
+        let len = u32::try_from(input.len()).context("Plugin input is too large")?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory.write(&mut store, ptr as usize, &input)?;
+        let (output_ptr, output_len) = unpack(analyze.call(&mut store, (ptr, len))?);
+        if output_ptr == 0 {
+            bail!("Plugin {} returned no output", self.name);
+        }
+        let output = read(&store, &memory, output_ptr, output_len)?;
+        dealloc.call(&mut store, (output_ptr, output_len))?;
+        Ok(serde_json::from_slice(&output)?)
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+use crate::event_data::TypedEventData;
+use crate::flags::AccessMask;
+use crate::sysmon_event::SysmonEvent;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Process {
+    pub guid: String,
+    pub process_id: Option<u32>,
+    pub parent_guid: Option<String>,
+    pub parent_process_id: Option<u32>,
+    pub image: String,
+    pub command_line: String,
+    pub start_time: Option<DateTime<FixedOffset>>,
+    pub end_time: Option<DateTime<FixedOffset>>,
+    pub children: Vec<String>,
+}
+
+impl Process {
+    fn observed(guid: &str, process_id: Option<u32>, image: &str) -> Self {
+        Process {
+            guid: guid.to_string(),
+            process_id,
+            parent_guid: None,
+            parent_process_id: None,
+            image: image.to_string(),
+            command_line: String::new(),
+            start_time: None,
+            end_time: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    RemoteThread,
+    Access,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessEdge {
+    pub kind: EdgeKind,
+    pub source: String,
+    pub target: String,
+    pub time: DateTime<FixedOffset>,
+    pub granted_access: Option<AccessMask>,
+    pub start_address: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParentSpoofWarning {
+    pub child_guid: String,
+    pub guid_parent: String,
+    pub guid_parent_process_id: Option<u32>,
+    pub claimed_parent_process_id: u32,
+    pub claimed_parent: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ProcessTree {
+    processes: HashMap<String, Process>,
+    edges: Vec<ProcessEdge>,
+}
+
+impl ProcessTree {
+    pub fn from_events(events: &[SysmonEvent]) -> Self {
+        let mut tree = Self::default();
+        tree.extend(events);
+        tree
+    }
+
+    pub fn extend(&mut self, events: &[SysmonEvent]) {
+        for event in events {
+            self.add(event);
+        }
+        self.link_children();
+    }
+
+    fn observe(&mut self, guid: &str, process_id: Option<u32>, image: &str) {
+        if !guid.is_empty() && !self.processes.contains_key(guid) {
+            self.processes
+                .insert(guid.to_string(), Process::observed(guid, process_id, image));
+        }
+    }
+
+    fn add(&mut self, event: &SysmonEvent) {
+        match event.typed_data() {
+            TypedEventData::ProcessCreate(data) => {
+                let process = self
+                    .processes
+                    .entry(data.process_guid.clone())
+                    .or_insert_with(|| Process::observed(&data.process_guid, None, ""));
+                process.process_id = data.process_id;
+                process.parent_guid = data.parent_process_guid;
+                process.parent_process_id = data.parent_process_id;
+                process.image = data.image;
+                process.command_line = data.command_line;
+                process.start_time = Some(event.time_created);
+            }
+            TypedEventData::ProcessTerminate(data) => {
+                self.observe(&data.process_guid, data.process_id, &data.image);
+                if let Some(process) = self.processes.get_mut(&data.process_guid) {
+                    process.end_time = Some(event.time_created);
+                }
+            }
+            TypedEventData::CreateRemoteThread(data) => {
+                self.observe(
+                    &data.source_process_guid,
+                    data.source_process_id,
+                    &data.source_image,
+                );
+                self.observe(
+                    &data.target_process_guid,
+                    data.target_process_id,
+                    &data.target_image,
+                );
+                self.edges.push(ProcessEdge {
+                    kind: EdgeKind::RemoteThread,
+                    source: data.source_process_guid,
+                    target: data.target_process_guid,
+                    time: event.time_created,
+                    granted_access: None,
+                    start_address: data.start_address,
+                });
+            }
+            TypedEventData::ProcessAccess(data) => {
+                self.observe(
+                    &data.source_process_guid,
+                    data.source_process_id,
+                    &data.source_image,
+                );
+                self.observe(
+                    &data.target_process_guid,
+                    data.target_process_id,
+                    &data.target_image,
+                );
+                self.edges.push(ProcessEdge {
+                    kind: EdgeKind::Access,
+                    source: data.source_process_guid,
+                    target: data.target_process_guid,
+                    time: event.time_created,
+                    granted_access: data.granted_access,
+                    start_address: None,
+                });
+            }
+            _ => (),
+        }
+    }
+
+    fn link_children(&mut self) {
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        for process in self.processes.values() {
+            if let Some(parent) = &process.parent_guid {
+                if self.processes.contains_key(parent) {
+                    children
+                        .entry(parent.clone())
+                        .or_default()
+                        .push(process.guid.clone());
+                }
+            }
+        }
+        for guids in children.values_mut() {
+            guids.sort_by_key(|g| self.processes[g].start_time);
+        }
+        for (parent, guids) in children {
+            if let Some(process) = self.processes.get_mut(&parent) {
+                process.children = guids;
+            }
+        }
+    }
+
+    pub fn get(&self, guid: &str) -> Option<&Process> {
+        self.processes.get(guid)
+    }
+
+    pub fn processes(&self) -> impl Iterator<Item = &Process> {
+        self.processes.values()
+    }
+
+    pub fn roots(&self) -> Vec<&Process> {
+        let mut roots: Vec<&Process> = self
+            .processes
+            .values()
+            .filter(|p| {
+                p.parent_guid
+                    .as_deref()
+                    .is_none_or(|g| !self.processes.contains_key(g))
+            })
+            .collect();
+        roots.sort_by_key(|p| p.start_time);
+        roots
+    }
+
+    pub fn children(&self, guid: &str) -> Vec<&Process> {
+        self.get(guid)
+            .map(|p| p.children.iter().filter_map(|c| self.get(c)).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn edges(&self) -> &[ProcessEdge] {
+        &self.edges
+    }
+
+    pub fn injections(&self) -> impl Iterator<Item = &ProcessEdge> {
+        self.edges
+            .iter()
+            .filter(|e| e.kind == EdgeKind::RemoteThread)
+    }
+
+    pub fn ancestors(&self, guid: &str) -> Vec<&Process> {
+        let mut result = Vec::new();
+        let mut current = self.get(guid);
+        while let Some(process) = current {
+            if result.iter().any(|p: &&Process| p.guid == process.guid) {
+                break;
+            }
+            result.push(process);
+            current = process.parent_guid.as_deref().and_then(|g| self.get(g));
+        }
+        result.reverse();
+        result
+    }
+
+    fn process_by_pid_at(&self, pid: u32, time: DateTime<FixedOffset>) -> Option<&Process> {
+        self.processes
+            .values()
+            .filter(|p| p.process_id == Some(pid) && p.start_time.is_some_and(|t| t <= time))
+            .max_by_key(|p| p.start_time)
+    }
+
+    pub fn parent_spoof_warnings(&self) -> Vec<ParentSpoofWarning> {
+        let mut result = Vec::new();
+        for child in self.processes.values() {
+            let (Some(parent_guid), Some(claimed_pid), Some(start_time)) = (
+                &child.parent_guid,
+                child.parent_process_id,
+                child.start_time,
+            ) else {
+                continue;
+            };
+            let Some(parent) = self.get(parent_guid) else {
+                continue;
+            };
+            if parent.process_id.is_none() || parent.process_id == Some(claimed_pid) {
+                continue;
+            }
+
+            result.push(ParentSpoofWarning {
+                child_guid: child.guid.clone(),
+                guid_parent: parent.guid.clone(),
+                guid_parent_process_id: parent.process_id,
+                claimed_parent_process_id: claimed_pid,
+                claimed_parent: self
+                    .process_by_pid_at(claimed_pid, start_time)
+                    .map(|p| p.guid.clone()),
+            });
+        }
+
+        result
+    }
+}
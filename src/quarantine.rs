@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+pub const QUARANTINE_FILE: &str = "quarantine.jsonl";
+
+const MAX_RAW_BYTES: usize = 64 * 1024;
+const MAX_EXAMPLES: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseErrorKind {
+    MalformedXml,
+    MalformedJson,
+    InvalidTimestamp,
+    InvalidEncoding,
+    Other,
+}
+
+impl ParseErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MalformedXml => "malformed_xml",
+            Self::MalformedJson => "malformed_json",
+            Self::InvalidTimestamp => "invalid_timestamp",
+            Self::InvalidEncoding => "invalid_encoding",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn classify(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if cause.is::<std::str::Utf8Error>() || cause.is::<std::string::FromUtf8Error>() {
+                return Self::InvalidEncoding;
+            }
+            if cause.is::<chrono::ParseError>() {
+                return Self::InvalidTimestamp;
+            }
+            if cause.is::<roxmltree::Error>() {
+                return Self::MalformedXml;
+            }
+            if cause.is::<serde_json::Error>() {
+                return Self::MalformedJson;
+            }
+        }
+        let message = format!("{:#}", error).to_lowercase();
+        if message.contains("time") {
+            Self::InvalidTimestamp
+        } else {
+            Self::Other
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuarantinedRecord {
+    pub time: DateTime<Local>,
+    pub source: String,
+    pub record: Option<u64>,
+    pub kind: ParseErrorKind,
+    pub error: String,
+    pub raw: String,
+    pub truncated: bool,
+}
+
+impl QuarantinedRecord {
+    pub fn new(source: &str, record: Option<u64>, raw: &[u8], error: &anyhow::Error) -> Self {
+        let truncated = raw.len() > MAX_RAW_BYTES;
+        Self {
+            time: Local::now(),
+            source: source.to_string(),
+            record,
+            kind: ParseErrorKind::classify(error),
+            error: format!("{:#}", error),
+            raw: String::from_utf8_lossy(&raw[..raw.len().min(MAX_RAW_BYTES)]).into_owned(),
+            truncated,
+        }
+    }
+}
+
+pub trait QuarantineSink: Send {
+    fn quarantine(&mut self, record: QuarantinedRecord) -> Result<()>;
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl QuarantineSink for Vec<QuarantinedRecord> {
+    fn quarantine(&mut self, record: QuarantinedRecord) -> Result<()> {
+        self.push(record);
+        Ok(())
+    }
+}
+
+pub struct FileQuarantine {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl FileQuarantine {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            writer: None,
+        }
+    }
+
+    fn writer(&mut self) -> Result<&mut BufWriter<File>> {
+        if self.writer.is_none() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.writer = Some(BufWriter::new(file));
+        }
+        Ok(self.writer.as_mut().expect("writer is open"))
+    }
+}
+
+impl QuarantineSink for FileQuarantine {
+    fn quarantine(&mut self, record: QuarantinedRecord) -> Result<()> {
+        let writer = self.writer()?;
+        serde_json::to_writer(&mut *writer, &record)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FileQuarantine {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Failed to flush quarantine {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+pub fn read_quarantine<P: AsRef<Path>>(dir: P) -> Result<Vec<QuarantinedRecord>> {
+    let path = dir.as_ref().join(QUARANTINE_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut records = Vec::new();
+    for line in BufReader::new(File::open(&path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(records)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParseFailureExample {
+    pub source: String,
+    pub record: Option<u64>,
+    pub kind: ParseErrorKind,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ParseFailureSummary {
+    pub total: usize,
+    pub by_kind: BTreeMap<String, usize>,
+    pub by_source: BTreeMap<String, usize>,
+    pub examples: Vec<ParseFailureExample>,
+}
+
+impl ParseFailureSummary {
+    pub fn from_records(records: &[QuarantinedRecord]) -> Self {
+        let mut summary = Self::default();
+        for record in records {
+            summary.add(record);
+        }
+        summary
+    }
+
+    pub fn add(&mut self, record: &QuarantinedRecord) {
+        self.total += 1;
+        *self
+            .by_kind
+            .entry(record.kind.name().to_string())
+            .or_default() += 1;
+        *self.by_source.entry(record.source.clone()).or_default() += 1;
+        if self.examples.len() < MAX_EXAMPLES {
+            self.examples.push(ParseFailureExample {
+                source: record.source.clone(),
+                record: record.record,
+                kind: record.kind,
+                error: record.error.clone(),
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+}
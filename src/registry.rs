@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+use crate::event_data::{RegistryEventData, TypedEventData};
+use crate::sysmon_event::SysmonEvent;
+
+const HIVE_PREFIXES: &[(&str, &str)] = &[
+    (r"\registry\machine", "HKLM"),
+    (r"\registry\user", "HKU"),
+    ("hkey_local_machine", "HKLM"),
+    ("hkey_current_user", "HKCU"),
+    ("hkey_users", "HKU"),
+    ("hkey_classes_root", "HKCR"),
+    ("hkey_current_config", "HKCC"),
+    ("hklm", "HKLM"),
+    ("hkcu", "HKCU"),
+    ("hku", "HKU"),
+    ("hkcr", "HKCR"),
+    ("hkcc", "HKCC"),
+];
+
+const CLASSES: &str = r"\software\classes";
+
+const PERSISTENCE_LOCATIONS: &[(&str, PersistenceKind)] = &[
+    (
+        r"\software\microsoft\windows\currentversion\run\",
+        PersistenceKind::RunKey,
+    ),
+    (
+        r"\software\microsoft\windows\currentversion\runonce\",
+        PersistenceKind::RunKey,
+    ),
+    (
+        r"\software\microsoft\windows\currentversion\runonceex\",
+        PersistenceKind::RunKey,
+    ),
+    (
+        r"\software\microsoft\windows\currentversion\runservices\",
+        PersistenceKind::RunKey,
+    ),
+    (
+        r"\software\microsoft\windows\currentversion\policies\explorer\run\",
+        PersistenceKind::RunKey,
+    ),
+    (
+        r"\software\wow6432node\microsoft\windows\currentversion\run\",
+        PersistenceKind::RunKey,
+    ),
+    (
+        r"\software\wow6432node\microsoft\windows\currentversion\runonce\",
+        PersistenceKind::RunKey,
+    ),
+    (
+        r"\system\currentcontrolset\services\",
+        PersistenceKind::Service,
+    ),
+    (
+        r"\software\microsoft\windows nt\currentversion\image file execution options\",
+        PersistenceKind::ImageFileExecutionOptions,
+    ),
+    (
+        r"\software\microsoft\windows nt\currentversion\silentprocessexit\",
+        PersistenceKind::ImageFileExecutionOptions,
+    ),
+    (
+        r"\software\microsoft\windows nt\currentversion\winlogon\",
+        PersistenceKind::Winlogon,
+    ),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PersistenceKind {
+    RunKey,
+    Service,
+    ImageFileExecutionOptions,
+    Winlogon,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryOperation {
+    CreateKey,
+    DeleteKey,
+    SetValue,
+    DeleteValue,
+    Rename,
+}
+
+impl RegistryOperation {
+    fn from_event_type(event_type: &str) -> Option<Self> {
+        match event_type.to_lowercase().as_str() {
+            "createkey" => Some(Self::CreateKey),
+            "deletekey" => Some(Self::DeleteKey),
+            "setvalue" => Some(Self::SetValue),
+            "deletevalue" => Some(Self::DeleteValue),
+            "renamekey" | "renamevalue" => Some(Self::Rename),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", content = "value")]
+pub enum RegistryData {
+    String(String),
+    Dword(u32),
+    Qword(u64),
+    Binary(Vec<u8>),
+    BinaryOmitted,
+}
+
+fn parse_hex_u64(s: &str) -> Option<u64> {
+    u64::from_str_radix(s.trim().trim_start_matches("0x"), 16).ok()
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix("hex:")
+        .or_else(|| s.strip_prefix("hex(b):"))
+        .unwrap_or(s);
+    let digits: String = s
+        .chars()
+        .filter(|c| !matches!(c, ',' | ' ' | '-'))
+        .collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+impl RegistryData {
+    pub fn decode(details: &str) -> Self {
+        let trimmed = details.trim();
+        if trimmed.eq_ignore_ascii_case("binary data") {
+            return Self::BinaryOmitted;
+        }
+        if let Some(value) = trimmed
+            .strip_prefix("DWORD (")
+            .and_then(|v| v.strip_suffix(')'))
+        {
+            if let Some(value) = parse_hex_u64(value) {
+                return Self::Dword(value as u32);
+            }
+        }
+        if let Some(value) = trimmed
+            .strip_prefix("QWORD (")
+            .and_then(|v| v.strip_suffix(')'))
+        {
+            let parts: Option<Vec<u64>> = value.split('-').map(parse_hex_u64).collect();
+            match parts.as_deref() {
+                Some([high, low]) => return Self::Qword(high << 32 | low),
+                Some([value]) => return Self::Qword(*value),
+                _ => {}
+            }
+        }
+        if trimmed.starts_with("hex") {
+            if let Some(bytes) = parse_hex_bytes(trimmed) {
+                return Self::Binary(bytes);
+            }
+        }
+        Self::String(details.to_string())
+    }
+
+    pub fn text(&self) -> Option<String> {
+        match self {
+            Self::String(s) => Some(s.clone()),
+            Self::Binary(bytes) if bytes.len() >= 2 && bytes.len().is_multiple_of(2) => {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                let text = String::from_utf16(&units).ok()?;
+                let text = text.trim_end_matches('\0');
+                (!text.is_empty() && text.chars().all(|c| !c.is_control() || c == '\0'))
+                    .then(|| text.replace('\0', " "))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RegistryData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::String(s) => write!(f, "{}", s),
+            Self::Dword(v) => write!(f, "DWORD 0x{:08x}", v),
+            Self::Qword(v) => write!(f, "QWORD 0x{:016x}", v),
+            Self::Binary(bytes) => match self.text() {
+                Some(text) => write!(f, "{}", text),
+                None => write!(f, "{} bytes", bytes.len()),
+            },
+            Self::BinaryOmitted => write!(f, "Binary Data"),
+        }
+    }
+}
+
+pub fn normalize_key(key: &str) -> String {
+    let key = key.trim();
+    let lower = key.to_lowercase();
+    for (prefix, hive) in HIVE_PREFIXES {
+        if let Some(rest) = lower.strip_prefix(prefix) {
+            if rest.is_empty() || rest.starts_with('\\') {
+                let rest = &key[prefix.len()..];
+                if *hive == "HKLM" {
+                    if let Some(classes) = rest.get(CLASSES.len()..) {
+                        if rest[..CLASSES.len()].eq_ignore_ascii_case(CLASSES)
+                            && (classes.is_empty() || classes.starts_with('\\'))
+                        {
+                            return format!("HKCR{}", classes);
+                        }
+                    }
+                }
+                return format!("{}{}", hive, rest);
+            }
+        }
+    }
+    key.to_string()
+}
+
+pub fn persistence_kind(key: &str) -> Option<PersistenceKind> {
+    let mut key = normalize_key(key).to_lowercase();
+    for set in 1..=3 {
+        key = key.replace(&format!(r"\controlset{:03}\", set), r"\currentcontrolset\");
+    }
+    PERSISTENCE_LOCATIONS
+        .iter()
+        .find(|(location, _)| key.contains(location))
+        .map(|(_, kind)| *kind)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegistryEntry {
+    pub key: String,
+    pub operation: RegistryOperation,
+    pub data: Option<RegistryData>,
+    pub new_name: Option<String>,
+    pub first_seen: DateTime<FixedOffset>,
+    pub last_seen: DateTime<FixedOffset>,
+    pub writes: usize,
+    pub images: Vec<String>,
+    pub persistence: Option<PersistenceKind>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RegistryDiff {
+    pub entries: Vec<RegistryEntry>,
+}
+
+impl RegistryDiff {
+    pub fn from_events(events: &[SysmonEvent]) -> Self {
+        let mut entries: Vec<RegistryEntry> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for event in events {
+            let TypedEventData::RegistryEvent(RegistryEventData {
+                event_type,
+                image,
+                target_object,
+                details,
+                new_name,
+                ..
+            }) = event.typed_data()
+            else {
+                continue;
+            };
+            let Some(operation) = RegistryOperation::from_event_type(&event_type) else {
+                continue;
+            };
+            let key = normalize_key(&target_object);
+            let data = details.as_deref().map(RegistryData::decode);
+            let new_name = new_name.map(|n| normalize_key(&n));
+
+            match index.get(&key.to_lowercase()) {
+                Some(&i) => {
+                    let entry = &mut entries[i];
+                    entry.operation = operation;
+                    if data.is_some() || operation != RegistryOperation::SetValue {
+                        entry.data = data;
+                    }
+                    entry.new_name = new_name.or(entry.new_name.take());
+                    entry.last_seen = event.time_created;
+                    entry.writes += 1;
+                    if !entry.images.contains(&image) {
+                        entry.images.push(image);
+                    }
+                }
+                None => {
+                    index.insert(key.to_lowercase(), entries.len());
+                    entries.push(RegistryEntry {
+                        persistence: persistence_kind(&key),
+                        key,
+                        operation,
+                        data,
+                        new_name,
+                        first_seen: event.time_created,
+                        last_seen: event.time_created,
+                        writes: 1,
+                        images: vec![image],
+                    });
+                }
+            }
+        }
+        Self { entries }
+    }
+
+    pub fn persistence(&self) -> impl Iterator<Item = &RegistryEntry> {
+        self.entries.iter().filter(|e| e.persistence.is_some())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
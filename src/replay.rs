@@ -0,0 +1,513 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::Duration;
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::agent::parse_log;
+use crate::agent::protocol::LogFormat;
+use crate::analysis_result::{artifact_dir, AnalysisResult, ExecutionLog};
+use crate::artifacts::{Artifact, ARTIFACTS_FILE};
+use crate::event_filter::EventFilter;
+use crate::memory::{MemoryAnalysis, MEMORY_FILE};
+use crate::report::{Delta, SandboxReport};
+use crate::storage::retention::{stored_report_path, DataClass};
+use crate::sysmon_event::SysmonEvent;
+use crate::telemetry::{TelemetryEvent, TelemetryReader};
+
+const STAGE_LOG: &str = "sysmon.log";
+const EXPECTED_EXTENSION: &str = "json";
+
+pub type Reporter<'a> = dyn Fn(&AnalysisResult) -> Result<SandboxReport> + Sync + 'a;
+
+fn stage_log(stage: usize) -> String {
+    match stage {
+        0 => STAGE_LOG.to_string(),
+        _ => format!("sysmon-reboot-{}.log", stage),
+    }
+}
+
+fn detect_format(log: &[u8]) -> LogFormat {
+    let text = String::from_utf8_lossy(&log[..log.len().min(256)]);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with('<') {
+        LogFormat::Xml
+    } else if text.starts_with('{') {
+        LogFormat::Vmi
+    } else if text.starts_with("type=") || text.starts_with("node=") {
+        LogFormat::Auditd
+    } else {
+        LogFormat::Syslog
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Some(serde_json::from_slice(&data)?))
+}
+
+fn recorded_stages(dir: &Path) -> Result<Vec<Vec<u8>>> {
+    let mut stages = Vec::new();
+    loop {
+        let path = dir.join(stage_log(stages.len()));
+        if !path.exists() {
+            break;
+        }
+        stages.push(fs::read(&path)?);
+    }
+    Ok(stages)
+}
+
+#[cfg(feature = "evtx")]
+fn recorded_evtx(dir: &Path) -> Result<Vec<SysmonEvent>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("evtx"))
+        })
+        .collect();
+    paths.sort();
+    let mut events = Vec::new();
+    for path in paths {
+        let mut reader = crate::evtx::EvtxReader::open(&path)?;
+        events.extend(reader.events().filter_map(Result::ok));
+    }
+    Ok(events)
+}
+
+fn clock_offset(raw: &[SysmonEvent], recorded: &[SysmonEvent]) -> Duration {
+    raw.iter()
+        .find_map(|event| {
+            let record_id = event.record_id?;
+            let recorded = recorded
+                .iter()
+                .find(|r| r.record_id == Some(record_id) && r.event_id == event.event_id)?;
+            Some(recorded.time_created - event.time_created)
+        })
+        .unwrap_or_else(Duration::zero)
+}
+
+fn telemetry_key(event: &TelemetryEvent) -> Option<String> {
+    serde_json::to_string(event).ok()
+}
+
+fn append_stage(
+    events: &mut Vec<SysmonEvent>,
+    telemetry: &mut Vec<TelemetryEvent>,
+    mut stage_events: Vec<SysmonEvent>,
+    mut stage_telemetry: Vec<TelemetryEvent>,
+    offset: Duration,
+) {
+    for event in &mut stage_events {
+        event.time_created += offset;
+    }
+    for event in &mut stage_telemetry {
+        event.set_time(event.time() + offset);
+    }
+    let seen: HashSet<String> = events.iter().map(SysmonEvent::dedup_key).collect();
+    events.extend(
+        stage_events
+            .into_iter()
+            .filter(|e| !seen.contains(&e.dedup_key())),
+    );
+    let seen: HashSet<String> = telemetry.iter().filter_map(telemetry_key).collect();
+    telemetry.extend(
+        stage_telemetry
+            .into_iter()
+            .filter(|e| telemetry_key(e).is_none_or(|key| !seen.contains(&key))),
+    );
+}
+
+pub fn replay_log(id: &str, log: &ExecutionLog) -> Result<Option<ExecutionLog>> {
+    let dir = PathBuf::from(artifact_dir(id, &log.id));
+    let stages = recorded_stages(&dir)?;
+    #[cfg(feature = "evtx")]
+    let evtx = recorded_evtx(&dir)?;
+    #[cfg(not(feature = "evtx"))]
+    let evtx: Vec<SysmonEvent> = Vec::new();
+    if stages.is_empty() && evtx.is_empty() {
+        return Ok(None);
+    }
+
+    let mut events = Vec::new();
+    let mut telemetry = Vec::new();
+    for data in &stages {
+        let format = detect_format(data);
+        let stage_events = parse_log(data, format);
+        let stage_telemetry = match format {
+            LogFormat::Xml => TelemetryReader::new(BufReader::new(data.as_slice()))
+                .filter_map(Result::ok)
+                .filter(|e| !matches!(e, TelemetryEvent::Sysmon(_)))
+                .collect(),
+            LogFormat::Syslog | LogFormat::Auditd | LogFormat::Vmi => Vec::new(),
+        };
+        let offset = clock_offset(&stage_events, &log.sysmon_events);
+        append_stage(
+            &mut events,
+            &mut telemetry,
+            stage_events,
+            stage_telemetry,
+            offset,
+        );
+    }
+    let offset = clock_offset(&evtx, &log.sysmon_events);
+    append_stage(&mut events, &mut telemetry, evtx, Vec::new(), offset);
+    telemetry.extend(
+        log.telemetry
+            .iter()
+            .filter(|e| matches!(e, TelemetryEvent::ApiCall(_)))
+            .cloned(),
+    );
+    telemetry.sort_by_key(TelemetryEvent::time);
+
+    let (artifacts, created_files) = match read_json::<Vec<Artifact>>(&dir.join(ARTIFACTS_FILE))? {
+        Some(artifacts) => {
+            let created_files: HashMap<String, String> = artifacts
+                .iter()
+                .map(|a| (a.path.clone(), a.file_name().to_string()))
+                .collect();
+            (artifacts, created_files)
+        }
+        None => (log.artifacts.clone(), log.created_files.clone()),
+    };
+    let memory = read_json::<Vec<MemoryAnalysis>>(&dir.join(MEMORY_FILE))?
+        .unwrap_or_else(|| log.memory.clone());
+
+    Ok(Some(ExecutionLog {
+        sysmon_events: events,
+        telemetry,
+        artifacts,
+        created_files,
+        memory,
+        purged: log
+            .purged
+            .iter()
+            .copied()
+            .filter(|c| *c != DataClass::Events)
+            .collect(),
+        ..log.clone()
+    }))
+}
+
+pub fn replay_result(result: &AnalysisResult) -> Result<AnalysisResult> {
+    let mut execution_logs = Vec::new();
+    let mut replayed = 0;
+    for log in &result.execution_logs {
+        match replay_log(&result.id, log)? {
+            Some(log) => {
+                replayed += 1;
+                execution_logs.push(log);
+            }
+            None => {
+                warn!(
+                    "No recorded telemetry for execution {} of {}, keeping stored events",
+                    log.id, result.id
+                );
+                execution_logs.push(log.clone());
+            }
+        }
+    }
+    if replayed == 0 {
+        bail!("No recorded telemetry for analysis {}", result.id);
+    }
+    Ok(AnalysisResult {
+        id: result.id.clone(),
+        hash: result.hash.clone(),
+        execution_logs,
+        tenant: result.tenant.clone(),
+        metadata: result.metadata.clone(),
+    })
+}
+
+pub fn load_corpus<P: AsRef<Path>>(path: P) -> Result<Vec<AnalysisResult>> {
+    let path = path.as_ref();
+    let mut files = if path.is_dir() {
+        fs::read_dir(path)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == EXPECTED_EXTENSION))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    files.sort();
+    let mut results = Vec::new();
+    for file in files {
+        let result = read_json::<AnalysisResult>(&file)?
+            .with_context(|| format!("Analysis result {} does not exist", file.display()))?;
+        results.push(result);
+    }
+    Ok(results)
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReportSummary {
+    pub score: f64,
+    pub verdict: Option<String>,
+    pub detections: BTreeSet<String>,
+    pub techniques: BTreeSet<String>,
+}
+
+impl ReportSummary {
+    pub fn from_json(report: &Value) -> Self {
+        let detections = report["detections"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|d| {
+                let source = d["source"].as_str()?;
+                let name = d["name"].as_str()?;
+                Some(format!("{}: {}", source, name))
+            })
+            .collect();
+        let techniques = report["techniques"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|t| t["id"].as_str().map(str::to_string))
+            .collect();
+        Self {
+            score: report["score"]["score"].as_f64().unwrap_or_default(),
+            verdict: report["verdict"]["verdict"].as_str().map(str::to_string),
+            detections,
+            techniques,
+        }
+    }
+}
+
+fn delta(expected: &BTreeSet<String>, actual: &BTreeSet<String>) -> Delta<String> {
+    Delta {
+        added: actual.difference(expected).cloned().collect(),
+        removed: expected.difference(actual).cloned().collect(),
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ReplayOutcome {
+    pub id: String,
+    pub execution_id: String,
+    pub expected: Option<ReportSummary>,
+    pub actual: ReportSummary,
+    pub detections: Delta<String>,
+    pub techniques: Delta<String>,
+    pub score_delta: f64,
+    pub verdict_changed: bool,
+}
+
+impl ReplayOutcome {
+    fn new(
+        id: &str,
+        execution_id: &str,
+        expected: Option<ReportSummary>,
+        actual: ReportSummary,
+    ) -> Self {
+        let empty = BTreeSet::new();
+        let (detections, techniques) = match &expected {
+            Some(expected) => (
+                delta(&expected.detections, &actual.detections),
+                delta(&expected.techniques, &actual.techniques),
+            ),
+            None => (delta(&empty, &empty), delta(&empty, &empty)),
+        };
+        let score_delta = expected.as_ref().map_or(0.0, |e| actual.score - e.score);
+        let verdict_changed = expected
+            .as_ref()
+            .is_some_and(|e| e.verdict != actual.verdict);
+        Self {
+            id: id.to_string(),
+            execution_id: execution_id.to_string(),
+            expected,
+            actual,
+            detections,
+            techniques,
+            score_delta,
+            verdict_changed,
+        }
+    }
+
+    pub fn changed(&self) -> bool {
+        !self.detections.is_empty()
+            || !self.techniques.is_empty()
+            || self.score_delta != 0.0
+            || self.verdict_changed
+    }
+}
+
+pub struct Replayer<'a> {
+    reporter: &'a Reporter<'a>,
+    filter: Option<&'a EventFilter>,
+    expected_dir: Option<PathBuf>,
+    update: bool,
+}
+
+impl<'a> Replayer<'a> {
+    pub fn new(reporter: &'a Reporter<'a>) -> Self {
+        Self {
+            reporter,
+            filter: None,
+            expected_dir: None,
+            update: false,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: &'a EventFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn with_expected_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.expected_dir = Some(dir.into());
+        self
+    }
+
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    fn expected_path(&self, id: &str, execution_id: &str) -> PathBuf {
+        match &self.expected_dir {
+            Some(dir) => dir
+                .join(id)
+                .join(execution_id)
+                .with_extension(EXPECTED_EXTENSION),
+            None => PathBuf::from(stored_report_path(id, execution_id)),
+        }
+    }
+
+    pub fn replay(&self, result: &AnalysisResult) -> Result<(SandboxReport, ReplayOutcome)> {
+        let mut replayed = replay_result(result)?;
+        if let Some(filter) = self.filter {
+            for log in &mut replayed.execution_logs {
+                filter.apply_log(log);
+            }
+        }
+        let report = (self.reporter)(&replayed)?;
+        let actual = serde_json::to_value(&report)?;
+
+        let path = self.expected_path(&report.id, &report.execution_id);
+        let expected = read_json::<Value>(&path)?;
+        let outcome = ReplayOutcome::new(
+            &report.id,
+            &report.execution_id,
+            expected.as_ref().map(ReportSummary::from_json),
+            ReportSummary::from_json(&actual),
+        );
+        if self.update && self.expected_dir.is_some() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, serde_json::to_vec_pretty(&actual)?)?;
+        }
+        Ok((report, outcome))
+    }
+
+    pub fn replay_corpus(&self, results: &[AnalysisResult]) -> Vec<ReplayOutcome> {
+        let mut outcomes = Vec::new();
+        for result in results {
+            match self.replay(result) {
+                Ok((_, outcome)) => outcomes.push(outcome),
+                Err(e) => warn!("Failed to replay analysis {}: {:#}", result.id, e),
+            }
+        }
+        info!(
+            "Replayed {} of {} analyses, {} changed",
+            outcomes.len(),
+            results.len(),
+            outcomes.iter().filter(|o| o.changed()).count()
+        );
+        outcomes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+
+    const FIXTURE: &str = "tests/fixtures/replay";
+
+    fn event(record_id: Option<u64>, time: &str) -> SysmonEvent {
+        SysmonEvent {
+            event_id: crate::sysmon_event::SysmonEventId::PROCESS_CREATE,
+            time_created: chrono::DateTime::parse_from_rfc3339(time).unwrap(),
+            computer: Some("WIN10".to_string()),
+            record_id,
+            channel: None,
+            event_data: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn stages_drop_overlap_but_keep_events_sharing_a_timestamp() {
+        let time = "2024-01-01T10:00:01Z";
+        let mut events = vec![event(Some(1), time), event(Some(2), time)];
+        let mut telemetry = Vec::new();
+        append_stage(
+            &mut events,
+            &mut telemetry,
+            vec![event(Some(2), time), event(Some(3), time)],
+            Vec::new(),
+            Duration::zero(),
+        );
+        let ids: Vec<Option<u64>> = events.iter().map(|e| e.record_id).collect();
+        assert_eq!(ids, [Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn replays_recorded_fixture_against_expected_summary() {
+        let id = format!("replay-{}", Uuid::new_v4());
+        let execution_id = "run-1";
+        let dir = PathBuf::from(artifact_dir(&id, execution_id));
+        fs::create_dir_all(&dir).unwrap();
+        for stage in 0..2 {
+            let name = stage_log(stage);
+            fs::copy(Path::new(FIXTURE).join(&name), dir.join(&name)).unwrap();
+        }
+        let expected_dir = std::env::temp_dir().join(format!("replay-{}", Uuid::new_v4()));
+        fs::create_dir_all(expected_dir.join(&id)).unwrap();
+        fs::copy(
+            Path::new(FIXTURE).join("expected.json"),
+            expected_dir
+                .join(&id)
+                .join(execution_id)
+                .with_extension(EXPECTED_EXTENSION),
+        )
+        .unwrap();
+
+        let result: AnalysisResult = serde_json::from_value(json!({
+            "id": id,
+            "hash": "0".repeat(64),
+            "execution_logs": [{
+                "id": execution_id,
+                "time": "2024-01-01T10:00:00Z",
+                "sysmon_events": [],
+                "created_files": {},
+            }],
+        }))
+        .unwrap();
+        let reporter = |result: &AnalysisResult| SandboxReport::from_analysis_result(result);
+        let replayed = Replayer::new(&reporter)
+            .with_expected_dir(&expected_dir)
+            .replay(&result);
+        let _ = fs::remove_dir_all(Path::new(&artifact_dir(&id, "")).parent().unwrap());
+        let _ = fs::remove_dir_all(&expected_dir);
+
+        let (report, outcome) = replayed.unwrap();
+        let records: Vec<Option<u64>> = report.events.iter().map(|e| e.record_id).collect();
+        assert_eq!(records, [Some(1), Some(2), Some(3)]);
+        assert!(outcome.expected.is_some());
+        assert!(!outcome.changed(), "{:?}", outcome);
+    }
+}
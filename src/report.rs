@@ -0,0 +1,2800 @@
+mod diff;
+mod matrix;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::analysis_result::{
+    artifact_dir, container_path, sample_path, scripts_dir, AnalysisResult, ExecutionLog,
+};
+use crate::analyzer::api_trace::detect_api_abuse;
+use crate::analyzer::capability::{Capability, CapabilityRules, FileFeatures};
+use crate::analyzer::clipboard::{
+    attach_captures, detect_clippers, ClipboardCapture, ClipperAlert,
+};
+use crate::analyzer::code_signing::{
+    SignatureValidation, SignedTarget, TrustPolicy, ValidationStatus,
+};
+use crate::analyzer::credential_access::{detect_credential_theft, CredentialTheft};
+use crate::analyzer::driver::{analyze_driver_loads, DriverBlocklist, DriverLoad, InstallMethod};
+use crate::analyzer::fingerprint::{connect_events, FingerprintBlocklist};
+use crate::analyzer::masquerading::detect_masquerading;
+use crate::analyzer::parentage::ParentageRules;
+use crate::analyzer::persistence::{
+    detect_scheduled_tasks, detect_service_install, reconstruct_wmi_subscriptions,
+    ScheduledTaskAlert, ServiceInstallAlert, WmiSubscription,
+};
+use crate::analyzer::ransomware::{
+    detect_ransomware, RansomwareIndicator, RansomwareOptions, RansomwareVerdict,
+};
+use crate::analyzer::script_block::{detect_suspicious_script_blocks, ScriptBlockSignature};
+use crate::analyzer::sigma::{self, SigmaRule};
+use crate::analyzer::signature::SignatureRegistry;
+use crate::analyzer::yara_scan::ArtifactMatch;
+use crate::artifacts::{is_file_event, Artifact};
+use crate::attack::{summarize_techniques, technique_hits, TechniqueSummary};
+use crate::beacon::{self, Beacon};
+use crate::browsing::BrowsingChain;
+use crate::clock::ClockSkew;
+use crate::cmdline::match_lolbins;
+use crate::config_extractor::{ConfigExtractorRegistry, ExtractionInput, InputKind, MalwareConfig};
+use crate::dns::encrypted::{self, merge_encrypted_dns, EncryptedDnsSession};
+use crate::dns::{merge_captured_dns, resolution_timeline, DnsResolution};
+use crate::encoding::{is_bidi_control, is_invisible, safe_display, EncodingFlag};
+use crate::enrichment::geo::{GeoDatabase, GeoInfo};
+use crate::enrichment::{Enrichment, ENRICHMENT_FILE};
+use crate::event_data::{Platform, TypedEventData};
+use crate::export::stix::to_stix_bundle;
+use crate::filesystem::{summarize, summarize_with, FilesystemOptions, ProcessFileActivity};
+use crate::flags::RegistryValueType;
+use crate::ioc::{is_private, is_routable, Confidence, Ioc, IocSet};
+use crate::lineage::{descendants, Derivation, Descendant};
+use crate::memory::MemoryAnalysis;
+use crate::metadata::SampleMetadata;
+use crate::netsim::{attribute, AttributedRequest, SimulatedRequest, NETSIM_LOG_FILE};
+use crate::network::NetworkConnect;
+use crate::orchestrator::image::ImageMetadata;
+use crate::orchestrator::limits::LimitViolation;
+use crate::path::normalize;
+use crate::pcap::http::{self as pcap_http, HttpExchange};
+use crate::pcap::ids::{self, IdsAlert, IDS_DIR};
+use crate::pcap::{self, correlate, flows, CorrelatedFlow, Packet, PCAP_FILE_NAME};
+use crate::plugin::{AnalysisContext, AnalyzerRegistry, AnalyzerResult};
+use crate::process_tree::{Process, ProcessTree};
+use crate::quarantine::ParseFailureSummary;
+use crate::registry::RegistryDiff;
+use crate::schema::SCHEMA_VERSION;
+use crate::scoring::{score_with, Score, ScoringOptions};
+use crate::static_analysis::container::{read_container, Container};
+use crate::static_analysis::email::{self, Email};
+use crate::static_analysis::pe::{self as static_pe, PeInfo};
+use crate::static_analysis::{
+    captured_scripts, deobfuscate_powershell, extract_scripts, read_scripts, ExtractedScript,
+};
+use crate::sync_objects::{self, ObservedMutex, ProcessSyncObjects, SyncObjectKind};
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+use crate::tamper::FileTamper;
+use crate::telemetry::{
+    amsi_scans, api_calls, derived_sysmon_events, logons, script_blocks, AmsiScan, ApiCall, Logon,
+    ScriptBlock,
+};
+use crate::timeline::{read_screenshots, EntryData, Timeline, RECORDING_FILE};
+use crate::verdict::{Assessment, VerdictPolicy};
+
+pub use diff::{
+    ChangedFile, ChangedRegistryKey, Delta, Destination, DroppedFile, FileDiff, ProcessSummary,
+    RegistryKeyChange, RegistryKeyDiff, ReportDiff, RunInfo,
+};
+pub use matrix::{BehaviorKind, BehaviorMatrix, MatrixRow, ProfileSummary};
+
+const TRAFFIC_TIME_TOLERANCE_SECS: i64 = 120;
+const SCREENSHOT_WIDTH: u32 = 640;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeKind {
+    Created,
+    CreationTimeChanged,
+    StreamCreated,
+    Deleted,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileChange {
+    pub kind: FileChangeKind,
+    pub time: DateTime<FixedOffset>,
+    pub image: String,
+    pub path: String,
+    pub sha256: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegistryChange {
+    pub event_type: String,
+    pub time: DateTime<FixedOffset>,
+    pub image: String,
+    pub target_object: String,
+    pub details: Option<String>,
+    pub value_type: Option<RegistryValueType>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Detection {
+    pub source: String,
+    pub name: String,
+    pub level: Option<String>,
+    pub tags: Vec<String>,
+    pub events: Vec<SysmonEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SandboxReport {
+    pub schema_version: u32,
+    pub id: String,
+    pub hash: String,
+    pub execution_id: String,
+    pub time: DateTime<Local>,
+    pub platform: Option<Platform>,
+    pub image: Option<ImageMetadata>,
+    pub terminated: Option<LimitViolation>,
+    pub metadata: SampleMetadata,
+    pub score: Score,
+    pub verdict: Assessment,
+    pub static_analysis: Option<PeInfo>,
+    pub code_signing: Vec<SignatureValidation>,
+    pub email: Option<Email>,
+    pub container: Option<Container>,
+    pub capabilities: Vec<Capability>,
+    pub scripts: Vec<ExtractedScript>,
+    pub captured_scripts: Vec<ExtractedScript>,
+    pub process_tree: ProcessTree,
+    pub network: Vec<NetworkConnect>,
+    pub traffic: Vec<CorrelatedFlow>,
+    pub clock: Option<ClockSkew>,
+    pub ids_alerts: Vec<IdsAlert>,
+    pub http: Vec<HttpExchange>,
+    pub dns: Vec<DnsResolution>,
+    pub encrypted_dns: Vec<EncryptedDnsSession>,
+    pub simulated_requests: Vec<AttributedRequest>,
+    pub browsing: Option<BrowsingChain>,
+    pub beacons: Vec<Beacon>,
+    pub file_changes: Vec<FileChange>,
+    pub artifacts: Vec<Artifact>,
+    pub filesystem: Vec<ProcessFileActivity>,
+    pub registry_changes: Vec<RegistryChange>,
+    pub registry: RegistryDiff,
+    pub sync_objects: Vec<ProcessSyncObjects>,
+    pub services: Vec<ServiceInstallAlert>,
+    pub scheduled_tasks: Vec<ScheduledTaskAlert>,
+    pub wmi_subscriptions: Vec<WmiSubscription>,
+    pub ransomware: Option<RansomwareVerdict>,
+    pub credential_theft: Vec<CredentialTheft>,
+    pub drivers: Vec<DriverLoad>,
+    pub tampered: Vec<FileTamper>,
+    pub clipboard: Vec<ClipboardCapture>,
+    pub clippers: Vec<ClipperAlert>,
+    pub detections: Vec<Detection>,
+    pub techniques: Vec<TechniqueSummary>,
+    pub iocs: IocSet,
+    pub enrichment: Vec<Enrichment>,
+    pub geo: Vec<GeoInfo>,
+    pub script_blocks: Vec<ScriptBlock>,
+    pub amsi: Vec<AmsiScan>,
+    pub logons: Vec<Logon>,
+    pub api_calls: Vec<ApiCall>,
+    pub memory: Vec<MemoryAnalysis>,
+    pub configs: Vec<MalwareConfig>,
+    pub analyzers: Vec<AnalyzerResult>,
+    pub matrix: Option<BehaviorMatrix>,
+    pub parse_failures: ParseFailureSummary,
+    pub derivation: Vec<Derivation>,
+    pub descendants: Vec<Descendant>,
+    pub events: Vec<SysmonEvent>,
+}
+
+fn ids_alert_matches(alert: &IdsAlert, event: &SysmonEvent) -> bool {
+    let Some(connect) = NetworkConnect::from_event(event) else {
+        return false;
+    };
+    if alert.process_guid.is_some() && connect.process_guid != alert.process_guid {
+        return false;
+    }
+    [alert.source, alert.destination]
+        .into_iter()
+        .flatten()
+        .any(|a| {
+            a.port() != 0
+                && connect.destination_ip == Some(a.ip())
+                && connect.destination_port == Some(a.port())
+        })
+}
+
+pub fn ransomware_detection(verdict: &RansomwareVerdict) -> Detection {
+    let mut tags = vec!["attack.impact".to_string(), "attack.t1486".to_string()];
+    if verdict
+        .indicators
+        .contains(&RansomwareIndicator::RecoveryInhibition)
+    {
+        tags.push("attack.t1490".to_string());
+    }
+    Detection {
+        source: "ransomware".to_string(),
+        name: format!(
+            "Ransomware behavior ({})",
+            verdict
+                .evidence
+                .iter()
+                .map(|e| e.description.as_str())
+                .join("; ")
+        ),
+        level: Some("critical".to_string()),
+        tags,
+        events: verdict
+            .evidence
+            .iter()
+            .flat_map(|e| e.events.iter().cloned())
+            .collect(),
+    }
+}
+
+fn file_change(event: &SysmonEvent) -> Option<FileChange> {
+    let (kind, image, path, hashes) = match event.typed_data() {
+        TypedEventData::FileCreate(d) => (
+            FileChangeKind::Created,
+            d.image,
+            d.target_filename,
+            d.hashes,
+        ),
+        TypedEventData::FileCreateTime(d) => (
+            FileChangeKind::CreationTimeChanged,
+            d.image,
+            d.target_filename,
+            d.hashes,
+        ),
+        TypedEventData::FileCreateStreamHash(d) => (
+            FileChangeKind::StreamCreated,
+            d.image,
+            d.target_filename,
+            d.hashes,
+        ),
+        TypedEventData::FileDelete(d) => (
+            FileChangeKind::Deleted,
+            d.image,
+            d.target_filename,
+            d.hashes,
+        ),
+        _ => return None,
+    };
+    Some(FileChange {
+        kind,
+        time: event.time_created,
+        image,
+        path,
+        sha256: hashes.sha256,
+    })
+}
+
+fn registry_change(event: &SysmonEvent) -> Option<RegistryChange> {
+    let TypedEventData::RegistryEvent(d) = event.typed_data() else {
+        return None;
+    };
+    Some(RegistryChange {
+        event_type: d.event_type,
+        time: event.time_created,
+        image: d.image,
+        target_object: d.target_object,
+        details: d.details,
+        value_type: d.value_type,
+    })
+}
+
+impl SandboxReport {
+    pub fn from_analysis_result(result: &AnalysisResult) -> Result<Self> {
+        let log = result
+            .execution_logs
+            .last()
+            .context("No execution log for the sample")?;
+        let mut events = log.sysmon_events.clone();
+        events.extend(derived_sysmon_events(&log.telemetry));
+        events.sort_by_key(|e| e.time_created);
+        attach_captures(&mut events, &log.clipboard);
+        let events = &events;
+        let captured = captured_scripts(&log.telemetry);
+
+        let mut detections: Vec<Detection> = Vec::new();
+        for hit in technique_hits(events)? {
+            let tactic = hit.tactic.tag();
+            let existing = detections
+                .iter_mut()
+                .find(|d| d.name == hit.technique.name && d.tags.first() == Some(&tactic));
+            match existing {
+                Some(detection) => detection.events.push(hit.event),
+                None => detections.push(Detection {
+                    source: "builtin".to_string(),
+                    name: hit.technique.name.to_string(),
+                    level: None,
+                    tags: vec![
+                        tactic,
+                        format!("attack.{}", hit.technique.id.to_lowercase()),
+                    ],
+                    events: vec![hit.event],
+                }),
+            }
+        }
+
+        let mut report = Self {
+            schema_version: SCHEMA_VERSION,
+            id: result.id.clone(),
+            hash: result.hash.clone(),
+            execution_id: log.id.clone(),
+            time: log.time,
+            platform: events.first().map(SysmonEvent::platform),
+            image: log.image.clone(),
+            terminated: log.terminated.clone(),
+            metadata: result.metadata.merged(&log.metadata),
+            score: score_with(events, &ScoringOptions::default()),
+            verdict: Assessment::default(),
+            static_analysis: None,
+            code_signing: Vec::new(),
+            email: None,
+            container: None,
+            capabilities: Vec::new(),
+            scripts: deobfuscate_powershell(events, &captured),
+            captured_scripts: captured,
+            process_tree: ProcessTree::from_events(events),
+            network: events
+                .iter()
+                .filter_map(NetworkConnect::from_event)
+                .collect(),
+            traffic: Vec::new(),
+            clock: None,
+            ids_alerts: Vec::new(),
+            http: Vec::new(),
+            dns: resolution_timeline(events),
+            encrypted_dns: Vec::new(),
+            simulated_requests: Vec::new(),
+            browsing: None,
+            beacons: Vec::new(),
+            file_changes: events
+                .iter()
+                .filter_map(file_change)
+                .map(|mut change| {
+                    let artifact = log
+                        .artifacts
+                        .iter()
+                        .find(|a| normalize(&a.path) == normalize(&change.path))
+                        .map(|a| a.hashes.sha256.clone());
+                    change.sha256 = artifact.or(change.sha256);
+                    change
+                })
+                .collect(),
+            artifacts: log.artifacts.clone(),
+            filesystem: summarize(events),
+            registry_changes: events.iter().filter_map(registry_change).collect(),
+            registry: RegistryDiff::from_events(events),
+            sync_objects: sync_objects::summarize(events, &log.mutexes),
+            services: detect_service_install(events),
+            scheduled_tasks: detect_scheduled_tasks(events),
+            wmi_subscriptions: reconstruct_wmi_subscriptions(events),
+            ransomware: detect_ransomware(events, &log.artifacts, &RansomwareOptions::default()),
+            credential_theft: detect_credential_theft(events, &log.memory),
+            drivers: Vec::new(),
+            tampered: log.tampered.clone(),
+            clipboard: log.clipboard.clone(),
+            clippers: detect_clippers(events, &log.clipboard),
+            detections,
+            techniques: Vec::new(),
+            iocs: IocSet::from_events(events),
+            enrichment: Vec::new(),
+            geo: Vec::new(),
+            script_blocks: script_blocks(&log.telemetry),
+            amsi: amsi_scans(&log.telemetry),
+            logons: logons(&log.telemetry),
+            api_calls: api_calls(&log.telemetry),
+            memory: log.memory.clone(),
+            configs: Vec::new(),
+            analyzers: Vec::new(),
+            matrix: None,
+            parse_failures: log.parse_failures.clone(),
+            derivation: log.derivation.clone(),
+            descendants: Vec::new(),
+            events: events.clone(),
+        };
+        report
+            .score
+            .add_memory(&report.memory, &ScoringOptions::default());
+        report.add_script_block_detections();
+        report.add_masquerading_detections();
+        report.add_amsi_detections();
+        report.add_api_trace_detections();
+        report.add_lolbin_detections();
+        report.add_sync_object_detections(&log.mutexes);
+        report.add_wmi_detections();
+        report.add_ransomware_detection();
+        report.add_credential_theft_detections();
+        report.add_tamper_detections();
+        report.add_clipper_detections();
+        report.add_drivers(&DriverBlocklist::default());
+        report.add_payload_iocs();
+        if let Some(url) = &log.url {
+            report.update_browsing(url);
+        }
+        report.update_techniques();
+        report.add_verdict(&VerdictPolicy::default());
+        Ok(report)
+    }
+
+    pub fn from_events(hash: &str, events: Vec<SysmonEvent>) -> Result<Self> {
+        Self::from_analysis_result(&AnalysisResult {
+            id: Uuid::new_v4().to_string(),
+            hash: hash.to_string(),
+            execution_logs: vec![ExecutionLog {
+                id: Uuid::new_v4().to_string(),
+                time: Local::now(),
+                sysmon_events: events,
+                created_files: Default::default(),
+                artifacts: Vec::new(),
+                telemetry: Vec::new(),
+                memory: Vec::new(),
+                machine: None,
+                snapshot: None,
+                mutexes: Vec::new(),
+                clipboard: Vec::new(),
+                image: None,
+                terminated: None,
+                purged: Vec::new(),
+                url: None,
+                profile: None,
+                tampered: Vec::new(),
+                parse_failures: ParseFailureSummary::default(),
+                derivation: Vec::new(),
+                metadata: SampleMetadata::default(),
+            }],
+            tenant: None,
+            metadata: SampleMetadata::default(),
+        })
+    }
+
+    fn update_techniques(&mut self) {
+        self.techniques = summarize_techniques(
+            self.detections
+                .iter()
+                .map(|d| (d.source.as_str(), d.tags.as_slice(), d.events.len())),
+        );
+    }
+
+    pub fn add_sigma_detections(&mut self, rules: &[SigmaRule]) {
+        for m in sigma::evaluate(rules, &self.events) {
+            self.detections.push(Detection {
+                source: "sigma".to_string(),
+                name: m.title,
+                level: m.level,
+                tags: m.tags,
+                events: vec![m.event],
+            });
+        }
+        self.update_techniques();
+    }
+
+    fn add_script_block_detections(&mut self) {
+        for alert in detect_suspicious_script_blocks(&self.script_blocks) {
+            self.detections.push(Detection {
+                source: "script_block".to_string(),
+                name: alert.signature,
+                level: Some("high".to_string()),
+                tags: vec![format!("attack.{}", alert.technique.to_lowercase())],
+                events: Vec::new(),
+            });
+        }
+    }
+
+    fn add_masquerading_detections(&mut self) {
+        for alert in detect_masquerading(&self.events) {
+            let level = match alert.flags.contains(&EncodingFlag::BidiOverride) {
+                true => "high",
+                false => "medium",
+            };
+            self.detections.push(Detection {
+                source: "masquerading".to_string(),
+                name: format!("{}: {}", alert.title(), safe_display(&alert.value)),
+                level: Some(level.to_string()),
+                tags: vec![
+                    "attack.defense_evasion".to_string(),
+                    format!("attack.{}", alert.technique().to_lowercase()),
+                ],
+                events: alert.events,
+            });
+        }
+    }
+
+    fn add_amsi_detections(&mut self) {
+        let signatures = ScriptBlockSignature::defaults();
+        let mut detections: Vec<Detection> = Vec::new();
+        for scan in &self.amsi {
+            let mut hits: Vec<(String, Option<String>)> = signatures
+                .iter()
+                .filter(|s| s.is_match(&scan.content))
+                .map(|s| (s.name.clone(), Some(s.technique.to_lowercase())))
+                .collect();
+            if scan.is_detected() {
+                let subject = scan
+                    .content_name
+                    .as_deref()
+                    .or(scan.app_name.as_deref())
+                    .unwrap_or("script content");
+                hits.push((format!("AMSI flagged {}", subject), None));
+            }
+            for (name, technique) in hits {
+                if detections.iter().any(|d| d.name == name) {
+                    continue;
+                }
+                detections.push(Detection {
+                    source: "amsi".to_string(),
+                    name,
+                    level: Some("high".to_string()),
+                    tags: technique
+                        .map(|t| format!("attack.{}", t))
+                        .into_iter()
+                        .collect(),
+                    events: Vec::new(),
+                });
+            }
+        }
+        self.detections.extend(detections);
+    }
+
+    fn add_api_trace_detections(&mut self) {
+        for alert in detect_api_abuse(&self.api_calls) {
+            let process = self
+                .events
+                .iter()
+                .find(|e| {
+                    e.event_id == SysmonEventId::PROCESS_CREATE
+                        && e.event_data.get("ProcessId") == Some(&alert.process_id.to_string())
+                })
+                .cloned();
+            self.detections.push(Detection {
+                source: "api_trace".to_string(),
+                name: alert.signature,
+                level: Some(alert.level.to_string()),
+                tags: vec![format!("attack.{}", alert.technique.to_lowercase())],
+                events: process.into_iter().collect(),
+            });
+        }
+    }
+
+    fn add_lolbin_detections(&mut self) {
+        for event in &self.events {
+            if event.event_id != SysmonEventId::PROCESS_CREATE {
+                continue;
+            }
+            let Some(command_line) = event.event_data.get("CommandLine") else {
+                continue;
+            };
+            for lolbin in match_lolbins(command_line) {
+                let existing = self
+                    .detections
+                    .iter_mut()
+                    .find(|d| d.source == "lolbas" && d.name == lolbin.description);
+                match existing {
+                    Some(detection) => detection.events.push(event.clone()),
+                    None => self.detections.push(Detection {
+                        source: "lolbas".to_string(),
+                        name: lolbin.description.to_string(),
+                        level: Some("medium".to_string()),
+                        tags: vec![format!("attack.{}", lolbin.technique.to_lowercase())],
+                        events: vec![event.clone()],
+                    }),
+                }
+            }
+        }
+    }
+
+    fn add_sync_object_detections(&mut self, mutexes: &[ObservedMutex]) {
+        for mutex in mutexes {
+            self.iocs.insert(
+                Ioc::Mutex(mutex.name.clone()),
+                Confidence::Medium,
+                mutex.time.into(),
+            );
+        }
+        for m in sync_objects::known_objects_in(&self.events, mutexes) {
+            let mut tags = vec![format!(
+                "malware.{}",
+                m.family.to_lowercase().replace(' ', "_")
+            )];
+            let kind = match m.kind {
+                SyncObjectKind::Pipe => {
+                    tags.push("attack.t1055".to_string());
+                    "named pipe"
+                }
+                SyncObjectKind::Mutex => "mutex",
+            };
+            self.detections.push(Detection {
+                source: "sync_objects".to_string(),
+                name: format!("Known {} {} ({})", m.family, kind, m.name),
+                level: Some("high".to_string()),
+                tags,
+                events: m.events,
+            });
+        }
+    }
+
+    fn add_wmi_detections(&mut self) {
+        for subscription in &self.wmi_subscriptions {
+            let action = subscription
+                .destination
+                .as_deref()
+                .unwrap_or(&subscription.consumer);
+            self.detections.push(Detection {
+                source: "wmi".to_string(),
+                name: format!(
+                    "WMI event subscription {} -> {}",
+                    subscription.filter, action
+                ),
+                level: Some("high".to_string()),
+                tags: vec![
+                    "attack.persistence".to_string(),
+                    "attack.t1546.003".to_string(),
+                ],
+                events: subscription.events.clone(),
+            });
+        }
+    }
+
+    fn add_ransomware_detection(&mut self) {
+        if let Some(verdict) = &self.ransomware {
+            self.detections.push(ransomware_detection(verdict));
+        }
+    }
+
+    fn add_clipper_detections(&mut self) {
+        for clipper in &self.clippers {
+            self.detections.push(Detection {
+                source: "clipboard".to_string(),
+                name: format!(
+                    "{} address replaced in clipboard by {}",
+                    clipper.currency,
+                    clipper.image.as_deref().unwrap_or("unknown process")
+                ),
+                level: Some("high".to_string()),
+                tags: vec![
+                    "attack.collection".to_string(),
+                    "attack.t1115".to_string(),
+                    "attack.impact".to_string(),
+                    "attack.t1565.002".to_string(),
+                ],
+                events: clipper.events.clone(),
+            });
+        }
+    }
+
+    pub fn add_drivers(&mut self, blocklist: &DriverBlocklist) {
+        self.drivers = analyze_driver_loads(&self.events, blocklist);
+        self.detections.retain(|d| d.source != "driver");
+        for driver in &self.drivers {
+            let loaded_by = driver
+                .installer
+                .as_ref()
+                .map(|i| i.image.as_deref().unwrap_or("unknown process"));
+            let mut problems = Vec::new();
+            if driver.unsigned() {
+                problems.push("unsigned");
+            }
+            if driver.revoked() {
+                problems.push("revoked signature");
+            }
+            let (name, level, mut tags) = match (&driver.vulnerable, loaded_by) {
+                (Some(vulnerable), _) => (
+                    format!(
+                        "Known vulnerable driver {} loaded ({})",
+                        driver.path,
+                        [
+                            Some(vulnerable.driver.description.as_str()),
+                            vulnerable.driver.cve.as_deref(),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .join(", ")
+                    ),
+                    if loaded_by.is_some() {
+                        "critical"
+                    } else {
+                        "high"
+                    },
+                    vec![
+                        "attack.privilege_escalation".to_string(),
+                        "attack.t1068".to_string(),
+                        "attack.defense_evasion".to_string(),
+                        "attack.t1562.001".to_string(),
+                    ],
+                ),
+                (None, Some(image)) if problems.is_empty() => (
+                    format!("Kernel driver {} loaded by {}", driver.path, image),
+                    "high",
+                    vec![
+                        "attack.defense_evasion".to_string(),
+                        "attack.t1014".to_string(),
+                    ],
+                ),
+                (None, Some(image)) => (
+                    format!(
+                        "Kernel driver {} loaded by {} ({})",
+                        driver.path,
+                        image,
+                        problems.join(", ")
+                    ),
+                    "high",
+                    vec![
+                        "attack.defense_evasion".to_string(),
+                        "attack.t1014".to_string(),
+                    ],
+                ),
+                (None, None) => continue,
+            };
+            let mut events = vec![driver.event.clone()];
+            if let Some(installer) = &driver.installer {
+                if installer.method == InstallMethod::Service {
+                    tags.push("attack.persistence".to_string());
+                    tags.push("attack.t1543.003".to_string());
+                }
+                events.insert(0, installer.event.clone());
+            }
+            self.detections.push(Detection {
+                source: "driver".to_string(),
+                name,
+                level: Some(level.to_string()),
+                tags,
+                events,
+            });
+        }
+        self.update_techniques();
+    }
+
+    fn add_payload_iocs(&mut self) {
+        let time = self.time.into();
+        for payload in self.memory.iter().flat_map(|m| &m.payloads) {
+            self.iocs
+                .insert(Ioc::Sha256(payload.sha256.clone()), Confidence::High, time);
+            if let Some(imphash) = &payload.imphash {
+                self.iocs
+                    .insert(Ioc::Imphash(imphash.clone()), Confidence::Medium, time);
+            }
+        }
+    }
+
+    fn add_credential_theft_detections(&mut self) {
+        for theft in &self.credential_theft {
+            let technique = theft.kind.technique();
+            let name = format!("Credential theft: {}", theft.description);
+            let existing = self
+                .detections
+                .iter_mut()
+                .find(|d| d.source == "credential_access" && d.name == name);
+            match existing {
+                Some(detection) => detection.events.extend(theft.event.clone()),
+                None => self.detections.push(Detection {
+                    source: "credential_access".to_string(),
+                    name,
+                    level: Some("high".to_string()),
+                    tags: vec![
+                        "attack.credential_access".to_string(),
+                        format!("attack.{}", technique.to_lowercase()),
+                    ],
+                    events: theft.event.iter().cloned().collect(),
+                }),
+            }
+        }
+    }
+
+    fn add_tamper_detections(&mut self) {
+        for tamper in &self.tampered {
+            let path = normalize(&tamper.path);
+            self.detections.push(Detection {
+                source: "tamper".to_string(),
+                name: format!(
+                    "Patched system component {} ({})",
+                    tamper.path,
+                    tamper.summary()
+                ),
+                level: Some(
+                    if tamper.code_patched() {
+                        "critical"
+                    } else {
+                        "high"
+                    }
+                    .to_string(),
+                ),
+                tags: vec!["attack.persistence".to_string(), "attack.t1554".to_string()],
+                events: self
+                    .events
+                    .iter()
+                    .filter(|e| is_file_event(e))
+                    .filter(|e| {
+                        e.event_data
+                            .get("TargetFilename")
+                            .is_some_and(|f| normalize(f) == path)
+                    })
+                    .cloned()
+                    .collect(),
+            });
+        }
+    }
+
+    pub fn add_signature_detections(&mut self, registry: &SignatureRegistry) {
+        for m in registry.evaluate(&self.events) {
+            self.detections.push(Detection {
+                source: "signature".to_string(),
+                name: m.signature,
+                level: m.level,
+                tags: m.tags,
+                events: m.events,
+            });
+        }
+        self.update_techniques();
+    }
+
+    pub fn add_descendants(&mut self, results: &[AnalysisResult]) {
+        self.descendants = descendants(&self.id, results);
+    }
+
+    pub fn add_yara_detections(&mut self, matches: &[ArtifactMatch]) {
+        for artifact in matches {
+            for m in &artifact.matches {
+                self.detections.push(Detection {
+                    source: "yara".to_string(),
+                    name: format!("{} ({})", m.rule, artifact.original_path),
+                    level: Some("high".to_string()),
+                    tags: m.tags.clone(),
+                    events: artifact.events.clone(),
+                });
+            }
+        }
+        self.update_techniques();
+    }
+
+    pub fn add_parentage_detections(&mut self, rules: &ParentageRules) {
+        for m in rules.evaluate(&self.events) {
+            self.detections.push(Detection {
+                source: "parentage".to_string(),
+                name: m.rule,
+                level: m.level,
+                tags: m.tags,
+                events: m.events,
+            });
+        }
+        self.update_techniques();
+    }
+
+    pub fn add_fingerprint_detections(&mut self, blocklist: &FingerprintBlocklist) {
+        let mut detections = Vec::new();
+        for m in blocklist.evaluate(&self.traffic) {
+            detections.push(Detection {
+                source: "fingerprint".to_string(),
+                name: m.entry.name.clone(),
+                level: m.entry.level.clone(),
+                tags: m.entry.tags.clone(),
+                events: connect_events(&m.flow.flow, &self.events)
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            });
+        }
+        self.detections.extend(detections);
+        self.update_techniques();
+    }
+
+    pub fn add_filesystem_summary(&mut self, options: &FilesystemOptions) {
+        self.filesystem = summarize_with(&self.events, options);
+    }
+
+    pub fn add_score(&mut self, options: &ScoringOptions) {
+        self.score = score_with(&self.events, options);
+        self.score.add_memory(&self.memory, options);
+    }
+
+    pub fn add_verdict(&mut self, policy: &VerdictPolicy) {
+        self.verdict = policy.evaluate(self);
+    }
+
+    pub fn add_traffic(&mut self, packets: &[Packet]) {
+        let tolerance = Duration::seconds(TRAFFIC_TIME_TOLERANCE_SECS);
+        let flows = flows(packets);
+        self.clock = ClockSkew::measure(&self.events, &flows);
+        let offset = self.clock.map_or(Duration::zero(), |c| c.offset());
+        self.traffic = correlate(&self.events, &flows, tolerance, offset);
+        self.http = pcap_http::exchanges(packets);
+        self.dns = resolution_timeline(&self.events);
+        merge_captured_dns(&mut self.dns, packets, tolerance);
+        self.update_beacons();
+        self.update_encrypted_dns();
+        self.refresh_browsing();
+    }
+
+    pub fn add_simulated_requests(&mut self, requests: &[SimulatedRequest]) {
+        self.simulated_requests = attribute(
+            requests,
+            &self.events,
+            Duration::seconds(TRAFFIC_TIME_TOLERANCE_SECS),
+            self.clock.map_or(Duration::zero(), |c| c.offset()),
+        );
+        self.update_beacons();
+        self.update_encrypted_dns();
+        self.refresh_browsing();
+    }
+
+    fn refresh_browsing(&mut self) {
+        if let Some(url) = self.browsing.as_ref().map(|b| b.url.clone()) {
+            self.update_browsing(&url);
+            self.update_techniques();
+        }
+    }
+
+    fn update_browsing(&mut self, url: &str) {
+        let browsing = BrowsingChain::analyze(
+            url,
+            self.simulated_requests.iter().map(|r| &r.request),
+            &self.http,
+            &self.process_tree,
+        );
+        self.detections.retain(|d| d.source != "browsing");
+        for indicator in &browsing.indicators {
+            self.detections.push(Detection {
+                source: "browsing".to_string(),
+                name: indicator.clone(),
+                level: Some("high".to_string()),
+                tags: vec![
+                    "attack.initial_access".to_string(),
+                    "attack.t1189".to_string(),
+                ],
+                events: Vec::new(),
+            });
+        }
+        for download in &browsing.downloads {
+            self.iocs.insert(
+                Ioc::Url(download.url.clone()),
+                Confidence::Medium,
+                download.time.into(),
+            );
+            if let Some(sha256) = &download.sha256 {
+                self.iocs.insert(
+                    Ioc::Sha256(sha256.clone()),
+                    Confidence::High,
+                    download.time.into(),
+                );
+            }
+        }
+        self.browsing = Some(browsing);
+    }
+
+    fn update_beacons(&mut self) {
+        let beacons = beacon::detect(&beacon::callbacks(
+            &self.traffic,
+            &self.http,
+            &self.simulated_requests,
+        ));
+        for beacon in &beacons {
+            let ioc = beacon.ioc();
+            if self.beacons.iter().all(|b| b.ioc() != ioc) {
+                self.iocs
+                    .insert(ioc, beacon.confidence, beacon.first_seen.into());
+            }
+        }
+        self.beacons = beacons;
+    }
+
+    fn update_encrypted_dns(&mut self) {
+        self.encrypted_dns = encrypted::detect(&self.traffic, &self.http, &self.simulated_requests);
+        merge_encrypted_dns(&mut self.dns, &self.encrypted_dns);
+
+        self.detections.retain(|d| d.source != "encrypted_dns");
+        for session in &self.encrypted_dns {
+            let process = session.image.as_deref().unwrap_or("unknown process");
+            let resolver = session
+                .resolver
+                .clone()
+                .or_else(|| session.server.map(|s| s.to_string()))
+                .unwrap_or_default();
+            let name = format!(
+                "{} bypassed the system resolver via {:?} to {}",
+                process, session.protocol, resolver
+            );
+            if self.detections.iter().any(|d| d.name == name) {
+                continue;
+            }
+            self.detections.push(Detection {
+                source: "encrypted_dns".to_string(),
+                name,
+                level: Some("medium".to_string()),
+                tags: vec![
+                    "attack.defense_evasion".to_string(),
+                    "attack.command_and_control".to_string(),
+                    "attack.t1071.004".to_string(),
+                    "attack.t1572".to_string(),
+                ],
+                events: Vec::new(),
+            });
+        }
+        self.update_techniques();
+    }
+
+    pub fn add_captured_traffic(&mut self) -> Result<()> {
+        let artifact_dir = artifact_dir(&self.id, &self.execution_id);
+        let pcap_path = format!("{}/{}", artifact_dir, PCAP_FILE_NAME);
+        if Path::new(&pcap_path).exists() {
+            self.add_traffic(&pcap::read_file(pcap_path)?);
+        }
+        let netsim_path = format!("{}/{}", artifact_dir, NETSIM_LOG_FILE);
+        if Path::new(&netsim_path).exists() {
+            let requests: Vec<SimulatedRequest> =
+                serde_json::from_slice(&std::fs::read(netsim_path)?)?;
+            self.add_simulated_requests(&requests);
+        }
+        let ids_dir = format!("{}/{}", artifact_dir, IDS_DIR);
+        if Path::new(&ids_dir).exists() {
+            self.add_ids_alerts(ids::read_alerts(ids_dir)?);
+        }
+        Ok(())
+    }
+
+    pub fn add_ids_alerts(&mut self, mut alerts: Vec<IdsAlert>) {
+        ids::attribute(&mut alerts, &self.traffic);
+        self.detections
+            .retain(|d| d.source != "suricata" && d.source != "zeek");
+        for alert in &alerts {
+            let source = alert.engine.name();
+            let name = match &alert.image {
+                Some(image) => format!("{} ({})", alert.title(), image),
+                None => alert.title(),
+            };
+            let events: Vec<SysmonEvent> = self
+                .events
+                .iter()
+                .filter(|e| ids_alert_matches(alert, e))
+                .cloned()
+                .collect();
+            match self
+                .detections
+                .iter_mut()
+                .find(|d| d.source == source && d.name == name)
+            {
+                Some(detection) => {
+                    for event in events {
+                        if !detection.events.iter().any(|e| {
+                            e.record_id == event.record_id && e.time_created == event.time_created
+                        }) {
+                            detection.events.push(event);
+                        }
+                    }
+                }
+                None => {
+                    let mut tags = vec!["network".to_string()];
+                    tags.extend(
+                        alert
+                            .techniques
+                            .iter()
+                            .map(|t| format!("attack.{}", t.to_lowercase())),
+                    );
+                    self.detections.push(Detection {
+                        source: source.to_string(),
+                        name,
+                        level: Some(alert.level().to_string()),
+                        tags,
+                        events,
+                    });
+                }
+            }
+        }
+        self.ids_alerts = alerts;
+        self.update_techniques();
+    }
+
+    pub fn add_code_signing(&mut self, policy: &TrustPolicy) {
+        let mut validations = Vec::new();
+        if let Some(pe) = &self.static_analysis {
+            validations.push(policy.validate_pe(pe, self.time.into()));
+        }
+        validations.extend(policy.validate_loads(&self.events));
+
+        for validation in &validations {
+            let (level, problem) = match validation.status {
+                ValidationStatus::Valid => continue,
+                ValidationStatus::Unsigned if validation.target != SignedTarget::Driver => continue,
+                ValidationStatus::Unsigned => ("high", "unsigned"),
+                ValidationStatus::Blocked => ("high", "signed with a blocked certificate"),
+                ValidationStatus::Revoked => ("high", "signed with a revoked certificate"),
+                ValidationStatus::Invalid => ("medium", "signed with an invalid signature"),
+                ValidationStatus::Untrusted => ("low", "signed by an untrusted certificate"),
+            };
+            let (level, subject) = match validation.target {
+                SignedTarget::Sample => (level, "Sample".to_string()),
+                SignedTarget::Driver => ("high", format!("Driver {}", validation.path)),
+                SignedTarget::Image => (level, format!("Image {}", validation.path)),
+            };
+            let mut tags = vec![
+                "attack.defense_evasion".to_string(),
+                "attack.t1553.002".to_string(),
+            ];
+            if validation.target == SignedTarget::Driver {
+                tags.push("attack.t1014".to_string());
+            }
+            self.detections.push(Detection {
+                source: "code_signing".to_string(),
+                name: format!("{} {}", subject, problem),
+                level: Some(level.to_string()),
+                tags,
+                events: validation.events.clone(),
+            });
+        }
+        self.code_signing = validations;
+        self.update_techniques();
+    }
+
+    pub fn add_capabilities(&mut self, rules: &CapabilityRules) -> Result<()> {
+        let path = sample_path(&self.id);
+        if !Path::new(&path).exists() {
+            return Ok(());
+        }
+        let sample = std::fs::read(path)?;
+        if !static_pe::is_pe(&sample) {
+            return Ok(());
+        }
+        let pe = match &self.static_analysis {
+            Some(pe) => pe.clone(),
+            None => static_pe::analyze(&sample)?,
+        };
+        self.capabilities = rules.evaluate(&FileFeatures::extract(&pe, &sample));
+        self.detections.retain(|d| d.source != "capa");
+        for capability in &self.capabilities {
+            self.detections.push(Detection {
+                source: "capa".to_string(),
+                name: capability.name.clone(),
+                level: None,
+                tags: capability.tags.clone(),
+                events: Vec::new(),
+            });
+        }
+        self.update_techniques();
+        Ok(())
+    }
+
+    pub fn add_analyzers(&mut self, registry: &AnalyzerRegistry, result: &AnalysisResult) {
+        let Some(log) = result.execution_logs.last() else {
+            return;
+        };
+        self.analyzers = registry.run(&AnalysisContext::new(result, log));
+        for analyzer in &self.analyzers {
+            for finding in &analyzer.output.findings {
+                self.detections.push(Detection {
+                    source: format!("plugin:{}", analyzer.analyzer),
+                    name: finding.name.clone(),
+                    level: finding.level.clone(),
+                    tags: finding.tags.clone(),
+                    events: finding.events.clone(),
+                });
+            }
+        }
+        self.update_techniques();
+    }
+
+    pub fn add_behavior_matrix(&mut self, result: &AnalysisResult) -> Result<()> {
+        self.matrix = BehaviorMatrix::from_result(result)?;
+        Ok(())
+    }
+
+    fn proxied_destination(&self, event: &SysmonEvent) -> Option<IpAddr> {
+        let port: u16 = event.event_data.get("SourcePort")?.parse().ok()?;
+        let flow = self
+            .traffic
+            .iter()
+            .map(|t| &t.flow)
+            .find(|f| f.client.port() == port)?;
+        let target = flow
+            .http_requests
+            .iter()
+            .find_map(|r| r.strip_prefix("CONNECT ")?.split(' ').next())
+            .map(str::to_string)
+            .or_else(|| flow.http_host.clone())
+            .or_else(|| flow.sni.clone())?;
+        let host = match target.rsplit_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_ok() => host,
+            _ => &target,
+        };
+        if let Ok(ip) = host.trim_matches(['[', ']']).parse() {
+            return Some(ip);
+        }
+        let host = host.to_lowercase();
+        self.dns
+            .iter()
+            .filter(|r| r.query == host)
+            .flat_map(|r| &r.answers)
+            .find_map(|a| a.data.parse().ok())
+    }
+
+    pub fn add_geo(&mut self, geo: &GeoDatabase) {
+        let mut ips: Vec<IpAddr> = self
+            .iocs
+            .iter()
+            .filter_map(|i| match i.ioc {
+                Ioc::Ip(ip) => Some(ip),
+                _ => None,
+            })
+            .collect();
+        ips.extend(
+            self.dns
+                .iter()
+                .flat_map(|r| &r.answers)
+                .filter_map(|a| a.data.parse::<IpAddr>().ok()),
+        );
+        let infos: BTreeMap<IpAddr, GeoInfo> = ips
+            .into_iter()
+            .filter(|ip| is_routable(ip) && !is_private(ip) && !geo.is_proxy(ip))
+            .filter_map(|ip| Some((ip, geo.lookup(ip)?)))
+            .collect();
+
+        let mut enriched = Vec::new();
+        for (index, event) in self.events.iter().enumerate() {
+            if event.event_id != SysmonEventId::NETWORK_CONNECT {
+                continue;
+            }
+            let Some(ip) = event
+                .event_data
+                .get("DestinationIp")
+                .and_then(|ip| ip.parse::<IpAddr>().ok())
+            else {
+                continue;
+            };
+            let (ip, proxied) = match geo.is_proxy(&ip) {
+                true => match self.proxied_destination(event) {
+                    Some(destination) => (destination, true),
+                    None => continue,
+                },
+                false => (ip, false),
+            };
+            if let Some(info) = infos.get(&ip) {
+                enriched.push((index, info, proxied));
+            }
+        }
+        for (index, info, proxied) in enriched {
+            let event = &mut self.events[index];
+            if proxied {
+                event.set_enriched("DestinationProxiedIp", info.ip.to_string());
+            }
+            if let Some(asn) = info.asn {
+                event.set_enriched("DestinationAsn", asn.to_string());
+            }
+            if let Some(org) = &info.as_org {
+                event.set_enriched("DestinationAsOrg", org.clone());
+            }
+            if let Some(country) = &info.country {
+                event.set_enriched("DestinationCountry", country.clone());
+            }
+            if !info.tags.is_empty() {
+                event.set_enriched("DestinationTags", info.tags.join(","));
+            }
+        }
+        self.geo = infos.into_values().collect();
+    }
+
+    pub fn add_enrichment(&mut self) -> Result<()> {
+        let path = format!(
+            "{}/{}",
+            artifact_dir(&self.id, &self.execution_id),
+            ENRICHMENT_FILE
+        );
+        if Path::new(&path).exists() {
+            self.enrichment = serde_json::from_slice(&std::fs::read(path)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn add_static_analysis(&mut self) -> Result<()> {
+        let path = sample_path(&self.id);
+        if !Path::new(&path).exists() {
+            return Ok(());
+        }
+        let sample = std::fs::read(path)?;
+        if static_pe::is_pe(&sample) {
+            self.static_analysis = Some(static_pe::analyze(&sample)?);
+        }
+        if email::is_email(&sample) {
+            self.add_email(email::parse(&sample)?);
+        }
+        if let Some(container) = read_container(container_path(&self.id))? {
+            self.add_container(container);
+        }
+        let mut scripts = read_scripts(scripts_dir(&self.id))?;
+        if scripts.is_empty() {
+            scripts = extract_scripts("sample", &sample)?;
+        }
+        scripts.append(&mut self.scripts);
+        self.scripts = scripts;
+        Ok(())
+    }
+
+    pub fn add_configs(&mut self, registry: &ConfigExtractorRegistry) -> Result<()> {
+        let artifact_dir = artifact_dir(&self.id, &self.execution_id);
+        let mut sources = Vec::new();
+        let sample = sample_path(&self.id);
+        if Path::new(&sample).exists() {
+            sources.push((
+                "sample".to_string(),
+                InputKind::Sample,
+                std::fs::read(sample)?,
+            ));
+        }
+        for memory in &self.memory {
+            let path = format!("{}/{}", artifact_dir, memory.dump);
+            if Path::new(&path).exists() {
+                sources.push((
+                    memory.dump.clone(),
+                    InputKind::MemoryDump,
+                    std::fs::read(path)?,
+                ));
+            }
+        }
+        for artifact in self.artifacts.iter().filter(|a| a.dumped()) {
+            let path = format!("{}/{}", artifact_dir, artifact.file_name());
+            if Path::new(&path).exists() {
+                sources.push((
+                    artifact.path.clone(),
+                    InputKind::Payload,
+                    std::fs::read(path)?,
+                ));
+            }
+        }
+        let inputs: Vec<ExtractionInput> = sources
+            .iter()
+            .map(|(name, kind, data)| ExtractionInput {
+                name,
+                kind: *kind,
+                data,
+            })
+            .collect();
+
+        let configs = registry.run(&inputs);
+        if configs.is_empty() {
+            return Ok(());
+        }
+        let time = self.time.into();
+        for config in &configs {
+            for ioc in config.iocs() {
+                self.iocs.insert(ioc, Confidence::High, time);
+            }
+            let generic = config.family == "generic";
+            let mut tags = vec!["attack.command_and_control".to_string()];
+            if !generic {
+                tags.push(format!("malware.{}", config.family));
+            }
+            self.detections.push(Detection {
+                source: "config".to_string(),
+                name: format!(
+                    "{} configuration extracted from {}",
+                    config.family, config.source
+                ),
+                level: Some(if generic { "medium" } else { "high" }.to_string()),
+                tags,
+                events: Vec::new(),
+            });
+        }
+        self.configs = configs;
+        self.update_techniques();
+        Ok(())
+    }
+
+    fn add_container(&mut self, container: Container) {
+        let time = self.time.into();
+        for (_, entry) in container.executables() {
+            if let Some(sha256) = &entry.sha256 {
+                self.iocs
+                    .insert(Ioc::Sha256(sha256.clone()), Confidence::High, time);
+            }
+        }
+        self.container = Some(container);
+    }
+
+    fn add_email(&mut self, email: Email) {
+        let time = self.time.into();
+        for address in email.addresses() {
+            self.iocs
+                .insert(Ioc::Email(address.to_string()), Confidence::High, time);
+        }
+        for url in &email.urls {
+            self.iocs
+                .insert(Ioc::Url(url.clone()), Confidence::Medium, time);
+        }
+        for attachment in &email.attachments {
+            self.iocs.insert(
+                Ioc::Sha256(attachment.sha256.clone()),
+                Confidence::High,
+                time,
+            );
+        }
+        if email.reply_to_mismatch() {
+            self.detections.push(Detection {
+                source: "email".to_string(),
+                name: "Reply-To domain differs from sender".to_string(),
+                level: Some("medium".to_string()),
+                tags: vec![
+                    "attack.initial_access".to_string(),
+                    "attack.t1566".to_string(),
+                ],
+                events: Vec::new(),
+            });
+            self.update_techniques();
+        }
+        self.email = Some(email);
+    }
+
+    pub fn timeline(&self) -> Result<Timeline> {
+        let mut timeline = Timeline::new().with_clock_skew(self.clock);
+        timeline.add_events(&self.events);
+        timeline.add_flows(&self.traffic);
+        timeline.add_observations(&self.simulated_requests);
+        timeline.add_script_blocks(&self.script_blocks);
+        let artifact_dir = artifact_dir(&self.id, &self.execution_id);
+        if Path::new(&artifact_dir).exists() {
+            timeline.add_screenshots(&read_screenshots(artifact_dir)?);
+        }
+        Ok(timeline)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_stix(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&to_stix_bundle(self))?)
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let _ = self.write_html(&mut html);
+        html
+    }
+
+    fn write_html(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<!DOCTYPE html>")?;
+        writeln!(html, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(html, "<title>Sandbox report {}</title>", escape(&self.id))?;
+        writeln!(
+            html,
+            "<style>table {{ border-collapse: collapse; }} td, th {{ border: 1px solid #999; padding: 2px 6px; }}</style>"
+        )?;
+        writeln!(html, "</head>\n<body>")?;
+
+        writeln!(html, "<h1>Sandbox report</h1>")?;
+        writeln!(html, "<table>")?;
+        writeln!(html, "<tr><th>ID</th><td>{}</td></tr>", escape(&self.id))?;
+        writeln!(
+            html,
+            "<tr><th>Hash</th><td>{}</td></tr>",
+            escape(&self.hash)
+        )?;
+        writeln!(
+            html,
+            "<tr><th>Execution</th><td>{}</td></tr>",
+            escape(&self.execution_id)
+        )?;
+        writeln!(html, "<tr><th>Time</th><td>{}</td></tr>", self.time)?;
+        if let Some(tlp) = self.metadata.tlp {
+            writeln!(html, "<tr><th>TLP</th><td>{}</td></tr>", tlp.label())?;
+        }
+        if let Some(campaign) = &self.metadata.campaign {
+            writeln!(
+                html,
+                "<tr><th>Campaign</th><td>{}</td></tr>",
+                escape(campaign)
+            )?;
+        }
+        if let Some(source) = &self.metadata.source {
+            writeln!(html, "<tr><th>Source</th><td>{}</td></tr>", escape(source))?;
+        }
+        if !self.metadata.tags.is_empty() {
+            writeln!(
+                html,
+                "<tr><th>Tags</th><td>{}</td></tr>",
+                escape(&self.metadata.tags.join(", "))
+            )?;
+        }
+        writeln!(
+            html,
+            "<tr><th>Score</th><td>{:.1} / 10</td></tr>",
+            self.score.score
+        )?;
+        writeln!(
+            html,
+            "<tr><th>Verdict</th><td>{}</td></tr>",
+            self.verdict.verdict
+        )?;
+        writeln!(
+            html,
+            "<tr><th>Events</th><td>{}</td></tr>",
+            self.events.len()
+        )?;
+        if let Some(violation) = &self.terminated {
+            writeln!(
+                html,
+                "<tr><th>Terminated by limit</th><td>{}</td></tr>",
+                escape(&violation.to_string())
+            )?;
+        }
+        if let Some(clock) = &self.clock {
+            writeln!(
+                html,
+                "<tr><th>Guest clock skew</th><td>{} ms ({} samples, spread {} ms)</td></tr>",
+                clock.offset_ms, clock.samples, clock.spread_ms
+            )?;
+        }
+        if !self.parse_failures.is_empty() {
+            writeln!(
+                html,
+                "<tr><th>Unparsable records</th><td>{}</td></tr>",
+                self.parse_failures.total
+            )?;
+        }
+        writeln!(html, "</table>")?;
+        if !self.parse_failures.is_empty() {
+            writeln!(html, "<h2>Parse failures</h2>")?;
+            table(
+                html,
+                &["Source", "Record", "Kind", "Error"],
+                self.parse_failures.examples.iter().map(|e| {
+                    vec![
+                        e.source.clone(),
+                        e.record.map(|r| r.to_string()).unwrap_or_default(),
+                        e.kind.name().to_string(),
+                        e.error.clone(),
+                    ]
+                }),
+            )?;
+        }
+        if !self.metadata.notes.is_empty() {
+            writeln!(html, "<h2>Analyst notes</h2>")?;
+            table(
+                html,
+                &["Time", "Author", "Note"],
+                self.metadata
+                    .notes
+                    .iter()
+                    .map(|n| vec![n.time.to_rfc3339(), n.author.clone(), n.text.clone()]),
+            )?;
+        }
+        if !self.derivation.is_empty() || !self.descendants.is_empty() {
+            self.write_lineage(html)?;
+        }
+
+        if let Some(pe) = &self.static_analysis {
+            self.write_static_analysis(html, pe)?;
+        }
+        if let Some(email) = &self.email {
+            self.write_email(html, email)?;
+        }
+        if let Some(container) = &self.container {
+            writeln!(html, "<h2>Container</h2>")?;
+            write_container(html, container, "")?;
+        }
+        if let Some(image) = &self.image {
+            writeln!(html, "<h2>Guest image</h2>")?;
+            table(
+                html,
+                &[
+                    "Name",
+                    "Version",
+                    "Snapshot",
+                    "OS",
+                    "Tooling",
+                    "Sysmon config",
+                    "Verified",
+                ],
+                std::iter::once(vec![
+                    image.name.clone(),
+                    image.version.to_string(),
+                    image.snapshot.clone(),
+                    image.os_version.clone().unwrap_or_default(),
+                    image.tooling.join(", "),
+                    image.sysmon_config_hash.clone().unwrap_or_default(),
+                    image.verified.map(|v| v.to_rfc3339()).unwrap_or_default(),
+                ]),
+            )?;
+        }
+        if !self.capabilities.is_empty() {
+            writeln!(html, "<h2>Capabilities</h2>")?;
+            table(
+                html,
+                &["Capability", "Namespace", "ATT&CK", "Evidence"],
+                self.capabilities.iter().map(|c| {
+                    vec![
+                        c.name.clone(),
+                        c.namespace.clone().unwrap_or_default(),
+                        c.attack.join(", "),
+                        c.evidence.join(", "),
+                    ]
+                }),
+            )?;
+        }
+        if !self.scripts.is_empty() {
+            self.write_scripts(html)?;
+        }
+        if let Some(matrix) = &self.matrix {
+            write_matrix(html, matrix)?;
+        }
+
+        writeln!(html, "<h2>Verdict rationale</h2>")?;
+        table(
+            html,
+            &["Step", "Description", "Verdict"],
+            self.verdict
+                .rationale
+                .iter()
+                .map(|r| vec![r.step.clone(), r.description.clone(), r.verdict.to_string()]),
+        )?;
+
+        writeln!(html, "<h2>Scoring signatures</h2>")?;
+        table(
+            html,
+            &["Signature", "Description", "Weight", "Events"],
+            self.score.signatures.iter().map(|s| {
+                vec![
+                    s.name.clone(),
+                    s.description.clone(),
+                    format!("{:.1}", s.weight),
+                    s.events.len().to_string(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Detections</h2>")?;
+        table(
+            html,
+            &["Source", "Name", "Level", "Tags", "Events"],
+            self.detections.iter().map(|d| {
+                vec![
+                    d.source.clone(),
+                    d.name.clone(),
+                    d.level.clone().unwrap_or_default(),
+                    d.tags.join(", "),
+                    d.events.len().to_string(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>ATT&amp;CK techniques</h2>")?;
+        table(
+            html,
+            &["ID", "Name", "Tactics", "Sources", "Events"],
+            self.techniques.iter().map(|t| {
+                vec![
+                    t.id.clone(),
+                    t.name.clone().unwrap_or_default(),
+                    t.tactics.iter().map(|t| t.name()).join(", "),
+                    t.sources.join(", "),
+                    t.events.to_string(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Process tree</h2>")?;
+        writeln!(html, "<ul>")?;
+        for root in self.process_tree.roots() {
+            self.write_process(html, root)?;
+        }
+        writeln!(html, "</ul>")?;
+
+        writeln!(html, "<h2>Cross-process access</h2>")?;
+        table(
+            html,
+            &["Time", "Kind", "Source", "Target", "Granted access"],
+            self.process_tree.edges().iter().map(|e| {
+                let image = |guid: &str| {
+                    self.process_tree
+                        .get(guid)
+                        .map(|p| p.image.clone())
+                        .unwrap_or_else(|| guid.to_string())
+                };
+                vec![
+                    e.time.to_rfc3339(),
+                    format!("{:?}", e.kind),
+                    image(&e.source),
+                    image(&e.target),
+                    e.granted_access.map(|a| a.to_string()).unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Network</h2>")?;
+        table(
+            html,
+            &["Image", "Protocol", "Source", "Destination", "Hostname"],
+            self.network.iter().map(|n| {
+                vec![
+                    n.image.clone(),
+                    n.protocol.clone(),
+                    endpoint(n.source_ip, n.source_port),
+                    endpoint(n.destination_ip, n.destination_port),
+                    n.destination_hostname.clone().unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Geolocation</h2>")?;
+        table(
+            html,
+            &["IP", "ASN", "Organization", "Country", "Tags"],
+            self.geo.iter().map(|g| {
+                vec![
+                    g.ip.to_string(),
+                    g.asn.map(|a| format!("AS{}", a)).unwrap_or_default(),
+                    g.as_org.clone().unwrap_or_default(),
+                    g.country.clone().unwrap_or_default(),
+                    g.tags.join(", "),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Captured traffic</h2>")?;
+        table(
+            html,
+            &[
+                "Image",
+                "Protocol",
+                "Client",
+                "Server",
+                "Sent",
+                "Received",
+                "SNI",
+                "HTTP",
+                "Fingerprints",
+                "Payload",
+            ],
+            self.traffic.iter().map(|t| {
+                vec![
+                    t.image.clone().unwrap_or_default(),
+                    t.flow.transport.name().to_string(),
+                    t.flow.client.to_string(),
+                    t.flow.server.to_string(),
+                    t.flow.bytes_sent.to_string(),
+                    t.flow.bytes_received.to_string(),
+                    t.flow.sni.clone().unwrap_or_default(),
+                    t.flow.http_requests.join("\n"),
+                    [
+                        ("JA3", &t.flow.ja3),
+                        ("JA3S", &t.flow.ja3s),
+                        ("HASSH", &t.flow.hassh),
+                        ("HASSH server", &t.flow.hassh_server),
+                    ]
+                    .iter()
+                    .filter_map(|(name, hash)| hash.as_ref().map(|h| format!("{} {}", name, h)))
+                    .join("\n"),
+                    t.flow.payload_preview.clone().unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        if !self.ids_alerts.is_empty() {
+            writeln!(html, "<h2>IDS alerts</h2>")?;
+            table(
+                html,
+                &[
+                    "Time",
+                    "Engine",
+                    "Signature",
+                    "Category",
+                    "Severity",
+                    "Source",
+                    "Destination",
+                    "Image",
+                ],
+                self.ids_alerts.iter().map(|a| {
+                    vec![
+                        a.time.to_rfc3339(),
+                        a.engine.name().to_string(),
+                        a.title(),
+                        a.category.clone().unwrap_or_default(),
+                        a.severity.map(|s| s.to_string()).unwrap_or_default(),
+                        a.source.map(|s| s.to_string()).unwrap_or_default(),
+                        a.destination.map(|d| d.to_string()).unwrap_or_default(),
+                        a.image.clone().unwrap_or_default(),
+                    ]
+                }),
+            )?;
+        }
+
+        if let Some(browsing) = &self.browsing {
+            writeln!(html, "<h2>Browsing</h2>")?;
+            writeln!(html, "<ol>")?;
+            for url in browsing.redirect_chain() {
+                writeln!(html, "<li>{}</li>", escape(url))?;
+            }
+            writeln!(html, "</ol>")?;
+            table(
+                html,
+                &[
+                    "Time",
+                    "URL",
+                    "File name",
+                    "Content type",
+                    "Payload",
+                    "SHA256",
+                ],
+                browsing.downloads.iter().map(|d| {
+                    vec![
+                        d.time.to_rfc3339(),
+                        d.url.clone(),
+                        d.file_name.clone().unwrap_or_default(),
+                        d.content_type.clone().unwrap_or_default(),
+                        d.payload.map(|p| p.name().to_string()).unwrap_or_default(),
+                        d.sha256.clone().unwrap_or_default(),
+                    ]
+                }),
+            )?;
+        }
+
+        writeln!(html, "<h2>Simulated services</h2>")?;
+        table(
+            html,
+            &["Time", "Service", "Client", "Image", "Request"],
+            self.simulated_requests.iter().map(|r| {
+                vec![
+                    r.request.time.to_rfc3339(),
+                    r.request.service.name().to_string(),
+                    r.request.client.to_string(),
+                    r.image.clone().unwrap_or_default(),
+                    r.request.summary.clone(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>HTTP</h2>")?;
+        table(
+            html,
+            &[
+                "Time",
+                "Client",
+                "Method",
+                "URL",
+                "Status",
+                "Request body",
+                "Response body",
+            ],
+            self.http.iter().map(|e| {
+                vec![
+                    e.time.to_rfc3339(),
+                    e.client.to_string(),
+                    e.request.method.clone(),
+                    e.url(),
+                    e.response
+                        .as_ref()
+                        .map(|r| format!("{} {}", r.status, r.reason))
+                        .unwrap_or_default(),
+                    e.request.body_preview.clone().unwrap_or_default(),
+                    e.response
+                        .as_ref()
+                        .and_then(|r| r.body_preview.clone())
+                        .unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Suspected C2 beacons</h2>")?;
+        table(
+            html,
+            &[
+                "Destination",
+                "Image",
+                "Callbacks",
+                "Interval",
+                "Jitter",
+                "URI pattern",
+                "Confidence",
+            ],
+            self.beacons.iter().map(|b| {
+                vec![
+                    b.destination.clone(),
+                    b.image.clone().unwrap_or_default(),
+                    b.callbacks.to_string(),
+                    format!("{:.1}s", b.interval_secs),
+                    format!("{:.0}%", b.jitter * 100.0),
+                    b.uri_pattern.clone().unwrap_or_default(),
+                    format!("{:?}", b.confidence),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>DNS</h2>")?;
+        table(
+            html,
+            &["Time", "Query", "Answers", "Image", "Sources"],
+            self.dns.iter().map(|r| {
+                vec![
+                    r.time.to_rfc3339(),
+                    r.query.clone(),
+                    r.answers
+                        .iter()
+                        .map(|a| match a.ttl {
+                            Some(ttl) => format!("{} {} (ttl {})", a.record_type, a.data, ttl),
+                            None => format!("{} {}", a.record_type, a.data),
+                        })
+                        .join("\n"),
+                    r.image.clone().unwrap_or_default(),
+                    r.sources.iter().map(|s| format!("{:?}", s)).join(", "),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Encrypted DNS</h2>")?;
+        table(
+            html,
+            &["First seen", "Protocol", "Resolver", "Image", "Queries"],
+            self.encrypted_dns.iter().map(|s| {
+                vec![
+                    s.first_seen.to_rfc3339(),
+                    format!("{:?}", s.protocol),
+                    s.resolver
+                        .clone()
+                        .or_else(|| s.server.map(|a| a.to_string()))
+                        .unwrap_or_default(),
+                    s.image.clone().unwrap_or_default(),
+                    s.queries.iter().map(|q| q.query.as_str()).join("\n"),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>File changes</h2>")?;
+        table(
+            html,
+            &["Time", "Kind", "Image", "Path", "SHA256"],
+            self.file_changes.iter().map(|f| {
+                vec![
+                    f.time.to_rfc3339(),
+                    format!("{:?}", f.kind),
+                    f.image.clone(),
+                    f.path.clone(),
+                    f.sha256.clone().unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Dropped files</h2>")?;
+        table(
+            html,
+            &[
+                "Path",
+                "Size",
+                "MD5",
+                "SHA1",
+                "SHA256",
+                "ssdeep",
+                "Created by",
+            ],
+            self.artifacts.iter().map(|a| {
+                vec![
+                    a.path.clone(),
+                    a.size.to_string(),
+                    a.hashes.md5.clone(),
+                    a.hashes.sha1.clone(),
+                    a.hashes.sha256.clone(),
+                    a.hashes.ssdeep.clone(),
+                    a.provenance
+                        .iter()
+                        .filter_map(|p| p.image.as_deref())
+                        .unique()
+                        .join(", "),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Filesystem activity</h2>")?;
+        table(
+            html,
+            &["Image", "Created", "Deleted", "Timestomped", "Suppressed"],
+            self.filesystem.iter().map(|a| {
+                vec![
+                    a.image.clone(),
+                    a.created.to_string(),
+                    a.deleted.to_string(),
+                    a.timestomped.to_string(),
+                    a.suppressed.to_string(),
+                ]
+            }),
+        )?;
+        table(
+            html,
+            &["Time", "Image", "Operation", "Path", "Interest"],
+            self.filesystem.iter().flat_map(|a| {
+                a.interesting().map(|o| {
+                    vec![
+                        o.time.to_rfc3339(),
+                        a.image.clone(),
+                        format!("{:?}", o.operation),
+                        o.path.clone(),
+                        o.interest.iter().map(|i| format!("{:?}", i)).join(", "),
+                    ]
+                })
+            }),
+        )?;
+
+        writeln!(html, "<h2>Registry changes</h2>")?;
+        table(
+            html,
+            &["Time", "Type", "Image", "Target", "Value type", "Details"],
+            self.registry_changes.iter().map(|r| {
+                vec![
+                    r.time.to_rfc3339(),
+                    r.event_type.clone(),
+                    r.image.clone(),
+                    r.target_object.clone(),
+                    r.value_type.map(|t| t.to_string()).unwrap_or_default(),
+                    r.details.clone().unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Registry diff</h2>")?;
+        table(
+            html,
+            &[
+                "Key",
+                "Operation",
+                "Value",
+                "Writes",
+                "Images",
+                "Persistence",
+            ],
+            self.registry.entries.iter().map(|e| {
+                vec![
+                    e.key.clone(),
+                    format!("{:?}", e.operation),
+                    e.data.as_ref().map(|d| d.to_string()).unwrap_or_default(),
+                    e.writes.to_string(),
+                    e.images.join(", "),
+                    e.persistence
+                        .map(|p| format!("{:?}", p))
+                        .unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        if let Some(verdict) = &self.ransomware {
+            writeln!(html, "<h2>Ransomware</h2>")?;
+            table(
+                html,
+                &["Indicator", "Description", "Paths", "Artifacts"],
+                verdict.evidence.iter().map(|e| {
+                    vec![
+                        format!("{:?}", e.indicator),
+                        e.description.clone(),
+                        e.paths.iter().take(10).join(", "),
+                        e.artifacts.join(", "),
+                    ]
+                }),
+            )?;
+        }
+
+        writeln!(html, "<h2>Memory payloads</h2>")?;
+        table(
+            html,
+            &["Dump", "Address", "Size", "Origin", "Imports", "SHA256"],
+            self.memory.iter().flat_map(|m| {
+                m.payloads.iter().map(|p| {
+                    vec![
+                        m.dump.clone(),
+                        format!("{:#x}", p.address),
+                        p.size.to_string(),
+                        format!("{:?}", p.origin),
+                        format!(
+                            "{:?} ({})",
+                            p.import_recovery,
+                            p.imports.iter().map(|i| i.dll.as_str()).join(", ")
+                        ),
+                        p.sha256.clone(),
+                    ]
+                })
+            }),
+        )?;
+
+        writeln!(html, "<h2>Code signing</h2>")?;
+        table(
+            html,
+            &[
+                "Target",
+                "Path",
+                "Signer",
+                "Status",
+                "Revocation",
+                "Reasons",
+            ],
+            self.code_signing.iter().map(|v| {
+                vec![
+                    format!("{:?}", v.target),
+                    v.path.clone(),
+                    v.signer.clone().unwrap_or_default(),
+                    format!("{:?}", v.status),
+                    format!("{:?}", v.revocation),
+                    v.reasons.join("; "),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Drivers</h2>")?;
+        table(
+            html,
+            &[
+                "Path",
+                "SHA256",
+                "Signer",
+                "Signature status",
+                "Vulnerable",
+                "Loaded by",
+            ],
+            self.drivers.iter().map(|d| {
+                vec![
+                    d.path.clone(),
+                    d.sha256.clone().unwrap_or_default(),
+                    d.signer.clone().unwrap_or_default(),
+                    d.signature_status.clone().unwrap_or_default(),
+                    d.vulnerable
+                        .as_ref()
+                        .map(|v| format!("{} ({:?})", v.driver.description, v.matched_by))
+                        .unwrap_or_default(),
+                    d.installer
+                        .as_ref()
+                        .map(|i| {
+                            format!(
+                                "{} ({:?})",
+                                i.image.as_deref().unwrap_or_default(),
+                                i.method
+                            )
+                        })
+                        .unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        if !self.tampered.is_empty() {
+            writeln!(html, "<h2>Tampered system files</h2>")?;
+            table(
+                html,
+                &[
+                    "Path",
+                    "Golden SHA256",
+                    "SHA256",
+                    "Changes",
+                    "Changed regions",
+                ],
+                self.tampered.iter().map(|t| {
+                    let regions = t
+                        .sections
+                        .iter()
+                        .flat_map(|s| &s.regions)
+                        .chain(&t.regions)
+                        .map(|r| {
+                            format!("0x{:x}+{}: {} -> {}", r.offset, r.length, r.before, r.after)
+                        })
+                        .join("; ");
+                    vec![
+                        t.path.clone(),
+                        t.golden_sha256.clone(),
+                        t.sha256.clone(),
+                        t.summary(),
+                        regions,
+                    ]
+                }),
+            )?;
+        }
+
+        writeln!(html, "<h2>Malware configuration</h2>")?;
+        table(
+            html,
+            &["Family", "Source", "C2", "URLs", "Settings"],
+            self.configs.iter().map(|c| {
+                vec![
+                    c.family.clone(),
+                    c.source.clone(),
+                    c.c2.join(", "),
+                    c.urls.iter().take(10).join(", "),
+                    c.values
+                        .iter()
+                        .map(|(k, v)| format!("{}={}", k, v))
+                        .join(", "),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Credential access</h2>")?;
+        table(
+            html,
+            &["Technique", "Process", "Description", "Memory dump"],
+            self.credential_theft.iter().map(|t| {
+                vec![
+                    t.kind.technique().to_string(),
+                    t.process.clone(),
+                    t.description.clone(),
+                    t.dump.clone().unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Logons</h2>")?;
+        table(
+            html,
+            &["Time", "Result", "Type", "User", "Process", "Source"],
+            self.logons.iter().map(|l| {
+                vec![
+                    l.time.to_rfc3339(),
+                    if l.success { "success" } else { "failure" }.to_string(),
+                    l.logon_type.map(|t| t.to_string()).unwrap_or_default(),
+                    l.user.clone().unwrap_or_default(),
+                    l.logon_process.clone().unwrap_or_default(),
+                    endpoint(
+                        l.source_ip.as_deref().and_then(|ip| ip.parse().ok()),
+                        l.source_port,
+                    ),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Services</h2>")?;
+        table(
+            html,
+            &["Name", "Binary path", "Start type", "Source"],
+            self.services.iter().map(|s| {
+                vec![
+                    s.service_name.clone(),
+                    s.binary_path.clone().unwrap_or_default(),
+                    s.start_type.clone().unwrap_or_default(),
+                    [
+                        s.process_event.as_ref().map(|_| "command line"),
+                        s.registry_event.as_ref().map(|_| "registry"),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .join(", "),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Scheduled tasks</h2>")?;
+        table(
+            html,
+            &["Name", "Command", "Trigger", "Source"],
+            self.scheduled_tasks.iter().map(|t| {
+                vec![
+                    t.task_name.clone(),
+                    t.command.clone().unwrap_or_default(),
+                    t.trigger.clone().unwrap_or_default(),
+                    [
+                        t.process_event.as_ref().map(|_| "command line"),
+                        t.file_event.as_ref().map(|_| "tasks folder"),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .join(", "),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>WMI subscriptions</h2>")?;
+        table(
+            html,
+            &[
+                "Time",
+                "User",
+                "Filter",
+                "Namespace",
+                "Query",
+                "Consumer",
+                "Type",
+                "Action",
+            ],
+            self.wmi_subscriptions.iter().map(|w| {
+                vec![
+                    w.time.to_rfc3339(),
+                    w.user.clone().unwrap_or_default(),
+                    w.filter.clone(),
+                    w.namespace.clone().unwrap_or_default(),
+                    w.query.clone().unwrap_or_default(),
+                    match &w.consumer_class {
+                        Some(class) => format!("{} ({})", w.consumer, class),
+                        None => w.consumer.clone(),
+                    },
+                    w.consumer_type.clone().unwrap_or_default(),
+                    w.destination.clone().unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Clipboard</h2>")?;
+        table(
+            html,
+            &["Currency", "Original", "Replacement", "Delay (ms)", "Image"],
+            self.clippers.iter().map(|c| {
+                vec![
+                    c.currency.clone(),
+                    c.original.clone(),
+                    c.replacement.clone(),
+                    c.delay_ms.to_string(),
+                    c.image.clone().unwrap_or_default(),
+                ]
+            }),
+        )?;
+        table(
+            html,
+            &["Time", "Content"],
+            self.clipboard
+                .iter()
+                .map(|c| vec![c.time.to_rfc3339(), c.content.clone()]),
+        )?;
+
+        writeln!(html, "<h2>Named pipes and mutexes</h2>")?;
+        table(
+            html,
+            &[
+                "PID",
+                "Image",
+                "Pipes created",
+                "Pipes connected",
+                "Mutexes",
+            ],
+            self.sync_objects.iter().map(|p| {
+                vec![
+                    p.process_id.map(|p| p.to_string()).unwrap_or_default(),
+                    p.image.clone(),
+                    p.pipes_created.join(", "),
+                    p.pipes_connected.join(", "),
+                    p.mutexes.join(", "),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Indicators</h2>")?;
+        table(
+            html,
+            &["Type", "Value", "Confidence", "First seen", "Occurrences"],
+            self.iocs.iter().map(|i| {
+                vec![
+                    i.ioc.kind().to_string(),
+                    i.ioc.value(),
+                    format!("{:?}", i.confidence),
+                    i.first_seen.to_rfc3339(),
+                    i.occurrences.to_string(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h2>Enrichment</h2>")?;
+        table(
+            html,
+            &[
+                "Provider",
+                "Type",
+                "Value",
+                "Verdict",
+                "Detections",
+                "Family",
+                "Tags",
+                "Link",
+            ],
+            self.enrichment.iter().map(|e| {
+                vec![
+                    e.provider.name().to_string(),
+                    e.subject.kind().to_string(),
+                    e.subject.value(),
+                    format!("{:?}", e.verdict),
+                    match (e.detections, e.engines) {
+                        (Some(d), Some(n)) => format!("{} / {}", d, n),
+                        (Some(d), None) => d.to_string(),
+                        _ => String::new(),
+                    },
+                    e.family.clone().unwrap_or_default(),
+                    e.tags.join(", "),
+                    e.link.clone().unwrap_or_default(),
+                ]
+            }),
+        )?;
+
+        if let Ok(timeline) = self.timeline() {
+            self.write_timeline(html, &timeline)?;
+        }
+
+        writeln!(html, "</body>\n</html>")
+    }
+
+    fn write_lineage(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<h2>Derivation</h2>")?;
+        let generations = self.derivation.len();
+        let ancestors = self.derivation.iter().enumerate().map(|(i, d)| {
+            vec![
+                format!("-{}", generations - i),
+                d.kind.name().to_string(),
+                d.analysis_id.clone(),
+                d.execution_id.clone().unwrap_or_default(),
+                d.artifact.clone(),
+                d.sha256.clone(),
+            ]
+        });
+        let current = std::iter::once(vec![
+            "0".to_string(),
+            String::new(),
+            self.id.clone(),
+            self.execution_id.clone(),
+            String::new(),
+            String::new(),
+        ]);
+        let descendants = self.descendants.iter().map(|d| {
+            vec![
+                format!("+{}", d.generation),
+                d.derivation.kind.name().to_string(),
+                d.analysis_id.clone(),
+                d.execution_id.clone(),
+                format!(
+                    "{} (from {})",
+                    d.derivation.artifact, d.derivation.analysis_id
+                ),
+                d.derivation.sha256.clone(),
+            ]
+        });
+        table(
+            html,
+            &[
+                "Generation",
+                "Kind",
+                "Analysis",
+                "Run",
+                "Artifact",
+                "SHA-256",
+            ],
+            ancestors.chain(current).chain(descendants),
+        )
+    }
+
+    fn write_timeline(&self, html: &mut String, timeline: &Timeline) -> std::fmt::Result {
+        writeln!(html, "<h2>Timeline</h2>")?;
+        let recording = format!(
+            "{}/{}",
+            artifact_dir(&self.id, &self.execution_id),
+            RECORDING_FILE
+        );
+        if Path::new(&recording).exists() {
+            writeln!(
+                html,
+                "<p>Desktop recording: <a href=\"{0}\">{0}</a></p>",
+                escape(&recording)
+            )?;
+        }
+        writeln!(html, "<table>")?;
+        writeln!(
+            html,
+            "<tr><th>Time</th><th>Original time</th><th>Clock</th><th>Kind</th><th>Summary</th></tr>"
+        )?;
+        for entry in timeline.entries() {
+            write!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}",
+                entry.time.to_rfc3339(),
+                entry.original_time.to_rfc3339(),
+                entry.clock.name(),
+                entry.kind.name(),
+                escape(&entry.summary)
+            )?;
+            if let EntryData::Screenshot(screenshot) = &entry.data {
+                if let Ok(png) = std::fs::read(&screenshot.path) {
+                    write!(
+                        html,
+                        "<br><img width=\"{}\" src=\"data:image/png;base64,{}\">",
+                        SCREENSHOT_WIDTH,
+                        STANDARD.encode(png)
+                    )?;
+                }
+            }
+            writeln!(html, "</td></tr>")?;
+        }
+        writeln!(html, "</table>")
+    }
+
+    fn write_email(&self, html: &mut String, email: &Email) -> std::fmt::Result {
+        writeln!(html, "<h2>Email</h2>")?;
+        table(
+            html,
+            &["Subject", "From", "Reply-To", "Return-Path", "To", "Date"],
+            std::iter::once(vec![
+                email.subject.clone().unwrap_or_default(),
+                email.from.clone().unwrap_or_default(),
+                email.reply_to.clone().unwrap_or_default(),
+                email.return_path.clone().unwrap_or_default(),
+                email.to.join(", "),
+                email.date.clone().unwrap_or_default(),
+            ]),
+        )?;
+
+        if !email.urls.is_empty() {
+            writeln!(html, "<h3>URLs</h3>")?;
+            table(html, &["URL"], email.urls.iter().map(|u| vec![u.clone()]))?;
+        }
+
+        writeln!(html, "<h3>Attachments</h3>")?;
+        table(
+            html,
+            &[
+                "Name",
+                "Content type",
+                "Size",
+                "Entropy",
+                "SHA256",
+                "Scripts",
+            ],
+            email.attachments.iter().map(|a| {
+                vec![
+                    a.name.clone(),
+                    a.content_type.clone().unwrap_or_default(),
+                    a.size.to_string(),
+                    format!("{:.2}", a.entropy),
+                    a.sha256.clone(),
+                    a.scripts.iter().map(|s| s.name.as_str()).join(", "),
+                ]
+            }),
+        )
+    }
+
+    fn write_static_analysis(&self, html: &mut String, pe: &PeInfo) -> std::fmt::Result {
+        writeln!(html, "<h2>Static analysis</h2>")?;
+        table(
+            html,
+            &[
+                "Machine",
+                "Type",
+                "Compiled",
+                "Entry point",
+                "Imphash",
+                "Signature",
+                "Signer",
+            ],
+            std::iter::once(vec![
+                pe.machine.clone(),
+                if pe.is_dll { "DLL" } else { "EXE" }.to_string(),
+                pe.compile_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                format!("{:#x}", pe.entry_point),
+                pe.imphash.clone().unwrap_or_default(),
+                format!("{:?}", pe.signature),
+                pe.certificates
+                    .signer()
+                    .and_then(|c| c.common_name.clone())
+                    .unwrap_or_default(),
+            ]),
+        )?;
+
+        writeln!(html, "<h3>Sections</h3>")?;
+        table(
+            html,
+            &[
+                "Name",
+                "Virtual address",
+                "Virtual size",
+                "Raw size",
+                "Entropy",
+                "Flags",
+            ],
+            pe.sections.iter().map(|s| {
+                vec![
+                    s.name.clone(),
+                    format!("{:#x}", s.virtual_address),
+                    s.virtual_size.to_string(),
+                    s.raw_size.to_string(),
+                    format!("{:.2}", s.entropy),
+                    [(s.executable, "X"), (s.writable, "W")]
+                        .iter()
+                        .filter(|(set, _)| *set)
+                        .map(|(_, flag)| *flag)
+                        .collect(),
+                ]
+            }),
+        )?;
+
+        writeln!(html, "<h3>Imports</h3>")?;
+        table(
+            html,
+            &["DLL", "Functions"],
+            pe.imports
+                .iter()
+                .map(|i| vec![i.dll.clone(), i.functions.join(", ")]),
+        )?;
+
+        if !pe.exports.is_empty() {
+            writeln!(html, "<h3>Exports</h3>")?;
+            table(html, &["Name"], pe.exports.iter().map(|e| vec![e.clone()]))?;
+        }
+
+        writeln!(html, "<h3>Resources</h3>")?;
+        table(
+            html,
+            &["Type", "Name", "Language", "Size", "Entropy"],
+            pe.resources.iter().map(|r| {
+                vec![
+                    r.resource_type.clone(),
+                    r.name.clone(),
+                    r.language.to_string(),
+                    r.size.to_string(),
+                    format!("{:.2}", r.entropy),
+                ]
+            }),
+        )
+    }
+
+    fn write_scripts(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<h2>Extracted scripts</h2>")?;
+        for script in &self.scripts {
+            writeln!(
+                html,
+                "<h3>{} ({})</h3>",
+                escape(&script.name),
+                script.language.name()
+            )?;
+            writeln!(html, "<pre>{}</pre>", escape(&script.normalized))?;
+            for decoded in &script.decoded {
+                writeln!(html, "<h4>Decoded</h4>\n<pre>{}</pre>", escape(decoded))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_process(&self, html: &mut String, process: &Process) -> std::fmt::Result {
+        write!(
+            html,
+            "<li><code>{}</code> {}",
+            escape(&process.image),
+            escape(&process.command_line)
+        )?;
+        let children = self.process_tree.children(&process.guid);
+        if !children.is_empty() {
+            writeln!(html, "<ul>")?;
+            for child in children {
+                self.write_process(html, child)?;
+            }
+            write!(html, "</ul>")?;
+        }
+        writeln!(html, "</li>")
+    }
+}
+
+fn endpoint(ip: Option<std::net::IpAddr>, port: Option<u16>) -> String {
+    match (ip, port) {
+        (Some(ip), Some(port)) => format!("{}:{}", ip, port),
+        (Some(ip), None) => ip.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn write_container(html: &mut String, container: &Container, parent: &str) -> std::fmt::Result {
+    writeln!(
+        html,
+        "<p>{} {}{}</p>",
+        container.kind.name(),
+        escape(parent),
+        container
+            .password
+            .as_ref()
+            .map(|p| format!(" (password: {})", escape(p)))
+            .unwrap_or_default()
+    )?;
+    table(
+        html,
+        &["Path", "Size", "Encrypted", "Executable", "SHA256"],
+        container.entries.iter().map(|e| {
+            vec![
+                e.path.clone(),
+                e.size.to_string(),
+                e.encrypted.to_string(),
+                e.executable.to_string(),
+                e.sha256.clone().unwrap_or_default(),
+            ]
+        }),
+    )?;
+    for error in &container.errors {
+        writeln!(html, "<p>{}</p>", escape(error))?;
+    }
+    for entry in &container.entries {
+        if let Some(nested) = &entry.container {
+            let path = match parent {
+                "" => entry.path.clone(),
+                parent => format!("{}/{}", parent, entry.path),
+            };
+            write_container(html, nested, &path)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_matrix(html: &mut String, matrix: &BehaviorMatrix) -> std::fmt::Result {
+    writeln!(html, "<h2>Behavior matrix</h2>")?;
+    table(
+        html,
+        &[
+            "Profile",
+            "Machine",
+            "Verdict",
+            "Score",
+            "Events",
+            "Processes",
+            "Network",
+        ],
+        matrix.profiles.iter().map(|p| {
+            vec![
+                p.profile.clone(),
+                p.machine.clone().unwrap_or_default(),
+                p.verdict.to_string(),
+                format!("{:.1}", p.score),
+                p.events.to_string(),
+                p.processes.to_string(),
+                p.network.to_string(),
+            ]
+        }),
+    )?;
+    let profiles: Vec<String> = matrix.profiles.iter().map(|p| escape(&p.profile)).collect();
+    let headers: Vec<&str> = ["Behavior", "Kind"]
+        .into_iter()
+        .chain(profiles.iter().map(String::as_str))
+        .collect();
+    table(
+        html,
+        &headers,
+        matrix.rows.iter().map(|row| {
+            let mut cells = vec![row.behavior.clone(), row.kind.name().to_string()];
+            cells.extend(matrix.profiles.iter().map(|p| {
+                if row.profiles.contains(&p.profile) {
+                    "x"
+                } else {
+                    ""
+                }
+                .to_string()
+            }));
+            cells
+        }),
+    )
+}
+
+fn table<I>(html: &mut String, headers: &[&str], rows: I) -> std::fmt::Result
+where
+    I: Iterator<Item = Vec<String>>,
+{
+    writeln!(html, "<table>")?;
+    write!(html, "<tr>")?;
+    for header in headers {
+        write!(html, "<th>{}</th>", header)?;
+    }
+    writeln!(html, "</tr>")?;
+    for row in rows {
+        write!(html, "<tr>")?;
+        for cell in row {
+            write!(html, "<td>{}</td>", escape(&cell))?;
+        }
+        writeln!(html, "</tr>")?;
+    }
+    writeln!(html, "</table>")
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c if is_bidi_control(c) || is_invisible(c) => {
+                let _ = write!(escaped, "[U+{:04X}]", c as u32);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use super::SandboxReport;
+use crate::event_data::Platform;
+use crate::path::normalize;
+use crate::registry::{RegistryData, RegistryOperation};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RunInfo {
+    pub id: String,
+    pub execution_id: String,
+    pub hash: String,
+    pub platform: Option<Platform>,
+}
+
+impl RunInfo {
+    fn new(report: &SandboxReport) -> Self {
+        Self {
+            id: report.id.clone(),
+            execution_id: report.execution_id.clone(),
+            hash: report.hash.clone(),
+            platform: report.platform,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Delta<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+}
+
+impl<T> Delta<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+fn delta<T: Ord + Clone>(left: &BTreeSet<T>, right: &BTreeSet<T>) -> Delta<T> {
+    Delta {
+        added: right.difference(left).cloned().collect(),
+        removed: left.difference(right).cloned().collect(),
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProcessSummary {
+    pub image: String,
+    pub command_line: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Destination {
+    pub protocol: String,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DroppedFile {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChangedFile {
+    pub path: String,
+    pub left_sha256: String,
+    pub right_sha256: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FileDiff {
+    pub added: Vec<DroppedFile>,
+    pub removed: Vec<DroppedFile>,
+    pub changed: Vec<ChangedFile>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RegistryKeyChange {
+    pub key: String,
+    pub operation: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChangedRegistryKey {
+    pub key: String,
+    pub left: Option<RegistryData>,
+    pub right: Option<RegistryData>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RegistryKeyDiff {
+    pub added: Vec<RegistryKeyChange>,
+    pub removed: Vec<RegistryKeyChange>,
+    pub changed: Vec<ChangedRegistryKey>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ReportDiff {
+    pub left: RunInfo,
+    pub right: RunInfo,
+    pub processes: Delta<ProcessSummary>,
+    pub network: Delta<Destination>,
+    pub files: FileDiff,
+    pub registry: RegistryKeyDiff,
+    pub detections: Delta<String>,
+}
+
+impl ReportDiff {
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+            && self.network.is_empty()
+            && self.files.added.is_empty()
+            && self.files.removed.is_empty()
+            && self.files.changed.is_empty()
+            && self.registry.added.is_empty()
+            && self.registry.removed.is_empty()
+            && self.registry.changed.is_empty()
+            && self.detections.is_empty()
+    }
+}
+
+fn processes(report: &SandboxReport) -> BTreeSet<ProcessSummary> {
+    report
+        .process_tree
+        .processes()
+        .map(|p| ProcessSummary {
+            image: normalize(&p.image),
+            command_line: p.command_line.trim().to_string(),
+        })
+        .collect()
+}
+
+fn destinations(report: &SandboxReport) -> BTreeSet<Destination> {
+    report
+        .network
+        .iter()
+        .filter(|c| c.initiated)
+        .filter_map(|c| {
+            let host = match &c.destination_hostname {
+                Some(hostname) if !hostname.is_empty() => hostname.to_lowercase(),
+                _ => c.destination_ip?.to_string(),
+            };
+            Some(Destination {
+                protocol: c.protocol.to_lowercase(),
+                host,
+                port: c.destination_port,
+            })
+        })
+        .collect()
+}
+
+fn dropped_files(report: &SandboxReport) -> BTreeMap<String, String> {
+    report
+        .artifacts
+        .iter()
+        .filter(|a| !a.deleted())
+        .map(|a| (normalize(&a.path), a.hashes.sha256.clone()))
+        .collect()
+}
+
+fn operation_name(operation: RegistryOperation) -> String {
+    format!("{:?}", operation)
+}
+
+fn registry_keys(report: &SandboxReport) -> BTreeMap<(String, String), Option<RegistryData>> {
+    report
+        .registry
+        .entries
+        .iter()
+        .map(|e| {
+            (
+                (e.key.to_lowercase(), operation_name(e.operation)),
+                e.data.clone(),
+            )
+        })
+        .collect()
+}
+
+fn files(left: &SandboxReport, right: &SandboxReport) -> FileDiff {
+    let left = dropped_files(left);
+    let right = dropped_files(right);
+    let file = |(path, sha256): (&String, &String)| DroppedFile {
+        path: path.clone(),
+        sha256: sha256.clone(),
+    };
+    FileDiff {
+        added: right
+            .iter()
+            .filter(|(path, _)| !left.contains_key(*path))
+            .map(file)
+            .collect(),
+        removed: left
+            .iter()
+            .filter(|(path, _)| !right.contains_key(*path))
+            .map(file)
+            .collect(),
+        changed: left
+            .iter()
+            .filter_map(|(path, left_sha256)| {
+                let right_sha256 = right.get(path)?;
+                (left_sha256 != right_sha256).then(|| ChangedFile {
+                    path: path.clone(),
+                    left_sha256: left_sha256.clone(),
+                    right_sha256: right_sha256.clone(),
+                })
+            })
+            .collect(),
+    }
+}
+
+fn registry(left: &SandboxReport, right: &SandboxReport) -> RegistryKeyDiff {
+    let left = registry_keys(left);
+    let right = registry_keys(right);
+    let change = |(key, operation): &(String, String)| RegistryKeyChange {
+        key: key.clone(),
+        operation: operation.clone(),
+    };
+    RegistryKeyDiff {
+        added: right
+            .keys()
+            .filter(|k| !left.contains_key(*k))
+            .map(change)
+            .collect(),
+        removed: left
+            .keys()
+            .filter(|k| !right.contains_key(*k))
+            .map(change)
+            .collect(),
+        changed: left
+            .iter()
+            .filter_map(|(k, left_data)| {
+                let right_data = right.get(k)?;
+                (left_data != right_data).then(|| ChangedRegistryKey {
+                    key: k.0.clone(),
+                    left: left_data.clone(),
+                    right: right_data.clone(),
+                })
+            })
+            .collect(),
+    }
+}
+
+fn detections(report: &SandboxReport) -> BTreeSet<String> {
+    report.detections.iter().map(|d| d.name.clone()).collect()
+}
+
+impl SandboxReport {
+    pub fn diff(&self, other: &SandboxReport) -> ReportDiff {
+        ReportDiff {
+            left: RunInfo::new(self),
+            right: RunInfo::new(other),
+            processes: delta(&processes(self), &processes(other)),
+            network: delta(&destinations(self), &destinations(other)),
+            files: files(self, other),
+            registry: registry(self, other),
+            detections: delta(&detections(self), &detections(other)),
+        }
+    }
+}
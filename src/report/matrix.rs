@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::SandboxReport;
+use crate::analysis_result::{AnalysisResult, ExecutionLog};
+use crate::sysmon_event::SysmonEventId;
+use crate::verdict::Verdict;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum BehaviorKind {
+    Technique,
+    Detection,
+    Process,
+}
+
+impl BehaviorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Technique => "technique",
+            Self::Detection => "detection",
+            Self::Process => "process",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileSummary {
+    pub profile: String,
+    pub execution_id: String,
+    pub machine: Option<String>,
+    pub score: f64,
+    pub verdict: Verdict,
+    pub events: usize,
+    pub processes: usize,
+    pub network: usize,
+    pub file_changes: usize,
+    pub registry_changes: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MatrixRow {
+    pub kind: BehaviorKind,
+    pub behavior: String,
+    pub profiles: Vec<String>,
+    pub divergent: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BehaviorMatrix {
+    pub profiles: Vec<ProfileSummary>,
+    pub rows: Vec<MatrixRow>,
+}
+
+fn profile_logs(result: &AnalysisResult) -> Vec<(&str, &ExecutionLog)> {
+    let mut logs: Vec<(&str, &ExecutionLog)> = Vec::new();
+    for log in &result.execution_logs {
+        let Some(profile) = log.profile.as_deref() else {
+            continue;
+        };
+        match logs.iter_mut().find(|(p, _)| *p == profile) {
+            Some(entry) => entry.1 = log,
+            None => logs.push((profile, log)),
+        }
+    }
+    logs
+}
+
+fn behaviors(report: &SandboxReport) -> BTreeSet<(BehaviorKind, String)> {
+    let techniques = report.techniques.iter().map(|t| {
+        let name = match &t.name {
+            Some(name) => format!("{} {}", t.id, name),
+            None => t.id.clone(),
+        };
+        (BehaviorKind::Technique, name)
+    });
+    let detections = report
+        .detections
+        .iter()
+        .map(|d| (BehaviorKind::Detection, d.name.clone()));
+    let processes = report
+        .events
+        .iter()
+        .filter(|e| e.event_id == SysmonEventId::PROCESS_CREATE)
+        .filter_map(|e| e.event_data.get("Image"))
+        .map(|image| {
+            let name = image.rsplit(['\\', '/']).next().unwrap_or(image);
+            (BehaviorKind::Process, name.to_lowercase())
+        });
+    techniques.chain(detections).chain(processes).collect()
+}
+
+impl BehaviorMatrix {
+    pub fn from_result(result: &AnalysisResult) -> Result<Option<Self>> {
+        let logs = profile_logs(result);
+        if logs.is_empty() {
+            return Ok(None);
+        }
+
+        let mut profiles = Vec::new();
+        let mut seen: BTreeMap<(BehaviorKind, String), Vec<String>> = BTreeMap::new();
+        for (profile, log) in &logs {
+            let report = SandboxReport::from_analysis_result(&AnalysisResult {
+                id: result.id.clone(),
+                hash: result.hash.clone(),
+                execution_logs: vec![(*log).clone()],
+                tenant: result.tenant.clone(),
+                metadata: result.metadata.clone(),
+            })?;
+            for behavior in behaviors(&report) {
+                seen.entry(behavior).or_default().push(profile.to_string());
+            }
+            profiles.push(ProfileSummary {
+                profile: profile.to_string(),
+                execution_id: log.id.clone(),
+                machine: log.machine.clone(),
+                score: report.score.score,
+                verdict: report.verdict.verdict,
+                events: report.events.len(),
+                processes: report
+                    .events
+                    .iter()
+                    .filter(|e| e.event_id == SysmonEventId::PROCESS_CREATE)
+                    .count(),
+                network: report.network.len(),
+                file_changes: report.file_changes.len(),
+                registry_changes: report.registry_changes.len(),
+            });
+        }
+
+        let rows = seen
+            .into_iter()
+            .map(|((kind, behavior), seen_in)| MatrixRow {
+                kind,
+                behavior,
+                divergent: seen_in.len() != logs.len(),
+                profiles: seen_in,
+            })
+            .collect();
+        Ok(Some(Self { profiles, rows }))
+    }
+
+    pub fn divergent(&self) -> impl Iterator<Item = &MatrixRow> {
+        self.rows.iter().filter(|r| r.divergent)
+    }
+}
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::analyzer::sigma::SigmaRule;
+use crate::analyzer::signature::SignatureRegistry;
+use crate::analyzer::yara_scan::YaraScanner;
+
+pub const SIGNATURE_FILE: &str = "pack.sig";
+pub const SIGMA_DIR: &str = "sigma";
+pub const SIGNATURES_DIR: &str = "signatures";
+pub const YARA_DIR: &str = "yara";
+
+const YARA_TIMEOUT: i32 = 10;
+
+fn default_interval_secs() -> u64 {
+    300
+}
+
+fn default_cache_dir() -> String {
+    "rule_packs".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RulePackConfig {
+    pub source: String,
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    #[serde(default)]
+    pub require_signature: bool,
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_cache_dir")]
+    pub cache_dir: String,
+}
+
+impl RulePackConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn is_remote(&self) -> bool {
+        self.source.starts_with("http://") || self.source.starts_with("https://")
+    }
+}
+
+fn pack_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            pack_files(root, &path, files)?;
+            continue;
+        }
+        let relative = path
+            .strip_prefix(root)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if relative != SIGNATURE_FILE {
+            files.push((relative, path));
+        }
+    }
+    Ok(())
+}
+
+pub fn pack_digest<P: AsRef<Path>>(dir: P) -> Result<[u8; 32]> {
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+    pack_files(dir, dir, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for (relative, path) in files {
+        let data = fs::read(&path)?;
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(&data);
+    }
+    Ok(hasher.finalize().into())
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(feature = "rulepacks")]
+fn unhex<const N: usize>(value: &str) -> Result<[u8; N]> {
+    let value = value.trim();
+    if value.len() != N * 2 || !value.is_ascii() {
+        bail!("Expected {} hex encoded bytes", N);
+    }
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16)?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(feature = "rulepacks")]
+fn verify(dir: &Path, digest: &[u8; 32], trusted_keys: &[String]) -> Result<Option<String>> {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    let path = dir.join(SIGNATURE_FILE);
+    if !path.exists() || trusted_keys.is_empty() {
+        return Ok(None);
+    }
+    let signature = Signature::from_bytes(&unhex(&fs::read_to_string(&path)?)?);
+    for key in trusted_keys {
+        let verifying_key = VerifyingKey::from_bytes(&unhex(key)?)?;
+        if verifying_key.verify(digest, &signature).is_ok() {
+            return Ok(Some(key.to_lowercase()));
+        }
+    }
+    bail!("Rule pack signature does not match any trusted key")
+}
+
+#[cfg(not(feature = "rulepacks"))]
+fn verify(_dir: &Path, _digest: &[u8; 32], trusted_keys: &[String]) -> Result<Option<String>> {
+    if !trusted_keys.is_empty() {
+        bail!("Rule pack signature verification requires the rulepacks feature");
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "rulepacks")]
+async fn download(url: &str, cache_dir: &Path) -> Result<PathBuf> {
+    let data = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+    let dir = cache_dir.join(hex(&Sha256::digest(&data)));
+    if dir.exists() {
+        return Ok(dir);
+    }
+    let staging = dir.with_extension("tmp");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(cache_dir)?;
+    zip::ZipArchive::new(std::io::Cursor::new(data))?.extract(&staging)?;
+    fs::rename(&staging, &dir)?;
+    Ok(dir)
+}
+
+#[cfg(not(feature = "rulepacks"))]
+async fn download(_url: &str, _cache_dir: &Path) -> Result<PathBuf> {
+    bail!("Remote rule packs require the rulepacks feature")
+}
+
+pub struct RulePack {
+    pub digest: String,
+    pub signer: Option<String>,
+    pub loaded_at: DateTime<Local>,
+    pub sigma: Vec<SigmaRule>,
+    pub signatures: SignatureRegistry,
+    pub yara: Option<YaraScanner>,
+}
+
+impl RulePack {
+    pub fn load<P: AsRef<Path>>(dir: P, config: &RulePackConfig) -> Result<Self> {
+        let dir = dir.as_ref();
+        let digest = pack_digest(dir)?;
+        let signer = verify(dir, &digest, &config.trusted_keys)?;
+        if signer.is_none() && config.require_signature {
+            bail!("Rule pack {} is not signed by a trusted key", dir.display());
+        }
+
+        let sigma = match dir.join(SIGMA_DIR) {
+            path if path.is_dir() => SigmaRule::load_dir(path)?,
+            _ => Vec::new(),
+        };
+        let mut signatures = SignatureRegistry::new();
+        let path = dir.join(SIGNATURES_DIR);
+        if path.is_dir() {
+            signatures.load_dir(path)?;
+        }
+        let yara = match dir.join(YARA_DIR) {
+            path if path.is_dir() => Some(YaraScanner::from_dir(path, YARA_TIMEOUT)?),
+            _ => None,
+        };
+
+        Ok(Self {
+            digest: hex(&digest),
+            signer,
+            loaded_at: Local::now(),
+            sigma,
+            signatures,
+            yara,
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RulePackStatus {
+    pub source: String,
+    pub digest: Option<String>,
+    pub signer: Option<String>,
+    pub loaded_at: Option<DateTime<Local>>,
+    pub sigma_rules: usize,
+    pub signatures: usize,
+    pub yara: bool,
+    pub last_error: Option<String>,
+}
+
+pub struct RulePackManager {
+    config: RulePackConfig,
+    current: RwLock<Option<Arc<RulePack>>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl fmt::Debug for RulePackManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RulePackManager")
+            .field("config", &self.config)
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+impl RulePackManager {
+    pub fn new(config: RulePackConfig) -> Self {
+        Self {
+            config,
+            current: RwLock::new(None),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    pub fn current(&self) -> Option<Arc<RulePack>> {
+        self.current.read().ok()?.clone()
+    }
+
+    pub async fn reload(&self) -> Result<bool> {
+        let outcome = self.try_reload().await;
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = outcome.as_ref().err().map(|e| e.to_string());
+        }
+        outcome
+    }
+
+    async fn try_reload(&self) -> Result<bool> {
+        let dir = match self.config.is_remote() {
+            true => download(&self.config.source, Path::new(&self.config.cache_dir)).await?,
+            false => PathBuf::from(&self.config.source),
+        };
+        let digest = hex(&pack_digest(&dir)?);
+        if self.current().is_some_and(|p| p.digest == digest) {
+            return Ok(false);
+        }
+
+        let pack = RulePack::load(&dir, &self.config)?;
+        info!(
+            "Loaded rule pack {} from {} ({} sigma rules, {} signatures{})",
+            pack.digest,
+            self.config.source,
+            pack.sigma.len(),
+            pack.signatures.len(),
+            if pack.yara.is_some() { ", yara" } else { "" }
+        );
+        *self
+            .current
+            .write()
+            .map_err(|_| anyhow!("Rule pack lock is poisoned"))? = Some(Arc::new(pack));
+        Ok(true)
+    }
+
+    pub async fn watch(self: Arc<Self>) {
+        if self.config.interval_secs == 0 {
+            return;
+        }
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.reload().await {
+                warn!(
+                    "Failed to reload rule pack from {}: {}",
+                    self.config.source, e
+                );
+            }
+        }
+    }
+
+    pub fn status(&self) -> RulePackStatus {
+        let pack = self.current();
+        RulePackStatus {
+            source: self.config.source.clone(),
+            digest: pack.as_ref().map(|p| p.digest.clone()),
+            signer: pack.as_ref().and_then(|p| p.signer.clone()),
+            loaded_at: pack.as_ref().map(|p| p.loaded_at),
+            sigma_rules: pack.as_ref().map_or(0, |p| p.sigma.len()),
+            signatures: pack.as_ref().map_or(0, |p| p.signatures.len()),
+            yara: pack.as_ref().is_some_and(|p| p.yara.is_some()),
+            last_error: self.last_error.lock().ok().and_then(|e| e.clone()),
+        }
+    }
+}
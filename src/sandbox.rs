@@ -1,16 +1,18 @@
 use std::collections::HashMap;
 use std::fs::{create_dir_all, File};
-use std::io::{BufRead, BufReader};
+use std::io::BufReader;
 
 use anyhow::{Context, Result};
 use chrono::Local;
 use log::{info, warn};
-use regex::Regex;
 use tokio::time::{timeout, Duration};
 use uuid::Uuid;
 
-use crate::analysis_result::ExecutionLog;
-use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+use crate::analysis_result::{artifact_dir, ExecutionLog};
+use crate::artifacts::{collect, is_file_event, ARTIFACTS_FILE};
+use crate::metadata::SampleMetadata;
+use crate::quarantine::ParseFailureSummary;
+use crate::syslog::SyslogReader;
 use crate::vm::Vm;
 
 pub struct Sandbox {
@@ -30,7 +32,7 @@ impl Sandbox {
 
     pub async fn exec(&self, id: &str, sample_path: &str, limit_time: u64) -> Result<ExecutionLog> {
         let execution_id = Uuid::new_v4().to_string();
-        let artifact_dir = format!("analysis_result/{}/artifact/{}", id, execution_id);
+        let artifact_dir = artifact_dir(id, &execution_id);
         create_dir_all(&artifact_dir)?;
 
         info!("Pushing sysmon config file...");
@@ -65,37 +67,56 @@ impl Sandbox {
 
         info!("Parsing syslog...");
         let syslog = File::open(syslog_path)?;
-        let re = Regex::new(r"[A-Z][a-z]{2}\s+\d+\s\d\d:\d\d:\d\d\s\S+\ssysmon\S*:\s(.+)").unwrap();
 
         let mut created_files = HashMap::new();
         let mut sysmon_events = Vec::new();
-        for l in BufReader::new(syslog).lines() {
-            if let Some(c) = re.captures(&l?) {
-                if let Ok(event) = SysmonEvent::from_xml(&c[1]) {
-                    if event.event_id == SysmonEventId::FILE_CREATE {
-                        let file_name = event
-                            .event_data
-                            .get("TargetFilename")
-                            .context("No TargetFilename attribute")?;
-                        if !created_files.contains_key(file_name) {
-                            created_files.insert(file_name.to_string(), Uuid::new_v4().to_string());
-                        }
-                    }
-
-                    sysmon_events.push(event);
+        for event in SyslogReader::new(BufReader::new(syslog)) {
+            let event = event?;
+            if is_file_event(&event) {
+                let file_name = event
+                    .event_data
+                    .get("TargetFilename")
+                    .context("No TargetFilename attribute")?;
+                if !created_files.contains_key(file_name) {
+                    created_files.insert(file_name.to_string(), Uuid::new_v4().to_string());
                 }
-            };
+            }
+
+            sysmon_events.push(event);
         }
 
         info!("Pulling created files...");
-        for f in &created_files {
-            if let Err(e) = self
-                .vm
-                .pull_file(f.0, format!("{}/{}", &artifact_dir, f.1))
-                .await
-            {
-                warn!("Failed to pull '{}': {}", f.0, e);
+        let mut pulled = Vec::new();
+        for (file_name, name) in &created_files {
+            let path = format!("{}/{}", &artifact_dir, name);
+            if let Err(e) = self.vm.pull_file(file_name, &path).await {
+                warn!("Failed to pull '{}': {}", file_name, e);
+                continue;
             }
+            let content = std::fs::read(&path)?;
+            std::fs::remove_file(&path)?;
+            pulled.push((file_name.clone(), content));
+        }
+
+        let artifacts = collect(
+            &sysmon_events,
+            pulled
+                .iter()
+                .map(|(path, content)| (path.as_str(), content.as_slice())),
+        );
+        created_files.clear();
+        for (artifact, (_, content)) in artifacts.iter().zip(&pulled) {
+            std::fs::write(
+                format!("{}/{}", &artifact_dir, artifact.file_name()),
+                content,
+            )?;
+            created_files.insert(artifact.path.clone(), artifact.file_name().to_string());
+        }
+        if !artifacts.is_empty() {
+            std::fs::write(
+                format!("{}/{}", &artifact_dir, ARTIFACTS_FILE),
+                serde_json::to_vec_pretty(&artifacts)?,
+            )?;
         }
 
         Ok(ExecutionLog {
@@ -103,6 +124,22 @@ impl Sandbox {
             time: Local::now(),
             sysmon_events,
             created_files,
+            artifacts,
+            telemetry: Vec::new(),
+            memory: Vec::new(),
+            machine: None,
+            snapshot: None,
+            mutexes: Vec::new(),
+            clipboard: Vec::new(),
+            image: None,
+            terminated: None,
+            purged: Vec::new(),
+            url: None,
+            profile: None,
+            tampered: Vec::new(),
+            parse_failures: ParseFailureSummary::default(),
+            derivation: Vec::new(),
+            metadata: SampleMetadata::default(),
         })
     }
 }
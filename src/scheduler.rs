@@ -0,0 +1,452 @@
+pub mod profile;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinSet;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use crate::agent::protocol::{ExecutionRequest, TimeWarp};
+use crate::analysis_result::{artifact_dir, ExecutionLog};
+use crate::lineage::Derivation;
+use crate::metrics::{
+    self, JOBS, JOBS_SUBMITTED, MACHINES, QUEUE_DEPTH, QUEUE_WAIT, RUNS, RUN_DURATION,
+};
+use crate::orchestrator::limits::ResourceLimits;
+use crate::orchestrator::{save_artifacts, Hypervisor, Orchestrator, VmSpec};
+use crate::pcap::ids::IdsConfig;
+use profile::DetonationProfile;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobState {
+    pub const ALL: &'static [Self] = &[Self::Pending, Self::Running, Self::Completed, Self::Failed];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Job {
+    pub id: String,
+    pub analysis_id: String,
+    pub sample_path: String,
+    pub request: ExecutionRequest,
+    pub tags: Vec<String>,
+    pub priority: i32,
+    pub max_attempts: u32,
+    pub attempts: u32,
+    pub state: JobState,
+    pub machine: Option<String>,
+    pub error: Option<String>,
+    pub submitted: DateTime<Local>,
+    pub retry_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub limits: Option<ResourceLimits>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub derivation: Vec<Derivation>,
+}
+
+impl Job {
+    pub fn new(analysis_id: &str, sample_path: &str, request: ExecutionRequest) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            analysis_id: analysis_id.to_string(),
+            sample_path: sample_path.to_string(),
+            request,
+            tags: Vec::new(),
+            priority: 0,
+            max_attempts: 3,
+            attempts: 0,
+            state: JobState::Pending,
+            machine: None,
+            error: None,
+            submitted: Local::now(),
+            retry_at: None,
+            parent: None,
+            tenant: None,
+            limits: None,
+            profile: None,
+            derivation: Vec::new(),
+        }
+    }
+
+    fn is_ready(&self, now: DateTime<Local>) -> bool {
+        self.state == JobState::Pending && self.retry_at.is_none_or(|t| t <= now)
+    }
+}
+
+pub trait JobStore {
+    fn save(&mut self, job: &Job) -> Result<()>;
+
+    fn load_unfinished(&mut self) -> Result<Vec<Job>>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    jobs: HashMap<String, Job>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl JobStore for MemoryStore {
+    fn save(&mut self, job: &Job) -> Result<()> {
+        self.jobs.insert(job.id.clone(), job.clone());
+        Ok(())
+    }
+
+    fn load_unfinished(&mut self) -> Result<Vec<Job>> {
+        Ok(self
+            .jobs
+            .values()
+            .filter(|j| !j.state.is_finished())
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Machine {
+    pub spec: VmSpec,
+    pub tags: Vec<String>,
+}
+
+impl Machine {
+    pub fn new(spec: VmSpec, tags: &[&str]) -> Self {
+        Self {
+            spec,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    pub fn accepts(&self, job: &Job) -> bool {
+        job.tags.iter().all(|t| self.tags.contains(t))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerOptions {
+    pub max_concurrency: usize,
+    pub retry_delay: Duration,
+    pub poll_interval: Duration,
+    pub time_warp: Option<TimeWarp>,
+    pub limits: ResourceLimits,
+    pub ids: Option<IdsConfig>,
+}
+
+impl Default for SchedulerOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 4,
+            retry_delay: Duration::from_secs(30),
+            poll_interval: Duration::from_secs(5),
+            time_warp: None,
+            limits: ResourceLimits::default(),
+            ids: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JobResult {
+    pub job: Job,
+    pub execution_log: ExecutionLog,
+}
+
+pub struct Scheduler<H, S> {
+    orchestrator: Arc<Orchestrator<H>>,
+    machines: Vec<Machine>,
+    store: Mutex<S>,
+    jobs: Mutex<HashMap<String, Job>>,
+    notify: Notify,
+    options: SchedulerOptions,
+}
+
+impl<H, S> Scheduler<H, S>
+where
+    H: Hypervisor + Send + Sync + 'static,
+    S: JobStore,
+{
+    pub fn new(orchestrator: Orchestrator<H>, machines: Vec<Machine>, store: S) -> Result<Self> {
+        Self::with_options(orchestrator, machines, store, SchedulerOptions::default())
+    }
+
+    pub fn with_options(
+        orchestrator: Orchestrator<H>,
+        machines: Vec<Machine>,
+        mut store: S,
+        options: SchedulerOptions,
+    ) -> Result<Self> {
+        let mut jobs = HashMap::new();
+        for mut job in store.load_unfinished()? {
+            if job.state == JobState::Running {
+                warn!("Requeueing interrupted job {}", job.id);
+                job.state = JobState::Pending;
+                job.machine = None;
+                store.save(&job)?;
+            }
+            jobs.insert(job.id.clone(), job);
+        }
+        if !jobs.is_empty() {
+            info!("Recovered {} queued jobs", jobs.len());
+        }
+
+        Ok(Self {
+            orchestrator: Arc::new(orchestrator),
+            machines,
+            store: Mutex::new(store),
+            jobs: Mutex::new(jobs),
+            notify: Notify::new(),
+            options,
+        })
+    }
+
+    fn save(&self, job: &Job) -> Result<()> {
+        self.store
+            .lock()
+            .map_err(|_| anyhow!("Job store lock poisoned"))?
+            .save(job)?;
+        self.jobs
+            .lock()
+            .map_err(|_| anyhow!("Job table lock poisoned"))?
+            .insert(job.id.clone(), job.clone());
+        Ok(())
+    }
+
+    pub fn submit(&self, job: Job) -> Result<String> {
+        if !self.machines.iter().any(|m| m.accepts(&job)) {
+            return Err(anyhow!("No machine matches tags {:?}", job.tags));
+        }
+        let id = job.id.clone();
+        self.save(&job)?;
+        JOBS_SUBMITTED.inc(&[]);
+        self.notify.notify_one();
+        Ok(id)
+    }
+
+    pub fn submit_profiles(&self, job: Job, profiles: &[DetonationProfile]) -> Result<Vec<String>> {
+        let jobs: Vec<Job> = profiles.iter().map(|p| p.apply(&job)).collect();
+        if let Some((profile, job)) = profiles
+            .iter()
+            .zip(&jobs)
+            .find(|(_, job)| !self.machines.iter().any(|m| m.accepts(job)))
+        {
+            return Err(anyhow!(
+                "No machine for profile {} matches tags {:?}",
+                profile.name,
+                job.tags
+            ));
+        }
+        let mut ids: Vec<String> = Vec::new();
+        for mut job in jobs {
+            job.parent = ids.first().cloned().or(job.parent);
+            ids.push(self.submit(job)?);
+        }
+        Ok(ids)
+    }
+
+    pub fn job(&self, id: &str) -> Option<Job> {
+        self.jobs.lock().ok()?.get(id).cloned()
+    }
+
+    pub fn jobs(&self) -> Vec<Job> {
+        self.jobs
+            .lock()
+            .map(|jobs| jobs.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn record_metrics(&self) {
+        let jobs = self.jobs();
+        for state in JobState::ALL {
+            let count = jobs.iter().filter(|j| j.state == *state).count();
+            JOBS.set(&[("state", state.name())], count as f64);
+        }
+        let pending = jobs.iter().filter(|j| j.state == JobState::Pending).count();
+        QUEUE_DEPTH.set(&[], pending as f64);
+        let busy = jobs.iter().filter(|j| j.state == JobState::Running).count();
+        MACHINES.set(&[("status", "busy")], busy as f64);
+        MACHINES.set(
+            &[("status", "idle")],
+            self.machines.len().saturating_sub(busy) as f64,
+        );
+    }
+
+    fn next_assignment(&self, idle: &[usize]) -> Option<(Job, usize)> {
+        let now = Local::now();
+        let jobs = self.jobs.lock().ok()?;
+        let mut ready: Vec<&Job> = jobs.values().filter(|j| j.is_ready(now)).collect();
+        ready.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then(a.submitted.cmp(&b.submitted))
+        });
+        ready.into_iter().find_map(|job| {
+            idle.iter()
+                .find(|&&m| self.machines[m].accepts(job))
+                .map(|&m| (job.clone(), m))
+        })
+    }
+
+    fn finish(
+        &self,
+        mut job: Job,
+        result: Result<ExecutionLog>,
+        results: &mpsc::UnboundedSender<JobResult>,
+    ) -> Result<()> {
+        job.machine = None;
+        match result {
+            Ok(execution_log) => {
+                match &execution_log.terminated {
+                    Some(violation) => {
+                        warn!("Job {} terminated by limit: {}", job.id, violation);
+                        RUNS.inc(&[("outcome", "terminated")]);
+                    }
+                    None => {
+                        info!("Job {} completed", job.id);
+                        RUNS.inc(&[("outcome", "completed")]);
+                    }
+                }
+                job.state = JobState::Completed;
+                job.error = None;
+                self.save(&job)?;
+                let _ = results.send(JobResult { job, execution_log });
+            }
+            Err(e) if job.attempts < job.max_attempts => {
+                warn!(
+                    "Job {} failed (attempt {}/{}): {}",
+                    job.id, job.attempts, job.max_attempts, e
+                );
+                RUNS.inc(&[("outcome", "retried")]);
+                job.state = JobState::Pending;
+                job.error = Some(e.to_string());
+                job.retry_at = chrono::Duration::from_std(self.options.retry_delay)
+                    .ok()
+                    .map(|d| Local::now() + d);
+                self.save(&job)?;
+            }
+            Err(e) => {
+                warn!("Job {} failed permanently: {}", job.id, e);
+                RUNS.inc(&[("outcome", "failed")]);
+                job.state = JobState::Failed;
+                job.error = Some(e.to_string());
+                self.save(&job)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn serve(&self, results: mpsc::UnboundedSender<JobResult>) -> Result<()> {
+        let mut idle: Vec<usize> = (0..self.machines.len()).collect();
+        let mut running = JoinSet::new();
+
+        loop {
+            while running.len() < self.options.max_concurrency {
+                let Some((mut job, machine)) = self.next_assignment(&idle) else {
+                    break;
+                };
+                idle.retain(|&m| m != machine);
+
+                let vm = self.machines[machine].spec.clone();
+                info!("Assigning job {} to {}", job.id, vm.name);
+                if job.attempts == 0 {
+                    let waited = (Local::now() - job.submitted).num_milliseconds();
+                    QUEUE_WAIT.observe(&[], waited.max(0) as f64 / 1000.0);
+                }
+                job.state = JobState::Running;
+                job.attempts += 1;
+                job.retry_at = None;
+                job.machine = Some(vm.name.clone());
+                self.save(&job)?;
+
+                let orchestrator = self.orchestrator.clone();
+                let mut request = job.request.clone();
+                if request.time_warp.is_none() {
+                    request.time_warp = self.options.time_warp;
+                }
+                let limits = match &job.limits {
+                    Some(limits) => self.options.limits.merge(limits),
+                    None => self.options.limits.clone(),
+                };
+                let ids = self.options.ids.clone();
+                running.spawn(async move {
+                    let mut span = metrics::timed(
+                        "scheduler.run_job",
+                        &RUN_DURATION,
+                        &[("machine", vm.name.as_str())],
+                    );
+                    span.attribute("job.id", &job.id);
+                    span.attribute("analysis.id", &job.analysis_id);
+                    let result = async {
+                        let sample = tokio::fs::read(&job.sample_path).await?;
+                        let stages = orchestrator.run(&vm, &request, &limits, &sample).await?;
+                        let mut log = save_artifacts(&job.analysis_id, &stages)?;
+                        if let Some(ids) = &ids {
+                            let dir = artifact_dir(&job.analysis_id, &log.id);
+                            if let Err(e) = ids.inspect(&dir).await {
+                                warn!("Failed to inspect traffic of {}: {}", job.id, e);
+                            }
+                        }
+                        log.machine = Some(vm.name.clone());
+                        log.snapshot = Some(vm.snapshot.clone());
+                        log.image = vm.image.clone();
+                        log.url = job.request.url.clone();
+                        log.profile = job.profile.clone();
+                        log.derivation = job.derivation.clone();
+                        Ok::<_, anyhow::Error>(log)
+                    }
+                    .await;
+                    if let Err(e) = &result {
+                        span.fail(e);
+                    }
+                    drop(span);
+                    (job, machine, result)
+                });
+            }
+
+            tokio::select! {
+                Some(done) = running.join_next() => {
+                    let (job, machine, result) = done?;
+                    idle.push(machine);
+                    self.finish(job, result, &results)?;
+                }
+                _ = self.notify.notified() => (),
+                _ = sleep(self.options.poll_interval) => (),
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::Job;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DetonationProfile {
+    pub name: String,
+    pub os: String,
+    pub arch: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl DetonationProfile {
+    pub fn new(name: &str, os: &str, arch: &str, tags: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            os: os.to_string(),
+            arch: arch.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            timeout_secs: None,
+        }
+    }
+
+    pub fn apply(&self, job: &Job) -> Job {
+        let mut job = Job {
+            id: Uuid::new_v4().to_string(),
+            profile: Some(self.name.clone()),
+            ..job.clone()
+        };
+        for tag in &self.tags {
+            if !job.tags.contains(tag) {
+                job.tags.push(tag.clone());
+            }
+        }
+        if let Some(timeout) = self.timeout_secs {
+            job.request.timeout_secs = timeout;
+        }
+        job
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProfileRegistry {
+    #[serde(default)]
+    pub profiles: Vec<DetonationProfile>,
+    #[serde(default)]
+    pub sets: HashMap<String, Vec<String>>,
+}
+
+impl ProfileRegistry {
+    pub fn with_defaults() -> Self {
+        let profiles = vec![
+            DetonationProfile::new("win10-x64", "Windows 10", "x64", &["win10", "x64"]),
+            DetonationProfile::new("win11-x64", "Windows 11", "x64", &["win11", "x64"]),
+            DetonationProfile::new("win7-x86", "Windows 7", "x86", &["win7", "x86"]),
+            DetonationProfile::new(
+                "ubuntu-22.04",
+                "Ubuntu 22.04",
+                "x64",
+                &["ubuntu2204", "x64"],
+            ),
+        ];
+        let windows: Vec<String> = profiles
+            .iter()
+            .filter(|p| p.os.starts_with("Windows"))
+            .map(|p| p.name.clone())
+            .collect();
+        let all = profiles.iter().map(|p| p.name.clone()).collect();
+        Self {
+            profiles,
+            sets: HashMap::from([("windows".to_string(), windows), ("all".to_string(), all)]),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DetonationProfile> {
+        self.profiles
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn resolve(&self, names: &[&str]) -> Result<Vec<DetonationProfile>> {
+        let mut resolved: Vec<DetonationProfile> = Vec::new();
+        for name in names {
+            let members: Vec<&str> = match self.sets.get(*name) {
+                Some(set) => set.iter().map(String::as_str).collect(),
+                None => vec![*name],
+            };
+            for member in members {
+                let profile = self
+                    .get(member)
+                    .ok_or_else(|| anyhow!("Unknown detonation profile '{}'", member))?;
+                if !resolved.contains(profile) {
+                    resolved.push(profile.clone());
+                }
+            }
+        }
+        Ok(resolved)
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+
+use super::{Job, JobState, JobStore};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS jobs (
+    id TEXT PRIMARY KEY,
+    state TEXT NOT NULL,
+    job TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS jobs_state ON jobs(state);
+";
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+}
+
+impl JobStore for SqliteStore {
+    fn save(&mut self, job: &Job) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO jobs (id, state, job) VALUES (?1, ?2, ?3)",
+            params![job.id, job.state.name(), serde_json::to_string(job)?],
+        )?;
+        Ok(())
+    }
+
+    fn load_unfinished(&mut self) -> Result<Vec<Job>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT job FROM jobs WHERE state IN (?1, ?2)")?;
+        let rows = stmt.query_map(
+            params![JobState::Pending.name(), JobState::Running.name()],
+            |row| row.get::<_, String>(0),
+        )?;
+        let mut jobs = Vec::new();
+        for row in rows {
+            jobs.push(serde_json::from_str(&row?)?);
+        }
+        Ok(jobs)
+    }
+}
@@ -0,0 +1,554 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::report::{Detection, SandboxReport};
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub const SCHEMA_VERSION: u32 = 1;
+pub const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+const SCHEMA_BASE_URI: &str = "https://schemas.malware-analysis-sandbox.local";
+
+const REPORT_LIST_FIELDS: &[&str] = &[
+    "capabilities",
+    "scripts",
+    "captured_scripts",
+    "network",
+    "traffic",
+    "ids_alerts",
+    "http",
+    "dns",
+    "simulated_requests",
+    "beacons",
+    "file_changes",
+    "artifacts",
+    "filesystem",
+    "registry_changes",
+    "sync_objects",
+    "services",
+    "scheduled_tasks",
+    "wmi_subscriptions",
+    "credential_theft",
+    "tampered",
+    "detections",
+    "techniques",
+    "enrichment",
+    "script_blocks",
+    "amsi",
+    "logons",
+    "api_calls",
+    "memory",
+    "analyzers",
+    "derivation",
+    "descendants",
+    "events",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaKind {
+    Event,
+    Detection,
+    Report,
+}
+
+impl SchemaKind {
+    pub const ALL: &'static [Self] = &[Self::Event, Self::Detection, Self::Report];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Event => "event",
+            Self::Detection => "detection",
+            Self::Report => "report",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|k| k.name().eq_ignore_ascii_case(name))
+    }
+
+    fn id(self) -> String {
+        format!(
+            "{}/v{}/{}.json",
+            SCHEMA_BASE_URI,
+            SCHEMA_VERSION,
+            self.name()
+        )
+    }
+
+    pub fn schema(self) -> Value {
+        let mut schema = match self {
+            Self::Event => event_schema(),
+            Self::Detection => detection_schema(),
+            Self::Report => report_schema(),
+        };
+        schema["$schema"] = json!("https://json-schema.org/draft/2020-12/schema");
+        schema["$id"] = json!(self.id());
+        schema
+    }
+}
+
+fn version_property() -> Value {
+    json!({
+        "type": "integer",
+        "const": SCHEMA_VERSION,
+        "description": "Version of this schema. Readers upgrade older documents before use."
+    })
+}
+
+fn event_schema() -> Value {
+    json!({
+        "title": "SysmonEvent",
+        "description": "A single Sysmon event, or a telemetry record normalized to Sysmon's shape.",
+        "type": "object",
+        "required": ["event_id", "time_created", "event_data"],
+        "properties": {
+            "schema_version": version_property(),
+            "event_id": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 255,
+                "description": "Sysmon event id, e.g. 1 for ProcessCreate. 255 is the Sysmon error event."
+            },
+            "time_created": {
+                "type": "string",
+                "format": "date-time",
+                "description": "System/TimeCreated/@SystemTime as RFC 3339."
+            },
+            "computer": {
+                "type": ["string", "null"],
+                "description": "System/Computer."
+            },
+            "record_id": {
+                "type": ["integer", "null"],
+                "minimum": 0,
+                "description": "System/EventRecordID, unique per channel and computer."
+            },
+            "channel": {
+                "type": ["string", "null"],
+                "description": "System/Channel."
+            },
+            "event_data": {
+                "type": "object",
+                "additionalProperties": { "type": "string" },
+                "description": "EventData/Data values keyed by their Name attribute. Keys prefixed with _enriched. are added by the sandbox."
+            }
+        }
+    })
+}
+
+fn detection_schema() -> Value {
+    json!({
+        "title": "Detection",
+        "description": "A finding raised by a builtin detector, a signature, a sigma rule or a plugin.",
+        "type": "object",
+        "required": ["source", "name", "tags", "events"],
+        "properties": {
+            "schema_version": version_property(),
+            "source": {
+                "type": "string",
+                "description": "Detector that raised the finding, e.g. builtin, sigma, signature or ransomware."
+            },
+            "name": { "type": "string", "description": "Human readable name of the finding." },
+            "level": {
+                "type": ["string", "null"],
+                "enum": ["informational", "low", "medium", "high", "critical", null],
+                "description": "Severity in sigma terms."
+            },
+            "tags": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Sigma style tags such as attack.execution or attack.t1059.001."
+            },
+            "events": {
+                "type": "array",
+                "items": { "$ref": "event.json" },
+                "description": "Events that triggered the finding."
+            }
+        }
+    })
+}
+
+fn report_schema() -> Value {
+    let mut properties = Map::new();
+    properties.insert(SCHEMA_VERSION_FIELD.to_string(), version_property());
+    properties.insert(
+        "id".to_string(),
+        json!({ "type": "string", "description": "Analysis id." }),
+    );
+    properties.insert(
+        "hash".to_string(),
+        json!({ "type": "string", "description": "SHA3-512 of the sample." }),
+    );
+    properties.insert(
+        "execution_id".to_string(),
+        json!({ "type": "string", "description": "Id of the execution the report covers." }),
+    );
+    properties.insert(
+        "time".to_string(),
+        json!({ "type": "string", "format": "date-time", "description": "Time of the execution." }),
+    );
+    properties.insert(
+        "metadata".to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "campaign": { "type": ["string", "null"] },
+                "source": { "type": ["string", "null"] },
+                "tlp": {
+                    "type": ["string", "null"],
+                    "enum": ["clear", "green", "amber", "amber_strict", "red", null]
+                },
+                "notes": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["author", "time", "text"],
+                        "properties": {
+                            "author": { "type": "string" },
+                            "time": { "type": "string", "format": "date-time" },
+                            "text": { "type": "string" }
+                        }
+                    }
+                }
+            },
+            "description": "Analyst tags, campaign, source, TLP level and notes of the sample merged with those of the run."
+        }),
+    );
+    properties.insert(
+        "score".to_string(),
+        json!({
+            "type": "object",
+            "required": ["score", "signatures"],
+            "properties": {
+                "score": { "type": "number", "minimum": 0, "maximum": 10 },
+                "signatures": { "type": "array" }
+            },
+            "description": "Overall maliciousness score and the behavioral signatures behind it."
+        }),
+    );
+    properties.insert(
+        "verdict".to_string(),
+        json!({
+            "type": "object",
+            "required": ["verdict", "rationale"],
+            "properties": {
+                "verdict": { "type": "string", "enum": ["unknown", "clean", "suspicious", "malicious"] },
+                "rationale": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["step", "description", "verdict"],
+                        "properties": {
+                            "step": { "type": "string" },
+                            "description": { "type": "string" },
+                            "verdict": { "type": "string" }
+                        }
+                    }
+                }
+            },
+            "description": "Verdict derived from the score, possibly overridden by a policy rule, with the steps that led to it."
+        }),
+    );
+    properties.insert(
+        "clock".to_string(),
+        json!({
+            "type": ["object", "null"],
+            "required": ["offset_ms", "samples", "spread_ms"],
+            "properties": {
+                "offset_ms": { "type": "integer" },
+                "samples": { "type": "integer", "minimum": 1 },
+                "spread_ms": { "type": "integer", "minimum": 0 }
+            },
+            "description": "Guest clock skew relative to the host capture clock, measured from network connections matched to captured flows and applied to guest timestamps on the merged timeline."
+        }),
+    );
+    properties.insert(
+        "parse_failures".to_string(),
+        json!({
+            "type": "object",
+            "required": ["total", "by_kind", "by_source", "examples"],
+            "properties": {
+                "total": { "type": "integer", "minimum": 0 },
+                "by_kind": { "type": "object", "additionalProperties": { "type": "integer" } },
+                "by_source": { "type": "object", "additionalProperties": { "type": "integer" } },
+                "examples": { "type": "array" }
+            },
+            "description": "Records that could not be parsed during ingestion and were quarantined instead of aborting the run."
+        }),
+    );
+    for field in REPORT_LIST_FIELDS {
+        properties.insert(field.to_string(), json!({ "type": "array" }));
+    }
+    properties.insert(
+        "detections".to_string(),
+        json!({
+            "type": "array",
+            "items": { "$ref": "detection.json" },
+            "description": "All findings raised for the execution."
+        }),
+    );
+    properties.insert(
+        "events".to_string(),
+        json!({
+            "type": "array",
+            "items": { "$ref": "event.json" },
+            "description": "Every event of the execution in time order."
+        }),
+    );
+
+    json!({
+        "title": "SandboxReport",
+        "description": "Analysis report for one execution of a sample. Unknown properties may be added in minor revisions and must be ignored by readers.",
+        "type": "object",
+        "required": [SCHEMA_VERSION_FIELD, "id", "hash", "execution_id", "time", "score", "detections", "events"],
+        "properties": properties
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Versioned<T> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            data,
+        }
+    }
+}
+
+pub fn version_of(value: &Value) -> Result<u32> {
+    match value.get(SCHEMA_VERSION_FIELD) {
+        None | Some(Value::Null) => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .context("schema_version is not an integer"),
+    }
+}
+
+fn upgrade_event_v0(event: &mut Map<String, Value>) -> Result<()> {
+    if let Some(Value::String(id)) = event.get("event_id") {
+        let leading = id.split_whitespace().next().unwrap_or_default();
+        let id = leading
+            .parse::<SysmonEventId>()
+            .ok()
+            .or_else(|| SysmonEventId::from_name(id))
+            .with_context(|| format!("Invalid event_id {}", id))?;
+        event.insert("event_id".to_string(), json!(id.value()));
+    }
+    for field in ["computer", "record_id", "channel"] {
+        event.entry(field).or_insert(Value::Null);
+    }
+    if let Some(Value::Object(data)) = event.get_mut("event_data") {
+        for value in data.values_mut() {
+            match value {
+                Value::String(_) => (),
+                Value::Null => *value = json!(""),
+                other => *other = json!(other.to_string()),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn upgrade_detection_v0(detection: &mut Map<String, Value>) -> Result<()> {
+    detection.entry("level").or_insert(Value::Null);
+    detection.entry("tags").or_insert(json!([]));
+    if let Some(Value::Array(events)) = detection.get_mut("events") {
+        for event in events {
+            upgrade_nested(SchemaKind::Event, event)?;
+        }
+    }
+    Ok(())
+}
+
+fn upgrade_report_v0(report: &mut Map<String, Value>) -> Result<()> {
+    for field in REPORT_LIST_FIELDS {
+        report.entry(*field).or_insert(json!([]));
+    }
+    if let Some(Value::Array(detections)) = report.get_mut("detections") {
+        for detection in detections {
+            upgrade_nested(SchemaKind::Detection, detection)?;
+        }
+    }
+    if let Some(Value::Array(events)) = report.get_mut("events") {
+        for event in events {
+            upgrade_nested(SchemaKind::Event, event)?;
+        }
+    }
+    Ok(())
+}
+
+fn upgrade_nested(kind: SchemaKind, value: &mut Value) -> Result<()> {
+    let object = value.as_object_mut().context("Expected a JSON object")?;
+    match kind {
+        SchemaKind::Event => upgrade_event_v0(object),
+        SchemaKind::Detection => upgrade_detection_v0(object),
+        SchemaKind::Report => upgrade_report_v0(object),
+    }
+}
+
+pub fn upgrade(kind: SchemaKind, mut value: Value) -> Result<Value> {
+    let version = version_of(&value)?;
+    if version > SCHEMA_VERSION {
+        bail!(
+            "{} schema version {} is newer than the supported version {}",
+            kind.name(),
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    if version == 0 {
+        upgrade_nested(kind, &mut value)?;
+    }
+    value[SCHEMA_VERSION_FIELD] = json!(SCHEMA_VERSION);
+    Ok(value)
+}
+
+pub fn event_to_value(event: &SysmonEvent) -> Result<Value> {
+    Ok(serde_json::to_value(Versioned::new(event))?)
+}
+
+pub fn event_from_value(value: Value) -> Result<SysmonEvent> {
+    let versioned: Versioned<SysmonEvent> =
+        serde_json::from_value(upgrade(SchemaKind::Event, value)?)?;
+    Ok(versioned.data)
+}
+
+pub fn detection_from_value(value: Value) -> Result<Detection> {
+    let versioned: Versioned<Detection> =
+        serde_json::from_value(upgrade(SchemaKind::Detection, value)?)?;
+    Ok(versioned.data)
+}
+
+pub fn report_from_value(value: Value) -> Result<SandboxReport> {
+    Ok(serde_json::from_value(upgrade(SchemaKind::Report, value)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> SysmonEvent {
+        serde_json::from_value(json!({
+            "event_id": 1,
+            "time_created": "2024-01-01T00:00:00+00:00",
+            "computer": "WIN10",
+            "record_id": 7,
+            "channel": "Microsoft-Windows-Sysmon/Operational",
+            "event_data": {
+                "Image": "C:\\Windows\\System32\\cmd.exe",
+                "CommandLine": "cmd.exe /c whoami",
+                "ProcessId": "42"
+            }
+        }))
+        .unwrap()
+    }
+
+    fn detection() -> Detection {
+        Detection {
+            source: "sigma".to_string(),
+            name: "Suspicious shell".to_string(),
+            level: Some("high".to_string()),
+            tags: vec!["attack.t1059".to_string()],
+            events: vec![event()],
+        }
+    }
+
+    #[test]
+    fn event_round_trips() {
+        let value = event_to_value(&event()).unwrap();
+        assert_eq!(value[SCHEMA_VERSION_FIELD], json!(SCHEMA_VERSION));
+        let parsed = event_from_value(value.clone()).unwrap();
+        assert_eq!(event_to_value(&parsed).unwrap(), value);
+    }
+
+    #[test]
+    fn event_upgrades_from_v0() {
+        let parsed = event_from_value(json!({
+            "event_id": "1 (Process creation)",
+            "time_created": "2024-01-01T00:00:00+00:00",
+            "event_data": {"Image": "cmd.exe", "ProcessId": 42, "ParentImage": null}
+        }))
+        .unwrap();
+        assert_eq!(parsed.event_id, SysmonEventId::PROCESS_CREATE);
+        assert_eq!(parsed.computer, None);
+        assert_eq!(parsed.record_id, None);
+        assert_eq!(parsed.event_data["ProcessId"], "42");
+        assert_eq!(parsed.event_data["ParentImage"], "");
+    }
+
+    #[test]
+    fn detection_round_trips() {
+        let value = serde_json::to_value(Versioned::new(detection())).unwrap();
+        let parsed = detection_from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(Versioned::new(parsed)).unwrap(), value);
+    }
+
+    #[test]
+    fn detection_upgrades_from_v0() {
+        let parsed = detection_from_value(json!({
+            "source": "sigma",
+            "name": "Suspicious shell",
+            "events": [{
+                "event_id": "1",
+                "time_created": "2024-01-01T00:00:00+00:00",
+                "event_data": {"Image": "cmd.exe"}
+            }]
+        }))
+        .unwrap();
+        assert_eq!(parsed.level, None);
+        assert!(parsed.tags.is_empty());
+        assert_eq!(parsed.events[0].event_id, SysmonEventId::PROCESS_CREATE);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut value = event_to_value(&event()).unwrap();
+        value[SCHEMA_VERSION_FIELD] = json!(SCHEMA_VERSION + 1);
+        assert!(event_from_value(value).is_err());
+    }
+
+    #[test]
+    fn report_round_trips() {
+        let mut report = SandboxReport::from_events("abc", vec![event()]).unwrap();
+        report.detections.push(detection());
+        let value: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        let parsed = report_from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), value);
+    }
+
+    #[test]
+    fn report_upgrades_from_v0() {
+        let mut report = SandboxReport::from_events("abc", vec![event()]).unwrap();
+        report.detections.push(detection());
+        let mut value = serde_json::to_value(&report).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove(SCHEMA_VERSION_FIELD);
+        for field in ["dns", "beacons", "derivation", "descendants"] {
+            object.remove(field);
+        }
+        value["events"][0]["event_id"] = json!("1");
+        value["detections"][0]["events"][0]["event_id"] = json!("1");
+        value["detections"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("tags");
+
+        let parsed = report_from_value(value).unwrap();
+        assert_eq!(parsed.schema_version, SCHEMA_VERSION);
+        assert!(parsed.dns.is_empty());
+        assert!(parsed.detections[0].tags.is_empty());
+        assert_eq!(parsed.events[0].event_id, SysmonEventId::PROCESS_CREATE);
+    }
+}
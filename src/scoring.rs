@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::analyzer::clipboard::detect_clipboard_abuse;
+use crate::analyzer::credential_access::detect_browser_cred_access;
+use crate::analyzer::dns_anomaly::detect_dns_anomalies;
+use crate::analyzer::driver::{analyze_driver_loads, DriverBlocklist};
+use crate::analyzer::parentage::ParentageRules;
+use crate::analyzer::persistence::{detect_scheduled_tasks, detect_service_install};
+use crate::analyzer::privilege::detect_privilege_abuse;
+use crate::analyzer::ransomware::{detect_ransomware, is_recovery_inhibition, RansomwareOptions};
+use crate::cmdline::match_lolbins;
+use crate::enrichment::geo::BULLETPROOF_TAG;
+use crate::flags::AccessMask;
+use crate::memory::MemoryAnalysis;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub const MAX_SCORE: f64 = 10.0;
+
+const PERSISTENCE_KEYS: &[&str] = &[
+    r"\software\microsoft\windows\currentversion\run\",
+    r"\software\microsoft\windows\currentversion\runonce\",
+    r"\software\microsoft\windows\currentversion\policies\explorer\run\",
+    r"\software\wow6432node\microsoft\windows\currentversion\run\",
+    r"\software\microsoft\windows nt\currentversion\winlogon\shell",
+    r"\software\microsoft\windows nt\currentversion\winlogon\userinit",
+    r"\software\microsoft\windows nt\currentversion\image file execution options\",
+    r"\software\microsoft\windows nt\currentversion\windows\appinit_dlls",
+    r"\environment\userinitmprlogonscript",
+];
+
+struct Signature {
+    name: &'static str,
+    description: &'static str,
+    weight: f64,
+}
+
+const SIGNATURES: &[Signature] = &[
+    Signature {
+        name: "process_tampering",
+        description: "Process image was replaced or tampered with (hollowing/herpaderping)",
+        weight: 4.0,
+    },
+    Signature {
+        name: "remote_thread_injection",
+        description: "Thread created in another process",
+        weight: 3.0,
+    },
+    Signature {
+        name: "persistence_registry",
+        description: "Autostart registry location modified",
+        weight: 2.0,
+    },
+    Signature {
+        name: "shadow_copy_deletion",
+        description: "Volume shadow copies or backups deleted",
+        weight: 5.0,
+    },
+    Signature {
+        name: "ransomware",
+        description:
+            "Files encrypted and renamed in bulk alongside ransom notes or recovery tampering",
+        weight: 5.0,
+    },
+    Signature {
+        name: "lsass_access",
+        description: "LSASS memory opened for reading",
+        weight: 5.0,
+    },
+    Signature {
+        name: "service_install",
+        description: "Service installed or reconfigured",
+        weight: 2.0,
+    },
+    Signature {
+        name: "scheduled_task",
+        description: "Scheduled task created",
+        weight: 2.0,
+    },
+    Signature {
+        name: "privilege_abuse",
+        description: "Privilege escalation tooling executed",
+        weight: 2.0,
+    },
+    Signature {
+        name: "anomalous_parentage",
+        description: "Process started by an unexpected parent or from an unexpected location",
+        weight: 2.0,
+    },
+    Signature {
+        name: "lolbin_abuse",
+        description: "Living-off-the-land binary abused to download or execute code",
+        weight: 2.0,
+    },
+    Signature {
+        name: "browser_credential_access",
+        description: "Browser credential store read by a foreign process",
+        weight: 3.0,
+    },
+    Signature {
+        name: "dns_anomaly",
+        description: "Suspicious DNS query pattern",
+        weight: 1.0,
+    },
+    Signature {
+        name: "suspicious_hosting",
+        description: "Connection to an autonomous system tagged as bulletproof hosting",
+        weight: 3.0,
+    },
+    Signature {
+        name: "vulnerable_driver",
+        description: "Known vulnerable kernel driver loaded",
+        weight: 5.0,
+    },
+    Signature {
+        name: "clipboard_abuse",
+        description: "Clipboard monitored or replaced",
+        weight: 2.0,
+    },
+    Signature {
+        name: "memory_rwx_region",
+        description: "Readable, writable and executable memory region in a dumped process",
+        weight: 2.0,
+    },
+    Signature {
+        name: "memory_unbacked_pe",
+        description: "PE image in memory not backed by a file on disk",
+        weight: 4.0,
+    },
+];
+
+#[derive(Debug, Clone)]
+pub struct ScoringOptions {
+    pub weights: HashMap<String, f64>,
+}
+
+impl Default for ScoringOptions {
+    fn default() -> Self {
+        Self {
+            weights: SIGNATURES
+                .iter()
+                .map(|s| (s.name.to_string(), s.weight))
+                .collect(),
+        }
+    }
+}
+
+impl ScoringOptions {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let overrides: HashMap<String, f64> =
+            serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+        let mut options = Self::default();
+        options.weights.extend(overrides);
+        Ok(options)
+    }
+
+    fn weight(&self, name: &str) -> f64 {
+        self.weights.get(name).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignatureHit {
+    pub name: String,
+    pub description: String,
+    pub weight: f64,
+    pub events: Vec<SysmonEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Score {
+    pub score: f64,
+    pub signatures: Vec<SignatureHit>,
+}
+
+fn is_remote_thread(event: &SysmonEvent) -> bool {
+    event.event_id == SysmonEventId::CREATE_REMOTE_THREAD
+        && event.event_data.get("SourceProcessGuid") != event.event_data.get("TargetProcessGuid")
+}
+
+fn is_persistence_registry(event: &SysmonEvent) -> bool {
+    if event.event_id != SysmonEventId::REGISTRY_EVENT_SET
+        && event.event_id != SysmonEventId::REGISTRY_EVENT_ADD_DELETE
+    {
+        return false;
+    }
+    if event.event_data.get("EventType").is_some_and(|t| {
+        t.eq_ignore_ascii_case("DeleteKey") || t.eq_ignore_ascii_case("DeleteValue")
+    }) {
+        return false;
+    }
+    let Some(target) = event.event_data.get("TargetObject") else {
+        return false;
+    };
+    let target = target.to_lowercase();
+    PERSISTENCE_KEYS.iter().any(|k| target.contains(k))
+}
+
+fn is_lolbin_abuse(event: &SysmonEvent) -> bool {
+    event.event_id == SysmonEventId::PROCESS_CREATE
+        && event
+            .event_data
+            .get("CommandLine")
+            .is_some_and(|c| !match_lolbins(c).is_empty())
+}
+
+fn is_suspicious_hosting(event: &SysmonEvent) -> bool {
+    event.event_id == SysmonEventId::NETWORK_CONNECT
+        && event
+            .enriched("DestinationTags")
+            .is_some_and(|tags| tags.split(',').any(|t| t == BULLETPROOF_TAG))
+}
+
+fn is_lsass_access(event: &SysmonEvent) -> bool {
+    if event.event_id != SysmonEventId::PROCESS_ACCESS {
+        return false;
+    }
+    let targets_lsass = event
+        .event_data
+        .get("TargetImage")
+        .is_some_and(|i| i.to_lowercase().ends_with(r"\lsass.exe"));
+    let granted = event
+        .event_data
+        .get("GrantedAccess")
+        .and_then(|a| AccessMask::parse(a))
+        .unwrap_or_default();
+    targets_lsass && granted.contains(AccessMask::PROCESS_VM_READ)
+}
+
+fn matching(events: &[SysmonEvent], predicate: fn(&SysmonEvent) -> bool) -> Vec<SysmonEvent> {
+    events.iter().filter(|e| predicate(e)).cloned().collect()
+}
+
+fn signature_events(name: &str, events: &[SysmonEvent]) -> Vec<SysmonEvent> {
+    match name {
+        "process_tampering" => matching(events, |e| e.event_id == SysmonEventId::PROCESS_TAMPERING),
+        "remote_thread_injection" => matching(events, is_remote_thread),
+        "persistence_registry" => matching(events, is_persistence_registry),
+        "shadow_copy_deletion" => matching(events, is_recovery_inhibition),
+        "lsass_access" => matching(events, is_lsass_access),
+        "ransomware" => detect_ransomware(events, &[], &RansomwareOptions::default())
+            .into_iter()
+            .flat_map(|v| v.evidence)
+            .flat_map(|e| e.events.into_iter().next())
+            .collect(),
+        "lolbin_abuse" => matching(events, is_lolbin_abuse),
+        "suspicious_hosting" => matching(events, is_suspicious_hosting),
+        "vulnerable_driver" => analyze_driver_loads(events, &DriverBlocklist::default())
+            .into_iter()
+            .filter(|d| d.vulnerable.is_some())
+            .map(|d| d.event)
+            .collect(),
+        "anomalous_parentage" => ParentageRules::with_defaults()
+            .evaluate(events)
+            .into_iter()
+            .flat_map(|m| m.events.into_iter().last())
+            .collect(),
+        "service_install" => detect_service_install(events)
+            .into_iter()
+            .flat_map(|a| a.process_event.into_iter().chain(a.registry_event))
+            .collect(),
+        "scheduled_task" => detect_scheduled_tasks(events)
+            .into_iter()
+            .flat_map(|a| a.process_event.into_iter().chain(a.file_event))
+            .collect(),
+        "privilege_abuse" => detect_privilege_abuse(events)
+            .into_iter()
+            .map(|a| a.event)
+            .collect(),
+        "browser_credential_access" => detect_browser_cred_access(events)
+            .into_iter()
+            .map(|a| a.event)
+            .collect(),
+        "dns_anomaly" => detect_dns_anomalies(events)
+            .into_iter()
+            .map(|a| a.event)
+            .collect(),
+        "clipboard_abuse" => detect_clipboard_abuse(events)
+            .into_iter()
+            .flat_map(|a| a.events)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn score(events: &[SysmonEvent]) -> Score {
+    score_with(events, &ScoringOptions::default())
+}
+
+pub fn score_with(events: &[SysmonEvent], options: &ScoringOptions) -> Score {
+    let mut signatures = Vec::new();
+    for signature in SIGNATURES {
+        let weight = options.weight(signature.name);
+        if weight <= 0.0 {
+            continue;
+        }
+        let events = signature_events(signature.name, events);
+        if events.is_empty() {
+            continue;
+        }
+        signatures.push(SignatureHit {
+            name: signature.name.to_string(),
+            description: signature.description.to_string(),
+            weight,
+            events,
+        });
+    }
+    Score::from_hits(signatures)
+}
+
+impl Score {
+    fn from_hits(mut signatures: Vec<SignatureHit>) -> Self {
+        signatures.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+
+        let total: f64 = signatures.iter().map(|s| s.weight).sum();
+        Self {
+            score: (total.min(MAX_SCORE) * 10.0).round() / 10.0,
+            signatures,
+        }
+    }
+
+    pub fn add_memory(&mut self, memory: &[MemoryAnalysis], options: &ScoringOptions) {
+        let found = [
+            (
+                "memory_rwx_region",
+                memory.iter().any(|m| m.rwx_regions().next().is_some()),
+            ),
+            (
+                "memory_unbacked_pe",
+                memory.iter().any(|m| m.unbacked_pes().next().is_some()),
+            ),
+        ];
+        let mut signatures = std::mem::take(&mut self.signatures);
+        for (name, found) in found {
+            let weight = options.weight(name);
+            if !found || weight <= 0.0 || signatures.iter().any(|s| s.name == name) {
+                continue;
+            }
+            let Some(signature) = SIGNATURES.iter().find(|s| s.name == name) else {
+                continue;
+            };
+            signatures.push(SignatureHit {
+                name: name.to_string(),
+                description: signature.description.to_string(),
+                weight,
+                events: Vec::new(),
+            });
+        }
+        *self = Self::from_hits(signatures);
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(feature = "sqlite")]
+pub mod index;
+
+use fuzzyhash::FuzzyHash;
+use serde::{Deserialize, Serialize};
+use tlsh2::{TlshDefault, TlshDefaultBuilder};
+
+const MAX_TLSH_DISTANCE: i32 = 300;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyHashes {
+    pub ssdeep: String,
+    pub tlsh: Option<String>,
+}
+
+impl FuzzyHashes {
+    pub fn compute(data: &[u8]) -> Self {
+        Self {
+            ssdeep: FuzzyHash::new(data).to_string(),
+            tlsh: tlsh(data),
+        }
+    }
+}
+
+pub fn tlsh(data: &[u8]) -> Option<String> {
+    let hash = TlshDefaultBuilder::build_from(data)?.hash();
+    String::from_utf8(hash.to_vec()).ok()
+}
+
+pub fn ssdeep_score(a: &str, b: &str) -> u32 {
+    if a.is_empty() || b.is_empty() {
+        return 0;
+    }
+    FuzzyHash::compare(a, b).unwrap_or(0)
+}
+
+pub fn tlsh_distance(a: &str, b: &str) -> Option<i32> {
+    let a: TlshDefault = a.parse().ok()?;
+    let b: TlshDefault = b.parse().ok()?;
+    Some(a.diff(&b, true))
+}
+
+fn ssdeep_block_size(hash: &str) -> Option<u64> {
+    hash.split(':').next()?.parse().ok()
+}
+
+pub fn comparable(a: &str, b: &str) -> bool {
+    match (ssdeep_block_size(a), ssdeep_block_size(b)) {
+        (Some(a), Some(b)) => a == b || a * 2 == b || b * 2 == a,
+        _ => false,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Similarity {
+    pub ssdeep: u32,
+    pub tlsh_distance: Option<i32>,
+    pub score: u32,
+}
+
+pub fn compare(a: &FuzzyHashes, b: &FuzzyHashes) -> Similarity {
+    let ssdeep = if comparable(&a.ssdeep, &b.ssdeep) {
+        ssdeep_score(&a.ssdeep, &b.ssdeep)
+    } else {
+        0
+    };
+    let tlsh_distance = match (&a.tlsh, &b.tlsh) {
+        (Some(a), Some(b)) => tlsh_distance(a, b),
+        _ => None,
+    };
+    let tlsh_score = tlsh_distance
+        .map(|d| (100 - d.clamp(0, MAX_TLSH_DISTANCE) * 100 / MAX_TLSH_DISTANCE) as u32)
+        .unwrap_or(0);
+    Similarity {
+        ssdeep,
+        tlsh_distance,
+        score: ssdeep.max(tlsh_score),
+    }
+}
@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{compare, FuzzyHashes, Similarity};
+use crate::analysis_result::ExecutionLog;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    sha256 TEXT NOT NULL,
+    analysis_id TEXT NOT NULL,
+    execution_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    path TEXT,
+    ssdeep TEXT NOT NULL,
+    tlsh TEXT,
+    time TEXT NOT NULL,
+    PRIMARY KEY (sha256, analysis_id, execution_id)
+);
+CREATE INDEX IF NOT EXISTS files_sha256 ON files(sha256);
+";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Sample,
+    Dropped,
+}
+
+impl FileKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sample => "sample",
+            Self::Dropped => "dropped",
+        }
+    }
+
+    fn from_name(name: &str) -> Self {
+        match name {
+            "sample" => Self::Sample,
+            _ => Self::Dropped,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IndexedFile {
+    pub sha256: String,
+    pub analysis_id: String,
+    pub execution_id: Option<String>,
+    pub kind: FileKind,
+    pub path: Option<String>,
+    pub hashes: FuzzyHashes,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SimilarFile {
+    pub file: IndexedFile,
+    pub similarity: Similarity,
+}
+
+fn indexed_file(row: &Row) -> rusqlite::Result<IndexedFile> {
+    let execution_id: String = row.get(2)?;
+    let kind: String = row.get(3)?;
+    Ok(IndexedFile {
+        sha256: row.get(0)?,
+        analysis_id: row.get(1)?,
+        execution_id: (!execution_id.is_empty()).then_some(execution_id),
+        kind: FileKind::from_name(&kind),
+        path: row.get(4)?,
+        hashes: FuzzyHashes {
+            ssdeep: row.get(5)?,
+            tlsh: row.get(6)?,
+        },
+    })
+}
+
+#[derive(Debug)]
+pub struct SimilarityIndex {
+    conn: Connection,
+}
+
+impl SimilarityIndex {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    pub fn add(&mut self, file: &IndexedFile) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO files
+                 (sha256, analysis_id, execution_id, kind, path, ssdeep, tlsh, time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                file.sha256,
+                file.analysis_id,
+                file.execution_id.as_deref().unwrap_or_default(),
+                file.kind.name(),
+                file.path,
+                file.hashes.ssdeep,
+                file.hashes.tlsh,
+                Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_sample(&mut self, analysis_id: &str, data: &[u8]) -> Result<()> {
+        self.add(&IndexedFile {
+            sha256: format!("{:x}", Sha256::digest(data)),
+            analysis_id: analysis_id.to_string(),
+            execution_id: None,
+            kind: FileKind::Sample,
+            path: None,
+            hashes: FuzzyHashes::compute(data),
+        })
+    }
+
+    pub fn add_execution(&mut self, analysis_id: &str, log: &ExecutionLog) -> Result<()> {
+        for artifact in &log.artifacts {
+            self.add(&IndexedFile {
+                sha256: artifact.hashes.sha256.clone(),
+                analysis_id: analysis_id.to_string(),
+                execution_id: Some(log.id.clone()),
+                kind: FileKind::Dropped,
+                path: Some(artifact.path.clone()),
+                hashes: FuzzyHashes {
+                    ssdeep: artifact.hashes.ssdeep.clone(),
+                    tlsh: artifact.hashes.tlsh.clone(),
+                },
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn hashes_of(&self, sha256: &str) -> Result<Option<FuzzyHashes>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT ssdeep, tlsh FROM files WHERE sha256 = ?1 LIMIT 1",
+                params![sha256.to_lowercase()],
+                |row| {
+                    Ok(FuzzyHashes {
+                        ssdeep: row.get(0)?,
+                        tlsh: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    pub fn similar(
+        &self,
+        hashes: &FuzzyHashes,
+        min_score: u32,
+        limit: usize,
+    ) -> Result<Vec<SimilarFile>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT sha256, analysis_id, execution_id, kind, path, ssdeep, tlsh FROM files",
+        )?;
+        let mut matches = Vec::new();
+        for file in stmt.query_map([], indexed_file)? {
+            let file = file?;
+            let similarity = compare(hashes, &file.hashes);
+            if similarity.score >= min_score {
+                matches.push(SimilarFile { file, similarity });
+            }
+        }
+        matches.sort_by_key(|m| std::cmp::Reverse(m.similarity.score));
+        matches.truncate(limit);
+        Ok(matches)
+    }
+
+    pub fn similar_to(
+        &self,
+        sha256: &str,
+        min_score: u32,
+        limit: usize,
+    ) -> Result<Vec<SimilarFile>> {
+        let sha256 = sha256.to_lowercase();
+        let hashes = self.hashes_of(&sha256)?.context("Hash is not indexed")?;
+        let mut matches = self.similar(&hashes, min_score, usize::MAX)?;
+        matches.retain(|m| m.file.sha256 != sha256);
+        matches.truncate(limit);
+        Ok(matches)
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::Write;
+
+use anyhow::Result;
+use log::warn;
+
+use crate::schema::Versioned;
+use crate::sysmon_event::SysmonEvent;
+
+pub trait EventSink {
+    fn write(&mut self, event: &SysmonEvent) -> Result<()>;
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn checkpoint(&mut self, _records: u64) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl EventSink for Vec<SysmonEvent> {
+    fn write(&mut self, event: &SysmonEvent) -> Result<()> {
+        self.push(event.clone());
+        Ok(())
+    }
+}
+
+pub struct NdjsonSink<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    pending: usize,
+    batch_size: usize,
+}
+
+impl<W: Write> NdjsonSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_batch_size(writer, 1024)
+    }
+
+    pub fn with_batch_size(writer: W, batch_size: usize) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+            pending: 0,
+            batch_size: batch_size.max(1),
+        }
+    }
+}
+
+impl<W: Write> EventSink for NdjsonSink<W> {
+    fn write(&mut self, event: &SysmonEvent) -> Result<()> {
+        serde_json::to_writer(&mut self.buffer, &Versioned::new(event))?;
+        self.buffer.push(b'\n');
+        self.pending += 1;
+        if self.pending >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            self.writer.write_all(&self.buffer)?;
+            self.buffer.clear();
+            self.pending = 0;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for NdjsonSink<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Failed to flush NDJSON sink: {}", e);
+        }
+    }
+}
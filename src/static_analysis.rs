@@ -0,0 +1,214 @@
+pub mod authenticode;
+pub mod container;
+pub mod email;
+pub mod office;
+pub mod pe;
+pub mod script;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use script::{normalize, ScriptLanguage};
+
+use crate::cmdline::{program_name, tokenize};
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+use crate::telemetry::{amsi_scans, module_logs, script_blocks, AmsiScan, TelemetryEvent};
+
+pub const SCRIPTS_FILE: &str = "scripts.json";
+pub const DEOBFUSCATED_DIR: &str = "deobfuscated";
+pub const CAPTURED_DIR: &str = "captured";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtractedScript {
+    pub name: String,
+    pub language: ScriptLanguage,
+    pub source: String,
+    pub normalized: String,
+    pub decoded: Vec<String>,
+}
+
+impl ExtractedScript {
+    pub fn new(name: &str, language: ScriptLanguage, source: String) -> Self {
+        let normalized = normalize(language, &source);
+        Self {
+            name: name.to_string(),
+            language,
+            source,
+            normalized: normalized.source,
+            decoded: normalized.decoded,
+        }
+    }
+}
+
+pub fn extract_scripts(file_name: &str, data: &[u8]) -> Result<Vec<ExtractedScript>> {
+    if office::is_ole(data) || office::is_ooxml(data) {
+        return Ok(office::extract_vba(data)?
+            .into_iter()
+            .map(|m| ExtractedScript::new(&m.name, ScriptLanguage::Vba, m.code))
+            .collect());
+    }
+    let Some(language) = ScriptLanguage::from_file_name(file_name) else {
+        return Ok(Vec::new());
+    };
+    let source = match std::str::from_utf8(data) {
+        Ok(source) => source.trim_start_matches('\u{feff}').to_string(),
+        Err(_) => String::from_utf8_lossy(data).into_owned(),
+    };
+    Ok(vec![ExtractedScript::new(file_name, language, source)])
+}
+
+fn is_powershell(command_line: &str) -> bool {
+    tokenize(command_line)
+        .first()
+        .is_some_and(|p| matches!(program_name(p).as_str(), "powershell" | "pwsh"))
+}
+
+fn amsi_language(scan: &AmsiScan) -> Option<ScriptLanguage> {
+    let app = scan.app_name.as_deref()?.to_lowercase();
+    if scan.is_powershell() {
+        Some(ScriptLanguage::PowerShell)
+    } else if app.starts_with("jscript") {
+        Some(ScriptLanguage::JScript)
+    } else if app.starts_with("vbscript") || app.starts_with("office_vba") {
+        Some(ScriptLanguage::Vba)
+    } else {
+        None
+    }
+}
+
+pub fn captured_scripts(telemetry: &[TelemetryEvent]) -> Vec<ExtractedScript> {
+    let blocks = script_blocks(telemetry).into_iter().map(|b| {
+        (
+            format!("scriptblock-{}", b.script_block_id),
+            ScriptLanguage::PowerShell,
+            b.text,
+        )
+    });
+    let modules = module_logs(telemetry)
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| {
+            (
+                format!("module-{}", i),
+                ScriptLanguage::PowerShell,
+                m.payload,
+            )
+        });
+    let amsi = amsi_scans(telemetry)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, scan)| {
+            let language = amsi_language(&scan)?;
+            Some((format!("amsi-{}", i), language, scan.content))
+        });
+
+    let mut scripts: Vec<ExtractedScript> = Vec::new();
+    for (name, language, source) in blocks.chain(amsi).chain(modules) {
+        if source.trim().is_empty() || scripts.iter().any(|s| s.source == source) {
+            continue;
+        }
+        scripts.push(ExtractedScript::new(&name, language, source));
+    }
+    scripts
+}
+
+pub fn deobfuscate_powershell(
+    events: &[SysmonEvent],
+    captured: &[ExtractedScript],
+) -> Vec<ExtractedScript> {
+    let command_lines = events
+        .iter()
+        .filter(|e| e.event_id == SysmonEventId::PROCESS_CREATE)
+        .filter_map(|e| {
+            let command_line = e.event_data.get("CommandLine")?;
+            let name = match e.event_data.get("ProcessId") {
+                Some(pid) => format!("cmdline-{}", pid),
+                None => "cmdline".to_string(),
+            };
+            is_powershell(command_line).then(|| (name, command_line.clone()))
+        })
+        .map(|(name, source)| ExtractedScript::new(&name, ScriptLanguage::PowerShell, source));
+    let captured = captured
+        .iter()
+        .filter(|s| s.language == ScriptLanguage::PowerShell)
+        .cloned();
+
+    let mut scripts: Vec<ExtractedScript> = Vec::new();
+    for script in command_lines.chain(captured) {
+        if scripts.iter().any(|s| s.source == script.source) {
+            continue;
+        }
+        if !script.decoded.is_empty() {
+            scripts.push(script);
+        }
+    }
+    scripts
+}
+
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+pub fn write_scripts<P: AsRef<Path>>(dir: P, scripts: &[ExtractedScript]) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+    let mut paths = Vec::new();
+    for (i, script) in scripts.iter().enumerate() {
+        let stem = format!("{}-{}", i, file_stem(&script.name));
+        let extension = script.language.extension();
+        let mut files = vec![
+            (format!("{}.{}", stem, extension), &script.source),
+            (
+                format!("{}.normalized.{}", stem, extension),
+                &script.normalized,
+            ),
+        ];
+        for (j, decoded) in script.decoded.iter().enumerate() {
+            files.push((format!("{}.decoded-{}.txt", stem, j), decoded));
+        }
+        for (name, content) in files {
+            let path = dir.join(name);
+            std::fs::write(&path, content)?;
+            paths.push(path);
+        }
+    }
+    std::fs::write(dir.join(SCRIPTS_FILE), serde_json::to_vec_pretty(scripts)?)?;
+    Ok(paths)
+}
+
+pub fn read_scripts<P: AsRef<Path>>(dir: P) -> Result<Vec<ExtractedScript>> {
+    let path = dir.as_ref().join(SCRIPTS_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[usize::from(b)] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -0,0 +1,160 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use x509_parser::extensions::{DistributionPointName, GeneralName, ParsedExtension};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+const MAX_CHAIN_DEPTH: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub common_name: Option<String>,
+    pub serial: String,
+    pub thumbprint: String,
+    pub not_before: Option<DateTime<Utc>>,
+    pub not_after: Option<DateTime<Utc>>,
+    pub self_signed: bool,
+    pub code_signing: bool,
+    pub crl_urls: Vec<String>,
+}
+
+impl CertificateInfo {
+    pub fn valid_at(&self, time: DateTime<Utc>) -> bool {
+        self.not_before.is_none_or(|t| t <= time) && self.not_after.is_none_or(|t| time <= t)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CertificateChain {
+    pub certificates: Vec<CertificateInfo>,
+    pub verified: bool,
+}
+
+impl CertificateChain {
+    pub fn signer(&self) -> Option<&CertificateInfo> {
+        self.certificates.first()
+    }
+
+    pub fn root(&self) -> Option<&CertificateInfo> {
+        self.certificates.last()
+    }
+}
+
+fn timestamp(seconds: i64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(seconds, 0).single()
+}
+
+fn crl_urls(certificate: &X509Certificate) -> Vec<String> {
+    let mut urls = Vec::new();
+    for extension in certificate.extensions() {
+        let ParsedExtension::CRLDistributionPoints(points) = extension.parsed_extension() else {
+            continue;
+        };
+        for point in points.points.iter() {
+            let Some(DistributionPointName::FullName(names)) = &point.distribution_point else {
+                continue;
+            };
+            for name in names {
+                if let GeneralName::URI(uri) = name {
+                    urls.push(uri.to_string());
+                }
+            }
+        }
+    }
+    urls
+}
+
+fn is_code_signing(certificate: &X509Certificate) -> bool {
+    certificate
+        .extended_key_usage()
+        .ok()
+        .flatten()
+        .is_some_and(|eku| eku.value.code_signing)
+}
+
+fn info(certificate: &X509Certificate, der: &[u8]) -> CertificateInfo {
+    let validity = certificate.validity();
+    CertificateInfo {
+        subject: certificate.subject().to_string(),
+        issuer: certificate.issuer().to_string(),
+        common_name: certificate
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string),
+        serial: certificate
+            .raw_serial()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+        thumbprint: format!("{:x}", Sha1::digest(der)),
+        not_before: timestamp(validity.not_before.timestamp()),
+        not_after: timestamp(validity.not_after.timestamp()),
+        self_signed: certificate.subject() == certificate.issuer(),
+        code_signing: is_code_signing(certificate),
+        crl_urls: crl_urls(certificate),
+    }
+}
+
+fn embedded_certificates(data: &[u8]) -> Vec<(X509Certificate<'_>, &[u8])> {
+    let mut certificates = Vec::new();
+    let mut offset = 0;
+    while offset + 1 < data.len() {
+        if data[offset] != 0x30 || data[offset + 1] != 0x82 {
+            offset += 1;
+            continue;
+        }
+        match X509Certificate::from_der(&data[offset..]) {
+            Ok((rest, certificate)) => {
+                let end = data.len() - rest.len();
+                certificates.push((certificate, &data[offset..end]));
+                offset = end;
+            }
+            Err(_) => offset += 1,
+        }
+    }
+    certificates
+}
+
+pub fn chain(signed_data: &[u8]) -> CertificateChain {
+    let certificates = embedded_certificates(signed_data);
+    let is_issuer = |candidate: &X509Certificate| {
+        certificates.iter().any(|(other, _)| {
+            other.issuer() == candidate.subject() && other.subject() != candidate.subject()
+        })
+    };
+    let leaf = certificates
+        .iter()
+        .position(|(c, _)| !is_issuer(c) && is_code_signing(c))
+        .or_else(|| certificates.iter().position(|(c, _)| !is_issuer(c)));
+    let Some(mut current) = leaf else {
+        return CertificateChain::default();
+    };
+
+    let mut chain = CertificateChain {
+        certificates: Vec::new(),
+        verified: true,
+    };
+    for _ in 0..MAX_CHAIN_DEPTH {
+        let (certificate, der) = &certificates[current];
+        chain.certificates.push(info(certificate, der));
+        if certificate.subject() == certificate.issuer() {
+            chain.verified &= certificate.verify_signature(None).is_ok();
+            break;
+        }
+        let Some(parent) = certificates
+            .iter()
+            .position(|(c, _)| c.subject() == certificate.issuer())
+        else {
+            break;
+        };
+        chain.verified &= certificate
+            .verify_signature(Some(certificates[parent].0.public_key()))
+            .is_ok();
+        current = parent;
+    }
+    chain
+}
@@ -0,0 +1,679 @@
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use super::pe::is_pe;
+use super::script::ScriptLanguage;
+
+pub const DEFAULT_PASSWORDS: &[&str] = &[
+    "infected",
+    "malware",
+    "virus",
+    "password",
+    "dangerous",
+    "sample",
+    "123",
+    "1234",
+    "12345",
+];
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const RAR_MAGIC: &[u8] = b"Rar!\x1a\x07";
+const SEVEN_ZIP_MAGIC: &[u8] = b"7z\xbc\xaf\x27\x1c";
+const ISO_MAGIC: &[u8] = b"CD001";
+const ISO_SECTOR: usize = 2048;
+const ISO_DESCRIPTOR_START: usize = 16;
+const ISO_ROOT_RECORD: std::ops::Range<usize> = 156..190;
+const JOLIET_ESCAPES: &[&[u8]] = &[b"%/@", b"%/C", b"%/E"];
+const OOXML_MARKER: &str = "[Content_Types].xml";
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "dll", "scr", "com", "cpl", "sys", "msi", "lnk", "hta", "bat", "cmd", "jar", "pif",
+];
+
+const MAX_DEPTH: usize = 4;
+const MAX_ISO_DEPTH: usize = 16;
+const MAX_ENTRIES: usize = 4096;
+const MAX_UNPACKED_SIZE: u64 = 512 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerKind {
+    Zip,
+    Rar,
+    SevenZip,
+    Iso,
+}
+
+impl ContainerKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Rar => "rar",
+            Self::SevenZip => "7z",
+            Self::Iso => "iso",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContainerEntry {
+    pub path: String,
+    pub size: u64,
+    pub encrypted: bool,
+    pub extracted: bool,
+    pub sha256: Option<String>,
+    pub executable: bool,
+    pub container: Option<Container>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Container {
+    pub kind: ContainerKind,
+    pub password: Option<String>,
+    pub entries: Vec<ContainerEntry>,
+    pub errors: Vec<String>,
+}
+
+impl Container {
+    pub fn executables(&self) -> Vec<(String, &ContainerEntry)> {
+        let mut executables = Vec::new();
+        for entry in &self.entries {
+            if entry.executable {
+                executables.push((entry.path.clone(), entry));
+            }
+            if let Some(nested) = &entry.container {
+                for (path, inner) in nested.executables() {
+                    executables.push((format!("{}/{}", entry.path, path), inner));
+                }
+            }
+        }
+        executables
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InnerFile {
+    pub name: String,
+    pub path: String,
+    pub provenance: Vec<ContainerKind>,
+    pub data: Vec<u8>,
+}
+
+impl InnerFile {
+    pub fn tags(&self) -> Vec<String> {
+        let chain: Vec<&str> = self.provenance.iter().map(ContainerKind::name).collect();
+        vec![
+            format!("container:{}", chain.join(">")),
+            format!("container_path:{}", self.path),
+        ]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Unpacked {
+    pub container: Container,
+    pub files: Vec<InnerFile>,
+}
+
+struct RawEntry {
+    path: String,
+    size: u64,
+    encrypted: bool,
+    data: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct Listing {
+    password: Option<String>,
+    entries: Vec<RawEntry>,
+    errors: Vec<String>,
+}
+
+struct IsoRecord {
+    name: String,
+    offset: usize,
+    size: usize,
+    is_dir: bool,
+}
+
+pub fn container_kind(data: &[u8]) -> Option<ContainerKind> {
+    if data.starts_with(ZIP_MAGIC) {
+        (!is_office_document(data)).then_some(ContainerKind::Zip)
+    } else if data.starts_with(RAR_MAGIC) {
+        Some(ContainerKind::Rar)
+    } else if data.starts_with(SEVEN_ZIP_MAGIC) {
+        Some(ContainerKind::SevenZip)
+    } else if data
+        .get(ISO_DESCRIPTOR_START * ISO_SECTOR + 1..)
+        .is_some_and(|d| d.starts_with(ISO_MAGIC))
+    {
+        Some(ContainerKind::Iso)
+    } else {
+        None
+    }
+}
+
+fn is_office_document(data: &[u8]) -> bool {
+    ZipArchive::new(Cursor::new(data)).is_ok_and(|a| a.file_names().any(|n| n == OOXML_MARKER))
+}
+
+fn is_executable(path: &str, data: &[u8]) -> bool {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .unwrap_or_default();
+    is_pe(data)
+        || data.starts_with(b"\x7fELF")
+        || EXECUTABLE_EXTENSIONS.contains(&extension.as_str())
+        || ScriptLanguage::from_file_name(path).is_some()
+}
+
+pub fn passwords(user: &[String]) -> Vec<String> {
+    let mut passwords: Vec<String> = Vec::new();
+    for password in user
+        .iter()
+        .map(String::as_str)
+        .chain(DEFAULT_PASSWORDS.iter().copied())
+    {
+        if !password.is_empty() && !passwords.iter().any(|p| p == password) {
+            passwords.push(password.to_string());
+        }
+    }
+    passwords
+}
+
+fn read_limited(reader: impl Read, limit: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        bail!("Entry exceeds the unpacked size limit");
+    }
+    Ok(data)
+}
+
+fn read_zip(data: &[u8], passwords: &[String], budget: &mut u64) -> Result<Listing> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut listing = Listing::default();
+    for i in 0..archive.len().min(MAX_ENTRIES) {
+        let (path, size, is_dir) = {
+            let entry = archive.by_index_raw(i)?;
+            (entry.name().to_string(), entry.size(), entry.is_dir())
+        };
+        if is_dir {
+            continue;
+        }
+        let encrypted = matches!(
+            archive.by_index(i),
+            Err(ZipError::UnsupportedArchive(reason)) if reason == ZipError::PASSWORD_REQUIRED
+        );
+        let mut entry = RawEntry {
+            path,
+            size,
+            encrypted,
+            data: None,
+        };
+        if size > *budget {
+            listing
+                .errors
+                .push(format!("{}: unpacked size limit reached", entry.path));
+        } else if !encrypted {
+            match read_limited(archive.by_index(i)?, *budget) {
+                Ok(data) => entry.data = Some(data),
+                Err(e) => listing.errors.push(format!("{}: {}", entry.path, e)),
+            }
+        } else {
+            let candidates: Vec<&String> = listing
+                .password
+                .iter()
+                .chain(
+                    passwords
+                        .iter()
+                        .filter(|p| Some(*p) != listing.password.as_ref()),
+                )
+                .collect();
+            let mut found = None;
+            for password in candidates {
+                let Ok(Ok(file)) = archive.by_index_decrypt(i, password.as_bytes()) else {
+                    continue;
+                };
+                if let Ok(data) = read_limited(file, *budget) {
+                    found = Some((password.clone(), data));
+                    break;
+                }
+            }
+            match found {
+                Some((password, data)) => {
+                    listing.password = Some(password);
+                    entry.data = Some(data);
+                }
+                None => listing
+                    .errors
+                    .push(format!("{}: no matching password", entry.path)),
+            }
+        }
+        if let Some(data) = &entry.data {
+            *budget -= data.len() as u64;
+        }
+        listing.entries.push(entry);
+    }
+    Ok(listing)
+}
+
+#[cfg(feature = "archives")]
+fn with_passwords(
+    passwords: &[String],
+    budget: &mut u64,
+    mut read: impl FnMut(&str, u64) -> Result<Vec<RawEntry>>,
+) -> Result<Listing> {
+    let mut error = None;
+    for password in std::iter::once("").chain(passwords.iter().map(String::as_str)) {
+        match read(password, *budget) {
+            Ok(entries) => {
+                *budget -= entries
+                    .iter()
+                    .filter_map(|e| e.data.as_ref())
+                    .map(|d| d.len() as u64)
+                    .sum::<u64>();
+                return Ok(Listing {
+                    password: (!password.is_empty()).then(|| password.to_string()),
+                    entries,
+                    errors: Vec::new(),
+                });
+            }
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(error.unwrap_or_else(|| anyhow::anyhow!("No matching password")))
+}
+
+#[cfg(feature = "archives")]
+fn read_seven_zip(data: &[u8], passwords: &[String], budget: &mut u64) -> Result<Listing> {
+    use sevenz_rust::{Password, SevenZReader};
+
+    with_passwords(passwords, budget, |password, mut remaining| {
+        let mut archive = SevenZReader::new(
+            Cursor::new(data),
+            data.len() as u64,
+            Password::from(password),
+        )?;
+        let mut entries = Vec::new();
+        archive.for_each_entries(|entry, reader| {
+            if entry.is_directory() {
+                return Ok(true);
+            }
+            let data = if entry.size() <= remaining {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                remaining = remaining.saturating_sub(data.len() as u64);
+                Some(data)
+            } else {
+                std::io::copy(reader, &mut std::io::sink())?;
+                None
+            };
+            entries.push(RawEntry {
+                path: entry.name().to_string(),
+                size: entry.size(),
+                encrypted: !password.is_empty(),
+                data,
+            });
+            Ok(entries.len() < MAX_ENTRIES)
+        })?;
+        Ok(entries)
+    })
+}
+
+#[cfg(not(feature = "archives"))]
+fn read_seven_zip(_data: &[u8], _passwords: &[String], _budget: &mut u64) -> Result<Listing> {
+    bail!("7z support requires the archives feature")
+}
+
+#[cfg(feature = "archives")]
+fn read_rar_file(path: &Path, password: &str, mut remaining: u64) -> Result<Vec<RawEntry>> {
+    use unrar::Archive;
+
+    let archive = match password {
+        "" => Archive::new(path),
+        password => Archive::with_password(path, password),
+    };
+    let mut archive = archive.open_for_processing()?;
+    let mut entries = Vec::new();
+    while let Some(header) = archive.read_header()? {
+        let entry = header.entry();
+        let path = entry.filename.to_string_lossy().replace('\\', "/");
+        let size = entry.unpacked_size;
+        let encrypted = entry.is_encrypted();
+        if entry.is_directory() {
+            archive = header.skip()?;
+            continue;
+        }
+        if size > remaining {
+            archive = header.skip()?;
+            entries.push(RawEntry {
+                path,
+                size,
+                encrypted,
+                data: None,
+            });
+        } else {
+            let (data, next) = header.read()?;
+            remaining = remaining.saturating_sub(data.len() as u64);
+            archive = next;
+            entries.push(RawEntry {
+                path,
+                size,
+                encrypted,
+                data: Some(data),
+            });
+        }
+        if entries.len() >= MAX_ENTRIES {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(feature = "archives")]
+fn read_rar(data: &[u8], passwords: &[String], budget: &mut u64) -> Result<Listing> {
+    let path = std::env::temp_dir().join(format!("{}.rar", uuid::Uuid::new_v4()));
+    std::fs::write(&path, data).context("Failed to stage RAR archive")?;
+    let listing = with_passwords(passwords, budget, |password, remaining| {
+        read_rar_file(&path, password, remaining)
+    });
+    let _ = std::fs::remove_file(&path);
+    listing
+}
+
+#[cfg(not(feature = "archives"))]
+fn read_rar(_data: &[u8], _passwords: &[String], _budget: &mut u64) -> Result<Listing> {
+    bail!("RAR support requires the archives feature")
+}
+
+fn iso_record(record: &[u8], joliet: bool) -> Option<IsoRecord> {
+    let extent = u32::from_le_bytes(record.get(2..6)?.try_into().ok()?) as usize;
+    let size = u32::from_le_bytes(record.get(10..14)?.try_into().ok()?) as usize;
+    let flags = *record.get(25)?;
+    let name_len = *record.get(32)? as usize;
+    let raw = record.get(33..33 + name_len)?;
+    let name = if joliet {
+        let units: Vec<u16> = raw
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        raw.iter().map(|&b| b as char).collect()
+    };
+    let name = name.split(';').next().unwrap_or_default();
+    Some(IsoRecord {
+        name: name.strip_suffix('.').unwrap_or(name).to_string(),
+        offset: extent * ISO_SECTOR,
+        size,
+        is_dir: flags & 0x02 != 0,
+    })
+}
+
+fn iso_root(data: &[u8]) -> Option<(IsoRecord, bool)> {
+    let mut primary = None;
+    for sector in ISO_DESCRIPTOR_START.. {
+        let Some(descriptor) = data.get(sector * ISO_SECTOR..(sector + 1) * ISO_SECTOR) else {
+            break;
+        };
+        if &descriptor[1..6] != ISO_MAGIC {
+            break;
+        }
+        match descriptor[0] {
+            1 => primary = iso_record(&descriptor[ISO_ROOT_RECORD], false),
+            2 if JOLIET_ESCAPES.contains(&&descriptor[88..91]) => {
+                if let Some(root) = iso_record(&descriptor[ISO_ROOT_RECORD], true) {
+                    return Some((root, true));
+                }
+            }
+            255 => break,
+            _ => (),
+        }
+    }
+    primary.map(|root| (root, false))
+}
+
+fn iso_directory(data: &[u8], dir: &IsoRecord, joliet: bool) -> Vec<IsoRecord> {
+    let Some(extent) = data.get(dir.offset..dir.offset.saturating_add(dir.size)) else {
+        return Vec::new();
+    };
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < extent.len() {
+        let len = extent[offset] as usize;
+        if len == 0 {
+            offset = (offset / ISO_SECTOR + 1) * ISO_SECTOR;
+            continue;
+        }
+        let Some(record) = extent.get(offset..offset + len) else {
+            break;
+        };
+        let is_self_or_parent = record.get(32) == Some(&1) && matches!(record.get(33), Some(0 | 1));
+        if !is_self_or_parent {
+            records.extend(iso_record(record, joliet));
+        }
+        offset += len;
+    }
+    records
+}
+
+fn read_iso(data: &[u8], budget: &mut u64) -> Result<Listing> {
+    let (root, joliet) = iso_root(data).context("No ISO 9660 volume descriptor")?;
+    let mut listing = Listing::default();
+    let mut visited = HashSet::from([root.offset]);
+    let mut directories = vec![(String::new(), root, 0)];
+    while let Some((prefix, dir, depth)) = directories.pop() {
+        for record in iso_directory(data, &dir, joliet) {
+            if listing.entries.len() + visited.len() >= MAX_ENTRIES {
+                return Ok(listing);
+            }
+            let path = match prefix.as_str() {
+                "" => record.name.clone(),
+                prefix => format!("{}/{}", prefix, record.name),
+            };
+            if record.is_dir {
+                if depth < MAX_ISO_DEPTH && visited.insert(record.offset) {
+                    directories.push((path, record, depth + 1));
+                }
+                continue;
+            }
+            let size = record.size as u64;
+            let content = data.get(record.offset..record.offset.saturating_add(record.size));
+            let data = match content {
+                Some(content) if size <= *budget => {
+                    *budget -= size;
+                    Some(content.to_vec())
+                }
+                Some(_) => {
+                    listing
+                        .errors
+                        .push(format!("{}: unpacked size limit reached", path));
+                    None
+                }
+                None => {
+                    listing
+                        .errors
+                        .push(format!("{}: extent out of bounds", path));
+                    None
+                }
+            };
+            listing.entries.push(RawEntry {
+                path,
+                size,
+                encrypted: false,
+                data,
+            });
+        }
+    }
+    Ok(listing)
+}
+
+struct Unpacker {
+    passwords: Vec<String>,
+    budget: u64,
+    files: Vec<InnerFile>,
+}
+
+impl Unpacker {
+    fn container(
+        &mut self,
+        kind: ContainerKind,
+        data: &[u8],
+        parent: &str,
+        provenance: &[ContainerKind],
+        depth: usize,
+    ) -> Result<Container> {
+        let listing = match kind {
+            ContainerKind::Zip => read_zip(data, &self.passwords, &mut self.budget)?,
+            ContainerKind::Rar => read_rar(data, &self.passwords, &mut self.budget)?,
+            ContainerKind::SevenZip => read_seven_zip(data, &self.passwords, &mut self.budget)?,
+            ContainerKind::Iso => read_iso(data, &mut self.budget)?,
+        };
+        let mut provenance = provenance.to_vec();
+        provenance.push(kind);
+
+        let mut container = Container {
+            kind,
+            password: listing.password,
+            entries: Vec::new(),
+            errors: listing.errors,
+        };
+        for raw in listing.entries {
+            let path = match parent {
+                "" => raw.path.clone(),
+                parent => format!("{}/{}", parent, raw.path),
+            };
+            let mut entry = ContainerEntry {
+                path: raw.path,
+                size: raw.size,
+                encrypted: raw.encrypted,
+                extracted: raw.data.is_some(),
+                sha256: None,
+                executable: false,
+                container: None,
+            };
+            if let Some(data) = raw.data {
+                entry.sha256 = Some(format!("{:x}", Sha256::digest(&data)));
+                match container_kind(&data) {
+                    Some(nested) if depth < MAX_DEPTH => {
+                        match self.container(nested, &data, &path, &provenance, depth + 1) {
+                            Ok(nested) => entry.container = Some(nested),
+                            Err(e) => container.errors.push(format!("{}: {}", entry.path, e)),
+                        }
+                    }
+                    _ if is_executable(&entry.path, &data) => {
+                        entry.executable = true;
+                        self.files.push(InnerFile {
+                            name: entry
+                                .path
+                                .rsplit('/')
+                                .next()
+                                .unwrap_or(&entry.path)
+                                .to_string(),
+                            path,
+                            provenance: provenance.clone(),
+                            data,
+                        });
+                    }
+                    _ => (),
+                }
+            }
+            container.entries.push(entry);
+        }
+        Ok(container)
+    }
+}
+
+pub fn unpack(data: &[u8], passwords: &[String]) -> Result<Unpacked> {
+    let kind = container_kind(data).context("Not a supported container")?;
+    let mut unpacker = Unpacker {
+        passwords: self::passwords(passwords),
+        budget: MAX_UNPACKED_SIZE,
+        files: Vec::new(),
+    };
+    let container = unpacker.container(kind, data, "", &[], 0)?;
+    Ok(Unpacked {
+        container,
+        files: unpacker.files,
+    })
+}
+
+pub fn write_container<P: AsRef<Path>>(path: P, container: &Container) -> Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(container)?)?;
+    Ok(())
+}
+
+pub fn read_container<P: AsRef<Path>>(path: P) -> Result<Option<Container>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&std::fs::read(path)?)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(extent: u32, size: u32, is_dir: bool, name: &[u8]) -> Vec<u8> {
+        let mut record = vec![0; 33 + name.len()];
+        record[0] = record.len() as u8;
+        record[2..6].copy_from_slice(&extent.to_le_bytes());
+        record[10..14].copy_from_slice(&size.to_le_bytes());
+        record[25] = if is_dir { 0x02 } else { 0 };
+        record[32] = name.len() as u8;
+        record[33..].copy_from_slice(name);
+        record
+    }
+
+    fn iso(root_entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0; 20 * ISO_SECTOR];
+        let primary = ISO_DESCRIPTOR_START * ISO_SECTOR;
+        data[primary] = 1;
+        data[primary + 1..primary + 6].copy_from_slice(ISO_MAGIC);
+        data[primary + ISO_ROOT_RECORD.start..primary + ISO_ROOT_RECORD.end]
+            .copy_from_slice(&record(18, ISO_SECTOR as u32, true, &[0]));
+        let terminator = primary + ISO_SECTOR;
+        data[terminator] = 255;
+        data[terminator + 1..terminator + 6].copy_from_slice(ISO_MAGIC);
+
+        let mut root = record(18, ISO_SECTOR as u32, true, &[0]);
+        root.extend(record(18, ISO_SECTOR as u32, true, &[1]));
+        for entry in root_entries {
+            root.extend(entry);
+        }
+        data[18 * ISO_SECTOR..18 * ISO_SECTOR + root.len()].copy_from_slice(&root);
+        data[19 * ISO_SECTOR..19 * ISO_SECTOR + 5].copy_from_slice(b"hello");
+        data
+    }
+
+    #[test]
+    fn iso_lists_files() {
+        let data = iso(&[record(19, 5, false, b"SAMPLE.EXE;1")]);
+        let mut budget = MAX_UNPACKED_SIZE;
+        let listing = read_iso(&data, &mut budget).unwrap();
+        assert_eq!(listing.entries.len(), 1);
+        assert_eq!(listing.entries[0].path, "SAMPLE.EXE");
+        assert_eq!(listing.entries[0].data.as_deref(), Some(&b"hello"[..]));
+        assert_eq!(budget, MAX_UNPACKED_SIZE - 5);
+    }
+
+    #[test]
+    fn iso_directory_cycles_are_walked_once() {
+        let mut entries: Vec<Vec<u8>> = (0..50)
+            .map(|_| record(18, ISO_SECTOR as u32, true, b"LOOP"))
+            .collect();
+        entries.push(record(19, 5, false, b"SAMPLE.EXE;1"));
+        let mut budget = MAX_UNPACKED_SIZE;
+        let listing = read_iso(&iso(&entries), &mut budget).unwrap();
+        assert_eq!(listing.entries.len(), 1);
+    }
+}
@@ -0,0 +1,537 @@
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use cfb::CompoundFile;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::office::is_ole;
+use super::pe::{self, PeInfo};
+use super::{entropy, extract_scripts, ExtractedScript};
+
+const MAX_DEPTH: usize = 8;
+const SNIFF_LEN: usize = 4096;
+const MIN_EML_HEADERS: usize = 2;
+const EML_HEADERS: &[&str] = &[
+    "received",
+    "return-path",
+    "from",
+    "to",
+    "subject",
+    "message-id",
+    "mime-version",
+    "delivered-to",
+];
+const MSG_PROPERTY_PREFIX: &str = "__substg1.0_";
+const MSG_ATTACHMENT_PREFIX: &str = "__attach_version1.0_";
+const URL_TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '\''];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailFormat {
+    Eml,
+    Msg,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Attachment {
+    pub name: String,
+    pub content_type: Option<String>,
+    pub size: usize,
+    pub sha256: String,
+    pub entropy: f64,
+    pub pe: Option<PeInfo>,
+    pub scripts: Vec<ExtractedScript>,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    fn new(name: String, content_type: Option<String>, data: Vec<u8>) -> Self {
+        let pe = match pe::is_pe(&data) {
+            true => pe::analyze(&data).ok(),
+            false => None,
+        };
+        Self {
+            scripts: extract_scripts(&name, &data).unwrap_or_default(),
+            name,
+            content_type,
+            size: data.len(),
+            sha256: format!("{:x}", Sha256::digest(&data)),
+            entropy: entropy(&data),
+            pe,
+            data,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Email {
+    pub format: EmailFormat,
+    pub subject: Option<String>,
+    pub from: Option<String>,
+    pub sender: Option<String>,
+    pub reply_to: Option<String>,
+    pub return_path: Option<String>,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub date: Option<String>,
+    pub message_id: Option<String>,
+    pub received: Vec<String>,
+    pub urls: Vec<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+impl Email {
+    fn new(format: EmailFormat) -> Self {
+        Self {
+            format,
+            subject: None,
+            from: None,
+            sender: None,
+            reply_to: None,
+            return_path: None,
+            to: Vec::new(),
+            cc: Vec::new(),
+            date: None,
+            message_id: None,
+            received: Vec::new(),
+            urls: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    fn apply_headers(&mut self, headers: &[(String, String)]) {
+        let get = |name: &str| header(headers, name).map(decode_words);
+        self.subject = get("subject").or(self.subject.take());
+        self.from = get("from").or(self.from.take());
+        self.sender = self
+            .from
+            .as_deref()
+            .and_then(address)
+            .or(self.sender.take());
+        self.reply_to = get("reply-to").as_deref().and_then(address);
+        self.return_path = get("return-path").as_deref().and_then(address);
+        if let Some(to) = get("to") {
+            self.to = addresses(&to);
+        }
+        if let Some(cc) = get("cc") {
+            self.cc = addresses(&cc);
+        }
+        self.date = get("date").or(self.date.take());
+        self.message_id = get("message-id").or(self.message_id.take());
+        self.received = headers
+            .iter()
+            .filter(|(name, _)| name == "received")
+            .map(|(_, value)| value.clone())
+            .collect();
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &str> {
+        [&self.sender, &self.reply_to, &self.return_path]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    pub fn reply_to_mismatch(&self) -> bool {
+        match (&self.sender, &self.reply_to) {
+            (Some(sender), Some(reply_to)) => domain(sender) != domain(reply_to),
+            _ => false,
+        }
+    }
+}
+
+pub fn is_email(data: &[u8]) -> bool {
+    if is_ole(data) {
+        return CompoundFile::open(Cursor::new(data)).is_ok_and(|ole| {
+            ole.read_root_storage()
+                .any(|e| e.name().starts_with(MSG_PROPERTY_PREFIX))
+        });
+    }
+    let head = String::from_utf8_lossy(&data[..data.len().min(SNIFF_LEN)]);
+    let Some(first) = head.lines().next() else {
+        return false;
+    };
+    if !first
+        .split_once(':')
+        .is_some_and(|(name, _)| is_header_name(name))
+    {
+        return false;
+    }
+    let (headers, _) = split_message(head.as_bytes());
+    EML_HEADERS
+        .iter()
+        .filter(|name| header(&headers, name).is_some())
+        .count()
+        >= MIN_EML_HEADERS
+}
+
+pub fn parse(data: &[u8]) -> Result<Email> {
+    if is_ole(data) {
+        parse_msg(data)
+    } else if is_email(data) {
+        Ok(parse_eml(data))
+    } else {
+        bail!("Not an .eml or .msg message")
+    }
+}
+
+fn is_header_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn split_message(data: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let (head, body) = if let Some(body) = data.strip_prefix(b"\r\n") {
+        (&data[..0], body)
+    } else if let Some(body) = data.strip_prefix(b"\n") {
+        (&data[..0], body)
+    } else {
+        match (find(data, b"\r\n\r\n"), find(data, b"\n\n")) {
+            (Some(crlf), Some(lf)) if lf < crlf => (&data[..lf], &data[lf + 2..]),
+            (Some(crlf), _) => (&data[..crlf], &data[crlf + 4..]),
+            (None, Some(lf)) => (&data[..lf], &data[lf + 2..]),
+            (None, None) => (data, &data[data.len()..]),
+        }
+    };
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in String::from_utf8_lossy(head).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+    (headers, body)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+fn parameter(value: &str, name: &str) -> Option<String> {
+    for param in value.split(';').skip(1) {
+        let Some((key, raw)) = param.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let raw = raw.trim().trim_matches('"');
+        if key == name {
+            return Some(decode_words(raw));
+        }
+        if key == format!("{}*", name) {
+            let encoded = raw.splitn(3, '\'').last().unwrap_or(raw);
+            return Some(percent_decode(encoded));
+        }
+    }
+    None
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn decode_quoted_printable(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'=' {
+            let rest = &data[i + 1..];
+            if rest.starts_with(b"\r\n") {
+                i += 3;
+                continue;
+            }
+            if rest.starts_with(b"\n") {
+                i += 2;
+                continue;
+            }
+            let byte = rest
+                .get(..2)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(byte) = byte {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    out
+}
+
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    let charset = charset.to_lowercase();
+    if charset.contains("8859") || charset.contains("1252") || charset == "us-ascii" {
+        bytes.iter().map(|&b| b as char).collect()
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+fn decode_words(value: &str) -> String {
+    static ADJACENT: OnceLock<Regex> = OnceLock::new();
+    static WORD: OnceLock<Regex> = OnceLock::new();
+    let value = ADJACENT
+        .get_or_init(|| Regex::new(r"\?=\s+=\?").expect("Invalid encoded word pattern"))
+        .replace_all(value, "?==?");
+    WORD.get_or_init(|| {
+        Regex::new(r"=\?([^?]+)\?([bBqQ])\?([^?]*)\?=").expect("Invalid encoded word pattern")
+    })
+    .replace_all(&value, |caps: &Captures| {
+        let text = &caps[3];
+        let bytes = match &caps[2] {
+            "b" | "B" => STANDARD
+                .decode(text)
+                .unwrap_or_else(|_| text.as_bytes().to_vec()),
+            _ => decode_quoted_printable(text.replace('_', " ").as_bytes()),
+        };
+        decode_charset(&bytes, &caps[1])
+    })
+    .into_owned()
+}
+
+fn decode_body(body: &[u8], encoding: Option<&str>) -> Vec<u8> {
+    match encoding.map(|e| e.trim().to_lowercase()).as_deref() {
+        Some("base64") => {
+            let compact: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            STANDARD.decode(&compact).unwrap_or_else(|_| body.to_vec())
+        }
+        Some("quoted-printable") => decode_quoted_printable(body),
+        _ => body.to_vec(),
+    }
+}
+
+fn address(value: &str) -> Option<String> {
+    let value = match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    };
+    let value = value.trim().trim_matches('"');
+    value.contains('@').then(|| value.to_lowercase())
+}
+
+fn addresses(value: &str) -> Vec<String> {
+    let mut addresses = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                addresses.extend(address(&value[start..i]));
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    addresses.extend(address(&value[start..]));
+    addresses
+}
+
+fn domain(address: &str) -> &str {
+    address
+        .rsplit_once('@')
+        .map_or(address, |(_, domain)| domain)
+}
+
+fn split_parts<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+    while let Some(pos) = find(&body[offset..], &delimiter) {
+        let at = offset + pos;
+        offset = at + delimiter.len();
+        if at > 0 && body[at - 1] != b'\n' {
+            continue;
+        }
+        if let Some(start) = start {
+            let part = &body[start..at];
+            let part = part.strip_suffix(b"\n").unwrap_or(part);
+            parts.push(part.strip_suffix(b"\r").unwrap_or(part));
+        }
+        if body[offset..].starts_with(b"--") {
+            return parts;
+        }
+        offset = find(&body[offset..], b"\n").map_or(body.len(), |n| offset + n + 1);
+        start = Some(offset);
+    }
+    if let Some(start) = start {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+fn extract_urls(text: &str, urls: &mut Vec<String>) {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    let text = text.replace("&amp;", "&");
+    let pattern = URL
+        .get_or_init(|| Regex::new(r#"(?i)\bhttps?://[^\s"'<>`]+"#).expect("Invalid URL pattern"));
+    for m in pattern.find_iter(&text) {
+        let url = m.as_str().trim_end_matches(URL_TRAILING).to_string();
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+}
+
+fn walk(headers: &[(String, String)], body: &[u8], depth: usize, email: &mut Email) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    let content_type = header(headers, "content-type").unwrap_or("text/plain");
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    if mime.starts_with("multipart/") {
+        let Some(boundary) = parameter(content_type, "boundary") else {
+            return;
+        };
+        for part in split_parts(body, &boundary) {
+            let (headers, body) = split_message(part);
+            walk(&headers, body, depth + 1, email);
+        }
+        return;
+    }
+
+    let decoded = decode_body(body, header(headers, "content-transfer-encoding"));
+    let disposition = header(headers, "content-disposition").unwrap_or_default();
+    let name = parameter(disposition, "filename").or_else(|| parameter(content_type, "name"));
+    let is_attachment = disposition.to_lowercase().starts_with("attachment") || name.is_some();
+    if mime == "message/rfc822" && !is_attachment {
+        let (headers, body) = split_message(&decoded);
+        walk(&headers, body, depth + 1, email);
+    } else if is_attachment {
+        let name = name.unwrap_or_else(|| format!("attachment-{}", email.attachments.len()));
+        email
+            .attachments
+            .push(Attachment::new(name, Some(mime), decoded));
+    } else if mime.starts_with("text/") {
+        let charset = parameter(content_type, "charset").unwrap_or_default();
+        extract_urls(&decode_charset(&decoded, &charset), &mut email.urls);
+    }
+}
+
+fn parse_eml(data: &[u8]) -> Email {
+    let mut email = Email::new(EmailFormat::Eml);
+    let (headers, body) = split_message(data);
+    email.apply_headers(&headers);
+    walk(&headers, body, 0, &mut email);
+    email
+}
+
+fn read_stream<F: Read + Seek>(ole: &mut CompoundFile<F>, path: &Path) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    ole.open_stream(path).ok()?.read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+fn msg_string<F: Read + Seek>(ole: &mut CompoundFile<F>, dir: &Path, id: &str) -> Option<String> {
+    if let Some(data) = read_stream(ole, &dir.join(format!("{}{}001F", MSG_PROPERTY_PREFIX, id))) {
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        return Some(
+            String::from_utf16_lossy(&units)
+                .trim_end_matches('\0')
+                .to_string(),
+        );
+    }
+    let data = read_stream(ole, &dir.join(format!("{}{}001E", MSG_PROPERTY_PREFIX, id)))?;
+    Some(
+        decode_charset(&data, "windows-1252")
+            .trim_end_matches('\0')
+            .to_string(),
+    )
+}
+
+fn parse_msg(data: &[u8]) -> Result<Email> {
+    let mut ole = CompoundFile::open(Cursor::new(data))?;
+    let root = PathBuf::from("/");
+    let mut email = Email::new(EmailFormat::Msg);
+    email.subject = msg_string(&mut ole, &root, "0037");
+    email.sender = msg_string(&mut ole, &root, "5D01")
+        .or_else(|| msg_string(&mut ole, &root, "0C1F"))
+        .as_deref()
+        .and_then(address);
+    email.from = msg_string(&mut ole, &root, "0042").or_else(|| email.sender.clone());
+    email.to = msg_string(&mut ole, &root, "0E04")
+        .map(|to| to.split(';').filter_map(address).collect())
+        .unwrap_or_default();
+    if let Some(headers) = msg_string(&mut ole, &root, "007D") {
+        let (headers, _) = split_message(headers.as_bytes());
+        email.apply_headers(&headers);
+    }
+
+    if let Some(body) = msg_string(&mut ole, &root, "1000") {
+        extract_urls(&body, &mut email.urls);
+    }
+    let html = format!("{}10130102", MSG_PROPERTY_PREFIX);
+    if let Some(body) = read_stream(&mut ole, &root.join(html)) {
+        extract_urls(&String::from_utf8_lossy(&body), &mut email.urls);
+    }
+
+    let attachments: Vec<PathBuf> = ole
+        .read_root_storage()
+        .filter(|e| e.is_storage() && e.name().starts_with(MSG_ATTACHMENT_PREFIX))
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    for dir in attachments {
+        let data_path = dir.join(format!("{}37010102", MSG_PROPERTY_PREFIX));
+        let Some(data) = read_stream(&mut ole, &data_path) else {
+            continue;
+        };
+        let name = msg_string(&mut ole, &dir, "3707")
+            .or_else(|| msg_string(&mut ole, &dir, "3704"))
+            .unwrap_or_else(|| format!("attachment-{}", email.attachments.len()));
+        let content_type = msg_string(&mut ole, &dir, "370E");
+        email
+            .attachments
+            .push(Attachment::new(name, content_type, data));
+    }
+    Ok(email)
+}
@@ -0,0 +1,195 @@
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use cfb::CompoundFile;
+use serde::Serialize;
+use zip::ZipArchive;
+
+const OLE_MAGIC: &[u8] = &[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const VBA_PROJECT_NAME: &str = "vbaproject.bin";
+const MAX_VBA_PROJECT_SIZE: u64 = 64 * 1024 * 1024;
+
+const CHUNK_SIZE: usize = 4096;
+
+const PROJECTVERSION: u16 = 0x0009;
+const MODULENAME: u16 = 0x0019;
+const MODULESTREAMNAME: u16 = 0x001a;
+const MODULEOFFSET: u16 = 0x0031;
+const MODULE_TERMINATOR: u16 = 0x002b;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct VbaModule {
+    pub name: String,
+    pub stream: String,
+    pub code: String,
+}
+
+pub fn is_ole(data: &[u8]) -> bool {
+    data.starts_with(OLE_MAGIC)
+}
+
+pub fn is_ooxml(data: &[u8]) -> bool {
+    data.starts_with(ZIP_MAGIC)
+}
+
+pub fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    if data.first() != Some(&1) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut pos = 1;
+    while pos + 2 <= data.len() {
+        let header = u16::from_le_bytes([data[pos], data[pos + 1]]);
+        let end = (pos + usize::from(header & 0x0fff) + 3).min(data.len());
+        pos += 2;
+        if header & 0x8000 == 0 {
+            let end = (pos + CHUNK_SIZE).min(data.len());
+            out.extend_from_slice(&data[pos..end]);
+            pos = end;
+            continue;
+        }
+
+        let chunk_start = out.len();
+        while pos < end {
+            let flags = data[pos];
+            pos += 1;
+            for bit in 0..8 {
+                if pos >= end {
+                    break;
+                }
+                if flags & (1 << bit) == 0 {
+                    out.push(data[pos]);
+                    pos += 1;
+                    continue;
+                }
+                let token = u16::from_le_bytes([data[pos], *data.get(pos + 1)?]);
+                pos += 2;
+                let current = out.len() - chunk_start;
+                let mut bit_count = 4;
+                while (1 << bit_count) < current && bit_count < 12 {
+                    bit_count += 1;
+                }
+                let offset = usize::from(token >> (16 - bit_count)) + 1;
+                let length = usize::from(token & (0xffff >> bit_count)) + 3;
+                if offset > current {
+                    return None;
+                }
+                for _ in 0..length {
+                    out.push(out[out.len() - offset]);
+                }
+            }
+        }
+    }
+    Some(out)
+}
+
+fn latin1(data: &[u8]) -> String {
+    data.iter().map(|&b| char::from(b)).collect()
+}
+
+struct ModuleRecord {
+    name: String,
+    stream: String,
+    offset: usize,
+}
+
+fn parse_dir(dir: &[u8]) -> Vec<ModuleRecord> {
+    let mut modules = Vec::new();
+    let mut name = String::new();
+    let mut stream = String::new();
+    let mut offset = 0;
+    let mut pos = 0;
+    while pos + 6 <= dir.len() {
+        let id = u16::from_le_bytes([dir[pos], dir[pos + 1]]);
+        let mut size =
+            u32::from_le_bytes([dir[pos + 2], dir[pos + 3], dir[pos + 4], dir[pos + 5]]) as usize;
+        if id == PROJECTVERSION {
+            size = 6;
+        }
+        pos += 6;
+        let Some(data) = dir.get(pos..pos + size) else {
+            break;
+        };
+        match id {
+            MODULENAME => name = latin1(data),
+            MODULESTREAMNAME => stream = latin1(data),
+            MODULEOFFSET if size >= 4 => {
+                offset = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize
+            }
+            MODULE_TERMINATOR => modules.push(ModuleRecord {
+                name: std::mem::take(&mut name),
+                stream: std::mem::take(&mut stream),
+                offset: std::mem::take(&mut offset),
+            }),
+            _ => {}
+        }
+        pos += size;
+    }
+    modules
+}
+
+fn read_stream<F: Read + Seek>(ole: &mut CompoundFile<F>, path: &Path) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    ole.open_stream(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn extract_ole(data: &[u8]) -> Result<Vec<VbaModule>> {
+    let mut ole = CompoundFile::open(Cursor::new(data))?;
+    let projects: Vec<PathBuf> = ole
+        .walk()
+        .filter(|e| e.is_stream() && e.name().eq_ignore_ascii_case("dir"))
+        .filter_map(|e| e.path().parent().map(Path::to_path_buf))
+        .collect();
+
+    let mut modules = Vec::new();
+    for project in projects {
+        let dir = read_stream(&mut ole, &project.join("dir"))?;
+        let dir = decompress(&dir).context("Invalid compressed VBA dir stream")?;
+        for record in parse_dir(&dir) {
+            let stream = match read_stream(&mut ole, &project.join(&record.stream)) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let Some(code) = stream.get(record.offset..).and_then(decompress) else {
+                continue;
+            };
+            modules.push(VbaModule {
+                name: record.name,
+                stream: record.stream,
+                code: latin1(&code),
+            });
+        }
+    }
+    Ok(modules)
+}
+
+fn extract_ooxml(data: &[u8]) -> Result<Vec<VbaModule>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut modules = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.name().to_lowercase().ends_with(VBA_PROJECT_NAME) {
+            continue;
+        }
+        if entry.size() > MAX_VBA_PROJECT_SIZE {
+            bail!("{} is too large", entry.name());
+        }
+        let mut project = Vec::new();
+        entry.read_to_end(&mut project)?;
+        modules.extend(extract_ole(&project)?);
+    }
+    Ok(modules)
+}
+
+pub fn extract_vba(data: &[u8]) -> Result<Vec<VbaModule>> {
+    if is_ole(data) {
+        extract_ole(data)
+    } else if is_ooxml(data) {
+        extract_ooxml(data)
+    } else {
+        bail!("Not an OLE or OOXML document")
+    }
+}
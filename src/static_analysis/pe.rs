@@ -0,0 +1,375 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use goblin::pe::header::machine_to_str;
+use goblin::pe::options::ParseOptions;
+use goblin::pe::section_table::{SectionTable, IMAGE_SCN_MEM_EXECUTE, IMAGE_SCN_MEM_WRITE};
+use goblin::pe::utils::find_offset;
+use goblin::pe::PE;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::authenticode::{self, CertificateChain};
+use super::entropy;
+
+const RICH_MARKER: &[u8] = b"Rich";
+const DANS_MARKER: u32 = 0x536e6144;
+const MAX_RESOURCES: usize = 4096;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    pub entropy: f64,
+    pub executable: bool,
+    pub writable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Import {
+    pub dll: String,
+    pub functions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Resource {
+    pub resource_type: String,
+    pub name: String,
+    pub language: u32,
+    pub size: u32,
+    pub entropy: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RichEntry {
+    pub product_id: u16,
+    pub build: u16,
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Unsigned,
+    DigestMatches,
+    DigestMismatch,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeInfo {
+    pub machine: String,
+    pub is_64: bool,
+    pub is_dll: bool,
+    pub compile_time: Option<DateTime<Utc>>,
+    pub entry_point: u64,
+    pub image_base: u64,
+    pub subsystem: Option<u16>,
+    pub sections: Vec<Section>,
+    pub imports: Vec<Import>,
+    pub exports: Vec<String>,
+    pub resources: Vec<Resource>,
+    pub rich_header: Vec<RichEntry>,
+    pub signature: SignatureStatus,
+    pub certificates: CertificateChain,
+    pub imphash: Option<String>,
+}
+
+pub fn is_pe(data: &[u8]) -> bool {
+    data.starts_with(b"MZ")
+}
+
+fn imphash(imports: &[Import]) -> Option<String> {
+    let mut entries = Vec::new();
+    for import in imports {
+        let dll = import.dll.to_lowercase();
+        let dll = ["dll", "ocx", "sys"]
+            .iter()
+            .find_map(|ext| dll.strip_suffix(&format!(".{}", ext)))
+            .unwrap_or(&dll)
+            .to_string();
+        for function in &import.functions {
+            let function = match function.strip_prefix("ORDINAL ") {
+                Some(ordinal) => format!("ord{}", ordinal),
+                None => function.to_lowercase(),
+            };
+            entries.push(format!("{}.{}", dll, function));
+        }
+    }
+    if entries.is_empty() {
+        return None;
+    }
+    Some(format!("{:x}", Md5::digest(entries.join(","))))
+}
+
+fn rich_header(data: &[u8], pe_pointer: usize) -> Vec<RichEntry> {
+    let stub = &data[..pe_pointer.min(data.len())];
+    let Some(rich) = stub.windows(4).rposition(|w| w == RICH_MARKER) else {
+        return Vec::new();
+    };
+    let dword = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            stub.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let Some(key) = dword(rich + 4) else {
+        return Vec::new();
+    };
+
+    let mut start = None;
+    let mut offset = rich;
+    while offset >= 0x80 {
+        offset -= 4;
+        if dword(offset).map(|d| d ^ key) == Some(DANS_MARKER) {
+            start = Some(offset);
+            break;
+        }
+    }
+    let Some(start) = start else {
+        return Vec::new();
+    };
+
+    let mut entries = Vec::new();
+    let mut offset = start + 16;
+    while offset + 8 <= rich {
+        let (Some(comp_id), Some(count)) = (dword(offset), dword(offset + 4)) else {
+            break;
+        };
+        let comp_id = comp_id ^ key;
+        entries.push(RichEntry {
+            product_id: (comp_id >> 16) as u16,
+            build: (comp_id & 0xffff) as u16,
+            count: count ^ key,
+        });
+        offset += 8;
+    }
+    entries
+}
+
+fn resource_type_name(id: u32) -> String {
+    match id {
+        1 => "RT_CURSOR".to_string(),
+        2 => "RT_BITMAP".to_string(),
+        3 => "RT_ICON".to_string(),
+        4 => "RT_MENU".to_string(),
+        5 => "RT_DIALOG".to_string(),
+        6 => "RT_STRING".to_string(),
+        7 => "RT_FONTDIR".to_string(),
+        8 => "RT_FONT".to_string(),
+        9 => "RT_ACCELERATOR".to_string(),
+        10 => "RT_RCDATA".to_string(),
+        11 => "RT_MESSAGETABLE".to_string(),
+        12 => "RT_GROUP_CURSOR".to_string(),
+        14 => "RT_GROUP_ICON".to_string(),
+        16 => "RT_VERSION".to_string(),
+        17 => "RT_DLGINCLUDE".to_string(),
+        19 => "RT_PLUGPLAY".to_string(),
+        20 => "RT_VXD".to_string(),
+        21 => "RT_ANICURSOR".to_string(),
+        22 => "RT_ANIICON".to_string(),
+        23 => "RT_HTML".to_string(),
+        24 => "RT_MANIFEST".to_string(),
+        id => id.to_string(),
+    }
+}
+
+struct ResourceWalker<'a> {
+    data: &'a [u8],
+    root: usize,
+    sections: &'a [SectionTable],
+    file_alignment: u32,
+    resources: Vec<Resource>,
+}
+
+impl ResourceWalker<'_> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(
+            self.data.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(
+            self.data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    }
+
+    fn entry_name(&self, name: u32) -> Option<String> {
+        if name & 0x8000_0000 == 0 {
+            return None;
+        }
+        let offset = self.root + (name & 0x7fff_ffff) as usize;
+        let len = usize::from(self.u16(offset)?);
+        let units: Vec<u16> = (0..len)
+            .map(|i| self.u16(offset + 2 + i * 2))
+            .collect::<Option<_>>()?;
+        Some(String::from_utf16_lossy(&units))
+    }
+
+    fn walk(&mut self, directory: usize, depth: usize, path: &mut Vec<(u32, Option<String>)>) {
+        let (Some(named), Some(ids)) = (self.u16(directory + 12), self.u16(directory + 14)) else {
+            return;
+        };
+        for i in 0..usize::from(named) + usize::from(ids) {
+            if self.resources.len() >= MAX_RESOURCES {
+                return;
+            }
+            let entry = directory + 16 + i * 8;
+            let (Some(name), Some(target)) = (self.u32(entry), self.u32(entry + 4)) else {
+                return;
+            };
+            path.push((name & 0xffff, self.entry_name(name)));
+            let offset = self.root + (target & 0x7fff_ffff) as usize;
+            if target & 0x8000_0000 != 0 {
+                if depth < 2 {
+                    self.walk(offset, depth + 1, path);
+                }
+            } else {
+                self.add(offset, path);
+            }
+            path.pop();
+        }
+    }
+
+    fn add(&mut self, entry: usize, path: &[(u32, Option<String>)]) {
+        let (Some(rva), Some(size)) = (self.u32(entry), self.u32(entry + 4)) else {
+            return;
+        };
+        let content = find_offset(
+            rva as usize,
+            self.sections,
+            self.file_alignment,
+            &ParseOptions::default(),
+        )
+        .and_then(|offset| self.data.get(offset..offset.saturating_add(size as usize)))
+        .unwrap_or_default();
+
+        let label = |i: usize| match path.get(i) {
+            Some((_, Some(name))) => name.clone(),
+            Some((id, None)) if i == 0 => resource_type_name(*id),
+            Some((id, None)) => id.to_string(),
+            None => String::new(),
+        };
+        self.resources.push(Resource {
+            resource_type: label(0),
+            name: label(1),
+            language: path.get(2).map(|(id, _)| *id).unwrap_or_default(),
+            size,
+            entropy: entropy(content),
+        });
+    }
+}
+
+fn signature_status(pe: &PE) -> SignatureStatus {
+    let Some(certificate) = pe.certificates.first() else {
+        return SignatureStatus::Unsigned;
+    };
+    let mut sha1 = Sha1::new();
+    let mut sha256 = Sha256::new();
+    for range in pe.authenticode_ranges() {
+        sha1.update(range);
+        sha256.update(range);
+    }
+    let signed = certificate.certificate;
+    let contains = |digest: &[u8]| signed.windows(digest.len()).any(|w| w == digest);
+    if contains(&sha256.finalize()) || contains(&sha1.finalize()) {
+        SignatureStatus::DigestMatches
+    } else {
+        SignatureStatus::DigestMismatch
+    }
+}
+
+pub fn analyze(data: &[u8]) -> Result<PeInfo> {
+    if !is_pe(data) {
+        bail!("Not a PE file");
+    }
+    let pe = PE::parse(data)?;
+    let optional_header = pe.header.optional_header;
+    let file_alignment = optional_header
+        .map(|h| h.windows_fields.file_alignment)
+        .unwrap_or(0x200);
+
+    let sections = pe
+        .sections
+        .iter()
+        .map(|s| {
+            let start = s.pointer_to_raw_data as usize;
+            let end = start.saturating_add(s.size_of_raw_data as usize);
+            Section {
+                name: s.name().unwrap_or_default().to_string(),
+                virtual_address: s.virtual_address,
+                virtual_size: s.virtual_size,
+                raw_offset: s.pointer_to_raw_data,
+                raw_size: s.size_of_raw_data,
+                entropy: entropy(data.get(start..end.min(data.len())).unwrap_or_default()),
+                executable: s.characteristics & IMAGE_SCN_MEM_EXECUTE != 0,
+                writable: s.characteristics & IMAGE_SCN_MEM_WRITE != 0,
+            }
+        })
+        .collect();
+
+    let mut imports: Vec<Import> = Vec::new();
+    for import in &pe.imports {
+        match imports.iter_mut().find(|i| i.dll == import.dll) {
+            Some(existing) => existing.functions.push(import.name.to_string()),
+            None => imports.push(Import {
+                dll: import.dll.to_string(),
+                functions: vec![import.name.to_string()],
+            }),
+        }
+    }
+
+    let mut resources = Vec::new();
+    let resource_root = optional_header
+        .and_then(|h| *h.data_directories.get_resource_table())
+        .and_then(|d| {
+            find_offset(
+                d.virtual_address as usize,
+                &pe.sections,
+                file_alignment,
+                &ParseOptions::default(),
+            )
+        });
+    if let Some(root) = resource_root {
+        let mut walker = ResourceWalker {
+            data,
+            root,
+            sections: &pe.sections,
+            file_alignment,
+            resources: Vec::new(),
+        };
+        walker.walk(root, 0, &mut Vec::new());
+        resources = walker.resources;
+    }
+
+    let timestamp = pe.header.coff_header.time_date_stamp;
+    Ok(PeInfo {
+        machine: machine_to_str(pe.header.coff_header.machine).to_string(),
+        is_64: pe.is_64,
+        is_dll: pe.is_lib,
+        compile_time: (timestamp != 0)
+            .then(|| Utc.timestamp_opt(i64::from(timestamp), 0).single())
+            .flatten(),
+        entry_point: pe.entry as u64,
+        image_base: pe.image_base as u64,
+        subsystem: optional_header.map(|h| h.windows_fields.subsystem),
+        sections,
+        imphash: imphash(&imports),
+        imports,
+        exports: pe
+            .exports
+            .iter()
+            .filter_map(|e| e.name.map(str::to_string))
+            .collect(),
+        resources,
+        rich_header: rich_header(data, pe.header.dos_header.pe_pointer as usize),
+        signature: signature_status(&pe),
+        certificates: pe
+            .certificates
+            .first()
+            .map(|c| authenticode::chain(c.certificate))
+            .unwrap_or_default(),
+    })
+}
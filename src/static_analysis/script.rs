@@ -0,0 +1,339 @@
+use std::io::Read;
+use std::sync::OnceLock;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::{DeflateDecoder, GzDecoder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::cmdline::tokenize;
+
+const MIN_BASE64_LEN: usize = 16;
+const MAX_DECODE_DEPTH: usize = 3;
+const MAX_DECOMPRESSED_LEN: u64 = 16 * 1024 * 1024;
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptLanguage {
+    Vba,
+    PowerShell,
+    JScript,
+}
+
+impl ScriptLanguage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Vba => "vba",
+            Self::PowerShell => "powershell",
+            Self::JScript => "jscript",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Vba => "vba",
+            Self::PowerShell => "ps1",
+            Self::JScript => "js",
+        }
+    }
+
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let extension = file_name.rsplit_once('.')?.1.to_lowercase();
+        match extension.as_str() {
+            "vbs" | "vbe" | "vba" | "bas" | "cls" => Some(Self::Vba),
+            "ps1" | "psm1" | "psd1" => Some(Self::PowerShell),
+            "js" | "jse" | "wsf" => Some(Self::JScript),
+            _ => None,
+        }
+    }
+
+    fn quotes(&self) -> &'static [char] {
+        match self {
+            Self::Vba => &['"'],
+            Self::PowerShell | Self::JScript => &['"', '\''],
+        }
+    }
+
+    fn is_concat(&self, operator: &str) -> bool {
+        match self {
+            Self::Vba => operator == "&" || operator == "+",
+            Self::PowerShell | Self::JScript => operator == "+",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NormalizedScript {
+    pub source: String,
+    pub decoded: Vec<String>,
+}
+
+enum Token {
+    Literal(char, String),
+    Code(String),
+}
+
+fn tokens(language: ScriptLanguage, source: &str) -> Vec<Token> {
+    let quotes = language.quotes();
+    let mut tokens = Vec::new();
+    let mut code = String::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !quotes.contains(&c) || (language == ScriptLanguage::Vba && comment_open(&code)) {
+            code.push(c);
+            continue;
+        }
+
+        let mut literal = String::new();
+        let mut closed = false;
+        while let Some(next) = chars.next() {
+            match next {
+                '\\' if language == ScriptLanguage::JScript => {
+                    literal.push(next);
+                    literal.extend(chars.next());
+                }
+                '`' if language == ScriptLanguage::PowerShell && c == '"' => {
+                    literal.push(next);
+                    literal.extend(chars.next());
+                }
+                '\n' => {
+                    literal.push(next);
+                    if language != ScriptLanguage::PowerShell {
+                        break;
+                    }
+                }
+                q if q == c => {
+                    if chars.peek() == Some(&c) && language != ScriptLanguage::JScript {
+                        literal.push(q);
+                        literal.push(chars.next().unwrap_or(q));
+                    } else {
+                        closed = true;
+                        break;
+                    }
+                }
+                _ => literal.push(next),
+            }
+        }
+        if !closed {
+            code.push(c);
+            code.push_str(&literal);
+            continue;
+        }
+        tokens.push(Token::Code(std::mem::take(&mut code)));
+        tokens.push(Token::Literal(c, literal));
+    }
+    tokens.push(Token::Code(code));
+    tokens
+}
+
+fn comment_open(code: &str) -> bool {
+    let line = code.rsplit('\n').next().unwrap_or(code);
+    line.contains('\'') || line.trim_start().to_lowercase().starts_with("rem ")
+}
+
+fn join_concatenations(language: ScriptLanguage, source: &str) -> String {
+    let mut out = String::new();
+    let mut pending: Option<(char, String)> = None;
+    for token in tokens(language, source) {
+        match token {
+            Token::Literal(quote, literal) => match pending.as_mut() {
+                Some((_, joined)) => joined.push_str(&literal),
+                None => pending = Some((quote, literal)),
+            },
+            Token::Code(code) => {
+                if pending.is_some() && language.is_concat(code.trim()) {
+                    continue;
+                }
+                if let Some((quote, joined)) = pending.take() {
+                    out.push(quote);
+                    out.push_str(&joined);
+                    out.push(quote);
+                }
+                out.push_str(&code);
+            }
+        }
+    }
+    out
+}
+
+fn char_literal(language: ScriptLanguage, code: u32) -> Option<String> {
+    let c = char::from_u32(code).filter(|c| c.is_ascii_graphic() || *c == ' ')?;
+    let quote = match language {
+        ScriptLanguage::PowerShell if c == '\'' => return None,
+        ScriptLanguage::PowerShell => '\'',
+        _ if c == '"' || c == '\\' => return None,
+        _ => '"',
+    };
+    Some(format!("{}{}{}", quote, c, quote))
+}
+
+fn char_codes(language: ScriptLanguage) -> &'static Regex {
+    static VBA: OnceLock<Regex> = OnceLock::new();
+    static POWERSHELL: OnceLock<Regex> = OnceLock::new();
+    static JSCRIPT: OnceLock<Regex> = OnceLock::new();
+    match language {
+        ScriptLanguage::Vba => {
+            VBA.get_or_init(|| Regex::new(r"(?i)\bChrW?\$?\(\s*(\d+)\s*\)").expect("valid regex"))
+        }
+        ScriptLanguage::PowerShell => POWERSHELL
+            .get_or_init(|| Regex::new(r"(?i)\[char\]\s*\(?\s*(\d+)\s*\)?").expect("valid regex")),
+        ScriptLanguage::JScript => JSCRIPT.get_or_init(|| {
+            Regex::new(r"String\.fromCharCode\(\s*(\d+)\s*\)").expect("valid regex")
+        }),
+    }
+}
+
+fn replace_char_codes(language: ScriptLanguage, source: &str) -> String {
+    char_codes(language)
+        .replace_all(source, |caps: &regex::Captures| {
+            caps[1]
+                .parse()
+                .ok()
+                .and_then(|code| char_literal(language, code))
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+fn printable(text: &str) -> bool {
+    let total = text.chars().count();
+    total > 0
+        && text
+            .chars()
+            .filter(|c| !c.is_control() || c.is_ascii_whitespace())
+            .count()
+            * 10
+            >= total * 9
+}
+
+fn decompress(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    let result = match bytes.starts_with(GZIP_MAGIC) {
+        true => GzDecoder::new(bytes)
+            .take(MAX_DECOMPRESSED_LEN)
+            .read_to_end(&mut decompressed),
+        false => DeflateDecoder::new(bytes)
+            .take(MAX_DECOMPRESSED_LEN)
+            .read_to_end(&mut decompressed),
+    };
+    match result {
+        Ok(_) if !decompressed.is_empty() => Some(decompressed),
+        _ => None,
+    }
+}
+
+fn decode_text(bytes: Vec<u8>) -> Option<String> {
+    let utf16 = (bytes.len().is_multiple_of(2) && bytes.iter().skip(1).step_by(2).all(|&b| b == 0))
+        .then(|| {
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        });
+    utf16
+        .or_else(|| String::from_utf8(bytes).ok())
+        .filter(|text| printable(text))
+}
+
+pub fn decode_base64(data: &str) -> Option<String> {
+    let compact: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.len() < MIN_BASE64_LEN {
+        return None;
+    }
+    let bytes = STANDARD.decode(compact).ok()?;
+    if bytes.starts_with(GZIP_MAGIC) {
+        return decompress(&bytes).and_then(decode_text);
+    }
+    let compressed = decompress(&bytes);
+    decode_text(bytes).or_else(|| compressed.and_then(decode_text))
+}
+
+fn invoked_expressions(source: &str) -> Vec<String> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN
+        .get_or_init(|| {
+            Regex::new(
+                r#"(?is)(?:\biex\b|\binvoke-expression\b)\s*\(?\s*(?:'([^']*)'|"([^"]*)")|(?:'([^']*)'|"([^"]*)")\s*\)?\s*\|\s*(?:iex|invoke-expression)\b"#,
+            )
+            .expect("valid regex")
+        })
+        .captures_iter(source)
+        .filter_map(|caps| {
+            (1..=4)
+                .find_map(|i| caps.get(i))
+                .map(|m| m.as_str().replace("''", "'"))
+        })
+        .filter(|expression| !expression.trim().is_empty())
+        .collect()
+}
+
+fn encoded_commands(source: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    for line in source.lines() {
+        let args = tokenize(line);
+        for pair in args.windows(2) {
+            let flag = pair[0].to_lowercase();
+            let flag = flag.trim_start_matches(['-', '/']);
+            if flag == "e" || (flag.len() >= 2 && "encodedcommand".starts_with(flag)) {
+                commands.extend(decode_base64(&pair[1]));
+            }
+        }
+    }
+    commands
+}
+
+fn literals(language: ScriptLanguage, source: &str) -> Vec<String> {
+    tokens(language, source)
+        .into_iter()
+        .filter_map(|t| match t {
+            Token::Literal(_, literal) => Some(literal),
+            Token::Code(_) => None,
+        })
+        .collect()
+}
+
+fn normalize_source(language: ScriptLanguage, source: &str) -> String {
+    let source = match language {
+        ScriptLanguage::Vba => source.replace(" _\r\n", " ").replace(" _\n", " "),
+        _ => source.to_string(),
+    };
+    let source = replace_char_codes(language, &source);
+    join_concatenations(language, &source)
+}
+
+fn decode_nested(language: ScriptLanguage, source: &str, depth: usize, decoded: &mut Vec<String>) {
+    if depth >= MAX_DECODE_DEPTH {
+        return;
+    }
+    let mut found = encoded_commands(source);
+    found.extend(
+        literals(language, source)
+            .iter()
+            .filter_map(|l| decode_base64(l)),
+    );
+    if language == ScriptLanguage::PowerShell {
+        found.extend(invoked_expressions(source));
+    }
+    let inner = if language == ScriptLanguage::Vba {
+        ScriptLanguage::PowerShell
+    } else {
+        language
+    };
+    for text in found {
+        let text = normalize_source(inner, &text);
+        if decoded.contains(&text) {
+            continue;
+        }
+        decoded.push(text.clone());
+        decode_nested(inner, &text, depth + 1, decoded);
+    }
+}
+
+pub fn normalize(language: ScriptLanguage, source: &str) -> NormalizedScript {
+    let source = normalize_source(language, source);
+    let mut decoded = Vec::new();
+    decode_nested(language, &source, 0, &mut decoded);
+    NormalizedScript { source, decoded }
+}
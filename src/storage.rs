@@ -0,0 +1,81 @@
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod retention;
+pub mod search;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::ioc::Ioc;
+use crate::metadata::{Facet, SampleMetadata};
+use crate::report::SandboxReport;
+use search::{EventHit, EventQuery};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoredRun {
+    pub execution_id: String,
+    pub analysis_id: String,
+    pub hash: String,
+    pub time: DateTime<Local>,
+    pub score: f64,
+}
+
+impl StoredRun {
+    pub fn from_report(report: &SandboxReport) -> Self {
+        Self {
+            execution_id: report.execution_id.clone(),
+            analysis_id: report.id.clone(),
+            hash: report.hash.clone(),
+            time: report.time,
+            score: report.score.score,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunQuery {
+    Hash(String),
+    Ioc(Ioc),
+    Facet(Facet, String),
+}
+
+impl RunQuery {
+    pub fn domain(domain: &str) -> Self {
+        Self::Ioc(Ioc::Domain(domain.to_lowercase()))
+    }
+
+    pub fn mutex(mutex: &str) -> Self {
+        Self::Ioc(Ioc::Mutex(mutex.to_string()))
+    }
+
+    pub fn facet(facet: Facet, value: &str) -> Result<Self> {
+        Ok(Self::Facet(facet, facet.normalize(value)?))
+    }
+}
+
+pub trait ResultStore {
+    fn save_report(&mut self, report: &SandboxReport) -> Result<()>;
+
+    fn find_runs(&mut self, query: &RunQuery) -> Result<Vec<StoredRun>>;
+
+    fn save_metadata(&mut self, execution_id: &str, metadata: &SampleMetadata) -> Result<()>;
+
+    fn purge_events(&mut self, execution_id: &str) -> Result<usize>;
+
+    fn search_events(&mut self, query: &EventQuery, limit: usize) -> Result<Vec<EventHit>>;
+
+    fn runs_by_hash(&mut self, hash: &str) -> Result<Vec<StoredRun>> {
+        self.find_runs(&RunQuery::Hash(hash.to_lowercase()))
+    }
+
+    fn runs_by_domain(&mut self, domain: &str) -> Result<Vec<StoredRun>> {
+        self.find_runs(&RunQuery::domain(domain))
+    }
+
+    fn runs_by_mutex(&mut self, mutex: &str) -> Result<Vec<StoredRun>> {
+        self.find_runs(&RunQuery::mutex(mutex))
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use postgres::types::{Json, ToSql};
+use postgres::{Client, NoTls, Row, Transaction};
+
+use super::search::{Dialect, EventHit, EventQuery};
+use super::{ResultStore, RunQuery, StoredRun};
+use crate::metadata::SampleMetadata;
+use crate::report::SandboxReport;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE runs (
+    execution_id TEXT PRIMARY KEY,
+    analysis_id TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    time TIMESTAMPTZ NOT NULL,
+    score DOUBLE PRECISION NOT NULL
+);
+CREATE TABLE events (
+    id BIGSERIAL PRIMARY KEY,
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    time TIMESTAMPTZ NOT NULL,
+    event_id INTEGER NOT NULL,
+    computer TEXT,
+    record_id BIGINT,
+    event_data JSONB NOT NULL
+);
+CREATE TABLE artifacts (
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    md5 TEXT NOT NULL,
+    sha1 TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    ssdeep TEXT NOT NULL
+);
+CREATE TABLE detections (
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    source TEXT NOT NULL,
+    name TEXT NOT NULL,
+    level TEXT,
+    tags TEXT[] NOT NULL,
+    events BIGINT NOT NULL
+);
+CREATE TABLE iocs (
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    confidence TEXT NOT NULL,
+    first_seen TIMESTAMPTZ NOT NULL,
+    occurrences BIGINT NOT NULL
+);
+CREATE INDEX runs_hash ON runs(hash);
+CREATE INDEX events_run ON events(run);
+CREATE INDEX events_event_data ON events USING GIN (event_data);
+CREATE INDEX artifacts_run ON artifacts(run);
+CREATE INDEX artifacts_sha256 ON artifacts(sha256);
+CREATE INDEX detections_run ON detections(run);
+CREATE INDEX iocs_run ON iocs(run);
+CREATE INDEX iocs_kind_value ON iocs(kind, value);
+",
+    "
+CREATE TABLE facets (
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    facet TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE INDEX facets_run ON facets(run);
+CREATE INDEX facets_facet_value ON facets(facet, value);
+",
+];
+
+const RUN_COLUMNS: &str = "runs.execution_id, runs.analysis_id, runs.hash, runs.time, runs.score";
+const EVENT_COLUMNS: &str =
+    "events.time, events.event_id, events.computer, events.record_id, events.event_data";
+
+fn stored_run(row: &Row) -> Result<StoredRun> {
+    Ok(StoredRun {
+        execution_id: row.try_get(0)?,
+        analysis_id: row.try_get(1)?,
+        hash: row.try_get(2)?,
+        time: row.try_get(3)?,
+        score: row.try_get(4)?,
+    })
+}
+
+fn event_hit(row: &Row) -> Result<EventHit> {
+    let event_id: i32 = row.try_get(6)?;
+    let record_id: Option<i64> = row.try_get(8)?;
+    let Json(event_data): Json<HashMap<String, String>> = row.try_get(9)?;
+    let time_created: DateTime<FixedOffset> = row.try_get(5)?;
+    Ok(EventHit {
+        run: stored_run(row)?,
+        event: SysmonEvent {
+            event_id: u8::try_from(event_id)
+                .ok()
+                .and_then(SysmonEventId::new)
+                .with_context(|| format!("Invalid stored EventID {}", event_id))?,
+            time_created,
+            computer: row.try_get(7)?,
+            record_id: record_id.map(|r| r as u64),
+            channel: None,
+            event_data,
+        },
+    })
+}
+
+pub struct PostgresResultStore {
+    client: Client,
+}
+
+impl PostgresResultStore {
+    pub fn connect(params: &str) -> Result<Self> {
+        let mut client = Client::connect(params, NoTls)?;
+        migrate(&mut client)?;
+        Ok(Self { client })
+    }
+
+    fn query_runs(&mut self, sql: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<StoredRun>> {
+        self.client
+            .query(sql, params)?
+            .iter()
+            .map(stored_run)
+            .collect()
+    }
+}
+
+fn insert_facets(tx: &mut Transaction, run: &str, metadata: &SampleMetadata) -> Result<()> {
+    let insert_facet = tx.prepare("INSERT INTO facets (run, facet, value) VALUES ($1, $2, $3)")?;
+    for (facet, value) in metadata.facets() {
+        tx.execute(&insert_facet, &[&run, &facet.name(), &value])?;
+    }
+    Ok(())
+}
+
+fn migrate(client: &mut Client) -> Result<()> {
+    client.batch_execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY)",
+    )?;
+    let version: i32 = client
+        .query_one(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+            &[],
+        )?
+        .try_get(0)?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let mut tx = client.transaction()?;
+        tx.batch_execute(migration)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version) VALUES ($1)",
+            &[&(i as i32 + 1)],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+impl ResultStore for PostgresResultStore {
+    fn save_report(&mut self, report: &SandboxReport) -> Result<()> {
+        let run = StoredRun::from_report(report);
+        let mut tx = self.client.transaction()?;
+        tx.execute(
+            "DELETE FROM runs WHERE execution_id = $1",
+            &[&run.execution_id],
+        )?;
+        tx.execute(
+            "INSERT INTO runs (execution_id, analysis_id, hash, time, score)
+             VALUES ($1, $2, $3, $4, $5)",
+            &[
+                &run.execution_id,
+                &run.analysis_id,
+                &run.hash,
+                &run.time,
+                &run.score,
+            ],
+        )?;
+
+        let insert_event = tx.prepare(
+            "INSERT INTO events (run, time, event_id, computer, record_id, event_data)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )?;
+        for event in &report.events {
+            tx.execute(
+                &insert_event,
+                &[
+                    &run.execution_id,
+                    &event.time_created,
+                    &i32::from(event.event_id.value()),
+                    &event.computer,
+                    &event.record_id.map(|id| id as i64),
+                    &Json(&event.event_data),
+                ],
+            )?;
+        }
+
+        let insert_artifact = tx.prepare(
+            "INSERT INTO artifacts (run, path, size, md5, sha1, sha256, ssdeep)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )?;
+        for artifact in &report.artifacts {
+            tx.execute(
+                &insert_artifact,
+                &[
+                    &run.execution_id,
+                    &artifact.path,
+                    &(artifact.size as i64),
+                    &artifact.hashes.md5,
+                    &artifact.hashes.sha1,
+                    &artifact.hashes.sha256,
+                    &artifact.hashes.ssdeep,
+                ],
+            )?;
+        }
+
+        let insert_detection = tx.prepare(
+            "INSERT INTO detections (run, source, name, level, tags, events)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )?;
+        for detection in &report.detections {
+            tx.execute(
+                &insert_detection,
+                &[
+                    &run.execution_id,
+                    &detection.source,
+                    &detection.name,
+                    &detection.level,
+                    &detection.tags,
+                    &(detection.events.len() as i64),
+                ],
+            )?;
+        }
+
+        let insert_ioc = tx.prepare(
+            "INSERT INTO iocs (run, kind, value, confidence, first_seen, occurrences)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )?;
+        for indicator in report.iocs.iter() {
+            tx.execute(
+                &insert_ioc,
+                &[
+                    &run.execution_id,
+                    &indicator.ioc.kind(),
+                    &indicator.ioc.value(),
+                    &format!("{:?}", indicator.confidence),
+                    &indicator.first_seen,
+                    &(indicator.occurrences as i64),
+                ],
+            )?;
+        }
+        insert_facets(&mut tx, &run.execution_id, &report.metadata)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_metadata(&mut self, execution_id: &str, metadata: &SampleMetadata) -> Result<()> {
+        let mut tx = self.client.transaction()?;
+        tx.execute("DELETE FROM facets WHERE run = $1", &[&execution_id])?;
+        insert_facets(&mut tx, execution_id, metadata)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn purge_events(&mut self, execution_id: &str) -> Result<usize> {
+        let deleted = self
+            .client
+            .execute("DELETE FROM events WHERE run = $1", &[&execution_id])?;
+        Ok(deleted as usize)
+    }
+
+    fn search_events(&mut self, query: &EventQuery, limit: usize) -> Result<Vec<EventHit>> {
+        let filter = query.to_sql(Dialect::Postgres);
+        let mut sql = format!(
+            "SELECT {}, {} FROM events
+             JOIN runs ON runs.execution_id = events.run
+             WHERE {}
+             ORDER BY runs.time DESC, events.time",
+            RUN_COLUMNS, EVENT_COLUMNS, filter.sql
+        );
+        if filter.exact {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        let params: Vec<&(dyn ToSql + Sync)> = filter
+            .params
+            .iter()
+            .map(|p| p as &(dyn ToSql + Sync))
+            .collect();
+        let mut hits = Vec::new();
+        for row in self.client.query(&sql, &params)? {
+            let hit = event_hit(&row)?;
+            if query.matches(&hit.event) {
+                hits.push(hit);
+                if hits.len() >= limit {
+                    break;
+                }
+            }
+        }
+        Ok(hits)
+    }
+
+    fn find_runs(&mut self, query: &RunQuery) -> Result<Vec<StoredRun>> {
+        match query {
+            RunQuery::Hash(hash) => self.query_runs(
+                &format!(
+                    "SELECT DISTINCT {} FROM runs
+                     LEFT JOIN artifacts ON artifacts.run = runs.execution_id
+                     WHERE runs.hash = $1 OR artifacts.md5 = $1
+                         OR artifacts.sha1 = $1 OR artifacts.sha256 = $1
+                     ORDER BY runs.time DESC",
+                    RUN_COLUMNS
+                ),
+                &[&hash.to_lowercase()],
+            ),
+            RunQuery::Ioc(ioc) => self.query_runs(
+                &format!(
+                    "SELECT DISTINCT {} FROM runs
+                     JOIN iocs ON iocs.run = runs.execution_id
+                     WHERE iocs.kind = $1 AND iocs.value = $2
+                     ORDER BY runs.time DESC",
+                    RUN_COLUMNS
+                ),
+                &[&ioc.kind(), &ioc.value()],
+            ),
+            RunQuery::Facet(facet, value) => self.query_runs(
+                &format!(
+                    "SELECT DISTINCT {} FROM runs
+                     JOIN facets ON facets.run = runs.execution_id
+                     WHERE facets.facet = $1 AND facets.value = $2
+                     ORDER BY runs.time DESC",
+                    RUN_COLUMNS
+                ),
+                &[&facet.name(), value],
+            ),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::ResultStore;
+use crate::analysis_result::{artifact_dir, AnalysisResult, AnalysisResultManager, ExecutionLog};
+use crate::export::archive::REPORT_FILE;
+use crate::pcap::PCAP_FILE_NAME;
+use crate::report::SandboxReport;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataClass {
+    Events,
+    Pcap,
+    MemoryDumps,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionRule {
+    pub events: Option<u32>,
+    pub pcap: Option<u32>,
+    pub memory_dumps: Option<u32>,
+}
+
+impl RetentionRule {
+    fn or(&self, fallback: &Self) -> Self {
+        Self {
+            events: self.events.or(fallback.events),
+            pcap: self.pcap.or(fallback.pcap),
+            memory_dumps: self.memory_dumps.or(fallback.memory_dumps),
+        }
+    }
+
+    fn days(&self, class: DataClass) -> Option<u32> {
+        match class {
+            DataClass::Events => self.events,
+            DataClass::Pcap => self.pcap,
+            DataClass::MemoryDumps => self.memory_dumps,
+        }
+    }
+
+    pub fn is_expired(
+        &self,
+        class: DataClass,
+        time: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> bool {
+        self.days(class)
+            .is_some_and(|days| time + Duration::days(days.into()) <= now)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub default: RetentionRule,
+    #[serde(default)]
+    pub tenants: HashMap<String, RetentionRule>,
+}
+
+impl RetentionPolicy {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn rule_for(&self, tenant: Option<&str>) -> RetentionRule {
+        match tenant.and_then(|t| self.tenants.get(t)) {
+            Some(rule) => rule.or(&self.default),
+            None => self.default.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcSummary {
+    pub analyses: usize,
+    pub executions: usize,
+    pub events: usize,
+    pub pcaps: usize,
+    pub memory_dumps: usize,
+    pub bytes: u64,
+}
+
+pub fn stored_report_path(id: &str, execution_id: &str) -> String {
+    format!("{}/{}", artifact_dir(id, execution_id), REPORT_FILE)
+}
+
+type Reporter<'a> = dyn Fn(&AnalysisResult) -> Result<SandboxReport> + Sync + 'a;
+
+pub struct GarbageCollector<'a> {
+    policy: &'a RetentionPolicy,
+    results: &'a AnalysisResultManager,
+    store: Option<&'a Mutex<dyn ResultStore + Send>>,
+    reporter: Option<&'a Reporter<'a>>,
+    dry_run: bool,
+}
+
+impl<'a> GarbageCollector<'a> {
+    pub fn new(policy: &'a RetentionPolicy, results: &'a AnalysisResultManager) -> Self {
+        Self {
+            policy,
+            results,
+            store: None,
+            reporter: None,
+            dry_run: false,
+        }
+    }
+
+    pub fn with_store(mut self, store: &'a Mutex<dyn ResultStore + Send>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn with_reporter(mut self, reporter: &'a Reporter<'a>) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub async fn run(&self, now: DateTime<Local>) -> Result<GcSummary> {
+        let mut summary = GcSummary::default();
+        for id in self.results.ids().await? {
+            let Some(mut result) = self.results.get(&id).await? else {
+                continue;
+            };
+            let rule = self.policy.rule_for(result.tenant.as_deref());
+            let mut changed = false;
+            for i in 0..result.execution_logs.len() {
+                changed |= self.collect_log(&mut result, i, &rule, now, &mut summary)?;
+            }
+            if changed {
+                summary.analyses += 1;
+                if !self.dry_run {
+                    self.results.replace(&result).await?;
+                }
+            }
+        }
+        info!(
+            "Retention removed {} events, {} pcaps and {} memory dumps from {} analyses ({} bytes)",
+            summary.events, summary.pcaps, summary.memory_dumps, summary.analyses, summary.bytes
+        );
+        Ok(summary)
+    }
+
+    fn collect_log(
+        &self,
+        result: &mut AnalysisResult,
+        index: usize,
+        rule: &RetentionRule,
+        now: DateTime<Local>,
+        summary: &mut GcSummary,
+    ) -> Result<bool> {
+        let log = &result.execution_logs[index];
+        let dir = artifact_dir(&result.id, &log.id);
+        let mut changed = false;
+
+        if !log.purged.contains(&DataClass::Events)
+            && rule.is_expired(DataClass::Events, log.time, now)
+        {
+            if !self.dry_run {
+                self.keep_report(result, index)?;
+                if let Some(store) = self.store {
+                    store
+                        .lock()
+                        .map_err(|_| anyhow::anyhow!("Result store is poisoned"))?
+                        .purge_events(&result.execution_logs[index].id)?;
+                }
+            }
+            let log = &mut result.execution_logs[index];
+            summary.executions += 1;
+            summary.events += log.sysmon_events.len() + log.telemetry.len();
+            log.sysmon_events = Vec::new();
+            log.telemetry = Vec::new();
+            log.purged.push(DataClass::Events);
+            changed = true;
+        }
+
+        let log = &mut result.execution_logs[index];
+        if !log.purged.contains(&DataClass::Pcap) && rule.is_expired(DataClass::Pcap, log.time, now)
+        {
+            if let Some(size) = self.remove(&format!("{}/{}", dir, PCAP_FILE_NAME))? {
+                summary.pcaps += 1;
+                summary.bytes += size;
+            }
+            log.purged.push(DataClass::Pcap);
+            changed = true;
+        }
+
+        if !log.purged.contains(&DataClass::MemoryDumps)
+            && rule.is_expired(DataClass::MemoryDumps, log.time, now)
+        {
+            for memory in &log.memory {
+                if let Some(size) = self.remove(&format!("{}/{}", dir, memory.dump))? {
+                    summary.memory_dumps += 1;
+                    summary.bytes += size;
+                }
+            }
+            log.purged.push(DataClass::MemoryDumps);
+            changed = true;
+        }
+        Ok(changed)
+    }
+
+    fn keep_report(&self, result: &AnalysisResult, index: usize) -> Result<()> {
+        let Some(reporter) = self.reporter else {
+            return Ok(());
+        };
+        let log = &result.execution_logs[index];
+        let path = stored_report_path(&result.id, &log.id);
+        if Path::new(&path).exists() {
+            return Ok(());
+        }
+        let single = AnalysisResult {
+            id: result.id.clone(),
+            hash: result.hash.clone(),
+            execution_logs: vec![ExecutionLog {
+                purged: Vec::new(),
+                ..log.clone()
+            }],
+            tenant: result.tenant.clone(),
+            metadata: result.metadata.clone(),
+        };
+        let report = reporter(&single)
+            .with_context(|| format!("Failed to render the report of {}", log.id))?;
+        if let Some(parent) = Path::new(&path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, report.to_json()?)?;
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> Result<Option<u64>> {
+        let size = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return Ok(None),
+        };
+        if !self.dry_run {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("Failed to remove {}: {}", path, e);
+                return Ok(None);
+            }
+        }
+        Ok(Some(size))
+    }
+}
@@ -0,0 +1,594 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+use serde::Serialize;
+
+use super::StoredRun;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Any,
+    Process,
+    Network,
+    File,
+    Registry,
+    Dns,
+    ImageLoad,
+    Driver,
+    Pipe,
+    Wmi,
+}
+
+impl Category {
+    fn from_name(name: &str) -> Option<Self> {
+        let category = match name.to_lowercase().as_str() {
+            "any" => Self::Any,
+            "process" => Self::Process,
+            "network" => Self::Network,
+            "file" => Self::File,
+            "registry" => Self::Registry,
+            "dns" => Self::Dns,
+            "image_load" | "library" => Self::ImageLoad,
+            "driver" => Self::Driver,
+            "pipe" => Self::Pipe,
+            "wmi" => Self::Wmi,
+            _ => return None,
+        };
+        Some(category)
+    }
+
+    pub fn event_ids(&self) -> &'static [SysmonEventId] {
+        match self {
+            Self::Any => &[],
+            Self::Process => &[
+                SysmonEventId::PROCESS_CREATE,
+                SysmonEventId::PROCESS_TERMINATE,
+            ],
+            Self::Network => &[SysmonEventId::NETWORK_CONNECT],
+            Self::File => &[
+                SysmonEventId::FILE_CREATE_TIME,
+                SysmonEventId::FILE_CREATE,
+                SysmonEventId::FILE_CREATE_STREAM_HASH,
+                SysmonEventId::FILE_DELETE,
+                SysmonEventId::FILE_DELETE_DETECTED,
+            ],
+            Self::Registry => &[
+                SysmonEventId::REGISTRY_EVENT_ADD_DELETE,
+                SysmonEventId::REGISTRY_EVENT_SET,
+                SysmonEventId::REGISTRY_EVENT_RENAME,
+            ],
+            Self::Dns => &[SysmonEventId::DNS_QUERY],
+            Self::ImageLoad => &[SysmonEventId::IMAGE_LOAD],
+            Self::Driver => &[SysmonEventId::DRIVER_LOAD],
+            Self::Pipe => &[
+                SysmonEventId::PIPE_EVENT_CREATE,
+                SysmonEventId::PIPE_EVENT_CONNECT,
+            ],
+            Self::Wmi => &[
+                SysmonEventId::WMI_EVENT_FILTER,
+                SysmonEventId::WMI_EVENT_CONSUMER,
+                SysmonEventId::WMI_EVENT_CONSUMER_FILTER,
+            ],
+        }
+    }
+
+    fn matches(&self, event: &SysmonEvent) -> bool {
+        let ids = self.event_ids();
+        ids.is_empty() || ids.contains(&event.event_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    StartsWith,
+    EndsWith,
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    EventId,
+    RecordId,
+    Computer,
+    Time,
+    Data(String),
+}
+
+impl Field {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "event_id" => Self::EventId,
+            "record_id" => Self::RecordId,
+            "computer" => Self::Computer,
+            "time" => Self::Time,
+            _ => Self::Data(name.to_string()),
+        }
+    }
+
+    fn value(&self, event: &SysmonEvent) -> Option<String> {
+        match self {
+            Self::EventId => Some(event.event_id.value().to_string()),
+            Self::RecordId => event.record_id.map(|r| r.to_string()),
+            Self::Computer => event.computer.clone(),
+            Self::Time => Some(event.time_created.to_rfc3339()),
+            Self::Data(name) => event.event_data.get(name).cloned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        field: Field,
+        operator: Operator,
+        values: Vec<String>,
+    },
+}
+
+fn compare_ordered(value: &str, other: &str) -> std::cmp::Ordering {
+    match (value.parse::<f64>(), other.parse::<f64>()) {
+        (Ok(value), Ok(other)) => value.total_cmp(&other),
+        _ => value.cmp(other),
+    }
+}
+
+impl Expr {
+    fn matches(&self, event: &SysmonEvent) -> bool {
+        match self {
+            Self::And(left, right) => left.matches(event) && right.matches(event),
+            Self::Or(left, right) => left.matches(event) || right.matches(event),
+            Self::Not(expr) => !expr.matches(event),
+            Self::Compare {
+                field,
+                operator,
+                values,
+            } => {
+                let Some(value) = field.value(event) else {
+                    return false;
+                };
+                let lower = value.to_ascii_lowercase();
+                values.iter().any(|other| match operator {
+                    Operator::Eq | Operator::In => value.eq_ignore_ascii_case(other),
+                    Operator::Ne => !value.eq_ignore_ascii_case(other),
+                    Operator::Lt => compare_ordered(&value, other).is_lt(),
+                    Operator::Le => compare_ordered(&value, other).is_le(),
+                    Operator::Gt => compare_ordered(&value, other).is_gt(),
+                    Operator::Ge => compare_ordered(&value, other).is_ge(),
+                    Operator::Contains => lower.contains(&other.to_ascii_lowercase()),
+                    Operator::StartsWith => lower.starts_with(&other.to_ascii_lowercase()),
+                    Operator::EndsWith => lower.ends_with(&other.to_ascii_lowercase()),
+                })
+            }
+        }
+    }
+
+    fn to_sql(&self, filter: &mut SqlFilter) -> (String, bool) {
+        match self {
+            Self::And(left, right) => {
+                let (left, left_exact) = left.to_sql(filter);
+                let (right, right_exact) = right.to_sql(filter);
+                (
+                    format!("({} AND {})", left, right),
+                    left_exact && right_exact,
+                )
+            }
+            Self::Or(left, right) => {
+                let (left, left_exact) = left.to_sql(filter);
+                let (right, right_exact) = right.to_sql(filter);
+                (
+                    format!("({} OR {})", left, right),
+                    left_exact && right_exact,
+                )
+            }
+            Self::Not(expr) => match expr.to_sql(filter) {
+                (sql, true) => (format!("(NOT {})", sql), true),
+                (_, false) => ("TRUE".to_string(), false),
+            },
+            Self::Compare {
+                field,
+                operator,
+                values,
+            } => filter.compare(field, *operator, values),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+#[derive(Debug, Clone)]
+pub struct SqlFilter {
+    dialect: Dialect,
+    pub sql: String,
+    pub params: Vec<String>,
+    pub exact: bool,
+}
+
+fn like_pattern(value: &str, prefix: &str, suffix: &str) -> String {
+    let escaped = value
+        .to_ascii_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("{}{}{}", prefix, escaped, suffix)
+}
+
+impl SqlFilter {
+    fn new(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            sql: String::new(),
+            params: Vec::new(),
+            exact: true,
+        }
+    }
+
+    fn param(&mut self, value: String) -> String {
+        self.params.push(value);
+        match self.dialect {
+            Dialect::Sqlite => format!("?{}", self.params.len()),
+            Dialect::Postgres => format!("${}", self.params.len()),
+        }
+    }
+
+    fn column(&mut self, name: &str) -> String {
+        match self.dialect {
+            Dialect::Sqlite => {
+                let path = self.param(format!("$.\"{}\"", name));
+                format!("json_extract(events.event_data, {})", path)
+            }
+            Dialect::Postgres => {
+                let key = self.param(name.to_string());
+                format!("(events.event_data->>({}::text))", key)
+            }
+        }
+    }
+
+    fn compare(&mut self, field: &Field, operator: Operator, values: &[String]) -> (String, bool) {
+        let column = match field {
+            Field::EventId | Field::RecordId => {
+                let Ok(numbers) = values
+                    .iter()
+                    .map(|v| v.parse::<i64>())
+                    .collect::<Result<Vec<_>, _>>()
+                else {
+                    return ("TRUE".to_string(), false);
+                };
+                let column = match field {
+                    Field::EventId => "events.event_id",
+                    _ => "events.record_id",
+                };
+                let sql = match operator {
+                    Operator::Eq | Operator::In => format!(
+                        "{} IN ({})",
+                        column,
+                        numbers
+                            .iter()
+                            .map(i64::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    Operator::Ne | Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge => {
+                        let symbol = match operator {
+                            Operator::Ne => "<>",
+                            Operator::Lt => "<",
+                            Operator::Le => "<=",
+                            Operator::Gt => ">",
+                            _ => ">=",
+                        };
+                        numbers
+                            .iter()
+                            .map(|n| format!("{} {} {}", column, symbol, n))
+                            .collect::<Vec<_>>()
+                            .join(" OR ")
+                    }
+                    Operator::Contains | Operator::StartsWith | Operator::EndsWith => {
+                        return ("TRUE".to_string(), false)
+                    }
+                };
+                return (format!("COALESCE(({}), FALSE)", sql), true);
+            }
+            Field::Time => return ("TRUE".to_string(), false),
+            Field::Computer => "events.computer".to_string(),
+            Field::Data(name) => self.column(name),
+        };
+        let column = format!("lower({})", column);
+        let comparisons: Vec<String> = match operator {
+            Operator::Eq | Operator::In | Operator::Ne => values
+                .iter()
+                .map(|v| {
+                    let param = self.param(v.to_ascii_lowercase());
+                    let symbol = if operator == Operator::Ne { "<>" } else { "=" };
+                    format!("{} {} {}", column, symbol, param)
+                })
+                .collect(),
+            Operator::Contains | Operator::StartsWith | Operator::EndsWith => values
+                .iter()
+                .map(|v| {
+                    let pattern = match operator {
+                        Operator::Contains => like_pattern(v, "%", "%"),
+                        Operator::StartsWith => like_pattern(v, "", "%"),
+                        _ => like_pattern(v, "%", ""),
+                    };
+                    let param = self.param(pattern);
+                    format!("{} LIKE {} ESCAPE '\\'", column, param)
+                })
+                .collect(),
+            Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge => {
+                return ("TRUE".to_string(), false)
+            }
+        };
+        (
+            format!("COALESCE(({}), FALSE)", comparisons.join(" OR ")),
+            true,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventQuery {
+    pub category: Category,
+    pub condition: Option<Expr>,
+}
+
+impl EventQuery {
+    pub fn parse(query: &str) -> Result<Self> {
+        QueryParser::new(query)?.parse()
+    }
+
+    pub fn matches(&self, event: &SysmonEvent) -> bool {
+        self.category.matches(event) && self.condition.as_ref().is_none_or(|c| c.matches(event))
+    }
+
+    pub fn to_sql(&self, dialect: Dialect) -> SqlFilter {
+        let mut filter = SqlFilter::new(dialect);
+        let mut clauses = Vec::new();
+        let ids = self.category.event_ids();
+        if !ids.is_empty() {
+            clauses.push(format!(
+                "events.event_id IN ({})",
+                ids.iter()
+                    .map(|id| id.value().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if let Some(condition) = &self.condition {
+            let (sql, exact) = condition.to_sql(&mut filter);
+            clauses.push(sql);
+            filter.exact = exact;
+        }
+        filter.sql = if clauses.is_empty() {
+            "TRUE".to_string()
+        } else {
+            clauses.join(" AND ")
+        };
+        filter
+    }
+}
+
+impl FromStr for EventQuery {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EventHit {
+    pub run: StoredRun,
+    pub event: SysmonEvent,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    String(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') => value.push(chars.next().context("Unterminated string")?),
+                    Some(q) if q == c => break,
+                    Some(other) => value.push(other),
+                    None => bail!("Unterminated string in query"),
+                }
+            }
+            tokens.push(Token::String(value));
+        } else if c.is_alphanumeric() || c == '_' || c == '-' {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                    word.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Word(word));
+        } else {
+            chars.next();
+            let next = chars.peek().copied();
+            let symbol = match (c, next) {
+                ('=', Some('=')) | ('!', Some('=')) | ('<', Some('=')) | ('>', Some('=')) => {
+                    chars.next();
+                    match c {
+                        '=' => "==",
+                        '!' => "!=",
+                        '<' => "<=",
+                        _ => ">=",
+                    }
+                }
+                ('<', _) => "<",
+                ('>', _) => ">",
+                ('(', _) => "(",
+                (')', _) => ")",
+                (',', _) => ",",
+                _ => bail!("Unexpected character in query: {}", c),
+            };
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+struct QueryParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl QueryParser {
+    fn new(query: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(query)?,
+            position: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn symbol(&mut self, symbol: &str) -> Result<()> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            other => Err(anyhow!("Expected {} in query, found {:?}", symbol, other)),
+        }
+    }
+
+    fn parse(mut self) -> Result<EventQuery> {
+        let category = match self.next() {
+            Some(Token::Word(name)) => Category::from_name(&name)
+                .with_context(|| format!("Unknown event category: {}", name))?,
+            other => bail!("Expected an event category, found {:?}", other),
+        };
+        let condition = if self.keyword("where") {
+            self.next();
+            Some(self.parse_or()?)
+        } else {
+            None
+        };
+        match self.peek() {
+            None => Ok(EventQuery {
+                category,
+                condition,
+            }),
+            Some(t) => Err(anyhow!("Unexpected token in query: {:?}", t)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.keyword("or") {
+            self.next();
+            left = Expr::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut left = self.parse_not()?;
+        while self.keyword("and") {
+            self.next();
+            left = Expr::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.keyword("not") {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn value(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::String(value)) | Some(Token::Word(value)) => Ok(value),
+            other => Err(anyhow!("Expected a value in query, found {:?}", other)),
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr> {
+        let field = match self.next() {
+            Some(Token::Symbol("(")) => {
+                let expr = self.parse_or()?;
+                self.symbol(")")?;
+                return Ok(expr);
+            }
+            Some(Token::Word(field)) => Field::from_name(&field),
+            other => bail!("Expected a field name in query, found {:?}", other),
+        };
+        let operator = match self.next() {
+            Some(Token::Symbol("==")) => Operator::Eq,
+            Some(Token::Symbol("!=")) => Operator::Ne,
+            Some(Token::Symbol("<")) => Operator::Lt,
+            Some(Token::Symbol("<=")) => Operator::Le,
+            Some(Token::Symbol(">")) => Operator::Gt,
+            Some(Token::Symbol(">=")) => Operator::Ge,
+            Some(Token::Word(w)) => match w.to_lowercase().as_str() {
+                "contains" => Operator::Contains,
+                "startswith" => Operator::StartsWith,
+                "endswith" => Operator::EndsWith,
+                "in" => Operator::In,
+                _ => bail!("Unknown operator in query: {}", w),
+            },
+            other => bail!("Expected an operator in query, found {:?}", other),
+        };
+        let values = if operator == Operator::In {
+            self.symbol("(")?;
+            let mut values = vec![self.value()?];
+            while matches!(self.peek(), Some(Token::Symbol(","))) {
+                self.next();
+                values.push(self.value()?);
+            }
+            self.symbol(")")?;
+            values
+        } else {
+            vec![self.value()?]
+        };
+        Ok(Expr::Compare {
+            field,
+            operator,
+            values,
+        })
+    }
+}
@@ -0,0 +1,320 @@
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, Row, ToSql};
+
+use super::search::{Dialect, EventHit, EventQuery};
+use super::{ResultStore, RunQuery, StoredRun};
+use crate::metadata::SampleMetadata;
+use crate::report::SandboxReport;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE runs (
+    execution_id TEXT PRIMARY KEY,
+    analysis_id TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    time TEXT NOT NULL,
+    score REAL NOT NULL
+);
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY,
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    time TEXT NOT NULL,
+    event_id INTEGER NOT NULL,
+    computer TEXT,
+    record_id INTEGER,
+    event_data TEXT NOT NULL
+);
+CREATE TABLE artifacts (
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    md5 TEXT NOT NULL,
+    sha1 TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    ssdeep TEXT NOT NULL
+);
+CREATE TABLE detections (
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    source TEXT NOT NULL,
+    name TEXT NOT NULL,
+    level TEXT,
+    tags TEXT NOT NULL,
+    events INTEGER NOT NULL
+);
+CREATE TABLE iocs (
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    confidence TEXT NOT NULL,
+    first_seen TEXT NOT NULL,
+    occurrences INTEGER NOT NULL
+);
+CREATE INDEX runs_hash ON runs(hash);
+CREATE INDEX events_run ON events(run);
+CREATE INDEX artifacts_run ON artifacts(run);
+CREATE INDEX artifacts_sha256 ON artifacts(sha256);
+CREATE INDEX detections_run ON detections(run);
+CREATE INDEX iocs_run ON iocs(run);
+CREATE INDEX iocs_kind_value ON iocs(kind, value);
+",
+    "
+CREATE TABLE facets (
+    run TEXT NOT NULL REFERENCES runs(execution_id) ON DELETE CASCADE,
+    facet TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE INDEX facets_run ON facets(run);
+CREATE INDEX facets_facet_value ON facets(facet, value);
+",
+];
+
+const RUN_COLUMNS: &str = "runs.execution_id, runs.analysis_id, runs.hash, runs.time, runs.score";
+const EVENT_COLUMNS: &str =
+    "events.time, events.event_id, events.computer, events.record_id, events.event_data";
+
+fn conversion_error(
+    index: usize,
+    e: impl std::error::Error + Send + Sync + 'static,
+) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e))
+}
+
+fn stored_run(row: &Row) -> rusqlite::Result<StoredRun> {
+    let time: String = row.get(3)?;
+    Ok(StoredRun {
+        execution_id: row.get(0)?,
+        analysis_id: row.get(1)?,
+        hash: row.get(2)?,
+        time: time.parse().map_err(|e| conversion_error(3, e))?,
+        score: row.get(4)?,
+    })
+}
+
+fn event_hit(row: &Row) -> rusqlite::Result<EventHit> {
+    let time: String = row.get(5)?;
+    let event_id: u8 = row.get(6)?;
+    let record_id: Option<i64> = row.get(8)?;
+    let event_data: String = row.get(9)?;
+    Ok(EventHit {
+        run: stored_run(row)?,
+        event: SysmonEvent {
+            event_id: SysmonEventId::new(event_id)
+                .ok_or(rusqlite::Error::IntegralValueOutOfRange(6, event_id.into()))?,
+            time_created: time.parse().map_err(|e| conversion_error(5, e))?,
+            computer: row.get(7)?,
+            record_id: record_id.map(|r| r as u64),
+            channel: None,
+            event_data: serde_json::from_str(&event_data).map_err(|e| conversion_error(9, e))?,
+        },
+    })
+}
+
+#[derive(Debug)]
+pub struct SqliteResultStore {
+    conn: Connection,
+}
+
+impl SqliteResultStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        migrate(&mut conn)?;
+        Ok(Self { conn })
+    }
+
+    fn query_runs(&self, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<StoredRun>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let runs = stmt
+            .query_map(params, stored_run)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(runs)
+    }
+}
+
+fn insert_facets(conn: &Connection, run: &str, metadata: &SampleMetadata) -> Result<()> {
+    let mut insert_facet =
+        conn.prepare("INSERT INTO facets (run, facet, value) VALUES (?1, ?2, ?3)")?;
+    for (facet, value) in metadata.facets() {
+        insert_facet.execute(params![run, facet.name(), value])?;
+    }
+    Ok(())
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", i + 1))?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+impl ResultStore for SqliteResultStore {
+    fn save_report(&mut self, report: &SandboxReport) -> Result<()> {
+        let run = StoredRun::from_report(report);
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "DELETE FROM runs WHERE execution_id = ?1",
+            params![run.execution_id],
+        )?;
+        tx.execute(
+            "INSERT INTO runs (execution_id, analysis_id, hash, time, score)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                run.execution_id,
+                run.analysis_id,
+                run.hash,
+                run.time.to_rfc3339(),
+                run.score,
+            ],
+        )?;
+        {
+            let mut insert_event = tx.prepare(
+                "INSERT INTO events (run, time, event_id, computer, record_id, event_data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for event in &report.events {
+                insert_event.execute(params![
+                    run.execution_id,
+                    event.time_created.to_rfc3339(),
+                    event.event_id.value(),
+                    event.computer,
+                    event.record_id,
+                    serde_json::to_string(&event.event_data)?,
+                ])?;
+            }
+
+            let mut insert_artifact = tx.prepare(
+                "INSERT INTO artifacts (run, path, size, md5, sha1, sha256, ssdeep)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for artifact in &report.artifacts {
+                insert_artifact.execute(params![
+                    run.execution_id,
+                    artifact.path,
+                    artifact.size,
+                    artifact.hashes.md5,
+                    artifact.hashes.sha1,
+                    artifact.hashes.sha256,
+                    artifact.hashes.ssdeep,
+                ])?;
+            }
+
+            let mut insert_detection = tx.prepare(
+                "INSERT INTO detections (run, source, name, level, tags, events)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for detection in &report.detections {
+                insert_detection.execute(params![
+                    run.execution_id,
+                    detection.source,
+                    detection.name,
+                    detection.level,
+                    serde_json::to_string(&detection.tags)?,
+                    detection.events.len(),
+                ])?;
+            }
+
+            let mut insert_ioc = tx.prepare(
+                "INSERT INTO iocs (run, kind, value, confidence, first_seen, occurrences)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for indicator in report.iocs.iter() {
+                insert_ioc.execute(params![
+                    run.execution_id,
+                    indicator.ioc.kind(),
+                    indicator.ioc.value(),
+                    format!("{:?}", indicator.confidence),
+                    indicator.first_seen.to_rfc3339(),
+                    indicator.occurrences,
+                ])?;
+            }
+        }
+        insert_facets(&tx, &run.execution_id, &report.metadata)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_metadata(&mut self, execution_id: &str, metadata: &SampleMetadata) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM facets WHERE run = ?1", params![execution_id])?;
+        insert_facets(&tx, execution_id, metadata)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn purge_events(&mut self, execution_id: &str) -> Result<usize> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM events WHERE run = ?1", params![execution_id])?)
+    }
+
+    fn search_events(&mut self, query: &EventQuery, limit: usize) -> Result<Vec<EventHit>> {
+        let filter = query.to_sql(Dialect::Sqlite);
+        let mut sql = format!(
+            "SELECT {}, {} FROM events
+             JOIN runs ON runs.execution_id = events.run
+             WHERE {}
+             ORDER BY runs.time DESC, events.time",
+            RUN_COLUMNS, EVENT_COLUMNS, filter.sql
+        );
+        if filter.exact {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        let mut stmt = self.conn.prepare(&sql)?;
+        let params: Vec<&dyn ToSql> = filter.params.iter().map(|p| p as &dyn ToSql).collect();
+        let mut hits = Vec::new();
+        for hit in stmt.query_map(params.as_slice(), event_hit)? {
+            let hit = hit?;
+            if query.matches(&hit.event) {
+                hits.push(hit);
+                if hits.len() >= limit {
+                    break;
+                }
+            }
+        }
+        Ok(hits)
+    }
+
+    fn find_runs(&mut self, query: &RunQuery) -> Result<Vec<StoredRun>> {
+        match query {
+            RunQuery::Hash(hash) => self.query_runs(
+                &format!(
+                    "SELECT DISTINCT {} FROM runs
+                     LEFT JOIN artifacts ON artifacts.run = runs.execution_id
+                     WHERE runs.hash = ?1 OR artifacts.md5 = ?1
+                         OR artifacts.sha1 = ?1 OR artifacts.sha256 = ?1
+                     ORDER BY runs.time DESC",
+                    RUN_COLUMNS
+                ),
+                params![hash.to_lowercase()],
+            ),
+            RunQuery::Ioc(ioc) => self.query_runs(
+                &format!(
+                    "SELECT DISTINCT {} FROM runs
+                     JOIN iocs ON iocs.run = runs.execution_id
+                     WHERE iocs.kind = ?1 AND iocs.value = ?2
+                     ORDER BY runs.time DESC",
+                    RUN_COLUMNS
+                ),
+                params![ioc.kind(), ioc.value()],
+            ),
+            RunQuery::Facet(facet, value) => self.query_runs(
+                &format!(
+                    "SELECT DISTINCT {} FROM runs
+                     JOIN facets ON facets.run = runs.execution_id
+                     WHERE facets.facet = ?1 AND facets.value = ?2
+                     ORDER BY runs.time DESC",
+                    RUN_COLUMNS
+                ),
+                params![facet.name(), value],
+            ),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::event_data::TypedEventData;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+const KNOWN_PIPES: &[(&str, &str)] = &[
+    ("Cobalt Strike", r"^MSSE-\d{1,5}-server$"),
+    ("Cobalt Strike", r"^msagent_[0-9a-f]{2,4}$"),
+    ("Cobalt Strike", r"^postex_(?:ssh_)?[0-9a-f]{4}$"),
+    ("Cobalt Strike", r"^status_[0-9a-f]{2}$"),
+    (
+        "Cobalt Strike",
+        r"^mojo\.5688\.8052\.(?:183894939787088877|35780273329370473)[0-9a-f]{2}$",
+    ),
+    ("Cobalt Strike", r"^wkssvc_?[0-9a-f]{2}$"),
+    ("Cobalt Strike", r"^ntsvcs[0-9a-f]{2}$"),
+    ("Cobalt Strike", r"^DserNamePipe[0-9a-f]{2}$"),
+    ("Cobalt Strike", r"^SearchTextHarvester[0-9a-f]{2}$"),
+    (
+        "Cobalt Strike",
+        r"^(?:scerpc|tsvcpipe-|winsock)[0-9a-f-]{2,}$",
+    ),
+    ("Covenant", r"^gruntsvc$"),
+    ("PsExec", r"^(?:PSEXESVC|RemCom_communicaton|csexecsvc)"),
+    (
+        "Meterpreter",
+        r"^(?:isapi_http|isapi_dg2?|sdlrpc|ahexec|winsession)$",
+    ),
+    (
+        "Credential dumping",
+        r"^(?:lsadump|cachedump|wceservicepipe|lsassw)$",
+    ),
+    ("Turla", r"^(?:comnap|atctl|userpipe|iehelper)$"),
+    (
+        "Equation Group",
+        r"^(?:46a676ab7f179e511e30dd2dc41bd388|9f81f59bc58452127884ce513865ed20|e710f28d59aa529d6792ca6ff0ca1b34)$",
+    ),
+    ("PoshC2", r"^jaccdpqnvbrrxlaf$"),
+];
+
+const KNOWN_MUTEXES: &[(&str, &str)] = &[
+    ("WannaCry", r"^MsWinZonesCacheCounterMutexA"),
+    ("DarkComet", r"^DC_MUTEX-"),
+    ("Remcos", r"^Remcos(?:_Mutex_Inj|-[0-9A-Z]{6})$"),
+    ("AsyncRAT", r"^AsyncMutex_"),
+    ("Quasar", r"^QSR_MUTEX_"),
+    ("Poison Ivy", r"^\)!VoqA\.I4$"),
+    ("Zeus", r"^_AVIRA_21\d{2,3}$"),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ObservedMutex {
+    pub time: DateTime<Utc>,
+    pub process_id: u32,
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncObjectKind {
+    Pipe,
+    Mutex,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProcessSyncObjects {
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub pipes_created: Vec<String>,
+    pub pipes_connected: Vec<String>,
+    pub mutexes: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SyncObjectMatch {
+    pub kind: SyncObjectKind,
+    pub name: String,
+    pub family: &'static str,
+    pub process_id: Option<u32>,
+    pub image: String,
+    pub time: DateTime<FixedOffset>,
+    pub events: Vec<SysmonEvent>,
+}
+
+fn known_objects(kind: SyncObjectKind) -> &'static [(&'static str, Regex)] {
+    static PIPES: OnceLock<Vec<(&str, Regex)>> = OnceLock::new();
+    static MUTEXES: OnceLock<Vec<(&str, Regex)>> = OnceLock::new();
+    let (cell, patterns) = match kind {
+        SyncObjectKind::Pipe => (&PIPES, KNOWN_PIPES),
+        SyncObjectKind::Mutex => (&MUTEXES, KNOWN_MUTEXES),
+    };
+    cell.get_or_init(|| {
+        patterns
+            .iter()
+            .map(|(family, pattern)| {
+                let regex = Regex::new(&format!("(?i){}", pattern)).expect("valid regex");
+                (*family, regex)
+            })
+            .collect()
+    })
+}
+
+pub fn object_name(name: &str) -> &str {
+    name.rsplit('\\').next().unwrap_or(name)
+}
+
+pub fn known_family(kind: SyncObjectKind, name: &str) -> Option<&'static str> {
+    let name = object_name(name.trim());
+    known_objects(kind)
+        .iter()
+        .find(|(_, regex)| regex.is_match(name))
+        .map(|(family, _)| *family)
+}
+
+pub fn parse_handle_output(output: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let Some((_, rest)) = line.split_once(": Mutant") else {
+            continue;
+        };
+        let name = rest.trim();
+        if !name.is_empty() && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+fn image_of(events: &[SysmonEvent], process_id: u32) -> String {
+    events
+        .iter()
+        .filter(|e| e.event_id == SysmonEventId::PROCESS_CREATE)
+        .filter(|e| e.event_data.get("ProcessId") == Some(&process_id.to_string()))
+        .filter_map(|e| e.event_data.get("Image"))
+        .next_back()
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn entry<'a>(
+    summary: &'a mut Vec<ProcessSyncObjects>,
+    process_id: Option<u32>,
+    image: &str,
+) -> &'a mut ProcessSyncObjects {
+    let index = match summary
+        .iter()
+        .position(|p| p.process_id == process_id && p.image == image)
+    {
+        Some(index) => index,
+        None => {
+            summary.push(ProcessSyncObjects {
+                process_id,
+                image: image.to_string(),
+                ..Default::default()
+            });
+            summary.len() - 1
+        }
+    };
+    &mut summary[index]
+}
+
+fn push_unique(names: &mut Vec<String>, name: &str) {
+    if !names.iter().any(|n| n == name) {
+        names.push(name.to_string());
+    }
+}
+
+pub fn summarize(events: &[SysmonEvent], mutexes: &[ObservedMutex]) -> Vec<ProcessSyncObjects> {
+    let mut summary = Vec::new();
+    for event in events {
+        let TypedEventData::PipeEvent(d) = event.typed_data() else {
+            continue;
+        };
+        let process = entry(&mut summary, d.process_id, &d.image);
+        match event.event_id {
+            SysmonEventId::PIPE_EVENT_CREATE => {
+                push_unique(&mut process.pipes_created, &d.pipe_name)
+            }
+            _ => push_unique(&mut process.pipes_connected, &d.pipe_name),
+        }
+    }
+    for mutex in mutexes {
+        let image = image_of(events, mutex.process_id);
+        let process = entry(&mut summary, Some(mutex.process_id), &image);
+        push_unique(&mut process.mutexes, &mutex.name);
+    }
+    summary
+}
+
+pub fn known_objects_in(events: &[SysmonEvent], mutexes: &[ObservedMutex]) -> Vec<SyncObjectMatch> {
+    let mut matches: Vec<SyncObjectMatch> = Vec::new();
+    for event in events {
+        let TypedEventData::PipeEvent(d) = event.typed_data() else {
+            continue;
+        };
+        let Some(family) = known_family(SyncObjectKind::Pipe, &d.pipe_name) else {
+            continue;
+        };
+        let existing = matches
+            .iter_mut()
+            .find(|m| m.kind == SyncObjectKind::Pipe && m.name == d.pipe_name);
+        match existing {
+            Some(m) => m.events.push(event.clone()),
+            None => matches.push(SyncObjectMatch {
+                kind: SyncObjectKind::Pipe,
+                name: d.pipe_name,
+                family,
+                process_id: d.process_id,
+                image: d.image,
+                time: event.time_created,
+                events: vec![event.clone()],
+            }),
+        }
+    }
+    for mutex in mutexes {
+        let Some(family) = known_family(SyncObjectKind::Mutex, &mutex.name) else {
+            continue;
+        };
+        if matches
+            .iter()
+            .any(|m| m.kind == SyncObjectKind::Mutex && m.name == mutex.name)
+        {
+            continue;
+        }
+        matches.push(SyncObjectMatch {
+            kind: SyncObjectKind::Mutex,
+            name: mutex.name.clone(),
+            family,
+            process_id: Some(mutex.process_id),
+            image: image_of(events, mutex.process_id),
+            time: mutex.time.into(),
+            events: Vec::new(),
+        });
+    }
+    matches
+}
@@ -0,0 +1,107 @@
+use std::io::BufRead;
+
+use anyhow::Result;
+use log::warn;
+use regex::Regex;
+
+use crate::sysmon_event::{ParseOptions, SysmonEvent};
+
+const SYSLOG_PATTERN: &str = r"[A-Z][a-z]{2}\s+\d+\s\d\d:\d\d:\d\d\s\S+\ssysmon\S*:\s(.+)";
+
+type EventFilter<'a> = Box<dyn FnMut(&SysmonEvent) -> bool + 'a>;
+
+struct Progress<'a> {
+    every: usize,
+    callback: Box<dyn FnMut(usize, u64) + 'a>,
+}
+
+pub struct SyslogReader<'a, R> {
+    reader: R,
+    re: Regex,
+    line: String,
+    options: ParseOptions,
+    parsed: usize,
+    bytes: u64,
+    progress: Option<Progress<'a>>,
+    filter: Option<EventFilter<'a>>,
+}
+
+impl<'a, R: BufRead> SyslogReader<'a, R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            re: Regex::new(SYSLOG_PATTERN).unwrap(),
+            line: String::new(),
+            options: ParseOptions::default(),
+            parsed: 0,
+            bytes: 0,
+            progress: None,
+            filter: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_progress<F: FnMut(usize, u64) + 'a>(mut self, every: usize, callback: F) -> Self {
+        self.progress = Some(Progress {
+            every: every.max(1),
+            callback: Box::new(callback),
+        });
+        self
+    }
+
+    pub fn filter_parsed<F: FnMut(&SysmonEvent) -> bool + 'a>(mut self, pred: F) -> Self {
+        self.filter = Some(Box::new(pred));
+        self
+    }
+
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    fn report_progress(&mut self) {
+        if let Some(progress) = &mut self.progress {
+            if self.parsed.is_multiple_of(progress.every) {
+                (progress.callback)(self.parsed, self.bytes);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for SyslogReader<'_, R> {
+    type Item = Result<SysmonEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(n) => self.bytes += n as u64,
+                Err(e) => return Some(Err(e.into())),
+            }
+
+            let parsed = self
+                .re
+                .captures(self.line.trim_end())
+                .and_then(|c| SysmonEvent::from_xml_with(&c[1], &self.options).ok());
+            if let Some(parsed) = parsed {
+                for warning in &parsed.warnings {
+                    warn!("{}: {}", parsed.event.dedup_key(), warning);
+                }
+                let event = parsed.event;
+                self.parsed += 1;
+                self.report_progress();
+                if self.filter.as_mut().is_none_or(|f| f(&event)) {
+                    return Some(Ok(event));
+                }
+            }
+        }
+    }
+}
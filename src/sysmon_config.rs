@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+use crate::report::{escape, SandboxReport};
+use crate::sysmon_event::{SysmonEvent, SysmonEventId};
+
+pub const SCHEMA_VERSION: &str = "4.81";
+
+const ELEMENTS: &[(&str, Option<&str>)] = &[
+    ("ProcessCreate", Some("Image")),
+    ("FileCreateTime", Some("Image")),
+    ("NetworkConnect", Some("Image")),
+    ("ProcessTerminate", Some("Image")),
+    ("DriverLoad", Some("ImageLoaded")),
+    ("ImageLoad", Some("ImageLoaded")),
+    ("CreateRemoteThread", Some("SourceImage")),
+    ("RawAccessRead", Some("Image")),
+    ("ProcessAccess", Some("SourceImage")),
+    ("FileCreate", Some("Image")),
+    ("RegistryEvent", Some("Image")),
+    ("FileCreateStreamHash", Some("Image")),
+    ("PipeEvent", Some("Image")),
+    ("WmiEvent", None),
+    ("DnsQuery", Some("QueryName")),
+    ("FileDelete", Some("Image")),
+    ("ClipboardChange", Some("Image")),
+    ("ProcessTampering", Some("Image")),
+    ("FileDeleteDetected", Some("Image")),
+];
+
+const PROCESS_FIELDS: &[&str] = &["ProcessGuid", "SourceProcessGuid"];
+
+fn element(id: SysmonEventId) -> Option<&'static str> {
+    let name = match id {
+        SysmonEventId::PROCESS_CREATE => "ProcessCreate",
+        SysmonEventId::FILE_CREATE_TIME => "FileCreateTime",
+        SysmonEventId::NETWORK_CONNECT => "NetworkConnect",
+        SysmonEventId::PROCESS_TERMINATE => "ProcessTerminate",
+        SysmonEventId::DRIVER_LOAD => "DriverLoad",
+        SysmonEventId::IMAGE_LOAD => "ImageLoad",
+        SysmonEventId::CREATE_REMOTE_THREAD => "CreateRemoteThread",
+        SysmonEventId::RAW_ACCESS_READ => "RawAccessRead",
+        SysmonEventId::PROCESS_ACCESS => "ProcessAccess",
+        SysmonEventId::FILE_CREATE => "FileCreate",
+        SysmonEventId::REGISTRY_EVENT_ADD_DELETE
+        | SysmonEventId::REGISTRY_EVENT_SET
+        | SysmonEventId::REGISTRY_EVENT_RENAME => "RegistryEvent",
+        SysmonEventId::FILE_CREATE_STREAM_HASH => "FileCreateStreamHash",
+        SysmonEventId::PIPE_EVENT_CREATE | SysmonEventId::PIPE_EVENT_CONNECT => "PipeEvent",
+        SysmonEventId::WMI_EVENT_FILTER
+        | SysmonEventId::WMI_EVENT_CONSUMER
+        | SysmonEventId::WMI_EVENT_CONSUMER_FILTER => "WmiEvent",
+        SysmonEventId::DNS_QUERY => "DnsQuery",
+        SysmonEventId::FILE_DELETE => "FileDelete",
+        SysmonEventId::CLIPBOARD_CHANGE => "ClipboardChange",
+        SysmonEventId::PROCESS_TAMPERING => "ProcessTampering",
+        SysmonEventId::FILE_DELETE_DETECTED => "FileDeleteDetected",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn key_field(element: &str) -> Option<&'static str> {
+    ELEMENTS
+        .iter()
+        .find(|(name, _)| *name == element)
+        .and_then(|(_, field)| *field)
+}
+
+fn default_include_ratio() -> f64 {
+    100.0
+}
+
+fn default_max_include_values() -> usize {
+    20
+}
+
+fn default_min_noise() -> usize {
+    1
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RecommendOptions {
+    #[serde(default = "default_include_ratio")]
+    pub include_ratio: f64,
+    #[serde(default = "default_max_include_values")]
+    pub max_include_values: usize,
+    #[serde(default = "default_min_noise")]
+    pub min_noise: usize,
+}
+
+impl Default for RecommendOptions {
+    fn default() -> Self {
+        Self {
+            include_ratio: default_include_ratio(),
+            max_include_values: default_max_include_values(),
+            min_noise: default_min_noise(),
+        }
+    }
+}
+
+impl RecommendOptions {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleMode {
+    Include,
+    Exclude,
+}
+
+impl RuleMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Include => "include",
+            Self::Exclude => "exclude",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RuleSet {
+    pub element: String,
+    pub mode: RuleMode,
+    pub field: Option<String>,
+    pub values: Vec<String>,
+    pub malicious: usize,
+    pub benign: usize,
+    pub dropped: usize,
+}
+
+#[derive(Default)]
+struct Observed {
+    malicious: usize,
+    benign: usize,
+    unkeyed_malicious: bool,
+    malicious_values: BTreeMap<String, String>,
+    benign_values: BTreeMap<String, (String, usize)>,
+}
+
+type EventKey = (Option<u64>, DateTime<FixedOffset>);
+
+fn event_key(event: &SysmonEvent) -> EventKey {
+    (event.record_id, event.time_created)
+}
+
+fn malicious_events(report: &SandboxReport) -> (HashSet<EventKey>, HashSet<String>) {
+    let detected: HashSet<EventKey> = report
+        .detections
+        .iter()
+        .flat_map(|d| d.events.iter().map(event_key))
+        .collect();
+    let flagged: HashSet<String> = report
+        .detections
+        .iter()
+        .flat_map(|d| &d.events)
+        .flat_map(|e| PROCESS_FIELDS.iter().filter_map(|f| e.event_data.get(*f)))
+        .map(|g| g.to_lowercase())
+        .collect();
+    let processes: HashSet<String> = report
+        .process_tree
+        .processes()
+        .filter(|p| {
+            report
+                .process_tree
+                .ancestors(&p.guid)
+                .iter()
+                .any(|a| flagged.contains(&a.guid.to_lowercase()))
+        })
+        .map(|p| p.guid.to_lowercase())
+        .chain(flagged.iter().cloned())
+        .collect();
+    (detected, processes)
+}
+
+fn is_malicious(
+    event: &SysmonEvent,
+    detected: &HashSet<EventKey>,
+    processes: &HashSet<String>,
+) -> bool {
+    detected.contains(&event_key(event))
+        || PROCESS_FIELDS
+            .iter()
+            .filter_map(|f| event.event_data.get(*f))
+            .any(|g| processes.contains(&g.to_lowercase()))
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SysmonConfig {
+    pub runs: usize,
+    pub rules: Vec<RuleSet>,
+}
+
+impl SysmonConfig {
+    pub fn from_reports(reports: &[SandboxReport], options: &RecommendOptions) -> Self {
+        let mut observed: BTreeMap<&'static str, Observed> = BTreeMap::new();
+        for report in reports {
+            let (detected, processes) = malicious_events(report);
+            for event in &report.events {
+                let Some(element) = element(event.event_id) else {
+                    continue;
+                };
+                let entry = observed.entry(element).or_default();
+                let value = key_field(element).and_then(|f| event.event_data.get(f));
+                if is_malicious(event, &detected, &processes) {
+                    entry.malicious += 1;
+                    match value {
+                        Some(value) => {
+                            entry
+                                .malicious_values
+                                .entry(value.to_lowercase())
+                                .or_insert_with(|| value.clone());
+                        }
+                        None => entry.unkeyed_malicious = true,
+                    }
+                } else {
+                    entry.benign += 1;
+                    if let Some(value) = value {
+                        entry
+                            .benign_values
+                            .entry(value.to_lowercase())
+                            .or_insert_with(|| (value.clone(), 0))
+                            .1 += 1;
+                    }
+                }
+            }
+        }
+
+        let rules = ELEMENTS
+            .iter()
+            .map(|(element, field)| {
+                let observed = observed.remove(element).unwrap_or_default();
+                Self::rule_set(element, *field, observed, options)
+            })
+            .collect();
+        Self {
+            runs: reports.len(),
+            rules,
+        }
+    }
+
+    fn rule_set(
+        element: &str,
+        field: Option<&str>,
+        observed: Observed,
+        options: &RecommendOptions,
+    ) -> RuleSet {
+        let mut rules = RuleSet {
+            element: element.to_string(),
+            mode: RuleMode::Exclude,
+            field: field.map(str::to_string),
+            values: Vec::new(),
+            malicious: observed.malicious,
+            benign: observed.benign,
+            dropped: 0,
+        };
+        if field.is_none() {
+            return rules;
+        }
+
+        let ratio = observed.benign as f64 / observed.malicious.max(1) as f64;
+        if observed.malicious > 0
+            && !observed.unkeyed_malicious
+            && ratio >= options.include_ratio
+            && observed.malicious_values.len() <= options.max_include_values
+        {
+            rules.mode = RuleMode::Include;
+            rules.dropped = observed
+                .benign_values
+                .iter()
+                .filter(|(key, _)| !observed.malicious_values.contains_key(*key))
+                .map(|(_, (_, count))| count)
+                .sum();
+            rules.values = observed.malicious_values.into_values().collect();
+            return rules;
+        }
+
+        let excluded: BTreeSet<&String> = observed
+            .benign_values
+            .iter()
+            .filter(|(key, (_, count))| {
+                *count >= options.min_noise && !observed.malicious_values.contains_key(*key)
+            })
+            .map(|(key, _)| key)
+            .collect();
+        rules.dropped = excluded
+            .iter()
+            .map(|key| observed.benign_values[*key].1)
+            .sum();
+        rules.values = excluded
+            .into_iter()
+            .map(|key| observed.benign_values[key].0.clone())
+            .collect();
+        rules
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::new();
+        let _ = self.write_xml(&mut xml);
+        xml
+    }
+
+    fn write_xml(&self, xml: &mut String) -> std::fmt::Result {
+        writeln!(xml, r#"<Sysmon schemaversion="{}">"#, SCHEMA_VERSION)?;
+        writeln!(xml, "    <HashAlgorithms>*</HashAlgorithms>")?;
+        writeln!(
+            xml,
+            "    <!-- Generated from {} analyzed runs -->",
+            self.runs
+        )?;
+        writeln!(xml, "    <EventFiltering>")?;
+        for rules in &self.rules {
+            let (Some(field), false) = (&rules.field, rules.values.is_empty()) else {
+                writeln!(xml, r#"        <{} onmatch="exclude" />"#, rules.element)?;
+                continue;
+            };
+            writeln!(
+                xml,
+                "        <!-- {}: {} malicious, {} benign events observed, {} benign events dropped -->",
+                rules.element, rules.malicious, rules.benign, rules.dropped
+            )?;
+            writeln!(
+                xml,
+                r#"        <RuleGroup name="{}" groupRelation="or">"#,
+                rules.element
+            )?;
+            writeln!(
+                xml,
+                r#"            <{} onmatch="{}">"#,
+                rules.element,
+                rules.mode.name()
+            )?;
+            for value in &rules.values {
+                writeln!(
+                    xml,
+                    r#"                <{0} condition="is">{1}</{0}>"#,
+                    field,
+                    escape(value)
+                )?;
+            }
+            writeln!(xml, "            </{}>", rules.element)?;
+            writeln!(xml, "        </RuleGroup>")?;
+        }
+        writeln!(xml, "    </EventFiltering>")?;
+        writeln!(xml, "</Sysmon>")
+    }
+}
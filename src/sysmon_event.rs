@@ -2,18 +2,22 @@ use std::fmt;
 use std::num::NonZeroU8;
 use std::{collections::HashMap, str::FromStr};
 
-use anyhow::{Context, Error, Result};
+use anyhow::{anyhow, Context, Error, Result};
 use chrono::{DateTime, FixedOffset};
-use roxmltree::Document;
-use serde::{Deserialize, Serialize};
+use log::warn;
+use roxmltree::{Document, Node};
+use serde::{de, Deserialize, Deserializer, Serialize};
 
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone)]
+use crate::encoding::harden_event;
+
+#[derive(PartialEq, Eq, Serialize, Clone, Copy)]
 pub struct SysmonEventId(NonZeroU8);
 
 impl SysmonEventId {
     pub const PROCESS_CREATE: Self = Self::new_unchecked(1);
     pub const FILE_CREATE_TIME: Self = Self::new_unchecked(2);
     pub const NETWORK_CONNECT: Self = Self::new_unchecked(3);
+    pub const SERVICE_STATE_CHANGE: Self = Self::new_unchecked(4);
     pub const PROCESS_TERMINATE: Self = Self::new_unchecked(5);
     pub const DRIVER_LOAD: Self = Self::new_unchecked(6);
     pub const IMAGE_LOAD: Self = Self::new_unchecked(7);
@@ -25,6 +29,7 @@ impl SysmonEventId {
     pub const REGISTRY_EVENT_SET: Self = Self::new_unchecked(13);
     pub const REGISTRY_EVENT_RENAME: Self = Self::new_unchecked(14);
     pub const FILE_CREATE_STREAM_HASH: Self = Self::new_unchecked(15);
+    pub const CONFIG_STATE_CHANGE: Self = Self::new_unchecked(16);
     pub const PIPE_EVENT_CREATE: Self = Self::new_unchecked(17);
     pub const PIPE_EVENT_CONNECT: Self = Self::new_unchecked(18);
     pub const WMI_EVENT_FILTER: Self = Self::new_unchecked(19);
@@ -35,43 +40,377 @@ impl SysmonEventId {
     pub const CLIPBOARD_CHANGE: Self = Self::new_unchecked(24);
     pub const PROCESS_TAMPERING: Self = Self::new_unchecked(25);
     pub const FILE_DELETE_DETECTED: Self = Self::new_unchecked(26);
+    pub const FILE_BLOCK_EXECUTABLE: Self = Self::new_unchecked(27);
+    pub const FILE_BLOCK_SHREDDING: Self = Self::new_unchecked(28);
+    pub const FILE_EXECUTABLE_DETECTED: Self = Self::new_unchecked(29);
+    pub const ERROR: Self = Self::new_unchecked(255);
+
+    pub const ALL: &'static [Self] = &[
+        Self::PROCESS_CREATE,
+        Self::FILE_CREATE_TIME,
+        Self::NETWORK_CONNECT,
+        Self::SERVICE_STATE_CHANGE,
+        Self::PROCESS_TERMINATE,
+        Self::DRIVER_LOAD,
+        Self::IMAGE_LOAD,
+        Self::CREATE_REMOTE_THREAD,
+        Self::RAW_ACCESS_READ,
+        Self::PROCESS_ACCESS,
+        Self::FILE_CREATE,
+        Self::REGISTRY_EVENT_ADD_DELETE,
+        Self::REGISTRY_EVENT_SET,
+        Self::REGISTRY_EVENT_RENAME,
+        Self::FILE_CREATE_STREAM_HASH,
+        Self::CONFIG_STATE_CHANGE,
+        Self::PIPE_EVENT_CREATE,
+        Self::PIPE_EVENT_CONNECT,
+        Self::WMI_EVENT_FILTER,
+        Self::WMI_EVENT_CONSUMER,
+        Self::WMI_EVENT_CONSUMER_FILTER,
+        Self::DNS_QUERY,
+        Self::FILE_DELETE,
+        Self::CLIPBOARD_CHANGE,
+        Self::PROCESS_TAMPERING,
+        Self::FILE_DELETE_DETECTED,
+        Self::FILE_BLOCK_EXECUTABLE,
+        Self::FILE_BLOCK_SHREDDING,
+        Self::FILE_EXECUTABLE_DETECTED,
+        Self::ERROR,
+    ];
 
     const fn new_unchecked(n: u8) -> Self {
         Self(unsafe { NonZeroU8::new_unchecked(n) })
     }
+
+    pub fn new(n: u8) -> Option<Self> {
+        NonZeroU8::new(n).map(Self)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0.get()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::PROCESS_CREATE => "Process Create",
+            Self::FILE_CREATE_TIME => "File creation time changed",
+            Self::NETWORK_CONNECT => "Network connection detected",
+            Self::SERVICE_STATE_CHANGE => "Sysmon service state changed",
+            Self::PROCESS_TERMINATE => "Process terminated",
+            Self::DRIVER_LOAD => "Driver loaded",
+            Self::IMAGE_LOAD => "Image loaded",
+            Self::CREATE_REMOTE_THREAD => "CreateRemoteThread detected",
+            Self::RAW_ACCESS_READ => "RawAccessRead detected",
+            Self::PROCESS_ACCESS => "Process accessed",
+            Self::FILE_CREATE => "File created",
+            Self::REGISTRY_EVENT_ADD_DELETE => "Registry object added or deleted",
+            Self::REGISTRY_EVENT_SET => "Registry value set",
+            Self::REGISTRY_EVENT_RENAME => "Registry object renamed",
+            Self::FILE_CREATE_STREAM_HASH => "File stream created",
+            Self::CONFIG_STATE_CHANGE => "Sysmon config state changed",
+            Self::PIPE_EVENT_CREATE => "Pipe Created",
+            Self::PIPE_EVENT_CONNECT => "Pipe Connected",
+            Self::WMI_EVENT_FILTER => "WmiEventFilter activity detected",
+            Self::WMI_EVENT_CONSUMER => "WmiEventConsumer activity detected",
+            Self::WMI_EVENT_CONSUMER_FILTER => "WmiEventConsumerToFilter activity detected",
+            Self::DNS_QUERY => "Dns query",
+            Self::FILE_DELETE => "File Delete archived",
+            Self::CLIPBOARD_CHANGE => "Clipboard changed",
+            Self::PROCESS_TAMPERING => "Process Tampering",
+            Self::FILE_DELETE_DETECTED => "File Delete logged",
+            Self::FILE_BLOCK_EXECUTABLE => "File Block Executable",
+            Self::FILE_BLOCK_SHREDDING => "File Block Shredding",
+            Self::FILE_EXECUTABLE_DETECTED => "File Executable Detected",
+            Self::ERROR => "Error report",
+            _ => "Unknown event",
+        }
+    }
+
+    pub fn required_fields(&self) -> &'static [&'static str] {
+        match *self {
+            Self::PROCESS_CREATE => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "CommandLine",
+                "CurrentDirectory",
+                "User",
+                "LogonGuid",
+                "LogonId",
+                "TerminalSessionId",
+                "IntegrityLevel",
+                "Hashes",
+                "ParentProcessGuid",
+                "ParentProcessId",
+                "ParentImage",
+                "ParentCommandLine",
+            ],
+            Self::FILE_CREATE_TIME => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetFilename",
+                "CreationUtcTime",
+                "PreviousCreationUtcTime",
+            ],
+            Self::NETWORK_CONNECT => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "User",
+                "Protocol",
+                "Initiated",
+                "SourceIsIpv6",
+                "SourceIp",
+                "SourcePort",
+                "DestinationIsIpv6",
+                "DestinationIp",
+                "DestinationPort",
+            ],
+            Self::SERVICE_STATE_CHANGE => &["UtcTime", "State", "Version", "SchemaVersion"],
+            Self::PROCESS_TERMINATE => &["UtcTime", "ProcessGuid", "ProcessId", "Image"],
+            Self::DRIVER_LOAD => &[
+                "UtcTime",
+                "ImageLoaded",
+                "Hashes",
+                "Signed",
+                "Signature",
+                "SignatureStatus",
+            ],
+            Self::IMAGE_LOAD => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "ImageLoaded",
+                "Hashes",
+                "Signed",
+                "Signature",
+                "SignatureStatus",
+            ],
+            Self::CREATE_REMOTE_THREAD => &[
+                "UtcTime",
+                "SourceProcessGuid",
+                "SourceProcessId",
+                "SourceImage",
+                "TargetProcessGuid",
+                "TargetProcessId",
+                "TargetImage",
+                "NewThreadId",
+                "StartAddress",
+            ],
+            Self::RAW_ACCESS_READ => &["UtcTime", "ProcessGuid", "ProcessId", "Image", "Device"],
+            Self::PROCESS_ACCESS => &[
+                "UtcTime",
+                "SourceProcessGUID",
+                "SourceProcessId",
+                "SourceThreadId",
+                "SourceImage",
+                "TargetProcessGUID",
+                "TargetProcessId",
+                "TargetImage",
+                "GrantedAccess",
+                "CallTrace",
+            ],
+            Self::FILE_CREATE => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetFilename",
+                "CreationUtcTime",
+            ],
+            Self::REGISTRY_EVENT_ADD_DELETE => &[
+                "EventType",
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetObject",
+            ],
+            Self::REGISTRY_EVENT_SET => &[
+                "EventType",
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetObject",
+                "Details",
+            ],
+            Self::REGISTRY_EVENT_RENAME => &[
+                "EventType",
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetObject",
+                "NewName",
+            ],
+            Self::FILE_CREATE_STREAM_HASH => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetFilename",
+                "CreationUtcTime",
+                "Hash",
+            ],
+            Self::CONFIG_STATE_CHANGE => &["UtcTime", "Configuration", "ConfigurationFileHash"],
+            Self::PIPE_EVENT_CREATE | Self::PIPE_EVENT_CONNECT => &[
+                "EventType",
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "PipeName",
+                "Image",
+            ],
+            Self::WMI_EVENT_FILTER => &[
+                "EventType",
+                "UtcTime",
+                "Operation",
+                "User",
+                "EventNamespace",
+                "Name",
+                "Query",
+            ],
+            Self::WMI_EVENT_CONSUMER => &[
+                "EventType",
+                "UtcTime",
+                "Operation",
+                "User",
+                "Name",
+                "Type",
+                "Destination",
+            ],
+            Self::WMI_EVENT_CONSUMER_FILTER => &[
+                "EventType",
+                "UtcTime",
+                "Operation",
+                "User",
+                "Consumer",
+                "Filter",
+            ],
+            Self::DNS_QUERY => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "QueryName",
+                "QueryStatus",
+                "QueryResults",
+                "Image",
+            ],
+            Self::FILE_DELETE => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetFilename",
+                "Hashes",
+                "IsExecutable",
+                "Archived",
+            ],
+            Self::CLIPBOARD_CHANGE => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "Session",
+                "ClientInfo",
+                "Hashes",
+                "Archived",
+            ],
+            Self::PROCESS_TAMPERING => &["UtcTime", "ProcessGuid", "ProcessId", "Image", "Type"],
+            Self::FILE_DELETE_DETECTED => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetFilename",
+                "Hashes",
+                "IsExecutable",
+            ],
+            Self::FILE_BLOCK_EXECUTABLE | Self::FILE_EXECUTABLE_DETECTED => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetFilename",
+                "Hashes",
+            ],
+            Self::FILE_BLOCK_SHREDDING => &[
+                "UtcTime",
+                "ProcessGuid",
+                "ProcessId",
+                "Image",
+                "TargetFilename",
+                "Hashes",
+                "IsExecutable",
+            ],
+            Self::ERROR => &["UtcTime", "ID", "Description"],
+            _ => &[],
+        }
+    }
+
+    pub fn optional_fields(&self) -> &'static [&'static str] {
+        match *self {
+            Self::PROCESS_CREATE => &[
+                "RuleName",
+                "FileVersion",
+                "Description",
+                "Product",
+                "Company",
+                "OriginalFileName",
+                "ParentUser",
+            ],
+            Self::NETWORK_CONNECT => &[
+                "RuleName",
+                "SourceHostname",
+                "SourcePortName",
+                "DestinationHostname",
+                "DestinationPortName",
+            ],
+            Self::IMAGE_LOAD => &[
+                "RuleName",
+                "FileVersion",
+                "Description",
+                "Product",
+                "Company",
+                "OriginalFileName",
+                "User",
+            ],
+            Self::CREATE_REMOTE_THREAD => &[
+                "RuleName",
+                "StartModule",
+                "StartFunction",
+                "SourceUser",
+                "TargetUser",
+            ],
+            Self::PROCESS_ACCESS => &["RuleName", "SourceUser", "TargetUser"],
+            Self::FILE_CREATE_STREAM_HASH => &["RuleName", "Contents", "User"],
+            Self::DRIVER_LOAD
+            | Self::WMI_EVENT_FILTER
+            | Self::WMI_EVENT_CONSUMER
+            | Self::WMI_EVENT_CONSUMER_FILTER => &["RuleName"],
+            Self::SERVICE_STATE_CHANGE | Self::CONFIG_STATE_CHANGE | Self::ERROR => &[],
+            _ if Self::ALL.contains(self) => &["RuleName", "User"],
+            _ => &[],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|id| id.name().eq_ignore_ascii_case(name.trim()))
+            .cloned()
+    }
 }
 
 impl fmt::Debug for SysmonEventId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let event_name = match self {
-            &Self::PROCESS_CREATE => "Process Create",
-            &Self::FILE_CREATE_TIME => "File creation time changed",
-            &Self::NETWORK_CONNECT => "Network connection detected",
-            &Self::PROCESS_TERMINATE => "Process terminated",
-            &Self::DRIVER_LOAD => "Driver loaded",
-            &Self::IMAGE_LOAD => "Image loaded",
-            &Self::CREATE_REMOTE_THREAD => "CreateRemoteThread detected",
-            &Self::RAW_ACCESS_READ => "RawAccessRead detected",
-            &Self::PROCESS_ACCESS => "Process accessed",
-            &Self::FILE_CREATE => "File created",
-            &Self::REGISTRY_EVENT_ADD_DELETE => "Registry object added or deleted",
-            &Self::REGISTRY_EVENT_SET => "Registry value set",
-            &Self::REGISTRY_EVENT_RENAME => "Registry object renamed",
-            &Self::FILE_CREATE_STREAM_HASH => "File stream created",
-            &Self::PIPE_EVENT_CREATE => "Pipe Created",
-            &Self::PIPE_EVENT_CONNECT => "Pipe Connected",
-            &Self::WMI_EVENT_FILTER => "WmiEventFilter activity detected",
-            &Self::WMI_EVENT_CONSUMER => "WmiEventConsumer activity detected",
-            &Self::WMI_EVENT_CONSUMER_FILTER => "WmiEventConsumerToFilter activity detected",
-            &Self::DNS_QUERY => "Dns query",
-            &Self::FILE_DELETE => "File Delete archived",
-            &Self::CLIPBOARD_CHANGE => "Clipboard changed",
-            &Self::PROCESS_TAMPERING => "Process Tampering",
-            &Self::FILE_DELETE_DETECTED => "File Delete logged",
-            _ => "Unknown event",
-        };
-
-        write!(f, "{} {}", u8::from(self.0), event_name)
+        write!(f, "{} {}", u8::from(self.0), self.name())
     }
 }
 
@@ -85,33 +424,56 @@ impl FromStr for SysmonEventId {
     }
 }
 
+impl<'de> Deserialize<'de> for SysmonEventId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(u8),
+            Text(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(n) => NonZeroU8::new(n)
+                .map(SysmonEventId)
+                .ok_or_else(|| de::Error::custom("Invalid EventID")),
+            Repr::Text(s) => {
+                let leading = s.split_whitespace().next().unwrap_or_default();
+                leading
+                    .parse::<Self>()
+                    .ok()
+                    .or_else(|| Self::from_name(&s))
+                    .ok_or_else(|| de::Error::custom(format!("Invalid EventID: {}", s)))
+            }
+        }
+    }
+}
+
+pub const ENRICHED_PREFIX: &str = "_enriched.";
+pub const UTC_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.tag_name().name() == name)
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct SysmonEvent {
+pub struct EventHeader {
     pub event_id: SysmonEventId,
     pub time_created: DateTime<FixedOffset>,
-    pub event_data: HashMap<String, String>,
+    pub computer: Option<String>,
+    pub record_id: Option<u64>,
+    pub channel: Option<String>,
 }
 
-impl SysmonEvent {
-    pub fn from_xml(xml: &str) -> Result<Self> {
+impl EventHeader {
+    pub fn from_node(event: Node) -> Result<Self> {
         let mut event_id_opt = None;
         let mut time_created_opt = None;
-        let mut event_data = HashMap::new();
-
-        let event = Document::parse(xml)?;
-        let system_xml = event
-            .root_element()
-            .children()
-            .filter(|n| n.tag_name().name() == "System")
-            .nth(0)
-            .context("No System node")?;
-        let event_data_xml = event
-            .root_element()
-            .children()
-            .filter(|n| n.tag_name().name() == "EventData")
-            .nth(0)
-            .context("No EventData node")?;
+        let mut computer = None;
+        let mut record_id = None;
+        let mut channel = None;
 
+        let system_xml = child(event, "System").context("No System node")?;
         for node in system_xml.children() {
             match node.tag_name().name() {
                 "EventID" => {
@@ -128,30 +490,431 @@ impl SysmonEvent {
                     )
                     .ok()
                 }
+                "Computer" => computer = node.text().map(String::from),
+                "EventRecordID" => record_id = node.text().and_then(|t| t.parse().ok()),
+                "Channel" => channel = node.text().map(String::from),
                 _ => (),
             }
         }
 
-        let event_id = event_id_opt.context("No EventID")?;
-        let time_created = time_created_opt.context("No TimeCreated")?;
+        Ok(EventHeader {
+            event_id: event_id_opt.context("No EventID")?,
+            time_created: time_created_opt.context("No TimeCreated")?,
+            computer,
+            record_id,
+            channel,
+        })
+    }
+}
 
-        for node in event_data_xml.children() {
-            if node.tag_name().name() == "Data" {
-                event_data.insert(
-                    node.attribute("Name")
-                        .context("EventData/Data has no Name attribute")?
-                        .to_string(),
-                    node.text()
-                        .context("EventData/Data has no text")?
-                        .to_string(),
-                );
+pub fn parse_header_only(xml: &str) -> Result<EventHeader> {
+    EventHeader::from_node(Document::parse(xml)?.root_element())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SysmonEvent {
+    pub event_id: SysmonEventId,
+    pub time_created: DateTime<FixedOffset>,
+    #[serde(default)]
+    pub computer: Option<String>,
+    #[serde(default)]
+    pub record_id: Option<u64>,
+    #[serde(default)]
+    pub channel: Option<String>,
+    pub event_data: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    pub lenient: bool,
+}
+
+impl ParseOptions {
+    pub fn strict() -> Self {
+        Self { lenient: false }
+    }
+
+    pub fn lenient() -> Self {
+        Self { lenient: true }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParseWarning {
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ParseWarning {
+    fn new(field: Option<&str>, message: &str) -> Self {
+        Self {
+            field: field.map(String::from),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}: {}", field, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedEvent {
+    pub event: SysmonEvent,
+    pub warnings: Vec<ParseWarning>,
+}
+
+impl SysmonEvent {
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        Self::from_node(Document::parse(xml)?.root_element())
+    }
+
+    pub fn from_node(event: Node) -> Result<Self> {
+        Ok(Self::from_node_with(event, &ParseOptions::strict())?.event)
+    }
+
+    pub fn from_xml_with(xml: &str, options: &ParseOptions) -> Result<ParsedEvent> {
+        Self::from_node_with(Document::parse(xml)?.root_element(), options)
+    }
+
+    pub fn from_node_with(event: Node, options: &ParseOptions) -> Result<ParsedEvent> {
+        let header = EventHeader::from_node(event)?;
+        let mut warnings = Vec::new();
+        let mut warn_or_bail = |warning: ParseWarning| {
+            if options.lenient {
+                warnings.push(warning);
+                Ok(())
+            } else {
+                Err(anyhow!("{}", warning))
+            }
+        };
+
+        let mut event_data = HashMap::new();
+        match child(event, "EventData") {
+            Some(event_data_xml) => {
+                for node in event_data_xml.children() {
+                    if node.tag_name().name() != "Data" {
+                        continue;
+                    }
+                    let Some(name) = node.attribute("Name") else {
+                        warn_or_bail(ParseWarning::new(
+                            None,
+                            "EventData/Data has no Name attribute",
+                        ))?;
+                        continue;
+                    };
+                    let text = match node.text() {
+                        Some(text) => text,
+                        None => {
+                            warn_or_bail(ParseWarning::new(
+                                Some(name),
+                                "EventData/Data has no text",
+                            ))?;
+                            ""
+                        }
+                    };
+                    event_data.insert(name.to_string(), text.to_string());
+                }
             }
+            None => warn_or_bail(ParseWarning::new(None, "No EventData node"))?,
         }
 
-        Ok(SysmonEvent {
-            event_id,
-            time_created,
+        let mut event = SysmonEvent {
+            event_id: header.event_id,
+            time_created: header.time_created,
+            computer: header.computer,
+            record_id: header.record_id,
+            channel: header.channel,
             event_data,
-        })
+        };
+        harden_event(&mut event);
+
+        if options.lenient {
+            for field in event.missing_required_fields() {
+                warnings.push(ParseWarning::new(Some(field), "Required field is missing"));
+            }
+        }
+
+        Ok(ParsedEvent { event, warnings })
+    }
+
+    pub fn missing_required_fields(&self) -> Vec<&'static str> {
+        self.event_id
+            .required_fields()
+            .iter()
+            .filter(|f| !self.event_data.contains_key(**f))
+            .copied()
+            .collect()
+    }
+
+    pub fn dedup_key(&self) -> String {
+        match self.record_id {
+            Some(record_id) => format!(
+                "{}|{}|{}",
+                self.computer.as_deref().unwrap_or_default(),
+                self.event_id.value(),
+                record_id
+            ),
+            None => format!(
+                "{}|{}|{}|{}",
+                self.computer.as_deref().unwrap_or_default(),
+                self.event_id.value(),
+                self.time_created.to_rfc3339(),
+                self.event_data
+                    .get("ProcessGuid")
+                    .map(String::as_str)
+                    .unwrap_or_default()
+            ),
+        }
+    }
+
+    pub fn set_field<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        self.event_data.insert(key.into(), value.into());
+    }
+
+    pub fn with_field<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.set_field(key, value);
+        self
+    }
+
+    pub fn set_enriched<V: Into<String>>(&mut self, key: &str, value: V) {
+        self.set_field(format!("{}{}", ENRICHED_PREFIX, key), value);
+    }
+
+    pub fn enriched(&self, key: &str) -> Option<&String> {
+        self.event_data.get(&format!("{}{}", ENRICHED_PREFIX, key))
+    }
+}
+
+pub fn filter_events<I, F>(events: I, pred: F) -> impl Iterator<Item = SysmonEvent>
+where
+    I: IntoIterator<Item = SysmonEvent>,
+    F: FnMut(&SysmonEvent) -> bool,
+{
+    events.into_iter().filter(pred)
+}
+
+fn merge_event_data(target: &mut SysmonEvent, other: SysmonEvent) {
+    for (key, value) in other.event_data {
+        match target.event_data.get(&key) {
+            None => {
+                target.event_data.insert(key, value);
+            }
+            Some(current) if current == &value || value.is_empty() => (),
+            Some(current) => {
+                if !current.is_empty()
+                    && !value.starts_with(current.as_str())
+                    && !current.starts_with(value.as_str())
+                {
+                    warn!(
+                        "Conflicting {} for {}: {:?} vs {:?}",
+                        key,
+                        target.dedup_key(),
+                        current,
+                        value
+                    );
+                }
+                if value.len() > current.len() {
+                    target.event_data.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+pub fn merge_duplicates(events: Vec<SysmonEvent>) -> Vec<SysmonEvent> {
+    let mut merged: Vec<SysmonEvent> = Vec::new();
+    let mut index = HashMap::new();
+    for event in events {
+        let key = event.dedup_key();
+        match index.get(&key) {
+            Some(&i) => merge_event_data(&mut merged[i], event),
+            None => {
+                index.insert(key, merged.len());
+                merged.push(event);
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(event_id: SysmonEventId, fields: &[&str]) -> String {
+        let data: String = fields
+            .iter()
+            .map(|f| format!("<Data Name=\"{}\">{}-value</Data>", f, f))
+            .collect();
+        format!(
+            "<Event xmlns=\"http://schemas.microsoft.com/win/2004/08/events/event\">\
+             <System>\
+             <EventID>{}</EventID>\
+             <TimeCreated SystemTime=\"2024-01-01T00:00:00.000000Z\"/>\
+             <EventRecordID>1</EventRecordID>\
+             <Channel>Microsoft-Windows-Sysmon/Operational</Channel>\
+             <Computer>WIN10</Computer>\
+             </System>\
+             <EventData>{}</EventData>\
+             </Event>",
+            event_id.value(),
+            data
+        )
+    }
+
+    #[test]
+    fn every_event_id_parses_required_and_optional_fields() {
+        for id in SysmonEventId::ALL {
+            let fields: Vec<&str> = id
+                .required_fields()
+                .iter()
+                .chain(id.optional_fields())
+                .copied()
+                .collect();
+            let parsed =
+                SysmonEvent::from_xml_with(&sample(*id, &fields), &ParseOptions::lenient())
+                    .unwrap_or_else(|e| panic!("event {}: {}", id.value(), e));
+            assert_eq!(parsed.event.event_id, *id);
+            assert!(parsed.warnings.is_empty(), "event {}", id.value());
+            for field in fields {
+                assert_eq!(
+                    parsed.event.event_data.get(field).map(String::as_str),
+                    Some(format!("{}-value", field).as_str()),
+                    "event {} field {}",
+                    id.value(),
+                    field
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn required_and_optional_fields_do_not_overlap() {
+        for id in SysmonEventId::ALL {
+            assert!(!id.required_fields().is_empty(), "event {}", id.value());
+            for field in id.optional_fields() {
+                assert!(
+                    !id.required_fields().contains(field),
+                    "event {} lists {} as both required and optional",
+                    id.value(),
+                    field
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn optional_fields_may_be_absent() {
+        for id in SysmonEventId::ALL {
+            let parsed = SysmonEvent::from_xml_with(
+                &sample(*id, id.required_fields()),
+                &ParseOptions::lenient(),
+            )
+            .unwrap();
+            assert!(parsed.warnings.is_empty(), "event {}", id.value());
+            assert!(parsed.event.missing_required_fields().is_empty());
+        }
+    }
+
+    #[test]
+    fn each_missing_required_field_is_reported() {
+        for id in SysmonEventId::ALL {
+            for missing in id.required_fields() {
+                let fields: Vec<&str> = id
+                    .required_fields()
+                    .iter()
+                    .filter(|f| *f != missing)
+                    .copied()
+                    .collect();
+                let parsed =
+                    SysmonEvent::from_xml_with(&sample(*id, &fields), &ParseOptions::lenient())
+                        .unwrap();
+                assert_eq!(parsed.event.missing_required_fields(), vec![*missing]);
+                assert_eq!(parsed.warnings.len(), 1);
+                assert_eq!(parsed.warnings[0].field.as_deref(), Some(*missing));
+            }
+        }
+    }
+
+    #[test]
+    fn process_create_sample_has_required_fields() {
+        let xml = r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event">
+  <System>
+    <Provider Name="Microsoft-Windows-Sysmon" Guid="{5770385f-c22a-43e0-bf4c-06f5698ffbd9}"/>
+    <EventID>1</EventID>
+    <Version>5</Version>
+    <TimeCreated SystemTime="2024-03-05T10:15:30.1234567Z"/>
+    <EventRecordID>4242</EventRecordID>
+    <Channel>Microsoft-Windows-Sysmon/Operational</Channel>
+    <Computer>DESKTOP-SANDBOX</Computer>
+  </System>
+  <EventData>
+    <Data Name="RuleName">-</Data>
+    <Data Name="UtcTime">2024-03-05 10:15:30.120</Data>
+    <Data Name="ProcessGuid">{6a1f1c2e-1a2b-65e6-3c01-000000000f00}</Data>
+    <Data Name="ProcessId">4312</Data>
+    <Data Name="Image">C:\Windows\System32\cmd.exe</Data>
+    <Data Name="FileVersion">10.0.19041.746</Data>
+    <Data Name="Description">Windows Command Processor</Data>
+    <Data Name="Product">Microsoft Windows Operating System</Data>
+    <Data Name="Company">Microsoft Corporation</Data>
+    <Data Name="OriginalFileName">Cmd.Exe</Data>
+    <Data Name="CommandLine">cmd.exe /c whoami</Data>
+    <Data Name="CurrentDirectory">C:\Users\analyst\</Data>
+    <Data Name="User">DESKTOP-SANDBOX\analyst</Data>
+    <Data Name="LogonGuid">{6a1f1c2e-0b1c-65e6-2a4f-020000000000}</Data>
+    <Data Name="LogonId">0x24f2a</Data>
+    <Data Name="TerminalSessionId">1</Data>
+    <Data Name="IntegrityLevel">Medium</Data>
+    <Data Name="Hashes">SHA256=B99D114B267FFD068C3289199B6DF95A9C8CA2D0E3E3C7A1C0A8B9E5A1A2B3C4</Data>
+    <Data Name="ParentProcessGuid">{6a1f1c2e-1a2a-65e6-3b01-000000000f00}</Data>
+    <Data Name="ParentProcessId">3980</Data>
+    <Data Name="ParentImage">C:\Windows\explorer.exe</Data>
+    <Data Name="ParentCommandLine">C:\Windows\Explorer.EXE</Data>
+    <Data Name="ParentUser">DESKTOP-SANDBOX\analyst</Data>
+  </EventData>
+</Event>"#;
+        let event = SysmonEvent::from_xml(xml).unwrap();
+        assert_eq!(event.event_id, SysmonEventId::PROCESS_CREATE);
+        assert_eq!(event.record_id, Some(4242));
+        assert_eq!(event.computer.as_deref(), Some("DESKTOP-SANDBOX"));
+        assert!(event.missing_required_fields().is_empty());
+        for field in SysmonEventId::PROCESS_CREATE.optional_fields() {
+            assert!(event.event_data.contains_key(*field), "{}", field);
+        }
+        assert_eq!(event.event_data["CommandLine"], "cmd.exe /c whoami");
+    }
+
+    #[test]
+    fn dns_query_sample_has_required_fields() {
+        let xml = r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event">
+  <System>
+    <EventID>22</EventID>
+    <TimeCreated SystemTime="2024-03-05T10:15:31.0000000Z"/>
+    <EventRecordID>4243</EventRecordID>
+    <Channel>Microsoft-Windows-Sysmon/Operational</Channel>
+    <Computer>DESKTOP-SANDBOX</Computer>
+  </System>
+  <EventData>
+    <Data Name="RuleName">-</Data>
+    <Data Name="UtcTime">2024-03-05 10:15:30.998</Data>
+    <Data Name="ProcessGuid">{6a1f1c2e-1a2b-65e6-3c01-000000000f00}</Data>
+    <Data Name="ProcessId">4312</Data>
+    <Data Name="QueryName">example.com</Data>
+    <Data Name="QueryStatus">0</Data>
+    <Data Name="QueryResults">::ffff:93.184.216.34;</Data>
+    <Data Name="Image">C:\Windows\System32\cmd.exe</Data>
+  </EventData>
+</Event>"#;
+        let parsed = SysmonEvent::from_xml_with(xml, &ParseOptions::lenient()).unwrap();
+        assert_eq!(parsed.event.event_id, SysmonEventId::DNS_QUERY);
+        assert!(parsed.warnings.is_empty());
+        assert_eq!(parsed.event.event_data["QueryName"], "example.com");
     }
 }
@@ -0,0 +1,358 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::baseline::component;
+use crate::static_analysis::pe::{self as static_pe, Section};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const MAX_REGIONS: usize = 16;
+const MERGE_GAP: usize = 8;
+const PREVIEW_BYTES: usize = 16;
+const HEADERS: &str = "(headers)";
+const OVERLAY: &str = "(overlay)";
+
+pub const DEFAULT_WATCHED_FILES: &[&str] = &[
+    r"C:\Windows\explorer.exe",
+    r"C:\Windows\System32\ntdll.dll",
+    r"C:\Windows\System32\kernel32.dll",
+    r"C:\Windows\System32\kernelbase.dll",
+    r"C:\Windows\System32\user32.dll",
+    r"C:\Windows\System32\advapi32.dll",
+    r"C:\Windows\System32\winlogon.exe",
+    r"C:\Windows\System32\userinit.exe",
+    r"C:\Windows\System32\lsass.exe",
+    r"C:\Windows\System32\services.exe",
+    r"C:\Windows\System32\svchost.exe",
+    r"C:\Windows\System32\sethc.exe",
+    r"C:\Windows\System32\utilman.exe",
+    r"C:\Windows\System32\drivers\tcpip.sys",
+    r"C:\Windows\System32\drivers\ntfs.sys",
+];
+
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter()
+        .take(PREVIEW_BYTES)
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GoldenFile {
+    pub path: String,
+    pub sha256: String,
+}
+
+impl GoldenFile {
+    pub fn unknown(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            sha256: String::new(),
+        }
+    }
+
+    pub fn matches(&self, content: &[u8]) -> bool {
+        self.sha256.eq_ignore_ascii_case(&sha256(content))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GoldenImage {
+    pub machine: String,
+    pub snapshot: String,
+    pub created: DateTime<Utc>,
+    pub files: Vec<GoldenFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChangedRegion {
+    pub offset: u64,
+    pub length: u64,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionStatus {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SectionChange {
+    pub name: String,
+    pub status: SectionStatus,
+    pub executable: bool,
+    pub regions: Vec<ChangedRegion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileTamper {
+    pub path: String,
+    pub golden_sha256: String,
+    pub sha256: String,
+    pub golden_size: u64,
+    pub size: u64,
+    pub pe: bool,
+    pub changed_bytes: u64,
+    pub sections: Vec<SectionChange>,
+    pub regions: Vec<ChangedRegion>,
+}
+
+impl FileTamper {
+    pub fn code_patched(&self) -> bool {
+        self.sections
+            .iter()
+            .any(|s| s.executable && s.status != SectionStatus::Removed)
+    }
+
+    pub fn summary(&self) -> String {
+        if self.sections.is_empty() {
+            return format!("{} bytes changed", self.changed_bytes);
+        }
+        let sections: Vec<String> = self
+            .sections
+            .iter()
+            .map(|s| match s.status {
+                SectionStatus::Modified => s.name.clone(),
+                SectionStatus::Added => format!("+{}", s.name),
+                SectionStatus::Removed => format!("-{}", s.name),
+            })
+            .collect();
+        format!(
+            "{} bytes changed in {}",
+            self.changed_bytes,
+            sections.join(", ")
+        )
+    }
+}
+
+fn changed_regions(before: &[u8], after: &[u8], base: u64) -> (Vec<ChangedRegion>, u64) {
+    let len = before.len().max(after.len());
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut changed = 0;
+    for offset in 0..len {
+        if before.get(offset) == after.get(offset) {
+            continue;
+        }
+        changed += 1;
+        match spans.last_mut() {
+            Some((_, end)) if offset <= *end + MERGE_GAP => *end = offset + 1,
+            _ => spans.push((offset, offset + 1)),
+        }
+    }
+    let slice = |data: &[u8], start: usize, end: usize| {
+        hex(data
+            .get(start.min(data.len())..end.min(data.len()))
+            .unwrap_or_default())
+    };
+    let regions = spans
+        .into_iter()
+        .take(MAX_REGIONS)
+        .map(|(start, end)| ChangedRegion {
+            offset: base + start as u64,
+            length: (end - start) as u64,
+            before: slice(before, start, end),
+            after: slice(after, start, end),
+        })
+        .collect();
+    (regions, changed)
+}
+
+fn raw_data<'a>(data: &'a [u8], section: &Section) -> &'a [u8] {
+    let start = (section.raw_offset as usize).min(data.len());
+    let end = start
+        .saturating_add(section.raw_size as usize)
+        .min(data.len());
+    &data[start..end]
+}
+
+fn layout<'a>(data: &'a [u8], sections: &[Section]) -> Vec<(String, bool, u64, &'a [u8])> {
+    let headers_end = sections
+        .iter()
+        .filter(|s| s.raw_size > 0)
+        .map(|s| s.raw_offset as usize)
+        .min()
+        .unwrap_or(data.len())
+        .min(data.len());
+    let overlay_start = sections
+        .iter()
+        .map(|s| s.raw_offset as usize + s.raw_size as usize)
+        .max()
+        .unwrap_or(data.len())
+        .min(data.len());
+    let mut parts = vec![(HEADERS.to_string(), false, 0, &data[..headers_end])];
+    parts.extend(sections.iter().map(|s| {
+        (
+            s.name.clone(),
+            s.executable,
+            s.raw_offset as u64,
+            raw_data(data, s),
+        )
+    }));
+    if overlay_start < data.len() {
+        parts.push((
+            OVERLAY.to_string(),
+            false,
+            overlay_start as u64,
+            &data[overlay_start..],
+        ));
+    }
+    parts
+}
+
+fn diff_sections(golden: &[u8], current: &[u8]) -> Option<(Vec<SectionChange>, u64)> {
+    let before = layout(golden, &static_pe::analyze(golden).ok()?.sections);
+    let after = layout(current, &static_pe::analyze(current).ok()?.sections);
+    let mut changes = Vec::new();
+    let mut changed = 0;
+    for (name, executable, offset, data) in &after {
+        match before.iter().find(|(n, ..)| n == name) {
+            Some((_, _, _, original)) if original == data => {}
+            Some((_, _, _, original)) => {
+                let (regions, bytes) = changed_regions(original, data, *offset);
+                changed += bytes;
+                changes.push(SectionChange {
+                    name: name.clone(),
+                    status: SectionStatus::Modified,
+                    executable: *executable,
+                    regions,
+                });
+            }
+            None => {
+                changed += data.len() as u64;
+                changes.push(SectionChange {
+                    name: name.clone(),
+                    status: SectionStatus::Added,
+                    executable: *executable,
+                    regions: changed_regions(&[], data, *offset).0,
+                });
+            }
+        }
+    }
+    for (name, executable, _, data) in &before {
+        if !after.iter().any(|(n, ..)| n == name) {
+            changed += data.len() as u64;
+            changes.push(SectionChange {
+                name: name.clone(),
+                status: SectionStatus::Removed,
+                executable: *executable,
+                regions: Vec::new(),
+            });
+        }
+    }
+    Some((changes, changed))
+}
+
+pub fn diff_file(path: &str, golden: &[u8], current: &[u8]) -> FileTamper {
+    let pe = static_pe::is_pe(golden) && static_pe::is_pe(current);
+    let (sections, regions, changed_bytes) = match pe.then(|| diff_sections(golden, current)) {
+        Some(Some((sections, changed))) => (sections, Vec::new(), changed),
+        _ => {
+            let (regions, changed) = changed_regions(golden, current, 0);
+            (Vec::new(), regions, changed)
+        }
+    };
+    FileTamper {
+        path: path.to_string(),
+        golden_sha256: sha256(golden),
+        sha256: sha256(current),
+        golden_size: golden.len() as u64,
+        size: current.len() as u64,
+        pe,
+        changed_bytes,
+        sections,
+        regions,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GoldenStore {
+    dir: PathBuf,
+}
+
+impl GoldenStore {
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn snapshot_dir(&self, machine: &str, snapshot: &str) -> PathBuf {
+        self.dir.join(component(machine)).join(component(snapshot))
+    }
+
+    pub fn save(
+        &self,
+        machine: &str,
+        snapshot: &str,
+        files: &[(String, Vec<u8>)],
+    ) -> Result<GoldenImage> {
+        let dir = self.snapshot_dir(machine, snapshot);
+        fs::create_dir_all(&dir)?;
+        let mut image = GoldenImage {
+            machine: machine.to_string(),
+            snapshot: snapshot.to_string(),
+            created: Utc::now(),
+            files: Vec::new(),
+        };
+        for (path, content) in files {
+            let hash = sha256(content);
+            fs::write(dir.join(&hash), content)?;
+            image.files.push(GoldenFile {
+                path: path.clone(),
+                sha256: hash,
+            });
+        }
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_vec_pretty(&image)?)?;
+        Ok(image)
+    }
+
+    pub fn load(&self, machine: &str, snapshot: &str) -> Result<Option<GoldenImage>> {
+        let path = self.snapshot_dir(machine, snapshot).join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(&path)
+            .with_context(|| format!("Failed to read golden manifest {}", path.display()))?;
+        Ok(Some(serde_json::from_slice(&data)?))
+    }
+
+    pub fn content(&self, image: &GoldenImage, file: &GoldenFile) -> Result<Vec<u8>> {
+        let path = self
+            .snapshot_dir(&image.machine, &image.snapshot)
+            .join(component(&file.sha256));
+        fs::read(&path).with_context(|| format!("Failed to read golden copy of {}", file.path))
+    }
+
+    pub fn diff(
+        &self,
+        image: &GoldenImage,
+        path: &str,
+        current: &[u8],
+    ) -> Result<Option<FileTamper>> {
+        let Some(file) = image
+            .files
+            .iter()
+            .find(|f| f.path.eq_ignore_ascii_case(path))
+        else {
+            return Ok(None);
+        };
+        if file.matches(current) {
+            return Ok(None);
+        }
+        let golden = self.content(image, file)?;
+        Ok(Some(diff_file(&file.path, &golden, current)))
+    }
+}
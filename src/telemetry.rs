@@ -0,0 +1,710 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+
+use crate::event_reader::SysmonEventReader;
+use crate::flags::LogonType;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId, UTC_TIME_FORMAT};
+
+pub const LINUX_SYSMON_PROVIDER: &str = "Linux-Sysmon";
+
+const AMSI_RESULT_DETECTED: u32 = 32768;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Provider {
+    Sysmon,
+    PowerShell,
+    DnsClient,
+    KernelProcess,
+    Security,
+    ApiTrace,
+    Amsi,
+}
+
+impl Provider {
+    pub const ALL: &'static [Self] = &[
+        Self::Sysmon,
+        Self::PowerShell,
+        Self::DnsClient,
+        Self::KernelProcess,
+        Self::Security,
+        Self::ApiTrace,
+        Self::Amsi,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sysmon => "Microsoft-Windows-Sysmon",
+            Self::PowerShell => "Microsoft-Windows-PowerShell",
+            Self::DnsClient => "Microsoft-Windows-DNS-Client",
+            Self::KernelProcess => "Microsoft-Windows-Kernel-Process",
+            Self::Security => "Microsoft-Windows-Security-Auditing",
+            Self::ApiTrace => "Sandbox-ApiTrace",
+            Self::Amsi => "Microsoft-Antimalware-Scan-Interface",
+        }
+    }
+
+    pub fn channel(&self) -> &'static str {
+        match self {
+            Self::Sysmon => "Microsoft-Windows-Sysmon/Operational",
+            Self::PowerShell => "Microsoft-Windows-PowerShell/Operational",
+            Self::DnsClient => "Microsoft-Windows-DNS-Client/Operational",
+            Self::KernelProcess => "Microsoft-Windows-Kernel-Process/Analytic",
+            Self::Security => "Security",
+            Self::ApiTrace => "Sandbox-ApiTrace/Operational",
+            Self::Amsi => "Microsoft-Antimalware-Scan-Interface/Operational",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|p| p.name().eq_ignore_ascii_case(name.trim()))
+            .copied()
+            .or_else(|| {
+                name.trim()
+                    .eq_ignore_ascii_case(LINUX_SYSMON_PROVIDER)
+                    .then_some(Self::Sysmon)
+            })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScriptBlock {
+    pub time: DateTime<FixedOffset>,
+    pub process_id: Option<u32>,
+    pub script_block_id: String,
+    pub message_number: u32,
+    pub message_total: u32,
+    pub text: String,
+    pub path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModuleLog {
+    pub time: DateTime<FixedOffset>,
+    pub process_id: Option<u32>,
+    pub context: Option<String>,
+    pub payload: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AmsiScan {
+    pub time: DateTime<FixedOffset>,
+    pub process_id: Option<u32>,
+    pub app_name: Option<String>,
+    pub content_name: Option<String>,
+    pub scan_result: Option<u32>,
+    pub content: String,
+}
+
+impl AmsiScan {
+    pub fn is_powershell(&self) -> bool {
+        self.app_name
+            .as_deref()
+            .is_some_and(|a| a.to_lowercase().starts_with("powershell"))
+    }
+
+    pub fn is_detected(&self) -> bool {
+        self.scan_result.is_some_and(|r| r >= AMSI_RESULT_DETECTED)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnsClientQuery {
+    pub time: DateTime<FixedOffset>,
+    pub process_id: Option<u32>,
+    pub query_name: String,
+    pub query_type: Option<u16>,
+    pub status: Option<u32>,
+    pub results: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessActivity {
+    Start,
+    Stop,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KernelProcess {
+    pub time: DateTime<FixedOffset>,
+    pub activity: ProcessActivity,
+    pub process_id: u32,
+    pub parent_process_id: Option<u32>,
+    pub image: Option<String>,
+    pub exit_code: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SecurityProcessCreate {
+    pub time: DateTime<FixedOffset>,
+    pub process_id: u32,
+    pub parent_process_id: Option<u32>,
+    pub image: Option<String>,
+    pub parent_image: Option<String>,
+    pub command_line: Option<String>,
+    pub user: Option<String>,
+    pub logon_id: Option<String>,
+    pub integrity_level: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Logon {
+    pub time: DateTime<FixedOffset>,
+    pub success: bool,
+    pub logon_type: Option<LogonType>,
+    pub user: Option<String>,
+    pub logon_id: Option<String>,
+    pub logon_process: Option<String>,
+    pub authentication_package: Option<String>,
+    pub workstation: Option<String>,
+    pub source_ip: Option<String>,
+    pub source_port: Option<u16>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub failure_status: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilteringConnection {
+    pub time: DateTime<FixedOffset>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub outbound: bool,
+    pub protocol: Option<u8>,
+    pub source_ip: Option<String>,
+    pub source_port: Option<u16>,
+    pub destination_ip: Option<String>,
+    pub destination_port: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiCall {
+    pub time: DateTime<FixedOffset>,
+    pub process_id: u32,
+    #[serde(default)]
+    pub thread_id: Option<u32>,
+    pub api: String,
+    #[serde(default)]
+    pub args: HashMap<String, String>,
+    #[serde(default)]
+    pub return_value: Option<String>,
+}
+
+impl ApiCall {
+    pub fn arg(&self, name: &str) -> Option<&str> {
+        self.args
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .filter(|value| !value.is_empty())
+    }
+
+    pub fn is(&self, api: &str) -> bool {
+        let name = self.api.rsplit('!').next().unwrap_or(&self.api);
+        name.eq_ignore_ascii_case(api)
+            || name
+                .strip_suffix(['A', 'W'])
+                .is_some_and(|base| base.eq_ignore_ascii_case(api))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum TelemetryEvent {
+    Sysmon(SysmonEvent),
+    ScriptBlock(ScriptBlock),
+    Module(ModuleLog),
+    Amsi(AmsiScan),
+    DnsQuery(DnsClientQuery),
+    Process(KernelProcess),
+    SecurityProcess(SecurityProcessCreate),
+    Logon(Logon),
+    Connection(FilteringConnection),
+    ApiCall(ApiCall),
+}
+
+#[derive(Debug, Clone)]
+pub struct TelemetryRecord {
+    pub provider: Provider,
+    pub event_id: u16,
+    pub time: DateTime<FixedOffset>,
+    pub process_id: Option<u32>,
+    pub fields: HashMap<String, String>,
+}
+
+impl TelemetryRecord {
+    fn text(&self, name: &str) -> Option<String> {
+        self.fields
+            .get(name)
+            .map(|v| v.trim_end_matches('\0').to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        let value = self.fields.get(name)?.trim();
+        match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok()?.to_string().parse().ok(),
+            None => value.parse().ok(),
+        }
+    }
+}
+
+fn account(domain: Option<String>, user: Option<String>) -> Option<String> {
+    match (domain, user) {
+        (Some(domain), Some(user)) => Some(format!("{}\\{}", domain, user)),
+        (None, user) => user,
+        (domain, None) => domain,
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim().trim_start_matches("0x");
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn decode_content(bytes: &[u8]) -> String {
+    let wide = bytes.len() >= 2
+        && bytes.len().is_multiple_of(2)
+        && bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count() * 2 >= bytes.len() / 2;
+    if wide {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+            .trim_end_matches('\0')
+            .to_string()
+    } else {
+        String::from_utf8_lossy(bytes)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+}
+
+fn integrity_level(label: &str) -> Option<&'static str> {
+    match label.rsplit('-').next()? {
+        "0" => Some("Untrusted"),
+        "4096" => Some("Low"),
+        "8192" => Some("Medium"),
+        "8448" => Some("MediumPlus"),
+        "12288" => Some("High"),
+        "16384" => Some("System"),
+        "20480" => Some("Protected"),
+        _ => None,
+    }
+}
+
+fn is_outbound(direction: &str) -> bool {
+    direction.contains("14593") || direction.eq_ignore_ascii_case("outbound")
+}
+
+fn protocol_name(protocol: u8) -> Option<&'static str> {
+    match protocol {
+        6 => Some("tcp"),
+        17 => Some("udp"),
+        1 => Some("icmp"),
+        _ => None,
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.tag_name().name() == name)
+}
+
+impl TelemetryEvent {
+    pub fn from_xml(xml: &str) -> Result<Option<Self>> {
+        let document = Document::parse(xml)?;
+        let event = document.root_element();
+        let system = child(event, "System").context("No System node")?;
+        let Some(provider) = child(system, "Provider")
+            .and_then(|p| p.attribute("Name"))
+            .and_then(Provider::from_name)
+        else {
+            return Ok(None);
+        };
+        if provider == Provider::Sysmon {
+            return Ok(Some(Self::Sysmon(SysmonEvent::from_node(event)?)));
+        }
+
+        let event_id = child(system, "EventID")
+            .and_then(|n| n.text())
+            .context("No EventID")?
+            .trim()
+            .parse()?;
+        let time = child(system, "TimeCreated")
+            .and_then(|n| n.attribute("SystemTime"))
+            .context("No TimeCreated")?;
+        let process_id = child(system, "Execution")
+            .and_then(|n| n.attribute("ProcessID"))
+            .and_then(|p| p.parse().ok());
+        let fields = child(event, "EventData")
+            .map(|data| {
+                data.children()
+                    .filter(|n| n.tag_name().name() == "Data")
+                    .filter_map(|n| {
+                        Some((
+                            n.attribute("Name")?.to_string(),
+                            n.text().unwrap_or_default().to_string(),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self::from_record(TelemetryRecord {
+            provider,
+            event_id,
+            time: DateTime::parse_from_rfc3339(time)?,
+            process_id,
+            fields,
+        }))
+    }
+
+    pub fn from_record(record: TelemetryRecord) -> Option<Self> {
+        match (record.provider, record.event_id) {
+            (Provider::Sysmon, id) => Some(Self::Sysmon(SysmonEvent {
+                event_id: id.to_string().parse::<SysmonEventId>().ok()?,
+                time_created: record.time,
+                computer: None,
+                record_id: None,
+                channel: Some(Provider::Sysmon.channel().to_string()),
+                event_data: record.fields,
+            })),
+            (Provider::PowerShell, 4104) => Some(Self::ScriptBlock(ScriptBlock {
+                time: record.time,
+                process_id: record.process_id,
+                script_block_id: record.text("ScriptBlockId")?,
+                message_number: record.number("MessageNumber").unwrap_or(1),
+                message_total: record.number("MessageTotal").unwrap_or(1),
+                text: record.text("ScriptBlockText").unwrap_or_default(),
+                path: record.text("Path"),
+            })),
+            (Provider::PowerShell, 4103) => Some(Self::Module(ModuleLog {
+                time: record.time,
+                process_id: record.process_id,
+                context: record.text("ContextInfo"),
+                payload: record.text("Payload")?,
+            })),
+            (Provider::Amsi, 1101) => Some(Self::Amsi(AmsiScan {
+                time: record.time,
+                process_id: record.process_id,
+                app_name: record.text("appname"),
+                content_name: record.text("contentname"),
+                scan_result: record.number("scanResult"),
+                content: decode_content(&decode_hex(&record.text("content")?)?),
+            })),
+            (Provider::DnsClient, 3008) => Some(Self::DnsQuery(DnsClientQuery {
+                time: record.time,
+                process_id: record.process_id,
+                query_name: record.text("QueryName")?,
+                query_type: record.number("QueryType"),
+                status: record.number("QueryStatus"),
+                results: record.text("QueryResults"),
+            })),
+            (Provider::KernelProcess, 1 | 2) => Some(Self::Process(KernelProcess {
+                time: record.time,
+                activity: match record.event_id {
+                    1 => ProcessActivity::Start,
+                    _ => ProcessActivity::Stop,
+                },
+                process_id: record.number("ProcessID")?,
+                parent_process_id: record.number("ParentProcessID"),
+                image: record.text("ImageName"),
+                exit_code: record.number("ExitCode"),
+            })),
+            (Provider::Security, 4688) => Some(Self::SecurityProcess(SecurityProcessCreate {
+                time: record.time,
+                process_id: record.number("NewProcessId")?,
+                parent_process_id: record.number("ProcessId"),
+                image: record.text("NewProcessName"),
+                parent_image: record.text("ParentProcessName"),
+                command_line: record.text("CommandLine"),
+                user: account(
+                    record.text("SubjectDomainName"),
+                    record.text("SubjectUserName"),
+                ),
+                logon_id: record.text("SubjectLogonId"),
+                integrity_level: record
+                    .text("MandatoryLabel")
+                    .and_then(|l| integrity_level(&l))
+                    .map(String::from),
+            })),
+            (Provider::Security, 4624 | 4625) => Some(Self::Logon(Logon {
+                time: record.time,
+                success: record.event_id == 4624,
+                logon_type: record.number("LogonType").map(LogonType),
+                user: account(
+                    record.text("TargetDomainName"),
+                    record.text("TargetUserName"),
+                ),
+                logon_id: record.text("TargetLogonId"),
+                logon_process: record.text("LogonProcessName"),
+                authentication_package: record.text("AuthenticationPackageName"),
+                workstation: record.text("WorkstationName"),
+                source_ip: record.text("IpAddress").filter(|ip| ip != "-"),
+                source_port: record.number("IpPort").filter(|&p| p != 0),
+                process_id: record.number("ProcessId"),
+                image: record.text("ProcessName").filter(|p| p != "-"),
+                failure_status: record.text("Status"),
+            })),
+            (Provider::Security, 5156) => Some(Self::Connection(FilteringConnection {
+                time: record.time,
+                process_id: record.number("ProcessID"),
+                image: record.text("Application"),
+                outbound: record.text("Direction").is_some_and(|d| is_outbound(&d)),
+                protocol: record.number("Protocol"),
+                source_ip: record.text("SourceAddress"),
+                source_port: record.number("SourcePort"),
+                destination_ip: record.text("DestAddress"),
+                destination_port: record.number("DestPort"),
+            })),
+            _ => None,
+        }
+    }
+
+    pub fn provider(&self) -> Provider {
+        match self {
+            Self::Sysmon(_) => Provider::Sysmon,
+            Self::ScriptBlock(_) | Self::Module(_) => Provider::PowerShell,
+            Self::Amsi(_) => Provider::Amsi,
+            Self::DnsQuery(_) => Provider::DnsClient,
+            Self::Process(_) => Provider::KernelProcess,
+            Self::SecurityProcess(_) | Self::Logon(_) | Self::Connection(_) => Provider::Security,
+            Self::ApiCall(_) => Provider::ApiTrace,
+        }
+    }
+
+    pub fn time(&self) -> DateTime<FixedOffset> {
+        match self {
+            Self::Sysmon(e) => e.time_created,
+            Self::ScriptBlock(e) => e.time,
+            Self::Module(e) => e.time,
+            Self::Amsi(e) => e.time,
+            Self::DnsQuery(e) => e.time,
+            Self::Process(e) => e.time,
+            Self::SecurityProcess(e) => e.time,
+            Self::Logon(e) => e.time,
+            Self::Connection(e) => e.time,
+            Self::ApiCall(e) => e.time,
+        }
+    }
+
+    pub fn set_time(&mut self, time: DateTime<FixedOffset>) {
+        match self {
+            Self::Sysmon(e) => e.time_created = time,
+            Self::ScriptBlock(e) => e.time = time,
+            Self::Module(e) => e.time = time,
+            Self::Amsi(e) => e.time = time,
+            Self::DnsQuery(e) => e.time = time,
+            Self::Process(e) => e.time = time,
+            Self::SecurityProcess(e) => e.time = time,
+            Self::Logon(e) => e.time = time,
+            Self::Connection(e) => e.time = time,
+            Self::ApiCall(e) => e.time = time,
+        }
+    }
+
+    pub fn to_sysmon(&self) -> Option<SysmonEvent> {
+        let (event_id, time, fields) = match self {
+            Self::Sysmon(e) => return Some(e.clone()),
+            Self::ScriptBlock(_)
+            | Self::Module(_)
+            | Self::Amsi(_)
+            | Self::Logon(_)
+            | Self::ApiCall(_) => return None,
+            Self::DnsQuery(e) => (
+                SysmonEventId::DNS_QUERY,
+                e.time,
+                vec![
+                    ("ProcessId", e.process_id.map(|p| p.to_string())),
+                    ("QueryName", Some(e.query_name.clone())),
+                    ("QueryStatus", e.status.map(|s| s.to_string())),
+                    ("QueryResults", e.results.clone()),
+                ],
+            ),
+            Self::Process(e) => (
+                match e.activity {
+                    ProcessActivity::Start => SysmonEventId::PROCESS_CREATE,
+                    ProcessActivity::Stop => SysmonEventId::PROCESS_TERMINATE,
+                },
+                e.time,
+                vec![
+                    ("ProcessId", Some(e.process_id.to_string())),
+                    (
+                        "ParentProcessId",
+                        e.parent_process_id.map(|p| p.to_string()),
+                    ),
+                    ("Image", e.image.clone()),
+                ],
+            ),
+            Self::SecurityProcess(e) => (
+                SysmonEventId::PROCESS_CREATE,
+                e.time,
+                vec![
+                    ("ProcessId", Some(e.process_id.to_string())),
+                    ("Image", e.image.clone()),
+                    ("CommandLine", e.command_line.clone()),
+                    ("User", e.user.clone()),
+                    ("LogonId", e.logon_id.clone()),
+                    ("IntegrityLevel", e.integrity_level.clone()),
+                    (
+                        "ParentProcessId",
+                        e.parent_process_id.map(|p| p.to_string()),
+                    ),
+                    ("ParentImage", e.parent_image.clone()),
+                ],
+            ),
+            Self::Connection(e) => (
+                SysmonEventId::NETWORK_CONNECT,
+                e.time,
+                vec![
+                    ("ProcessId", e.process_id.map(|p| p.to_string())),
+                    ("Image", e.image.clone()),
+                    (
+                        "Protocol",
+                        e.protocol.and_then(protocol_name).map(String::from),
+                    ),
+                    ("Initiated", Some(e.outbound.to_string())),
+                    ("SourceIp", e.source_ip.clone()),
+                    ("SourcePort", e.source_port.map(|p| p.to_string())),
+                    ("DestinationIp", e.destination_ip.clone()),
+                    ("DestinationPort", e.destination_port.map(|p| p.to_string())),
+                ],
+            ),
+        };
+
+        let mut event = SysmonEvent {
+            event_id,
+            time_created: time,
+            computer: None,
+            record_id: None,
+            channel: Some(self.provider().channel().to_string()),
+            event_data: HashMap::new(),
+        };
+        event.set_field(
+            "UtcTime",
+            time.naive_utc().format(UTC_TIME_FORMAT).to_string(),
+        );
+        for (key, value) in fields {
+            if let Some(value) = value {
+                event.set_field(key, value);
+            }
+        }
+        Some(event)
+    }
+}
+
+pub fn derived_sysmon_events(events: &[TelemetryEvent]) -> Vec<SysmonEvent> {
+    events
+        .iter()
+        .filter(|e| e.provider() != Provider::Sysmon)
+        .filter_map(TelemetryEvent::to_sysmon)
+        .collect()
+}
+
+pub fn logons(events: &[TelemetryEvent]) -> Vec<Logon> {
+    events
+        .iter()
+        .filter_map(|e| match e {
+            TelemetryEvent::Logon(logon) => Some(logon.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn api_calls(events: &[TelemetryEvent]) -> Vec<ApiCall> {
+    events
+        .iter()
+        .filter_map(|e| match e {
+            TelemetryEvent::ApiCall(call) => Some(call.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn amsi_scans(events: &[TelemetryEvent]) -> Vec<AmsiScan> {
+    events
+        .iter()
+        .filter_map(|e| match e {
+            TelemetryEvent::Amsi(scan) => Some(scan.clone()),
+            _ => None,
+        })
+        .filter(|scan| !scan.content.trim().is_empty())
+        .collect()
+}
+
+pub fn module_logs(events: &[TelemetryEvent]) -> Vec<ModuleLog> {
+    events
+        .iter()
+        .filter_map(|e| match e {
+            TelemetryEvent::Module(log) => Some(log.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn script_blocks(events: &[TelemetryEvent]) -> Vec<ScriptBlock> {
+    let mut parts: HashMap<&str, Vec<&ScriptBlock>> = HashMap::new();
+    for event in events {
+        if let TelemetryEvent::ScriptBlock(block) = event {
+            parts.entry(&block.script_block_id).or_default().push(block);
+        }
+    }
+
+    let mut blocks: Vec<ScriptBlock> = parts
+        .into_values()
+        .map(|mut parts| {
+            parts.sort_by_key(|p| p.message_number);
+            parts.dedup_by_key(|p| p.message_number);
+            let mut block = parts[0].clone();
+            block.text = parts.iter().map(|p| p.text.as_str()).collect();
+            block.message_number = 1;
+            block.message_total = parts.len() as u32;
+            block
+        })
+        .collect();
+    blocks.sort_by_key(|b| b.time);
+    blocks
+}
+
+pub struct TelemetryReader<R> {
+    inner: SysmonEventReader<R>,
+}
+
+impl<R: BufRead> TelemetryReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: SysmonEventReader::new(reader),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for TelemetryReader<R> {
+    type Item = Result<TelemetryEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next_element() {
+                Ok(Some(xml)) => match TelemetryEvent::from_xml(&xml) {
+                    Ok(Some(event)) => return Some(Ok(event)),
+                    Ok(None) => continue,
+                    Err(e) => return Some(Err(e)),
+                },
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
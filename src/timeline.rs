@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Error, Result};
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::clock::{normalize, ClockDomain, ClockSkew};
+use crate::netsim::AttributedRequest;
+use crate::pcap::CorrelatedFlow;
+use crate::sysmon_event::SysmonEvent;
+use crate::telemetry::ScriptBlock;
+
+pub const SCREENSHOT_PREFIX: &str = "screenshot-";
+pub const SCREENSHOT_EXTENSION: &str = "png";
+pub const SCREENSHOT_TIMES_FILE: &str = "screenshots.json";
+pub const RECORDING_FILE: &str = "recording.mkv";
+
+const SCRIPT_BLOCK_SUMMARY_LEN: usize = 80;
+const PROCESS_GUID_FIELDS: &[&str] = &["ProcessGuid", "SourceProcessGuid", "SourceProcessGUID"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EntryKind {
+    Sysmon,
+    Flow,
+    Observation,
+    Screenshot,
+    ScriptBlock,
+}
+
+impl EntryKind {
+    pub const ALL: &'static [Self] = &[
+        Self::Sysmon,
+        Self::Flow,
+        Self::Observation,
+        Self::Screenshot,
+        Self::ScriptBlock,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sysmon => "sysmon",
+            Self::Flow => "flow",
+            Self::Observation => "observation",
+            Self::Screenshot => "screenshot",
+            Self::ScriptBlock => "script_block",
+        }
+    }
+
+    pub fn clock_domain(&self) -> ClockDomain {
+        match self {
+            Self::Flow | Self::Observation => ClockDomain::Host,
+            Self::Sysmon | Self::Screenshot | Self::ScriptBlock => ClockDomain::Guest,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|k| k.name().eq_ignore_ascii_case(name.trim()))
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cursor {
+    pub time: DateTime<Utc>,
+    pub kind: EntryKind,
+    pub seq: usize,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:09}:{}:{}",
+            self.time.timestamp(),
+            self.time.timestamp_subsec_nanos(),
+            self.kind.name(),
+            self.seq
+        )
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let (Some(time), Some(kind), Some(seq), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Invalid cursor: {}", s);
+        };
+        let (secs, nanos) = time.split_once('.').context("Invalid cursor time")?;
+        Ok(Self {
+            time: Utc
+                .timestamp_opt(secs.parse()?, nanos.parse()?)
+                .single()
+                .context("Invalid cursor time")?,
+            kind: EntryKind::from_name(kind).context("Invalid cursor kind")?,
+            seq: seq.parse()?,
+        })
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Screenshot {
+    pub time: DateTime<Utc>,
+    pub index: usize,
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum EntryData {
+    Sysmon(SysmonEvent),
+    Flow(CorrelatedFlow),
+    Observation(AttributedRequest),
+    Screenshot(Screenshot),
+    ScriptBlock(ScriptBlock),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TimelineEntry {
+    pub cursor: Cursor,
+    pub time: DateTime<Utc>,
+    pub original_time: DateTime<FixedOffset>,
+    pub clock: ClockDomain,
+    pub kind: EntryKind,
+    pub process_guid: Option<String>,
+    pub summary: String,
+    pub data: EntryData,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TimelineFilter {
+    pub process_guid: Option<String>,
+    pub kinds: Vec<EntryKind>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl TimelineFilter {
+    pub fn matches(&self, entry: &TimelineEntry) -> bool {
+        let guid_matches = self.process_guid.as_ref().is_none_or(|guid| {
+            entry
+                .process_guid
+                .as_ref()
+                .is_some_and(|g| g.eq_ignore_ascii_case(guid))
+        });
+        guid_matches
+            && (self.kinds.is_empty() || self.kinds.contains(&entry.kind))
+            && self.start.is_none_or(|start| entry.time >= start)
+            && self.end.is_none_or(|end| entry.time < end)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TimelinePage<'a> {
+    pub entries: Vec<&'a TimelineEntry>,
+    pub next: Option<Cursor>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    entries: Vec<TimelineEntry>,
+    counts: HashMap<EntryKind, usize>,
+    skew: Option<ClockSkew>,
+}
+
+fn process_guid(event: &SysmonEvent) -> Option<String> {
+    PROCESS_GUID_FIELDS
+        .iter()
+        .find_map(|f| event.event_data.get(*f))
+        .filter(|g| !g.is_empty())
+        .cloned()
+}
+
+fn sysmon_summary(event: &SysmonEvent) -> String {
+    let image = event
+        .event_data
+        .get("Image")
+        .or_else(|| event.event_data.get("SourceImage"));
+    let target = [
+        "TargetFilename",
+        "TargetObject",
+        "QueryName",
+        "ImageLoaded",
+        "TargetImage",
+    ]
+    .iter()
+    .find_map(|f| event.event_data.get(*f));
+    match (image, target) {
+        (Some(image), Some(target)) => format!("{}: {} {}", event.event_id.name(), image, target),
+        (Some(image), None) => format!("{}: {}", event.event_id.name(), image),
+        _ => event.event_id.name().to_string(),
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_clock_skew(mut self, skew: Option<ClockSkew>) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    pub fn clock_skew(&self) -> Option<&ClockSkew> {
+        self.skew.as_ref()
+    }
+
+    fn push(
+        &mut self,
+        kind: EntryKind,
+        original_time: DateTime<FixedOffset>,
+        process_guid: Option<String>,
+        summary: String,
+        data: EntryData,
+    ) {
+        let clock = kind.clock_domain();
+        let time = normalize(original_time, clock, self.skew.as_ref());
+        let count = self.counts.entry(kind).or_default();
+        let seq = *count;
+        *count += 1;
+        self.entries.push(TimelineEntry {
+            cursor: Cursor { time, kind, seq },
+            time,
+            original_time,
+            clock,
+            kind,
+            process_guid,
+            summary,
+            data,
+        });
+    }
+
+    fn sort(&mut self) {
+        self.entries.sort_by_key(|e| e.cursor);
+    }
+
+    pub fn add_events(&mut self, events: &[SysmonEvent]) {
+        for event in events {
+            self.push(
+                EntryKind::Sysmon,
+                event.time_created,
+                process_guid(event),
+                sysmon_summary(event),
+                EntryData::Sysmon(event.clone()),
+            );
+        }
+        self.sort();
+    }
+
+    pub fn add_flows(&mut self, flows: &[CorrelatedFlow]) {
+        for flow in flows {
+            let summary = format!(
+                "{} {} -> {}",
+                flow.flow.transport.name(),
+                flow.flow.client,
+                flow.flow.server
+            );
+            self.push(
+                EntryKind::Flow,
+                flow.flow.first_seen.into(),
+                flow.process_guid.clone(),
+                summary,
+                EntryData::Flow(flow.clone()),
+            );
+        }
+        self.sort();
+    }
+
+    pub fn add_observations(&mut self, requests: &[AttributedRequest]) {
+        for request in requests {
+            let summary = format!(
+                "{}: {}",
+                request.request.service.name(),
+                request.request.summary
+            );
+            self.push(
+                EntryKind::Observation,
+                request.request.time.into(),
+                request.process_guid.clone(),
+                summary,
+                EntryData::Observation(request.clone()),
+            );
+        }
+        self.sort();
+    }
+
+    pub fn add_screenshots(&mut self, screenshots: &[Screenshot]) {
+        for screenshot in screenshots {
+            self.push(
+                EntryKind::Screenshot,
+                screenshot.time.into(),
+                None,
+                format!("Screenshot {}", screenshot.index),
+                EntryData::Screenshot(screenshot.clone()),
+            );
+        }
+        self.sort();
+    }
+
+    pub fn add_script_blocks(&mut self, blocks: &[ScriptBlock]) {
+        for block in blocks {
+            let line = block.text.lines().map(str::trim).find(|l| !l.is_empty());
+            let summary = format!(
+                "PowerShell script block: {}",
+                line.unwrap_or_default()
+                    .chars()
+                    .take(SCRIPT_BLOCK_SUMMARY_LEN)
+                    .collect::<String>()
+            );
+            self.push(
+                EntryKind::ScriptBlock,
+                block.time,
+                None,
+                summary,
+                EntryData::ScriptBlock(block.clone()),
+            );
+        }
+        self.sort();
+    }
+
+    pub fn entries(&self) -> &[TimelineEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn filter<'a>(
+        &'a self,
+        filter: &'a TimelineFilter,
+    ) -> impl Iterator<Item = &'a TimelineEntry> + 'a {
+        self.entries.iter().filter(move |e| filter.matches(e))
+    }
+
+    pub fn page(
+        &self,
+        filter: &TimelineFilter,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> TimelinePage<'_> {
+        let start = after.map_or(0, |cursor| {
+            self.entries.partition_point(|e| e.cursor <= *cursor)
+        });
+        let mut matching = self.entries[start..].iter().filter(|e| filter.matches(e));
+        let entries: Vec<&TimelineEntry> = matching.by_ref().take(limit).collect();
+        let next = match matching.next() {
+            Some(_) => entries.last().map(|e| e.cursor),
+            None => None,
+        };
+        TimelinePage { entries, next }
+    }
+}
+
+pub fn screenshot_path<P: AsRef<Path>>(dir: P, index: usize) -> PathBuf {
+    dir.as_ref().join(format!(
+        "{}{}.{}",
+        SCREENSHOT_PREFIX, index, SCREENSHOT_EXTENSION
+    ))
+}
+
+pub fn read_screenshots<P: AsRef<Path>>(dir: P) -> Result<Vec<Screenshot>> {
+    let times: Vec<DateTime<Utc>> = match std::fs::read(dir.as_ref().join(SCREENSHOT_TIMES_FILE)) {
+        Ok(json) => serde_json::from_slice(&json)?,
+        Err(_) => Vec::new(),
+    };
+    let mut screenshots = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(index) = name
+            .strip_prefix(SCREENSHOT_PREFIX)
+            .and_then(|n| n.strip_suffix(SCREENSHOT_EXTENSION))
+            .and_then(|n| n.strip_suffix('.'))
+            .and_then(|n| n.parse().ok())
+        else {
+            continue;
+        };
+        let time = match times.get(index) {
+            Some(time) => *time,
+            None => entry.metadata()?.modified()?.into(),
+        };
+        screenshots.push(Screenshot {
+            time,
+            index,
+            path: entry.path(),
+        });
+    }
+    screenshots.sort_by_key(|s| s.index);
+    Ok(screenshots)
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::analyzer::code_signing::{SignedTarget, ValidationStatus};
+use crate::report::{Detection, SandboxReport};
+
+const LEVELS: &[&str] = &["informational", "low", "medium", "high", "critical"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    #[default]
+    Unknown,
+    Clean,
+    Suspicious,
+    Malicious,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Unknown => "unknown",
+            Self::Clean => "clean",
+            Self::Suspicious => "suspicious",
+            Self::Malicious => "malicious",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub suspicious: f64,
+    pub malicious: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            suspicious: 3.0,
+            malicious: 7.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DetectionCondition {
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub min_level: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+pub(crate) fn level_rank(level: &str) -> Option<usize> {
+    LEVELS.iter().position(|l| l.eq_ignore_ascii_case(level))
+}
+
+impl DetectionCondition {
+    fn matches(&self, detection: &Detection) -> bool {
+        self.source.as_ref().is_none_or(|s| s == &detection.source)
+            && self.min_level.as_deref().is_none_or(|min| {
+                match (
+                    detection.level.as_deref().and_then(level_rank),
+                    level_rank(min),
+                ) {
+                    (Some(level), Some(min)) => level >= min,
+                    _ => false,
+                }
+            })
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|n| detection.name.to_lowercase().contains(&n.to_lowercase()))
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|t| detection.tags.iter().any(|tag| tag.eq_ignore_ascii_case(t)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Detection(DetectionCondition),
+    Signer(String),
+    Hash(Vec<String>),
+    Family(String),
+    Technique(String),
+    ScoreAtLeast(f64),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+impl Condition {
+    fn evaluate(&self, report: &SandboxReport) -> Option<String> {
+        match self {
+            Self::Detection(condition) => report
+                .detections
+                .iter()
+                .find(|d| condition.matches(d))
+                .map(|d| {
+                    format!(
+                        "{} detection \"{}\" ({})",
+                        d.source,
+                        d.name,
+                        d.level.as_deref().unwrap_or("unknown")
+                    )
+                }),
+            Self::Signer(signer) => report
+                .code_signing
+                .iter()
+                .filter(|v| v.target == SignedTarget::Sample && v.status == ValidationStatus::Valid)
+                .find_map(|v| {
+                    v.signer
+                        .as_deref()
+                        .filter(|s| s.eq_ignore_ascii_case(signer))
+                })
+                .map(|s| format!("sample has a valid signature by {}", s)),
+            Self::Hash(hashes) => hashes
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(&report.hash))
+                .then(|| "sample hash is listed".to_string()),
+            Self::Family(family) => report
+                .configs
+                .iter()
+                .find(|c| c.family.eq_ignore_ascii_case(family))
+                .map(|c| format!("{} configuration extracted from {}", c.family, c.source)),
+            Self::Technique(technique) => report
+                .techniques
+                .iter()
+                .find(|t| t.id.eq_ignore_ascii_case(technique))
+                .map(|t| format!("technique {} observed", t.id)),
+            Self::ScoreAtLeast(score) => (report.score.score >= *score)
+                .then(|| format!("score {:.1} >= {:.1}", report.score.score, score)),
+            Self::All(conditions) => {
+                let reasons: Option<Vec<String>> =
+                    conditions.iter().map(|c| c.evaluate(report)).collect();
+                reasons.filter(|r| !r.is_empty()).map(|r| r.join(" and "))
+            }
+            Self::Any(conditions) => conditions.iter().find_map(|c| c.evaluate(report)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OverrideRule {
+    pub name: String,
+    pub when: Condition,
+    pub verdict: Verdict,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VerdictPolicy {
+    #[serde(default)]
+    pub thresholds: Thresholds,
+    #[serde(default)]
+    pub rules: Vec<OverrideRule>,
+}
+
+impl Default for VerdictPolicy {
+    fn default() -> Self {
+        Self {
+            thresholds: Thresholds::default(),
+            rules: vec![OverrideRule {
+                name: "sigma_critical".to_string(),
+                when: Condition::Detection(DetectionCondition {
+                    source: Some("sigma".to_string()),
+                    min_level: Some("critical".to_string()),
+                    ..DetectionCondition::default()
+                }),
+                verdict: Verdict::Malicious,
+            }],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rationale {
+    pub step: String,
+    pub description: String,
+    pub verdict: Verdict,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Assessment {
+    pub verdict: Verdict,
+    pub rationale: Vec<Rationale>,
+}
+
+impl VerdictPolicy {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn base(&self, report: &SandboxReport) -> Rationale {
+        let score = report.score.score;
+        let (verdict, description) = if report.events.is_empty() && report.detections.is_empty() {
+            (Verdict::Unknown, "no behavior was observed".to_string())
+        } else if score >= self.thresholds.malicious {
+            (
+                Verdict::Malicious,
+                format!("score {:.1} >= {:.1}", score, self.thresholds.malicious),
+            )
+        } else if score >= self.thresholds.suspicious {
+            (
+                Verdict::Suspicious,
+                format!("score {:.1} >= {:.1}", score, self.thresholds.suspicious),
+            )
+        } else {
+            (
+                Verdict::Clean,
+                format!("score {:.1} < {:.1}", score, self.thresholds.suspicious),
+            )
+        };
+        Rationale {
+            step: "score".to_string(),
+            description,
+            verdict,
+        }
+    }
+
+    pub fn evaluate(&self, report: &SandboxReport) -> Assessment {
+        let base = self.base(report);
+        let mut assessment = Assessment {
+            verdict: base.verdict,
+            rationale: vec![base],
+        };
+        let matched = self
+            .rules
+            .iter()
+            .find_map(|rule| rule.when.evaluate(report).map(|reason| (rule, reason)));
+        if let Some((rule, reason)) = matched {
+            assessment.verdict = rule.verdict;
+            assessment.rationale.push(Rationale {
+                step: format!("rule:{}", rule.name),
+                description: reason,
+                verdict: rule.verdict,
+            });
+        }
+        assessment
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::guid::Guid;
+use crate::registry::normalize_key;
+use crate::sysmon_event::{SysmonEvent, SysmonEventId, UTC_TIME_FORMAT};
+
+pub const VMI_CHANNEL: &str = "vmi";
+
+const SYSTEM_PID: u32 = 4;
+const OBJECT_PREFIXES: &[&str] = &[r"\??\", r"\\?\", r"\DosDevices\"];
+const CREATE_DISPOSITIONS: &[&str] = &[
+    "0",
+    "2",
+    "3",
+    "4",
+    "5",
+    "FILE_SUPERSEDE",
+    "FILE_CREATE",
+    "FILE_OPEN_IF",
+    "FILE_OVERWRITE",
+    "FILE_OVERWRITE_IF",
+];
+const SUCCESS_STATUSES: &[&str] = &["0", "0x0", "0x00000000", "STATUS_SUCCESS"];
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VmiConfig {
+    pub trace_command: Vec<String>,
+    #[serde(default)]
+    pub inject_command: Vec<String>,
+    #[serde(default = "default_boot_delay")]
+    pub boot_delay_secs: u64,
+}
+
+fn default_boot_delay() -> u64 {
+    60
+}
+
+impl VmiConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Observation {
+    pub time: DateTime<Utc>,
+    #[serde(default)]
+    pub domain: Option<String>,
+    pub pid: u32,
+    #[serde(flatten)]
+    pub kind: ObservationKind,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ObservationKind {
+    ProcessCreate {
+        ppid: u32,
+        image: String,
+        #[serde(default)]
+        command_line: Option<String>,
+        #[serde(default)]
+        user: Option<String>,
+    },
+    ProcessExit {
+        #[serde(default)]
+        exit_code: Option<i64>,
+    },
+    ModuleLoad {
+        path: String,
+        #[serde(default)]
+        base: Option<String>,
+    },
+    Syscall {
+        name: String,
+        #[serde(default)]
+        args: HashMap<String, String>,
+        #[serde(default)]
+        status: Option<String>,
+    },
+}
+
+fn object_path(name: &str) -> String {
+    OBJECT_PREFIXES
+        .iter()
+        .find_map(|prefix| {
+            name.get(..prefix.len())
+                .filter(|p| p.eq_ignore_ascii_case(prefix))
+                .map(|_| name[prefix.len()..].to_string())
+        })
+        .unwrap_or_else(|| name.to_string())
+}
+
+fn succeeded(status: Option<&str>) -> bool {
+    status.is_none_or(|s| SUCCESS_STATUSES.iter().any(|ok| s.eq_ignore_ascii_case(ok)))
+}
+
+#[derive(Debug, Clone)]
+struct Process {
+    guid: String,
+    image: String,
+    command_line: String,
+    user: Option<String>,
+}
+
+pub struct VmiReader<R> {
+    reader: R,
+    line: String,
+    pending: VecDeque<SysmonEvent>,
+    processes: HashMap<u32, Process>,
+    record_id: u64,
+}
+
+impl<R: BufRead> VmiReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+            pending: VecDeque::new(),
+            processes: HashMap::new(),
+            record_id: 0,
+        }
+    }
+
+    fn process(&mut self, pid: u32) -> Process {
+        self.processes
+            .entry(pid)
+            .or_insert_with(|| Process {
+                guid: Guid::from(Uuid::new_v5(
+                    &Uuid::NAMESPACE_OID,
+                    pid.to_string().as_bytes(),
+                ))
+                .to_string(),
+                image: String::new(),
+                command_line: String::new(),
+                user: None,
+            })
+            .clone()
+    }
+
+    fn emit(
+        &mut self,
+        event_id: SysmonEventId,
+        observation: &Observation,
+        fields: Vec<(&str, Option<String>)>,
+    ) {
+        self.record_id += 1;
+        let mut event = SysmonEvent {
+            event_id,
+            time_created: observation.time.into(),
+            computer: observation.domain.clone(),
+            record_id: Some(self.record_id),
+            channel: Some(VMI_CHANNEL.to_string()),
+            event_data: HashMap::new(),
+        };
+        event.set_field(
+            "UtcTime",
+            observation.time.format(UTC_TIME_FORMAT).to_string(),
+        );
+        for (key, value) in fields {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                event.set_field(key, value);
+            }
+        }
+        self.pending.push_back(event);
+    }
+
+    fn convert(&mut self, observation: Observation) {
+        let pid = observation.pid;
+        match &observation.kind {
+            ObservationKind::ProcessCreate {
+                ppid,
+                image,
+                command_line,
+                user,
+            } => {
+                let parent = self.process(*ppid);
+                let process = Process {
+                    guid: Guid::from(Uuid::new_v5(
+                        &Uuid::NAMESPACE_OID,
+                        format!("{}:{}", pid, observation.time.timestamp_micros()).as_bytes(),
+                    ))
+                    .to_string(),
+                    image: image.clone(),
+                    command_line: command_line.clone().unwrap_or_else(|| image.clone()),
+                    user: user.clone().or(parent.user.clone()),
+                };
+                self.processes.insert(pid, process.clone());
+                self.emit(
+                    SysmonEventId::PROCESS_CREATE,
+                    &observation,
+                    vec![
+                        ("ProcessGuid", Some(process.guid)),
+                        ("ProcessId", Some(pid.to_string())),
+                        ("Image", Some(process.image)),
+                        ("CommandLine", Some(process.command_line)),
+                        ("User", process.user),
+                        ("ParentProcessGuid", Some(parent.guid)),
+                        ("ParentProcessId", Some(ppid.to_string())),
+                        ("ParentImage", Some(parent.image)),
+                        ("ParentCommandLine", Some(parent.command_line)),
+                    ],
+                );
+            }
+            ObservationKind::ProcessExit { .. } => {
+                let process = self.process(pid);
+                self.processes.remove(&pid);
+                self.emit(
+                    SysmonEventId::PROCESS_TERMINATE,
+                    &observation,
+                    vec![
+                        ("ProcessGuid", Some(process.guid)),
+                        ("ProcessId", Some(pid.to_string())),
+                        ("Image", Some(process.image)),
+                        ("User", process.user),
+                    ],
+                );
+            }
+            ObservationKind::ModuleLoad { path, .. } => {
+                let path = object_path(path);
+                if pid == SYSTEM_PID || path.to_lowercase().ends_with(".sys") {
+                    self.emit(
+                        SysmonEventId::DRIVER_LOAD,
+                        &observation,
+                        vec![("ImageLoaded", Some(path))],
+                    );
+                    return;
+                }
+                let process = self.process(pid);
+                self.emit(
+                    SysmonEventId::IMAGE_LOAD,
+                    &observation,
+                    vec![
+                        ("ProcessGuid", Some(process.guid)),
+                        ("ProcessId", Some(pid.to_string())),
+                        ("Image", Some(process.image)),
+                        ("ImageLoaded", Some(path)),
+                        ("User", process.user),
+                    ],
+                );
+            }
+            ObservationKind::Syscall { name, args, status } => {
+                if succeeded(status.as_deref()) {
+                    self.convert_syscall(&observation, name, args);
+                }
+            }
+        }
+    }
+
+    fn convert_syscall(
+        &mut self,
+        observation: &Observation,
+        name: &str,
+        args: &HashMap<String, String>,
+    ) {
+        let pid = observation.pid;
+        let process = self.process(pid);
+        let arg = |key: &str| args.get(key).filter(|v| !v.is_empty()).cloned();
+        let object = arg("ObjectName").or_else(|| arg("FileName"));
+        let key = arg("KeyName")
+            .or_else(|| object.clone())
+            .map(|k| normalize_key(&k));
+        let subject = vec![
+            ("ProcessGuid", Some(process.guid.clone())),
+            ("ProcessId", Some(pid.to_string())),
+            ("Image", Some(process.image.clone())),
+            ("User", process.user.clone()),
+        ];
+
+        let (event_id, fields) = match name {
+            "NtCreateFile" => {
+                let created = args.get("CreateDisposition").is_some_and(|d| {
+                    CREATE_DISPOSITIONS
+                        .iter()
+                        .any(|c| d.eq_ignore_ascii_case(c))
+                });
+                match object {
+                    Some(object) if created => (
+                        SysmonEventId::FILE_CREATE,
+                        vec![("TargetFilename", Some(object_path(&object)))],
+                    ),
+                    _ => return,
+                }
+            }
+            "NtDeleteFile" => match object {
+                Some(object) => (
+                    SysmonEventId::FILE_DELETE_DETECTED,
+                    vec![("TargetFilename", Some(object_path(&object)))],
+                ),
+                None => return,
+            },
+            "NtCreateKey" | "NtDeleteKey" => match key {
+                Some(key) => (
+                    SysmonEventId::REGISTRY_EVENT_ADD_DELETE,
+                    vec![
+                        (
+                            "EventType",
+                            Some(
+                                if name == "NtCreateKey" {
+                                    "CreateKey"
+                                } else {
+                                    "DeleteKey"
+                                }
+                                .to_string(),
+                            ),
+                        ),
+                        ("TargetObject", Some(key)),
+                    ],
+                ),
+                None => return,
+            },
+            "NtSetValueKey" => match key {
+                Some(key) => {
+                    let target = match arg("ValueName") {
+                        Some(value) => format!(r"{}\{}", key, value),
+                        None => key,
+                    };
+                    (
+                        SysmonEventId::REGISTRY_EVENT_SET,
+                        vec![
+                            ("EventType", Some("SetValue".to_string())),
+                            ("TargetObject", Some(target)),
+                            ("Details", arg("Data")),
+                        ],
+                    )
+                }
+                None => return,
+            },
+            "NtOpenProcess" | "NtCreateThreadEx" => {
+                let Some(target) = arg("TargetProcessId")
+                    .and_then(|t| t.parse::<u32>().ok())
+                    .filter(|t| *t != pid)
+                else {
+                    return;
+                };
+                let target_process = self.process(target);
+                let mut fields = vec![
+                    ("SourceProcessGuid", Some(process.guid)),
+                    ("SourceProcessId", Some(pid.to_string())),
+                    ("SourceImage", Some(process.image)),
+                    ("TargetProcessGuid", Some(target_process.guid)),
+                    ("TargetProcessId", Some(target.to_string())),
+                    ("TargetImage", Some(target_process.image)),
+                ];
+                let event_id = if name == "NtOpenProcess" {
+                    fields.push(("GrantedAccess", arg("DesiredAccess")));
+                    SysmonEventId::PROCESS_ACCESS
+                } else {
+                    fields.push(("StartAddress", arg("StartRoutine")));
+                    SysmonEventId::CREATE_REMOTE_THREAD
+                };
+                self.emit(event_id, observation, fields);
+                return;
+            }
+            _ => return,
+        };
+        self.emit(
+            event_id,
+            observation,
+            subject.into_iter().chain(fields).collect(),
+        );
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        while self.pending.is_empty() {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                break;
+            }
+            let line = self.line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let observation: Observation =
+                serde_json::from_str(line).context("Invalid introspection record")?;
+            self.convert(observation);
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for VmiReader<R> {
+    type Item = Result<SysmonEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.fill() {
+            return Some(Err(e));
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}